@@ -1,5 +1,6 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::compile_protos("proto/manager.proto")?;
     tonic_build::compile_protos("proto/monitor.proto")?;
+    tonic_build::compile_protos("proto/settings.proto")?;
     Ok(())
 }
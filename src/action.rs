@@ -0,0 +1,119 @@
+use std::process::{ExitStatus, Stdio};
+
+use snafu::ResultExt;
+use tokio::{io::AsyncWriteExt, process::Command};
+
+/// Placeholder in commands which is replaced with the clip.
+pub const PLACEHOLDER: &str = "{}";
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum ActionError {
+    #[snafu(display("Could not run action {}, error: {}", name, source))]
+    RunCommand { name: String, source: std::io::Error },
+
+    #[snafu(display("Could not pass clip to action {}, error: {}", name, source))]
+    WriteStdin { name: String, source: std::io::Error },
+
+    #[snafu(display("Action {} failed, {}", name, status))]
+    CommandFailed { name: String, status: ExitStatus },
+}
+
+/// A named shell command which runs on a clip, e.g. to upload it to a pastebin.
+/// The clip replaces `{}` in the command, or is passed on stdin if the command
+/// has no placeholder.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Action {
+    pub name: String,
+
+    pub command: String,
+
+    /// The output of the command is inserted as a new clip instead of being
+    /// printed, e.g. the URL of an upload.
+    #[serde(default)]
+    pub insert_output: bool,
+}
+
+impl Action {
+    /// Returns the action named `name`.
+    pub fn find<'a>(actions: &'a [Action], name: &str) -> Option<&'a Action> {
+        actions.iter().find(|action| action.name == name)
+    }
+
+    #[inline]
+    pub fn has_placeholder(&self) -> bool { self.command.contains(PLACEHOLDER) }
+
+    /// Returns the command with each placeholder replaced by `data`, which is
+    /// quoted for the shell.
+    pub fn command_line(&self, data: &str) -> String {
+        self.command.replace(PLACEHOLDER, &shell_quote(data))
+    }
+
+    /// Runs the command with `sh` on `data` and returns its output.
+    pub async fn run(&self, data: &str) -> Result<String, ActionError> {
+        let stdin = if self.has_placeholder() { Stdio::null() } else { Stdio::piped() };
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(self.command_line(data))
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .spawn()
+            .context(RunCommand { name: &self.name })?;
+
+        // the clip is written while the output is read, large clips would fill
+        // both pipes otherwise
+        let writer = child.stdin.take().map(|mut stdin| {
+            let data = data.to_owned();
+            tokio::spawn(async move { stdin.write_all(data.as_bytes()).await })
+        });
+        let output = child.wait_with_output().await.context(RunCommand { name: &self.name })?;
+        if let Some(writer) = writer {
+            match writer.await {
+                // commands do not have to read the clip
+                Ok(Err(err)) if err.kind() != std::io::ErrorKind::BrokenPipe => {
+                    return Err(err).context(WriteStdin { name: &self.name });
+                }
+                _ => {}
+            }
+        }
+
+        if !output.status.success() {
+            return Err(ActionError::CommandFailed {
+                name: self.name.clone(),
+                status: output.status,
+            });
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+fn shell_quote(data: &str) -> String { format!("'{}'", data.replace('\'', "'\\''")) }
+
+#[cfg(test)]
+mod tests {
+    use crate::action::Action;
+
+    fn action(command: &str) -> Action {
+        Action { name: "test".to_owned(), command: command.to_owned(), insert_output: false }
+    }
+
+    #[test]
+    fn test_command_line() {
+        assert_eq!(action("echo {}").command_line("it's $HOME"), "echo 'it'\\''s $HOME'");
+        assert_eq!(action("wc -c").command_line("clipcat"), "wc -c");
+
+        let actions = vec![action("wc -c")];
+        assert!(Action::find(&actions, "test").is_some());
+        assert!(Action::find(&actions, "upload").is_none());
+    }
+
+    #[test]
+    fn test_run() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            assert_eq!(action("tr a-z A-Z").run("clipcat").await.unwrap(), "CLIPCAT");
+            assert_eq!(action("printf %s {}").run("it's $HOME").await.unwrap(), "it's $HOME");
+            assert!(action("exit 3").run("clipcat").await.is_err());
+        });
+    }
+}
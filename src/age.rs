@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+// Units of ages, the largest one first.
+const UNITS: &[(char, u64)] =
+    &[('w', 7 * 24 * 60 * 60), ('d', 24 * 60 * 60), ('h', 60 * 60), ('m', 60), ('s', 1)];
+
+/// Parses an age in seconds, minutes, hours, days or weeks, e.g. `90m` or
+/// `30d`, plain numbers are seconds.
+pub fn parse(src: &str) -> Result<Duration, String> {
+    let src = src.trim();
+    let (number, unit) = match src.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => src.split_at(index),
+        None => (src, "s"),
+    };
+    let number: u64 = number.parse().map_err(|_| format!("Invalid age {}", src))?;
+    let factor = match UNITS.iter().find(|(name, _)| unit.len() == 1 && unit.starts_with(*name)) {
+        Some((_, factor)) => factor,
+        None => return Err(format!("Invalid unit of age {}, expected s, m, h, d or w", src)),
+    };
+    number
+        .checked_mul(*factor)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Age {} is too large", src))
+}
+
+/// Formats `age` with the largest unit which fits it exactly, e.g. `14d`.
+pub fn format(age: Duration) -> String {
+    let secs = age.as_secs();
+    match UNITS.iter().find(|(_, factor)| secs != 0 && secs.is_multiple_of(*factor)) {
+        Some((name, factor)) => format!("{}{}", secs / factor, name),
+        None => format!("{}s", secs),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::age;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(age::parse("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(age::parse("90m"), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(age::parse("14d"), Ok(Duration::from_secs(14 * 24 * 60 * 60)));
+        assert!(age::parse("d").is_err());
+        assert!(age::parse("3y").is_err());
+        assert!(age::parse("3dd").is_err());
+
+        assert_eq!(age::format(Duration::from_secs(14 * 24 * 60 * 60)), "2w");
+        assert_eq!(age::format(Duration::from_secs(90 * 60)), "90m");
+        assert_eq!(age::format(Duration::from_secs(0)), "0s");
+    }
+}
@@ -0,0 +1,262 @@
+use std::{ffi::CString, os::raw::c_void, thread, time::Duration};
+
+use objc::{
+    class, msg_send,
+    runtime::{Object, BOOL, NO},
+    sel, sel_impl,
+};
+use snafu::ResultExt;
+
+use crate::{error, mime, ClipboardError, ClipboardEvent, ClipboardType};
+
+// NSPasteboard posts no notification when it changes, its change count is
+// polled instead.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+const TYPE_TEXT: &str = "public.utf8-plain-text";
+const TYPE_FILE_URL: &str = "public.file-url";
+
+// Pasteboard types of MIME types, other MIME types are their own pasteboard
+// type.
+const PASTEBOARD_TYPES: &[(&str, &str)] = &[
+    (mime::TEXT_HTML, "public.html"),
+    (mime::TEXT_RTF, "public.rtf"),
+    (mime::IMAGE_PNG, "public.png"),
+    (mime::IMAGE_JPEG, "public.jpeg"),
+];
+
+// Markers of nspasteboard.org, password managers offer the concealed type and
+// transient contents are not meant to be stored by clipboard managers.
+const TYPE_CONCEALED: &str = "org.nspasteboard.ConcealedType";
+const TYPE_TRANSIENT: &str = "org.nspasteboard.TransientType";
+
+type Id = *mut Object;
+
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {}
+
+// Releases the autoreleased objects of one access to the pasteboard.
+struct AutoreleasePool(Id);
+
+impl AutoreleasePool {
+    fn new() -> AutoreleasePool {
+        AutoreleasePool(unsafe { msg_send![class!(NSAutoreleasePool), new] })
+    }
+}
+
+impl Drop for AutoreleasePool {
+    fn drop(&mut self) { let _: () = unsafe { msg_send![self.0, drain] }; }
+}
+
+/// Watches the general pasteboard, macOS has no primary selection.
+pub struct Watcher {
+    clipboard_type: ClipboardType,
+    change_count: isize,
+}
+
+impl Watcher {
+    pub fn new(clipboard_type: ClipboardType) -> Watcher {
+        Watcher { clipboard_type, change_count: change_count() }
+    }
+
+    pub fn load(&mut self) -> Result<ClipboardEvent, ClipboardError> {
+        let _pool = AutoreleasePool::new();
+        self.change_count = change_count();
+        Ok(self.build_event())
+    }
+
+    pub fn wait(&mut self) -> Result<ClipboardEvent, ClipboardError> {
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            if change_count() != self.change_count {
+                return self.load();
+            }
+        }
+    }
+
+    fn build_event(&self) -> ClipboardEvent {
+        let pasteboard = general_pasteboard();
+        // one-shot clips are offered by clipcat itself
+        if has_type(pasteboard, mime::CLIPCAT_ONE_SHOT) {
+            return ClipboardEvent::from_bytes(mime::TEXT_PLAIN, Vec::new(), self.clipboard_type);
+        }
+
+        let uris = file_urls(pasteboard);
+        let text = data_for_type(pasteboard, TYPE_TEXT).unwrap_or_default();
+        let (mut event, text) = if !uris.is_empty() {
+            let data = mime::format_uri_list(&uris);
+            (ClipboardEvent::from_bytes(mime::TEXT_URI_LIST, data, self.clipboard_type), text)
+        } else if !text.is_empty() {
+            (ClipboardEvent::from_bytes(mime::TEXT_PLAIN, text, self.clipboard_type), Vec::new())
+        } else {
+            let image = mime::IMAGE_TYPES.iter().find_map(|mime| {
+                data_for_type(pasteboard, pasteboard_type(mime)).map(|data| (*mime, data))
+            });
+            match image {
+                Some((mime, data)) => {
+                    (ClipboardEvent::from_bytes(mime, data, self.clipboard_type), Vec::new())
+                }
+                None => {
+                    return ClipboardEvent::from_bytes(
+                        mime::TEXT_PLAIN,
+                        Vec::new(),
+                        self.clipboard_type,
+                    )
+                }
+            }
+        };
+
+        if !text.is_empty() {
+            event.targets.insert(mime::TEXT_PLAIN.to_owned(), text);
+        }
+        for mime in mime::ALTERNATIVE_TYPES {
+            if *mime != event.mime && *mime != mime::TEXT_URI_LIST {
+                if let Some(data) = data_for_type(pasteboard, pasteboard_type(mime)) {
+                    event.targets.insert(mime.to_string(), data);
+                }
+            }
+        }
+        event.sensitive = has_type(pasteboard, TYPE_CONCEALED);
+        event
+    }
+}
+
+/// Replaces the content of the general pasteboard with `targets`.
+pub async fn store(
+    targets: Vec<(String, Vec<u8>)>,
+    _clipboard_type: ClipboardType,
+) -> Result<(), ClipboardError> {
+    tokio::task::spawn_blocking(move || write_pasteboard(&targets))
+        .await
+        .context(error::SpawnBlockingTask)?
+}
+
+/// Like `store`, but the content is marked as transient. macOS does not tell
+/// the owner when the content is pasted, so it stays until the pasteboard
+/// changes.
+pub async fn store_once(
+    mut targets: Vec<(String, Vec<u8>)>,
+    clipboard_type: ClipboardType,
+) -> Result<(), ClipboardError> {
+    targets.push((TYPE_CONCEALED.to_owned(), Vec::new()));
+    targets.push((TYPE_TRANSIENT.to_owned(), Vec::new()));
+    store(targets, clipboard_type).await
+}
+
+fn write_pasteboard(targets: &[(String, Vec<u8>)]) -> Result<(), ClipboardError> {
+    let _pool = AutoreleasePool::new();
+    let pasteboard = general_pasteboard();
+    let _: isize = unsafe { msg_send![pasteboard, clearContents] };
+
+    // each file is an item of its own, the other types are written to the
+    // first item
+    if let Some((_mime, data)) = targets.iter().find(|(mime, _)| mime::is_file_list(mime)) {
+        let urls: Vec<Id> = mime::parse_uri_list(data)
+            .iter()
+            .filter_map(|uri| {
+                let url: Id = unsafe { msg_send![class!(NSURL), URLWithString: ns_string(uri)] };
+                if url.is_null() {
+                    None
+                } else {
+                    Some(url)
+                }
+            })
+            .collect();
+        let array: Id = unsafe {
+            msg_send![class!(NSArray), arrayWithObjects: urls.as_ptr() count: urls.len()]
+        };
+        let written: BOOL = unsafe { msg_send![pasteboard, writeObjects: array] };
+        if written == NO {
+            return Err(ClipboardError::WriteMacOSPasteboard {
+                mime: mime::TEXT_URI_LIST.to_owned(),
+            });
+        }
+    }
+
+    for (mime, data) in targets {
+        if mime::is_file_list(mime) {
+            continue;
+        }
+        let uti = if mime::is_text(mime) { TYPE_TEXT } else { pasteboard_type(mime) };
+        let data: Id = unsafe {
+            msg_send![class!(NSData), dataWithBytes: data.as_ptr() as *const c_void length: data.len()]
+        };
+        let written: BOOL = unsafe { msg_send![pasteboard, setData: data forType: ns_string(uti)] };
+        if written == NO {
+            return Err(ClipboardError::WriteMacOSPasteboard { mime: mime.clone() });
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+fn pasteboard_type(mime: &str) -> &str {
+    PASTEBOARD_TYPES.iter().find(|&&(name, _)| name == mime).map_or(mime, |&(_, uti)| uti)
+}
+
+#[inline]
+fn general_pasteboard() -> Id { unsafe { msg_send![class!(NSPasteboard), generalPasteboard] } }
+
+fn change_count() -> isize {
+    let _pool = AutoreleasePool::new();
+    unsafe { msg_send![general_pasteboard(), changeCount] }
+}
+
+// Returns an autoreleased NSString, strings with null bytes become empty.
+fn ns_string(s: &str) -> Id {
+    let s = CString::new(s).unwrap_or_default();
+    unsafe { msg_send![class!(NSString), stringWithUTF8String: s.as_ptr()] }
+}
+
+fn has_type(pasteboard: Id, pasteboard_type: &str) -> bool {
+    let types: Id = unsafe { msg_send![pasteboard, types] };
+    if types.is_null() {
+        return false;
+    }
+    let contains: BOOL = unsafe { msg_send![types, containsObject: ns_string(pasteboard_type)] };
+    contains != NO
+}
+
+// `pasteboard` may be the pasteboard or one of its items, both answer
+// `dataForType:`.
+fn data_for_type(pasteboard: Id, pasteboard_type: &str) -> Option<Vec<u8>> {
+    let data: Id = unsafe { msg_send![pasteboard, dataForType: ns_string(pasteboard_type)] };
+    if data.is_null() {
+        return None;
+    }
+    let length: usize = unsafe { msg_send![data, length] };
+    let bytes: *const u8 = unsafe { msg_send![data, bytes] };
+    if length == 0 || bytes.is_null() {
+        return None;
+    }
+    Some(unsafe { std::slice::from_raw_parts(bytes, length) }.to_vec())
+}
+
+// Finder puts each copied file into an item of its own.
+fn file_urls(pasteboard: Id) -> Vec<String> {
+    let items: Id = unsafe { msg_send![pasteboard, pasteboardItems] };
+    if items.is_null() {
+        return Vec::new();
+    }
+    let count: usize = unsafe { msg_send![items, count] };
+    (0..count)
+        .filter_map(|index| {
+            let item: Id = unsafe { msg_send![items, objectAtIndex: index] };
+            data_for_type(item, TYPE_FILE_URL)
+        })
+        .map(|data| String::from_utf8_lossy(&data).trim().to_owned())
+        .filter(|uri| !uri.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend::macos::pasteboard_type, mime};
+
+    #[test]
+    fn test_pasteboard_type() {
+        assert_eq!(pasteboard_type(mime::IMAGE_PNG), "public.png");
+        assert_eq!(pasteboard_type(mime::TEXT_HTML), "public.html");
+        assert_eq!(pasteboard_type(mime::CLIPCAT_ONE_SHOT), mime::CLIPCAT_ONE_SHOT);
+    }
+}
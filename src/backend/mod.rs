@@ -0,0 +1,312 @@
+use std::{fmt, str::FromStr};
+
+use crate::{mime, ClipboardData, ClipboardError, ClipboardEvent, ClipboardType};
+
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "linux")]
+mod wayland;
+#[cfg(windows)]
+mod windows;
+#[cfg(target_os = "linux")]
+mod x11;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Default)]
+pub enum ClipboardBackendType {
+    #[serde(rename = "auto")]
+    #[default]
+    Auto,
+
+    #[serde(rename = "x11")]
+    X11,
+
+    #[serde(rename = "wayland")]
+    Wayland,
+
+    /// The general pasteboard of macOS.
+    #[serde(rename = "macos")]
+    MacOS,
+
+    /// The Win32 clipboard.
+    #[serde(rename = "windows")]
+    Windows,
+}
+
+impl ClipboardBackendType {
+    /// Resolves `Auto` into a concrete backend by inspecting the session
+    /// environment.
+    pub fn resolve(self) -> ClipboardBackendType {
+        match self {
+            ClipboardBackendType::Auto => {
+                if cfg!(target_os = "macos") {
+                    ClipboardBackendType::MacOS
+                } else if cfg!(windows) {
+                    ClipboardBackendType::Windows
+                } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                    ClipboardBackendType::Wayland
+                } else {
+                    ClipboardBackendType::X11
+                }
+            }
+            backend => backend,
+        }
+    }
+
+    /// Returns `false` for macOS and Windows, which have no primary selection.
+    #[inline]
+    pub fn has_primary(self) -> bool {
+        !matches!(self.resolve(), ClipboardBackendType::MacOS | ClipboardBackendType::Windows)
+    }
+
+    /// Returns `true` for X11 and Wayland, whose displays or seats can be
+    /// watched besides the default one.
+    #[inline]
+    pub fn has_displays(self) -> bool {
+        matches!(self.resolve(), ClipboardBackendType::X11 | ClipboardBackendType::Wayland)
+    }
+}
+
+impl FromStr for ClipboardBackendType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "auto" => Ok(ClipboardBackendType::Auto),
+            "x11" => Ok(ClipboardBackendType::X11),
+            "wayland" => Ok(ClipboardBackendType::Wayland),
+            "macos" => Ok(ClipboardBackendType::MacOS),
+            "windows" => Ok(ClipboardBackendType::Windows),
+            _ => Err(format!("Invalid clipboard backend: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ClipboardBackendType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ClipboardBackendType::Auto => "auto",
+            ClipboardBackendType::X11 => "x11",
+            ClipboardBackendType::Wayland => "wayland",
+            ClipboardBackendType::MacOS => "macos",
+            ClipboardBackendType::Windows => "windows",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Line endings of captured text clips, Windows applications copy text with
+/// CRLF.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEndings {
+    /// Text is stored as it is copied.
+    #[default]
+    Keep,
+
+    Lf,
+
+    Crlf,
+}
+
+impl LineEndings {
+    /// Converts the line endings of `text`, lone CRs are line endings as well.
+    pub fn convert(self, text: &str) -> String {
+        let lf = || text.replace("\r\n", "\n").replace('\r', "\n");
+        match self {
+            LineEndings::Keep => text.to_owned(),
+            LineEndings::Lf => lf(),
+            LineEndings::Crlf => lf().replace('\n', "\r\n"),
+        }
+    }
+
+    /// Converts the text and the text targets of `event`.
+    pub fn apply(self, event: &mut ClipboardEvent) {
+        if self == LineEndings::Keep {
+            return;
+        }
+        event.data = self.convert(&event.data);
+        for (mime, data) in event.targets.iter_mut() {
+            if mime::is_text(mime) {
+                *data = self.convert(&String::from_utf8_lossy(data)).into_bytes();
+            }
+        }
+    }
+}
+
+pub enum ClipboardWatcher {
+    #[cfg(target_os = "linux")]
+    X11(x11::Watcher),
+    #[cfg(target_os = "linux")]
+    Wayland(wayland::Watcher),
+    #[cfg(target_os = "macos")]
+    MacOS(macos::Watcher),
+    #[cfg(windows)]
+    Windows(windows::Watcher),
+}
+
+impl ClipboardWatcher {
+    /// Watches `clipboard_type` of `display`, an X11 display or a Wayland
+    /// seat, or of the default display if it is `None`.
+    pub fn new(
+        backend: ClipboardBackendType,
+        clipboard_type: ClipboardType,
+        display: Option<&str>,
+    ) -> Result<ClipboardWatcher, ClipboardError> {
+        match backend.resolve() {
+            #[cfg(target_os = "linux")]
+            ClipboardBackendType::Wayland => {
+                Ok(ClipboardWatcher::Wayland(wayland::Watcher::new(clipboard_type, display)?))
+            }
+            #[cfg(target_os = "linux")]
+            ClipboardBackendType::X11 => {
+                Ok(ClipboardWatcher::X11(x11::Watcher::new(clipboard_type, display)?))
+            }
+            backend if display.is_some() => {
+                Err(ClipboardError::DisplaysUnsupported { backend: backend.to_string() })
+            }
+            #[cfg(target_os = "macos")]
+            ClipboardBackendType::MacOS => {
+                Ok(ClipboardWatcher::MacOS(macos::Watcher::new(clipboard_type)))
+            }
+            #[cfg(windows)]
+            ClipboardBackendType::Windows => {
+                Ok(ClipboardWatcher::Windows(windows::Watcher::new(clipboard_type)))
+            }
+            backend => Err(ClipboardError::UnsupportedBackend { backend: backend.to_string() }),
+        }
+    }
+
+    /// Loads the current content of the selection without waiting.
+    pub fn load(&mut self) -> Result<ClipboardEvent, ClipboardError> {
+        match self {
+            #[cfg(target_os = "linux")]
+            ClipboardWatcher::X11(watcher) => watcher.load(),
+            #[cfg(target_os = "linux")]
+            ClipboardWatcher::Wayland(watcher) => watcher.load(),
+            #[cfg(target_os = "macos")]
+            ClipboardWatcher::MacOS(watcher) => watcher.load(),
+            #[cfg(windows)]
+            ClipboardWatcher::Windows(watcher) => watcher.load(),
+        }
+    }
+
+    /// Blocks until the selection changes and returns its new content.
+    pub fn wait(&mut self) -> Result<ClipboardEvent, ClipboardError> {
+        match self {
+            #[cfg(target_os = "linux")]
+            ClipboardWatcher::X11(watcher) => watcher.wait(),
+            #[cfg(target_os = "linux")]
+            ClipboardWatcher::Wayland(watcher) => watcher.wait(),
+            #[cfg(target_os = "macos")]
+            ClipboardWatcher::MacOS(watcher) => watcher.wait(),
+            #[cfg(windows)]
+            ClipboardWatcher::Windows(watcher) => watcher.wait(),
+        }
+    }
+}
+
+/// Saves the clipboard when the application which owns it exits. Only X11 has
+/// a protocol for this, on Wayland, macOS and Windows the clipboard is taken
+/// over as clips are captured.
+pub fn manage_clipboard(backend: ClipboardBackendType) -> Result<bool, ClipboardError> {
+    match backend.resolve() {
+        #[cfg(target_os = "linux")]
+        ClipboardBackendType::X11 => x11::manage_clipboard().map(|()| true),
+        _ => Ok(false),
+    }
+}
+
+/// Takes ownership of the selection of `display`, or of the default display if
+/// it is `None`, and offers every representation of `clip`.
+pub async fn store(
+    backend: ClipboardBackendType,
+    display: Option<&str>,
+    clip: &ClipboardData,
+    clipboard_type: ClipboardType,
+) -> Result<(), ClipboardError> {
+    let targets = clip.offered_targets();
+    let display = display.map(ToOwned::to_owned);
+    match backend.resolve() {
+        #[cfg(target_os = "linux")]
+        ClipboardBackendType::Wayland => wayland::store(targets, clipboard_type, display).await,
+        #[cfg(target_os = "linux")]
+        ClipboardBackendType::X11 => x11::store(targets, clipboard_type, display).await,
+        backend if display.is_some() => {
+            Err(ClipboardError::DisplaysUnsupported { backend: backend.to_string() })
+        }
+        #[cfg(target_os = "macos")]
+        ClipboardBackendType::MacOS => macos::store(targets, clipboard_type).await,
+        #[cfg(windows)]
+        ClipboardBackendType::Windows => windows::store(targets, clipboard_type).await,
+        backend => Err(ClipboardError::UnsupportedBackend { backend: backend.to_string() }),
+    }
+}
+
+/// Like `store`, but gives up the selection after its content is pasted once.
+/// The clip is marked as a secret so that clipboard managers do not store it.
+pub async fn store_once(
+    backend: ClipboardBackendType,
+    clip: &ClipboardData,
+    clipboard_type: ClipboardType,
+) -> Result<(), ClipboardError> {
+    let mut targets = clip.offered_targets();
+    targets.push((mime::KDE_PASSWORD_MANAGER_HINT.to_owned(), b"secret".to_vec()));
+    targets.push((mime::CLIPCAT_ONE_SHOT.to_owned(), Vec::new()));
+    match backend.resolve() {
+        #[cfg(target_os = "linux")]
+        ClipboardBackendType::Wayland => wayland::store_once(targets, clipboard_type).await,
+        #[cfg(target_os = "linux")]
+        ClipboardBackendType::X11 => x11::store_once(targets, clipboard_type).await,
+        #[cfg(target_os = "macos")]
+        ClipboardBackendType::MacOS => macos::store_once(targets, clipboard_type).await,
+        #[cfg(windows)]
+        ClipboardBackendType::Windows => windows::store_once(targets, clipboard_type).await,
+        backend => Err(ClipboardError::UnsupportedBackend { backend: backend.to_string() }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::{ClipboardBackendType, LineEndings},
+        mime, ClipboardEvent, ClipboardType,
+    };
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("auto".parse(), Ok(ClipboardBackendType::Auto));
+        assert_eq!("X11".parse(), Ok(ClipboardBackendType::X11));
+        assert_eq!("wayland".parse(), Ok(ClipboardBackendType::Wayland));
+        assert_eq!("macOS".parse(), Ok(ClipboardBackendType::MacOS));
+        assert_eq!("windows".parse(), Ok(ClipboardBackendType::Windows));
+        assert!("quartz".parse::<ClipboardBackendType>().is_err());
+    }
+
+    #[test]
+    fn test_resolve_concrete() {
+        assert_eq!(ClipboardBackendType::X11.resolve(), ClipboardBackendType::X11);
+        assert_eq!(ClipboardBackendType::Wayland.resolve(), ClipboardBackendType::Wayland);
+        assert_eq!(ClipboardBackendType::MacOS.resolve(), ClipboardBackendType::MacOS);
+        assert!(!ClipboardBackendType::MacOS.has_primary());
+        assert!(!ClipboardBackendType::Windows.has_primary());
+        assert!(ClipboardBackendType::X11.has_primary());
+        assert_ne!(ClipboardBackendType::Auto.resolve(), ClipboardBackendType::Auto);
+    }
+
+    #[test]
+    fn test_line_endings() {
+        assert_eq!(LineEndings::Keep.convert("a\r\nb\n"), "a\r\nb\n");
+        assert_eq!(LineEndings::Lf.convert("a\r\nb\rc\n"), "a\nb\nc\n");
+        assert_eq!(LineEndings::Crlf.convert("a\r\nb\nc"), "a\r\nb\r\nc");
+
+        let mut event = ClipboardEvent::from_bytes(
+            mime::TEXT_HTML,
+            b"<p>\r\n</p>".to_vec(),
+            ClipboardType::Clipboard,
+        );
+        event.targets.insert(mime::TEXT_PLAIN.to_owned(), b"a\r\nb".to_vec());
+        LineEndings::Lf.apply(&mut event);
+        assert_eq!(event.targets[mime::TEXT_PLAIN], b"a\nb".to_vec());
+        assert_eq!(event.binary, b"<p>\r\n</p>".to_vec());
+    }
+}
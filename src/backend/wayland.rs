@@ -0,0 +1,478 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashSet},
+    io::Read,
+    process::Command,
+    rc::Rc,
+    sync::mpsc,
+    thread,
+};
+
+use snafu::ResultExt;
+use wayland_client::{
+    protocol::wl_seat::{self, WlSeat},
+    Display, EventQueue, GlobalManager, Main,
+};
+use wayland_protocols::wlr::unstable::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+};
+use wl_clipboard_rs::{copy, paste};
+
+use crate::{error, mime, ClipboardError, ClipboardEvent, ClipboardType};
+
+pub struct Watcher {
+    clipboard_type: ClipboardType,
+    seat: Option<String>,
+    last: Option<ClipboardEvent>,
+    changes: mpsc::Receiver<Result<(), ClipboardError>>,
+}
+
+impl Watcher {
+    /// Watches `clipboard_type` of `seat`, or of any seat if it is `None`.
+    pub fn new(
+        clipboard_type: ClipboardType,
+        seat: Option<&str>,
+    ) -> Result<Watcher, ClipboardError> {
+        let changes = watch_selection(clipboard_type, seat)?;
+        let mut watcher =
+            Watcher { clipboard_type, seat: seat.map(ToOwned::to_owned), last: None, changes };
+        // Remember the current content so that only subsequent changes are reported.
+        let _ = watcher.load();
+        Ok(watcher)
+    }
+
+    pub fn load(&mut self) -> Result<ClipboardEvent, ClipboardError> {
+        // loading a one-shot selection would count as its only paste
+        if self.offered_mime_types()?.contains(mime::CLIPCAT_ONE_SHOT) {
+            let event =
+                ClipboardEvent::from_bytes(mime::TEXT_PLAIN, Vec::new(), self.clipboard_type);
+            self.last = Some(event.clone());
+            return Ok(event);
+        }
+
+        let mut event = self.build_event()?;
+        event.sensitive = self.has_sensitive_hint()?;
+
+        self.last = Some(event.clone());
+        Ok(event)
+    }
+
+    fn build_event(&self) -> Result<ClipboardEvent, ClipboardError> {
+        let text = match self.load_mime(paste::MimeType::Text)? {
+            Some((_mime, data)) => data,
+            None => Vec::new(),
+        };
+        let (mut event, text) = match self.load_file_list()? {
+            Some(event) => (event, text),
+            None if !text.is_empty() => (
+                ClipboardEvent::from_bytes(mime::TEXT_PLAIN, text, self.clipboard_type),
+                Vec::new(),
+            ),
+            None => match self.load_image()? {
+                Some((mime, data)) => {
+                    (ClipboardEvent::from_bytes(&mime, data, self.clipboard_type), Vec::new())
+                }
+                None => {
+                    return Ok(ClipboardEvent::from_bytes(
+                        mime::TEXT_PLAIN,
+                        Vec::new(),
+                        self.clipboard_type,
+                    ))
+                }
+            },
+        };
+
+        // the other representations are stored as well, so pastes of the clip
+        // are faithful
+        if !text.is_empty() {
+            event.targets.insert(mime::TEXT_PLAIN.to_owned(), text);
+        }
+        event.targets.extend(self.load_alternatives(&event.mime)?);
+        Ok(event)
+    }
+
+    fn has_sensitive_hint(&self) -> Result<bool, ClipboardError> {
+        if !self.offered_mime_types()?.contains(mime::KDE_PASSWORD_MANAGER_HINT) {
+            return Ok(false);
+        }
+
+        let hint = self.load_mime(paste::MimeType::Specific(mime::KDE_PASSWORD_MANAGER_HINT))?;
+        Ok(hint.is_some_and(|(hint_mime, value)| mime::is_sensitive_hint(&hint_mime, &value)))
+    }
+
+    pub fn wait(&mut self) -> Result<ClipboardEvent, ClipboardError> {
+        let last = self.last.take();
+        loop {
+            match self.changes.recv() {
+                Ok(result) => result?,
+                Err(_) => return Err(ClipboardError::WaylandSelectionClosed),
+            }
+            let mut curr = self.load()?;
+            if Some(&curr) != last.as_ref() {
+                curr.application = focused_application();
+                return Ok(curr);
+            }
+        }
+    }
+
+    #[inline]
+    fn paste_clipboard_type(&self) -> paste::ClipboardType {
+        match self.clipboard_type {
+            ClipboardType::Clipboard => paste::ClipboardType::Regular,
+            ClipboardType::Primary => paste::ClipboardType::Primary,
+        }
+    }
+
+    fn paste_seat(&self) -> paste::Seat<'_> {
+        match self.seat {
+            Some(ref seat) => paste::Seat::Specific(seat),
+            None => paste::Seat::Unspecified,
+        }
+    }
+
+    fn offered_mime_types(&self) -> Result<HashSet<String>, ClipboardError> {
+        match paste::get_mime_types(self.paste_clipboard_type(), self.paste_seat()) {
+            Ok(offered) => Ok(offered),
+            Err(paste::Error::NoSeats)
+            | Err(paste::Error::ClipboardEmpty)
+            | Err(paste::Error::NoMimeType) => Ok(HashSet::new()),
+            Err(err) => Err(ClipboardError::LoadWaylandClipboard { error: err.to_string() }),
+        }
+    }
+
+    fn load_alternatives(
+        &self,
+        main_mime: &str,
+    ) -> Result<BTreeMap<String, Vec<u8>>, ClipboardError> {
+        let offered = self.offered_mime_types()?;
+        let mut targets = BTreeMap::new();
+        for mime in mime::ALTERNATIVE_TYPES {
+            if *mime != main_mime && offered.contains(*mime) {
+                if let Some((_mime, data)) = self.load_mime(paste::MimeType::Specific(mime))? {
+                    if !data.is_empty() {
+                        targets.insert(mime.to_string(), data);
+                    }
+                }
+            }
+        }
+        Ok(targets)
+    }
+
+    fn load_file_list(&self) -> Result<Option<ClipboardEvent>, ClipboardError> {
+        let offered = self.offered_mime_types()?;
+        let uris = if offered.contains(mime::TEXT_URI_LIST) {
+            self.load_mime(paste::MimeType::Specific(mime::TEXT_URI_LIST))?
+                .map(|(_mime, data)| mime::parse_uri_list(&data))
+        } else if offered.contains(mime::GNOME_COPIED_FILES) {
+            self.load_mime(paste::MimeType::Specific(mime::GNOME_COPIED_FILES))?
+                .map(|(_mime, data)| mime::parse_gnome_copied_files(&data))
+        } else {
+            None
+        };
+
+        match uris {
+            Some(uris) if !uris.is_empty() => Ok(Some(ClipboardEvent::from_bytes(
+                mime::TEXT_URI_LIST,
+                mime::format_uri_list(&uris),
+                self.clipboard_type,
+            ))),
+            _ => Ok(None),
+        }
+    }
+
+    fn load_image(&self) -> Result<Option<(String, Vec<u8>)>, ClipboardError> {
+        let offered = self.offered_mime_types()?;
+        for mime in mime::IMAGE_TYPES {
+            if offered.contains(*mime) {
+                return self.load_mime(paste::MimeType::Specific(mime));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn load_mime(
+        &self,
+        mime_type: paste::MimeType,
+    ) -> Result<Option<(String, Vec<u8>)>, ClipboardError> {
+        match paste::get_contents(self.paste_clipboard_type(), self.paste_seat(), mime_type) {
+            Ok((mut pipe, mime)) => {
+                let mut data = Vec::new();
+                pipe.read_to_end(&mut data).context(error::ReadWaylandClipboard)?;
+                Ok(Some((mime, data)))
+            }
+            Err(paste::Error::NoSeats)
+            | Err(paste::Error::ClipboardEmpty)
+            | Err(paste::Error::NoMimeType) => Ok(None),
+            Err(err) => Err(ClipboardError::LoadWaylandClipboard { error: err.to_string() }),
+        }
+    }
+}
+
+// The connection can not be sent to other threads, it is owned by a thread
+// which reports changes of the selection until the watcher is dropped.
+fn watch_selection(
+    clipboard_type: ClipboardType,
+    seat: Option<&str>,
+) -> Result<mpsc::Receiver<Result<(), ClipboardError>>, ClipboardError> {
+    let seat = seat.map(ToOwned::to_owned);
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let (changes_tx, changes_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut events = match SelectionEvents::connect(clipboard_type, seat.as_deref()) {
+            Ok(events) => {
+                let _ = ready_tx.send(Ok(()));
+                events
+            }
+            Err(err) => {
+                let _ = ready_tx.send(Err(err));
+                return;
+            }
+        };
+        loop {
+            let result = events.wait();
+            let failed = result.is_err();
+            if changes_tx.send(result).is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    ready_rx.recv().map_err(|_| ClipboardError::WaylandSelectionClosed)??;
+    Ok(changes_rx)
+}
+
+// wlr-data-control announces every change of the selection to all clients, not
+// only to the one which owns it. The offers are not read, the selection is
+// loaded through wl-clipboard-rs once it changes.
+struct SelectionEvents {
+    event_queue: EventQueue,
+    changed: Rc<Cell<bool>>,
+    finished: Rc<Cell<bool>>,
+
+    // events are received as long as the display and the devices are alive
+    _display: Display,
+    _devices: Vec<Main<ZwlrDataControlDeviceV1>>,
+}
+
+impl SelectionEvents {
+    fn connect(
+        clipboard_type: ClipboardType,
+        seat: Option<&str>,
+    ) -> Result<SelectionEvents, ClipboardError> {
+        let display = Display::connect_to_env().context(error::ConnectWayland)?;
+        let mut event_queue = display.create_event_queue();
+        let attached = (*display).clone().attach(event_queue.token());
+
+        let seats = Rc::new(RefCell::new(Vec::new()));
+        let globals = {
+            let seats = seats.clone();
+            GlobalManager::new_with_cb(
+                &attached,
+                wayland_client::global_filter!([
+                    WlSeat,
+                    2,
+                    move |seat: Main<WlSeat>, _: DispatchData| {
+                        let name = Rc::new(RefCell::new(None));
+                        let seat_name = name.clone();
+                        seat.quick_assign(move |_, event, _| {
+                            if let wl_seat::Event::Name { name } = event {
+                                *seat_name.borrow_mut() = Some(name);
+                            }
+                        });
+                        seats.borrow_mut().push((seat, name));
+                    }
+                ]),
+            )
+        };
+        // the globals are announced by the first roundtrip, the names of the
+        // seats by the second one
+        for _ in 0..2 {
+            event_queue
+                .sync_roundtrip(&mut (), |_, _, _| {})
+                .context(error::DispatchWaylandEvents)?;
+        }
+
+        // primary selections are announced since version 2
+        let manager = globals
+            .instantiate_range::<ZwlrDataControlManagerV1>(1, 2)
+            .context(error::BindDataControl)?;
+
+        let changed = Rc::new(Cell::new(false));
+        let finished = Rc::new(Cell::new(false));
+        let devices: Vec<_> = seats
+            .borrow()
+            .iter()
+            .filter(|(_, name)| seat.is_none_or(|seat| name.borrow().as_deref() == Some(seat)))
+            .map(|(proxy, _)| {
+                let device = manager.get_data_device(proxy);
+                let (changed, finished) = (changed.clone(), finished.clone());
+                device.quick_assign(move |_, event, _| {
+                    let (selection, offer) = match event {
+                        zwlr_data_control_device_v1::Event::Selection { id } => {
+                            (ClipboardType::Clipboard, id)
+                        }
+                        zwlr_data_control_device_v1::Event::PrimarySelection { id } => {
+                            (ClipboardType::Primary, id)
+                        }
+                        zwlr_data_control_device_v1::Event::Finished => {
+                            finished.set(true);
+                            return;
+                        }
+                        _ => return,
+                    };
+                    if let Some(offer) = offer {
+                        offer.destroy();
+                    }
+                    if selection == clipboard_type {
+                        changed.set(true);
+                    }
+                });
+                device
+            })
+            .collect();
+        if let (Some(seat), true) = (seat, devices.is_empty()) {
+            return Err(ClipboardError::WaylandSeatNotFound { seat: seat.to_owned() });
+        }
+
+        Ok(SelectionEvents { event_queue, changed, finished, _display: display, _devices: devices })
+    }
+
+    // Blocks until the selection changes, the first change is the selection
+    // which is current when the devices are created.
+    fn wait(&mut self) -> Result<(), ClipboardError> {
+        self.changed.set(false);
+        while !self.changed.get() {
+            if self.finished.get() {
+                return Err(ClipboardError::WaylandSelectionClosed);
+            }
+            self.event_queue
+                .dispatch(&mut (), |_, _, _| {})
+                .context(error::DispatchWaylandEvents)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the app ID of the focused window. wlr-data-control does not tell
+/// which client offers the selection, but the clip was usually just copied
+/// in the focused window. Only sway and Hyprland are asked for it.
+fn focused_application() -> Option<String> {
+    if std::env::var_os("SWAYSOCK").is_some() {
+        let tree = command_output("swaymsg", &["--raw", "--type", "get_tree"])?;
+        return focused_sway_application(&serde_json::from_slice(&tree).ok()?);
+    }
+    if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        let window = command_output("hyprctl", &["activewindow", "-j"])?;
+        let window: serde_json::Value = serde_json::from_slice(&window).ok()?;
+        return json_string(&window["class"]);
+    }
+    None
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<Vec<u8>> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if output.status.success() {
+        Some(output.stdout)
+    } else {
+        None
+    }
+}
+
+// Finds the focused node in the tree of sway, X11 windows of Xwayland have a
+// class instead of an app ID.
+fn focused_sway_application(node: &serde_json::Value) -> Option<String> {
+    if node["focused"].as_bool() == Some(true) {
+        return json_string(&node["app_id"])
+            .or_else(|| json_string(&node["window_properties"]["class"]));
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[*key].as_array())
+        .flatten()
+        .find_map(focused_sway_application)
+}
+
+fn json_string(value: &serde_json::Value) -> Option<String> {
+    value.as_str().filter(|value| !value.is_empty()).map(ToOwned::to_owned)
+}
+
+/// Offers every representation in `targets` to Wayland clients of `seat`, or
+/// of all seats if it is `None`.
+pub async fn store(
+    targets: Vec<(String, Vec<u8>)>,
+    clipboard_type: ClipboardType,
+    seat: Option<String>,
+) -> Result<(), ClipboardError> {
+    store_with(targets, clipboard_type, seat, copy::ServeRequests::Unlimited).await
+}
+
+/// Offers `targets` for a single paste, the selection is cleared afterwards.
+pub async fn store_once(
+    targets: Vec<(String, Vec<u8>)>,
+    clipboard_type: ClipboardType,
+) -> Result<(), ClipboardError> {
+    store_with(targets, clipboard_type, None, copy::ServeRequests::Only(1)).await
+}
+
+async fn store_with(
+    targets: Vec<(String, Vec<u8>)>,
+    clipboard_type: ClipboardType,
+    seat: Option<String>,
+    serve_requests: copy::ServeRequests,
+) -> Result<(), ClipboardError> {
+    let clipboard_type = match clipboard_type {
+        ClipboardType::Clipboard => copy::ClipboardType::Regular,
+        ClipboardType::Primary => copy::ClipboardType::Primary,
+    };
+    let sources = targets
+        .into_iter()
+        .map(|(mime, data)| copy::MimeSource {
+            source: copy::Source::Bytes(data.into_boxed_slice()),
+            mime_type: if mime::is_text(&mime) {
+                copy::MimeType::Text
+            } else {
+                copy::MimeType::Specific(mime)
+            },
+        })
+        .collect();
+
+    tokio::task::spawn_blocking(move || -> Result<(), ClipboardError> {
+        let mut opts = copy::Options::new();
+        opts.clipboard(clipboard_type).serve_requests(serve_requests);
+        if let Some(seat) = seat {
+            opts.seat(copy::Seat::Specific(seat));
+        }
+        opts.copy_multi(sources)
+            .map_err(|err| ClipboardError::PasteToWaylandClipboard { error: err.to_string() })
+    })
+    .await
+    .context(error::SpawnBlockingTask)??;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::wayland::focused_sway_application;
+
+    #[test]
+    fn test_focused_sway_application() {
+        let tree = serde_json::json!({
+            "focused": false,
+            "nodes": [{
+                "focused": false,
+                "nodes": [
+                    { "focused": false, "app_id": "foot", "nodes": [] },
+                    { "focused": true, "app_id": null, "window_properties": { "class": "Gimp" } },
+                ],
+                "floating_nodes": [{ "focused": false, "app_id": "pavucontrol" }],
+            }],
+        });
+        assert_eq!(focused_sway_application(&tree), Some("Gimp".to_owned()));
+
+        let tree = serde_json::json!({ "focused": true, "app_id": "firefox" });
+        assert_eq!(focused_sway_application(&tree), Some("firefox".to_owned()));
+        assert_eq!(focused_sway_application(&serde_json::json!({ "nodes": [] })), None);
+    }
+}
@@ -0,0 +1,499 @@
+use std::{io, ptr, thread, time::Duration};
+
+use snafu::ResultExt;
+use winapi::{
+    shared::{
+        minwindef::{FALSE, UINT},
+        windef::HWND,
+    },
+    um::{
+        shellapi::{DragQueryFileW, HDROP},
+        winbase::{GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE},
+        winuser::{
+            AddClipboardFormatListener, CloseClipboard, CreateWindowExW, DestroyWindow,
+            EmptyClipboard, GetClipboardData, GetMessageW, IsClipboardFormatAvailable,
+            OpenClipboard, RegisterClipboardFormatW, RemoveClipboardFormatListener,
+            SetClipboardData, CF_HDROP, CF_UNICODETEXT, HWND_MESSAGE, MSG, WM_CLIPBOARDUPDATE,
+        },
+    },
+};
+
+use crate::{backend::LineEndings, error, mime, ClipboardError, ClipboardEvent, ClipboardType};
+
+// Another application may hold the clipboard open for a moment, opening it is
+// retried for up to 200ms.
+const OPEN_RETRIES: usize = 10;
+const OPEN_RETRY_INTERVAL: Duration = Duration::from_millis(20);
+
+const FORMAT_HTML: &str = "HTML Format";
+
+// Registered clipboard formats of MIME types, other MIME types are registered
+// with their own name.
+const CLIPBOARD_FORMATS: &[(&str, &str)] = &[
+    (mime::TEXT_HTML, FORMAT_HTML),
+    (mime::TEXT_RTF, "Rich Text Format"),
+    (mime::IMAGE_PNG, "PNG"),
+    (mime::IMAGE_JPEG, "JFIF"),
+];
+
+// Password managers offer these formats so that clipboard managers and the
+// clipboard history of Windows do not keep their content.
+const FORMAT_EXCLUDE_FROM_MONITOR: &str = "ExcludeClipboardContentFromMonitorProcessing";
+const FORMAT_CAN_INCLUDE_IN_HISTORY: &str = "CanIncludeInClipboardHistory";
+const FORMAT_CAN_UPLOAD_TO_CLOUD: &str = "CanUploadToCloudClipboard";
+
+// Size of the DROPFILES header of `CF_HDROP`.
+const DROP_FILES_SIZE: u32 = 20;
+
+// A message-only window, the clipboard sends its updates to a window.
+struct Window {
+    hwnd: HWND,
+    listening: bool,
+}
+
+// The window is only used by the thread which waits for updates, it is created
+// there as messages are delivered to the thread which creates the window.
+unsafe impl Send for Window {}
+
+impl Window {
+    fn new() -> Result<Window, ClipboardError> {
+        let class = encode_wide("STATIC");
+        let hwnd = unsafe {
+            CreateWindowExW(
+                0,
+                class.as_ptr(),
+                ptr::null(),
+                0,
+                0,
+                0,
+                0,
+                0,
+                HWND_MESSAGE,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        };
+        if hwnd.is_null() {
+            return Err(io::Error::last_os_error()).context(error::ListenWindowsClipboard);
+        }
+        Ok(Window { hwnd, listening: false })
+    }
+
+    fn listen(&mut self) -> Result<(), ClipboardError> {
+        if unsafe { AddClipboardFormatListener(self.hwnd) } == FALSE {
+            return Err(io::Error::last_os_error()).context(error::ListenWindowsClipboard);
+        }
+        self.listening = true;
+        Ok(())
+    }
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        unsafe {
+            if self.listening {
+                RemoveClipboardFormatListener(self.hwnd);
+            }
+            DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+// Closes the clipboard when it is dropped.
+struct OpenedClipboard;
+
+impl OpenedClipboard {
+    fn open(owner: HWND) -> Result<OpenedClipboard, ClipboardError> {
+        for _ in 0..OPEN_RETRIES {
+            if unsafe { OpenClipboard(owner) } != FALSE {
+                return Ok(OpenedClipboard);
+            }
+            thread::sleep(OPEN_RETRY_INTERVAL);
+        }
+        Err(io::Error::last_os_error()).context(error::OpenWindowsClipboard)
+    }
+}
+
+impl Drop for OpenedClipboard {
+    fn drop(&mut self) { unsafe { CloseClipboard() }; }
+}
+
+/// Watches the Windows clipboard, Windows has no primary selection.
+pub struct Watcher {
+    clipboard_type: ClipboardType,
+    window: Option<Window>,
+}
+
+impl Watcher {
+    pub fn new(clipboard_type: ClipboardType) -> Watcher {
+        Watcher { clipboard_type, window: None }
+    }
+
+    pub fn load(&mut self) -> Result<ClipboardEvent, ClipboardError> {
+        let _clipboard = OpenedClipboard::open(ptr::null_mut())?;
+        Ok(self.build_event())
+    }
+
+    pub fn wait(&mut self) -> Result<ClipboardEvent, ClipboardError> {
+        let hwnd = match self.window {
+            Some(ref window) => window.hwnd,
+            None => {
+                let mut window = Window::new()?;
+                window.listen()?;
+                let hwnd = window.hwnd;
+                self.window = Some(window);
+                hwnd
+            }
+        };
+
+        let mut msg: MSG = unsafe { std::mem::zeroed() };
+        loop {
+            match unsafe { GetMessageW(&mut msg, hwnd, 0, 0) } {
+                -1 => {
+                    return Err(io::Error::last_os_error()).context(error::ListenWindowsClipboard)
+                }
+                0 => {
+                    return Err(io::Error::from(io::ErrorKind::Interrupted))
+                        .context(error::ListenWindowsClipboard)
+                }
+                _ if msg.message == WM_CLIPBOARDUPDATE => match self.load() {
+                    Ok(event) => return Ok(event),
+                    // the content is captured with the next update
+                    Err(err) => tracing::warn!("{}", err),
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn build_event(&self) -> ClipboardEvent {
+        // one-shot clips are offered by clipcat itself
+        if has_format(clipboard_format(mime::CLIPCAT_ONE_SHOT)) {
+            return ClipboardEvent::from_bytes(mime::TEXT_PLAIN, Vec::new(), self.clipboard_type);
+        }
+
+        let uris = file_uris();
+        let text = clipboard_data(CF_UNICODETEXT)
+            .map(|data| decode_wide(&data).into_bytes())
+            .unwrap_or_default();
+        let (mut event, text) = if !uris.is_empty() {
+            let data = mime::format_uri_list(&uris);
+            (ClipboardEvent::from_bytes(mime::TEXT_URI_LIST, data, self.clipboard_type), text)
+        } else if !text.is_empty() {
+            (ClipboardEvent::from_bytes(mime::TEXT_PLAIN, text, self.clipboard_type), Vec::new())
+        } else {
+            let image = mime::IMAGE_TYPES
+                .iter()
+                .find_map(|mime| clipboard_data(clipboard_format(mime)).map(|data| (*mime, data)));
+            match image {
+                Some((mime, data)) => {
+                    (ClipboardEvent::from_bytes(mime, data, self.clipboard_type), Vec::new())
+                }
+                None => {
+                    return ClipboardEvent::from_bytes(
+                        mime::TEXT_PLAIN,
+                        Vec::new(),
+                        self.clipboard_type,
+                    )
+                }
+            }
+        };
+
+        if !text.is_empty() {
+            event.targets.insert(mime::TEXT_PLAIN.to_owned(), text);
+        }
+        for mime in mime::ALTERNATIVE_TYPES {
+            if *mime != event.mime && *mime != mime::TEXT_URI_LIST {
+                if let Some(data) = clipboard_data(clipboard_format(mime)) {
+                    let data = if *mime == mime::TEXT_HTML { parse_cf_html(&data) } else { data };
+                    event.targets.insert(mime.to_string(), data);
+                }
+            }
+        }
+        event.sensitive = has_format(registered_format(FORMAT_EXCLUDE_FROM_MONITOR))
+            || clipboard_data(registered_format(FORMAT_CAN_INCLUDE_IN_HISTORY))
+                .map_or(false, |data| data.iter().all(|&byte| byte == 0));
+        event
+    }
+}
+
+/// Replaces the content of the clipboard with `targets`, text is written with
+/// CRLF line endings.
+pub async fn store(
+    targets: Vec<(String, Vec<u8>)>,
+    _clipboard_type: ClipboardType,
+) -> Result<(), ClipboardError> {
+    tokio::task::spawn_blocking(move || write_clipboard(&targets))
+        .await
+        .context(error::SpawnBlockingTask)?
+}
+
+/// Like `store`, but the content is kept out of clipboard managers and the
+/// clipboard history of Windows. Windows does not tell the owner when the
+/// content is pasted, so it stays until the clipboard changes.
+pub async fn store_once(
+    mut targets: Vec<(String, Vec<u8>)>,
+    clipboard_type: ClipboardType,
+) -> Result<(), ClipboardError> {
+    targets.push((FORMAT_EXCLUDE_FROM_MONITOR.to_owned(), Vec::new()));
+    targets.push((FORMAT_CAN_INCLUDE_IN_HISTORY.to_owned(), 0u32.to_le_bytes().to_vec()));
+    targets.push((FORMAT_CAN_UPLOAD_TO_CLOUD.to_owned(), 0u32.to_le_bytes().to_vec()));
+    store(targets, clipboard_type).await
+}
+
+fn write_clipboard(targets: &[(String, Vec<u8>)]) -> Result<(), ClipboardError> {
+    // the clipboard refuses data when it is emptied without an owner window
+    let owner = Window::new()?;
+    let _clipboard = OpenedClipboard::open(owner.hwnd)?;
+    if unsafe { EmptyClipboard() } == FALSE {
+        return Err(io::Error::last_os_error()).context(error::OpenWindowsClipboard);
+    }
+
+    for (mime, data) in targets {
+        let (format, data) = if mime::is_file_list(mime) {
+            let paths: Vec<String> =
+                mime::parse_uri_list(data).iter().map(|uri| uri_to_windows_path(uri)).collect();
+            (CF_HDROP, format_drop_files(&paths))
+        } else if mime::is_text(mime) {
+            let text = LineEndings::Crlf.convert(&String::from_utf8_lossy(data));
+            (CF_UNICODETEXT, encode_wide_bytes(&text))
+        } else if mime == mime::TEXT_HTML {
+            (clipboard_format(mime), format_cf_html(data))
+        } else {
+            (clipboard_format(mime), data.clone())
+        };
+        set_clipboard_data(format, &data)
+            .context(error::WriteWindowsClipboard { mime: mime.clone() })?;
+    }
+    Ok(())
+}
+
+fn set_clipboard_data(format: UINT, data: &[u8]) -> io::Result<()> {
+    // the clipboard owns the memory once it is set
+    let handle = unsafe { GlobalAlloc(GMEM_MOVEABLE, data.len().max(1)) };
+    if handle.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe {
+        let buffer = GlobalLock(handle) as *mut u8;
+        if buffer.is_null() {
+            let err = io::Error::last_os_error();
+            GlobalFree(handle);
+            return Err(err);
+        }
+        ptr::copy_nonoverlapping(data.as_ptr(), buffer, data.len());
+        GlobalUnlock(handle);
+
+        if SetClipboardData(format, handle).is_null() {
+            let err = io::Error::last_os_error();
+            GlobalFree(handle);
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+fn clipboard_format(mime: &str) -> UINT {
+    if mime::is_text(mime) {
+        return CF_UNICODETEXT;
+    }
+    let name =
+        CLIPBOARD_FORMATS.iter().find(|&&(name, _)| name == mime).map_or(mime, |&(_, name)| name);
+    registered_format(name)
+}
+
+#[inline]
+fn registered_format(name: &str) -> UINT {
+    let name = encode_wide(name);
+    unsafe { RegisterClipboardFormatW(name.as_ptr()) }
+}
+
+#[inline]
+fn has_format(format: UINT) -> bool {
+    format != 0 && unsafe { IsClipboardFormatAvailable(format) } != FALSE
+}
+
+// The clipboard has to be open.
+fn clipboard_data(format: UINT) -> Option<Vec<u8>> {
+    if !has_format(format) {
+        return None;
+    }
+    unsafe {
+        let handle = GetClipboardData(format);
+        if handle.is_null() {
+            return None;
+        }
+        let size = GlobalSize(handle);
+        let buffer = GlobalLock(handle) as *const u8;
+        if size == 0 || buffer.is_null() {
+            return None;
+        }
+        let data = std::slice::from_raw_parts(buffer, size).to_vec();
+        GlobalUnlock(handle);
+        Some(data)
+    }
+}
+
+// Files copied in Explorer, the clipboard has to be open.
+fn file_uris() -> Vec<String> {
+    if !has_format(CF_HDROP) {
+        return Vec::new();
+    }
+    let hdrop = unsafe { GetClipboardData(CF_HDROP) } as HDROP;
+    if hdrop.is_null() {
+        return Vec::new();
+    }
+    let count = unsafe { DragQueryFileW(hdrop, UINT::MAX, ptr::null_mut(), 0) };
+    (0..count)
+        .filter_map(|index| {
+            let len = unsafe { DragQueryFileW(hdrop, index, ptr::null_mut(), 0) } as usize;
+            if len == 0 {
+                return None;
+            }
+            let mut buffer = vec![0u16; len + 1];
+            let copied =
+                unsafe { DragQueryFileW(hdrop, index, buffer.as_mut_ptr(), buffer.len() as UINT) }
+                    as usize;
+            Some(mime::path_to_uri(&String::from_utf16_lossy(&buffer[..copied])))
+        })
+        .collect()
+}
+
+// Null-terminated UTF-16 as the wide functions of Win32 take it.
+fn encode_wide(text: &str) -> Vec<u16> { text.encode_utf16().chain(Some(0)).collect() }
+
+fn encode_wide_bytes(text: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(text.len() * 2 + 2);
+    for unit in encode_wide(text) {
+        data.extend_from_slice(&unit.to_le_bytes());
+    }
+    data
+}
+
+// `CF_UNICODETEXT` ends at the first null, the memory behind it may be
+// larger. Unpaired surrogates become replacement characters.
+fn decode_wide(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn uri_to_windows_path(uri: &str) -> String {
+    let path = mime::uri_to_path(uri);
+    // `/C:/Users` is `C:\Users`
+    let path = match path.strip_prefix('/') {
+        Some(rest) if rest.get(1..2) == Some(":") => rest,
+        _ => path.as_str(),
+    };
+    path.replace('/', "\\")
+}
+
+// `CF_HDROP` is a DROPFILES header followed by the null-terminated paths and
+// another null.
+fn format_drop_files(paths: &[String]) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&DROP_FILES_SIZE.to_le_bytes());
+    // the drop point and whether it is in the non-client area
+    data.extend_from_slice(&[0; 12]);
+    // the paths are wide
+    data.extend_from_slice(&1u32.to_le_bytes());
+    for path in paths {
+        data.extend(encode_wide_bytes(path));
+    }
+    data.extend_from_slice(&[0, 0]);
+    data
+}
+
+fn cf_html_header(
+    start_html: usize,
+    end_html: usize,
+    start_fragment: usize,
+    end_fragment: usize,
+) -> String {
+    format!(
+        "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\nStartFragment:{:010}\r\nEndFragment:\
+         {:010}\r\n",
+        start_html, end_html, start_fragment, end_fragment
+    )
+}
+
+// "HTML Format" is HTML behind a header with the byte offsets of the document
+// and of the copied fragment.
+fn format_cf_html(html: &[u8]) -> Vec<u8> {
+    const PREFIX: &str = "<html><body>\r\n<!--StartFragment-->";
+    const SUFFIX: &str = "<!--EndFragment-->\r\n</body></html>";
+
+    // the offsets have a fixed width, so the header has the same length with
+    // any offsets
+    let start_html = cf_html_header(0, 0, 0, 0).len();
+    let start_fragment = start_html + PREFIX.len();
+    let end_fragment = start_fragment + html.len();
+    let end_html = end_fragment + SUFFIX.len();
+
+    let mut data = cf_html_header(start_html, end_html, start_fragment, end_fragment).into_bytes();
+    data.extend_from_slice(PREFIX.as_bytes());
+    data.extend_from_slice(html);
+    data.extend_from_slice(SUFFIX.as_bytes());
+    data
+}
+
+// Extracts the copied fragment, or the document if the fragment is unknown.
+fn parse_cf_html(data: &[u8]) -> Vec<u8> {
+    let header = String::from_utf8_lossy(data);
+    let offset = |name: &str| {
+        header
+            .lines()
+            .take_while(|line| !line.starts_with('<'))
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.trim().parse::<usize>().ok())
+    };
+    let range = |start, end| match (offset(start), offset(end)) {
+        (Some(start), Some(end)) if start <= end && end <= data.len() => Some(start..end),
+        _ => None,
+    };
+    match range("StartFragment:", "EndFragment:").or_else(|| range("StartHTML:", "EndHTML:")) {
+        Some(range) => data[range].to_vec(),
+        None => {
+            let len = data.iter().rposition(|&byte| byte != 0).map_or(0, |pos| pos + 1);
+            data[..len].to_vec()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::windows::{
+        decode_wide, encode_wide_bytes, format_cf_html, format_drop_files, parse_cf_html,
+        uri_to_windows_path,
+    };
+
+    #[test]
+    fn test_wide() {
+        let mut data = encode_wide_bytes("ünïcødé 🐈\r\n");
+        assert_eq!(&data[data.len() - 2..], &[0, 0]);
+        // the memory of the clipboard may be larger than its text
+        data.extend_from_slice(&[b'x', 0, 0, 0]);
+        assert_eq!(decode_wide(&data), "ünïcødé 🐈\r\n");
+    }
+
+    #[test]
+    fn test_cf_html() {
+        let data = format_cf_html(b"<b>bold</b>");
+        assert!(data.starts_with(b"Version:0.9\r\nStartHTML:0000000105\r\n"));
+        assert_eq!(parse_cf_html(&data), b"<b>bold</b>".to_vec());
+        assert_eq!(parse_cf_html(b"<p>no header</p>\0\0"), b"<p>no header</p>".to_vec());
+    }
+
+    #[test]
+    fn test_files() {
+        assert_eq!(uri_to_windows_path("file:///C:/Users/me/a%20b.txt"), "C:\\Users\\me\\a b.txt");
+        let data = format_drop_files(&["C:\\a".to_owned()]);
+        assert_eq!(&data[..4], &[20, 0, 0, 0]);
+        assert_eq!(&data[20..], &[b'C', 0, b':', 0, b'\\', 0, b'a', 0, 0, 0, 0, 0]);
+    }
+}
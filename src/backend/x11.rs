@@ -0,0 +1,866 @@
+use std::{
+    collections::HashMap,
+    ops::Range,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+use snafu::ResultExt;
+use x11_clipboard::{xcb, xcb::Atom, Clipboard, Context};
+
+use crate::{error, mime, ClipboardError, ClipboardEvent, ClipboardType};
+
+const LOAD_TIMEOUT: Duration = Duration::from_millis(1000);
+
+// x11_clipboard reads large contents in chunks with INCR, a transfer of many
+// megabytes takes longer than `LOAD_TIMEOUT`.
+const CONTENT_LOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Values larger than this are served in chunks of this size with INCR, unless
+// the maximum request length of the server is even smaller.
+const INCR_CHUNK_SIZE: usize = 256 * 1024;
+
+// Bytes of a ChangeProperty request in front of its data.
+const CHANGE_PROPERTY_HEADER_SIZE: usize = 24;
+
+// Targets which describe a selection instead of holding its content, they are
+// not saved by the clipboard manager.
+const META_TARGETS: &[&str] = &["TARGETS", "MULTIPLE", "TIMESTAMP", "SAVE_TARGETS", "DELETE"];
+
+// Windows of the watchers of this process by selection, requests from them are
+// not counted as pastes of one-shot selections.
+static WATCHER_WINDOWS: [AtomicU32; 2] = [AtomicU32::new(0), AtomicU32::new(0)];
+
+// x11_clipboard connects a `Clipboard` to $DISPLAY, so the variable is set to
+// another display while its clipboard connects. Connections hold this lock, so
+// none of them sees the display of another one.
+static DISPLAY_LOCK: Mutex<()> = Mutex::new(());
+
+#[inline]
+fn is_watcher_window(window: xcb::Window) -> bool {
+    WATCHER_WINDOWS.iter().any(|watcher| watcher.load(Ordering::Relaxed) == window)
+}
+
+// Connects to `display`, or to $DISPLAY if it is `None`.
+fn connect_clipboard(display: Option<&str>) -> Result<Clipboard, ClipboardError> {
+    let _lock = DISPLAY_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let display = match display {
+        Some(display) => display,
+        None => return Clipboard::new().context(error::InitializeX11Clipboard),
+    };
+
+    let default = std::env::var_os("DISPLAY");
+    std::env::set_var("DISPLAY", display);
+    let clipboard = Clipboard::new();
+    match default {
+        Some(default) => std::env::set_var("DISPLAY", default),
+        None => std::env::remove_var("DISPLAY"),
+    }
+    clipboard.context(error::InitializeX11Clipboard)
+}
+
+fn connect_context(display: Option<&str>) -> Result<Context, ClipboardError> {
+    let _lock = DISPLAY_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    Context::new(display).context(error::InitializeX11Clipboard)
+}
+
+pub struct Watcher {
+    clipboard: Clipboard,
+    clipboard_type: ClipboardType,
+    atom_clipboard: Atom,
+    atom_utf8string: Atom,
+    atom_property: Atom,
+    atom_targets: Atom,
+    image_atoms: Vec<(&'static str, Atom)>,
+    alternative_atoms: Vec<(&'static str, Atom)>,
+    atom_uri_list: Atom,
+    atom_gnome_copied_files: Atom,
+    atom_password_manager_hint: Atom,
+    atom_one_shot: Atom,
+    atom_net_wm_name: Atom,
+}
+
+impl Watcher {
+    /// Watches `clipboard_type` of `display`, or of $DISPLAY if it is `None`.
+    pub fn new(
+        clipboard_type: ClipboardType,
+        display: Option<&str>,
+    ) -> Result<Watcher, ClipboardError> {
+        let clipboard = connect_clipboard(display)?;
+        let atom_clipboard = match clipboard_type {
+            ClipboardType::Clipboard => clipboard.getter.atoms.clipboard,
+            ClipboardType::Primary => clipboard.getter.atoms.primary,
+        };
+        let atom_utf8string = clipboard.getter.atoms.utf8_string;
+        let atom_property = clipboard.getter.atoms.property;
+        let atom_targets = clipboard.getter.atoms.targets;
+        let image_atoms = intern_atoms(&clipboard.getter, mime::IMAGE_TYPES)?;
+        let alternative_atoms = intern_atoms(&clipboard.getter, mime::ALTERNATIVE_TYPES)?;
+        let atom_uri_list = clipboard
+            .getter
+            .get_atom(mime::TEXT_URI_LIST)
+            .context(error::InitializeX11Clipboard)?;
+        let atom_gnome_copied_files = clipboard
+            .getter
+            .get_atom(mime::GNOME_COPIED_FILES)
+            .context(error::InitializeX11Clipboard)?;
+        let atom_password_manager_hint = clipboard
+            .getter
+            .get_atom(mime::KDE_PASSWORD_MANAGER_HINT)
+            .context(error::InitializeX11Clipboard)?;
+        let atom_one_shot = clipboard
+            .getter
+            .get_atom(mime::CLIPCAT_ONE_SHOT)
+            .context(error::InitializeX11Clipboard)?;
+        let atom_net_wm_name =
+            clipboard.getter.get_atom("_NET_WM_NAME").context(error::InitializeX11Clipboard)?;
+
+        // one-shot clips are only offered on the default display
+        if display.is_none() {
+            WATCHER_WINDOWS[clipboard_type as usize]
+                .store(clipboard.getter.window, Ordering::Relaxed);
+        }
+
+        Ok(Watcher {
+            clipboard,
+            clipboard_type,
+            atom_clipboard,
+            atom_utf8string,
+            atom_property,
+            atom_targets,
+            image_atoms,
+            alternative_atoms,
+            atom_uri_list,
+            atom_gnome_copied_files,
+            atom_password_manager_hint,
+            atom_one_shot,
+            atom_net_wm_name,
+        })
+    }
+
+    pub fn load(&mut self) -> Result<ClipboardEvent, ClipboardError> {
+        let data = self
+            .clipboard
+            .load(self.atom_clipboard, self.atom_utf8string, self.atom_property, None)
+            .context(error::LoadX11Clipboard)?;
+        self.to_event(data)
+    }
+
+    pub fn wait(&mut self) -> Result<ClipboardEvent, ClipboardError> {
+        let data = self
+            .clipboard
+            .load_wait(self.atom_clipboard, self.atom_utf8string, self.atom_property)
+            .context(error::LoadX11Clipboard)?;
+        self.to_event(data)
+    }
+
+    /// Returns the window class of the selection owner, applications usually
+    /// own the selection with a hidden window which carries their `WM_CLASS`.
+    /// The title of the window is taken if it has no class.
+    fn owner_application(&self) -> Option<String> {
+        let connection = &self.clipboard.getter.connection;
+        let owner =
+            xcb::get_selection_owner(connection, self.atom_clipboard).get_reply().ok()?.owner();
+        if owner == xcb::NONE {
+            return None;
+        }
+
+        let property = |property, kind| {
+            let reply = xcb::get_property(connection, false, owner, property, kind, 0, 1024)
+                .get_reply()
+                .ok()?;
+            Some(reply.value::<u8>().to_vec()).filter(|value| !value.is_empty())
+        };
+        property(xcb::ATOM_WM_CLASS, xcb::ATOM_STRING)
+            .and_then(|value| parse_wm_class(&value))
+            .or_else(|| {
+                property(self.atom_net_wm_name, self.atom_utf8string)
+                    .or_else(|| property(xcb::ATOM_WM_NAME, xcb::ATOM_STRING))
+                    .and_then(|value| parse_window_title(&value))
+            })
+    }
+
+    fn to_event(&self, text: Vec<u8>) -> Result<ClipboardEvent, ClipboardError> {
+        let targets = self.load_targets()?;
+        // one-shot selections are offered by clipcat itself
+        if targets.contains(&self.atom_one_shot) {
+            return Ok(ClipboardEvent::from_bytes(
+                mime::TEXT_PLAIN,
+                Vec::new(),
+                self.clipboard_type,
+            ));
+        }
+
+        let mut event = self.build_event(text, &targets)?;
+        event.application = self.owner_application();
+        if targets.contains(&self.atom_password_manager_hint) {
+            let hint = self.load_target(self.atom_password_manager_hint)?;
+            event.sensitive = mime::is_sensitive_hint(mime::KDE_PASSWORD_MANAGER_HINT, &hint);
+        }
+        Ok(event)
+    }
+
+    fn build_event(
+        &self,
+        text: Vec<u8>,
+        targets: &[Atom],
+    ) -> Result<ClipboardEvent, ClipboardError> {
+        // file managers offer the paths as text as well, prefer the file list
+        let uris = if targets.contains(&self.atom_uri_list) {
+            mime::parse_uri_list(&self.load_content(self.atom_uri_list)?)
+        } else if targets.contains(&self.atom_gnome_copied_files) {
+            mime::parse_gnome_copied_files(&self.load_content(self.atom_gnome_copied_files)?)
+        } else {
+            Vec::new()
+        };
+        let (mut event, text) = if !uris.is_empty() {
+            let data = mime::format_uri_list(&uris);
+            (ClipboardEvent::from_bytes(mime::TEXT_URI_LIST, data, self.clipboard_type), text)
+        } else if !text.is_empty() {
+            (ClipboardEvent::from_bytes(mime::TEXT_PLAIN, text, self.clipboard_type), Vec::new())
+        } else {
+            // the owner does not offer text, look for an image target instead
+            match self.load_image(targets)? {
+                Some(event) => (event, Vec::new()),
+                None => {
+                    return Ok(ClipboardEvent::from_bytes(
+                        mime::TEXT_PLAIN,
+                        Vec::new(),
+                        self.clipboard_type,
+                    ))
+                }
+            }
+        };
+
+        // the other representations are stored as well, so pastes of the clip
+        // are faithful
+        if !text.is_empty() {
+            event.targets.insert(mime::TEXT_PLAIN.to_owned(), text);
+        }
+        for (mime, atom) in &self.alternative_atoms {
+            if *mime != event.mime && targets.contains(atom) {
+                let data = self.load_content(*atom)?;
+                if !data.is_empty() {
+                    event.targets.insert(mime.to_string(), data);
+                }
+            }
+        }
+        Ok(event)
+    }
+
+    fn load_image(&self, targets: &[Atom]) -> Result<Option<ClipboardEvent>, ClipboardError> {
+        for (mime, atom) in &self.image_atoms {
+            if targets.contains(atom) {
+                let data = self.load_content(*atom)?;
+                if !data.is_empty() {
+                    return Ok(Some(ClipboardEvent::from_bytes(mime, data, self.clipboard_type)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    #[inline]
+    fn load_target(&self, target: Atom) -> Result<Vec<u8>, ClipboardError> {
+        self.clipboard
+            .load(self.atom_clipboard, target, self.atom_property, LOAD_TIMEOUT)
+            .context(error::LoadX11Clipboard)
+    }
+
+    #[inline]
+    fn load_content(&self, target: Atom) -> Result<Vec<u8>, ClipboardError> {
+        self.clipboard
+            .load(self.atom_clipboard, target, self.atom_property, CONTENT_LOAD_TIMEOUT)
+            .context(error::LoadX11Clipboard)
+    }
+
+    fn load_targets(&self) -> Result<Vec<Atom>, ClipboardError> {
+        let data = self.load_target(self.atom_targets)?;
+        Ok(data
+            .chunks_exact(4)
+            .map(|atom| Atom::from_ne_bytes([atom[0], atom[1], atom[2], atom[3]]))
+            .collect())
+    }
+}
+
+// `WM_CLASS` consists of the instance name and the class name, both are null
+// terminated. The class name is preferred.
+fn parse_wm_class(value: &[u8]) -> Option<String> {
+    let mut names = value
+        .split(|b| *b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned());
+    let instance = names.next();
+    names.next().or(instance)
+}
+
+fn parse_window_title(value: &[u8]) -> Option<String> {
+    let title = String::from_utf8_lossy(value);
+    let title = title.trim_matches(char::from(0)).trim();
+    if title.is_empty() {
+        None
+    } else {
+        Some(title.to_owned())
+    }
+}
+
+fn intern_atoms(
+    context: &Context,
+    names: &[&'static str],
+) -> Result<Vec<(&'static str, Atom)>, ClipboardError> {
+    let mut atoms = Vec::with_capacity(names.len());
+    for name in names {
+        let atom = context.get_atom(name).context(error::InitializeX11Clipboard)?;
+        atoms.push((*name, atom));
+    }
+    Ok(atoms)
+}
+
+/// Takes ownership of the selection and serves `targets` from a background
+/// thread until another application becomes the selection owner.
+pub async fn store(
+    targets: Vec<(String, Vec<u8>)>,
+    clipboard_type: ClipboardType,
+    display: Option<String>,
+) -> Result<(), ClipboardError> {
+    store_with(targets, clipboard_type, display, false).await
+}
+
+/// Like `store`, but gives up the selection after its content is requested
+/// once by another application.
+pub async fn store_once(
+    targets: Vec<(String, Vec<u8>)>,
+    clipboard_type: ClipboardType,
+) -> Result<(), ClipboardError> {
+    store_with(targets, clipboard_type, None, true).await
+}
+
+async fn store_with(
+    targets: Vec<(String, Vec<u8>)>,
+    clipboard_type: ClipboardType,
+    display: Option<String>,
+    one_shot: bool,
+) -> Result<(), ClipboardError> {
+    tokio::task::spawn_blocking(move || -> Result<(), ClipboardError> {
+        let mut owner = SelectionOwner::new(targets, clipboard_type, display.as_deref())?;
+        if one_shot {
+            owner.set_one_shot()?;
+        }
+        owner.acquire()?;
+        thread::spawn(move || owner.serve());
+        Ok(())
+    })
+    .await
+    .context(error::SpawnBlockingTask)??;
+    Ok(())
+}
+
+struct SelectionOwner {
+    context: Context,
+    selection: Atom,
+    values: HashMap<Atom, Vec<u8>>,
+
+    // targets which do not count as a paste of a one-shot selection
+    uncounted_targets: Option<Vec<Atom>>,
+}
+
+impl SelectionOwner {
+    fn new(
+        targets: Vec<(String, Vec<u8>)>,
+        clipboard_type: ClipboardType,
+        display: Option<&str>,
+    ) -> Result<SelectionOwner, ClipboardError> {
+        let mut owner = SelectionOwner::with_values(HashMap::new(), clipboard_type, display)?;
+        for (mime, data) in targets {
+            let context = &owner.context;
+            if mime::is_text(&mime) {
+                let atom =
+                    context.get_atom(mime::TEXT_PLAIN).context(error::InitializeX11Clipboard)?;
+                let utf8_string = context.atoms.utf8_string;
+                owner.values.insert(atom, data.clone());
+                owner.values.insert(utf8_string, data);
+            } else {
+                let atom = context.get_atom(&mime).context(error::InitializeX11Clipboard)?;
+                owner.values.insert(atom, data);
+            }
+        }
+        Ok(owner)
+    }
+
+    // Serves `values` as they are, keyed by their target atoms.
+    fn with_values(
+        values: HashMap<Atom, Vec<u8>>,
+        clipboard_type: ClipboardType,
+        display: Option<&str>,
+    ) -> Result<SelectionOwner, ClipboardError> {
+        let context = connect_context(display)?;
+        let selection = match clipboard_type {
+            ClipboardType::Clipboard => context.atoms.clipboard,
+            ClipboardType::Primary => context.atoms.primary,
+        };
+        Ok(SelectionOwner { context, selection, values, uncounted_targets: None })
+    }
+
+    fn set_one_shot(&mut self) -> Result<(), ClipboardError> {
+        let mut uncounted = vec![self.context.atoms.targets];
+        for name in &[mime::KDE_PASSWORD_MANAGER_HINT, mime::CLIPCAT_ONE_SHOT] {
+            uncounted.push(self.context.get_atom(name).context(error::InitializeX11Clipboard)?);
+        }
+        self.uncounted_targets = Some(uncounted);
+        Ok(())
+    }
+
+    // Returns `true` if the request is the single paste of a one-shot selection.
+    fn is_paste(&self, target: Atom, requestor: xcb::Window, property: Atom) -> bool {
+        match self.uncounted_targets {
+            Some(ref uncounted) => {
+                property != xcb::ATOM_NONE
+                    && !uncounted.contains(&target)
+                    && !is_watcher_window(requestor)
+            }
+            None => false,
+        }
+    }
+
+    fn acquire(&self) -> Result<(), ClipboardError> {
+        let connection = &self.context.connection;
+        xcb::set_selection_owner(
+            connection,
+            self.context.window,
+            self.selection,
+            xcb::CURRENT_TIME,
+        );
+        connection.flush();
+
+        let owner = xcb::get_selection_owner(connection, self.selection)
+            .get_reply()
+            .map(|reply| reply.owner())
+            .ok();
+        if owner != Some(self.context.window) {
+            return Err(ClipboardError::AcquireX11Selection);
+        }
+
+        Ok(())
+    }
+
+    fn serve(self) {
+        let connection = &self.context.connection;
+        let mut targets = vec![self.context.atoms.targets];
+        targets.extend(self.values.keys().cloned());
+
+        let max_request_size = connection.get_maximum_request_length() as usize * 4;
+        let chunk_size = incr_chunk_size(max_request_size);
+        // INCR transfers by requestor and property, they are completed even if
+        // the selection is given up meanwhile
+        let mut transfers: HashMap<(xcb::Window, Atom), IncrTransfer> = HashMap::new();
+        let mut given_up = false;
+
+        while let Some(event) = connection.wait_for_event() {
+            match event.response_type() & !0x80 {
+                xcb::SELECTION_REQUEST => {
+                    let event = unsafe { xcb::cast_event::<xcb::SelectionRequestEvent>(&event) };
+                    let target = event.target();
+                    let requestor = event.requestor();
+                    // obsolete clients may not set a property
+                    let mut property =
+                        if event.property() == xcb::ATOM_NONE { target } else { event.property() };
+
+                    if target == self.context.atoms.targets {
+                        xcb::change_property(
+                            connection,
+                            xcb::PROP_MODE_REPLACE as u8,
+                            requestor,
+                            property,
+                            xcb::ATOM_ATOM,
+                            32,
+                            &targets[..],
+                        );
+                    } else if let Some(value) = self.values.get(&target) {
+                        if value.len() > chunk_size {
+                            // the requestor deletes the property to ask for each chunk
+                            xcb::change_window_attributes(
+                                connection,
+                                requestor,
+                                &[(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_PROPERTY_CHANGE)],
+                            );
+                            xcb::change_property(
+                                connection,
+                                xcb::PROP_MODE_REPLACE as u8,
+                                requestor,
+                                property,
+                                self.context.atoms.incr,
+                                32,
+                                &[value.len() as u32],
+                            );
+                            transfers.insert(
+                                (requestor, property),
+                                IncrTransfer::new(target, value.len(), chunk_size),
+                            );
+                        } else {
+                            xcb::change_property(
+                                connection,
+                                xcb::PROP_MODE_REPLACE as u8,
+                                requestor,
+                                property,
+                                target,
+                                8,
+                                value.as_slice(),
+                            );
+                        }
+                    } else {
+                        property = xcb::ATOM_NONE;
+                    }
+
+                    let notify = xcb::SelectionNotifyEvent::new(
+                        event.time(),
+                        requestor,
+                        event.selection(),
+                        target,
+                        property,
+                    );
+                    xcb::send_event(
+                        connection,
+                        false,
+                        requestor,
+                        xcb::EVENT_MASK_NO_EVENT,
+                        &notify,
+                    );
+                    connection.flush();
+
+                    if self.is_paste(target, requestor, property) {
+                        tracing::debug!("One-shot selection is pasted, give up the selection");
+                        xcb::set_selection_owner(
+                            connection,
+                            xcb::NONE,
+                            self.selection,
+                            xcb::CURRENT_TIME,
+                        );
+                        connection.flush();
+                        given_up = true;
+                    }
+                }
+                xcb::PROPERTY_NOTIFY => {
+                    let event = unsafe { xcb::cast_event::<xcb::PropertyNotifyEvent>(&event) };
+                    let key = (event.window(), event.atom());
+                    if u32::from(event.state()) != xcb::PROPERTY_DELETE {
+                        continue;
+                    }
+                    let transfer = match transfers.get_mut(&key) {
+                        Some(transfer) => transfer,
+                        None => continue,
+                    };
+
+                    let target = transfer.target;
+                    let chunk = transfer.next_chunk().unwrap_or(0..0);
+                    let finished = chunk.is_empty();
+                    xcb::change_property(
+                        connection,
+                        xcb::PROP_MODE_REPLACE as u8,
+                        key.0,
+                        key.1,
+                        target,
+                        8,
+                        &self.values[&target][chunk],
+                    );
+                    if finished {
+                        // the empty chunk ends the transfer
+                        transfers.remove(&key);
+                        if !transfers.keys().any(|&(window, _)| window == key.0) {
+                            xcb::change_window_attributes(
+                                connection,
+                                key.0,
+                                &[(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_NO_EVENT)],
+                            );
+                        }
+                    }
+                    connection.flush();
+                }
+                xcb::SELECTION_CLEAR => {
+                    tracing::debug!("Selection ownership is taken by another application");
+                    given_up = true;
+                }
+                _ => {}
+            }
+
+            if given_up && transfers.is_empty() {
+                return;
+            }
+        }
+    }
+}
+
+/// The state of a value which is served in chunks with INCR.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct IncrTransfer {
+    target: Atom,
+    len: usize,
+    chunk_size: usize,
+    offset: usize,
+    finished: bool,
+}
+
+impl IncrTransfer {
+    fn new(target: Atom, len: usize, chunk_size: usize) -> IncrTransfer {
+        IncrTransfer { target, len, chunk_size, offset: 0, finished: false }
+    }
+
+    // Returns the range of the next chunk of the value, an empty range ends the
+    // transfer and nothing is left after it.
+    fn next_chunk(&mut self) -> Option<Range<usize>> {
+        if self.finished {
+            return None;
+        }
+        let start = self.offset;
+        let end = (start + self.chunk_size).min(self.len);
+        self.offset = end;
+        self.finished = start == end;
+        Some(start..end)
+    }
+}
+
+// Returns the size of the chunks of INCR transfers, a chunk has to fit into a
+// single ChangeProperty request.
+fn incr_chunk_size(max_request_size: usize) -> usize {
+    INCR_CHUNK_SIZE.min(max_request_size.saturating_sub(CHANGE_PROPERTY_HEADER_SIZE)).max(1)
+}
+
+/// Becomes the owner of `CLIPBOARD_MANAGER`, applications which own the
+/// clipboard ask it to save the clipboard with `SAVE_TARGETS` before they exit,
+/// see the freedesktop clipboard manager specification. Requests are served
+/// from a background thread until another clipboard manager takes over.
+pub fn manage_clipboard() -> Result<(), ClipboardError> {
+    let context = connect_context(None)?;
+    let manager = context.get_atom("CLIPBOARD_MANAGER").context(error::InitializeX11Clipboard)?;
+    let connection = &context.connection;
+
+    // another clipboard manager is not replaced
+    let owner = xcb::get_selection_owner(connection, manager)
+        .get_reply()
+        .map(|reply| reply.owner())
+        .unwrap_or(xcb::NONE);
+    if owner != xcb::NONE {
+        return Err(ClipboardError::ClipboardManagerExists);
+    }
+
+    xcb::set_selection_owner(connection, context.window, manager, xcb::CURRENT_TIME);
+    connection.flush();
+    let owner =
+        xcb::get_selection_owner(connection, manager).get_reply().map(|reply| reply.owner()).ok();
+    if owner != Some(context.window) {
+        return Err(ClipboardError::AcquireX11Selection);
+    }
+
+    let manager = ClipboardManagerSelection::new(context, manager)?;
+    thread::spawn(move || manager.serve());
+    Ok(())
+}
+
+struct ClipboardManagerSelection {
+    context: Context,
+    selection: Atom,
+    atom_save_targets: Atom,
+    atom_null: Atom,
+    meta_targets: Vec<Atom>,
+}
+
+impl ClipboardManagerSelection {
+    fn new(context: Context, selection: Atom) -> Result<ClipboardManagerSelection, ClipboardError> {
+        let atom_save_targets =
+            context.get_atom("SAVE_TARGETS").context(error::InitializeX11Clipboard)?;
+        let atom_null = context.get_atom("NULL").context(error::InitializeX11Clipboard)?;
+        let mut meta_targets = Vec::with_capacity(META_TARGETS.len());
+        for name in META_TARGETS {
+            meta_targets.push(context.get_atom(name).context(error::InitializeX11Clipboard)?);
+        }
+        Ok(ClipboardManagerSelection {
+            context,
+            selection,
+            atom_save_targets,
+            atom_null,
+            meta_targets,
+        })
+    }
+
+    fn serve(self) {
+        let connection = &self.context.connection;
+        while let Some(event) = connection.wait_for_event() {
+            match event.response_type() & !0x80 {
+                xcb::SELECTION_REQUEST => {
+                    let event = unsafe { xcb::cast_event::<xcb::SelectionRequestEvent>(&event) };
+                    let target = event.target();
+                    let requestor = event.requestor();
+                    let mut property =
+                        if event.property() == xcb::ATOM_NONE { target } else { event.property() };
+
+                    if target == self.context.atoms.targets {
+                        xcb::change_property(
+                            connection,
+                            xcb::PROP_MODE_REPLACE as u8,
+                            requestor,
+                            property,
+                            xcb::ATOM_ATOM,
+                            32,
+                            &[self.context.atoms.targets, self.atom_save_targets],
+                        );
+                    } else if target == self.atom_save_targets {
+                        match self.save_clipboard(requestor, event.property()) {
+                            Ok(()) => {
+                                tracing::info!("Clipboard is saved before its owner exits");
+                                // SAVE_TARGETS is answered like other side effect targets
+                                xcb::change_property(
+                                    connection,
+                                    xcb::PROP_MODE_REPLACE as u8,
+                                    requestor,
+                                    property,
+                                    self.atom_null,
+                                    32,
+                                    &[] as &[u32],
+                                );
+                            }
+                            Err(err) => {
+                                tracing::warn!("Could not save clipboard, error: {}", err);
+                                property = xcb::ATOM_NONE;
+                            }
+                        }
+                    } else {
+                        property = xcb::ATOM_NONE;
+                    }
+
+                    let notify = xcb::SelectionNotifyEvent::new(
+                        event.time(),
+                        requestor,
+                        event.selection(),
+                        target,
+                        property,
+                    );
+                    xcb::send_event(
+                        connection,
+                        false,
+                        requestor,
+                        xcb::EVENT_MASK_NO_EVENT,
+                        &notify,
+                    );
+                    connection.flush();
+                }
+                xcb::SELECTION_CLEAR => {
+                    let event = unsafe { xcb::cast_event::<xcb::SelectionClearEvent>(&event) };
+                    if event.selection() == self.selection {
+                        tracing::info!("CLIPBOARD_MANAGER is taken by another clipboard manager");
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Loads the targets of the clipboard which the exiting owner lists in
+    // `property`, or all of them if it lists none, and takes over the
+    // clipboard with them.
+    fn save_clipboard(&self, requestor: xcb::Window, property: Atom) -> Result<(), ClipboardError> {
+        let mut targets = Vec::new();
+        if property != xcb::ATOM_NONE {
+            if let Ok(reply) = xcb::get_property(
+                &self.context.connection,
+                false,
+                requestor,
+                property,
+                xcb::ATOM_ATOM,
+                0,
+                1024,
+            )
+            .get_reply()
+            {
+                targets.extend_from_slice(reply.value::<Atom>());
+            }
+        }
+
+        let clipboard = connect_clipboard(None)?;
+        let (selection, property) =
+            (clipboard.getter.atoms.clipboard, clipboard.getter.atoms.property);
+        if targets.is_empty() {
+            let data = clipboard
+                .load(selection, clipboard.getter.atoms.targets, property, LOAD_TIMEOUT)
+                .context(error::LoadX11Clipboard)?;
+            targets = data
+                .chunks_exact(4)
+                .map(|atom| Atom::from_ne_bytes([atom[0], atom[1], atom[2], atom[3]]))
+                .collect();
+        }
+
+        let mut values = HashMap::new();
+        for target in targets {
+            if self.meta_targets.contains(&target) {
+                continue;
+            }
+            // the owner may fail to convert some of its targets, the others
+            // are still saved
+            match clipboard.load(selection, target, property, CONTENT_LOAD_TIMEOUT) {
+                Ok(data) if !data.is_empty() => {
+                    values.insert(target, data);
+                }
+                _ => {}
+            }
+        }
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        let owner = SelectionOwner::with_values(values, ClipboardType::Clipboard, None)?;
+        owner.acquire()?;
+        thread::spawn(move || owner.serve());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::x11::{
+        incr_chunk_size, parse_window_title, parse_wm_class, IncrTransfer, INCR_CHUNK_SIZE,
+    };
+
+    fn chunks(len: usize, chunk_size: usize) -> Vec<std::ops::Range<usize>> {
+        let mut transfer = IncrTransfer::new(0, len, chunk_size);
+        std::iter::from_fn(|| transfer.next_chunk()).collect()
+    }
+
+    #[test]
+    fn test_incr_chunks() {
+        assert_eq!(chunks(8, 4), vec![0..4, 4..8, 8..8]);
+        assert_eq!(chunks(9, 4), vec![0..4, 4..8, 8..9, 9..9]);
+        assert_eq!(chunks(3, 4), vec![0..3, 3..3]);
+        assert_eq!(chunks(0, 4), vec![0..0]);
+
+        let len = INCR_CHUNK_SIZE * 3 + 1;
+        let chunks = chunks(len, INCR_CHUNK_SIZE);
+        assert_eq!(chunks.len(), 5);
+        assert_eq!(chunks.iter().map(|chunk| chunk.len()).sum::<usize>(), len);
+        assert_eq!(chunks[3], INCR_CHUNK_SIZE * 3..len);
+    }
+
+    #[test]
+    fn test_incr_chunk_size() {
+        // 4 MiB with BIG-REQUESTS
+        assert_eq!(incr_chunk_size(4 * 1024 * 1024), INCR_CHUNK_SIZE);
+        // the core protocol allows 256 KiB requests
+        assert_eq!(incr_chunk_size(256 * 1024), 256 * 1024 - 24);
+        assert_eq!(incr_chunk_size(INCR_CHUNK_SIZE + 24), INCR_CHUNK_SIZE);
+        assert_eq!(incr_chunk_size(0), 1);
+    }
+
+    #[test]
+    fn test_owner_application() {
+        assert_eq!(parse_wm_class(b"navigator\0Firefox\0"), Some("Firefox".to_owned()));
+        assert_eq!(parse_wm_class(b"xterm\0"), Some("xterm".to_owned()));
+        assert_eq!(parse_wm_class(b""), None);
+        assert_eq!(
+            parse_window_title(b" Mail - Thunderbird\0"),
+            Some("Mail - Thunderbird".to_owned())
+        );
+        assert_eq!(parse_window_title(b"\0"), None);
+    }
+}
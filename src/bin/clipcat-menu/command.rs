@@ -1,15 +1,23 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf, time::SystemTime};
 
 use snafu::ResultExt;
 use structopt::StructOpt;
 use tokio::runtime::Runtime;
 
-use clipcat::{editor::ExternalEditor, grpc::GrpcClient, ClipboardData, ClipboardType};
+use clipcat::{
+    action::Action,
+    editor::ExternalEditor,
+    export::Export,
+    grpc::{ConnectOptions, GrpcClient, ListOptions},
+    language::Language,
+    template::EntryTemplate,
+    ClipboardData, ClipboardType, SortOrder,
+};
 
 use crate::{
     config::Config,
     error::{self, Error},
-    finder::{FinderRunner, FinderType},
+    finder::{FinderRunner, FinderType, Selection},
 };
 
 const LINE_LENGTH: usize = 100;
@@ -31,6 +39,48 @@ pub struct Command {
 
     #[structopt(long, short = "l", help = "Specifies the length of a line showing on finder")]
     line_length: Option<usize>,
+
+    #[structopt(long, short = "n", help = "Specifies the maximum number of clips, 0 shows all")]
+    max_clips: Option<usize>,
+
+    #[structopt(long, help = "Pastes the inserted clip into the focused window")]
+    paste: bool,
+
+    #[structopt(
+        long = "tag",
+        short = "t",
+        help = "Only shows clips which carry this tag, can be repeated"
+    )]
+    tags: Vec<String>,
+
+    #[structopt(
+        long = "language",
+        help = "Only shows clips in this language: rust, python, json or shell"
+    )]
+    language: Option<Language>,
+
+    #[structopt(long = "app", help = "Only shows clips copied from this application, e.g. slack")]
+    application: Option<String>,
+
+    #[structopt(
+        long = "by-app",
+        conflicts_with = "application",
+        help = "Asks for an application first, then only shows clips copied from it"
+    )]
+    by_application: bool,
+
+    #[structopt(
+        long = "sort",
+        help = "Shows clips by time, created, size or use-count instead of the ranking, e.g. \
+                use-count for the most used clips first"
+    )]
+    order: Option<SortOrder>,
+
+    #[structopt(
+        long = "entry-format",
+        help = "Specifies how clips are shown, e.g. \"{time} {app} {preview}\""
+    )]
+    entry_format: Option<EntryTemplate>,
 }
 
 #[derive(Debug, Clone, StructOpt)]
@@ -48,15 +98,100 @@ pub enum SubCommand {
     ListFinder,
 
     #[structopt(about = "Insert selected clip into clipboard")]
-    Insert,
+    Insert {
+        #[structopt(
+            long = "one-shot",
+            help = "Clears clipboard and drops the clip after one paste"
+        )]
+        one_shot: bool,
+    },
 
     #[structopt(about = "Insert selected clip into primary clipboard")]
-    InsertPrimary,
+    InsertPrimary {
+        #[structopt(
+            long = "one-shot",
+            help = "Clears primary clipboard and drops the clip after one paste"
+        )]
+        one_shot: bool,
+    },
 
     #[structopt(
         aliases = &["rm", "delete", "del"],
         about = "Removes selected clip")]
-    Remove,
+    Remove {
+        #[structopt(
+            long = "matching",
+            short = "m",
+            help = "Removes all clips which match the text typed into the finder"
+        )]
+        matching: bool,
+    },
+
+    #[structopt(about = "Join selected text clips into a new clip and insert it into clipboard")]
+    Merge {
+        #[structopt(
+            long = "separator",
+            short = "s",
+            default_value = "\\n",
+            help = "Separates the clips, \\n and \\t are replaced with newline and tab"
+        )]
+        separator: String,
+    },
+
+    #[structopt(about = "Export selected clips as JSON, sensitive clips are left out")]
+    Export {
+        #[structopt(long = "output", short = "o", help = "Writes to this file")]
+        file_path: PathBuf,
+    },
+
+    #[structopt(about = "Pretty-print JSON or XML in selected clip")]
+    Format {
+        #[structopt(
+            long = "new",
+            help = "Inserts the formatted text as a new clip instead of updating the clip"
+        )]
+        new: bool,
+    },
+
+    #[structopt(aliases = &["qr"], about = "Show selected clip as QR code")]
+    Qrcode {
+        #[structopt(
+            long = "viewer",
+            default_value = "xdg-open",
+            help = "Specifies a program which shows the PNG image of the QR code"
+        )]
+        viewer: String,
+    },
+
+    #[structopt(about = "Run action from the configuration on selected clip, asks which one if \
+                         <name> is not given")]
+    Action { name: Option<String> },
+
+    #[structopt(about = "Type selected clip as keystrokes into the focused window, for fields \
+                         which block paste")]
+    Type,
+
+    #[structopt(about = "Open URL in selected clip in the browser")]
+    Open {
+        #[structopt(
+            long = "browser",
+            default_value = "xdg-open",
+            help = "Specifies a program which opens URLs"
+        )]
+        browser: String,
+    },
+
+    #[cfg(feature = "ocr")]
+    #[structopt(about = "Extract text from selected image clip and insert it as a new clip")]
+    Ocr {
+        #[structopt(
+            long = "language",
+            short = "l",
+            default_value = clipcat::ocr::DEFAULT_LANGUAGE,
+            help = "Specifies the languages of the text, e.g. eng+deu"
+        )]
+        language: String,
+    },
 
     #[structopt(about = "Edit selected clip")]
     Edit {
@@ -67,6 +202,12 @@ pub enum SubCommand {
             help = "Specifies a external editor"
         )]
         editor: String,
+
+        #[structopt(
+            long = "new",
+            help = "Inserts the edited text as a new clip instead of updating the clip"
+        )]
+        new: bool,
     },
 }
 
@@ -98,7 +239,7 @@ impl Command {
             }
             Some(SubCommand::ListFinder) => {
                 for ty in FinderType::available_types() {
-                    println!("{}", ty.to_string());
+                    println!("{}", ty);
                 }
                 return Ok(());
             }
@@ -112,47 +253,143 @@ impl Command {
             let level_filter = tracing_subscriber::filter::LevelFilter::INFO;
 
             let registry = tracing_subscriber::registry().with(level_filter).with(fmt_layer);
+            #[cfg(unix)]
             match tracing_journald::layer() {
                 Ok(layer) => registry.with(layer).init(),
                 Err(_err) => {
                     registry.init();
                 }
             }
+            #[cfg(not(unix))]
+            registry.init();
         }
 
         let mut config =
-            Config::load_or_default(&self.config_file.unwrap_or_else(Config::default_path));
-
-        let finder = {
-            if let Some(finder) = self.finder {
-                config.finder = finder;
-            }
+            Config::load_or_default(self.config_file.unwrap_or_else(Config::default_path));
 
+        if let Some(finder) = self.finder {
+            config.finder = finder;
+        }
+        let (line_length, menu_length) = (self.line_length, self.menu_length);
+        let new_finder = || -> Result<FinderRunner, Error> {
             let mut finder = FinderRunner::from_config(&config)?;
-            if let Some(line_length) = self.line_length {
+            if let Some(line_length) = line_length {
                 finder.set_line_length(line_length);
             }
 
-            if let Some(menu_length) = self.menu_length {
+            if let Some(menu_length) = menu_length {
                 finder.set_menu_length(menu_length);
             }
-            finder
+            Ok(finder)
+        };
+        let finder = new_finder()?;
+        // a second finder asks which URL to open or which action to run
+        let second_finder = match self.subcommand {
+            Some(SubCommand::Open { .. }) | Some(SubCommand::Action { name: None }) => {
+                Some(new_finder()?)
+            }
+            _ => None,
+        };
+        // and another one asks for the application with `--by-app`
+        let application_finder = if self.by_application { Some(new_finder()?) } else { None };
+
+        // the finder only shows previews, full clips are fetched when they are needed
+        let mut tags = self.tags;
+        tags.extend(self.language.map(|language| language.tag().to_owned()));
+        let list_opts = ListOptions {
+            tags,
+            offset: 0,
+            limit: self.max_clips.unwrap_or(config.max_clips),
+            application: self.application,
+            order: self.order,
+            preview_length: Some(finder.line_length().unwrap_or(0)),
         };
 
+        let entry_format = match (self.entry_format, config.entry_format.as_deref()) {
+            (Some(template), _) => Some(template),
+            (None, Some(template)) => Some(
+                template
+                    .parse::<EntryTemplate>()
+                    .map_err(|message| Error::ParseEntryFormat { message })?,
+            ),
+            (None, None) => None,
+        };
+        let paste = self.paste || config.auto_paste;
         let subcommand = self.subcommand;
         let fut = async move {
-            let grpc_addr = format!("http://{}:{}", config.server_host, config.server_port);
-            let mut client = GrpcClient::new(grpc_addr).await?;
-            let clips = client.list().await?;
+            let mut client = match config.server_socket {
+                #[cfg(unix)]
+                Some(socket_path) => {
+                    GrpcClient::connect_unix(socket_path, config.server_token.as_deref()).await?
+                }
+                _ => {
+                    let scheme = if config.tls.is_some() { "https" } else { "http" };
+                    let grpc_addr =
+                        format!("{}://{}:{}", scheme, config.server_host, config.server_port);
+                    let opts = ConnectOptions { token: config.server_token, tls: config.tls };
+                    GrpcClient::connect(grpc_addr, opts).await?
+                }
+            };
+            let (mut clips, _total) = client.list_page(&list_opts).await?;
+            if let Some(application_finder) = application_finder {
+                let application = match select_application(application_finder, &clips).await? {
+                    Some(application) => application,
+                    None => {
+                        tracing::info!("Nothing is selected");
+                        return Ok(());
+                    }
+                };
+                clips.retain(|clip| clip.is_from(&application));
+            }
+            // pinned clips are listed first, the sort is stable so they stay in order
+            clips.sort_by_key(|clip| !clip.pinned);
+            let mut finder = finder;
+            if finder.shows_thumbnails() {
+                finder.set_thumbnails(save_thumbnails(&mut client, &clips).await?);
+            }
+            if let Some(template) = entry_format {
+                let (now, line_length) = (SystemTime::now(), finder.line_length());
+                for (index, clip) in clips.iter_mut().enumerate() {
+                    clip.data = template.render(index, clip, now, line_length);
+                }
+            }
 
             match subcommand {
-                Some(SubCommand::Insert) | None => {
-                    insert_clip(&clips, finder, client, ClipboardType::Clipboard).await?
+                Some(SubCommand::Insert { one_shot }) => {
+                    insert_clip(&clips, finder, client, ClipboardType::Clipboard, one_shot, paste)
+                        .await?
+                }
+                None => {
+                    insert_clip(&clips, finder, client, ClipboardType::Clipboard, false, paste)
+                        .await?
+                }
+                Some(SubCommand::InsertPrimary { one_shot }) => {
+                    insert_clip(&clips, finder, client, ClipboardType::Primary, one_shot, paste)
+                        .await?
                 }
-                Some(SubCommand::InsertPrimary) => {
-                    insert_clip(&clips, finder, client, ClipboardType::Primary).await?
+                Some(SubCommand::Remove { matching: true }) => {
+                    let query = match finder.query(&clips).await? {
+                        Some(query) => query,
+                        None => {
+                            tracing::warn!(
+                                "Finder {} can not remove clips matching a filter",
+                                config.finder.to_string()
+                            );
+                            return Ok(());
+                        }
+                    };
+                    let indices = finder.matching_clips(&clips, &query);
+                    if indices.is_empty() {
+                        tracing::info!("Nothing matches {:?}", query);
+                        return Ok(());
+                    }
+                    let ids: Vec<_> = indices.into_iter().map(|index| clips[index].id).collect();
+                    let removed_ids = client.batch_remove(&ids).await?;
+                    for id in removed_ids {
+                        tracing::info!("Removing clip (id: {:016x})", id);
+                    }
                 }
-                Some(SubCommand::Remove) => {
+                Some(SubCommand::Remove { matching: false }) => {
                     let selections = finder.multiple_select(&clips).await?;
                     let ids: Vec<_> = selections.into_iter().map(|(_, clip)| clip.id).collect();
                     let removed_ids = client.batch_remove(&ids).await?;
@@ -160,17 +397,281 @@ impl Command {
                         tracing::info!("Removing clip (id: {:016x})", id);
                     }
                 }
-                Some(SubCommand::Edit { editor }) => {
+                Some(SubCommand::Merge { separator }) => {
+                    let selections = finder.multiple_select(&clips).await?;
+                    if selections.is_empty() {
+                        tracing::info!("Nothing is selected");
+                        return Ok(());
+                    }
+                    let mut parts = Vec::with_capacity(selections.len());
+                    for (_index, preview) in selections {
+                        let clip = client.get_clip(preview.id).await?;
+                        if clip.is_text() {
+                            parts.push(clip.data);
+                        } else {
+                            tracing::warn!(
+                                "Clip (id: {:016x}, type: {}) is not text, it is skipped",
+                                clip.id,
+                                clip.mime
+                            );
+                        }
+                    }
+                    let separator = separator.replace("\\n", "\n").replace("\\t", "\t");
+                    let new_id = client.insert_clipboard(&parts.join(&separator)).await?;
+                    tracing::info!("Merging {} clips into clip (id: {:016x})", parts.len(), new_id);
+                }
+                Some(SubCommand::Export { file_path }) => {
+                    let selections = finder.multiple_select(&clips).await?;
+                    if selections.is_empty() {
+                        tracing::info!("Nothing is selected");
+                        return Ok(());
+                    }
+                    let mut exported = Vec::with_capacity(selections.len());
+                    for (_index, preview) in selections {
+                        let clip = client.get_clip(preview.id).await?;
+                        if !clip.sensitive {
+                            exported.push(clip);
+                        }
+                    }
+                    let data = serde_json::to_vec_pretty(&Export::new(&exported))
+                        .context(error::SerializeExport)?;
+                    std::fs::write(&file_path, data)
+                        .context(error::WriteFile { filename: file_path.to_owned() })?;
+                    tracing::info!("Exported {} clips to {}", exported.len(), file_path.display());
+                }
+                Some(SubCommand::Edit { editor, new }) => {
                     let selection = finder.single_select(&clips).await?;
-                    if let Some((_index, clip)) = selection {
+                    if let Some((_index, preview)) = selection {
+                        let clip = client.get_clip(preview.id).await?;
+                        if !clip.is_text() {
+                            tracing::warn!(
+                                "Clip (id: {:016x}, type: {}) is not editable",
+                                clip.id,
+                                clip.mime
+                            );
+                            return Ok(());
+                        }
                         let editor = ExternalEditor::new(editor);
                         let new_data =
                             editor.execute(&clip.data).await.context(error::CallEditor)?;
-                        let (ok, new_id) = client.update(clip.id, &new_data).await?;
-                        if ok {
-                            tracing::info!("Editing clip (id: {:016x})", new_id);
+                        if new_data == clip.data {
+                            tracing::info!("Clip (id: {:016x}) is unchanged", clip.id);
+                        } else if new {
+                            let new_id = client.insert_clipboard(&new_data).await?;
+                            tracing::info!("Inserting edited clip (id: {:016x})", new_id);
+                        } else {
+                            let (ok, new_id) = client.update(clip.id, &new_data).await?;
+                            if ok {
+                                tracing::info!("Editing clip (id: {:016x})", new_id);
+                            }
+                            client.mark_as_clipboard(new_id).await?;
+                        }
+                    } else {
+                        tracing::info!("Nothing is selected");
+                        return Ok(());
+                    }
+                }
+                Some(SubCommand::Format { new }) => {
+                    let selection = finder.single_select(&clips).await?;
+                    if let Some((_index, preview)) = selection {
+                        let clip = client.get_clip(preview.id).await?;
+                        let formatted = if clip.is_text() {
+                            clipcat::format::pretty_print(&clip.data)
+                        } else {
+                            None
+                        };
+                        let (format, data) = match formatted {
+                            Some(formatted) => formatted,
+                            None => {
+                                tracing::warn!(
+                                    "Clip (id: {:016x}) is neither JSON nor XML",
+                                    clip.id
+                                );
+                                return Ok(());
+                            }
+                        };
+                        let new_id = if new {
+                            client.insert_clipboard(&data).await?
+                        } else {
+                            let (_ok, new_id) = client.update(clip.id, &data).await?;
+                            client.mark_as_clipboard(new_id).await?;
+                            new_id
+                        };
+                        tracing::info!("Formatting {} of clip (id: {:016x})", format, new_id);
+                    } else {
+                        tracing::info!("Nothing is selected");
+                        return Ok(());
+                    }
+                }
+                Some(SubCommand::Qrcode { viewer }) => {
+                    let selection = finder.single_select(&clips).await?;
+                    if let Some((_index, preview)) = selection {
+                        let clip = client.get_clip(preview.id).await?;
+                        if !clip.is_text() {
+                            tracing::warn!(
+                                "Clip (id: {:016x}, type: {}) can not be rendered as QR code",
+                                clip.id,
+                                clip.mime
+                            );
+                            return Ok(());
+                        }
+                        let filename = std::env::temp_dir()
+                            .join(format!("clipcat-qrcode-{:016x}.png", clip.id));
+                        clipcat::qr::save_png(&clip.data, &filename)
+                            .context(error::RenderQrCode)?;
+                        tracing::info!(
+                            "Showing {} of clip (id: {:016x}) as QR code",
+                            clipcat::qr::Payload::detect(&clip.data),
+                            clip.id
+                        );
+                        tokio::process::Command::new(&viewer)
+                            .arg(&filename)
+                            .status()
+                            .await
+                            .context(error::CallImageViewer { program: viewer.clone() })?;
+                    } else {
+                        tracing::info!("Nothing is selected");
+                        return Ok(());
+                    }
+                }
+                Some(SubCommand::Action { name }) => {
+                    let selection = finder.single_select(&clips).await?;
+                    let preview = match selection {
+                        Some((_index, preview)) => preview,
+                        None => {
+                            tracing::info!("Nothing is selected");
+                            return Ok(());
                         }
-                        client.mark_as_clipboard(new_id).await?;
+                    };
+                    let action = match name {
+                        Some(name) => match Action::find(&config.actions, &name) {
+                            Some(action) => action.clone(),
+                            None => {
+                                tracing::warn!("Action {} is not configured", name);
+                                return Ok(());
+                            }
+                        },
+                        None if config.actions.is_empty() => {
+                            tracing::warn!("No actions are configured");
+                            return Ok(());
+                        }
+                        None => {
+                            let entries: Vec<_> = config
+                                .actions
+                                .iter()
+                                .map(|action| ClipboardData::new_clipboard(&action.name))
+                                .collect();
+                            let finder =
+                                second_finder.expect("second finder is created for action");
+                            match finder.single_select(&entries).await? {
+                                Some((index, _entry)) => config.actions[index].clone(),
+                                None => {
+                                    tracing::info!("Nothing is selected");
+                                    return Ok(());
+                                }
+                            }
+                        }
+                    };
+
+                    let clip = client.get_clip(preview.id).await?;
+                    if !clip.is_text() {
+                        tracing::warn!(
+                            "Clip (id: {:016x}, type: {}) is not text",
+                            clip.id,
+                            clip.mime
+                        );
+                        return Ok(());
+                    }
+                    tracing::info!("Running action {} on clip (id: {:016x})", action.name, clip.id);
+                    let output = action.run(&clip.data).await.context(error::RunAction)?;
+                    if action.insert_output {
+                        let new_id = client.insert_clipboard(output.trim_end()).await?;
+                        tracing::info!("Inserting output of action (id: {:016x})", new_id);
+                    } else {
+                        print!("{}", output);
+                    }
+                }
+                Some(SubCommand::Type) => {
+                    let selection = finder.single_select(&clips).await?;
+                    if let Some((_index, preview)) = selection {
+                        let clip = client.get_clip(preview.id).await?;
+                        if !clip.is_text() {
+                            tracing::warn!(
+                                "Clip (id: {:016x}, type: {}) can not be typed",
+                                clip.id,
+                                clip.mime
+                            );
+                            return Ok(());
+                        }
+                        tracing::info!("Typing clip (id: {:016x})", clip.id);
+                        clipcat::typing::type_text(&clip.data, &config.typing)
+                            .await
+                            .context(error::TypeClip)?;
+                    } else {
+                        tracing::info!("Nothing is selected");
+                        return Ok(());
+                    }
+                }
+                Some(SubCommand::Open { browser }) => {
+                    let selection = finder.single_select(&clips).await?;
+                    let preview = match selection {
+                        Some((_index, preview)) => preview,
+                        None => {
+                            tracing::info!("Nothing is selected");
+                            return Ok(());
+                        }
+                    };
+                    let clip = client.get_clip(preview.id).await?;
+                    let urls =
+                        if clip.is_text() { clipcat::urls::find(&clip.data) } else { Vec::new() };
+                    let url = match urls.as_slice() {
+                        [] => {
+                            tracing::warn!("Clip (id: {:016x}) contains no URL", clip.id);
+                            return Ok(());
+                        }
+                        [url] => url.to_string(),
+                        urls => {
+                            let entries: Vec<_> =
+                                urls.iter().map(|url| ClipboardData::new_clipboard(url)).collect();
+                            let finder = second_finder.expect("second finder is created for open");
+                            match finder.single_select(&entries).await? {
+                                Some((_index, entry)) => entry.data,
+                                None => {
+                                    tracing::info!("Nothing is selected");
+                                    return Ok(());
+                                }
+                            }
+                        }
+                    };
+                    tracing::info!("Opening {} of clip (id: {:016x})", url, clip.id);
+                    tokio::process::Command::new(&browser)
+                        .arg(&url)
+                        .status()
+                        .await
+                        .context(error::CallBrowser { program: browser.clone() })?;
+                }
+                #[cfg(feature = "ocr")]
+                Some(SubCommand::Ocr { language }) => {
+                    let selection = finder.single_select(&clips).await?;
+                    if let Some((_index, preview)) = selection {
+                        let clip = client.get_clip(preview.id).await?;
+                        if !clip.is_image() {
+                            tracing::warn!(
+                                "Clip (id: {:016x}, type: {}) is not an image",
+                                clip.id,
+                                clip.mime
+                            );
+                            return Ok(());
+                        }
+                        let text = clipcat::ocr::recognize(clip.binary, &language)
+                            .await
+                            .context(error::RecognizeText)?;
+                        let new_id = client.insert_clipboard(&text).await?;
+                        tracing::info!(
+                            "Extracting text of clip (id: {:016x}) into clip (id: {:016x})",
+                            preview.id,
+                            new_id
+                        );
                     } else {
                         tracing::info!("Nothing is selected");
                         return Ok(());
@@ -187,30 +688,133 @@ impl Command {
     }
 }
 
+// Lists the applications which clips were copied from, the one with the most
+// clips first, and returns the selected one.
+async fn select_application(
+    finder: FinderRunner,
+    clips: &[ClipboardData],
+) -> Result<Option<String>, Error> {
+    // names are compared case-insensitively, the first spelling is shown
+    let mut counts = HashMap::<String, (String, usize)>::new();
+    for application in clips.iter().filter_map(|clip| clip.application.as_ref()) {
+        counts.entry(application.to_lowercase()).or_insert_with(|| (application.clone(), 0)).1 += 1;
+    }
+    if counts.is_empty() {
+        tracing::warn!("No clips with a known source application");
+        return Ok(None);
+    }
+
+    let mut applications: Vec<_> = counts.into_values().collect();
+    applications.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    let entries: Vec<_> = applications
+        .iter()
+        .map(|(application, count)| {
+            ClipboardData::new_clipboard(&format!("{} ({} clips)", application, count))
+        })
+        .collect();
+    let selection = finder.single_select(&entries).await?;
+    Ok(selection.map(|(index, _entry)| applications.swap_remove(index).0))
+}
+
+// Image clips are only listed as previews, they are fetched and saved so the
+// finder can show them. A thumbnail which can not be saved is left out.
+async fn save_thumbnails(
+    client: &mut GrpcClient,
+    clips: &[ClipboardData],
+) -> Result<HashMap<u64, PathBuf>, Error> {
+    let dir = std::env::temp_dir().join("clipcat-menu-thumbnails");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        tracing::warn!("Could not create directory {}, error: {}", dir.display(), err);
+        return Ok(HashMap::new());
+    }
+
+    let mut thumbnails = HashMap::new();
+    for preview in clips.iter().filter(|clip| clip.kind() == "image") {
+        let path = dir.join(format!("{:016x}", preview.id));
+        if !path.exists() {
+            let clip = client.get_clip(preview.id).await?;
+            if !clip.is_image() {
+                continue;
+            }
+            if let Err(err) = std::fs::write(&path, &clip.binary) {
+                tracing::warn!("Could not save thumbnail {}, error: {}", path.display(), err);
+                continue;
+            }
+        }
+        thumbnails.insert(preview.id, path);
+    }
+    Ok(thumbnails)
+}
+
 async fn insert_clip(
     clips: &[ClipboardData],
     finder: FinderRunner,
     mut client: GrpcClient,
     clipboard_type: ClipboardType,
+    one_shot: bool,
+    paste: bool,
 ) -> Result<(), Error> {
-    let selection = finder.single_select(&clips).await?;
-    if let Some((index, clip)) = selection {
-        tracing::info!(
-            "Inserting clip (index: {}, id: {:016x}, content: {:?})",
-            index,
-            clip.id,
-            clip.printable_data(Some(LINE_LENGTH)),
-        );
-        match clipboard_type {
-            ClipboardType::Clipboard => {
-                client.mark_as_clipboard(clip.id).await?;
-            }
-            ClipboardType::Primary => {
-                client.mark_as_primary(clip.id).await?;
+    // snippets are listed before clips, their names are shown in front of them
+    let snippets = client.list_snippets().await?;
+    let entries: Vec<_> = snippets
+        .iter()
+        .map(|snippet| {
+            ClipboardData::new_clipboard(&format!("[{}] {}", snippet.name, snippet.data))
+        })
+        .chain(clips.iter().cloned())
+        .collect();
+
+    let (index, clip) = match finder.select_or_remove(&entries).await? {
+        Some(Selection::Insert(index, clip)) => (index, clip),
+        Some(Selection::Remove(index, clip)) => {
+            if let Some(snippet) = snippets.get(index) {
+                tracing::warn!(
+                    "Snippet {} is not removed, it is defined in clipcatd",
+                    snippet.name
+                );
+            } else if client.remove(clip.id).await? {
+                tracing::info!("Removing clip (id: {:016x})", clip.id);
             }
+            return Ok(());
+        }
+        None => {
+            tracing::info!("Nothing is selected");
+            return Ok(());
+        }
+    };
+
+    if let Some(snippet) = snippets.get(index) {
+        tracing::info!("Inserting snippet (name: {})", snippet.name);
+        let id = client.insert_snippet(&snippet.name, clipboard_type).await?;
+        if let (Some(id), true) = (id, paste) {
+            client.paste(id, clipboard_type, false).await?;
+        }
+        return Ok(());
+    }
+
+    tracing::info!(
+        "Inserting clip (index: {}, id: {:016x}, content: {:?})",
+        index,
+        clip.id,
+        clip.printable_data(Some(LINE_LENGTH)),
+    );
+    if paste {
+        client.paste(clip.id, clipboard_type, one_shot).await?;
+        return Ok(());
+    }
+    match (clipboard_type, one_shot) {
+        (ClipboardType::Clipboard, false) => {
+            client.mark_as_clipboard(clip.id).await?;
+        }
+        (ClipboardType::Clipboard, true) => {
+            client.mark_as_clipboard_once(clip.id).await?;
+        }
+        (ClipboardType::Primary, false) => {
+            client.mark_as_primary(clip.id).await?;
+        }
+        (ClipboardType::Primary, true) => {
+            client.mark_as_primary_once(clip.id).await?;
         }
-    } else {
-        tracing::info!("Nothing is selected");
     }
 
     Ok(())
@@ -11,16 +11,52 @@ use crate::finder::FinderType;
 pub struct Config {
     pub server_host: IpAddr,
     pub server_port: u16,
+    #[serde(default)]
+    pub server_socket: Option<PathBuf>,
+    #[serde(default)]
+    pub server_token: Option<String>,
     pub finder: FinderType,
+    #[serde(default)]
+    pub max_clips: usize,
+    /// How clips are shown, e.g. `{time} {app} {preview}`.
+    #[serde(default)]
+    pub entry_format: Option<String>,
+    /// Lets clipcatd paste inserted clips into the focused window.
+    #[serde(default)]
+    pub auto_paste: bool,
     pub rofi: Option<Rofi>,
     pub dmenu: Option<Dmenu>,
+    #[serde(default)]
+    pub fzf: Option<Fzf>,
+    #[serde(default)]
+    pub wofi: Option<Wofi>,
+    #[serde(default)]
+    pub bemenu: Option<Bemenu>,
+    #[serde(default)]
+    pub fuzzel: Option<Fuzzel>,
+    #[serde(default)]
+    pub tofi: Option<Tofi>,
     pub custom_finder: Option<CustomFinder>,
+    #[serde(default)]
+    pub tls: Option<clipcat::grpc::ClientTlsOptions>,
+    #[serde(default)]
+    pub typing: clipcat::typing::TypingOptions,
+    #[serde(default)]
+    pub actions: Vec<clipcat::action::Action>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Rofi {
     pub line_length: usize,
     pub menu_length: usize,
+
+    /// Key which removes the chosen clip instead of inserting it.
+    #[serde(default = "Rofi::default_remove_key")]
+    pub remove_key: String,
+
+    /// Shows an icon of the type of clips, and thumbnails of image clips.
+    #[serde(default)]
+    pub show_icons: bool,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -29,10 +65,47 @@ pub struct Dmenu {
     pub menu_length: usize,
 }
 
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Fzf {
+    /// Prints the clip which is hovered in fzf, `{1}` is replaced by its ID.
+    /// An empty command leaves out the preview.
+    #[serde(default = "Fzf::default_preview_command")]
+    pub preview_command: String,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Wofi {
+    pub line_length: usize,
+    pub menu_length: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Bemenu {
+    pub line_length: usize,
+    pub menu_length: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Fuzzel {
+    pub line_length: usize,
+    pub menu_length: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Tofi {
+    pub line_length: usize,
+    pub menu_length: usize,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct CustomFinder {
     pub program: String,
     pub args: Vec<String>,
+
+    /// Appended to `args` when several clips can be selected, e.g. `["-m"]`
+    /// for fzf.
+    #[serde(default)]
+    pub multi_select_args: Vec<String>,
 }
 
 impl Default for Config {
@@ -40,24 +113,82 @@ impl Default for Config {
         Config {
             server_host: clipcat::DEFAULT_GRPC_HOST.parse().expect("Parse default gRPC host"),
             server_port: clipcat::DEFAULT_GRPC_PORT,
+            server_socket: None,
+            server_token: None,
             finder: FinderType::Rofi,
+            max_clips: 0,
+            entry_format: None,
+            auto_paste: false,
             rofi: Some(Rofi::default()),
             dmenu: Some(Dmenu::default()),
+            fzf: Some(Fzf::default()),
+            wofi: Some(Wofi::default()),
+            bemenu: Some(Bemenu::default()),
+            fuzzel: Some(Fuzzel::default()),
+            tofi: Some(Tofi::default()),
             custom_finder: Some(CustomFinder::default()),
+            tls: None,
+            typing: clipcat::typing::TypingOptions::default(),
+            actions: Vec::new(),
         }
     }
 }
 
+impl Rofi {
+    #[inline]
+    pub fn default_remove_key() -> String { "Alt+d".to_owned() }
+}
+
 impl Default for Rofi {
-    fn default() -> Rofi { Rofi { line_length: 100, menu_length: 30 } }
+    fn default() -> Rofi {
+        Rofi {
+            line_length: 100,
+            menu_length: 30,
+            remove_key: Rofi::default_remove_key(),
+            show_icons: false,
+        }
+    }
 }
 
 impl Default for Dmenu {
     fn default() -> Dmenu { Dmenu { line_length: 100, menu_length: 30 } }
 }
 
+impl Fzf {
+    #[inline]
+    pub fn default_preview_command() -> String {
+        format!("{} preview --highlight {{1}}", clipcat::CTL_PROGRAM_NAME)
+    }
+}
+
+impl Default for Fzf {
+    fn default() -> Fzf { Fzf { preview_command: Fzf::default_preview_command() } }
+}
+
+impl Default for Wofi {
+    fn default() -> Wofi { Wofi { line_length: 100, menu_length: 30 } }
+}
+
+impl Default for Bemenu {
+    fn default() -> Bemenu { Bemenu { line_length: 100, menu_length: 30 } }
+}
+
+impl Default for Fuzzel {
+    fn default() -> Fuzzel { Fuzzel { line_length: 100, menu_length: 30 } }
+}
+
+impl Default for Tofi {
+    fn default() -> Tofi { Tofi { line_length: 100, menu_length: 30 } }
+}
+
 impl Default for CustomFinder {
-    fn default() -> CustomFinder { CustomFinder { program: "fzf".to_string(), args: vec![] } }
+    fn default() -> CustomFinder {
+        CustomFinder {
+            program: "fzf".to_string(),
+            args: vec![],
+            multi_select_args: vec!["--multi".to_string()],
+        }
+    }
 }
 
 impl Config {
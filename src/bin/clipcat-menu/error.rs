@@ -1,6 +1,6 @@
 use snafu::Snafu;
 
-use clipcat::{editor::EditorError, grpc::GrpcClientError};
+use clipcat::{action::ActionError, editor::EditorError, grpc::GrpcClientError, qr::QrCodeError};
 
 use crate::finder::FinderError;
 
@@ -28,6 +28,55 @@ pub enum Error {
     CallEditor {
         source: EditorError,
     },
+
+    #[snafu(display("Could not render QR code, error: {}", source))]
+    RenderQrCode {
+        source: QrCodeError,
+    },
+
+    #[cfg(feature = "ocr")]
+    #[snafu(display("Could not extract text from image, error: {}", source))]
+    RecognizeText {
+        source: clipcat::ocr::OcrError,
+    },
+
+    #[snafu(display("Could not run action, error: {}", source))]
+    RunAction {
+        source: ActionError,
+    },
+
+    #[snafu(display("Could not type clip, error: {}", source))]
+    TypeClip {
+        source: ActionError,
+    },
+
+    #[snafu(display("Could not call browser: {}, error: {}", program, source))]
+    CallBrowser {
+        program: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Could not call image viewer: {}, error: {}", program, source))]
+    CallImageViewer {
+        program: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Invalid entry format, {}", message))]
+    ParseEntryFormat {
+        message: String,
+    },
+
+    #[snafu(display("Could not serialize export, error: {}", source))]
+    SerializeExport {
+        source: serde_json::Error,
+    },
+
+    #[snafu(display("Could not write file {}, error: {}", filename.display(), source))]
+    WriteFile {
+        filename: std::path::PathBuf,
+        source: std::io::Error,
+    },
 }
 
 impl From<std::io::Error> for Error {
@@ -26,16 +26,18 @@ impl BuiltinFinder {
                 SelectionMode::Single => false,
                 SelectionMode::Multiple => true,
             };
+            // the item reader parses the ANSI colors of the entries
             let options =
                 SkimOptionsBuilder::default().height(Some("100%")).multi(multi).build().unwrap();
 
-            let item_reader = SkimItemReader::default();
+            let item_reader =
+                SkimItemReader::new(SkimItemReaderOption::default().ansi(true).build());
             let items = item_reader.of_bufread(Cursor::new(input));
 
             // `run_with` would read and show items from the stream
             let selected_items = Skim::run_with(&options, Some(items))
                 .map(|out| out.selected_items)
-                .unwrap_or_else(Vec::new);
+                .unwrap_or_default();
 
             selected_items.iter().map(|item| item.text()).collect::<Vec<_>>().join(ENTRY_SEPARATOR)
         })
@@ -44,6 +46,29 @@ impl BuiltinFinder {
 
         Ok(self.parse_output(output.as_bytes()))
     }
+
+    /// Shows `clips` and returns the text which is typed into the finder.
+    pub async fn query(&self, clips: &[ClipboardData]) -> Result<String, FinderError> {
+        let input = self.generate_input(clips);
+
+        tokio::task::spawn_blocking(move || {
+            let options = SkimOptionsBuilder::default().height(Some("100%")).build().unwrap();
+
+            let item_reader =
+                SkimItemReader::new(SkimItemReaderOption::default().ansi(true).build());
+            let items = item_reader.of_bufread(Cursor::new(input));
+
+            // the query is dropped if the finder is aborted
+            Skim::run_with(&options, Some(items))
+                .filter(|out| !out.is_abort)
+                .map(|out| out.query)
+                .unwrap_or_default()
+        })
+        .await
+        .context(error::JoinTask)
+    }
 }
 
-impl FinderStream for BuiltinFinder {}
+impl FinderStream for BuiltinFinder {
+    fn supports_ansi(&self) -> bool { true }
+}
@@ -0,0 +1,45 @@
+use crate::{
+    config,
+    finder::{
+        external::{ExternalProgram, PROMPT},
+        FinderStream, SelectionMode,
+    },
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Bemenu {
+    menu_length: usize,
+    line_length: usize,
+}
+
+impl Bemenu {
+    pub fn from_config(config: &config::Bemenu) -> Bemenu {
+        let config::Bemenu { menu_length, line_length } = *config;
+
+        Bemenu { menu_length, line_length }
+    }
+}
+
+impl ExternalProgram for Bemenu {
+    fn program(&self) -> String { "bemenu".to_string() }
+
+    fn args(&self, _selection_mode: SelectionMode) -> Vec<String> {
+        vec![
+            "-i".to_owned(),
+            "-p".to_owned(),
+            PROMPT.to_owned(),
+            "-l".to_owned(),
+            self.menu_length.to_string(),
+        ]
+    }
+}
+
+impl FinderStream for Bemenu {
+    fn line_length(&self) -> Option<usize> { Some(self.line_length) }
+
+    fn menu_length(&self) -> Option<usize> { Some(self.menu_length) }
+
+    fn set_line_length(&mut self, line_length: usize) { self.line_length = line_length }
+
+    fn set_menu_length(&mut self, menu_length: usize) { self.menu_length = menu_length; }
+}
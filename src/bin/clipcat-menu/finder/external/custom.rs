@@ -7,20 +7,54 @@ use crate::{
 pub struct Custom {
     program: String,
     args: Vec<String>,
+    multi_select_args: Vec<String>,
 }
 
 impl Custom {
     #[inline]
     pub fn from_config(config: &config::CustomFinder) -> Custom {
-        let config::CustomFinder { program, args } = config;
-        Custom { program: program.clone(), args: args.clone() }
+        let config::CustomFinder { program, args, multi_select_args } = config;
+        Custom {
+            program: program.clone(),
+            args: args.clone(),
+            multi_select_args: multi_select_args.clone(),
+        }
     }
 }
 
 impl ExternalProgram for Custom {
     fn program(&self) -> String { self.program.clone() }
 
-    fn args(&self, _seletion_mode: SelectionMode) -> Vec<String> { self.args.clone() }
+    fn args(&self, selection_mode: SelectionMode) -> Vec<String> {
+        match selection_mode {
+            SelectionMode::Single => self.args.clone(),
+            SelectionMode::Multiple => {
+                self.args.iter().chain(&self.multi_select_args).cloned().collect()
+            }
+        }
+    }
 }
 
 impl FinderStream for Custom {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config,
+        finder::{external::ExternalProgram, Custom, SelectionMode},
+    };
+
+    #[test]
+    fn test_args() {
+        let custom = Custom::from_config(&config::CustomFinder {
+            program: "fzf".to_owned(),
+            args: vec!["--ansi".to_owned()],
+            multi_select_args: vec!["-m".to_owned()],
+        });
+        assert_eq!(custom.args(SelectionMode::Single), vec!["--ansi".to_owned()]);
+        assert_eq!(
+            custom.args(SelectionMode::Multiple),
+            vec!["--ansi".to_owned(), "-m".to_owned()]
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use clipcat::ClipboardData;
+
+use crate::{
+    config,
+    finder::{
+        external::{ExternalProgram, PROMPT},
+        finder_stream::ENTRY_SEPARATOR,
+        FinderStream, SelectionMode,
+    },
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Fuzzel {
+    menu_length: usize,
+    line_length: usize,
+}
+
+impl Fuzzel {
+    pub fn from_config(config: &config::Fuzzel) -> Fuzzel {
+        let config::Fuzzel { menu_length, line_length } = *config;
+
+        Fuzzel { menu_length, line_length }
+    }
+}
+
+// fuzzel matches case-insensitively and prints the index of the chosen entry
+// with `--index`, so entries are shown without indices like in rofi
+impl ExternalProgram for Fuzzel {
+    fn program(&self) -> String { "fuzzel".to_string() }
+
+    fn args(&self, _selection_mode: SelectionMode) -> Vec<String> {
+        vec![
+            "--dmenu".to_owned(),
+            "--index".to_owned(),
+            "--prompt".to_owned(),
+            format!("{} ", PROMPT),
+            "--lines".to_owned(),
+            self.menu_length.to_string(),
+        ]
+    }
+}
+
+impl FinderStream for Fuzzel {
+    fn generate_input(&self, clips: &[ClipboardData]) -> String {
+        clips
+            .iter()
+            .map(|data| data.printable_data(self.line_length()))
+            .collect::<Vec<_>>()
+            .join(ENTRY_SEPARATOR)
+    }
+
+    fn parse_output(&self, data: &[u8]) -> Vec<usize> {
+        String::from_utf8_lossy(data)
+            .trim()
+            .split(ENTRY_SEPARATOR)
+            .filter_map(|index| index.parse().ok())
+            .collect()
+    }
+
+    fn line_length(&self) -> Option<usize> { Some(self.line_length) }
+
+    fn menu_length(&self) -> Option<usize> { Some(self.menu_length) }
+
+    fn set_line_length(&mut self, line_length: usize) { self.line_length = line_length }
+
+    fn set_menu_length(&mut self, menu_length: usize) { self.menu_length = menu_length; }
+}
+
+#[cfg(test)]
+mod tests {
+    use clipcat::ClipboardData;
+
+    use crate::{
+        config,
+        finder::{external::ExternalProgram, FinderStream, Fuzzel, SelectionMode},
+    };
+
+    #[test]
+    fn test_args() {
+        let fuzzel = Fuzzel::from_config(&config::Fuzzel { menu_length: 30, line_length: 40 });
+        assert_eq!(
+            fuzzel.args(SelectionMode::Single),
+            vec![
+                "--dmenu".to_owned(),
+                "--index".to_owned(),
+                "--prompt".to_owned(),
+                "clipcat> ".to_owned(),
+                "--lines".to_owned(),
+                "30".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_input_and_output() {
+        let fuzzel = Fuzzel::from_config(&config::Fuzzel { menu_length: 30, line_length: 40 });
+        let clips = vec![ClipboardData::new_clipboard("abc"), ClipboardData::new_clipboard("def")];
+        assert_eq!(fuzzel.generate_input(&clips), "abc\ndef");
+        assert_eq!(fuzzel.parse_output(b"1\n"), vec![1]);
+        assert_eq!(fuzzel.parse_output(b""), Vec::<usize>::new());
+    }
+}
@@ -1,22 +1,111 @@
-use crate::finder::{external::ExternalProgram, FinderStream, SelectionMode};
+use clipcat::ClipboardData;
+
+use crate::{
+    config,
+    finder::{
+        external::ExternalProgram,
+        finder_stream::{ENTRY_SEPARATOR, INDEX_SEPARATOR},
+        FinderStream, SelectionMode,
+    },
+};
+
+// Separates the hidden ID of a clip from its entry.
+const ID_SEPARATOR: char = '\t';
 
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub struct Fzf;
+pub struct Fzf {
+    preview_command: String,
+}
 
 impl Fzf {
     #[inline]
-    pub fn new() -> Fzf { Fzf }
+    pub fn from_config(config: &config::Fzf) -> Fzf {
+        Fzf { preview_command: config.preview_command.clone() }
+    }
+
+    // the ID of clips is the hidden first field of entries, `{1}` in the
+    // preview command
+    fn preview_args(&self) -> Vec<String> {
+        if self.preview_command.is_empty() {
+            return Vec::new();
+        }
+        vec![
+            format!("--delimiter={}", ID_SEPARATOR),
+            "--with-nth=2..".to_owned(),
+            "--preview".to_owned(),
+            self.preview_command.clone(),
+            "--preview-window=right:50%:wrap".to_owned(),
+        ]
+    }
 }
 
 impl ExternalProgram for Fzf {
     fn program(&self) -> String { "fzf".to_string() }
 
     fn args(&self, selection_mode: SelectionMode) -> Vec<String> {
-        match selection_mode {
-            SelectionMode::Single => vec!["--no-multi".to_owned()],
-            SelectionMode::Multiple => vec!["--multi".to_owned()],
-        }
+        let mut args = match selection_mode {
+            SelectionMode::Single => vec!["--ansi".to_owned(), "--no-multi".to_owned()],
+            SelectionMode::Multiple => vec!["--ansi".to_owned(), "--multi".to_owned()],
+        };
+        args.extend(self.preview_args());
+        args
+    }
+
+    fn query_args(&self) -> Option<Vec<String>> {
+        let mut args =
+            vec!["--ansi".to_owned(), "--no-multi".to_owned(), "--print-query".to_owned()];
+        args.extend(self.preview_args());
+        Some(args)
     }
 }
 
-impl FinderStream for Fzf {}
+impl FinderStream for Fzf {
+    fn generate_input(&self, clips: &[ClipboardData]) -> String {
+        clips
+            .iter()
+            .enumerate()
+            .map(|(i, data)| {
+                format!("{:016x}{}{}", data.id, ID_SEPARATOR, self.format_entry(i, data))
+            })
+            .collect::<Vec<_>>()
+            .join(ENTRY_SEPARATOR)
+    }
+
+    fn parse_output(&self, data: &[u8]) -> Vec<usize> {
+        String::from_utf8_lossy(data)
+            .split(ENTRY_SEPARATOR)
+            .filter_map(|entry| {
+                let entry = entry.splitn(2, ID_SEPARATOR).last()?;
+                entry.split(INDEX_SEPARATOR).next()?.parse::<usize>().ok()
+            })
+            .collect()
+    }
+
+    fn supports_ansi(&self) -> bool { true }
+}
+
+#[cfg(test)]
+mod tests {
+    use clipcat::ClipboardData;
+
+    use crate::{
+        config,
+        finder::{external::ExternalProgram, FinderStream, Fzf, SelectionMode},
+    };
+
+    #[test]
+    fn test_preview() {
+        let fzf = Fzf::from_config(&config::Fzf::default());
+        let mut clip = ClipboardData::new_clipboard("abc");
+        clip.id = 0xff;
+        assert_eq!(fzf.generate_input(&[clip]), "00000000000000ff\t0: abc");
+        assert_eq!(fzf.parse_output(b"00000000000000ff\t3: abc\n"), vec![3]);
+        assert!(fzf.args(SelectionMode::Single).contains(&"--with-nth=2..".to_owned()));
+
+        let fzf = Fzf::from_config(&config::Fzf { preview_command: String::new() });
+        assert_eq!(
+            fzf.args(SelectionMode::Multiple),
+            vec!["--ansi".to_owned(), "--multi".to_owned()]
+        );
+    }
+}
@@ -1,30 +1,68 @@
-use std::process::Stdio;
+use std::{path::Path, process::Stdio};
 
 use tokio::process::Command;
 
 use crate::finder::{FinderStream, SelectionMode};
 
+/// Exit code of finders when their remove key is pressed, rofi exits with it
+/// on `-kb-custom-1`.
+pub const REMOVE_EXIT_CODE: i32 = 10;
+
+mod bemenu;
 mod custom;
 mod dmenu;
+mod fuzzel;
 mod fzf;
 mod rofi;
 mod skim;
+mod tofi;
+mod wofi;
+
+pub use self::{
+    bemenu::Bemenu, custom::Custom, dmenu::Dmenu, fuzzel::Fuzzel, fzf::Fzf, rofi::Rofi, skim::Skim,
+    tofi::Tofi, wofi::Wofi,
+};
 
-pub use self::{custom::Custom, dmenu::Dmenu, fzf::Fzf, rofi::Rofi, skim::Skim};
+/// Prompt of finders which show one.
+pub const PROMPT: &str = "clipcat>";
 
 pub trait ExternalProgram: FinderStream + Send + Sync {
     fn program(&self) -> String;
 
     fn args(&self, selection_mode: SelectionMode) -> Vec<String>;
 
-    fn spawn_child(
-        &self,
-        selection_mode: SelectionMode,
-    ) -> Result<tokio::process::Child, std::io::Error> {
-        Command::new(self.program())
-            .args(self.args(selection_mode))
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
+    /// Arguments which bind a key for removing the chosen entry, the finder
+    /// exits with `REMOVE_EXIT_CODE` when it is pressed.
+    fn remove_key_args(&self) -> Vec<String> { Vec::new() }
+
+    /// Arguments which make the finder print the text typed into it on the
+    /// first line of its output, `None` if the finder can not.
+    fn query_args(&self) -> Option<Vec<String>> { None }
+
+    fn spawn_child(&self, args: Vec<String>) -> Result<tokio::process::Child, std::io::Error> {
+        Command::new(self.program()).args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()
+    }
+}
+
+/// Returns `true` if `program` is a path to a file or is found in `$PATH`.
+pub fn is_installed(program: &str) -> bool {
+    if program.contains('/') {
+        return Path::new(program).is_file();
+    }
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|path| path.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::finder::external::is_installed;
+
+    #[test]
+    fn test_is_installed() {
+        assert!(is_installed("sh"));
+        assert!(is_installed("/bin/sh"));
+        assert!(!is_installed("clipcat-no-such-finder"));
+        assert!(!is_installed("/no/such/finder"));
     }
 }
@@ -1,3 +1,5 @@
+use std::{collections::HashMap, path::PathBuf};
+
 use clipcat::ClipboardData;
 
 use crate::{
@@ -7,17 +9,46 @@ use crate::{
     },
 };
 
+// Icons of the freedesktop icon theme specification which show the type of
+// clips.
+const TEXT_ICON: &str = "text-x-generic";
+const URL_ICON: &str = "text-html";
+const IMAGE_ICON: &str = "image-x-generic";
+const FILE_ICON: &str = "folder";
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Rofi {
     line_length: usize,
     menu_length: usize,
+    remove_key: String,
+    show_icons: bool,
+    thumbnails: HashMap<u64, PathBuf>,
 }
 
 impl Rofi {
     pub fn from_config(config: &config::Rofi) -> Rofi {
-        let config::Rofi { menu_length, line_length } = *config;
+        let config::Rofi { menu_length, line_length, ref remove_key, show_icons } = *config;
+
+        Rofi {
+            menu_length,
+            line_length,
+            remove_key: remove_key.clone(),
+            show_icons,
+            thumbnails: HashMap::new(),
+        }
+    }
 
-        Rofi { menu_length, line_length }
+    fn icon(&self, data: &ClipboardData) -> String {
+        if let Some(thumbnail) = self.thumbnails.get(&data.id) {
+            return thumbnail.display().to_string();
+        }
+        let icon = match data.kind() {
+            "url" => URL_ICON,
+            "image" => IMAGE_ICON,
+            "files" => FILE_ICON,
+            _ => TEXT_ICON,
+        };
+        icon.to_owned()
     }
 }
 
@@ -25,7 +56,7 @@ impl ExternalProgram for Rofi {
     fn program(&self) -> String { "rofi".to_string() }
 
     fn args(&self, selection_mode: SelectionMode) -> Vec<String> {
-        match selection_mode {
+        let mut args = match selection_mode {
             SelectionMode::Single => vec![
                 "-dmenu".to_owned(),
                 "-l".to_owned(),
@@ -45,7 +76,29 @@ impl ExternalProgram for Rofi {
                 "-format".to_owned(),
                 "i".to_owned(),
             ],
+        };
+        if self.show_icons {
+            args.push("-show-icons".to_owned());
         }
+        args
+    }
+
+    fn remove_key_args(&self) -> Vec<String> {
+        vec!["-kb-custom-1".to_owned(), self.remove_key.clone()]
+    }
+
+    // the filter is printed instead of the entries, it is printed even if
+    // nothing matches
+    fn query_args(&self) -> Option<Vec<String>> {
+        Some(vec![
+            "-dmenu".to_owned(),
+            "-l".to_owned(),
+            self.menu_length.to_string(),
+            "-sep".to_owned(),
+            ENTRY_SEPARATOR.to_owned(),
+            "-format".to_owned(),
+            "f".to_owned(),
+        ])
     }
 }
 
@@ -53,13 +106,21 @@ impl FinderStream for Rofi {
     fn generate_input(&self, clips: &[ClipboardData]) -> String {
         clips
             .iter()
-            .map(|data| data.printable_data(self.line_length()))
+            .map(|data| {
+                let line = data.printable_data(self.line_length());
+                if self.show_icons {
+                    // row metadata of rofi, see `rofi-dmenu(5)`
+                    format!("{}\0icon\x1f{}", line, self.icon(data))
+                } else {
+                    line
+                }
+            })
             .collect::<Vec<_>>()
             .join(ENTRY_SEPARATOR)
     }
 
     fn parse_output(&self, data: &[u8]) -> Vec<usize> {
-        String::from_utf8_lossy(&data)
+        String::from_utf8_lossy(data)
             .trim()
             .split(ENTRY_SEPARATOR)
             .filter_map(|index| index.parse().ok())
@@ -73,18 +134,31 @@ impl FinderStream for Rofi {
     fn set_line_length(&mut self, line_length: usize) { self.line_length = line_length }
 
     fn set_menu_length(&mut self, menu_length: usize) { self.menu_length = menu_length; }
+
+    fn shows_thumbnails(&self) -> bool { self.show_icons }
+
+    fn set_thumbnails(&mut self, thumbnails: HashMap<u64, PathBuf>) {
+        self.thumbnails = thumbnails;
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use clipcat::ClipboardData;
+
     use crate::{
         config,
-        finder::{external::ExternalProgram, Rofi, SelectionMode},
+        finder::{external::ExternalProgram, FinderStream, Rofi, SelectionMode},
     };
 
     #[test]
     fn test_args() {
-        let rofi = Rofi::from_config(&config::Rofi { menu_length: 30, line_length: 40 });
+        let rofi = Rofi::from_config(&config::Rofi {
+            menu_length: 30,
+            line_length: 40,
+            remove_key: "Alt+d".to_owned(),
+            show_icons: false,
+        });
         assert_eq!(
             rofi.args(SelectionMode::Single),
             vec![
@@ -110,5 +184,33 @@ mod tests {
                 "i".to_owned(),
             ]
         );
+        assert_eq!(rofi.remove_key_args(), vec!["-kb-custom-1".to_owned(), "Alt+d".to_owned()]);
+    }
+
+    #[test]
+    fn test_icons() {
+        let mut rofi = Rofi::from_config(&config::Rofi {
+            menu_length: 30,
+            line_length: 40,
+            remove_key: "Alt+d".to_owned(),
+            show_icons: true,
+        });
+        assert!(rofi.args(SelectionMode::Single).contains(&"-show-icons".to_owned()));
+
+        let image = ClipboardData::new_clipboard("[image/png 16x16, 1.0 KiB]");
+        let clips = vec![
+            ClipboardData::new_clipboard("clipcat"),
+            ClipboardData::new_clipboard("https://example.com"),
+            ClipboardData::new_clipboard("[2 file(s)] /a /b"),
+            image.clone(),
+        ];
+        assert_eq!(
+            rofi.generate_input(&clips),
+            "clipcat\0icon\x1ftext-x-generic\nhttps://example.com\0icon\x1ftext-html\n[2 file(s)] \
+             /a /b\0icon\x1ffolder\n[image/png 16x16, 1.0 KiB]\0icon\x1fimage-x-generic"
+        );
+
+        rofi.set_thumbnails(vec![(image.id, "/tmp/a.png".into())].into_iter().collect());
+        assert_eq!(rofi.generate_input(&[image]), "[image/png 16x16, 1.0 KiB]\0icon\x1f/tmp/a.png");
     }
 }
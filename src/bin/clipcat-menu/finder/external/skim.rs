@@ -13,10 +13,16 @@ impl ExternalProgram for Skim {
 
     fn args(&self, selection_mode: SelectionMode) -> Vec<String> {
         match selection_mode {
-            SelectionMode::Single => vec!["--no-multi".to_owned()],
-            SelectionMode::Multiple => vec!["--multi".to_owned()],
+            SelectionMode::Single => vec!["--ansi".to_owned(), "--no-multi".to_owned()],
+            SelectionMode::Multiple => vec!["--ansi".to_owned(), "--multi".to_owned()],
         }
     }
+
+    fn query_args(&self) -> Option<Vec<String>> {
+        Some(vec!["--ansi".to_owned(), "--no-multi".to_owned(), "--print-query".to_owned()])
+    }
 }
 
-impl FinderStream for Skim {}
+impl FinderStream for Skim {
+    fn supports_ansi(&self) -> bool { true }
+}
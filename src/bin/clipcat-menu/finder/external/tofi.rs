@@ -0,0 +1,45 @@
+use crate::{
+    config,
+    finder::{
+        external::{ExternalProgram, PROMPT},
+        FinderStream, SelectionMode,
+    },
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Tofi {
+    menu_length: usize,
+    line_length: usize,
+}
+
+impl Tofi {
+    pub fn from_config(config: &config::Tofi) -> Tofi {
+        let config::Tofi { menu_length, line_length } = *config;
+
+        Tofi { menu_length, line_length }
+    }
+}
+
+// tofi only accepts options in the form of `--key=value` and matches
+// case-insensitively
+impl ExternalProgram for Tofi {
+    fn program(&self) -> String { "tofi".to_string() }
+
+    fn args(&self, _selection_mode: SelectionMode) -> Vec<String> {
+        vec![
+            format!("--prompt-text={} ", PROMPT),
+            format!("--num-results={}", self.menu_length),
+            "--require-match=true".to_owned(),
+        ]
+    }
+}
+
+impl FinderStream for Tofi {
+    fn line_length(&self) -> Option<usize> { Some(self.line_length) }
+
+    fn menu_length(&self) -> Option<usize> { Some(self.menu_length) }
+
+    fn set_line_length(&mut self, line_length: usize) { self.line_length = line_length }
+
+    fn set_menu_length(&mut self, menu_length: usize) { self.menu_length = menu_length; }
+}
@@ -0,0 +1,46 @@
+use crate::{
+    config,
+    finder::{
+        external::{ExternalProgram, PROMPT},
+        FinderStream, SelectionMode,
+    },
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Wofi {
+    menu_length: usize,
+    line_length: usize,
+}
+
+impl Wofi {
+    pub fn from_config(config: &config::Wofi) -> Wofi {
+        let config::Wofi { menu_length, line_length } = *config;
+
+        Wofi { menu_length, line_length }
+    }
+}
+
+impl ExternalProgram for Wofi {
+    fn program(&self) -> String { "wofi".to_string() }
+
+    fn args(&self, _selection_mode: SelectionMode) -> Vec<String> {
+        vec![
+            "--dmenu".to_owned(),
+            "--insensitive".to_owned(),
+            "--prompt".to_owned(),
+            PROMPT.to_owned(),
+            "--lines".to_owned(),
+            self.menu_length.to_string(),
+        ]
+    }
+}
+
+impl FinderStream for Wofi {
+    fn line_length(&self) -> Option<usize> { Some(self.line_length) }
+
+    fn menu_length(&self) -> Option<usize> { Some(self.menu_length) }
+
+    fn set_line_length(&mut self, line_length: usize) { self.line_length = line_length }
+
+    fn set_menu_length(&mut self, menu_length: usize) { self.menu_length = menu_length; }
+}
@@ -1,4 +1,10 @@
-use clipcat::ClipboardData;
+use std::{collections::HashMap, path::PathBuf};
+
+use clipcat::{
+    color::Color,
+    language::{self, Language},
+    ClipboardData,
+};
 
 pub const ENTRY_SEPARATOR: &str = "\n";
 pub const INDEX_SEPARATOR: char = ':';
@@ -8,13 +14,29 @@ pub trait FinderStream: Send + Sync {
         clips
             .iter()
             .enumerate()
-            .map(|(i, data)| {
-                format!("{}{} {}", i, INDEX_SEPARATOR, data.printable_data(self.line_length()))
-            })
+            .map(|(i, data)| self.format_entry(i, data))
             .collect::<Vec<_>>()
             .join(ENTRY_SEPARATOR)
     }
 
+    /// Returns the entry of the clip at `index`, prefixed with the index.
+    fn format_entry(&self, index: usize, data: &ClipboardData) -> String {
+        let line = data.printable_data(self.line_length());
+        if !self.supports_ansi() || !data.is_text() {
+            return format!("{}{} {}", index, INDEX_SEPARATOR, line);
+        }
+        // swatches are drawn in front of color values, code is highlighted
+        match (Color::parse(&data.data), Language::from_tags(&data.tags)) {
+            (Some(color), _) => {
+                format!("{}{} {} {}", index, INDEX_SEPARATOR, color.swatch(), line)
+            }
+            (None, Some(language)) => {
+                format!("{}{} {}", index, INDEX_SEPARATOR, language::highlight(language, &line))
+            }
+            (None, None) => format!("{}{} {}", index, INDEX_SEPARATOR, line),
+        }
+    }
+
     fn parse_output(&self, data: &[u8]) -> Vec<usize> {
         let line = String::from_utf8_lossy(data);
         line.split(ENTRY_SEPARATOR)
@@ -33,14 +55,25 @@ pub trait FinderStream: Send + Sync {
 
     fn set_menu_length(&mut self, _menu_length: usize) {}
 
+    #[allow(dead_code)]
     fn menu_length(&self) -> Option<usize> { None }
 
     fn line_length(&self) -> Option<usize> { None }
+
+    /// Returns `true` if the finder renders ANSI colors in its entries.
+    fn supports_ansi(&self) -> bool { false }
+
+    /// Returns `true` if the finder shows thumbnails of image clips.
+    fn shows_thumbnails(&self) -> bool { false }
+
+    /// Sets the image files which are shown as thumbnails of the clips with
+    /// their IDs.
+    fn set_thumbnails(&mut self, _thumbnails: HashMap<u64, PathBuf>) {}
 }
 
 #[cfg(test)]
 mod tests {
-    use clipcat::ClipboardData;
+    use clipcat::{language::Language, ClipboardData};
 
     use crate::finder::FinderStream;
 
@@ -68,39 +101,60 @@ mod tests {
         assert_eq!(v, "0: abcde\n1: АбВГД\n2: あいうえお");
     }
 
+    #[test]
+    fn test_generate_input_ansi() {
+        struct Ansi;
+        impl FinderStream for Ansi {
+            fn supports_ansi(&self) -> bool { true }
+        }
+
+        let clips =
+            vec![ClipboardData::new_clipboard("#ff8800"), ClipboardData::new_clipboard("abcde")];
+        assert_eq!(Dummy.generate_input(&clips), "0: #ff8800\n1: abcde");
+        assert_eq!(
+            Ansi.generate_input(&clips),
+            "0: \x1b[48;2;255;136;0m   \x1b[0m #ff8800\n1: abcde"
+        );
+
+        let mut code = ClipboardData::new_clipboard("fn main");
+        code.tags.insert(Language::Rust.tag().to_owned());
+        assert_eq!(Dummy.generate_input(&[code.clone()]), "0: fn main");
+        assert_eq!(Ansi.generate_input(&[code]), "0: \x1b[35mfn\x1b[0m main");
+    }
+
     #[test]
     fn test_parse_output() {
         let d = Dummy;
         let output = "";
-        let v = d.parse_output(&output.as_bytes());
+        let v = d.parse_output(output.as_bytes());
         assert!(v.is_empty());
 
         let output = ":";
-        let v = d.parse_output(&output.as_bytes());
+        let v = d.parse_output(output.as_bytes());
         assert!(v.is_empty());
 
         let output = "::::::::";
-        let v = d.parse_output(&output.as_bytes());
+        let v = d.parse_output(output.as_bytes());
         assert!(v.is_empty());
 
         let output = "\n\n\n\n\n";
-        let v = d.parse_output(&output.as_bytes());
+        let v = d.parse_output(output.as_bytes());
         assert!(v.is_empty());
 
         let output = "9\n3\n0\n4\n1\n";
-        let v = d.parse_output(&output.as_bytes());
+        let v = d.parse_output(output.as_bytes());
         assert_eq!(v, &[9, 3, 0, 4, 1]);
 
         let output = "203: abcde|АбВГД3|200あいうえお385";
-        let v = d.parse_output(&output.as_bytes());
+        let v = d.parse_output(output.as_bytes());
         assert_eq!(v, &[203]);
 
         let output = "2:3:4:5";
-        let v = d.parse_output(&output.as_bytes());
+        let v = d.parse_output(output.as_bytes());
         assert_eq!(v, &[2]);
 
         let output = "10: abcde\n2: АбВГД3020\n9:333\n7:30あいうえお38405\n1:323";
-        let v = d.parse_output(&output.as_bytes());
+        let v = d.parse_output(output.as_bytes());
         assert_eq!(v, &[10, 2, 9, 7, 1]);
     }
 }
@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{collections::HashMap, fmt, path::PathBuf, process::Output, str::FromStr};
 
 use snafu::{OptionExt, ResultExt};
 use tokio::io::AsyncWriteExt;
@@ -14,7 +14,10 @@ mod finder_stream;
 
 use self::{
     builtin::BuiltinFinder,
-    external::{Custom, Dmenu, ExternalProgram, Fzf, Rofi, Skim},
+    external::{
+        Bemenu, Custom, Dmenu, ExternalProgram, Fuzzel, Fzf, Rofi, Skim, Tofi, Wofi,
+        REMOVE_EXIT_CODE,
+    },
 };
 pub use self::{error::FinderError, finder_stream::FinderStream};
 
@@ -24,9 +27,18 @@ pub enum SelectionMode {
     Multiple,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+/// A clip which is chosen in the finder, and whether it was chosen with the
+/// remove key.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Selection {
+    Insert(usize, ClipboardData),
+    Remove(usize, ClipboardData),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Default)]
 pub enum FinderType {
     #[serde(rename = "builtin")]
+    #[default]
     Builtin,
 
     #[serde(rename = "rofi")]
@@ -41,6 +53,18 @@ pub enum FinderType {
     #[serde(rename = "fzf")]
     Fzf,
 
+    #[serde(rename = "wofi")]
+    Wofi,
+
+    #[serde(rename = "bemenu")]
+    Bemenu,
+
+    #[serde(rename = "fuzzel")]
+    Fuzzel,
+
+    #[serde(rename = "tofi")]
+    Tofi,
+
     #[serde(rename = "custom")]
     Custom,
 }
@@ -54,15 +78,15 @@ impl FinderType {
             FinderType::Dmenu,
             FinderType::Skim,
             FinderType::Fzf,
+            FinderType::Wofi,
+            FinderType::Bemenu,
+            FinderType::Fuzzel,
+            FinderType::Tofi,
             FinderType::Custom,
         ]
     }
 }
 
-impl Default for FinderType {
-    fn default() -> FinderType { FinderType::Builtin }
-}
-
 impl FromStr for FinderType {
     type Err = FinderError;
 
@@ -73,22 +97,31 @@ impl FromStr for FinderType {
             "dmenu" => Ok(FinderType::Dmenu),
             "skim" => Ok(FinderType::Skim),
             "fzf" => Ok(FinderType::Fzf),
+            "wofi" => Ok(FinderType::Wofi),
+            "bemenu" => Ok(FinderType::Bemenu),
+            "fuzzel" => Ok(FinderType::Fuzzel),
+            "tofi" => Ok(FinderType::Tofi),
             "custom" => Ok(FinderType::Custom),
             _ => Err(FinderError::InvalidFinder { finder: finder.to_owned() }),
         }
     }
 }
 
-impl ToString for FinderType {
-    fn to_string(&self) -> String {
-        match self {
-            FinderType::Builtin => "builtin".to_owned(),
-            FinderType::Rofi => "rofi".to_owned(),
-            FinderType::Dmenu => "dmenu".to_owned(),
-            FinderType::Skim => "skim".to_owned(),
-            FinderType::Fzf => "fzf".to_owned(),
-            FinderType::Custom => "custom".to_owned(),
-        }
+impl fmt::Display for FinderType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FinderType::Builtin => "builtin",
+            FinderType::Rofi => "rofi",
+            FinderType::Dmenu => "dmenu",
+            FinderType::Skim => "skim",
+            FinderType::Fzf => "fzf",
+            FinderType::Wofi => "wofi",
+            FinderType::Bemenu => "bemenu",
+            FinderType::Fuzzel => "fuzzel",
+            FinderType::Tofi => "tofi",
+            FinderType::Custom => "custom",
+        };
+        f.write_str(name)
     }
 }
 
@@ -101,18 +134,43 @@ impl FinderRunner {
         let external: Option<Box<dyn ExternalProgram>> = match config.finder {
             FinderType::Builtin => None,
             FinderType::Skim => Some(Box::new(Skim::new())),
-            FinderType::Fzf => Some(Box::new(Fzf::new())),
+            FinderType::Fzf => {
+                Some(Box::new(Fzf::from_config(&config.fzf.clone().unwrap_or_default())))
+            }
             FinderType::Rofi => {
                 Some(Box::new(Rofi::from_config(&config.rofi.clone().unwrap_or_default())))
             }
             FinderType::Dmenu => {
                 Some(Box::new(Dmenu::from_config(&config.dmenu.clone().unwrap_or_default())))
             }
+            FinderType::Wofi => {
+                Some(Box::new(Wofi::from_config(&config.wofi.clone().unwrap_or_default())))
+            }
+            FinderType::Bemenu => {
+                Some(Box::new(Bemenu::from_config(&config.bemenu.clone().unwrap_or_default())))
+            }
+            FinderType::Fuzzel => {
+                Some(Box::new(Fuzzel::from_config(&config.fuzzel.clone().unwrap_or_default())))
+            }
+            FinderType::Tofi => {
+                Some(Box::new(Tofi::from_config(&config.tofi.clone().unwrap_or_default())))
+            }
             FinderType::Custom => Some(Box::new(Custom::from_config(
                 &config.custom_finder.clone().unwrap_or_default(),
             ))),
         };
 
+        // the built-in finder works in any terminal, it stands in for missing finders
+        if let Some(ref program) = external {
+            if !external::is_installed(&program.program()) {
+                tracing::warn!(
+                    "Finder {} is not installed, the built-in finder is used",
+                    program.program()
+                );
+                return Ok(FinderRunner { external: None });
+            }
+        }
+
         Ok(FinderRunner { external })
     }
 
@@ -145,6 +203,74 @@ impl FinderRunner {
         Ok(clips)
     }
 
+    /// Selects a single clip like `single_select`, finders which bind a remove
+    /// key report if the clip was chosen with it.
+    pub async fn select_or_remove(
+        self,
+        clips: &[ClipboardData],
+    ) -> Result<Option<Selection>, FinderError> {
+        let external = match self.external {
+            Some(external) => external,
+            None => {
+                let selection = self.single_select(clips).await?;
+                return Ok(selection.map(|(index, clip)| Selection::Insert(index, clip)));
+            }
+        };
+
+        let mut args = external.args(SelectionMode::Single);
+        args.extend(external.remove_key_args());
+        let output = run_external(external.as_ref(), clips, args).await?;
+        let index = match external.parse_output(&output.stdout).first() {
+            Some(&index) if index < clips.len() => index,
+            _ => return Ok(None),
+        };
+        let clip = clips[index].clone();
+        if output.status.code() == Some(REMOVE_EXIT_CODE) {
+            Ok(Some(Selection::Remove(index, clip)))
+        } else {
+            Ok(Some(Selection::Insert(index, clip)))
+        }
+    }
+
+    /// Shows `clips` and returns the text which is typed into the finder,
+    /// `None` if the finder can not report it.
+    pub async fn query(&self, clips: &[ClipboardData]) -> Result<Option<String>, FinderError> {
+        let external = match self.external {
+            Some(ref external) => external,
+            None => {
+                let finder = BuiltinFinder::new();
+                return finder.query(clips).await.map(Some);
+            }
+        };
+
+        let args = match external.query_args() {
+            Some(args) => args,
+            None => return Ok(None),
+        };
+        let output = run_external(external.as_ref(), clips, args).await?;
+        let output = String::from_utf8_lossy(&output.stdout);
+        Ok(Some(output.lines().next().unwrap_or_default().to_owned()))
+    }
+
+    /// Returns the indices of `clips` which match `query` as shown in the
+    /// finder, each word of `query` is contained in them ignoring case.
+    pub fn matching_clips(&self, clips: &[ClipboardData], query: &str) -> Vec<usize> {
+        let words: Vec<_> = query.split_whitespace().map(str::to_lowercase).collect();
+        if words.is_empty() {
+            return Vec::new();
+        }
+        let line_length = self.line_length();
+        clips
+            .iter()
+            .enumerate()
+            .filter(|(_, clip)| {
+                let line = clip.printable_data(line_length).to_lowercase();
+                words.iter().all(|word| line.contains(word.as_str()))
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
     pub async fn select(
         self,
         clips: &[ClipboardData],
@@ -164,26 +290,24 @@ impl FinderRunner {
         selection_mode: SelectionMode,
     ) -> Result<Vec<usize>, FinderError> {
         if let Some(external) = self.external {
-            let input_data = external.generate_input(clips);
-            let mut child =
-                external.spawn_child(selection_mode).context(error::SpawnExternalProcess)?;
-            {
-                let stdin = child.stdin.as_mut().context(error::OpenStdin)?;
-                stdin.write_all(input_data.as_bytes()).await.context(error::WriteStdin)?;
-            }
-
-            let output = child.wait_with_output().await.context(error::ReadStdout)?;
+            let args = external.args(selection_mode);
+            let output = run_external(external.as_ref(), clips, args).await?;
             if output.stdout.is_empty() {
                 return Ok(vec![]);
             }
 
-            let selected_indices = external.parse_output(&output.stdout.as_slice());
+            let selected_indices = external.parse_output(output.stdout.as_slice());
             Ok(selected_indices)
         } else {
             Ok(vec![])
         }
     }
 
+    #[inline]
+    pub fn line_length(&self) -> Option<usize> {
+        self.external.as_ref().and_then(|external| external.line_length())
+    }
+
     #[inline]
     pub fn set_line_length(&mut self, line_length: usize) {
         if let Some(external) = self.external.as_mut() {
@@ -191,6 +315,18 @@ impl FinderRunner {
         }
     }
 
+    #[inline]
+    pub fn shows_thumbnails(&self) -> bool {
+        self.external.as_ref().is_some_and(|external| external.shows_thumbnails())
+    }
+
+    #[inline]
+    pub fn set_thumbnails(&mut self, thumbnails: HashMap<u64, PathBuf>) {
+        if let Some(external) = self.external.as_mut() {
+            external.set_thumbnails(thumbnails);
+        }
+    }
+
     #[inline]
     pub fn set_menu_length(&mut self, menu_length: usize) {
         if let Some(external) = self.external.as_mut() {
@@ -198,3 +334,38 @@ impl FinderRunner {
         }
     }
 }
+
+async fn run_external(
+    external: &dyn ExternalProgram,
+    clips: &[ClipboardData],
+    args: Vec<String>,
+) -> Result<Output, FinderError> {
+    let input_data = external.generate_input(clips);
+    let mut child = external.spawn_child(args).context(error::SpawnExternalProcess)?;
+    {
+        let stdin = child.stdin.as_mut().context(error::OpenStdin)?;
+        stdin.write_all(input_data.as_bytes()).await.context(error::WriteStdin)?;
+    }
+
+    child.wait_with_output().await.context(error::ReadStdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use clipcat::ClipboardData;
+
+    use crate::finder::FinderRunner;
+
+    #[test]
+    fn test_matching_clips() {
+        let finder = FinderRunner { external: None };
+        let clips = vec![
+            ClipboardData::new_clipboard("Meeting at 10:30"),
+            ClipboardData::new_clipboard("https://example.com/meeting"),
+            ClipboardData::new_clipboard("clipcat"),
+        ];
+        assert_eq!(finder.matching_clips(&clips, "meeting"), vec![0, 1]);
+        assert_eq!(finder.matching_clips(&clips, "MEETING example"), vec![1]);
+        assert_eq!(finder.matching_clips(&clips, "  "), Vec::<usize>::new());
+    }
+}
@@ -1,14 +1,25 @@
-use std::{num::ParseIntError, path::PathBuf};
+use std::{num::ParseIntError, path::PathBuf, time::Duration};
 
-use snafu::ResultExt;
+use snafu::{OptionExt, ResultExt};
 use structopt::StructOpt;
 use tokio::runtime::Runtime;
 
-use clipcat::{editor::ExternalEditor, grpc::GrpcClient, MonitorState};
+use clipcat::{
+    action::Action,
+    color::Color,
+    editor::ExternalEditor,
+    export::Export,
+    grpc::{ConnectOptions, GrpcClient, ListOptions},
+    language::Language,
+    template::{self, EntryTemplate},
+    ClipboardData, ClipboardType, MonitorState, SortOrder,
+};
 
 use crate::{
     config::Config,
     error::{self, Error},
+    importer::{self, ImportSource},
+    tui,
 };
 
 #[derive(StructOpt)]
@@ -26,6 +37,12 @@ pub struct Command {
     #[structopt(short = "p", long = "port", help = "Specifies a server port")]
     server_port: Option<u16>,
 
+    #[structopt(
+        long = "socket",
+        help = "Specifies a server unix socket, takes precedence over host and port"
+    )]
+    server_socket: Option<PathBuf>,
+
     #[structopt(long = "log-level", help = "Specifies a log level")]
     log_level: Option<tracing::Level>,
 }
@@ -44,21 +61,43 @@ pub enum SubCommand {
     #[structopt(
         aliases = &["paste"],
         about = "Inserts new clip into clipboard")]
-    Insert { data: String },
+    Insert {
+        data: String,
+
+        #[structopt(
+            long = "sensitive",
+            help = "Removes the clip again after the sensitive timeout"
+        )]
+        sensitive: bool,
+    },
 
     #[structopt(about = "Inserts new clip into primary clipboard")]
-    InsertPrimary { data: String },
+    InsertPrimary {
+        data: String,
+
+        #[structopt(
+            long = "sensitive",
+            help = "Removes the clip again after the sensitive timeout"
+        )]
+        sensitive: bool,
+    },
 
     #[structopt(aliases = &["cut"], about = "Loads file into clipboard")]
     Load {
         #[structopt(long = "file", short = "f")]
         file_path: Option<PathBuf>,
+
+        #[structopt(long = "mime", short = "m", help = "Specifies the MIME type, e.g. image/png")]
+        mime: Option<String>,
     },
 
     #[structopt(aliases = &["cut-primary"], about = "Loads file into primary clipboard")]
     LoadPrimary {
         #[structopt(long = "file", short = "f")]
         file_path: Option<PathBuf>,
+
+        #[structopt(long = "mime", short = "m", help = "Specifies the MIME type, e.g. image/png")]
+        mime: Option<String>,
     },
 
     #[structopt(aliases = &["paste"], about = "Pastes content of current clipboard into file")]
@@ -73,10 +112,70 @@ pub enum SubCommand {
         file_path: Option<PathBuf>,
     },
 
+    #[structopt(about = "Exports clips as JSON, sensitive clips are left out")]
+    Export {
+        #[structopt(long = "output", short = "o", help = "Writes to file instead of stdout")]
+        file_path: Option<PathBuf>,
+
+        #[structopt(
+            long = "tag",
+            short = "t",
+            help = "Only exports clips which carry this tag, can be repeated"
+        )]
+        tags: Vec<String>,
+
+        #[structopt(long, help = "Only exports pinned clips")]
+        pinned: bool,
+    },
+
+    #[structopt(
+        about = "Imports clips exported by `clipcatctl export` or other clipboard managers"
+    )]
+    Import {
+        #[structopt(
+            long = "from",
+            default_value = "clipcat",
+            help = "Format of the history: clipcat, greenclip, clipmenu or copyq"
+        )]
+        source: ImportSource,
+
+        #[structopt(long = "file", short = "f", help = "Reads from file instead of stdin")]
+        file_path: Option<PathBuf>,
+
+        #[structopt(help = "Same as --file, the cache directory for clipmenu")]
+        path: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Restores clips from a snapshot written by the backups of clipcatd")]
+    Restore {
+        snapshot: PathBuf,
+
+        #[structopt(long, help = "Clears the history before restoring")]
+        clear: bool,
+    },
+
+    #[structopt(about = "Rewrites the history files of clipcatd to reclaim unused space")]
+    Vacuum,
+
+    #[structopt(
+        aliases = &["dedup"],
+        about = "Merges clips with the same content into the most recent one"
+    )]
+    Deduplicate,
+
+    #[structopt(about = "Prints or changes settings of clipcatd while it runs")]
+    Config {
+        #[structopt(subcommand)]
+        subcommand: ConfigCommand,
+    },
+
     #[structopt(about = "Prints clip with <id>")]
     Get {
         #[structopt(parse(try_from_str = parse_hex))]
         id: Option<u64>,
+
+        #[structopt(long = "progress", help = "Prints progress of receiving the clip to stderr")]
+        progress: bool,
     },
 
     #[structopt(
@@ -85,6 +184,67 @@ pub enum SubCommand {
     List {
         #[structopt(long)]
         no_id: bool,
+
+        #[structopt(
+            long = "tag",
+            short = "t",
+            help = "Only lists clips which carry this tag, can be repeated"
+        )]
+        tags: Vec<String>,
+
+        #[structopt(
+            long = "language",
+            help = "Only lists clips in this language: rust, python, json or shell"
+        )]
+        language: Option<Language>,
+
+        #[structopt(
+            long = "app",
+            help = "Only lists clips copied from this application, e.g. firefox"
+        )]
+        application: Option<String>,
+
+        #[structopt(
+            long = "sort",
+            help = "Lists clips by time, created, size or use-count instead of the ranking"
+        )]
+        order: Option<SortOrder>,
+
+        #[structopt(long, default_value = "0", help = "Skips the first <offset> clips")]
+        offset: usize,
+
+        #[structopt(
+            long,
+            short = "n",
+            default_value = "0",
+            help = "Maximum number of clips, 0 lists all"
+        )]
+        limit: usize,
+
+        #[structopt(
+            long = "format",
+            help = "Specifies how clips are listed, e.g. \"{index} {time:rel} {type} \
+                    {preview:60}\""
+        )]
+        format: Option<EntryTemplate>,
+
+        #[structopt(
+            long,
+            short = "v",
+            conflicts_with = "format",
+            help = "Lists the time, the type, the use count and the source application of clips \
+                    as well"
+        )]
+        verbose: bool,
+    },
+
+    #[structopt(about = "Prints clips which hold a color value with a swatch and its hex code")]
+    Colors {
+        #[structopt(long)]
+        no_id: bool,
+
+        #[structopt(long, help = "Prints no swatches, e.g. for terminals without true color")]
+        no_swatch: bool,
     },
 
     #[structopt(about = "Updates clip with <id>")]
@@ -94,15 +254,116 @@ pub enum SubCommand {
         data: String,
     },
 
+    #[structopt(about = "Pretty-prints JSON or XML in clip with <id>")]
+    Format {
+        #[structopt(
+            long = "new",
+            help = "Inserts the formatted text as a new clip instead of updating the clip"
+        )]
+        new: bool,
+
+        #[structopt(parse(try_from_str = parse_hex))]
+        id: u64,
+    },
+
+    #[structopt(
+        aliases = &["qr"],
+        about = "Renders clip with <id> as QR code in the terminal")]
+    Qrcode {
+        #[structopt(
+            long = "output",
+            short = "o",
+            help = "Saves the QR code as PNG image to <output> instead of printing it"
+        )]
+        output: Option<PathBuf>,
+
+        #[structopt(parse(try_from_str = parse_hex))]
+        id: u64,
+    },
+
+    #[structopt(about = "Runs action <name> from the configuration on clip with <id>")]
+    Action {
+        name: String,
+
+        #[structopt(parse(try_from_str = parse_hex))]
+        id: u64,
+    },
+
+    #[structopt(about = "Types text clip with <id> as keystrokes into the focused window, for \
+                         fields which block paste")]
+    Type {
+        #[structopt(
+            long = "delay",
+            help = "Specifies the milliseconds between two keystrokes, overrides the configuration"
+        )]
+        delay: Option<u64>,
+
+        #[structopt(parse(try_from_str = parse_hex))]
+        id: u64,
+    },
+
+    #[structopt(
+        about = "Opens URL in clip with <id> in the browser, asks which one if there are several"
+    )]
+    Open {
+        #[structopt(
+            long = "browser",
+            default_value = "xdg-open",
+            help = "Specifies a program which opens URLs"
+        )]
+        browser: String,
+
+        #[structopt(long = "all", help = "Opens all URLs in the clip")]
+        all: bool,
+
+        #[structopt(parse(try_from_str = parse_hex))]
+        id: u64,
+    },
+
+    #[cfg(feature = "ocr")]
+    #[structopt(about = "Extracts text from image clip with <id> and inserts it as a new clip")]
+    Ocr {
+        #[structopt(
+            long = "language",
+            short = "l",
+            default_value = clipcat::ocr::DEFAULT_LANGUAGE,
+            help = "Specifies the languages of the text, e.g. eng+deu"
+        )]
+        language: String,
+
+        #[structopt(parse(try_from_str = parse_hex))]
+        id: u64,
+    },
+
     #[structopt(about = "Edits clip with <id>")]
     Edit {
         #[structopt(env = "EDITOR", long = "editor", short = "e")]
         editor: String,
 
+        #[structopt(
+            long = "new",
+            help = "Inserts the edited text as a new clip instead of updating the clip"
+        )]
+        new: bool,
+
         #[structopt(parse(try_from_str = parse_hex))]
         id: u64,
     },
 
+    #[structopt(about = "Joins text clips with <ids> in order into a new clip and promotes it")]
+    Merge {
+        #[structopt(
+            long = "separator",
+            short = "s",
+            default_value = "\\n",
+            help = "Separates the clips, \\n and \\t are replaced with newline and tab"
+        )]
+        separator: String,
+
+        #[structopt(required = true, min_values = 2, parse(try_from_str = parse_hex))]
+        ids: Vec<u64>,
+    },
+
     #[structopt(
         aliases = &["rm", "delete", "del"],
         about = "Removes clips with [ids]")]
@@ -112,6 +373,12 @@ pub enum SubCommand {
     MarkAsClipboard {
         #[structopt(parse(try_from_str = parse_hex))]
         id: u64,
+
+        #[structopt(
+            long = "one-shot",
+            help = "Clears clipboard and drops the clip after one paste"
+        )]
+        one_shot: bool,
     },
 
     #[structopt(
@@ -121,19 +388,151 @@ pub enum SubCommand {
     MarkAsPrimary {
         #[structopt(parse(try_from_str = parse_hex))]
         id: u64,
+
+        #[structopt(
+            long = "one-shot",
+            help = "Clears primary clipboard and drops the clip after one paste"
+        )]
+        one_shot: bool,
+    },
+
+    #[structopt(about = "Pins clip with <id>, pinned clips are never evicted from history")]
+    Pin {
+        #[structopt(parse(try_from_str = parse_hex))]
+        id: u64,
+    },
+
+    #[structopt(about = "Unpins clip with <id>")]
+    Unpin {
+        #[structopt(parse(try_from_str = parse_hex))]
+        id: u64,
+    },
+
+    #[structopt(about = "Attaches [tags] to clip with <id>")]
+    Tag {
+        #[structopt(parse(try_from_str = parse_hex))]
+        id: u64,
+        tags: Vec<String>,
+    },
+
+    #[structopt(about = "Detaches [tags] from clip with <id>")]
+    Untag {
+        #[structopt(parse(try_from_str = parse_hex))]
+        id: u64,
+        tags: Vec<String>,
+    },
+
+    #[structopt(aliases = &["snippets"], about = "Prints snippets")]
+    ListSnippets {
+        #[structopt(long)]
+        no_name: bool,
+    },
+
+    #[structopt(aliases = &["snippet"], about = "Inserts snippet with <name> into clipboard")]
+    InsertSnippet {
+        name: String,
+
+        #[structopt(long, help = "Inserts into primary clipboard instead")]
+        primary: bool,
     },
 
     #[structopt(
         aliases = &["remove-all"],
         about = "Removes all clips in clipboard"
     )]
-    Clear,
+    Clear {
+        #[structopt(
+            long,
+            parse(try_from_str = clipcat::age::parse),
+            help = "Only removes unpinned clips copied before this age, e.g. 90m, 12h or 30d"
+        )]
+        older_than: Option<Duration>,
+    },
+
+    #[structopt(about = "Restores the clips which were removed last")]
+    Undo,
+
+    #[structopt(about = "Lists or restores clips which were removed")]
+    Trash {
+        #[structopt(subcommand)]
+        subcommand: TrashCommand,
+    },
+
+    #[structopt(about = "Prints clipboard events as they are captured")]
+    Subscribe {
+        #[structopt(long)]
+        no_id: bool,
+    },
+
+    #[structopt(about = "Browses clipboard history interactively in the terminal")]
+    Tui,
+
+    #[structopt(about = "Prints every new clip as it is captured, one clip per line")]
+    Watch {
+        #[structopt(long, help = "Prints clips as JSON objects with their full content")]
+        json: bool,
+
+        #[structopt(long)]
+        no_id: bool,
+    },
+
+    #[structopt(
+        aliases = &["find"],
+        about = "Prints clips which fuzzy match <query>, best matches first")]
+    Search {
+        query: String,
+
+        #[structopt(long, short = "n", default_value = "0", help = "Maximum number of results")]
+        limit: usize,
+
+        #[structopt(long)]
+        no_id: bool,
+
+        #[structopt(
+            long = "language",
+            help = "Only searches clips in this language: rust, python, json or shell"
+        )]
+        language: Option<Language>,
+    },
+
+    #[structopt(about = "Prints lines of clips which match regular expression <pattern>, the \
+                         whole content of each clip is searched")]
+    Grep {
+        pattern: String,
+
+        #[structopt(
+            long,
+            short = "C",
+            default_value = "0",
+            help = "Number of lines to print around each matched line"
+        )]
+        context: usize,
+
+        #[structopt(long, short = "n", default_value = "0", help = "Maximum number of clips")]
+        limit: usize,
+
+        #[structopt(
+            long = "language",
+            help = "Only searches clips in this language: rust, python, json or shell"
+        )]
+        language: Option<Language>,
+    },
 
     #[structopt(
         aliases = &["count", "len"],
         about = "Prints length of clipboard history")]
     Length,
 
+    #[structopt(about = "Prints statistics of clipboard history")]
+    Stats {
+        #[structopt(
+            long,
+            default_value = "5",
+            help = "Number of the largest clips which are listed"
+        )]
+        largest: usize,
+    },
+
     #[structopt(aliases = &["enable"], about = "Enable clipboard monitor")]
     EnableMonitor,
 
@@ -145,6 +544,48 @@ pub enum SubCommand {
 
     #[structopt(aliases = &["monitor-state"], about = "Get clipboard monitor state")]
     GetMonitorState,
+
+    #[structopt(
+        setting = structopt::clap::AppSettings::Hidden,
+        about = "Prints the full content of clip with <id> for previews of finders"
+    )]
+    Preview {
+        #[structopt(long, help = "Highlights code and shows swatches of color values")]
+        highlight: bool,
+
+        // finders may pass the ID with the delimiter of their fields
+        #[structopt(parse(try_from_str = parse_preview_id))]
+        id: u64,
+    },
+}
+
+#[derive(StructOpt)]
+pub enum ConfigCommand {
+    #[structopt(about = "Prints setting <key>, or the whole configuration without <key>")]
+    Get {
+        #[structopt(help = "Name of the setting, e.g. max-history or capture.deny-regex")]
+        key: Option<String>,
+    },
+
+    #[structopt(about = "Changes setting <key> until clipcatd is restarted")]
+    Set {
+        key: String,
+
+        #[structopt(help = "Value in TOML, an empty value restores the default")]
+        value: String,
+    },
+}
+
+#[derive(StructOpt)]
+pub enum TrashCommand {
+    #[structopt(aliases = &["ls"], about = "Prints clips in the trash, most recently removed first")]
+    List,
+
+    #[structopt(about = "Restores clip with <id> from the trash")]
+    Restore {
+        #[structopt(parse(try_from_str = parse_hex))]
+        id: u64,
+    },
 }
 
 impl Command {
@@ -152,7 +593,7 @@ impl Command {
 
     fn load_config(&self) -> Config {
         let mut config =
-            Config::load_or_default(&self.config_file.clone().unwrap_or_else(Config::default_path));
+            Config::load_or_default(self.config_file.clone().unwrap_or_else(Config::default_path));
         if let Some(host) = self.server_host {
             config.server_host = host;
         }
@@ -161,6 +602,10 @@ impl Command {
             config.server_port = port;
         }
 
+        if let Some(socket) = self.server_socket.clone() {
+            config.server_socket = Some(socket);
+        }
+
         if let Ok(log_level) = std::env::var("RUST_LOG") {
             use std::str::FromStr;
             config.log_level = tracing::Level::from_str(&log_level).unwrap_or(tracing::Level::INFO);
@@ -202,7 +647,7 @@ impl Command {
                 let config_text =
                     toml::to_string_pretty(&Config::default()).expect("Config is serializable");
                 std::io::stdout()
-                    .write_all(&config_text.as_bytes())
+                    .write_all(config_text.as_bytes())
                     .expect("Failed to write to stdout");
                 return Ok(0);
             }
@@ -220,67 +665,208 @@ impl Command {
                     tracing_subscriber::filter::LevelFilter::from_level(config.log_level);
 
                 let registry = tracing_subscriber::registry().with(level_filter).with(fmt_layer);
+                #[cfg(unix)]
                 match tracing_journald::layer() {
                     Ok(layer) => registry.with(layer).init(),
                     Err(_err) => {
                         registry.init();
                     }
                 }
+                #[cfg(not(unix))]
+                registry.init();
             }
 
-            let mut client =
-                GrpcClient::new(format!("http://{}:{}", config.server_host, config.server_port))
-                    .await?;
+            let mut client = match config.server_socket {
+                #[cfg(unix)]
+                Some(ref socket_path) => {
+                    GrpcClient::connect_unix(socket_path, config.server_token.as_deref()).await?
+                }
+                _ => {
+                    let scheme = if config.tls.is_some() { "https" } else { "http" };
+                    let grpc_addr =
+                        format!("{}://{}:{}", scheme, config.server_host, config.server_port);
+                    let opts = ConnectOptions {
+                        token: config.server_token.clone(),
+                        tls: config.tls.clone(),
+                    };
+                    GrpcClient::connect(grpc_addr, opts).await?
+                }
+            };
 
             match self.subcommand {
                 None => {
-                    print_list(&mut client, false).await?;
+                    let format = config
+                        .list_format
+                        .as_deref()
+                        .map(str::parse::<EntryTemplate>)
+                        .transpose()
+                        .map_err(|message| Error::ParseListFormat { message })?;
+                    print_list(&mut client, false, ListOptions::default(), format).await?;
+                }
+                Some(SubCommand::List {
+                    no_id,
+                    mut tags,
+                    language,
+                    application,
+                    order,
+                    offset,
+                    limit,
+                    format,
+                    verbose,
+                }) => {
+                    tags.extend(language_tags(language));
+                    let format = match (format, config.list_format.as_deref()) {
+                        (Some(format), _) => Some(format),
+                        _ if verbose => Some(verbose_list_format(no_id)),
+                        (None, Some(format)) => Some(
+                            format
+                                .parse::<EntryTemplate>()
+                                .map_err(|message| Error::ParseListFormat { message })?,
+                        ),
+                        (None, None) => None,
+                    };
+                    let opts = ListOptions {
+                        tags,
+                        offset,
+                        limit,
+                        application,
+                        order,
+                        ..Default::default()
+                    };
+                    print_list(&mut client, no_id, opts, format).await?;
                 }
-                Some(SubCommand::List { no_id }) => {
-                    print_list(&mut client, no_id).await?;
+                Some(SubCommand::Colors { no_id, no_swatch }) => {
+                    print_colors(&mut client, no_id, no_swatch).await?;
                 }
-                Some(SubCommand::Get { id }) => {
-                    let data: String = match id {
-                        Some(id) => client.get(id).await?,
+                Some(SubCommand::Get { id, progress }) => {
+                    let clip = match id {
+                        Some(id) => {
+                            let clip = client
+                                .get_clip_chunked(id, 0, |received, size| {
+                                    if progress {
+                                        eprint!("\r{} / {} bytes", received, size);
+                                    }
+                                })
+                                .await?;
+                            if progress {
+                                eprintln!();
+                            }
+                            Some(clip)
+                        }
                         None => {
                             let clips = client.list().await?;
-                            clips
-                                .into_iter()
-                                .find(|entry| {
-                                    entry.clipboard_type == clipcat::ClipboardType::Clipboard
-                                })
-                                .map(|v| v.data)
-                                .unwrap_or_default()
+                            clips.into_iter().find(|entry| {
+                                entry.clipboard_type == clipcat::ClipboardType::Clipboard
+                            })
                         }
                     };
-                    println!("{}", data);
+                    match clip {
+                        Some(clip) if !clip.is_text() => {
+                            save_file_or_write_stdout(None, clip.binary).await?;
+                        }
+                        Some(clip) => println!("{}", clip.data),
+                        None => println!(),
+                    }
                 }
-                Some(SubCommand::Insert { data }) => {
+                Some(SubCommand::Insert { data, sensitive: false }) => {
                     client.insert_clipboard(&data).await?;
                 }
-                Some(SubCommand::InsertPrimary { data }) => {
+                Some(SubCommand::Insert { data, sensitive: true }) => {
+                    client.insert_sensitive(&data, ClipboardType::Clipboard).await?;
+                }
+                Some(SubCommand::InsertPrimary { data, sensitive: false }) => {
                     client.insert_primary(&data).await?;
                 }
+                Some(SubCommand::InsertPrimary { data, sensitive: true }) => {
+                    client.insert_sensitive(&data, ClipboardType::Primary).await?;
+                }
+                Some(SubCommand::Search { query, limit, no_id, language }) => {
+                    let tags = language_tags(language);
+                    print_search(&mut client, &query, &tags, limit, no_id).await?;
+                }
+                Some(SubCommand::Grep { pattern, context, limit, language }) => {
+                    let tags = language_tags(language);
+                    print_grep(&mut client, &pattern, &tags, context, limit).await?;
+                }
                 Some(SubCommand::Length) => {
                     let len = client.length().await?;
                     println!("{}", len);
                 }
-                Some(SubCommand::Load { file_path }) => {
+                Some(SubCommand::Stats { largest }) => {
+                    print_stats(&mut client, largest).await?;
+                }
+                Some(SubCommand::Load { file_path, mime }) => {
                     let data = load_file_or_read_stdin(file_path).await?;
-                    client.insert_clipboard(&data).await?;
+                    let mime = mime.unwrap_or_else(|| clipcat::mime::TEXT_PLAIN.to_owned());
+                    client.insert_bytes(&mime, data, ClipboardType::Clipboard).await?;
                 }
-                Some(SubCommand::LoadPrimary { file_path }) => {
+                Some(SubCommand::LoadPrimary { file_path, mime }) => {
                     let data = load_file_or_read_stdin(file_path).await?;
-                    client.insert_primary(&data).await?;
+                    let mime = mime.unwrap_or_else(|| clipcat::mime::TEXT_PLAIN.to_owned());
+                    client.insert_bytes(&mime, data, ClipboardType::Primary).await?;
                 }
                 Some(SubCommand::Save { file_path }) => {
-                    let data = client.get_current_clipboard().await?;
-                    save_file_or_write_stdout(file_path, data).await?;
+                    let clip = client.get_current_clip(ClipboardType::Clipboard).await?;
+                    save_file_or_write_stdout(file_path, clip.as_bytes()).await?;
                 }
                 Some(SubCommand::SavePrimary { file_path }) => {
-                    let data = client.get_current_primary().await?;
+                    let clip = client.get_current_clip(ClipboardType::Primary).await?;
+                    save_file_or_write_stdout(file_path, clip.as_bytes()).await?;
+                }
+                Some(SubCommand::Export { file_path, tags, pinned }) => {
+                    let clips = client.export(&tags, pinned).await?;
+                    let export = Export::new(&clips);
+                    let data =
+                        serde_json::to_vec_pretty(&export).context(error::SerializeExport)?;
                     save_file_or_write_stdout(file_path, data).await?;
                 }
+                Some(SubCommand::Import { source, file_path, path }) => {
+                    let clips = load_import(source, path.or(file_path)).await?;
+                    let total = clips.len();
+                    let count = client.import(clips).await?;
+                    println!("Imported {} of {} clips", count, total);
+                }
+                Some(SubCommand::Restore { snapshot, clear }) => {
+                    let clips = load_import(ImportSource::Clipcat, Some(snapshot)).await?;
+                    if clear {
+                        client.clear().await?;
+                    }
+                    let count = client.import(clips).await?;
+                    println!("Restored {} clips", count);
+                }
+                Some(SubCommand::Vacuum) => {
+                    let reclaimed = client.vacuum().await?;
+                    println!(
+                        "Reclaimed {}",
+                        clipcat::mime::human_readable_size(reclaimed as usize)
+                    );
+                }
+                Some(SubCommand::Deduplicate) => {
+                    let merged = client.deduplicate().await?;
+                    println!("Merged {} clips", merged);
+                }
+                Some(SubCommand::Config { subcommand: ConfigCommand::Get { key } }) => {
+                    let value = client.get_setting(&key.unwrap_or_default()).await?;
+                    println!("{}", value.trim_end());
+                }
+                Some(SubCommand::Config { subcommand: ConfigCommand::Set { key, value } }) => {
+                    let value = client.set_setting(&key, &value).await?;
+                    println!("{} = {}", key, value);
+                }
+                Some(SubCommand::Merge { separator, ids }) => {
+                    let mut parts = Vec::with_capacity(ids.len());
+                    for id in ids {
+                        let clip = client.get_clip(id).await?;
+                        if !clip.is_text() {
+                            eprintln!("Clip {:016x} ({}) is not text", id, clip.mime);
+                            return Ok(1);
+                        }
+                        parts.push(clip.data);
+                    }
+                    let separator = separator.replace("\\n", "\n").replace("\\t", "\t");
+                    let new_id = client.insert_clipboard(&parts.join(&separator)).await?;
+                    println!("{:016x}", new_id);
+                }
                 Some(SubCommand::Remove { ids }) => {
                     let ids: Vec<u64> = ids
                         .into_iter()
@@ -299,18 +885,165 @@ impl Command {
                     }
                     client.batch_remove(&ids).await?;
                 }
-                Some(SubCommand::Clear) => {
+                Some(SubCommand::Clear { older_than: None }) => {
                     client.clear().await?;
                 }
-                Some(SubCommand::Edit { id, editor }) => {
-                    let data = client.get(id).await?;
+                Some(SubCommand::Clear { older_than: Some(age) }) => {
+                    let ids = client.clear_older_than(age).await?;
+                    println!("Removed {} clips", ids.len());
+                }
+                Some(SubCommand::Undo) => {
+                    let ids = client.undo().await?;
+                    if ids.is_empty() {
+                        println!("Nothing is restored");
+                    }
+                    for id in ids {
+                        println!("{:016x}", id);
+                    }
+                }
+                Some(SubCommand::Trash { subcommand: TrashCommand::List }) => {
+                    print_trash(&mut client).await?;
+                }
+                Some(SubCommand::Trash { subcommand: TrashCommand::Restore { id } }) => {
+                    if !client.restore_from_trash(id).await? {
+                        eprintln!("Clip {:016x} is not in the trash", id);
+                        return Ok(1);
+                    }
+                }
+                Some(SubCommand::Edit { id, editor, new }) => {
+                    let clip = client.get_clip(id).await?;
+                    if !clip.is_text() {
+                        eprintln!("Clip {:016x} ({}) is not editable", id, clip.mime);
+                        return Ok(1);
+                    }
                     let editor = ExternalEditor::new(editor);
-                    let data = editor.execute(&data).await.context(error::CallEditor)?;
-                    let (ok, new_id) = client.update(id, &data).await?;
-                    if ok {
+                    let data = editor.execute(&clip.data).await.context(error::CallEditor)?;
+                    if data == clip.data {
+                        eprintln!("Clip {:016x} is unchanged", id);
+                        return Ok(0);
+                    }
+                    if new {
+                        let new_id = client.insert_clipboard(&data).await?;
+                        println!("{:016x}", new_id);
+                    } else {
+                        let (ok, new_id) = client.update(id, &data).await?;
+                        if ok {
+                            println!("{:016x}", new_id);
+                        }
+                        client.mark_as_clipboard(new_id).await?;
+                    }
+                }
+                Some(SubCommand::Format { id, new }) => {
+                    let clip = client.get_clip(id).await?;
+                    let formatted = if clip.is_text() {
+                        clipcat::format::pretty_print(&clip.data)
+                    } else {
+                        None
+                    };
+                    let data = match formatted {
+                        Some((_format, data)) => data,
+                        None => {
+                            eprintln!("Clip {:016x} is neither JSON nor XML", id);
+                            return Ok(1);
+                        }
+                    };
+                    let new_id = if new {
+                        client.insert_clipboard(&data).await?
+                    } else {
+                        let (_ok, new_id) = client.update(id, &data).await?;
+                        client.mark_as_clipboard(new_id).await?;
+                        new_id
+                    };
+                    println!("{:016x}", new_id);
+                }
+                Some(SubCommand::Qrcode { id, output }) => {
+                    let clip = client.get_clip(id).await?;
+                    if !clip.is_text() {
+                        eprintln!(
+                            "Clip {:016x} ({}) can not be rendered as QR code",
+                            id, clip.mime
+                        );
+                        return Ok(1);
+                    }
+                    match output {
+                        Some(output) => {
+                            clipcat::qr::save_png(&clip.data, &output)
+                                .context(error::RenderQrCode)?;
+                        }
+                        None => {
+                            let code = clipcat::qr::render_terminal(&clip.data)
+                                .context(error::RenderQrCode)?;
+                            println!("{}", code);
+                        }
+                    }
+                }
+                Some(SubCommand::Action { name, id }) => {
+                    let action = match Action::find(&config.actions, &name) {
+                        Some(action) => action,
+                        None => {
+                            eprintln!("Action {} is not configured", name);
+                            return Ok(1);
+                        }
+                    };
+                    let clip = client.get_clip(id).await?;
+                    if !clip.is_text() {
+                        eprintln!("Clip {:016x} ({}) is not text", id, clip.mime);
+                        return Ok(1);
+                    }
+                    let output = action.run(&clip.data).await.context(error::RunAction)?;
+                    if action.insert_output {
+                        let new_id = client.insert_clipboard(output.trim_end()).await?;
                         println!("{:016x}", new_id);
+                    } else {
+                        print!("{}", output);
+                    }
+                }
+                Some(SubCommand::Type { delay, id }) => {
+                    let clip = client.get_clip(id).await?;
+                    if !clip.is_text() {
+                        eprintln!("Clip {:016x} ({}) is not text", id, clip.mime);
+                        return Ok(1);
+                    }
+                    let mut opts = config.typing.clone();
+                    opts.key_delay = delay.unwrap_or(opts.key_delay);
+                    clipcat::typing::type_text(&clip.data, &opts).await.context(error::TypeClip)?;
+                }
+                Some(SubCommand::Open { id, browser, all }) => {
+                    let clip = client.get_clip(id).await?;
+                    let urls =
+                        if clip.is_text() { clipcat::urls::find(&clip.data) } else { Vec::new() };
+                    let urls = match urls.len() {
+                        0 => {
+                            eprintln!("Clip {:016x} contains no URL", id);
+                            return Ok(1);
+                        }
+                        1 => urls,
+                        _ if all => urls,
+                        _ => match select_url(&urls)? {
+                            Some(url) => vec![url],
+                            None => return Ok(1),
+                        },
+                    };
+                    for url in urls {
+                        tokio::process::Command::new(&browser)
+                            .arg(url)
+                            .status()
+                            .await
+                            .context(error::CallBrowser { program: browser.clone() })?;
+                    }
+                }
+                #[cfg(feature = "ocr")]
+                Some(SubCommand::Ocr { id, language }) => {
+                    let clip = client.get_clip(id).await?;
+                    if !clip.is_image() {
+                        eprintln!("Clip {:016x} ({}) is not an image", id, clip.mime);
+                        return Ok(1);
                     }
-                    client.mark_as_clipboard(new_id).await?;
+                    let text = clipcat::ocr::recognize(clip.binary, &language)
+                        .await
+                        .context(error::RecognizeText)?;
+                    let new_id = client.insert_clipboard(&text).await?;
+                    println!("{:016x}", new_id);
                 }
                 Some(SubCommand::Update { id, data }) => {
                     let (ok, new_id) = client.update(id, &data).await?;
@@ -318,16 +1051,60 @@ impl Command {
                         println!("{:016x}", new_id);
                     }
                 }
-                Some(SubCommand::MarkAsClipboard { id }) => {
-                    if client.mark_as_clipboard(id).await? {
+                Some(SubCommand::MarkAsClipboard { id, one_shot }) => {
+                    let ok = if one_shot {
+                        client.mark_as_clipboard_once(id).await?
+                    } else {
+                        client.mark_as_clipboard(id).await?
+                    };
+                    if ok {
+                        println!("Ok");
+                    }
+                }
+                Some(SubCommand::MarkAsPrimary { id, one_shot }) => {
+                    let ok = if one_shot {
+                        client.mark_as_primary_once(id).await?
+                    } else {
+                        client.mark_as_primary(id).await?
+                    };
+                    if ok {
+                        println!("Ok");
+                    }
+                }
+                Some(SubCommand::Pin { id }) => {
+                    if client.pin(id).await? {
+                        println!("Ok");
+                    }
+                }
+                Some(SubCommand::Unpin { id }) => {
+                    if client.unpin(id).await? {
                         println!("Ok");
                     }
                 }
-                Some(SubCommand::MarkAsPrimary { id }) => {
-                    if client.mark_as_primary(id).await? {
+                Some(SubCommand::Tag { id, tags }) => {
+                    if client.tag(id, &tags).await? {
                         println!("Ok");
                     }
                 }
+                Some(SubCommand::Untag { id, tags }) => {
+                    if client.untag(id, &tags).await? {
+                        println!("Ok");
+                    }
+                }
+                Some(SubCommand::ListSnippets { no_name }) => {
+                    print_snippets(&mut client, no_name).await?;
+                }
+                Some(SubCommand::InsertSnippet { name, primary }) => {
+                    let clipboard_type =
+                        if primary { ClipboardType::Primary } else { ClipboardType::Clipboard };
+                    match client.insert_snippet(&name, clipboard_type).await? {
+                        Some(id) => println!("{:016x}", id),
+                        None => {
+                            eprintln!("No snippet named {}", name);
+                            return Ok(1);
+                        }
+                    }
+                }
                 Some(SubCommand::EnableMonitor) => {
                     let state = client.enable_monitor().await?;
                     print_monitor_state(state);
@@ -344,6 +1121,19 @@ impl Command {
                     let state = client.get_monitor_state().await?;
                     print_monitor_state(state);
                 }
+                Some(SubCommand::Subscribe { no_id }) => {
+                    print_events(&mut client, no_id).await?;
+                }
+                Some(SubCommand::Preview { highlight, id }) => {
+                    let clip = client.get_clip(id).await?;
+                    print_preview(&clip, highlight);
+                }
+                Some(SubCommand::Watch { json, no_id }) => {
+                    watch_clips(&mut client, json, no_id).await?;
+                }
+                Some(SubCommand::Tui) => {
+                    tui::run(&mut client).await?;
+                }
                 _ => unreachable!(),
             }
             Ok(0)
@@ -357,10 +1147,26 @@ impl Command {
 #[inline]
 fn parse_hex(src: &str) -> Result<u64, ParseIntError> { u64::from_str_radix(src, 16) }
 
-async fn print_list(client: &mut GrpcClient, no_id: bool) -> Result<(), Error> {
+fn parse_preview_id(src: &str) -> Result<u64, ParseIntError> { parse_hex(src.trim()) }
+
+async fn print_list(
+    client: &mut GrpcClient,
+    no_id: bool,
+    opts: ListOptions,
+    format: Option<EntryTemplate>,
+) -> Result<(), Error> {
     const LINE_LENGTH: Option<usize> = Some(100);
 
-    let list = client.list().await?;
+    let preview_length = format.as_ref().and_then(EntryTemplate::preview_length).max(LINE_LENGTH);
+    let opts = ListOptions { preview_length, ..opts };
+    let (list, _total) = client.list_page(&opts).await?;
+    if let Some(format) = format {
+        let now = std::time::SystemTime::now();
+        for (index, data) in list.iter().enumerate() {
+            println!("{}", format.render(opts.offset + index, data, now, LINE_LENGTH));
+        }
+        return Ok(());
+    }
     for data in list {
         if no_id {
             println!("{}", data.printable_data(LINE_LENGTH));
@@ -371,15 +1177,291 @@ async fn print_list(client: &mut GrpcClient, no_id: bool) -> Result<(), Error> {
     Ok(())
 }
 
-async fn load_file_or_read_stdin(file_path: Option<PathBuf>) -> Result<String, Error> {
+// Columns of `clipcatctl list --verbose`, clips without a known source
+// application have an empty column.
+fn verbose_list_format(no_id: bool) -> EntryTemplate {
+    let format = if no_id {
+        "{time:abs}  {type:6}  {uses:4}  {app:16}  {preview}"
+    } else {
+        "{id}  {time:abs}  {type:6}  {uses:4}  {app:16}  {preview}"
+    };
+    format.parse().expect("format of verbose list is valid")
+}
+
+async fn print_stats(client: &mut GrpcClient, largest: usize) -> Result<(), Error> {
+    const PREVIEW_LENGTH: usize = 60;
+
+    let size = clipcat::mime::human_readable_size;
+    let stats = client.stats(largest, PREVIEW_LENGTH).await?;
+    println!("{} clips, {}", stats.count, size(stats.bytes));
+    for clipboard_type in &stats.clipboard_types {
+        println!(
+            "  {}: {} clips, {}",
+            clipboard_type.name,
+            clipboard_type.count,
+            size(clipboard_type.bytes)
+        );
+    }
+    println!("{} in the last hour, {} in the last day", stats.last_hour, stats.last_day);
+
+    let now = std::time::SystemTime::now();
+    if let (Some(oldest), Some(newest)) = (stats.oldest, stats.newest) {
+        println!(
+            "Oldest: {} ({})",
+            template::absolute_time(oldest),
+            template::relative_time(oldest, now)
+        );
+        println!(
+            "Newest: {} ({})",
+            template::absolute_time(newest),
+            template::relative_time(newest, now)
+        );
+    }
+
+    if !stats.mime_types.is_empty() {
+        println!("MIME types:");
+        for mime_type in &stats.mime_types {
+            println!("  {}: {} clips, {}", mime_type.name, mime_type.count, size(mime_type.bytes));
+        }
+    }
+    if !stats.largest.is_empty() {
+        println!("Largest clips:");
+        for clip in &stats.largest {
+            println!("  {:016x}: {} {} {}", clip.id, size(clip.size), clip.mime, clip.preview);
+        }
+    }
+    Ok(())
+}
+
+async fn print_trash(client: &mut GrpcClient) -> Result<(), Error> {
+    const PREVIEW_LENGTH: usize = 60;
+
+    let now = std::time::SystemTime::now();
+    for entry in client.list_trash(PREVIEW_LENGTH).await? {
+        println!(
+            "{:016x}: removed {} {}",
+            entry.clip.id,
+            template::relative_time(entry.removed_at, now),
+            entry.clip.data
+        );
+    }
+    Ok(())
+}
+
+async fn print_colors(client: &mut GrpcClient, no_id: bool, no_swatch: bool) -> Result<(), Error> {
+    for clip in client.list().await? {
+        let color = match Color::parse(&clip.data) {
+            Some(color) if clip.is_text() => color,
+            _ => continue,
+        };
+        let mut line = String::new();
+        if !no_id {
+            line.push_str(&format!("{:016x}: ", clip.id));
+        }
+        if !no_swatch {
+            line.push_str(&color.swatch());
+            line.push(' ');
+        }
+        line.push_str(&format!("{} {}", color.to_hex(), clip.data.trim()));
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn print_preview(clip: &ClipboardData, highlight: bool) {
+    if !clip.is_text() {
+        println!("{}", clip.printable_data(None));
+        return;
+    }
+    match (highlight, Color::parse(&clip.data), Language::from_tags(&clip.tags)) {
+        (true, Some(color), _) => println!("{} {}", color.swatch(), clip.data.trim()),
+        (true, None, Some(language)) => {
+            for line in clip.data.lines() {
+                println!("{}", clipcat::language::highlight(language, line));
+            }
+        }
+        _ => println!("{}", clip.data),
+    }
+}
+
+async fn print_search(
+    client: &mut GrpcClient,
+    query: &str,
+    tags: &[String],
+    limit: usize,
+    no_id: bool,
+) -> Result<(), Error> {
+    const LINE_LENGTH: usize = 100;
+
+    for result in client.search(query, tags, limit, LINE_LENGTH).await? {
+        if no_id {
+            println!("{}", result.preview);
+        } else {
+            println!("{:016x}: {}", result.id, result.preview);
+        }
+    }
+    Ok(())
+}
+
+async fn print_grep(
+    client: &mut GrpcClient,
+    pattern: &str,
+    tags: &[String],
+    context: usize,
+    limit: usize,
+) -> Result<(), Error> {
+    // like grep, matched lines are separated by `:` and context lines by `-`
+    let results = client.grep(pattern, tags, context, limit, 0).await?;
+    for (idx, result) in results.into_iter().enumerate() {
+        if context > 0 && idx > 0 {
+            println!("--");
+        }
+        for line in result.lines {
+            let separator = if line.matched { ':' } else { '-' };
+            println!("{:016x}{}{}{}{}", result.id, separator, line.number, separator, line.text);
+        }
+    }
+    Ok(())
+}
+
+// Asks on the terminal which of `urls` to open.
+fn select_url<'a>(urls: &[&'a str]) -> Result<Option<&'a str>, Error> {
+    for (i, url) in urls.iter().enumerate() {
+        eprintln!("{}: {}", i + 1, url);
+    }
+    eprint!("Open URL [1-{}]: ", urls.len());
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).context(error::ReadStdin)?;
+    match answer.trim().parse::<usize>() {
+        Ok(index) if (1..=urls.len()).contains(&index) => Ok(Some(urls[index - 1])),
+        _ => {
+            eprintln!("Nothing is selected");
+            Ok(None)
+        }
+    }
+}
+
+fn language_tags(language: Option<Language>) -> Vec<String> {
+    language.map(|language| language.tag().to_owned()).into_iter().collect()
+}
+
+async fn print_snippets(client: &mut GrpcClient, no_name: bool) -> Result<(), Error> {
+    const LINE_LENGTH: Option<usize> = Some(100);
+
+    for snippet in client.list_snippets().await? {
+        let data = ClipboardData::new_clipboard(&snippet.data).printable_data(LINE_LENGTH);
+        if no_name {
+            println!("{}", data);
+        } else {
+            println!("{}: {}", snippet.name, data);
+        }
+    }
+    Ok(())
+}
+
+async fn print_events(client: &mut GrpcClient, no_id: bool) -> Result<(), Error> {
+    let mut events = client.subscribe().await?;
+    while let Some(event) = events.next().await? {
+        let clipboard_type = clipboard_type_name(event.clipboard_type);
+        if no_id {
+            println!("[{}] {}", clipboard_type, event.preview);
+        } else {
+            println!("{:016x}: [{}] {}", event.id, clipboard_type, event.preview);
+        }
+    }
+    Ok(())
+}
+
+/// A clip printed by `watch --json`, binary clips carry no `data`.
+#[derive(Serialize)]
+struct WatchedClip {
+    id: String,
+    clipboard_type: &'static str,
+    mime: String,
+    timestamp: u64,
+    size: usize,
+    data: Option<String>,
+}
+
+impl From<ClipboardData> for WatchedClip {
+    fn from(clip: ClipboardData) -> WatchedClip {
+        let size = clip.total_size();
+        let timestamp = clip
+            .timestamp
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis() as u64);
+        WatchedClip {
+            id: format!("{:016x}", clip.id),
+            clipboard_type: clipboard_type_name(clip.clipboard_type),
+            data: if clip.is_text() { Some(clip.data) } else { None },
+            mime: clip.mime,
+            timestamp,
+            size,
+        }
+    }
+}
+
+#[inline]
+fn clipboard_type_name(clipboard_type: ClipboardType) -> &'static str {
+    match clipboard_type {
+        ClipboardType::Clipboard => "clipboard",
+        ClipboardType::Primary => "primary",
+    }
+}
+
+async fn watch_clips(client: &mut GrpcClient, json: bool, no_id: bool) -> Result<(), Error> {
+    let mut events = client.subscribe().await?;
+    while let Some(event) = events.next().await? {
+        // the clip may be gone already, e.g. if it was a one-shot clip
+        let clip = match client.get_clip(event.id).await {
+            Ok(clip) => clip,
+            Err(_) => continue,
+        };
+
+        if json {
+            let line =
+                serde_json::to_string(&WatchedClip::from(clip)).context(error::SerializeClip)?;
+            println!("{}", line);
+        } else if no_id {
+            println!("{}", clip.printable_data(None));
+        } else {
+            println!("{:016x}: {}", clip.id, clip.printable_data(None));
+        }
+    }
+    Ok(())
+}
+
+async fn load_import(
+    source: ImportSource,
+    path: Option<PathBuf>,
+) -> Result<Vec<ClipboardData>, Error> {
+    match source {
+        ImportSource::Clipcat => {
+            let data = load_file_or_read_stdin(path).await?;
+            let export: Export = serde_json::from_slice(&data).context(error::DeserializeExport)?;
+            export.into_clips().context(error::ImportClips)
+        }
+        ImportSource::Greenclip => {
+            Ok(importer::parse_greenclip(&load_file_or_read_stdin(path).await?))
+        }
+        ImportSource::Clipmenu => {
+            let dir = path.context(error::MissingClipmenuDirectory)?;
+            importer::load_clipmenu(&dir)
+        }
+        ImportSource::Copyq => Ok(importer::parse_copyq(&load_file_or_read_stdin(path).await?)),
+    }
+}
+
+async fn load_file_or_read_stdin(file_path: Option<PathBuf>) -> Result<Vec<u8>, Error> {
     use tokio::io::AsyncReadExt;
     match file_path {
-        Some(file_path) => tokio::fs::read_to_string(&file_path)
+        Some(file_path) => tokio::fs::read(&file_path)
             .await
             .context(error::ReadFile { filename: file_path.to_owned() }),
         None => {
-            let mut data = String::new();
-            tokio::io::stdin().read_to_string(&mut data).await.context(error::ReadStdin)?;
+            let mut data = Vec::new();
+            tokio::io::stdin().read_to_end(&mut data).await.context(error::ReadStdin)?;
             Ok(data)
         }
     }
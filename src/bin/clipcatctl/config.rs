@@ -11,8 +11,29 @@ pub struct Config {
 
     pub server_port: u16,
 
+    #[serde(default)]
+    pub server_socket: Option<PathBuf>,
+
+    #[serde(default)]
+    pub server_token: Option<String>,
+
     #[serde(default = "Config::default_log_level", with = "serde_with::rust::display_fromstr")]
     pub log_level: tracing::Level,
+
+    #[serde(default)]
+    pub tls: Option<clipcat::grpc::ClientTlsOptions>,
+
+    /// Commands which `clipcatctl action <name> <id>` runs on clips.
+    #[serde(default)]
+    pub actions: Vec<clipcat::action::Action>,
+
+    /// How `clipcatctl list` prints clips, e.g. `{index} {time:rel} {preview}`.
+    #[serde(default)]
+    pub list_format: Option<String>,
+
+    /// How `clipcatctl type <id>` types clips.
+    #[serde(default)]
+    pub typing: clipcat::typing::TypingOptions,
 }
 
 impl Default for Config {
@@ -20,7 +41,13 @@ impl Default for Config {
         Config {
             server_host: clipcat::DEFAULT_GRPC_HOST.parse().expect("Parse default gRPC host"),
             server_port: clipcat::DEFAULT_GRPC_PORT,
+            server_socket: None,
+            server_token: None,
             log_level: Self::default_log_level(),
+            tls: None,
+            actions: Vec::new(),
+            list_format: None,
+            typing: clipcat::typing::TypingOptions::default(),
         }
     }
 }
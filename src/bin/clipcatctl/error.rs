@@ -1,6 +1,9 @@
 use std::path::PathBuf;
 
-use clipcat::{editor::EditorError, grpc::GrpcClientError};
+use clipcat::{
+    action::ActionError, editor::EditorError, export::ImportError, grpc::GrpcClientError,
+    qr::QrCodeError,
+};
 
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub))]
@@ -14,6 +17,27 @@ pub enum Error {
     #[snafu(display("Could not write to stdout, error: {}", source))]
     WriteStdout { source: std::io::Error },
 
+    #[snafu(display("Could not serialize clip, error: {}", source))]
+    SerializeClip { source: serde_json::Error },
+
+    #[snafu(display("Could not serialize export, error: {}", source))]
+    SerializeExport { source: serde_json::Error },
+
+    #[snafu(display("Could not parse export, error: {}", source))]
+    DeserializeExport { source: serde_json::Error },
+
+    #[snafu(display(
+        "Unknown import source {}, expected clipcat, greenclip, clipmenu or copyq",
+        source_name
+    ))]
+    InvalidImportSource { source_name: String },
+
+    #[snafu(display("A cache directory is required to import from clipmenu"))]
+    MissingClipmenuDirectory,
+
+    #[snafu(display("Could not import clips, error: {}", source))]
+    ImportClips { source: ImportError },
+
     #[snafu(display("Could not create tokio runtime, error: {}", source))]
     CreateTokioRuntime { source: std::io::Error },
 
@@ -22,6 +46,28 @@ pub enum Error {
 
     #[snafu(display("Could not call external editor, error: {}", source))]
     CallEditor { source: EditorError },
+
+    #[snafu(display("Could not render QR code, error: {}", source))]
+    RenderQrCode { source: QrCodeError },
+
+    #[snafu(display("Could not run action, error: {}", source))]
+    RunAction { source: ActionError },
+
+    #[snafu(display("Could not type clip, error: {}", source))]
+    TypeClip { source: ActionError },
+
+    #[snafu(display("Could not call browser: {}, error: {}", program, source))]
+    CallBrowser { program: String, source: std::io::Error },
+
+    #[snafu(display("Could not access terminal, error: {}", source))]
+    AccessTerminal { source: std::io::Error },
+
+    #[snafu(display("Could not parse list format, error: {}", message))]
+    ParseListFormat { message: String },
+
+    #[cfg(feature = "ocr")]
+    #[snafu(display("Could not extract text from image, error: {}", source))]
+    RecognizeText { source: clipcat::ocr::OcrError },
 }
 
 impl From<GrpcClientError> for Error {
@@ -0,0 +1,158 @@
+use std::{
+    path::Path,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+use snafu::ResultExt;
+
+use clipcat::ClipboardData;
+
+use crate::error::{self, Error};
+
+// `greenclip print` replaces line breaks inside of a clip with this character.
+const GREENCLIP_LINE_BREAK: char = '\u{a0}';
+
+// Files in the cache directory of clipmenu which do not hold clips.
+const CLIPMENU_METADATA_PREFIXES: &[&str] = &["line_cache", "lock", "session_lock", "status"];
+
+/// Format of a history which is imported by `clipcatctl import`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ImportSource {
+    /// JSON written by `clipcatctl export`.
+    Clipcat,
+
+    /// Output of `greenclip print`, newest clip first.
+    Greenclip,
+
+    /// Cache directory of clipmenu, e.g. `/tmp/clipmenu.6.$USER`.
+    Clipmenu,
+
+    /// Clips printed by CopyQ separated by NUL characters, newest clip first.
+    Copyq,
+}
+
+impl FromStr for ImportSource {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source.to_lowercase().as_ref() {
+            "clipcat" => Ok(ImportSource::Clipcat),
+            "greenclip" => Ok(ImportSource::Greenclip),
+            "clipmenu" => Ok(ImportSource::Clipmenu),
+            "copyq" => Ok(ImportSource::Copyq),
+            _ => Err(Error::InvalidImportSource { source_name: source.to_owned() }),
+        }
+    }
+}
+
+pub fn parse_greenclip(data: &[u8]) -> Vec<ClipboardData> {
+    let text = String::from_utf8_lossy(data);
+    let entries = text
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.replace(GREENCLIP_LINE_BREAK, "\n"));
+    with_descending_timestamps(entries)
+}
+
+pub fn parse_copyq(data: &[u8]) -> Vec<ClipboardData> {
+    let text = String::from_utf8_lossy(data);
+    let entries = text.split('\0').filter(|entry| !entry.is_empty()).map(ToOwned::to_owned);
+    with_descending_timestamps(entries)
+}
+
+/// Loads every clip file of a clipmenu cache directory, the modification time
+/// of a file is used as the timestamp of its clip.
+pub fn load_clipmenu(dir: &Path) -> Result<Vec<ClipboardData>, Error> {
+    let entries = std::fs::read_dir(dir).context(error::ReadFile { filename: dir.to_owned() })?;
+
+    let mut clips = Vec::new();
+    for entry in entries {
+        let entry = entry.context(error::ReadFile { filename: dir.to_owned() })?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if CLIPMENU_METADATA_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) {
+            continue;
+        }
+
+        let path = entry.path();
+        let metadata = entry.metadata().context(error::ReadFile { filename: path.clone() })?;
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let data = std::fs::read(&path).context(error::ReadFile { filename: path.clone() })?;
+        if data.is_empty() {
+            continue;
+        }
+
+        let mut clip = ClipboardData::new_clipboard(&String::from_utf8_lossy(&data));
+        if let Ok(modified) = metadata.modified() {
+            clip.timestamp = modified;
+            clip.created_at = modified;
+        }
+        clips.push(clip);
+    }
+    Ok(clips)
+}
+
+// The formats carry no timestamps, the clips are a millisecond apart so they
+// keep their order.
+fn with_descending_timestamps(entries: impl Iterator<Item = String>) -> Vec<ClipboardData> {
+    let now = SystemTime::now();
+    entries
+        .enumerate()
+        .map(|(idx, entry)| {
+            let mut clip = ClipboardData::new_clipboard(&entry);
+            clip.timestamp = now - Duration::from_millis(idx as u64);
+            clip.created_at = clip.timestamp;
+            clip
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use crate::importer::{load_clipmenu, parse_copyq, parse_greenclip, ImportSource};
+
+    fn contents(clips: &[clipcat::ClipboardData]) -> Vec<&str> {
+        clips.iter().map(|clip| clip.data.as_str()).collect()
+    }
+
+    #[test]
+    fn test_import_source() {
+        assert_eq!("GreenClip".parse::<ImportSource>().unwrap(), ImportSource::Greenclip);
+        assert!("klipper".parse::<ImportSource>().is_err());
+    }
+
+    #[test]
+    fn test_parse_greenclip() {
+        let clips = parse_greenclip("newest\n\nfirst\u{a0}second\noldest\n".as_bytes());
+        assert_eq!(contents(&clips), vec!["newest", "first\nsecond", "oldest"]);
+        assert!(clips[0].timestamp > clips[1].timestamp);
+        assert!(clips[1].timestamp > clips[2].timestamp);
+    }
+
+    #[test]
+    fn test_parse_copyq() {
+        let clips = parse_copyq(b"newest\0multi\nline\0\0oldest\0");
+        assert_eq!(contents(&clips), vec!["newest", "multi\nline", "oldest"]);
+        assert!(clips[0].timestamp > clips[2].timestamp);
+    }
+
+    #[test]
+    fn test_load_clipmenu() {
+        let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_nanos();
+        let dir = std::env::temp_dir().join(format!("clipcat-clipmenu-{}", nanos));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("line_cache_clipboard"), "1600000000 clip").unwrap();
+        std::fs::write(dir.join("session_lock"), "").unwrap();
+        std::fs::write(dir.join("1234567890"), "clip\nwith lines").unwrap();
+        std::fs::write(dir.join("2345678901"), "").unwrap();
+
+        let clips = load_clipmenu(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(contents(&clips), vec!["clip\nwith lines"]);
+    }
+}
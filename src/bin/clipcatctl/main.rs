@@ -9,6 +9,8 @@ extern crate snafu;
 mod command;
 mod config;
 mod error;
+mod importer;
+mod tui;
 
 use self::command::Command;
 
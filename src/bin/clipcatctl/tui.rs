@@ -0,0 +1,384 @@
+use std::{cmp::Reverse, io, time::SystemTime};
+
+use crossterm::{
+    event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures::{FutureExt, StreamExt};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame, Terminal,
+};
+use snafu::ResultExt;
+
+use clipcat::{
+    fuzzy_score,
+    grpc::{ClipboardEventStream, GrpcClient, GrpcClientError, ListOptions},
+    template::relative_time,
+    ClipboardData, ClipboardNotification,
+};
+
+use crate::error::{self, Error};
+
+// Length of the previews which are listed and filtered, the selected clip is
+// fetched in full.
+const PREVIEW_LENGTH: usize = 200;
+
+const HELP: &str = "q quit  / filter  enter promote  p pin  d delete  r reload";
+
+type Backend = CrosstermBackend<io::Stdout>;
+
+/// Browses the history in the terminal until the user quits, the list is
+/// reloaded whenever the daemon captures a clip.
+pub async fn run(client: &mut GrpcClient) -> Result<(), Error> {
+    let mut events = Some(client.subscribe().await?);
+    let mut app = App::default();
+    app.set_clips(load_clips(client).await?);
+
+    terminal::enable_raw_mode().context(error::AccessTerminal)?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen).context(error::AccessTerminal)?;
+    let mut terminal =
+        Terminal::new(CrosstermBackend::new(stdout)).context(error::AccessTerminal)?;
+
+    let result = event_loop(client, &mut events, &mut terminal, &mut app).await;
+
+    // the terminal is restored even if the loop failed
+    let restored = restore_terminal(&mut terminal);
+    result.and(restored)
+}
+
+fn restore_terminal(terminal: &mut Terminal<Backend>) -> Result<(), Error> {
+    terminal::disable_raw_mode().context(error::AccessTerminal)?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen).context(error::AccessTerminal)?;
+    terminal.show_cursor().context(error::AccessTerminal)
+}
+
+enum Step {
+    Input(Option<io::Result<Event>>),
+    Notification(Result<Option<ClipboardNotification>, GrpcClientError>),
+}
+
+async fn event_loop(
+    client: &mut GrpcClient,
+    events: &mut Option<ClipboardEventStream>,
+    terminal: &mut Terminal<Backend>,
+    app: &mut App,
+) -> Result<(), Error> {
+    let mut input = EventStream::new();
+    loop {
+        let selected = app.selected_clip().map(|clip| clip.id);
+        if selected != app.preview.as_ref().map(|clip| clip.id) {
+            app.preview = match selected {
+                // the clip may be gone already, its preview is shown instead
+                Some(id) => client.get_clip(id).await.ok(),
+                None => None,
+            };
+        }
+        terminal.draw(|frame| draw(frame, app)).context(error::AccessTerminal)?;
+
+        let step = futures::select! {
+            event = input.next().fuse() => Step::Input(event),
+            notification = next_notification(events).fuse() => Step::Notification(notification),
+        };
+        match step {
+            Step::Input(None) => return Ok(()),
+            Step::Input(Some(event)) => {
+                if let Event::Key(key) = event.context(error::AccessTerminal)? {
+                    if handle_key(client, app, key).await? {
+                        return Ok(());
+                    }
+                }
+            }
+            Step::Notification(Ok(Some(_))) => app.set_clips(load_clips(client).await?),
+            Step::Notification(Ok(None)) | Step::Notification(Err(_)) => {
+                *events = None;
+                app.status = "Live updates stopped, press r to reload clips".to_owned();
+            }
+        }
+    }
+}
+
+async fn next_notification(
+    events: &mut Option<ClipboardEventStream>,
+) -> Result<Option<ClipboardNotification>, GrpcClientError> {
+    match events {
+        Some(events) => events.next().await,
+        None => futures::future::pending().await,
+    }
+}
+
+async fn load_clips(client: &mut GrpcClient) -> Result<Vec<ClipboardData>, Error> {
+    let opts = ListOptions { preview_length: Some(PREVIEW_LENGTH), ..Default::default() };
+    let (clips, _total) = client.list_page(&opts).await?;
+    Ok(clips)
+}
+
+// Returns `true` if the user quits.
+async fn handle_key(client: &mut GrpcClient, app: &mut App, key: KeyEvent) -> Result<bool, Error> {
+    if key.kind != KeyEventKind::Press {
+        return Ok(false);
+    }
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        return Ok(true);
+    }
+
+    if app.filtering {
+        match key.code {
+            KeyCode::Esc => {
+                app.filtering = false;
+                app.set_query(String::new());
+            }
+            KeyCode::Enter => app.filtering = false,
+            KeyCode::Backspace => {
+                let mut query = app.query.clone();
+                query.pop();
+                app.set_query(query);
+            }
+            KeyCode::Char(c) => {
+                let query = format!("{}{}", app.query, c);
+                app.set_query(query);
+            }
+            KeyCode::Up => app.select_previous(),
+            KeyCode::Down => app.select_next(),
+            _ => {}
+        }
+        return Ok(false);
+    }
+
+    app.status.clear();
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+        KeyCode::Char('/') => app.filtering = true,
+        KeyCode::Char('j') | KeyCode::Down => app.select_next(),
+        KeyCode::Char('k') | KeyCode::Up => app.select_previous(),
+        KeyCode::Char('g') | KeyCode::Home => app.selected = 0,
+        KeyCode::Char('G') | KeyCode::End => app.selected = app.matches.len().saturating_sub(1),
+        KeyCode::Char('r') => app.set_clips(load_clips(client).await?),
+        KeyCode::Enter => {
+            if let Some(id) = app.selected_clip().map(|clip| clip.id) {
+                client.mark_as_clipboard(id).await?;
+                app.status = format!("Promoted {:016x} to clipboard", id);
+                app.set_clips(load_clips(client).await?);
+            }
+        }
+        KeyCode::Char('p') => {
+            if let Some((id, pinned)) = app.selected_clip().map(|clip| (clip.id, clip.pinned)) {
+                if pinned {
+                    client.unpin(id).await?;
+                    app.status = format!("Unpinned {:016x}", id);
+                } else {
+                    client.pin(id).await?;
+                    app.status = format!("Pinned {:016x}", id);
+                }
+                app.set_clips(load_clips(client).await?);
+            }
+        }
+        KeyCode::Char('d') | KeyCode::Delete => {
+            if let Some(id) = app.selected_clip().map(|clip| clip.id) {
+                client.remove(id).await?;
+                app.status = format!("Removed {:016x}", id);
+                app.set_clips(load_clips(client).await?);
+            }
+        }
+        _ => {}
+    }
+    Ok(false)
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(frame.size());
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[0]);
+
+    let now = SystemTime::now();
+    let items: Vec<_> = app
+        .matches
+        .iter()
+        .map(|&index| {
+            let clip = &app.clips[index];
+            let pin = if clip.pinned { '*' } else { ' ' };
+            let time = relative_time(clip.timestamp, now);
+            ListItem::new(format!("{} {:>10}  {}", pin, time, clip.printable_data(None)))
+        })
+        .collect();
+    let title = format!(" clips {}/{} ", app.matches.len(), app.clips.len());
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    if !app.matches.is_empty() {
+        state.select(Some(app.selected));
+    }
+    frame.render_stateful_widget(list, columns[0], &mut state);
+
+    let (title, content) = match app.preview.as_ref().or_else(|| app.selected_clip()) {
+        Some(clip) => (preview_title(clip), preview_content(clip)),
+        None => (" preview ".to_owned(), String::new()),
+    };
+    let preview = Paragraph::new(content)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(preview, columns[1]);
+
+    let status = if app.filtering || !app.query.is_empty() {
+        format!("/{}", app.query)
+    } else if !app.status.is_empty() {
+        app.status.clone()
+    } else {
+        HELP.to_owned()
+    };
+    frame.render_widget(Paragraph::new(status), rows[1]);
+}
+
+fn preview_title(clip: &ClipboardData) -> String {
+    let mut title = format!(" {:016x} {}", clip.id, clip.kind());
+    if let Some(ref application) = clip.application {
+        title.push_str(&format!(" from {}", application));
+    }
+    if !clip.tags.is_empty() {
+        let tags: Vec<_> = clip.tags.iter().map(String::as_str).collect();
+        title.push_str(&format!(" [{}]", tags.join(",")));
+    }
+    title.push(' ');
+    title
+}
+
+fn preview_content(clip: &ClipboardData) -> String {
+    if clip.is_text() {
+        clip.data.clone()
+    } else {
+        clip.printable_data(None)
+    }
+}
+
+/// State of the browser, `matches` holds the indices of the clips which match
+/// `query`, best matches first.
+#[derive(Debug, Default)]
+struct App {
+    clips: Vec<ClipboardData>,
+    matches: Vec<usize>,
+    query: String,
+    filtering: bool,
+    selected: usize,
+
+    // the full selected clip
+    preview: Option<ClipboardData>,
+    status: String,
+}
+
+impl App {
+    fn selected_clip(&self) -> Option<&ClipboardData> {
+        self.matches.get(self.selected).map(|&index| &self.clips[index])
+    }
+
+    // Replaces the clips and keeps the selected clip selected if it is still
+    // listed.
+    fn set_clips(&mut self, clips: Vec<ClipboardData>) {
+        let selected = self.selected_clip().map(|clip| clip.id);
+        self.clips = clips;
+        self.update_matches();
+        if let Some(position) = selected
+            .and_then(|id| self.matches.iter().position(|&index| self.clips[index].id == id))
+        {
+            self.selected = position;
+        }
+    }
+
+    fn set_query(&mut self, query: String) {
+        self.query = query;
+        self.selected = 0;
+        self.update_matches();
+    }
+
+    fn update_matches(&mut self) {
+        if self.query.is_empty() {
+            self.matches = (0..self.clips.len()).collect();
+        } else {
+            let mut scored: Vec<_> = self
+                .clips
+                .iter()
+                .enumerate()
+                .filter_map(|(index, clip)| {
+                    fuzzy_score(&self.query, &clip.data).map(|score| (index, score))
+                })
+                .collect();
+            scored.sort_by_key(|&(index, score)| (Reverse(score), index));
+            self.matches = scored.into_iter().map(|(index, _)| index).collect();
+        }
+        self.selected = self.selected.min(self.matches.len().saturating_sub(1));
+    }
+
+    fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    fn select_previous(&mut self) { self.selected = self.selected.saturating_sub(1); }
+}
+
+#[cfg(test)]
+mod tests {
+    use clipcat::ClipboardData;
+
+    use super::App;
+
+    fn clips() -> Vec<ClipboardData> {
+        vec![
+            ClipboardData::new_clipboard("cargo build --release"),
+            ClipboardData::new_clipboard("https://example.com"),
+            ClipboardData::new_clipboard("clipcat tui"),
+        ]
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut app = App::default();
+        app.set_clips(clips());
+        assert_eq!(app.matches, vec![0, 1, 2]);
+
+        app.set_query("ct".to_owned());
+        assert_eq!(app.matches, vec![2]);
+        assert_eq!(app.selected_clip().map(|clip| clip.data.as_str()), Some("clipcat tui"));
+
+        app.set_query("zzz".to_owned());
+        assert!(app.matches.is_empty());
+        assert!(app.selected_clip().is_none());
+
+        app.set_query(String::new());
+        assert_eq!(app.matches, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut app = App::default();
+        app.set_clips(clips());
+        app.select_previous();
+        assert_eq!(app.selected, 0);
+        app.select_next();
+        app.select_next();
+        app.select_next();
+        assert_eq!(app.selected, 2);
+
+        // the selected clip stays selected when a new clip is captured
+        let mut updated = clips();
+        updated.insert(0, ClipboardData::new_clipboard("new clip"));
+        app.set_clips(updated);
+        assert_eq!(app.selected, 3);
+        assert_eq!(app.selected_clip().map(|clip| clip.data.as_str()), Some("clipcat tui"));
+
+        // and the selection moves up if it is removed
+        app.set_clips(clips()[..1].to_vec());
+        assert_eq!(app.selected, 0);
+    }
+}
@@ -0,0 +1,63 @@
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+
+// Sockets passed by systemd start at this file descriptor, see
+// sd_listen_fds(3).
+#[cfg(unix)]
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Returns whether systemd started clipcatd for a socket unit and passed its
+/// sockets.
+pub fn is_activated() -> bool {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let fds = std::env::var("LISTEN_FDS").ok();
+    listen_fds(pid.as_deref(), fds.as_deref(), std::process::id()) > 0
+}
+
+/// Takes the first socket passed by systemd, the variables which pass them are
+/// removed so that commands started by clipcatd do not take them as well.
+#[cfg(unix)]
+pub fn take_listener() -> Option<RawFd> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let fds = std::env::var("LISTEN_FDS").ok();
+    for name in &["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(name);
+    }
+
+    match listen_fds(pid.as_deref(), fds.as_deref(), std::process::id()) {
+        0 => None,
+        count => {
+            if count > 1 {
+                tracing::warn!("{} sockets are passed by systemd, only the first is served", count);
+            }
+            unsafe {
+                libc::fcntl(LISTEN_FDS_START, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+            Some(LISTEN_FDS_START)
+        }
+    }
+}
+
+// Number of sockets passed to `own_pid`, sockets are meant for another
+// process if `pid` differs, e.g. for the parent of a forked daemon.
+fn listen_fds(pid: Option<&str>, fds: Option<&str>, own_pid: u32) -> usize {
+    match pid.and_then(|pid| pid.parse::<u32>().ok()) {
+        Some(pid) if pid == own_pid => fds.and_then(|fds| fds.parse().ok()).unwrap_or(0),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::activation::listen_fds;
+
+    #[test]
+    fn test_listen_fds() {
+        assert_eq!(listen_fds(Some("42"), Some("1"), 42), 1);
+        assert_eq!(listen_fds(Some("42"), Some("2"), 42), 2);
+        assert_eq!(listen_fds(Some("41"), Some("1"), 42), 0);
+        assert_eq!(listen_fds(None, Some("1"), 42), 0);
+        assert_eq!(listen_fds(Some("42"), None, 42), 0);
+        assert_eq!(listen_fds(Some("42"), Some("many"), 42), 0);
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use snafu::ResultExt;
+
+use clipcat::{export::Export, ClipboardData};
+
+const SNAPSHOT_PREFIX: &str = "clipcat-";
+const SNAPSHOT_SUFFIX: &str = ".json";
+
+#[derive(Debug, Snafu)]
+pub enum BackupError {
+    #[snafu(display("Could not create directory {}: {}", path.display(), source))]
+    CreateDirectory { path: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Could not serialize snapshot: {}", source))]
+    SerializeSnapshot { source: serde_json::Error },
+
+    #[snafu(display("Could not write snapshot {}: {}", path.display(), source))]
+    WriteSnapshot { path: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Could not list snapshots in {}: {}", path.display(), source))]
+    ListSnapshots { path: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Could not remove snapshot {}: {}", path.display(), source))]
+    RemoveSnapshot { path: PathBuf, source: std::io::Error },
+}
+
+/// Writes snapshots of the history to a directory as JSON exports, which can
+/// be restored with `clipcatctl restore`. Snapshots are plaintext, they are
+/// only readable by the owner.
+pub struct BackupManager {
+    directory: PathBuf,
+    keep: usize,
+}
+
+impl BackupManager {
+    /// Keeps the latest `keep` snapshots, all snapshots are kept if `keep` is
+    /// 0.
+    #[inline]
+    pub fn new<P: AsRef<Path>>(directory: P, keep: usize) -> BackupManager {
+        BackupManager { directory: directory.as_ref().to_path_buf(), keep }
+    }
+
+    /// Writes a snapshot of `clips` which is named after `time`, returns the
+    /// path of the snapshot. A snapshot never replaces an earlier one which
+    /// is named after the same time.
+    pub fn snapshot(
+        &self,
+        clips: &[ClipboardData],
+        time: SystemTime,
+    ) -> Result<PathBuf, BackupError> {
+        create_private_dir(&self.directory)
+            .context(CreateDirectory { path: self.directory.clone() })?;
+
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let (secs, mut nanos) = (since_epoch.as_secs(), since_epoch.subsec_nanos());
+        let path = loop {
+            let path = self
+                .directory
+                .join(format!("{}{:010}-{:09}{}", SNAPSHOT_PREFIX, secs, nanos, SNAPSHOT_SUFFIX));
+            if !path.exists() {
+                break path;
+            }
+            nanos += 1;
+        };
+        let data = serde_json::to_vec(&Export::new(clips)).context(SerializeSnapshot)?;
+
+        // the snapshot is renamed into place so an interrupted write never
+        // leaves a truncated snapshot behind
+        let tmp_path = path.with_extension("tmp");
+        write_private_file(&tmp_path, &data).context(WriteSnapshot { path: tmp_path.clone() })?;
+        std::fs::rename(&tmp_path, &path).context(WriteSnapshot { path: path.clone() })?;
+        Ok(path)
+    }
+
+    /// Lists snapshots, the oldest snapshot first.
+    pub fn snapshots(&self) -> Result<Vec<PathBuf>, BackupError> {
+        let entries = match std::fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(source) => {
+                return Err(BackupError::ListSnapshots { path: self.directory.clone(), source })
+            }
+        };
+
+        let mut snapshots = Vec::new();
+        for entry in entries {
+            let entry = entry.context(ListSnapshots { path: self.directory.clone() })?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with(SNAPSHOT_PREFIX) && name.ends_with(SNAPSHOT_SUFFIX) {
+                snapshots.push(entry.path());
+            }
+        }
+        // timestamps in names are zero padded, so names sort by time
+        snapshots.sort();
+        Ok(snapshots)
+    }
+
+    /// Removes all but the latest `keep` snapshots, returns the removed
+    /// snapshots.
+    pub fn prune(&self) -> Result<Vec<PathBuf>, BackupError> {
+        let snapshots = self.snapshots()?;
+        if self.keep == 0 || snapshots.len() <= self.keep {
+            return Ok(Vec::new());
+        }
+
+        let excess = snapshots.len() - self.keep;
+        let removed: Vec<_> = snapshots.into_iter().take(excess).collect();
+        for path in &removed {
+            std::fs::remove_file(path).context(RemoveSnapshot { path: path.clone() })?;
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(unix)]
+fn create_private_dir(directory: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    std::fs::DirBuilder::new().recursive(true).mode(0o700).create(directory)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(directory: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(directory)
+}
+
+fn write_private_file(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(content)?;
+    file.sync_data()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use clipcat::{export::Export, ClipboardData};
+
+    use crate::backup::BackupManager;
+
+    #[test]
+    fn test_snapshot_and_prune() {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let dir = std::env::temp_dir().join(format!("clipcat-backup-{}", nanos));
+        let backup = BackupManager::new(&dir, 2);
+        assert!(backup.snapshots().unwrap().is_empty());
+
+        let clips = vec![ClipboardData::new_clipboard("clipcat")];
+        let paths: Vec<_> = (0..3)
+            .map(|i| {
+                let time = UNIX_EPOCH + Duration::from_secs(1_600_000_000 + i);
+                backup.snapshot(&clips, time).unwrap()
+            })
+            .collect();
+        assert_eq!(paths[0].file_name().unwrap(), "clipcat-1600000000-000000000.json");
+        assert_eq!(backup.snapshots().unwrap(), paths);
+
+        assert_eq!(backup.prune().unwrap(), paths[..1].to_vec());
+        assert_eq!(backup.snapshots().unwrap(), paths[1..].to_vec());
+
+        let export: Export = serde_json::from_slice(&std::fs::read(&paths[2]).unwrap()).unwrap();
+        assert_eq!(export.into_clips().unwrap(), clips);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = std::fs::metadata(&paths[2]).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // snapshots taken at the same time do not replace each other
+        let time = UNIX_EPOCH + Duration::from_millis(1_600_000_010_500);
+        let first = backup.snapshot(&clips, time).unwrap();
+        let second = backup.snapshot(&clips, time).unwrap();
+        assert_eq!(first.file_name().unwrap(), "clipcat-1600000010-500000000.json");
+        assert_ne!(first, second);
+        assert!(first.exists());
+        assert_eq!(backup.snapshots().unwrap()[2..].to_vec(), vec![first, second]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
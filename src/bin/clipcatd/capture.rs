@@ -0,0 +1,300 @@
+use std::collections::BTreeSet;
+
+use regex::RegexSet;
+
+use clipcat::{
+    color::{self, Color},
+    language::Language,
+    mime, ClipboardEvent,
+};
+
+use crate::{
+    config::{self, OversizedClip},
+    transform::Transformer,
+};
+
+// Appended to truncated text clips.
+const ELLIPSIS: &str = "…";
+
+/// What scripts and plugins decided about a clip which is kept.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Annotation {
+    pub tags: BTreeSet<String>,
+    pub sensitive: bool,
+}
+
+/// Decides which captured clips are stored, clips which are denied never reach
+/// ClipboardManager or the history.
+pub struct CaptureFilter {
+    honor_sensitive_hints: bool,
+    expire_sensitive_hints: bool,
+    deny_regex: RegexSet,
+    deny_applications: Vec<String>,
+    sensitive_regex: RegexSet,
+    max_clip_size: Option<usize>,
+    oversized_clip: OversizedClip,
+    transformer: Transformer,
+    tag_colors: bool,
+    tag_languages: bool,
+}
+
+impl CaptureFilter {
+    pub fn new(config: &config::Capture) -> Result<CaptureFilter, regex::Error> {
+        let deny_regex = RegexSet::new(&config.deny_regex)?;
+        let deny_applications =
+            config.deny_applications.iter().map(|app| app.to_lowercase()).collect();
+        let sensitive_regex = RegexSet::new(&config.sensitive_regex)?;
+        Ok(CaptureFilter {
+            honor_sensitive_hints: config.honor_sensitive_hints,
+            expire_sensitive_hints: config.expire_sensitive_hints,
+            deny_regex,
+            deny_applications,
+            sensitive_regex,
+            max_clip_size: config.max_clip_size,
+            oversized_clip: config.oversized_clip,
+            transformer: Transformer::new(&config.transforms),
+            tag_colors: config.tag_colors,
+            tag_languages: config.tag_languages,
+        })
+    }
+
+    /// Returns `true` if the clip of `event` must not be stored.
+    pub fn is_denied(&self, event: &ClipboardEvent) -> bool {
+        if self.honor_sensitive_hints && !self.expire_sensitive_hints && event.sensitive {
+            return true;
+        }
+
+        if let Some(ref application) = event.application {
+            let application = application.to_lowercase();
+            if self.deny_applications.contains(&application) {
+                return true;
+            }
+        }
+
+        mime::is_text(&event.mime) && self.deny_regex.is_match(&event.data)
+    }
+
+    /// Returns `true` if the clip of `event` is stored as a sensitive clip
+    /// which expires.
+    pub fn is_sensitive(&self, event: &ClipboardEvent) -> bool {
+        if self.honor_sensitive_hints && self.expire_sensitive_hints && event.sensitive {
+            return true;
+        }
+
+        mime::is_text(&event.mime) && self.sensitive_regex.is_match(&event.data)
+    }
+
+    /// Applies the transforms of its clipboard type to the text of `event`,
+    /// returns `false` if nothing is left of the clip.
+    pub fn transform(&self, event: &mut ClipboardEvent) -> bool {
+        if !self.transformer.is_enabled(event.clipboard_type) || !mime::is_text(&event.mime) {
+            return true;
+        }
+
+        let data = self.transformer.apply(event.clipboard_type, &event.data);
+        if data != event.data {
+            event.data = data;
+            // formatted representations carry the original text
+            event.targets.clear();
+        }
+        !event.data.is_empty()
+    }
+
+    /// Returns the tags which are detected in the clip of `event`.
+    pub fn detect_tags(&self, event: &ClipboardEvent) -> Vec<&'static str> {
+        let mut tags = Vec::new();
+        if !mime::is_text(&event.mime) {
+            return tags;
+        }
+        if self.tag_colors && Color::parse(&event.data).is_some() {
+            tags.push(color::COLOR_TAG);
+        } else if self.tag_languages {
+            tags.extend(Language::detect(&event.data).map(Language::tag));
+        }
+        tags
+    }
+
+    /// Applies `max_clip_size` to the clip of `event`, returns `false` if the
+    /// clip must not be stored.
+    pub fn limit_size(&self, event: &mut ClipboardEvent) -> bool {
+        let max_size = match self.max_clip_size {
+            Some(max_size) => max_size,
+            None => return true,
+        };
+
+        let size = event.data.len()
+            + event.binary.len()
+            + event.targets.values().map(Vec::len).sum::<usize>();
+        if size <= max_size {
+            return true;
+        }
+
+        if self.oversized_clip == OversizedClip::Skip || !mime::is_text(&event.mime) {
+            return false;
+        }
+
+        let mut end = max_size.saturating_sub(ELLIPSIS.len()).min(event.data.len());
+        while !event.data.is_char_boundary(end) {
+            end -= 1;
+        }
+        event.data.truncate(end);
+        event.data.push_str(ELLIPSIS);
+        // formatted representations can not be truncated consistently
+        event.targets.clear();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clipcat::{mime, ClipboardEvent, ClipboardType};
+
+    use crate::{
+        capture::CaptureFilter,
+        config::{self, OversizedClip, Transform, Transforms},
+    };
+
+    #[test]
+    fn test_deny_regex() {
+        let config = config::Capture {
+            deny_regex: vec![r"^\d{6}$".to_owned(), r"^[0-9a-f]{32,}$".to_owned()],
+            ..Default::default()
+        };
+        let filter = CaptureFilter::new(&config).unwrap();
+
+        assert!(filter.is_denied(&ClipboardEvent::new_clipboard("123456")));
+        assert!(filter.is_denied(&ClipboardEvent::new_clipboard("ab12".repeat(10))));
+        assert!(!filter.is_denied(&ClipboardEvent::new_clipboard("call me at 123456 please")));
+        assert!(!filter.is_denied(&ClipboardEvent::new_clipboard("clipcat")));
+
+        let filter = CaptureFilter::new(&config::Capture::default()).unwrap();
+        assert!(!filter.is_denied(&ClipboardEvent::new_clipboard("123456")));
+
+        let config = config::Capture { deny_regex: vec!["(".to_owned()], ..Default::default() };
+        assert!(CaptureFilter::new(&config).is_err());
+    }
+
+    #[test]
+    fn test_deny_applications() {
+        let config = config::Capture {
+            deny_applications: vec!["KeePassXC".to_owned(), "Bitwarden".to_owned()],
+            ..Default::default()
+        };
+        let filter = CaptureFilter::new(&config).unwrap();
+
+        let mut event = ClipboardEvent::new_clipboard("password");
+        assert!(!filter.is_denied(&event));
+
+        event.application = Some("keepassxc".to_owned());
+        assert!(filter.is_denied(&event));
+
+        event.application = Some("Firefox".to_owned());
+        assert!(!filter.is_denied(&event));
+    }
+
+    #[test]
+    fn test_sensitive_hints() {
+        let mut event = ClipboardEvent::new_clipboard("password");
+        event.sensitive = true;
+
+        let filter = CaptureFilter::new(&config::Capture::default()).unwrap();
+        assert!(filter.is_denied(&event));
+
+        let config = config::Capture { honor_sensitive_hints: false, ..Default::default() };
+        let filter = CaptureFilter::new(&config).unwrap();
+        assert!(!filter.is_denied(&event));
+        assert!(!filter.is_sensitive(&event));
+
+        let config = config::Capture { expire_sensitive_hints: true, ..Default::default() };
+        let filter = CaptureFilter::new(&config).unwrap();
+        assert!(!filter.is_denied(&event));
+        assert!(filter.is_sensitive(&event));
+    }
+
+    #[test]
+    fn test_limit_size() {
+        let mut event = ClipboardEvent::new_clipboard("clipcat");
+        let filter = CaptureFilter::new(&config::Capture::default()).unwrap();
+        assert!(filter.limit_size(&mut event));
+
+        let config = config::Capture { max_clip_size: Some(16), ..Default::default() };
+        let filter = CaptureFilter::new(&config).unwrap();
+        assert!(filter.limit_size(&mut event));
+        assert_eq!(event.data, "clipcat");
+
+        let mut event = ClipboardEvent::new_clipboard("clipcat ".repeat(4));
+        assert!(!filter.limit_size(&mut event));
+
+        let config = config::Capture {
+            max_clip_size: Some(16),
+            oversized_clip: OversizedClip::Truncate,
+            ..Default::default()
+        };
+        let filter = CaptureFilter::new(&config).unwrap();
+        assert!(filter.limit_size(&mut event));
+        assert_eq!(event.data, "clipcat clipc…");
+        assert!(event.data.len() <= 16);
+
+        let mut event =
+            ClipboardEvent::from_bytes(mime::IMAGE_PNG, vec![0; 32], ClipboardType::Clipboard);
+        assert!(!filter.limit_size(&mut event));
+    }
+
+    #[test]
+    fn test_transform() {
+        let config = config::Capture {
+            transforms: Transforms {
+                clipboard: vec![Transform::StripAnsi, Transform::Trim],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let filter = CaptureFilter::new(&config).unwrap();
+
+        let mut event = ClipboardEvent::new_clipboard(" \x1b[32mclipcat\x1b[0m\n");
+        event.targets.insert("text/html".to_owned(), b"<b>clipcat</b>".to_vec());
+        assert!(filter.transform(&mut event));
+        assert_eq!(event.data, "clipcat");
+        assert!(event.targets.is_empty());
+
+        let mut event = ClipboardEvent::new_primary(" clipcat ");
+        assert!(filter.transform(&mut event));
+        assert_eq!(event.data, " clipcat ");
+
+        assert!(!filter.transform(&mut ClipboardEvent::new_clipboard("  \n")));
+    }
+
+    #[test]
+    fn test_detect_tags() {
+        let filter = CaptureFilter::new(&config::Capture::default()).unwrap();
+        assert_eq!(filter.detect_tags(&ClipboardEvent::new_clipboard("#ff8800")), &["color"]);
+        assert_eq!(
+            filter.detect_tags(&ClipboardEvent::new_clipboard("hsl(0, 0%, 0%)")),
+            &["color"]
+        );
+        assert!(filter.detect_tags(&ClipboardEvent::new_clipboard("clipcat")).is_empty());
+
+        let json = ClipboardEvent::new_clipboard("{\"clip\": \"cat\"}");
+        assert_eq!(filter.detect_tags(&json), &["lang:json"]);
+
+        let config =
+            config::Capture { tag_colors: false, tag_languages: false, ..Default::default() };
+        let filter = CaptureFilter::new(&config).unwrap();
+        assert!(filter.detect_tags(&ClipboardEvent::new_clipboard("#ff8800")).is_empty());
+        assert!(filter.detect_tags(&json).is_empty());
+    }
+
+    #[test]
+    fn test_sensitive_regex() {
+        let config = config::Capture {
+            sensitive_regex: vec![r"^\d{4}-\d{4}$".to_owned()],
+            ..Default::default()
+        };
+        let filter = CaptureFilter::new(&config).unwrap();
+
+        let event = ClipboardEvent::new_clipboard("1234-5678");
+        assert!(!filter.is_denied(&event));
+        assert!(filter.is_sensitive(&event));
+        assert!(!filter.is_sensitive(&ClipboardEvent::new_clipboard("clipcat")));
+    }
+}
@@ -1,7 +1,6 @@
 use std::{
     net::IpAddr,
     path::{Path, PathBuf},
-    time::Duration,
 };
 
 use snafu::ResultExt;
@@ -9,8 +8,10 @@ use structopt::StructOpt;
 use tokio::runtime::Runtime;
 
 use crate::{
-    config::{Config, ConfigError},
+    activation,
+    config::{Config, ConfigError, LogFormat},
     error::{self, Error},
+    log_file::LogOutput,
     worker,
 };
 
@@ -56,7 +57,7 @@ impl Command {
 
     fn load_config(&self) -> Result<Config, ConfigError> {
         let config_file = &self.config_file.clone().unwrap_or_else(Config::default_path);
-        let mut config = Config::load(&config_file)?;
+        let mut config = Config::load(config_file)?;
 
         config.daemonize = !self.no_daemon;
 
@@ -96,7 +97,7 @@ impl Command {
                 let config_text =
                     toml::to_string_pretty(&Config::default()).expect("Config is serializable");
                 std::io::stdout()
-                    .write_all(&config_text.as_bytes())
+                    .write_all(config_text.as_bytes())
                     .expect("failed to write to stdout");
                 return Ok(());
             }
@@ -104,10 +105,14 @@ impl Command {
         }
 
         let config = self.load_config().context(error::LoadConfig)?;
-        run_clipcatd(config, self.replace)
+        let replace = self.replace;
+        let config_file = self.config_file.clone().unwrap_or_else(Config::default_path);
+        let config_loader: worker::ConfigLoader = Box::new(move || self.load_config());
+        run_clipcatd(config, config_file, config_loader, replace)
     }
 }
 
+#[cfg(unix)]
 #[inline]
 fn kill_other(pid: u64) -> Result<(), Error> {
     let ret = unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
@@ -117,43 +122,119 @@ fn kill_other(pid: u64) -> Result<(), Error> {
     Ok(())
 }
 
-fn run_clipcatd(config: Config, replace: bool) -> Result<(), Error> {
-    let daemonize = config.daemonize;
-    let pid_file = PidFile::from(config.pid_file.clone());
-    if daemonize {
-        if pid_file.exists() && replace {
-            let pid = pid_file.try_load()?;
-            kill_other(pid)?;
+#[cfg(unix)]
+fn detach(pid_file: &PidFile, replace: bool) -> Result<(), Error> {
+    if pid_file.exists() && replace {
+        let pid = pid_file.try_load()?;
+        kill_other(pid)?;
 
-            // sleep for a while
-            std::thread::sleep(Duration::from_millis(200));
-        }
+        // sleep for a while
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
 
-        let daemonize = daemonize::Daemonize::new().pid_file(pid_file.clone_path());
-        if let Err(err) = daemonize.start() {
-            return Err(Error::Daemonize { source: err });
-        }
+    let daemonize = daemonize::Daemonize::new().pid_file(pid_file.clone_path());
+    if let Err(err) = daemonize.start() {
+        return Err(Error::Daemonize { source: err });
+    }
+    Ok(())
+}
+
+fn run_clipcatd(
+    config: Config,
+    config_file: PathBuf,
+    config_loader: worker::ConfigLoader,
+    replace: bool,
+) -> Result<(), Error> {
+    // Windows cannot fork, the daemon stays in the foreground there, and
+    // systemd passes its sockets to the process it starts
+    let daemonize = config.daemonize && cfg!(unix) && !activation::is_activated();
+    let pid_file = PidFile::from(config.pid_file.clone());
+    #[cfg(unix)]
+    if daemonize {
+        detach(&pid_file, replace)?;
     }
+    #[cfg(not(unix))]
+    let _ = replace;
+
+    let runtime = Runtime::new().context(error::InitializeTokioRuntime)?;
 
-    {
+    let log_level = {
         use tracing_subscriber::prelude::*;
 
-        let fmt_layer = tracing_subscriber::fmt::layer().with_target(false);
+        // the exporter sends batches of spans on the runtime of the daemon
+        #[cfg(feature = "otlp")]
+        let otlp_layer = match config.otlp {
+            Some(ref otlp) => {
+                let _guard = runtime.enter();
+                Some(tracing_opentelemetry::layer().with_tracer(otlp_tracer(otlp)?))
+            }
+            None => None,
+        };
+
+        let output = match config.log.file_path {
+            Some(ref path) => LogOutput::file(
+                path,
+                config.log.rotation,
+                config.log.max_size,
+                config.log.max_files,
+            )
+            .context(error::OpenLogFile { path: path.clone() })?,
+            None => LogOutput::Stderr,
+        };
+        // files do not get the escape codes of colors
+        let ansi = !output.is_file();
+        let (text_layer, json_layer) = match config.log.format {
+            LogFormat::Text => (
+                Some(
+                    tracing_subscriber::fmt::layer()
+                        .with_target(false)
+                        .with_ansi(ansi)
+                        .with_writer(output),
+                ),
+                None,
+            ),
+            LogFormat::Json => (
+                None,
+                Some(
+                    tracing_subscriber::fmt::layer().json().with_target(false).with_writer(output),
+                ),
+            ),
+        };
         let level_filter = tracing_subscriber::filter::LevelFilter::from_level(config.log_level);
-
-        let registry = tracing_subscriber::registry().with(level_filter).with(fmt_layer);
+        // the log level is changed when the configuration is reloaded
+        let (level_filter, log_level) = tracing_subscriber::reload::Layer::new(level_filter);
+
+        let registry =
+            tracing_subscriber::registry().with(level_filter).with(text_layer).with(json_layer);
+        #[cfg(feature = "otlp")]
+        let registry = registry.with(otlp_layer);
+        #[cfg(unix)]
         match tracing_journald::layer() {
             Ok(layer) => registry.with(layer).init(),
             Err(_err) => {
                 registry.init();
             }
         }
-    }
+        #[cfg(not(unix))]
+        registry.init();
+        log_level
+    };
 
     tracing::info!("{} is initializing, pid: {}", clipcat::DAEMON_PROGRAM_NAME, std::process::id());
+    #[cfg(feature = "otlp")]
+    if let Some(ref otlp) = config.otlp {
+        tracing::info!("Spans are exported to {}", otlp.endpoint);
+    }
+    #[cfg(not(feature = "otlp"))]
+    if config.otlp.is_some() {
+        tracing::warn!("clipcatd is built without the otlp feature, [otlp] is ignored");
+    }
 
-    let runtime = Runtime::new().context(error::InitializeTokioRuntime)?;
-    runtime.block_on(worker::start(config))?;
+    let result = runtime.block_on(worker::start(config, config_file, config_loader, log_level));
+    // spans which are not exported yet are flushed
+    #[cfg(feature = "otlp")]
+    opentelemetry::global::shutdown_tracer_provider();
+    result?;
 
     if daemonize {
         pid_file.remove()?;
@@ -163,22 +244,41 @@ fn run_clipcatd(config: Config, replace: bool) -> Result<(), Error> {
     Ok(())
 }
 
+#[cfg(feature = "otlp")]
+fn otlp_tracer(otlp: &crate::config::Otlp) -> Result<opentelemetry::sdk::trace::Tracer, Error> {
+    use opentelemetry::{
+        sdk::{trace, Resource},
+        KeyValue,
+    };
+
+    let resource = Resource::new(vec![KeyValue::new("service.name", otlp.service_name.clone())]);
+    opentelemetry_otlp::new_pipeline()
+        .with_endpoint(otlp.endpoint.clone())
+        .with_trace_config(trace::config().with_resource(resource))
+        .install_batch(opentelemetry::runtime::Tokio)
+        .context(error::InstallOtlpExporter)
+}
+
 struct PidFile {
     path: PathBuf,
 }
 
 impl PidFile {
+    #[cfg(unix)]
     #[inline]
     fn exists(&self) -> bool { self.path.exists() }
 
+    #[cfg(unix)]
     #[inline]
     fn clone_path(&self) -> PathBuf { self.path().to_path_buf() }
 
+    #[cfg(unix)]
     #[inline]
     fn path(&self) -> &Path { &self.path }
 
+    #[cfg(unix)]
     fn try_load(&self) -> Result<u64, Error> {
-        let pid_data = std::fs::read_to_string(&self)
+        let pid_data = std::fs::read_to_string(self)
             .context(error::ReadPidFile { filename: self.clone_path() })?;
         let pid = pid_data.trim().parse().context(error::ParseProcessId { value: pid_data })?;
         Ok(pid)
@@ -1,12 +1,15 @@
 use std::{
     net::IpAddr,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use app_dirs::AppDataType;
 use serde::{Deserialize, Serialize};
 use snafu::{ResultExt, Snafu};
 
+use crate::history::HistoryDriverType;
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     pub daemonize: bool,
@@ -17,16 +20,399 @@ pub struct Config {
     #[serde(default = "Config::default_max_history")]
     pub max_history: usize,
 
+    /// Total size in bytes of unpinned clips, large and old clips are evicted
+    /// first when it is exceeded.
+    #[serde(default)]
+    pub max_history_bytes: Option<usize>,
+
+    /// Bytes of clips which are held in memory, large and old clips are
+    /// spilled to disk with `[spill]`, or evicted otherwise, when it is
+    /// exceeded.
+    #[serde(default)]
+    pub memory_budget: Option<usize>,
+
+    /// Unpinned clips are removed once they were last copied longer ago than
+    /// this, e.g. `14d`.
+    #[serde(default, with = "max_age")]
+    pub max_age: Option<Duration>,
+
+    /// Primary clips get their own capacity if it is set, `max_history` then
+    /// only applies to clipboard clips.
+    #[serde(default)]
+    pub max_primary_history: Option<usize>,
+
+    /// Bumps the existing clip instead of adding a duplicate when the same
+    /// content is copied again.
+    #[serde(default = "Config::default_deduplicate")]
+    pub deduplicate: bool,
+
+    /// Order in which clips are listed, `recency` or `frecency`.
+    #[serde(default)]
+    pub ranking: clipcat::Ranking,
+
     #[serde(default = "Config::default_history_file_path")]
     pub history_file_path: PathBuf,
 
+    /// Stores primary clips in this file instead of `history_file_path` if it
+    /// is set.
+    #[serde(default)]
+    pub primary_history_file_path: Option<PathBuf>,
+
+    #[serde(default)]
+    pub history_driver: HistoryDriverType,
+
+    /// Loads previews of large clips from the history at start up, their
+    /// bodies are loaded from the history when they are needed.
+    #[serde(default = "Config::default_lazy_history")]
+    pub lazy_history: bool,
+
     #[serde(default = "Config::default_log_level", with = "serde_with::rust::display_fromstr")]
     pub log_level: tracing::Level,
 
+    #[serde(default)]
+    pub log: Log,
+
     #[serde(default)]
     pub monitor: Monitor,
 
+    #[serde(default)]
+    pub capture: Capture,
+
     pub grpc: Grpc,
+
+    #[serde(default)]
+    pub history_encryption: Option<HistoryEncryption>,
+
+    #[serde(default)]
+    pub history_compression: HistoryCompression,
+
+    #[serde(default)]
+    pub history_journal: HistoryJournal,
+
+    #[serde(default)]
+    pub snippets: Vec<SnippetConfig>,
+
+    #[serde(default)]
+    pub backup: Option<Backup>,
+
+    #[serde(default)]
+    pub spill: Option<Spill>,
+
+    #[serde(default)]
+    pub notification: Option<Notification>,
+
+    #[serde(default)]
+    pub dbus: Dbus,
+
+    #[serde(default)]
+    pub http: Option<Http>,
+
+    #[serde(default)]
+    pub metrics: Option<Metrics>,
+
+    #[serde(default)]
+    pub otlp: Option<Otlp>,
+
+    #[serde(default)]
+    pub tray: Option<Tray>,
+
+    #[serde(default)]
+    pub hotkeys: Vec<Hotkey>,
+
+    #[serde(default)]
+    pub paste: Paste,
+
+    #[serde(default)]
+    pub trash: Trash,
+
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+
+    #[serde(default)]
+    pub mqtt: Option<Mqtt>,
+
+    /// Reloads the configuration file when it is changed, like `SIGHUP`.
+    #[serde(default)]
+    pub watch_config: bool,
+}
+
+/// Publishes the metadata and a preview of each recorded clip to `topic` on an
+/// MQTT broker, the content of sensitive clips is never published.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Mqtt {
+    pub host: String,
+
+    #[serde(default = "Mqtt::default_port")]
+    pub port: u16,
+
+    #[serde(default = "Mqtt::default_client_id")]
+    pub client_id: String,
+
+    #[serde(default = "Mqtt::default_topic")]
+    pub topic: String,
+
+    /// 0, 1 or 2.
+    #[serde(default = "Mqtt::default_qos")]
+    pub qos: u8,
+
+    #[serde(default)]
+    pub retain: bool,
+
+    #[serde(default)]
+    pub username: Option<String>,
+
+    #[serde(default)]
+    pub password: Option<String>,
+
+    #[serde(default)]
+    pub tls: Option<MqttTls>,
+}
+
+/// Connects to the broker with TLS, and authenticates with a client
+/// certificate if `client_certificate` and `client_key` are set.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MqttTls {
+    pub ca_certificate: PathBuf,
+
+    #[serde(default)]
+    pub client_certificate: Option<PathBuf>,
+
+    #[serde(default)]
+    pub client_key: Option<PathBuf>,
+}
+
+/// POSTs a JSON payload to `url` whenever a clip of `events` is captured or
+/// removed. The payload is rendered from `template` if it is set, and signed
+/// with HMAC-SHA256 if `secret` is set.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+
+    #[serde(default = "Webhook::default_events")]
+    pub events: Vec<WebhookEvent>,
+
+    #[serde(default)]
+    pub template: Option<String>,
+
+    #[serde(default)]
+    pub secret: Option<String>,
+
+    /// Seconds until a request is given up.
+    #[serde(default = "Webhook::default_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    Captured,
+    Removed,
+}
+
+/// Serves List, Get, Insert and Remove as JSON over HTTP, requests have to
+/// carry `grpc.token` as a bearer token if it is set. Requires the
+/// `http_gateway` feature.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Http {
+    #[serde(default = "Http::default_host")]
+    pub host: IpAddr,
+
+    #[serde(default = "Http::default_port")]
+    pub port: u16,
+
+    /// Origins of web pages which may open `/events` besides the gateway
+    /// itself, WebSocket upgrades from other origins are refused.
+    #[serde(default)]
+    pub allow_origins: Vec<String>,
+}
+
+/// Serves counters and gauges of clipcatd on `GET /metrics` for Prometheus,
+/// requests have to carry `grpc.token` as a bearer token if it is set.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+    #[serde(default = "Metrics::default_host")]
+    pub host: IpAddr,
+
+    #[serde(default = "Metrics::default_port")]
+    pub port: u16,
+}
+
+/// Exports spans of captures, history writes and gRPC requests to an
+/// OpenTelemetry collector over OTLP. Requires the `otlp` feature.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Otlp {
+    /// gRPC endpoint of the collector.
+    #[serde(default = "Otlp::default_endpoint")]
+    pub endpoint: String,
+
+    #[serde(default = "Otlp::default_service_name")]
+    pub service_name: String,
+}
+
+/// How clips are pasted when `clipcat-menu --paste` inserts them, the commands
+/// default to `xdotool` on X11 and `wtype` on Wayland.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Paste {
+    /// Milliseconds until the paste, for the menu to close and the previous
+    /// window to get focus back.
+    #[serde(default = "Paste::default_delay")]
+    pub delay: u64,
+
+    #[serde(default)]
+    pub clipboard_command: Option<String>,
+
+    #[serde(default)]
+    pub primary_command: Option<String>,
+}
+
+/// Clips which are removed are kept in the trash so that the removal can be
+/// undone, sensitive clips are never kept.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Trash {
+    /// The trash is disabled if it is 0.
+    #[serde(default = "Trash::default_max_clips")]
+    pub max_clips: usize,
+
+    /// Seconds for which removed clips are kept.
+    #[serde(default = "Trash::default_ttl")]
+    pub ttl: u64,
+}
+
+/// Grabs `key` globally, e.g. `super+v`, and runs `action` when it is pressed.
+/// The desktop portal is asked for the hotkeys on Wayland.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Hotkey {
+    pub key: String,
+
+    pub action: HotkeyAction,
+
+    /// The command which `command` runs, `menu` runs `clipcat-menu` unless it
+    /// is set.
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotkeyAction {
+    Menu,
+    PromotePrevious,
+    ToggleMonitor,
+    Command,
+}
+
+/// Shows a tray icon with the state of the monitor and a menu of the latest
+/// `recent_clips` clips. Requires the `tray` feature.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Tray {
+    #[serde(default = "Tray::default_recent_clips")]
+    pub recent_clips: usize,
+
+    #[serde(default = "Tray::default_preview_length")]
+    pub preview_length: usize,
+}
+
+/// Serves core operations on the D-Bus session bus as `org.clipcat.Clipcat`,
+/// and the interface of Klipper as `org.kde.klipper` if `klipper` is set.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Dbus {
+    #[serde(default)]
+    pub enable: bool,
+
+    #[serde(default)]
+    pub klipper: bool,
+}
+
+/// Shows a desktop notification with a preview of each recorded clip, clips
+/// shorter than `min_length` characters or bytes are not notified.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    #[serde(default = "Notification::default_enable_clipboard")]
+    pub enable_clipboard: bool,
+
+    #[serde(default)]
+    pub enable_primary: bool,
+
+    #[serde(default = "Notification::default_min_length")]
+    pub min_length: usize,
+
+    #[serde(default = "Notification::default_preview_length")]
+    pub preview_length: usize,
+
+    /// Milliseconds until the notification is closed.
+    #[serde(default = "Notification::default_timeout")]
+    pub timeout: u32,
+}
+
+/// Writes a snapshot of the history to `directory` every `interval` seconds and
+/// keeps the latest `keep` snapshots, all snapshots are kept if `keep` is 0.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Backup {
+    pub directory: PathBuf,
+
+    #[serde(default = "Backup::default_interval")]
+    pub interval: u64,
+
+    #[serde(default = "Backup::default_keep")]
+    pub keep: usize,
+}
+
+/// Keeps the bodies of clips which are larger than `threshold` bytes in files
+/// in `directory` and only their previews in memory, `directory` defaults to
+/// `<history_file_path>.spill`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Spill {
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+
+    #[serde(default = "Spill::default_threshold")]
+    pub threshold: usize,
+}
+
+/// A snippet which is always offered alongside the history, its content is
+/// either given inline as `content` or read from `file`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SnippetConfig {
+    pub name: String,
+
+    #[serde(default)]
+    pub content: Option<String>,
+
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+}
+
+/// Compresses clips in the history which are at least `threshold` bytes long.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HistoryCompression {
+    #[serde(default = "HistoryCompression::default_enable")]
+    pub enable: bool,
+
+    #[serde(default = "HistoryCompression::default_threshold")]
+    pub threshold: usize,
+}
+
+/// Appends captured clips to a journal next to the history file, so that they
+/// survive a crash, and compacts it into the history file every
+/// `compact_interval` seconds.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HistoryJournal {
+    #[serde(default = "HistoryJournal::default_enable")]
+    pub enable: bool,
+
+    #[serde(default = "HistoryJournal::default_compact_interval")]
+    pub compact_interval: u64,
+}
+
+/// Encrypts the history with a passphrase, which is read from `passphrase_file`
+/// or from the output of `passphrase_command`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEncryption {
+    #[serde(default)]
+    pub passphrase_file: Option<PathBuf>,
+
+    #[serde(default)]
+    pub passphrase_command: Option<String>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -34,12 +420,309 @@ pub struct Monitor {
     pub load_current: bool,
     pub enable_clipboard: bool,
     pub enable_primary: bool,
+
+    #[serde(default)]
+    pub backend: clipcat::ClipboardBackendType,
+
+    /// Copies clips between primary and clipboard as they are captured.
+    #[serde(default)]
+    pub sync: SelectionSync,
+
+    /// Owns `CLIPBOARD_MANAGER` on X11, so the clipboard is saved when the
+    /// application which owns it exits.
+    #[serde(default = "Monitor::default_clipboard_manager")]
+    pub clipboard_manager: bool,
+
+    /// Converts the line endings of captured text, e.g. the CRLF of Windows
+    /// applications.
+    #[serde(default)]
+    pub line_endings: clipcat::LineEndings,
+
+    /// X11 displays such as `:1` or Wayland seats which are monitored besides
+    /// the default one, e.g. of nested X servers.
+    #[serde(default)]
+    pub displays: Vec<String>,
+
+    /// Offers clips which are marked on all displays instead of the default
+    /// one only.
+    #[serde(default)]
+    pub mirror_promotions: bool,
+
+    /// Stops recording clips while logind reports the session as locked.
+    #[serde(default = "Monitor::default_pause_on_lock")]
+    pub pause_on_lock: bool,
+}
+
+/// How and where clipcatd writes its log, journald receives it as well on
+/// Unix.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Log {
+    #[serde(default)]
+    pub format: LogFormat,
+
+    /// Writes the log to this file instead of stderr.
+    #[serde(default)]
+    pub file_path: Option<PathBuf>,
+
+    #[serde(default)]
+    pub rotation: LogRotation,
+
+    /// Rotates the file before it grows beyond this size in bytes.
+    #[serde(default)]
+    pub max_size: Option<u64>,
+
+    /// Number of rotated files which are kept.
+    #[serde(default = "Log::default_max_files")]
+    pub max_files: usize,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+
+    /// One JSON object per line.
+    Json,
+}
+
+/// Rotates the log file when the hour or the day in UTC is over.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    #[default]
+    Never,
+    Hourly,
+    Daily,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionSync {
+    /// Each selection keeps its own content.
+    Off,
+
+    /// Selected text can be pasted with Ctrl+V.
+    PrimaryToClipboard,
+
+    /// Copied clips can be pasted with the middle button.
+    ClipboardToPrimary,
+
+    #[default]
+    Both,
+}
+
+impl SelectionSync {
+    /// Returns whether clips captured from `clipboard_type` are copied to the
+    /// other selection.
+    pub fn copies_from(self, clipboard_type: clipcat::ClipboardType) -> bool {
+        use clipcat::ClipboardType;
+
+        match self {
+            SelectionSync::Off => false,
+            SelectionSync::PrimaryToClipboard => clipboard_type == ClipboardType::Primary,
+            SelectionSync::ClipboardToPrimary => clipboard_type == ClipboardType::Clipboard,
+            SelectionSync::Both => true,
+        }
+    }
+}
+
+/// Rules for clips which are captured from the clipboard.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Capture {
+    /// Clips whose owner asks clipboard managers not to store them, e.g. with
+    /// `x-kde-passwordManagerHint`, are never stored.
+    #[serde(default = "Capture::default_honor_sensitive_hints")]
+    pub honor_sensitive_hints: bool,
+
+    /// Text clips matching any of these regular expressions are never stored.
+    #[serde(default)]
+    pub deny_regex: Vec<String>,
+
+    /// Clips copied from applications with these window classes are never
+    /// stored, the comparison is case-insensitive.
+    #[serde(default)]
+    pub deny_applications: Vec<String>,
+
+    /// Clips with sensitive hints are stored as sensitive clips instead of
+    /// being skipped, this only applies if `honor_sensitive_hints` is set.
+    #[serde(default)]
+    pub expire_sensitive_hints: bool,
+
+    /// Text clips matching any of these regular expressions are stored as
+    /// sensitive clips.
+    #[serde(default)]
+    pub sensitive_regex: Vec<String>,
+
+    /// Sensitive clips are removed from the daemon and the system clipboard
+    /// this many seconds after they are copied.
+    #[serde(default = "Capture::default_sensitive_timeout")]
+    pub sensitive_timeout: u64,
+
+    /// Clips larger than this many bytes, including all representations, are
+    /// handled according to `oversized_clip`.
+    #[serde(default)]
+    pub max_clip_size: Option<usize>,
+
+    #[serde(default)]
+    pub oversized_clip: OversizedClip,
+
+    #[serde(default)]
+    pub secrets: Secrets,
+
+    #[serde(default)]
+    pub transforms: Transforms,
+
+    /// Rhai scripts which filter, transform or tag clips before they are
+    /// stored, in order.
+    #[serde(default)]
+    pub scripts: Vec<PathBuf>,
+
+    /// WebAssembly plugins which filter, transform or tag clips after
+    /// `scripts`, in order.
+    #[serde(default)]
+    pub plugins: Vec<PathBuf>,
+
+    /// Text clips which hold a color value like `#ff8800` or `rgb(255, 136,
+    /// 0)` are tagged with `color`.
+    #[serde(default = "Capture::default_tag_colors")]
+    pub tag_colors: bool,
+
+    /// Code-like text clips are tagged with their probable language, e.g.
+    /// `lang:rust`.
+    #[serde(default = "Capture::default_tag_languages")]
+    pub tag_languages: bool,
+}
+
+/// Transforms which are applied in order to text clips of each clipboard type
+/// before they are stored.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Transforms {
+    #[serde(default)]
+    pub clipboard: Vec<Transform>,
+
+    #[serde(default)]
+    pub primary: Vec<Transform>,
+
+    /// Query parameters which `clean_urls` removes in addition to the built-in
+    /// ones, a trailing `*` matches any suffix.
+    #[serde(default)]
+    pub tracking_parameters: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// Removes leading and trailing whitespace.
+    Trim,
+
+    /// Replaces runs of spaces and tabs with a single space, line breaks are
+    /// kept.
+    CollapseWhitespace,
+
+    /// Removes ANSI escape sequences, e.g. colors of terminal output.
+    StripAnsi,
+
+    /// Replaces `\r\n` and `\r` with `\n`.
+    NormalizeLineEndings,
+
+    /// Removes tracking parameters like `utm_source` or `fbclid` from URLs.
+    CleanUrls,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OversizedClip {
+    /// Does not store the clip.
+    #[default]
+    Skip,
+
+    /// Stores the beginning of text clips followed by an ellipsis, other clips
+    /// are skipped.
+    Truncate,
+}
+
+/// Actions taken when a secret of each kind is found in a text clip.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Secrets {
+    #[serde(default = "Secrets::default_aws_access_key")]
+    pub aws_access_key: SecretAction,
+
+    #[serde(default = "Secrets::default_private_key")]
+    pub private_key: SecretAction,
+
+    #[serde(default = "Secrets::default_jwt")]
+    pub jwt: SecretAction,
+
+    /// Long random looking tokens, this is prone to false positives and is
+    /// disabled by default.
+    #[serde(default = "Secrets::default_high_entropy")]
+    pub high_entropy: SecretAction,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretAction {
+    /// Stores the clip as it is.
+    Off,
+
+    /// Does not store the clip.
+    Block,
+
+    /// Stores the clip with the secret replaced by a placeholder.
+    Mask,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Grpc {
     pub host: IpAddr,
     pub port: u16,
+
+    /// Serves gRPC on this unix socket instead of `host` and `port` if it is
+    /// set.
+    #[serde(default)]
+    pub socket_path: Option<PathBuf>,
+
+    /// Rejects requests which do not carry this token if it is set.
+    #[serde(default)]
+    pub token: Option<String>,
+
+    #[serde(default)]
+    pub tls: Option<GrpcTls>,
+
+    #[serde(default)]
+    pub web: Option<GrpcWeb>,
+}
+
+/// Serves gRPC-Web alongside gRPC, so in-browser clients call the services
+/// without a proxy.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct GrpcWeb {
+    /// Origins which are allowed to call the services, `"*"` allows any origin.
+    /// Cross-origin requests are refused if it is empty.
+    #[serde(default)]
+    pub allow_origins: Vec<String>,
+
+    /// Allows browsers to send credentials such as cookies, it can not be
+    /// combined with the `"*"` origin.
+    #[serde(default)]
+    pub allow_credentials: bool,
+
+    /// Seconds a browser caches the result of a preflight request.
+    #[serde(default = "GrpcWeb::default_max_age")]
+    pub max_age: u64,
+}
+
+/// TLS settings of the gRPC server, all files are PEM encoded.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct GrpcTls {
+    pub certificate: PathBuf,
+    pub key: PathBuf,
+
+    /// Requires clients to present a certificate signed by this CA if it is
+    /// set.
+    #[serde(default)]
+    pub client_ca_certificate: Option<PathBuf>,
 }
 
 impl Default for Config {
@@ -48,36 +731,477 @@ impl Default for Config {
             daemonize: true,
             pid_file: Config::default_pid_file_path(),
             max_history: Config::default_max_history(),
+            max_history_bytes: None,
+            memory_budget: None,
+            max_age: None,
+            max_primary_history: None,
+            deduplicate: Config::default_deduplicate(),
+            ranking: clipcat::Ranking::default(),
             history_file_path: Config::default_history_file_path(),
+            primary_history_file_path: None,
+            history_driver: HistoryDriverType::default(),
+            lazy_history: Config::default_lazy_history(),
             log_level: Config::default_log_level(),
+            log: Default::default(),
             monitor: Default::default(),
+            capture: Default::default(),
             grpc: Default::default(),
+            history_encryption: None,
+            history_compression: Default::default(),
+            history_journal: Default::default(),
+            snippets: Vec::new(),
+            backup: None,
+            spill: None,
+            notification: None,
+            dbus: Default::default(),
+            http: None,
+            metrics: None,
+            otlp: None,
+            tray: None,
+            hotkeys: Vec::new(),
+            paste: Default::default(),
+            trash: Default::default(),
+            webhooks: Vec::new(),
+            mqtt: None,
+            watch_config: false,
         }
     }
 }
 
+impl Monitor {
+    #[inline]
+    pub fn default_clipboard_manager() -> bool { true }
+
+    #[inline]
+    pub fn default_pause_on_lock() -> bool { true }
+}
+
 impl Default for Monitor {
     fn default() -> Monitor {
-        Monitor { load_current: true, enable_clipboard: true, enable_primary: true }
+        Monitor {
+            load_current: true,
+            enable_clipboard: true,
+            enable_primary: true,
+            backend: clipcat::ClipboardBackendType::Auto,
+            sync: SelectionSync::default(),
+            clipboard_manager: Monitor::default_clipboard_manager(),
+            line_endings: clipcat::LineEndings::default(),
+            displays: Vec::new(),
+            mirror_promotions: false,
+            pause_on_lock: Monitor::default_pause_on_lock(),
+        }
+    }
+}
+
+impl From<Monitor> for clipcat::ClipboardMonitorOptions {
+    fn from(monitor: Monitor) -> clipcat::ClipboardMonitorOptions {
+        let Monitor {
+            load_current,
+            enable_clipboard,
+            enable_primary,
+            backend,
+            clipboard_manager,
+            line_endings,
+            displays,
+            ..
+        } = monitor;
+        clipcat::ClipboardMonitorOptions {
+            load_current,
+            enable_clipboard,
+            enable_primary,
+            backend,
+            manage_clipboard: clipboard_manager,
+            line_endings,
+            displays,
+        }
     }
 }
 
-impl Into<clipcat::ClipboardMonitorOptions> for Monitor {
-    fn into(self) -> clipcat::ClipboardMonitorOptions {
-        let Monitor { load_current, enable_clipboard, enable_primary } = self;
-        clipcat::ClipboardMonitorOptions { load_current, enable_clipboard, enable_primary }
+impl Default for Capture {
+    fn default() -> Capture {
+        Capture {
+            honor_sensitive_hints: Capture::default_honor_sensitive_hints(),
+            deny_regex: Vec::new(),
+            deny_applications: Vec::new(),
+            expire_sensitive_hints: false,
+            sensitive_regex: Vec::new(),
+            sensitive_timeout: Capture::default_sensitive_timeout(),
+            max_clip_size: None,
+            oversized_clip: OversizedClip::default(),
+            secrets: Default::default(),
+            transforms: Default::default(),
+            scripts: Vec::new(),
+            plugins: Vec::new(),
+            tag_colors: Capture::default_tag_colors(),
+            tag_languages: Capture::default_tag_languages(),
+        }
+    }
+}
+
+impl Capture {
+    #[inline]
+    pub fn default_honor_sensitive_hints() -> bool { true }
+
+    #[inline]
+    pub fn default_sensitive_timeout() -> u64 { 45 }
+
+    #[inline]
+    pub fn default_tag_colors() -> bool { true }
+
+    #[inline]
+    pub fn default_tag_languages() -> bool { true }
+}
+
+impl Default for Secrets {
+    fn default() -> Secrets {
+        Secrets {
+            aws_access_key: Secrets::default_aws_access_key(),
+            private_key: Secrets::default_private_key(),
+            jwt: Secrets::default_jwt(),
+            high_entropy: Secrets::default_high_entropy(),
+        }
     }
 }
 
+impl Secrets {
+    #[inline]
+    pub fn default_aws_access_key() -> SecretAction { SecretAction::Block }
+
+    #[inline]
+    pub fn default_private_key() -> SecretAction { SecretAction::Block }
+
+    #[inline]
+    pub fn default_jwt() -> SecretAction { SecretAction::Mask }
+
+    #[inline]
+    pub fn default_high_entropy() -> SecretAction { SecretAction::Off }
+}
+
 impl Default for Grpc {
     fn default() -> Grpc {
         Grpc {
             host: clipcat::DEFAULT_GRPC_HOST.parse().expect("Parse default gRPC host"),
             port: clipcat::DEFAULT_GRPC_PORT,
+            socket_path: None,
+            token: None,
+            tls: None,
+            web: None,
         }
     }
 }
 
+impl Default for HistoryCompression {
+    fn default() -> HistoryCompression {
+        HistoryCompression {
+            enable: HistoryCompression::default_enable(),
+            threshold: HistoryCompression::default_threshold(),
+        }
+    }
+}
+
+impl HistoryJournal {
+    #[inline]
+    pub fn default_enable() -> bool { true }
+
+    #[inline]
+    pub fn default_compact_interval() -> u64 { 5 * 60 }
+
+    /// The journal of the history file at `history_file_path`.
+    pub fn path(history_file_path: &Path) -> PathBuf {
+        let mut path = history_file_path.as_os_str().to_owned();
+        path.push(".journal");
+        PathBuf::from(path)
+    }
+}
+
+impl Default for HistoryJournal {
+    fn default() -> HistoryJournal {
+        HistoryJournal {
+            enable: HistoryJournal::default_enable(),
+            compact_interval: HistoryJournal::default_compact_interval(),
+        }
+    }
+}
+
+impl Backup {
+    #[inline]
+    pub fn default_interval() -> u64 { 60 * 60 }
+
+    #[inline]
+    pub fn default_keep() -> usize { 24 }
+}
+
+impl Spill {
+    #[inline]
+    pub fn default_threshold() -> usize { clipcat::spill::DEFAULT_SPILL_THRESHOLD }
+
+    /// The directory of spilled clips of the history file at
+    /// `history_file_path`.
+    pub fn directory(&self, history_file_path: &Path) -> PathBuf {
+        match self.directory {
+            Some(ref directory) => directory.clone(),
+            None => {
+                let mut path = history_file_path.as_os_str().to_owned();
+                path.push(".spill");
+                PathBuf::from(path)
+            }
+        }
+    }
+}
+
+impl GrpcWeb {
+    #[inline]
+    pub fn default_max_age() -> u64 { 24 * 60 * 60 }
+
+    #[inline]
+    pub fn allows_any_origin(&self) -> bool {
+        self.allow_origins.iter().any(|origin| origin == "*")
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.allow_credentials && self.allows_any_origin() {
+            return Err(ConfigError::CredentialsWithAnyOrigin);
+        }
+        Ok(())
+    }
+}
+
+impl Http {
+    #[inline]
+    pub fn default_host() -> IpAddr {
+        clipcat::DEFAULT_WEBUI_HOST.parse().expect("Parse default HTTP host")
+    }
+
+    #[inline]
+    pub fn default_port() -> u16 { clipcat::DEFAULT_WEBUI_PORT }
+}
+
+impl Log {
+    #[inline]
+    pub fn default_max_files() -> usize { 5 }
+}
+
+impl Default for Log {
+    fn default() -> Log {
+        Log {
+            format: LogFormat::default(),
+            file_path: None,
+            rotation: LogRotation::default(),
+            max_size: None,
+            max_files: Log::default_max_files(),
+        }
+    }
+}
+
+impl Metrics {
+    #[inline]
+    pub fn default_host() -> IpAddr {
+        clipcat::DEFAULT_METRICS_HOST.parse().expect("Parse default metrics host")
+    }
+
+    #[inline]
+    pub fn default_port() -> u16 { clipcat::DEFAULT_METRICS_PORT }
+}
+
+impl Otlp {
+    #[inline]
+    pub fn default_endpoint() -> String { "http://127.0.0.1:4317".to_owned() }
+
+    #[inline]
+    pub fn default_service_name() -> String { clipcat::DAEMON_PROGRAM_NAME.to_owned() }
+}
+
+impl Default for Otlp {
+    fn default() -> Otlp {
+        Otlp { endpoint: Otlp::default_endpoint(), service_name: Otlp::default_service_name() }
+    }
+}
+
+impl Paste {
+    #[inline]
+    pub fn default_delay() -> u64 { 200 }
+}
+
+impl Default for Paste {
+    fn default() -> Paste {
+        Paste { delay: Paste::default_delay(), clipboard_command: None, primary_command: None }
+    }
+}
+
+impl Trash {
+    #[inline]
+    pub fn default_max_clips() -> usize { clipcat::trash::DEFAULT_TRASH_CAPACITY }
+
+    #[inline]
+    pub fn default_ttl() -> u64 { clipcat::trash::DEFAULT_TRASH_TTL.as_secs() }
+}
+
+impl Default for Trash {
+    fn default() -> Trash {
+        Trash { max_clips: Trash::default_max_clips(), ttl: Trash::default_ttl() }
+    }
+}
+
+impl From<Paste> for clipcat::paste::PasteOptions {
+    fn from(paste: Paste) -> clipcat::paste::PasteOptions {
+        let Paste { delay, clipboard_command, primary_command } = paste;
+        clipcat::paste::PasteOptions {
+            delay: Duration::from_millis(delay),
+            clipboard_command,
+            primary_command,
+        }
+    }
+}
+
+impl Tray {
+    #[inline]
+    pub fn default_recent_clips() -> usize { 10 }
+
+    #[inline]
+    pub fn default_preview_length() -> usize { 40 }
+}
+
+impl Default for Tray {
+    fn default() -> Tray {
+        Tray {
+            recent_clips: Tray::default_recent_clips(),
+            preview_length: Tray::default_preview_length(),
+        }
+    }
+}
+
+impl Default for Http {
+    fn default() -> Http {
+        Http { host: Http::default_host(), port: Http::default_port(), allow_origins: Vec::new() }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Metrics {
+        Metrics { host: Metrics::default_host(), port: Metrics::default_port() }
+    }
+}
+
+impl Webhook {
+    #[inline]
+    pub fn default_events() -> Vec<WebhookEvent> {
+        vec![WebhookEvent::Captured, WebhookEvent::Removed]
+    }
+
+    #[inline]
+    pub fn default_timeout() -> u64 { 10 }
+}
+
+impl Mqtt {
+    #[inline]
+    pub fn default_port() -> u16 { 1883 }
+
+    #[inline]
+    pub fn default_client_id() -> String { "clipcatd".to_owned() }
+
+    #[inline]
+    pub fn default_topic() -> String { "clipcat/clips".to_owned() }
+
+    #[inline]
+    pub fn default_qos() -> u8 { 1 }
+}
+
+impl WebhookEvent {
+    #[inline]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookEvent::Captured => "captured",
+            WebhookEvent::Removed => "removed",
+        }
+    }
+}
+
+impl Notification {
+    #[inline]
+    pub fn default_enable_clipboard() -> bool { true }
+
+    #[inline]
+    pub fn default_min_length() -> usize { 1 }
+
+    #[inline]
+    pub fn default_preview_length() -> usize { 80 }
+
+    #[inline]
+    pub fn default_timeout() -> u32 { 3000 }
+}
+
+impl Default for Notification {
+    fn default() -> Notification {
+        Notification {
+            enable_clipboard: Notification::default_enable_clipboard(),
+            enable_primary: false,
+            min_length: Notification::default_min_length(),
+            preview_length: Notification::default_preview_length(),
+            timeout: Notification::default_timeout(),
+        }
+    }
+}
+
+impl HistoryCompression {
+    #[inline]
+    pub fn default_enable() -> bool { true }
+
+    #[inline]
+    pub fn default_threshold() -> usize { 4096 }
+
+    #[inline]
+    pub fn threshold(&self) -> Option<usize> {
+        if self.enable {
+            Some(self.threshold)
+        } else {
+            None
+        }
+    }
+}
+
+impl SnippetConfig {
+    pub fn load(&self) -> Result<clipcat::Snippet, ConfigError> {
+        let data = match (&self.content, &self.file) {
+            (Some(content), _) => content.clone(),
+            (None, Some(file)) => std::fs::read_to_string(file)
+                .context(ReadSnippetFile { filename: file.to_path_buf() })?,
+            (None, None) => return Err(ConfigError::NoSnippetContent { name: self.name.clone() }),
+        };
+        Ok(clipcat::Snippet { name: self.name.clone(), data })
+    }
+}
+
+impl HistoryEncryption {
+    pub fn read_passphrase(&self) -> Result<String, ConfigError> {
+        let passphrase = match (&self.passphrase_file, &self.passphrase_command) {
+            (Some(file), _) => std::fs::read_to_string(file)
+                .context(ReadPassphraseFile { filename: file.to_path_buf() })?,
+            (None, Some(command)) => {
+                let output = std::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .stderr(std::process::Stdio::inherit())
+                    .output()
+                    .context(RunPassphraseCommand { command: command.to_owned() })?;
+                if !output.status.success() {
+                    return Err(ConfigError::PassphraseCommandFailed {
+                        command: command.to_owned(),
+                        status: output.status,
+                    });
+                }
+                String::from_utf8_lossy(&output.stdout).into_owned()
+            }
+            (None, None) => return Err(ConfigError::NoPassphraseSource),
+        };
+
+        let passphrase = passphrase.trim_end_matches(&['\r', '\n'][..]).to_owned();
+        if passphrase.is_empty() {
+            return Err(ConfigError::EmptyPassphrase);
+        }
+        Ok(passphrase)
+    }
+}
+
 impl Config {
     #[inline]
     pub fn default_path() -> PathBuf {
@@ -105,6 +1229,12 @@ impl Config {
     #[inline]
     pub fn default_max_history() -> usize { 50 }
 
+    #[inline]
+    pub fn default_deduplicate() -> bool { true }
+
+    #[inline]
+    pub fn default_lazy_history() -> bool { true }
+
     #[inline]
     pub fn default_pid_file_path() -> PathBuf {
         let mut path = std::env::var("XDG_RUNTIME_DIR")
@@ -125,10 +1255,38 @@ impl Config {
             config.max_history = Self::default_max_history();
         }
 
+        if let Some(ref web) = config.grpc.web {
+            web.validate()?;
+        }
+
         Ok(config)
     }
 }
 
+// `max_age` is written like `14d`, see `clipcat::age`.
+mod max_age {
+    use std::time::Duration;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        age: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match age {
+            Some(age) => serializer.serialize_some(&clipcat::age::format(*age)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        let age = Option::<String>::deserialize(deserializer)?;
+        age.map(|age| clipcat::age::parse(&age).map_err(D::Error::custom)).transpose()
+    }
+}
+
 #[derive(Debug, Snafu)]
 pub enum ConfigError {
     #[snafu(display("Could not open config from {}: {}", filename.display(), source))]
@@ -136,4 +1294,30 @@ pub enum ConfigError {
 
     #[snafu(display("Count not parse config from {}: {}", filename.display(), source))]
     ParseConfig { filename: PathBuf, source: toml::de::Error },
+
+    #[snafu(display("Could not read passphrase from {}: {}", filename.display(), source))]
+    ReadPassphraseFile { filename: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Could not run passphrase command `{}`: {}", command, source))]
+    RunPassphraseCommand { command: String, source: std::io::Error },
+
+    #[snafu(display("Passphrase command `{}` failed with {}", command, status))]
+    PassphraseCommandFailed { command: String, status: std::process::ExitStatus },
+
+    #[snafu(display("Either passphrase_file or passphrase_command is required"))]
+    NoPassphraseSource,
+
+    #[snafu(display("Passphrase is empty"))]
+    EmptyPassphrase,
+
+    #[snafu(display("Could not read snippet from {}: {}", filename.display(), source))]
+    ReadSnippetFile { filename: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Either content or file is required for snippet {}", name))]
+    NoSnippetContent { name: String },
+
+    #[snafu(display(
+        "gRPC-Web can not allow credentials for any origin, list the origins instead"
+    ))]
+    CredentialsWithAnyOrigin,
 }
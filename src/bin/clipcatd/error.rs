@@ -1,8 +1,16 @@
 use std::path::PathBuf;
 
 use snafu::Snafu;
+#[cfg(target_os = "linux")]
+use x11_clipboard::xcb;
 
-use crate::{config::ConfigError, history::HistoryError};
+#[cfg(feature = "plugins")]
+use crate::plugin::PluginError;
+#[cfg(feature = "scripts")]
+use crate::script::ScriptError;
+use crate::{
+    config::ConfigError, history::HistoryError, hotkey::HotkeyError, webhook::WebhookError,
+};
 
 #[derive(Debug, Snafu)]
 #[snafu(visibility = "pub")]
@@ -16,33 +24,150 @@ pub enum Error {
     #[snafu(display("Could not create HistoryManager, error: {}", source))]
     CreateHistoryManager { source: HistoryError },
 
+    #[snafu(display("Could not read passphrase of history, error: {}", source))]
+    ReadPassphrase { source: ConfigError },
+
+    #[snafu(display("Could not load snippet, error: {}", source))]
+    LoadSnippet { source: ConfigError },
+
+    #[cfg(feature = "scripts")]
+    #[snafu(display("Could not load script, error: {}", source))]
+    LoadScript { source: ScriptError },
+
+    #[cfg(feature = "plugins")]
+    #[snafu(display("Could not load plugin, error: {}", source))]
+    LoadPlugin { source: PluginError },
+
+    #[snafu(display("Could not create webhook, error: {}", source))]
+    CreateWebhook { source: WebhookError },
+
     #[snafu(display("Could not load HistoryManager, error: {}", source))]
     LoadHistoryManager { source: HistoryError },
 
+    #[snafu(display("Could not open directory of spilled clips {}, error: {}", path.display(), source))]
+    OpenSpillDirectory { path: PathBuf, source: std::io::Error },
+
+    #[allow(dead_code)]
     #[snafu(display("Could not clear HistoryManager, error: {}", source))]
     ClearHistoryManager { source: HistoryError },
 
     #[snafu(display("Could not create HistoryManager, error: {}", source))]
     CreateClipboardMonitor { source: clipcat::ClipboardError },
 
+    #[snafu(display("Could not compile regular expression of capture, error: {}", source))]
+    CompileCaptureRegex { source: regex::Error },
+
     #[snafu(display("Failed to parse socket address, error: {}", source))]
     ParseSockAddr { source: std::net::AddrParseError },
 
+    #[cfg(unix)]
     #[snafu(display("Failed to daemonize, error: {}", source))]
     Daemonize { source: daemonize::DaemonizeError },
 
+    #[cfg(unix)]
     #[snafu(display("Could not read PID file, filename: {}, error: {}", filename.display(), source))]
     ReadPidFile { filename: PathBuf, source: std::io::Error },
 
     #[snafu(display("Could not remove PID file, filename: {}, error: {}", pid_file.display(), source))]
     RemovePidFile { pid_file: PathBuf, source: std::io::Error },
 
+    #[cfg(unix)]
     #[snafu(display("Parse process id, value: {}, error: {}", value, source))]
     ParseProcessId { value: String, source: std::num::ParseIntError },
 
+    #[cfg(unix)]
     #[snafu(display("Failed to send SIGTERM to PID {}", pid))]
     SendSignalTerminal { pid: u64 },
 
+    #[snafu(display("Could not create directory {}, error: {}", path.display(), source))]
+    CreateSocketDirectory { path: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Could not bind unix socket {}, error: {}", path.display(), source))]
+    BindUnixSocket { path: PathBuf, source: std::io::Error },
+
+    #[cfg(unix)]
+    #[snafu(display("Could not listen on socket passed by systemd, error: {}", source))]
+    ListenActivatedSocket { source: std::io::Error },
+
+    #[snafu(display("Could not read {}, error: {}", filename.display(), source))]
+    ReadTlsFile { filename: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Could not configure TLS, error: {}", source))]
+    ConfigureTls { source: tonic::transport::Error },
+
     #[snafu(display("Failed to serve gRPC, error: {}", source))]
     ServeGrpc { source: tonic::transport::Error },
+
+    #[cfg(feature = "dbus")]
+    #[snafu(display("Could not connect to D-Bus session bus, error: {}", source))]
+    ConnectDbus { source: zbus::Error },
+
+    #[cfg(feature = "dbus")]
+    #[snafu(display("Could not own D-Bus name {}, error: {}", name, source))]
+    RequestDbusName { name: &'static str, source: zbus::fdo::Error },
+
+    #[cfg(feature = "dbus")]
+    #[snafu(display("Failed to serve D-Bus, error: {}", source))]
+    ServeDbus { source: zbus::Error },
+
+    #[snafu(display("Could not parse hotkey, error: {}", source))]
+    ParseHotkey { source: HotkeyError },
+
+    #[cfg(target_os = "linux")]
+    #[snafu(display("Could not connect to X11 server, error: {}", source))]
+    ConnectX11 { source: xcb::ConnError },
+
+    #[cfg(target_os = "linux")]
+    #[snafu(display("X11 server has no screen"))]
+    NoX11Screen,
+
+    #[cfg(target_os = "linux")]
+    #[snafu(display("Could not read keyboard mapping, error: {}", source))]
+    GetKeyboardMapping { source: xcb::GenericError },
+
+    #[cfg(feature = "dbus")]
+    #[snafu(display("Could not subscribe to desktop portal, error: {}", source))]
+    SubscribePortal { source: zbus::fdo::Error },
+
+    #[cfg(feature = "dbus")]
+    #[snafu(display("Invalid object path of desktop portal, error: {}", source))]
+    ParsePortalPath { source: zvariant::Error },
+
+    #[cfg(feature = "dbus")]
+    #[snafu(display("Could not call {} of desktop portal, error: {}", method, source))]
+    CallPortal { method: &'static str, source: zbus::Error },
+
+    #[cfg(feature = "dbus")]
+    #[snafu(display("Could not receive from desktop portal, error: {}", source))]
+    ReceivePortal { source: zbus::Error },
+
+    #[cfg(all(target_os = "linux", feature = "dbus"))]
+    #[snafu(display("Could not connect to D-Bus system bus, error: {}", source))]
+    ConnectSystemBus { source: zbus::Error },
+
+    #[cfg(all(target_os = "linux", feature = "dbus"))]
+    #[snafu(display("Could not find login session, error: {}", source))]
+    FindLoginSession { source: zbus::Error },
+
+    #[cfg(all(target_os = "linux", feature = "dbus"))]
+    #[snafu(display("Could not subscribe to login session, error: {}", source))]
+    SubscribeLoginSession { source: zbus::fdo::Error },
+
+    #[cfg(all(target_os = "linux", feature = "dbus"))]
+    #[snafu(display("Could not receive from login session, error: {}", source))]
+    ReceiveLoginSession { source: zbus::Error },
+
+    #[snafu(display("Could not open log file {}, error: {}", path.display(), source))]
+    OpenLogFile { path: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Could not watch configuration file, error: {}", source))]
+    WatchConfig { source: notify::Error },
+
+    #[cfg(feature = "otlp")]
+    #[snafu(display("Could not install OTLP exporter, error: {}", source))]
+    InstallOtlpExporter { source: opentelemetry::trace::TraceError },
+
+    #[cfg(feature = "dbus")]
+    #[snafu(display("Desktop portal rejected {} with response {}", method, response))]
+    PortalRejected { method: &'static str, response: u32 },
 }
@@ -0,0 +1,140 @@
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use std::{convert::TryInto, sync::Arc};
+
+use hmac::{Hmac, Mac, NewMac};
+use rand::{rngs::OsRng, RngCore};
+use sha2::Sha256;
+
+use clipcat::spill::SpillCipher;
+
+use crate::history::HistoryError;
+
+const MAGIC: &[u8] = b"CCE1";
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+const KDF_ROUNDS: u32 = 200_000;
+
+// Label of the key of row IDs, which is derived from the key of the cipher.
+const ID_KEY_LABEL: &[u8] = b"clipcat row ids";
+
+pub const SALT_LEN: usize = 16;
+
+/// Encrypts history entries with a key derived from a passphrase.
+pub struct Cipher {
+    cipher: XChaCha20Poly1305,
+    id_key: Hmac<Sha256>,
+    salt: Vec<u8>,
+}
+
+impl Cipher {
+    pub fn new(passphrase: &str, salt: &[u8]) -> Cipher {
+        let mut key = [0u8; KEY_LEN];
+        pbkdf2::pbkdf2::<Hmac<Sha256>>(passphrase.as_bytes(), salt, KDF_ROUNDS, &mut key);
+        let mut id_key = Hmac::<Sha256>::new_varkey(&key).expect("HMAC takes keys of any size");
+        id_key.update(ID_KEY_LABEL);
+        let id_key = Hmac::<Sha256>::new_varkey(&id_key.finalize().into_bytes())
+            .expect("HMAC takes keys of any size");
+        Cipher {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(&key)),
+            id_key,
+            salt: salt.to_vec(),
+        }
+    }
+
+    #[inline]
+    pub fn salt(&self) -> &[u8] { &self.salt }
+
+    pub fn generate_salt() -> Vec<u8> {
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        salt
+    }
+
+    #[inline]
+    pub fn is_encrypted(data: &[u8]) -> bool { data.starts_with(MAGIC) }
+
+    /// Returns the key of the row which holds clip `id`, IDs are hashes of the
+    /// content of clips, so they are keyed in order not to reveal it.
+    pub fn row_id(&self, id: u64) -> u64 {
+        let mut mac = self.id_key.clone();
+        mac.update(&id.to_le_bytes());
+        let digest = mac.finalize().into_bytes();
+        u64::from_le_bytes(digest[..8].try_into().expect("digest is 32 bytes long"))
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher
+            .encrypt(XNonce::from_slice(&nonce), plaintext)
+            .expect("plaintext is not too large");
+
+        let mut data = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        data
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, HistoryError> {
+        if !Self::is_encrypted(data) || data.len() < MAGIC.len() + NONCE_LEN {
+            return Err(HistoryError::Decrypt);
+        }
+
+        let (nonce, ciphertext) = data[MAGIC.len()..].split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| HistoryError::Decrypt)
+    }
+}
+
+/// Seals spilled clips like the entries of an encrypted history.
+pub struct HistorySpillCipher(pub Arc<Cipher>);
+
+impl SpillCipher for HistorySpillCipher {
+    #[inline]
+    fn seal(&self, content: &[u8]) -> Vec<u8> { self.0.encrypt(content) }
+
+    #[inline]
+    fn open(&self, content: &[u8]) -> Option<Vec<u8>> { self.0.decrypt(content).ok() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cipher;
+
+    #[test]
+    fn test_roundtrip() {
+        let salt = Cipher::generate_salt();
+        let cipher = Cipher::new("passphrase", &salt);
+
+        let data = cipher.encrypt(b"clipboard content");
+        assert!(Cipher::is_encrypted(&data));
+        assert_ne!(&data[..], &b"clipboard content"[..]);
+        assert_eq!(cipher.decrypt(&data).unwrap(), b"clipboard content".to_vec());
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let salt = Cipher::generate_salt();
+        let data = Cipher::new("passphrase", &salt).encrypt(b"clipboard content");
+
+        assert!(Cipher::new("wrong", &salt).decrypt(&data).is_err());
+        assert!(Cipher::new("passphrase", &salt).decrypt(b"plain text").is_err());
+    }
+
+    #[test]
+    fn test_row_id() {
+        let salt = Cipher::generate_salt();
+        let cipher = Cipher::new("passphrase", &salt);
+
+        assert_eq!(cipher.row_id(42), Cipher::new("passphrase", &salt).row_id(42));
+        assert_ne!(cipher.row_id(42), 42);
+        assert_ne!(cipher.row_id(42), cipher.row_id(43));
+        assert_ne!(cipher.row_id(42), Cipher::new("wrong", &salt).row_id(42));
+    }
+}
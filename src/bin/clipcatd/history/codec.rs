@@ -0,0 +1,532 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use clipcat::{ClipboardData, ClipboardType};
+
+use crate::history::{cipher::Cipher, HistoryError};
+
+pub const SALT_KEY: &str = "salt";
+pub const CHECK_KEY: &str = "check";
+const CHECK_VALUE: &[u8] = b"clipcat";
+
+// Prefix of compressed values, it never collides with serialized values since
+// it is neither a valid variant of `ClipboardValue` nor a sane length of
+// `LegacyClipboardValue::data`.
+const COMPRESSED_MAGIC: &[u8] = b"CCZ1";
+const COMPRESSION_LEVEL: i32 = 3;
+
+// Format written by clipcat 0.5 and earlier, only supports text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LegacyClipboardValue {
+    pub data: String,
+    pub timestamp: SystemTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum ClipboardValue {
+    V1 {
+        data: String,
+        timestamp: SystemTime,
+        mime: String,
+        binary: Vec<u8>,
+    },
+    V2 {
+        data: String,
+        timestamp: SystemTime,
+        mime: String,
+        binary: Vec<u8>,
+        targets: BTreeMap<String, Vec<u8>>,
+    },
+    V3 {
+        data: String,
+        timestamp: SystemTime,
+        mime: String,
+        binary: Vec<u8>,
+        targets: BTreeMap<String, Vec<u8>>,
+        pinned: bool,
+    },
+    V4 {
+        data: String,
+        timestamp: SystemTime,
+        mime: String,
+        binary: Vec<u8>,
+        targets: BTreeMap<String, Vec<u8>>,
+        pinned: bool,
+        tags: BTreeSet<String>,
+    },
+    V5 {
+        data: String,
+        timestamp: SystemTime,
+        mime: String,
+        binary: Vec<u8>,
+        targets: BTreeMap<String, Vec<u8>>,
+        pinned: bool,
+        tags: BTreeSet<String>,
+        use_count: u64,
+    },
+    V6 {
+        data: String,
+        timestamp: SystemTime,
+        mime: String,
+        binary: Vec<u8>,
+        targets: BTreeMap<String, Vec<u8>>,
+        pinned: bool,
+        tags: BTreeSet<String>,
+        use_count: u64,
+        application: Option<String>,
+    },
+    V7 {
+        data: String,
+        timestamp: SystemTime,
+        created_at: SystemTime,
+        mime: String,
+        binary: Vec<u8>,
+        targets: BTreeMap<String, Vec<u8>>,
+        pinned: bool,
+        tags: BTreeSet<String>,
+        use_count: u64,
+        application: Option<String>,
+    },
+    V8 {
+        id: u64,
+        data: String,
+        timestamp: SystemTime,
+        created_at: SystemTime,
+        mime: String,
+        binary: Vec<u8>,
+        targets: BTreeMap<String, Vec<u8>>,
+        pinned: bool,
+        tags: BTreeSet<String>,
+        use_count: u64,
+        application: Option<String>,
+    },
+}
+
+/// Converts clips into the values which are stored by history drivers, values
+/// are compressed if they are larger than the compression threshold and
+/// encrypted if a passphrase is provided.
+#[derive(Clone)]
+pub struct ClipboardCodec {
+    cipher: Option<Arc<Cipher>>,
+    compression_threshold: Option<usize>,
+
+    // whether the cipher is used for a history which was not encrypted before
+    new_cipher: bool,
+}
+
+impl ClipboardCodec {
+    /// Creates a codec from the metadata stored with the history, `salt` and
+    /// `check` are the values stored under `SALT_KEY` and `CHECK_KEY`.
+    pub fn new(
+        salt: Option<Vec<u8>>,
+        check: Option<Vec<u8>>,
+        passphrase: Option<&str>,
+    ) -> Result<ClipboardCodec, HistoryError> {
+        let new_cipher = salt.is_none() && passphrase.is_some();
+        let cipher = match (salt, passphrase) {
+            (None, None) => None,
+            (Some(_), None) => return Err(HistoryError::PassphraseRequired),
+            (Some(salt), Some(passphrase)) => {
+                let cipher = Cipher::new(passphrase, &salt);
+                let check = check.ok_or(HistoryError::Decrypt)?;
+                if cipher.decrypt(&check)? != CHECK_VALUE {
+                    return Err(HistoryError::Decrypt);
+                }
+                Some(cipher)
+            }
+            // existing clips have to be encrypted with `reencrypt`
+            (None, Some(passphrase)) => Some(Cipher::new(passphrase, &Cipher::generate_salt())),
+        };
+
+        Ok(ClipboardCodec { cipher: cipher.map(Arc::new), compression_threshold: None, new_cipher })
+    }
+
+    /// Whether encryption is enabled for a history which was not encrypted
+    /// before, its clips have to be encrypted with `reencrypt` at the time the
+    /// metadata is stored.
+    #[inline]
+    pub fn is_newly_encrypted(&self) -> bool { self.new_cipher }
+
+    /// Encrypts a value which was stored before encryption was enabled,
+    /// returns `None` if the value is encrypted already or can not be decoded.
+    pub fn reencrypt(&self, id: u64, raw_data: &[u8]) -> Option<Vec<u8>> {
+        if self.cipher.is_none() || Cipher::is_encrypted(raw_data) {
+            return None;
+        }
+        let clip = Self::deserialize(id, &Self::decompress(id, raw_data)?)?;
+        Some(self.encode(&clip))
+    }
+
+    /// Returns the key of the row which holds clip `id`, it is keyed with the
+    /// cipher of an encrypted history and `id` otherwise. Values carry the ID
+    /// of their clip, so rows of clips are only looked up by it.
+    #[inline]
+    pub fn row_id(&self, id: u64) -> u64 {
+        self.cipher.as_ref().map_or(id, |cipher| cipher.row_id(id))
+    }
+
+    /// The cipher of an encrypted history, e.g. to seal spilled clips with it.
+    #[inline]
+    pub fn cipher(&self) -> Option<Arc<Cipher>> { self.cipher.clone() }
+
+    /// Compresses values which are at least `threshold` bytes long, values are
+    /// never compressed if `threshold` is `None`.
+    pub fn with_compression_threshold(mut self, threshold: Option<usize>) -> ClipboardCodec {
+        self.compression_threshold = threshold;
+        self
+    }
+
+    /// Returns the metadata which has to be stored with the history.
+    pub fn metadata(&self) -> Vec<(&'static str, Vec<u8>)> {
+        match self.cipher {
+            Some(ref cipher) => {
+                vec![(SALT_KEY, cipher.salt().to_vec()), (CHECK_KEY, cipher.encrypt(CHECK_VALUE))]
+            }
+            None => Vec::new(),
+        }
+    }
+
+    pub fn encode(&self, data: &ClipboardData) -> Vec<u8> {
+        let value = ClipboardValue::V8 {
+            id: data.id,
+            data: data.data.clone(),
+            timestamp: data.timestamp,
+            created_at: data.created_at,
+            mime: data.mime.clone(),
+            binary: data.binary.clone(),
+            targets: data.targets.clone(),
+            pinned: data.pinned,
+            tags: data.tags.clone(),
+            use_count: data.use_count,
+            application: data.application.clone(),
+        };
+        let value = bincode::serialize(&value).expect("ClipboardData is serializable");
+        let value = self.compress(value);
+        match self.cipher {
+            Some(ref cipher) => cipher.encrypt(&value),
+            None => value,
+        }
+    }
+
+    pub fn decode(&self, id: u64, raw_data: &[u8]) -> Option<ClipboardData> {
+        if !Cipher::is_encrypted(raw_data) {
+            // values of an encrypted history are authenticated, so unencrypted
+            // values were planted by someone else
+            if self.cipher.is_some() {
+                tracing::warn!("Refuse unencrypted clip {:016x} of encrypted history", id);
+                return None;
+            }
+            return Self::deserialize(id, &Self::decompress(id, raw_data)?);
+        }
+
+        let cipher = self.cipher.as_ref()?;
+        match cipher.decrypt(raw_data) {
+            Ok(data) => Self::deserialize(id, &Self::decompress(id, &data)?),
+            Err(_) => {
+                tracing::warn!("Failed to decrypt clip {:016x}", id);
+                None
+            }
+        }
+    }
+
+    fn compress(&self, value: Vec<u8>) -> Vec<u8> {
+        match self.compression_threshold {
+            Some(threshold) if value.len() >= threshold => {}
+            _ => return value,
+        }
+
+        match zstd::encode_all(value.as_slice(), COMPRESSION_LEVEL) {
+            // keep the value as it is if compression does not pay off
+            Ok(compressed) if compressed.len() + COMPRESSED_MAGIC.len() < value.len() => {
+                let mut data = Vec::with_capacity(COMPRESSED_MAGIC.len() + compressed.len());
+                data.extend_from_slice(COMPRESSED_MAGIC);
+                data.extend_from_slice(&compressed);
+                data
+            }
+            Ok(_) => value,
+            Err(err) => {
+                tracing::warn!("Failed to compress clip, error: {}", err);
+                value
+            }
+        }
+    }
+
+    fn decompress(id: u64, raw_data: &[u8]) -> Option<Cow<'_, [u8]>> {
+        if !raw_data.starts_with(COMPRESSED_MAGIC) {
+            return Some(Cow::Borrowed(raw_data));
+        }
+
+        match zstd::decode_all(&raw_data[COMPRESSED_MAGIC.len()..]) {
+            Ok(data) => Some(Cow::Owned(data)),
+            Err(err) => {
+                tracing::warn!("Failed to decompress clip {:016x}, error: {}", id, err);
+                None
+            }
+        }
+    }
+
+    fn deserialize(id: u64, raw_data: &[u8]) -> Option<ClipboardData> {
+        let value = match bincode::deserialize::<ClipboardValue>(raw_data) {
+            Ok(value) => value,
+            Err(_) => match bincode::deserialize::<LegacyClipboardValue>(raw_data) {
+                Ok(LegacyClipboardValue { data, timestamp }) => ClipboardValue::V1 {
+                    data,
+                    timestamp,
+                    mime: ClipboardData::default_mime(),
+                    binary: Vec::new(),
+                },
+                Err(_) => {
+                    tracing::warn!("Failed to deserialize ClipboardValue");
+                    return None;
+                }
+            },
+        };
+
+        // clips saved by older versions were created when they were last copied,
+        // their IDs are the keys of their rows
+        let (
+            id,
+            data,
+            timestamp,
+            created_at,
+            mime,
+            binary,
+            targets,
+            pinned,
+            tags,
+            use_count,
+            application,
+        ) = match value {
+            ClipboardValue::V1 { data, timestamp, mime, binary } => (
+                id,
+                data,
+                timestamp,
+                timestamp,
+                mime,
+                binary,
+                BTreeMap::new(),
+                false,
+                BTreeSet::new(),
+                0,
+                None,
+            ),
+            ClipboardValue::V2 { data, timestamp, mime, binary, targets } => (
+                id,
+                data,
+                timestamp,
+                timestamp,
+                mime,
+                binary,
+                targets,
+                false,
+                BTreeSet::new(),
+                0,
+                None,
+            ),
+            ClipboardValue::V3 { data, timestamp, mime, binary, targets, pinned } => (
+                id,
+                data,
+                timestamp,
+                timestamp,
+                mime,
+                binary,
+                targets,
+                pinned,
+                BTreeSet::new(),
+                0,
+                None,
+            ),
+            ClipboardValue::V4 { data, timestamp, mime, binary, targets, pinned, tags } => {
+                (id, data, timestamp, timestamp, mime, binary, targets, pinned, tags, 0, None)
+            }
+            ClipboardValue::V5 {
+                data,
+                timestamp,
+                mime,
+                binary,
+                targets,
+                pinned,
+                tags,
+                use_count,
+            } => (
+                id, data, timestamp, timestamp, mime, binary, targets, pinned, tags, use_count,
+                None,
+            ),
+            ClipboardValue::V6 {
+                data,
+                timestamp,
+                mime,
+                binary,
+                targets,
+                pinned,
+                tags,
+                use_count,
+                application,
+            } => (
+                id,
+                data,
+                timestamp,
+                timestamp,
+                mime,
+                binary,
+                targets,
+                pinned,
+                tags,
+                use_count,
+                application,
+            ),
+            ClipboardValue::V7 {
+                data,
+                timestamp,
+                created_at,
+                mime,
+                binary,
+                targets,
+                pinned,
+                tags,
+                use_count,
+                application,
+            } => (
+                id,
+                data,
+                timestamp,
+                created_at,
+                mime,
+                binary,
+                targets,
+                pinned,
+                tags,
+                use_count,
+                application,
+            ),
+            ClipboardValue::V8 {
+                id,
+                data,
+                timestamp,
+                created_at,
+                mime,
+                binary,
+                targets,
+                pinned,
+                tags,
+                use_count,
+                application,
+            } => (
+                id,
+                data,
+                timestamp,
+                created_at,
+                mime,
+                binary,
+                targets,
+                pinned,
+                tags,
+                use_count,
+                application,
+            ),
+        };
+        Some(ClipboardData {
+            id,
+            data,
+            timestamp,
+            created_at,
+            clipboard_type: ClipboardType::Primary,
+            mime,
+            binary,
+            targets,
+            pinned,
+            tags,
+            sensitive: false,
+            use_count,
+            application,
+            spilled: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clipcat::ClipboardData;
+
+    use super::ClipboardCodec;
+
+    #[test]
+    fn test_encode_decode() {
+        let clip = ClipboardData::new_clipboard("clipcat");
+
+        let codec = ClipboardCodec::new(None, None, None).unwrap();
+        assert!(codec.metadata().is_empty());
+        let decoded = codec.decode(clip.id, &codec.encode(&clip)).unwrap();
+        assert_eq!(decoded.data, clip.data);
+        assert_eq!(decoded.timestamp, clip.timestamp);
+        assert_eq!(decoded.created_at, clip.created_at);
+        assert!(!decoded.pinned);
+
+        let mut clip = clip;
+        clip.pinned = true;
+        clip.tags.insert("work".to_owned());
+        clip.use_count = 3;
+        clip.application = Some("Firefox".to_owned());
+        let decoded = codec.decode(clip.id, &codec.encode(&clip)).unwrap();
+        assert!(decoded.pinned);
+        assert_eq!(decoded.tags, clip.tags);
+        assert_eq!(decoded.use_count, 3);
+        assert_eq!(decoded.application.as_deref(), Some("Firefox"));
+    }
+
+    #[test]
+    fn test_encrypted() {
+        let clip = ClipboardData::new_clipboard("clipcat");
+
+        let codec = ClipboardCodec::new(None, None, Some("passphrase")).unwrap();
+        assert!(codec.is_newly_encrypted());
+        let metadata = codec.metadata();
+        let value = codec.encode(&clip);
+        assert!(value.windows(7).all(|w| w != b"clipcat"));
+
+        let salt = Some(metadata[0].1.clone());
+        let check = Some(metadata[1].1.clone());
+        assert!(ClipboardCodec::new(salt.clone(), check.clone(), None).is_err());
+        assert!(ClipboardCodec::new(salt.clone(), check.clone(), Some("wrong")).is_err());
+
+        let codec = ClipboardCodec::new(salt, check, Some("passphrase")).unwrap();
+        assert!(!codec.is_newly_encrypted());
+        assert_eq!(codec.decode(clip.id, &value).unwrap().data, clip.data);
+
+        // rows are keyed, the ID of the clip is kept in its value
+        let row_id = codec.row_id(clip.id);
+        assert_ne!(row_id, clip.id);
+        assert_eq!(codec.decode(row_id, &value).unwrap().id, clip.id);
+        assert_eq!(ClipboardCodec::new(None, None, None).unwrap().row_id(clip.id), clip.id);
+
+        // unencrypted values are refused once the history is encrypted
+        let plain = ClipboardCodec::new(None, None, None).unwrap().encode(&clip);
+        assert!(codec.decode(clip.id, &plain).is_none());
+        assert!(codec.reencrypt(clip.id, &value).is_none());
+        let reencrypted = codec.reencrypt(clip.id, &plain).unwrap();
+        assert_eq!(codec.decode(clip.id, &reencrypted).unwrap().data, clip.data);
+    }
+
+    #[test]
+    fn test_compressed() {
+        let clip = ClipboardData::new_clipboard(&"clipcat ".repeat(1024));
+
+        let plain = ClipboardCodec::new(None, None, None).unwrap();
+        let uncompressed = plain.encode(&clip);
+
+        let codec =
+            ClipboardCodec::new(None, None, None).unwrap().with_compression_threshold(Some(1024));
+        let compressed = codec.encode(&clip);
+        assert!(compressed.len() < uncompressed.len());
+        assert_eq!(codec.decode(clip.id, &compressed).unwrap().data, clip.data);
+        assert_eq!(codec.decode(clip.id, &uncompressed).unwrap().data, clip.data);
+        assert_eq!(plain.decode(clip.id, &compressed).unwrap().data, clip.data);
+
+        let small = ClipboardData::new_clipboard("clipcat");
+        assert_eq!(codec.encode(&small), plain.encode(&small));
+    }
+}
@@ -2,8 +2,45 @@
 pub enum HistoryError {
     #[snafu(display("RocksDB error: {}", source))]
     RocksDB { source: rocksdb::Error },
+
+    #[cfg(feature = "sqlite")]
+    #[snafu(display("SQLite error: {}", source))]
+    Sqlite { source: rusqlite::Error },
+
+    #[cfg(not(feature = "sqlite"))]
+    #[snafu(display(
+        "clipcatd is built without the sqlite feature, the sqlite driver is unavailable"
+    ))]
+    SqliteUnsupported,
+
+    #[snafu(display("Could not create directory {}: {}", path.display(), source))]
+    CreateDirectory { path: std::path::PathBuf, source: std::io::Error },
+
+    #[snafu(display("History is encrypted, a passphrase is required"))]
+    PassphraseRequired,
+
+    #[snafu(display("Could not decrypt history, the passphrase may be wrong"))]
+    Decrypt,
+
+    #[snafu(display(
+        "History format version {} is newer than the supported version {}, please upgrade clipcat",
+        version,
+        supported
+    ))]
+    UnsupportedVersion { version: u32, supported: u32 },
+
+    #[snafu(display("Invalid format version of history"))]
+    InvalidVersion,
+
+    #[snafu(display("Could not access journal {}: {}", path.display(), source))]
+    Journal { path: std::path::PathBuf, source: std::io::Error },
 }
 
 impl From<rocksdb::Error> for HistoryError {
     fn from(err: rocksdb::Error) -> HistoryError { HistoryError::RocksDB { source: err } }
 }
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for HistoryError {
+    fn from(err: rusqlite::Error) -> HistoryError { HistoryError::Sqlite { source: err } }
+}
@@ -0,0 +1,104 @@
+use std::{
+    convert::TryInto,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::history::HistoryError;
+
+// ID and length of the value of a record.
+const HEADER_LEN: usize = 8 + 4;
+
+/// Clips which are appended as they are captured, so that they survive a
+/// crash of clipcatd until they are compacted into the history file. Each
+/// record is the ID of a clip, the length of its encoded value and the value,
+/// records are synced to disk before `append` returns.
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Journal, HistoryError> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|source| HistoryError::Journal { path: path.clone(), source })?;
+        Ok(Journal { path, file })
+    }
+
+    /// Appends `records` of IDs and values at once.
+    pub fn append(&mut self, records: &[(u64, Vec<u8>)]) -> Result<(), HistoryError> {
+        let mut data = Vec::new();
+        for (id, value) in records {
+            data.extend_from_slice(&id.to_le_bytes());
+            data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            data.extend_from_slice(value);
+        }
+        self.file
+            .write_all(&data)
+            .and_then(|_| self.file.sync_data())
+            .map_err(|source| HistoryError::Journal { path: self.path.clone(), source })
+    }
+
+    /// Reads the records in the order they were appended, a record which was
+    /// cut off by a crash ends the journal.
+    pub fn records(&self) -> Result<Vec<(u64, Vec<u8>)>, HistoryError> {
+        let data = std::fs::read(&self.path)
+            .map_err(|source| HistoryError::Journal { path: self.path.clone(), source })?;
+        Ok(parse_records(&data))
+    }
+
+    pub fn truncate(&mut self) -> Result<(), HistoryError> {
+        self.file
+            .set_len(0)
+            .and_then(|_| self.file.sync_all())
+            .map_err(|source| HistoryError::Journal { path: self.path.clone(), source })
+    }
+}
+
+fn parse_records(mut data: &[u8]) -> Vec<(u64, Vec<u8>)> {
+    let mut records = Vec::new();
+    while data.len() >= HEADER_LEN {
+        let id = u64::from_le_bytes(data[..8].try_into().expect("ID is 8 bytes"));
+        let len = u32::from_le_bytes(data[8..HEADER_LEN].try_into().expect("length is 4 bytes"));
+        let end = HEADER_LEN + len as usize;
+        if data.len() < end {
+            break;
+        }
+        records.push((id, data[HEADER_LEN..end].to_vec()));
+        data = &data[end..];
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::{journal::Journal, temp_path::TempPath};
+
+    #[test]
+    fn test_records() {
+        let path = TempPath::new("journal");
+
+        let mut journal = Journal::open(&path).unwrap();
+        journal.append(&[(1, b"first".to_vec())]).unwrap();
+        journal.append(&[(2, Vec::new())]).unwrap();
+        assert_eq!(journal.records().unwrap(), vec![(1, b"first".to_vec()), (2, Vec::new())]);
+
+        // a record which was cut off by a crash is dropped
+        let mut data = std::fs::read(&path).unwrap();
+        data.extend_from_slice(&3u64.to_le_bytes());
+        data.extend_from_slice(&10u32.to_le_bytes());
+        data.extend_from_slice(b"cut");
+        std::fs::write(&path, data).unwrap();
+        assert_eq!(journal.records().unwrap().len(), 2);
+
+        journal.truncate().unwrap();
+        assert!(journal.records().unwrap().is_empty());
+        journal.append(&[(4, b"after".to_vec()), (5, b"batch".to_vec())]).unwrap();
+        assert_eq!(journal.records().unwrap().len(), 2);
+    }
+}
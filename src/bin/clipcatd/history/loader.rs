@@ -0,0 +1,35 @@
+use clipcat::{spill::BodyLoader, ClipboardData};
+
+use crate::history::HistoryError;
+
+/// Reads clips from a history file through a handle which is separate from the
+/// driver that writes it.
+pub trait HistoryReader: Send + Sync {
+    fn get(&self, id: u64) -> Result<Option<ClipboardData>, HistoryError>;
+}
+
+/// Loads the bodies of clips which were imported as previews from the history
+/// files.
+pub struct HistoryBodyLoader {
+    readers: Vec<Box<dyn HistoryReader>>,
+}
+
+impl HistoryBodyLoader {
+    #[inline]
+    pub fn new(readers: Vec<Box<dyn HistoryReader>>) -> HistoryBodyLoader {
+        HistoryBodyLoader { readers }
+    }
+}
+
+impl BodyLoader for HistoryBodyLoader {
+    fn load_body(&self, id: u64) -> Result<Option<ClipboardData>, String> {
+        // bodies are loaded while the clipboard manager is locked, the readers
+        // never wait for the history manager which may be held by a writer
+        for reader in &self.readers {
+            if let Some(clip) = reader.get(id).map_err(|err| err.to_string())? {
+                return Ok(Some(clip));
+            }
+        }
+        Ok(None)
+    }
+}
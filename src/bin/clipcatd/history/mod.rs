@@ -1,11 +1,62 @@
-use std::path::{Path, PathBuf};
+use std::{
+    cmp::Reverse,
+    convert::TryInto,
+    path::{Path, PathBuf},
+};
 
-use clipcat::ClipboardData;
+use clipcat::{spill::SpillCipher, ClipboardData, ClipboardType};
 
+mod cipher;
+mod codec;
 mod error;
+mod journal;
+mod loader;
+mod previews;
 mod rocksdb;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(test)]
+mod temp_path;
 
-pub use self::{error::HistoryError, rocksdb::RocksDBDriver};
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteDriver;
+use self::{
+    cipher::HistorySpillCipher, codec::ClipboardCodec, journal::Journal, previews::PREVIEWS_KEY,
+};
+pub use self::{
+    error::HistoryError,
+    loader::{HistoryBodyLoader, HistoryReader},
+    rocksdb::RocksDBDriver,
+};
+
+/// Metadata key of the format version of a history file.
+pub const VERSION_KEY: &str = "version";
+
+/// Format version of history files written by this version of clipcat.
+pub const HISTORY_VERSION: u32 = 2;
+
+type Migration = fn(&mut dyn HistoryDriver) -> Result<(), HistoryError>;
+
+// Migrations which upgrade a history file by one version, the migration at
+// index `n` upgrades a file of version `n` to version `n + 1`. A migration has
+// to be appended whenever `HISTORY_VERSION` is bumped.
+const MIGRATIONS: &[Migration] = &[
+    // files without a version may hold values of any format which was written
+    // before, they are rewritten in the latest format
+    reencode_clips,
+    // rows of encrypted histories were keyed by the IDs of their clips, which
+    // are hashes of their content
+    rekey_clips,
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Default)]
+pub enum HistoryDriverType {
+    #[serde(rename = "rocksdb")]
+    #[default]
+    RocksDB,
+    #[serde(rename = "sqlite")]
+    Sqlite,
+}
 
 pub trait HistoryDriver: Send + Sync {
     fn load(&self) -> Result<Vec<ClipboardData>, HistoryError>;
@@ -16,10 +67,48 @@ pub trait HistoryDriver: Send + Sync {
 
     fn put(&mut self, data: &ClipboardData) -> Result<(), HistoryError>;
 
+    #[allow(dead_code)]
     fn get(&self, id: u64) -> Result<Option<ClipboardData>, HistoryError>;
 
+    fn remove(&mut self, id: u64) -> Result<(), HistoryError>;
+
+    fn metadata(&self, key: &str) -> Result<Option<Vec<u8>>, HistoryError>;
+
+    fn set_metadata(&mut self, key: &str, value: &[u8]) -> Result<(), HistoryError>;
+
+    /// The codec which clips are encoded with, the journal encodes clips with
+    /// it as well.
+    fn codec(&self) -> &ClipboardCodec;
+
+    /// Loads all pinned clips and at most `limit` other clips, the most recent
+    /// ones are preferred.
+    fn load_latest(&self, limit: usize) -> Result<Vec<ClipboardData>, HistoryError> {
+        Ok(latest(self.load()?, limit))
+    }
+
+    /// Stores previews of `clips`, which `load_previews` returns until the
+    /// previews are discarded.
+    fn save_previews(&mut self, clips: &[ClipboardData]) -> Result<(), HistoryError> {
+        let value = previews::encode(self.codec(), clips);
+        self.set_metadata(PREVIEWS_KEY, &value)
+    }
+
+    /// Loads the previews which were stored last, returns `None` if there are
+    /// none or they were discarded.
+    fn load_previews(&self) -> Result<Option<Vec<ClipboardData>>, HistoryError> {
+        Ok(self.metadata(PREVIEWS_KEY)?.and_then(|value| previews::decode(self.codec(), &value)))
+    }
+
+    /// Discards the previews, e.g. when clips are stored without them.
+    fn discard_previews(&mut self) -> Result<(), HistoryError> {
+        self.set_metadata(PREVIEWS_KEY, &[])
+    }
+
+    /// Removes the oldest clips until at most `min_capacity` clips which are
+    /// not pinned are left, pinned clips are never removed.
     fn shrink_to(&mut self, min_capacity: usize) -> Result<(), HistoryError>;
 
+    #[allow(dead_code)]
     fn save_and_shrink_to(
         &mut self,
         data: &[ClipboardData],
@@ -28,44 +117,255 @@ pub trait HistoryDriver: Send + Sync {
         self.save(data)?;
         self.shrink_to(min_capacity)
     }
+
+    /// Drops entries which can not be decoded and rewrites the store to free
+    /// unused space, returns the number of reclaimed bytes.
+    fn vacuum(&mut self) -> Result<u64, HistoryError>;
+
+    /// Opens a read only handle of the history file which is used without
+    /// locking the driver.
+    fn open_reader(&self) -> Result<Box<dyn HistoryReader>, HistoryError>;
 }
 
 pub struct HistoryManager {
     file_path: PathBuf,
     driver: Box<dyn HistoryDriver>,
+
+    // stores primary clips instead of `driver` if it is set
+    primary_driver: Option<Box<dyn HistoryDriver>>,
+
+    // captured clips are appended to it instead of being put into the drivers
+    journal: Option<Journal>,
+
+    // whether the history files may hold previews which match their clips
+    has_previews: bool,
 }
 
 impl HistoryManager {
     #[inline]
-    pub fn new<P: AsRef<Path>>(file_path: P) -> Result<HistoryManager, HistoryError> {
-        let driver = Box::new(RocksDBDriver::open(&file_path)?);
+    pub fn new<P: AsRef<Path>>(
+        file_path: P,
+        driver_type: HistoryDriverType,
+        passphrase: Option<&str>,
+        compression_threshold: Option<usize>,
+    ) -> Result<HistoryManager, HistoryError> {
+        let driver = open_driver(&file_path, driver_type, passphrase, compression_threshold)?;
         let file_path = file_path.as_ref().to_owned();
-        Ok(HistoryManager { driver, file_path })
+        Ok(HistoryManager {
+            driver,
+            file_path,
+            primary_driver: None,
+            journal: None,
+            has_previews: true,
+        })
+    }
+
+    /// Stores primary clips in a separate history file at `file_path`.
+    pub fn open_primary<P: AsRef<Path>>(
+        &mut self,
+        file_path: P,
+        driver_type: HistoryDriverType,
+        passphrase: Option<&str>,
+        compression_threshold: Option<usize>,
+    ) -> Result<(), HistoryError> {
+        let driver = open_driver(&file_path, driver_type, passphrase, compression_threshold)?;
+        self.primary_driver = Some(driver);
+        Ok(())
+    }
+
+    /// Appends captured clips to a journal at `file_path` until they are
+    /// compacted into the history files. Clips which are left in the journal
+    /// by a crash are compacted at once, returns their number.
+    pub fn open_journal<P: AsRef<Path>>(&mut self, file_path: P) -> Result<usize, HistoryError> {
+        self.journal = Some(Journal::open(file_path)?);
+        self.compact_journal()
     }
 
     #[inline]
     pub fn path(&self) -> &Path { &self.file_path }
 
+    fn driver_of(&mut self, clipboard_type: ClipboardType) -> &mut Box<dyn HistoryDriver> {
+        match (clipboard_type, self.primary_driver.as_mut()) {
+            (ClipboardType::Primary, Some(driver)) => driver,
+            _ => &mut self.driver,
+        }
+    }
+
+    fn drivers_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn HistoryDriver>> {
+        std::iter::once(&mut self.driver).chain(self.primary_driver.as_mut())
+    }
+
+    /// Stores `data`, sensitive clips are skipped.
     #[inline]
+    #[allow(dead_code)]
     pub fn put(&mut self, data: &ClipboardData) -> Result<(), HistoryError> {
-        self.driver.put(data)
+        self.put_all(std::slice::from_ref(data))
+    }
+
+    /// Stores `clips` at once, sensitive clips are skipped.
+    #[tracing::instrument(name = "history.put", skip(self, clips))]
+    pub fn put_all(&mut self, clips: &[ClipboardData]) -> Result<(), HistoryError> {
+        let clips = clips.iter().filter(|clip| !clip.sensitive);
+        match self.journal {
+            Some(ref mut journal) => {
+                let codec = self.driver.codec();
+                let records: Vec<_> =
+                    clips.map(|clip| (codec.row_id(clip.id), codec.encode(clip))).collect();
+                if records.is_empty() {
+                    return Ok(());
+                }
+                journal.append(&records)
+            }
+            None => {
+                self.discard_previews()?;
+                for clip in clips {
+                    self.driver_of(clip.clipboard_type).put(clip)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Puts the clips in the journal into the history files and empties the
+    /// journal, returns the number of compacted clips.
+    #[tracing::instrument(name = "history.compact", skip(self))]
+    pub fn compact_journal(&mut self) -> Result<usize, HistoryError> {
+        let records = match self.journal {
+            Some(ref journal) => journal.records()?,
+            None => return Ok(0),
+        };
+        if records.is_empty() {
+            return Ok(0);
+        }
+
+        let clips: Vec<_> = records
+            .into_iter()
+            .filter_map(|(id, value)| self.driver.codec().decode(id, &value))
+            .collect();
+        self.discard_previews()?;
+        for clip in &clips {
+            self.driver_of(clip.clipboard_type).put(clip)?;
+        }
+        self.truncate_journal()?;
+        Ok(clips.len())
+    }
+
+    // Previews are discarded once, before the clips are changed without them.
+    fn discard_previews(&mut self) -> Result<(), HistoryError> {
+        if self.has_previews {
+            self.drivers_mut().try_for_each(|driver| driver.discard_previews())?;
+            self.has_previews = false;
+        }
+        Ok(())
+    }
+
+    fn truncate_journal(&mut self) -> Result<(), HistoryError> {
+        match self.journal {
+            Some(ref mut journal) => journal.truncate(),
+            None => Ok(()),
+        }
+    }
+
+    /// Deletes clips `ids` from the history files at once, clips in the
+    /// journal are compacted first so that they are deleted as well.
+    #[tracing::instrument(name = "history.remove", skip(self, ids))]
+    pub fn remove_all(&mut self, ids: &[u64]) -> Result<(), HistoryError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        self.compact_journal()?;
+        self.discard_previews()?;
+        for driver in self.drivers_mut() {
+            ids.iter().try_for_each(|&id| driver.remove(id))?;
+        }
+        Ok(())
     }
 
     #[inline]
     #[allow(dead_code)]
-    pub fn clear(&mut self) -> Result<(), HistoryError> { self.driver.clear() }
+    pub fn clear(&mut self) -> Result<(), HistoryError> {
+        self.discard_previews()?;
+        self.drivers_mut().try_for_each(|driver| driver.clear())
+    }
 
     #[inline]
-    pub fn load(&self) -> Result<Vec<ClipboardData>, HistoryError> { self.driver.load() }
+    #[allow(dead_code)]
+    pub fn load(&self) -> Result<Vec<ClipboardData>, HistoryError> {
+        let mut clips = self.driver.load()?;
+        if let Some(ref driver) = self.primary_driver {
+            clips.extend(driver.load()?);
+        }
+        Ok(clips)
+    }
 
+    /// Loads at most `limit` clips which are not pinned from each history
+    /// file.
     #[inline]
+    pub fn load_latest(&self, limit: usize) -> Result<Vec<ClipboardData>, HistoryError> {
+        let mut clips = self.driver.load_latest(limit)?;
+        if let Some(ref driver) = self.primary_driver {
+            clips.extend(driver.load_latest(limit)?);
+        }
+        Ok(clips)
+    }
+
+    /// Loads the previews of all pinned clips and of at most `limit` other
+    /// clips from each history file, like `load_latest`. Returns `None` if a
+    /// history file holds no previews which match its clips, the bodies of
+    /// previews are loaded by `get`.
+    pub fn load_previews(&self, limit: usize) -> Result<Option<Vec<ClipboardData>>, HistoryError> {
+        let mut clips = match self.driver.load_previews()? {
+            Some(clips) => latest(clips, limit),
+            None => return Ok(None),
+        };
+        if let Some(ref driver) = self.primary_driver {
+            match driver.load_previews()? {
+                Some(primary) => clips.extend(latest(primary, limit)),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(clips))
+    }
+
+    /// Seals spilled clips with the cipher of the history file if it is
+    /// encrypted, see `SpillStore::open_sealed`.
+    pub fn spill_cipher(&self) -> Option<Box<dyn SpillCipher>> {
+        let cipher = self.driver.codec().cipher()?;
+        Some(Box::new(HistorySpillCipher(cipher)))
+    }
+
+    /// Loads the bodies of previews from the history files through handles of
+    /// their own, so it never waits for the history manager.
+    pub fn body_loader(&self) -> Result<HistoryBodyLoader, HistoryError> {
+        let mut readers = vec![self.driver.open_reader()?];
+        if let Some(ref driver) = self.primary_driver {
+            readers.push(driver.open_reader()?);
+        }
+        Ok(HistoryBodyLoader::new(readers))
+    }
+
+    /// Loads clip `id` with its body from the history files.
+    pub fn get(&self, id: u64) -> Result<Option<ClipboardData>, HistoryError> {
+        match self.driver.get(id)? {
+            Some(clip) => Ok(Some(clip)),
+            None => match self.primary_driver {
+                Some(ref driver) => driver.get(id),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Stores exactly `data` and previews of it, sensitive clips are skipped.
+    /// The journal is emptied as `data` supersedes it.
+    #[tracing::instrument(name = "history.save", skip(self, data))]
     pub fn save(&mut self, data: &[ClipboardData]) -> Result<(), HistoryError> {
-        self.driver.save(data)
+        self.save_with_previews(data, None)
     }
 
-    #[inline]
+    #[tracing::instrument(name = "history.shrink", skip(self))]
     pub fn shrink_to(&mut self, min_capacity: usize) -> Result<(), HistoryError> {
-        self.driver.shrink_to(min_capacity)
+        self.discard_previews()?;
+        self.drivers_mut().try_for_each(|driver| driver.shrink_to(min_capacity))
     }
 
     #[inline]
@@ -74,7 +374,300 @@ impl HistoryManager {
         data: &[ClipboardData],
         min_capacity: usize,
     ) -> Result<(), HistoryError> {
+        self.save_with_previews(data, Some(min_capacity))
+    }
+
+    // The previews only cover the clips which are left after shrinking each
+    // history file to `min_capacity`.
+    fn save_with_previews(
+        &mut self,
+        data: &[ClipboardData],
+        min_capacity: Option<usize>,
+    ) -> Result<(), HistoryError> {
+        let data = data.iter().filter(|clip| !clip.sensitive).cloned();
+        let partitions = match self.primary_driver {
+            Some(_) => {
+                let (primary, clipboard): (Vec<_>, Vec<_>) =
+                    data.partition(|clip| clip.clipboard_type == ClipboardType::Primary);
+                vec![clipboard, primary]
+            }
+            None => vec![data.collect()],
+        };
+
+        for (driver, clips) in self.drivers_mut().zip(partitions) {
+            driver.save(&clips)?;
+            let clips = match min_capacity {
+                Some(min_capacity) => {
+                    driver.shrink_to(min_capacity)?;
+                    latest(clips, min_capacity)
+                }
+                None => clips,
+            };
+            driver.save_previews(&clips)?;
+        }
+        self.has_previews = true;
+        self.truncate_journal()
+    }
+
+    /// Stores exactly `data` and vacuums each history file, returns the number
+    /// of reclaimed bytes.
+    #[tracing::instrument(name = "history.vacuum", skip(self, data))]
+    pub fn save_and_vacuum(&mut self, data: &[ClipboardData]) -> Result<u64, HistoryError> {
         self.save(data)?;
-        self.shrink_to(min_capacity)
+        self.drivers_mut().try_fold(0, |reclaimed, driver| Ok(reclaimed + driver.vacuum()?))
+    }
+}
+
+/// Keeps all pinned clips and at most `limit` other clips, the most recent ones
+/// are preferred.
+fn latest(clips: Vec<ClipboardData>, limit: usize) -> Vec<ClipboardData> {
+    let (mut clips, mut unpinned): (Vec<_>, Vec<_>) =
+        clips.into_iter().partition(|clip| clip.pinned);
+    unpinned.sort_by_key(|clip| Reverse(clip.timestamp));
+    unpinned.truncate(limit);
+    clips.extend(unpinned);
+    clips
+}
+
+/// Total size in bytes of the file or directory at `path`.
+fn disk_usage(path: &Path) -> u64 {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    std::fs::read_dir(path)
+        .map(|entries| entries.filter_map(Result::ok).map(|entry| disk_usage(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
+fn open_driver<P: AsRef<Path>>(
+    file_path: P,
+    driver_type: HistoryDriverType,
+    passphrase: Option<&str>,
+    compression_threshold: Option<usize>,
+) -> Result<Box<dyn HistoryDriver>, HistoryError> {
+    let mut driver: Box<dyn HistoryDriver> = match driver_type {
+        HistoryDriverType::RocksDB => {
+            Box::new(RocksDBDriver::open(&file_path, passphrase, compression_threshold)?)
+        }
+        #[cfg(feature = "sqlite")]
+        HistoryDriverType::Sqlite => {
+            Box::new(SqliteDriver::open(&file_path, passphrase, compression_threshold)?)
+        }
+        #[cfg(not(feature = "sqlite"))]
+        HistoryDriverType::Sqlite => return Err(HistoryError::SqliteUnsupported),
+    };
+    migrate(driver.as_mut(), file_path.as_ref())?;
+    Ok(driver)
+}
+
+/// Returns the format version of the history file, files written before the
+/// version was stored are version 0.
+fn history_version(driver: &dyn HistoryDriver) -> Result<u32, HistoryError> {
+    match driver.metadata(VERSION_KEY)? {
+        Some(value) => {
+            let bytes = value.as_slice().try_into().map_err(|_| HistoryError::InvalidVersion)?;
+            Ok(u32::from_le_bytes(bytes))
+        }
+        None => Ok(0),
+    }
+}
+
+/// Upgrades the history file to `HISTORY_VERSION` in place, files written by
+/// a newer version of clipcat are rejected instead of being overwritten.
+fn migrate(driver: &mut dyn HistoryDriver, file_path: &Path) -> Result<(), HistoryError> {
+    let version = history_version(driver)?;
+    if version > HISTORY_VERSION {
+        return Err(HistoryError::UnsupportedVersion { version, supported: HISTORY_VERSION });
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        tracing::info!("Migrate history {:?} from version {} to {}", file_path, from, from + 1);
+        migration(driver)?;
+        driver.set_metadata(VERSION_KEY, &(from as u32 + 1).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn reencode_clips(driver: &mut dyn HistoryDriver) -> Result<(), HistoryError> {
+    // values which can not be decoded are left untouched
+    driver.load()?.iter().try_for_each(|clip| driver.put(clip))
+}
+
+fn rekey_clips(driver: &mut dyn HistoryDriver) -> Result<(), HistoryError> {
+    if driver.codec().cipher().is_none() {
+        return Ok(());
+    }
+    // rows which can not be decoded are dropped with the old rows, previews
+    // are discarded since they refer to the old rows
+    let clips = driver.load()?;
+    driver.save(&clips)?;
+    driver.discard_previews()?;
+    driver.vacuum().map(drop)
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use clipcat::{spill::BodyLoader, ClipboardData};
+
+    use crate::history::{
+        history_version, open_driver, HistoryDriver, HistoryDriverType, HistoryError,
+        HistoryManager, SqliteDriver, HISTORY_VERSION, VERSION_KEY,
+    };
+
+    use super::temp_path::TempPath;
+
+    #[test]
+    fn test_migrate() {
+        let path = TempPath::new("history.db");
+        let clip = ClipboardData::new_clipboard("clipcat");
+
+        {
+            // a history file written before versions were stored
+            let mut driver = SqliteDriver::open(&path, None, None).unwrap();
+            driver.put(&clip).unwrap();
+            assert_eq!(history_version(&driver).unwrap(), 0);
+        }
+
+        {
+            let mut driver = open_driver(&path, HistoryDriverType::Sqlite, None, None).unwrap();
+            assert_eq!(history_version(driver.as_ref()).unwrap(), HISTORY_VERSION);
+            assert_eq!(driver.load().unwrap(), vec![clip]);
+            driver.set_metadata(VERSION_KEY, &(HISTORY_VERSION + 1).to_le_bytes()).unwrap();
+        }
+
+        let result = open_driver(&path, HistoryDriverType::Sqlite, None, None);
+        assert!(matches!(result, Err(HistoryError::UnsupportedVersion { .. })));
+    }
+
+    #[test]
+    fn test_compact_journal() {
+        let path = TempPath::new("history.db");
+        let journal_path = TempPath::new("history.journal");
+        let clip = ClipboardData::new_clipboard("clipcat");
+
+        {
+            let mut hm =
+                HistoryManager::new(&path, HistoryDriverType::Sqlite, Some("secret"), None)
+                    .unwrap();
+            assert_eq!(hm.open_journal(&journal_path).unwrap(), 0);
+            hm.put(&clip).unwrap();
+            assert!(hm.load().unwrap().is_empty());
+            // the journal is encrypted like the history file
+            assert!(!std::fs::read(&journal_path).unwrap().windows(7).any(|w| w == b"clipcat"));
+        }
+
+        // clips which are left in the journal are compacted when it is opened
+        let mut hm =
+            HistoryManager::new(&path, HistoryDriverType::Sqlite, Some("secret"), None).unwrap();
+        assert_eq!(hm.open_journal(&journal_path).unwrap(), 1);
+        assert_eq!(hm.load().unwrap(), vec![clip]);
+        assert_eq!(hm.compact_journal().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_encrypt_existing_clips() {
+        let path = TempPath::new("history.db");
+        let clip = ClipboardData::new_clipboard("clipcat");
+
+        {
+            let mut hm = HistoryManager::new(&path, HistoryDriverType::Sqlite, None, None).unwrap();
+            hm.put(&clip).unwrap();
+        }
+
+        // clips which were stored before encryption was enabled are encrypted
+        let hm =
+            HistoryManager::new(&path, HistoryDriverType::Sqlite, Some("secret"), None).unwrap();
+        assert_eq!(hm.load().unwrap(), vec![clip.clone()]);
+        assert!(!std::fs::read(&path).unwrap().windows(7).any(|w| w == b"clipcat"));
+        drop(hm);
+
+        // rows are keyed, so they do not reveal the content hashes of clips
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM clips WHERE id = ?1", [clip.id as i64], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(count, 0);
+
+        // unencrypted clips which are planted afterwards are refused
+        let planted = ClipboardData::new_clipboard("planted");
+        let mut driver = SqliteDriver::open(&path, Some("secret"), None).unwrap();
+        driver.put(&planted).unwrap();
+        let row_id = driver.codec().row_id(planted.id);
+        drop(driver);
+        let value =
+            crate::history::codec::ClipboardCodec::new(None, None, None).unwrap().encode(&planted);
+        conn.execute(
+            "UPDATE clips SET value = ?2 WHERE id = ?1",
+            rusqlite::params![row_id as i64, value],
+        )
+        .unwrap();
+        drop(conn);
+        let hm =
+            HistoryManager::new(&path, HistoryDriverType::Sqlite, Some("secret"), None).unwrap();
+        assert_eq!(hm.load().unwrap(), vec![clip]);
+    }
+
+    #[test]
+    fn test_remove_all() {
+        let path = TempPath::new("history.db");
+        let journal_path = TempPath::new("history.journal");
+        let kept = ClipboardData::new_clipboard("kept");
+        let stored = ClipboardData::new_clipboard("stored");
+        let journaled = ClipboardData::new_clipboard("journaled");
+
+        let mut hm = HistoryManager::new(&path, HistoryDriverType::Sqlite, None, None).unwrap();
+        hm.put_all(&[kept.clone(), stored.clone()]).unwrap();
+        hm.open_journal(&journal_path).unwrap();
+        hm.put(&journaled).unwrap();
+
+        // clips in the journal are deleted as well
+        hm.remove_all(&[stored.id, journaled.id]).unwrap();
+        assert_eq!(hm.load().unwrap(), vec![kept]);
+        assert_eq!(hm.compact_journal().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_load_previews() {
+        let path = TempPath::new("history.db");
+        let small = ClipboardData::new_clipboard("clipcat");
+        let large = ClipboardData::new_clipboard(&"clipcat ".repeat(1000));
+
+        let mut hm = HistoryManager::new(&path, HistoryDriverType::Sqlite, None, None).unwrap();
+        assert!(hm.load_previews(10).unwrap().is_none());
+        hm.save(&[small.clone(), large.clone()]).unwrap();
+
+        let mut clips = hm.load_previews(10).unwrap().unwrap();
+        clips.sort_by_key(|clip| clip.size());
+        assert_eq!(clips[0], small);
+        assert!(clips[1].spilled.is_some());
+        assert_eq!(clips[1].size(), large.size());
+        assert_eq!(hm.get(large.id).unwrap(), Some(large));
+
+        // previews are discarded when the history is changed without them
+        hm.put(&ClipboardData::new_clipboard("changed")).unwrap();
+        assert!(hm.load_previews(10).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_body_loader() {
+        let path = TempPath::new("history.db");
+        let clip = ClipboardData::new_clipboard("clipcat");
+
+        let mut hm =
+            HistoryManager::new(&path, HistoryDriverType::Sqlite, Some("secret"), None).unwrap();
+        hm.save(std::slice::from_ref(&clip)).unwrap();
+
+        // the loader reads through its own handle while the manager is in use
+        let loader = hm.body_loader().unwrap();
+        hm.put(&ClipboardData::new_clipboard("changed")).unwrap();
+        assert_eq!(loader.load_body(clip.id).unwrap(), Some(clip));
+        assert_eq!(loader.load_body(0).unwrap(), None);
     }
 }
@@ -0,0 +1,92 @@
+use std::convert::TryInto;
+
+use clipcat::{
+    spill::{self, SpilledBody},
+    ClipboardData,
+};
+
+use crate::history::codec::ClipboardCodec;
+
+/// Metadata key of the previews of the clips in a history file.
+pub const PREVIEWS_KEY: &str = "previews";
+
+// Clips which are at most this large are kept whole instead of a preview.
+const WHOLE_CLIP_LIMIT: usize = 4096;
+
+// ID, whether the value is a preview, the size and the total size of the clip
+// and the length of the value of a record.
+const HEADER_LEN: usize = 8 + 1 + 8 + 8 + 4;
+
+/// Encodes previews of `clips` with `codec`, small clips are kept whole.
+pub fn encode(codec: &ClipboardCodec, clips: &[ClipboardData]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for clip in clips {
+        let (is_preview, value) = if clip.total_size() <= WHOLE_CLIP_LIMIT {
+            (false, codec.encode(clip))
+        } else {
+            let mut preview = clip.clone();
+            spill::strip_body(&mut preview, None);
+            (true, codec.encode(&preview))
+        };
+        data.extend_from_slice(&codec.row_id(clip.id).to_le_bytes());
+        data.push(is_preview as u8);
+        data.extend_from_slice(&(clip.size() as u64).to_le_bytes());
+        data.extend_from_slice(&(clip.total_size() as u64).to_le_bytes());
+        data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        data.extend_from_slice(&value);
+    }
+    data
+}
+
+/// Decodes previews which were encoded by `encode`, returns `None` if there
+/// are none or one of them can not be decoded.
+pub fn decode(codec: &ClipboardCodec, mut data: &[u8]) -> Option<Vec<ClipboardData>> {
+    if data.is_empty() {
+        return None;
+    }
+
+    let mut clips = Vec::new();
+    while !data.is_empty() {
+        let header = data.get(..HEADER_LEN)?;
+        let id = u64::from_le_bytes(header[..8].try_into().ok()?);
+        let is_preview = header[8] != 0;
+        let size = u64::from_le_bytes(header[9..17].try_into().ok()?) as usize;
+        let total_size = u64::from_le_bytes(header[17..25].try_into().ok()?) as usize;
+        let len = u32::from_le_bytes(header[25..].try_into().ok()?) as usize;
+        let value = data.get(HEADER_LEN..HEADER_LEN + len)?;
+
+        let mut clip = codec.decode(id, value)?;
+        if is_preview {
+            clip.spilled = Some(SpilledBody { hash: None, size, total_size });
+        }
+        clips.push(clip);
+        data = &data[HEADER_LEN + len..];
+    }
+    Some(clips)
+}
+
+#[cfg(test)]
+mod tests {
+    use clipcat::ClipboardData;
+
+    use crate::history::{codec::ClipboardCodec, previews};
+
+    #[test]
+    fn test_encode_decode() {
+        let codec = ClipboardCodec::new(None, None, None).unwrap();
+        let small = ClipboardData::new_clipboard("clipcat");
+        let large = ClipboardData::new_clipboard(&"clipcat ".repeat(1000));
+
+        let data = previews::encode(&codec, &[small.clone(), large.clone()]);
+        let clips = previews::decode(&codec, &data).unwrap();
+        assert_eq!(clips[0], small);
+        assert!(clips[0].spilled.is_none());
+        assert_eq!(clips[1].id, large.id);
+        assert!(clips[1].data.len() < large.data.len());
+        assert_eq!(clips[1].spilled.map(|spilled| spilled.hash), Some(None));
+        assert_eq!(clips[1].size(), large.size());
+
+        assert!(previews::decode(&codec, &[]).is_none());
+        assert!(previews::decode(&codec, &data[..data.len() - 1]).is_none());
+    }
+}
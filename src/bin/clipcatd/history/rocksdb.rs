@@ -8,25 +8,92 @@ use rocksdb::{IteratorMode, Options as RocksDBOptions, WriteBatch, DB as RocksDB
 
 use clipcat::ClipboardData;
 
-use crate::history::{HistoryDriver, HistoryError};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ClipboardValue {
-    pub data: String,
-    pub timestamp: SystemTime,
-}
+use crate::history::{
+    codec::{self, ClipboardCodec},
+    disk_usage,
+    previews::PREVIEWS_KEY,
+    HistoryDriver, HistoryError, HistoryReader, VERSION_KEY,
+};
 
 pub struct RocksDBDriver {
     db: Option<RocksDB>,
+    codec: ClipboardCodec,
 }
 
 impl RocksDBDriver {
-    pub fn open<P: AsRef<Path>>(file_path: P) -> Result<RocksDBDriver, HistoryError> {
+    /// Opens the database, clips are encrypted with a key derived from
+    /// `passphrase` if it is provided and compressed if they are larger than
+    /// `compression_threshold`.
+    pub fn open<P: AsRef<Path>>(
+        file_path: P,
+        passphrase: Option<&str>,
+        compression_threshold: Option<usize>,
+    ) -> Result<RocksDBDriver, HistoryError> {
         let opt = Self::open_options();
         let db = RocksDB::open(&opt, file_path)?;
-        Ok(RocksDBDriver { db: Some(db) })
+
+        let codec = ClipboardCodec::new(
+            db.get(Self::metadata_key(codec::SALT_KEY))?,
+            db.get(Self::metadata_key(codec::CHECK_KEY))?,
+            passphrase,
+        )?
+        .with_compression_threshold(compression_threshold);
+        let driver = RocksDBDriver { db: Some(db), codec };
+        if driver.codec.is_newly_encrypted() {
+            driver.encrypt_clips()?;
+        } else {
+            driver.write_metadata()?;
+        }
+        Ok(driver)
+    }
+
+    // Keys of metadata entries never collide with IDs of clips which are 8 bytes
+    // long.
+    #[inline]
+    fn metadata_key(key: &str) -> Vec<u8> { format!("clipcat:{}", key).into_bytes() }
+
+    fn write_metadata(&self) -> Result<(), HistoryError> {
+        let db = self.db.as_ref().expect("RocksDB must be some");
+        for (key, value) in self.codec.metadata() {
+            db.put(Self::metadata_key(key), value)?;
+        }
+        Ok(())
+    }
+
+    // Encrypts the clips which were stored before encryption was enabled and
+    // stores the metadata in one batch, so that the history is never left with
+    // clips which are refused or can not be decrypted. The clips are moved to
+    // keyed rows and previews are discarded since they are not encrypted
+    // either.
+    fn encrypt_clips(&self) -> Result<(), HistoryError> {
+        let db = self.db.as_ref().expect("RocksDB must be some");
+        let mut batch = WriteBatch::default();
+        let mut count = 0;
+        for (key, value) in db.iterator(IteratorMode::Start).filter(|(k, _)| Self::is_clip_key(k)) {
+            let id = Self::deserialize_id(&key);
+            if let Some(value) = self.codec.reencrypt(id, &value) {
+                batch.delete(key);
+                batch.put(Self::serialize_id(self.codec.row_id(id)), value);
+                count += 1;
+            }
+        }
+        for (key, value) in self.codec.metadata() {
+            batch.put(Self::metadata_key(key), value);
+        }
+        batch.put(Self::metadata_key(PREVIEWS_KEY), []);
+        db.write(batch)?;
+        // drops the unencrypted values from the files of the database
+        db.compact_range(None::<&[u8]>, None::<&[u8]>);
+        tracing::info!("Encrypt {} clip(s) of history", count);
+        Ok(())
     }
 
+    #[inline]
+    fn is_clip_key(key: &[u8]) -> bool { key.len() == std::mem::size_of::<u64>() }
+
+    #[inline]
+    fn is_metadata_key(key: &[u8]) -> bool { key.starts_with(b"clipcat:") }
+
     fn open_options() -> RocksDBOptions {
         let mut opt = RocksDBOptions::default();
         opt.create_if_missing(true);
@@ -35,38 +102,15 @@ impl RocksDBDriver {
 
     fn serialize_id(id: u64) -> Vec<u8> { bincode::serialize(&id).expect("u64 is serializable") }
 
-    fn deserialize_id(id: &[u8]) -> u64 {
-        bincode::deserialize(&id).expect("u64 is deserializable")
-    }
+    fn deserialize_id(id: &[u8]) -> u64 { bincode::deserialize(id).expect("u64 is deserializable") }
 
-    fn deserialize_data(id: u64, raw_data: &[u8]) -> Option<ClipboardData> {
-        use clipcat::ClipboardType;
-
-        bincode::deserialize::<ClipboardValue>(&raw_data)
-            .map(|value| ClipboardData {
-                id,
-                data: value.data.clone(),
-                timestamp: value.timestamp,
-                clipboard_type: ClipboardType::Primary,
-            })
-            .map_err(|_| {
-                tracing::warn!("Failed to deserialize ClipboardValue");
-            })
-            .ok()
+    fn serialize_entry(&self, id: u64, data: &ClipboardData) -> (Vec<u8>, Vec<u8>) {
+        (Self::serialize_id(self.codec.row_id(id)), self.codec.encode(data))
     }
 
-    fn serialize_data(data: &ClipboardData) -> Vec<u8> {
-        let value = ClipboardValue { data: data.data.clone(), timestamp: data.timestamp };
-        bincode::serialize(&value).expect("ClipboardData is serializable")
-    }
-
-    fn serialize_entry(id: u64, data: &ClipboardData) -> (Vec<u8>, Vec<u8>) {
-        (Self::serialize_id(id), Self::serialize_data(data))
-    }
-
-    fn deserialize_entry(id: &[u8], data: &[u8]) -> Option<ClipboardData> {
+    fn deserialize_entry(&self, id: &[u8], data: &[u8]) -> Option<ClipboardData> {
         let id = Self::deserialize_id(id);
-        Self::deserialize_data(id, data)
+        self.codec.decode(id, data)
     }
 }
 
@@ -75,27 +119,28 @@ impl HistoryDriver for RocksDBDriver {
         let db = self.db.as_ref().expect("RocksDB must be some");
         let iter = db.iterator(IteratorMode::Start);
         let clips = iter
-            .filter_map(|(id, data)| Self::deserialize_entry(id.as_ref(), data.as_ref()))
+            .filter(|(id, _)| Self::is_clip_key(id))
+            .filter_map(|(id, data)| self.deserialize_entry(id.as_ref(), data.as_ref()))
             .collect();
         Ok(clips)
     }
 
     fn save(&mut self, data: &[ClipboardData]) -> Result<(), HistoryError> {
-        let db = self.db.as_mut().expect("RocksDB must be some");
-
-        let iter = db.iterator(IteratorMode::Start);
-        let ids_in_db: HashSet<Vec<u8>> = iter.map(|(k, _v)| k.into_vec()).collect();
-
         let mut batch = WriteBatch::default();
         let unsaved_ids: HashSet<_> = data
             .iter()
             .map(|clip| {
-                let (id, data) = Self::serialize_entry(clip.id, &clip);
+                let (id, data) = self.serialize_entry(clip.id, clip);
                 batch.put(id.clone(), data);
                 id
             })
             .collect();
 
+        let db = self.db.as_mut().expect("RocksDB must be some");
+        let iter = db.iterator(IteratorMode::Start);
+        let ids_in_db: HashSet<Vec<u8>> =
+            iter.map(|(k, _v)| k.into_vec()).filter(|k| Self::is_clip_key(k)).collect();
+
         ids_in_db.difference(&unsaved_ids).for_each(|id| {
             let _ = db.delete(id);
         });
@@ -105,18 +150,22 @@ impl HistoryDriver for RocksDBDriver {
     }
 
     fn shrink_to(&mut self, min_capacity: usize) -> Result<(), HistoryError> {
-        let db = self.db.as_mut().expect("RocksDB must be some");
-        if db.iterator(IteratorMode::Start).count() < min_capacity {
+        let db = self.db.as_ref().expect("RocksDB must be some");
+        if db.iterator(IteratorMode::Start).filter(|(k, _)| Self::is_clip_key(k)).count()
+            < min_capacity
+        {
             return Ok(());
         }
 
         let iter = db.iterator(IteratorMode::Start);
         let timestamps = iter
+            .filter(|(k, _)| Self::is_clip_key(k))
             .filter_map(|(k, v)| {
                 let id = Self::deserialize_id(&k);
-                let v = Self::deserialize_data(id, &v);
+                let v = self.codec.decode(id, &v);
                 v.map(|v| (v, Vec::from(k.as_ref())))
             })
+            .filter(|(v, _)| !v.pinned)
             .map(|(v, id)| (v.timestamp, id))
             .collect::<HashMap<SystemTime, Vec<u8>>>();
 
@@ -124,8 +173,8 @@ impl HistoryDriver for RocksDBDriver {
             let mut keys = timestamps.keys().cloned().collect::<Vec<_>>();
             keys.sort();
             let len = keys.len();
-            keys.resize(len - min_capacity, SystemTime::now());
-            keys.iter().filter_map(|ts| timestamps.get(&ts)).fold(
+            keys.truncate(len.saturating_sub(min_capacity));
+            keys.iter().filter_map(|ts| timestamps.get(ts)).fold(
                 WriteBatch::default(),
                 |mut batch, id| {
                     batch.delete(id);
@@ -139,29 +188,100 @@ impl HistoryDriver for RocksDBDriver {
     }
 
     fn clear(&mut self) -> Result<(), HistoryError> {
-        let db_path = {
+        let (db_path, version) = {
             let db = self.db.take().expect("RocksDB must be some");
             let db_path = db.path().to_path_buf();
+            let version = db.get(Self::metadata_key(VERSION_KEY))?;
             drop(db);
-            db_path
+            (db_path, version)
         };
 
         RocksDB::destroy(&RocksDBOptions::default(), &db_path)?;
         self.db = Some(RocksDB::open(&Self::open_options(), &db_path)?);
+        if let Some(version) = version {
+            self.set_metadata(VERSION_KEY, &version)?;
+        }
+        self.write_metadata()
+    }
+
+    fn metadata(&self, key: &str) -> Result<Option<Vec<u8>>, HistoryError> {
+        let db = self.db.as_ref().expect("RocksDB must be some");
+        Ok(db.get(Self::metadata_key(key))?)
+    }
+
+    fn set_metadata(&mut self, key: &str, value: &[u8]) -> Result<(), HistoryError> {
+        let db = self.db.as_ref().expect("RocksDB must be some");
+        db.put(Self::metadata_key(key), value)?;
         Ok(())
     }
 
+    #[inline]
+    fn codec(&self) -> &ClipboardCodec { &self.codec }
+
+    fn vacuum(&mut self) -> Result<u64, HistoryError> {
+        let db = self.db.as_ref().expect("RocksDB must be some");
+        let size = disk_usage(db.path());
+
+        let batch = db
+            .iterator(IteratorMode::Start)
+            .filter(|(key, value)| {
+                if Self::is_clip_key(key) {
+                    self.deserialize_entry(key, value).is_none()
+                } else {
+                    !Self::is_metadata_key(key)
+                }
+            })
+            .fold(WriteBatch::default(), |mut batch, (key, _)| {
+                batch.delete(key);
+                batch
+            });
+        db.write(batch)?;
+        db.compact_range(None::<&[u8]>, None::<&[u8]>);
+
+        Ok(size.saturating_sub(disk_usage(db.path())))
+    }
+
     fn put(&mut self, data: &ClipboardData) -> Result<(), HistoryError> {
+        let value = self.codec.encode(data);
         let db = self.db.as_mut().expect("RocksDB must be some");
-        db.put(Self::serialize_id(data.id), Self::serialize_data(&data))?;
+        db.put(Self::serialize_id(self.codec.row_id(data.id)), value)?;
         Ok(())
     }
 
     fn get(&self, id: u64) -> Result<Option<ClipboardData>, HistoryError> {
         let db = self.db.as_ref().expect("RocksDB must be some");
-        let serialized_id = Self::serialize_id(id);
+        let serialized_id = Self::serialize_id(self.codec.row_id(id));
         match db.get(&serialized_id)? {
-            Some(data) => Ok(Self::deserialize_data(id, &data)),
+            Some(data) => Ok(self.codec.decode(id, &data)),
+            None => Ok(None),
+        }
+    }
+
+    fn remove(&mut self, id: u64) -> Result<(), HistoryError> {
+        let db = self.db.as_ref().expect("RocksDB must be some");
+        db.delete(Self::serialize_id(self.codec.row_id(id)))?;
+        Ok(())
+    }
+
+    fn open_reader(&self) -> Result<Box<dyn HistoryReader>, HistoryError> {
+        let db = self.db.as_ref().expect("RocksDB must be some");
+        let reader = RocksDB::open_for_read_only(&RocksDBOptions::default(), db.path(), false)?;
+        Ok(Box::new(RocksDBReader { db: reader, codec: self.codec.clone() }))
+    }
+}
+
+/// Reads the database as it was when the reader was opened, which holds all
+/// clips whose previews were loaded before. Table files stay open, so they can
+/// be read after the driver compacts them away.
+pub struct RocksDBReader {
+    db: RocksDB,
+    codec: ClipboardCodec,
+}
+
+impl HistoryReader for RocksDBReader {
+    fn get(&self, id: u64) -> Result<Option<ClipboardData>, HistoryError> {
+        match self.db.get(RocksDBDriver::serialize_id(self.codec.row_id(id)))? {
+            Some(data) => Ok(self.codec.decode(id, &data)),
             None => Ok(None),
         }
     }
@@ -0,0 +1,299 @@
+use std::{
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+
+use clipcat::ClipboardData;
+
+use crate::history::{
+    codec::{self, ClipboardCodec},
+    disk_usage,
+    previews::PREVIEWS_KEY,
+    HistoryDriver, HistoryError, HistoryReader,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS metadata (
+    key TEXT PRIMARY KEY NOT NULL,
+    value BLOB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS clips (
+    id INTEGER PRIMARY KEY NOT NULL,
+    timestamp INTEGER NOT NULL,
+    pinned INTEGER NOT NULL DEFAULT 0,
+    value BLOB NOT NULL
+);
+CREATE INDEX IF NOT EXISTS clips_timestamp ON clips (timestamp);
+";
+
+pub struct SqliteDriver {
+    // rusqlite::Connection is not Sync
+    conn: Mutex<Connection>,
+    codec: ClipboardCodec,
+    file_path: PathBuf,
+}
+
+impl SqliteDriver {
+    /// Opens the database, clips are encrypted with a key derived from
+    /// `passphrase` if it is provided and compressed if they are larger than
+    /// `compression_threshold`.
+    pub fn open<P: AsRef<Path>>(
+        file_path: P,
+        passphrase: Option<&str>,
+        compression_threshold: Option<usize>,
+    ) -> Result<SqliteDriver, HistoryError> {
+        if let Some(dir) = file_path.as_ref().parent() {
+            std::fs::create_dir_all(dir).map_err(|source| HistoryError::CreateDirectory {
+                path: dir.to_path_buf(),
+                source,
+            })?;
+        }
+
+        let file_path = file_path.as_ref().to_path_buf();
+        let mut conn = Connection::open(&file_path)?;
+        conn.execute_batch(SCHEMA)?;
+
+        let codec = ClipboardCodec::new(
+            Self::get_metadata(&conn, codec::SALT_KEY)?,
+            Self::get_metadata(&conn, codec::CHECK_KEY)?,
+            passphrase,
+        )?
+        .with_compression_threshold(compression_threshold);
+        if codec.is_newly_encrypted() {
+            Self::encrypt_clips(&mut conn, &codec)?;
+        } else {
+            Self::write_metadata(&conn, &codec)?;
+        }
+
+        Ok(SqliteDriver { conn: Mutex::new(conn), codec, file_path })
+    }
+
+    fn get_metadata(conn: &Connection, key: &str) -> Result<Option<Vec<u8>>, HistoryError> {
+        let value = conn
+            .query_row("SELECT value FROM metadata WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()?;
+        Ok(value)
+    }
+
+    fn write_metadata(conn: &Connection, codec: &ClipboardCodec) -> Result<(), HistoryError> {
+        for (key, value) in codec.metadata() {
+            conn.execute(
+                "INSERT OR REPLACE INTO metadata (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )?;
+        }
+        Ok(())
+    }
+
+    // Encrypts the clips which were stored before encryption was enabled and
+    // stores the metadata in one transaction, so that the history is never left
+    // with clips which are refused or can not be decrypted. The clips are moved
+    // to keyed rows and previews are discarded since they are not encrypted
+    // either.
+    fn encrypt_clips(conn: &mut Connection, codec: &ClipboardCodec) -> Result<(), HistoryError> {
+        let tx = conn.transaction()?;
+        let values: Vec<(i64, Vec<u8>)> = {
+            let mut stmt = tx.prepare("SELECT id, value FROM clips")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<Result<_, _>>()?
+        };
+        let mut count = 0;
+        for (id, value) in values {
+            if let Some(value) = codec.reencrypt(id as u64, &value) {
+                let row_id = codec.row_id(id as u64) as i64;
+                tx.execute(
+                    "UPDATE clips SET id = ?2, value = ?3 WHERE id = ?1",
+                    params![id, row_id, value],
+                )?;
+                count += 1;
+            }
+        }
+        Self::write_metadata(&tx, codec)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES (?1, ?2)",
+            params![PREVIEWS_KEY, &[] as &[u8]],
+        )?;
+        tx.commit()?;
+        // drops the unencrypted values from the free pages of the database
+        conn.execute_batch("VACUUM")?;
+        tracing::info!("Encrypt {} clip(s) of history", count);
+        Ok(())
+    }
+
+    fn get_value(conn: &Connection, id: u64) -> Result<Option<Vec<u8>>, HistoryError> {
+        let value = conn
+            .query_row("SELECT value FROM clips WHERE id = ?1", params![id as i64], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(value)
+    }
+
+    #[inline]
+    fn conn(&self) -> MutexGuard<'_, Connection> { self.conn.lock().expect("lock is not poisoned") }
+
+    #[inline]
+    fn timestamp_to_millis(timestamp: SystemTime) -> i64 {
+        let millis = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        i64::try_from(millis).unwrap_or(i64::MAX)
+    }
+
+    fn put_clip(&self, conn: &Connection, data: &ClipboardData) -> Result<(), HistoryError> {
+        // IDs are hashes which use the full range of u64, they are stored as i64
+        conn.execute(
+            "INSERT OR REPLACE INTO clips (id, timestamp, pinned, value) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                self.codec.row_id(data.id) as i64,
+                Self::timestamp_to_millis(data.timestamp),
+                data.pinned,
+                self.codec.encode(data)
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn query_clips(
+        &self,
+        sql: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<ClipboardData>, HistoryError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(params, |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))?;
+
+        let mut clips = Vec::new();
+        for row in rows {
+            let (id, value): (i64, Vec<u8>) = row?;
+            if let Some(clip) = self.codec.decode(id as u64, &value) {
+                clips.push(clip);
+            }
+        }
+        Ok(clips)
+    }
+}
+
+impl HistoryDriver for SqliteDriver {
+    fn load(&self) -> Result<Vec<ClipboardData>, HistoryError> {
+        self.query_clips("SELECT id, value FROM clips", &[])
+    }
+
+    fn load_latest(&self, limit: usize) -> Result<Vec<ClipboardData>, HistoryError> {
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        self.query_clips(
+            "SELECT id, value FROM clips WHERE pinned OR id IN (SELECT id FROM clips WHERE NOT \
+             pinned ORDER BY timestamp DESC LIMIT ?1)",
+            &[&limit as &dyn rusqlite::ToSql],
+        )
+    }
+
+    fn save(&mut self, data: &[ClipboardData]) -> Result<(), HistoryError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute("CREATE TEMPORARY TABLE IF NOT EXISTS saved_ids (id INTEGER PRIMARY KEY)", [])?;
+        tx.execute("DELETE FROM saved_ids", [])?;
+        for clip in data {
+            self.put_clip(&tx, clip)?;
+            tx.execute(
+                "INSERT OR IGNORE INTO saved_ids (id) VALUES (?1)",
+                params![self.codec.row_id(clip.id) as i64],
+            )?;
+        }
+        tx.execute("DELETE FROM clips WHERE id NOT IN (SELECT id FROM saved_ids)", [])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn shrink_to(&mut self, min_capacity: usize) -> Result<(), HistoryError> {
+        let min_capacity = i64::try_from(min_capacity).unwrap_or(i64::MAX);
+        self.conn().execute(
+            "DELETE FROM clips WHERE NOT pinned AND id NOT IN (SELECT id FROM clips WHERE NOT \
+             pinned ORDER BY timestamp DESC LIMIT ?1)",
+            params![min_capacity],
+        )?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), HistoryError> {
+        self.conn().execute("DELETE FROM clips", [])?;
+        Ok(())
+    }
+
+    fn metadata(&self, key: &str) -> Result<Option<Vec<u8>>, HistoryError> {
+        Self::get_metadata(&self.conn(), key)
+    }
+
+    fn set_metadata(&mut self, key: &str, value: &[u8]) -> Result<(), HistoryError> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    #[inline]
+    fn codec(&self) -> &ClipboardCodec { &self.codec }
+
+    fn vacuum(&mut self) -> Result<u64, HistoryError> {
+        let size = disk_usage(&self.file_path);
+        let conn = self.conn();
+
+        let undecodable: Vec<i64> = {
+            let mut stmt = conn.prepare("SELECT id, value FROM clips")?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))?;
+            let mut ids = Vec::new();
+            for row in rows {
+                let (id, value): (i64, Vec<u8>) = row?;
+                if self.codec.decode(id as u64, &value).is_none() {
+                    ids.push(id);
+                }
+            }
+            ids
+        };
+        for id in undecodable {
+            conn.execute("DELETE FROM clips WHERE id = ?1", params![id])?;
+        }
+        conn.execute_batch("VACUUM")?;
+
+        Ok(size.saturating_sub(disk_usage(&self.file_path)))
+    }
+
+    fn put(&mut self, data: &ClipboardData) -> Result<(), HistoryError> {
+        let conn = self.conn();
+        self.put_clip(&conn, data)
+    }
+
+    fn get(&self, id: u64) -> Result<Option<ClipboardData>, HistoryError> {
+        let value = Self::get_value(&self.conn(), self.codec.row_id(id))?;
+        Ok(value.and_then(|value| self.codec.decode(id, &value)))
+    }
+
+    fn remove(&mut self, id: u64) -> Result<(), HistoryError> {
+        let row_id = self.codec.row_id(id) as i64;
+        self.conn().execute("DELETE FROM clips WHERE id = ?1", params![row_id])?;
+        Ok(())
+    }
+
+    fn open_reader(&self) -> Result<Box<dyn HistoryReader>, HistoryError> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let conn = Connection::open_with_flags(&self.file_path, flags)?;
+        Ok(Box::new(SqliteReader { conn: Mutex::new(conn), codec: self.codec.clone() }))
+    }
+}
+
+/// Reads the database through a connection of its own.
+pub struct SqliteReader {
+    conn: Mutex<Connection>,
+    codec: ClipboardCodec,
+}
+
+impl HistoryReader for SqliteReader {
+    fn get(&self, id: u64) -> Result<Option<ClipboardData>, HistoryError> {
+        let conn = self.conn.lock().expect("lock is not poisoned");
+        let value = SqliteDriver::get_value(&conn, self.codec.row_id(id))?;
+        Ok(value.and_then(|value| self.codec.decode(id, &value)))
+    }
+}
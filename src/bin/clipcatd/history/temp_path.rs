@@ -0,0 +1,40 @@
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A unique path in the temporary directory for tests, the file or directory
+/// at the path is removed when it is dropped, even if the test fails.
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    pub fn new(name: &str) -> TempPath {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let count = COUNTER.fetch_add(1, Ordering::SeqCst);
+        TempPath(std::env::temp_dir().join(format!("clipcat-{}-{}-{}", nanos, count, name)))
+    }
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path { &self.0 }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path { &self.0 }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = if self.0.is_dir() {
+            std::fs::remove_dir_all(&self.0)
+        } else {
+            std::fs::remove_file(&self.0)
+        };
+    }
+}
@@ -0,0 +1,211 @@
+use std::{fmt, str::FromStr};
+
+use crate::config::{self, HotkeyAction};
+
+/// Modifier masks of X11, the portal names them as in `Display`.
+pub const SHIFT_MASK: u16 = 1 << 0;
+pub const CONTROL_MASK: u16 = 1 << 2;
+pub const ALT_MASK: u16 = 1 << 3;
+pub const SUPER_MASK: u16 = 1 << 6;
+
+// Keysyms of keys which are not printable, printable Latin-1 characters are
+// their own keysym.
+const NAMED_KEYSYMS: &[(&str, u32)] = &[
+    ("space", 0x0020),
+    ("backspace", 0xff08),
+    ("tab", 0xff09),
+    ("return", 0xff0d),
+    ("enter", 0xff0d),
+    ("escape", 0xff1b),
+    ("home", 0xff50),
+    ("left", 0xff51),
+    ("up", 0xff52),
+    ("right", 0xff53),
+    ("down", 0xff54),
+    ("page_up", 0xff55),
+    ("page_down", 0xff56),
+    ("end", 0xff57),
+    ("print", 0xff61),
+    ("insert", 0xff63),
+    ("delete", 0xffff),
+];
+
+// Keysym of F1, F2 to F12 follow in order.
+const F1_KEYSYM: u32 = 0xffbe;
+
+#[derive(Debug, Snafu)]
+pub enum HotkeyError {
+    #[snafu(display("Unknown modifier {} in hotkey {}", modifier, hotkey))]
+    UnknownModifier { hotkey: String, modifier: String },
+
+    #[snafu(display("Unknown key {} in hotkey {}", key, hotkey))]
+    UnknownKey { hotkey: String, key: String },
+
+    #[snafu(display("Hotkey {} runs a command but has no command", hotkey))]
+    MissingCommand { hotkey: String },
+}
+
+/// A key with modifiers, e.g. `super+shift+v`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Hotkey {
+    pub modifiers: u16,
+    pub keysym: u32,
+
+    // the name of the key as it is configured, in lowercase
+    key: String,
+}
+
+impl FromStr for Hotkey {
+    type Err = HotkeyError;
+
+    fn from_str(hotkey: &str) -> Result<Hotkey, HotkeyError> {
+        let hotkey = hotkey.trim().to_lowercase();
+        let mut names: Vec<_> = hotkey.split('+').map(str::trim).collect();
+        let key = names.pop().unwrap_or_default().to_owned();
+
+        let mut modifiers = 0;
+        for modifier in names {
+            modifiers |= match modifier {
+                "shift" => SHIFT_MASK,
+                "ctrl" | "control" => CONTROL_MASK,
+                "alt" | "mod1" => ALT_MASK,
+                "super" | "logo" | "mod4" => SUPER_MASK,
+                _ => {
+                    return Err(HotkeyError::UnknownModifier {
+                        hotkey: hotkey.clone(),
+                        modifier: modifier.to_owned(),
+                    })
+                }
+            };
+        }
+
+        let keysym = keysym(&key)
+            .ok_or_else(|| HotkeyError::UnknownKey { hotkey: hotkey.clone(), key: key.clone() })?;
+        Ok(Hotkey { modifiers, keysym, key })
+    }
+}
+
+// Written in the format of the shortcuts of XDG desktop portal, e.g.
+// `LOGO+SHIFT+v`.
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &(mask, name) in &[
+            (CONTROL_MASK, "CTRL"),
+            (ALT_MASK, "ALT"),
+            (SHIFT_MASK, "SHIFT"),
+            (SUPER_MASK, "LOGO"),
+        ] {
+            if self.modifiers & mask != 0 {
+                write!(f, "{}+", name)?;
+            }
+        }
+        f.write_str(&self.key)
+    }
+}
+
+fn keysym(key: &str) -> Option<u32> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_graphic() => return Some(c as u32),
+        _ => {}
+    }
+    if let Some(number) = key.strip_prefix('f').and_then(|number| number.parse::<u32>().ok()) {
+        if (1..=12).contains(&number) {
+            return Some(F1_KEYSYM + number - 1);
+        }
+    }
+    NAMED_KEYSYMS.iter().find(|&&(name, _)| name == key).map(|&(_, keysym)| keysym)
+}
+
+/// What a hotkey does when it is pressed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Action {
+    /// Runs a shell command, e.g. `clipcat-menu`.
+    RunCommand(String),
+
+    /// Puts the second most recent clip into the clipboard.
+    PromotePrevious,
+
+    ToggleMonitor,
+}
+
+impl Action {
+    /// Describes the action to the desktop portal.
+    pub fn description(&self) -> String {
+        match self {
+            Action::RunCommand(command) => format!("Run {}", command),
+            Action::PromotePrevious => "Put the previous clip into the clipboard".to_owned(),
+            Action::ToggleMonitor => "Pause or resume recording clips".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Binding {
+    pub hotkey: Hotkey,
+    pub action: Action,
+}
+
+impl Binding {
+    pub fn new(config: &config::Hotkey) -> Result<Binding, HotkeyError> {
+        let hotkey = config.key.parse()?;
+        let action = match config.action {
+            HotkeyAction::Menu => Action::RunCommand(
+                config.command.clone().unwrap_or_else(|| clipcat::MENU_PROGRAM_NAME.to_owned()),
+            ),
+            HotkeyAction::Command => match config.command {
+                Some(ref command) => Action::RunCommand(command.clone()),
+                None => return Err(HotkeyError::MissingCommand { hotkey: config.key.clone() }),
+            },
+            HotkeyAction::PromotePrevious => Action::PromotePrevious,
+            HotkeyAction::ToggleMonitor => Action::ToggleMonitor,
+        };
+        Ok(Binding { hotkey, action })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{self, HotkeyAction},
+        hotkey::{Action, Binding, Hotkey, CONTROL_MASK, SHIFT_MASK, SUPER_MASK},
+    };
+
+    #[test]
+    fn test_parse() {
+        let hotkey: Hotkey = "Super+Shift+V".parse().unwrap();
+        assert_eq!(hotkey.modifiers, SUPER_MASK | SHIFT_MASK);
+        assert_eq!(hotkey.keysym, 'v' as u32);
+        assert_eq!(hotkey.to_string(), "SHIFT+LOGO+v");
+
+        let hotkey: Hotkey = "ctrl + F12".parse().unwrap();
+        assert_eq!(hotkey.modifiers, CONTROL_MASK);
+        assert_eq!(hotkey.keysym, 0xffc9);
+
+        let hotkey: Hotkey = "insert".parse().unwrap();
+        assert_eq!(hotkey.modifiers, 0);
+        assert_eq!(hotkey.keysym, 0xff63);
+
+        assert!("hyper+v".parse::<Hotkey>().is_err());
+        assert!("super+f13".parse::<Hotkey>().is_err());
+        assert!("super+".parse::<Hotkey>().is_err());
+    }
+
+    #[test]
+    fn test_binding() {
+        let mut config =
+            config::Hotkey { key: "super+v".to_owned(), action: HotkeyAction::Menu, command: None };
+        assert_eq!(
+            Binding::new(&config).unwrap().action,
+            Action::RunCommand("clipcat-menu".to_owned())
+        );
+
+        config.action = HotkeyAction::Command;
+        assert!(Binding::new(&config).is_err());
+        config.command = Some("clipcatctl tui".to_owned());
+        assert_eq!(
+            Binding::new(&config).unwrap().action,
+            Action::RunCommand("clipcatctl tui".to_owned())
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::config::LogRotation;
+
+/// A log file which is rotated when it grows beyond `max_size` or when the
+/// period of `rotation` is over. Rotated files are renamed to `<path>.1`,
+/// `<path>.2` and so on, only `max_files` of them are kept.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: LogRotation,
+    max_size: Option<u64>,
+    max_files: usize,
+
+    file: File,
+    size: u64,
+    period: u64,
+}
+
+impl RotatingFile {
+    fn open(
+        path: &Path,
+        rotation: LogRotation,
+        max_size: Option<u64>,
+        max_files: usize,
+    ) -> io::Result<RotatingFile> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            rotation,
+            max_size,
+            max_files,
+            file,
+            size,
+            period: period(rotation, SystemTime::now()),
+        })
+    }
+
+    fn write_record(&mut self, buf: &[u8], now: SystemTime) -> io::Result<usize> {
+        let period = period(self.rotation, now);
+        let full = self
+            .max_size
+            .is_some_and(|max_size| self.size > 0 && self.size + buf.len() as u64 > max_size);
+        if period != self.period || full {
+            self.rotate()?;
+            self.period = period;
+        }
+
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let _ = std::fs::remove_file(rotated_path(&self.path, self.max_files));
+            for index in (1..self.max_files).rev() {
+                let from = rotated_path(&self.path, index);
+                if from.exists() {
+                    std::fs::rename(&from, rotated_path(&self.path, index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{}", index));
+    PathBuf::from(path)
+}
+
+// Index of the hour or the day of `time` in UTC, files are rotated when it
+// changes.
+fn period(rotation: LogRotation, time: SystemTime) -> u64 {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+    match rotation {
+        LogRotation::Never => 0,
+        LogRotation::Hourly => secs / (60 * 60),
+        LogRotation::Daily => secs / (24 * 60 * 60),
+    }
+}
+
+/// Where the log records of clipcatd are written, the formatter writes each
+/// record at once, so files are only rotated between records.
+#[derive(Clone)]
+pub enum LogOutput {
+    Stderr,
+    File(Arc<Mutex<RotatingFile>>),
+}
+
+impl LogOutput {
+    pub fn file(
+        path: &Path,
+        rotation: LogRotation,
+        max_size: Option<u64>,
+        max_files: usize,
+    ) -> io::Result<LogOutput> {
+        let file = RotatingFile::open(path, rotation, max_size, max_files)?;
+        Ok(LogOutput::File(Arc::new(Mutex::new(file))))
+    }
+
+    #[inline]
+    pub fn is_file(&self) -> bool { matches!(self, LogOutput::File(_)) }
+}
+
+impl MakeWriter for LogOutput {
+    type Writer = LogOutput;
+
+    fn make_writer(&self) -> LogOutput { self.clone() }
+}
+
+impl Write for LogOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            LogOutput::Stderr => io::stderr().write(buf),
+            LogOutput::File(file) => {
+                let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+                file.write_record(buf, SystemTime::now())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            LogOutput::Stderr => io::stderr().flush(),
+            LogOutput::File(file) => {
+                file.lock().unwrap_or_else(PoisonError::into_inner).file.flush()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use crate::{
+        config::LogRotation,
+        log_file::{period, rotated_path, RotatingFile},
+    };
+
+    #[test]
+    fn test_rotate() {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let dir = std::env::temp_dir().join(format!("clipcat-log-{}", nanos));
+        let path = dir.join("clipcatd.log");
+        let read = |path: &Path| std::fs::read_to_string(path).unwrap();
+
+        let mut file = RotatingFile::open(&path, LogRotation::Daily, Some(8), 2).unwrap();
+        let day = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        file.period = period(LogRotation::Daily, day);
+        file.write_record(b"first\n", day).unwrap();
+        file.write_record(b"second\n", day).unwrap();
+        file.write_record(b"third\n", day).unwrap();
+        assert_eq!(read(&path), "third\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "second\n");
+        assert_eq!(read(&rotated_path(&path, 2)), "first\n");
+
+        // only two rotated files are kept
+        let next_day = day + Duration::from_secs(24 * 60 * 60);
+        file.write_record(b"4\n", next_day).unwrap();
+        assert_eq!(read(&path), "4\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "third\n");
+        assert_eq!(read(&rotated_path(&path, 2)), "second\n");
+        assert!(!rotated_path(&path, 3).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
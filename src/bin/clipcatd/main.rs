@@ -6,10 +6,25 @@ extern crate serde;
 
 use std::sync::atomic;
 
+mod activation;
+mod backup;
+mod capture;
 mod command;
 mod config;
 mod error;
 mod history;
+mod hotkey;
+mod log_file;
+mod metrics;
+#[cfg(feature = "dbus")]
+mod notification;
+#[cfg(feature = "plugins")]
+mod plugin;
+#[cfg(feature = "scripts")]
+mod script;
+mod secret;
+mod transform;
+mod webhook;
 mod worker;
 
 use self::command::Command;
@@ -0,0 +1,321 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+use clipcat::{ClipboardData, ClipboardType, MemoryPressure};
+
+// Upper bounds of the latency buckets in seconds, the defaults of the client
+// libraries of Prometheus.
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+// Methods of the gRPC services which are labeled in the metrics. Requests run
+// before they are authenticated, so all other paths share one label instead of
+// adding a label for each path which a client makes up.
+const GRPC_METHODS: &[&str] = &[
+    "/manager.Manager/List",
+    "/manager.Manager/Get",
+    "/manager.Manager/GetData",
+    "/manager.Manager/GetCurrentClipboard",
+    "/manager.Manager/GetCurrentPrimary",
+    "/manager.Manager/Remove",
+    "/manager.Manager/BatchRemove",
+    "/manager.Manager/Clear",
+    "/manager.Manager/Undo",
+    "/manager.Manager/ListTrash",
+    "/manager.Manager/RestoreFromTrash",
+    "/manager.Manager/Export",
+    "/manager.Manager/Import",
+    "/manager.Manager/Vacuum",
+    "/manager.Manager/Deduplicate",
+    "/manager.Manager/Insert",
+    "/manager.Manager/Update",
+    "/manager.Manager/MarkAsClipboard",
+    "/manager.Manager/MarkAsPrimary",
+    "/manager.Manager/Pin",
+    "/manager.Manager/Unpin",
+    "/manager.Manager/Tag",
+    "/manager.Manager/Untag",
+    "/manager.Manager/Length",
+    "/manager.Manager/Stats",
+    "/manager.Manager/Search",
+    "/manager.Manager/ListSnippets",
+    "/manager.Manager/InsertSnippet",
+    "/monitor.Monitor/EnableMonitor",
+    "/monitor.Monitor/DisableMonitor",
+    "/monitor.Monitor/ToggleMonitor",
+    "/monitor.Monitor/GetMonitorState",
+    "/monitor.Monitor/Subscribe",
+    "/settings.Settings/GetSetting",
+    "/settings.Settings/SetSetting",
+];
+const UNKNOWN_GRPC_METHOD: &str = "unknown";
+
+#[derive(Debug, Default)]
+struct Histogram {
+    // cumulative, like the buckets which are exposed
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if value <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+/// Counters of clipcatd, they are exposed in the text format of Prometheus.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    captured_clipboard: AtomicU64,
+    captured_primary: AtomicU64,
+    dropped_events: Arc<AtomicU64>,
+    grpc_requests: Mutex<BTreeMap<&'static str, Histogram>>,
+}
+
+impl Metrics {
+    #[inline]
+    pub fn new() -> Metrics { Metrics::default() }
+
+    pub fn clip_captured(&self, clipboard_type: ClipboardType) {
+        let counter = match clipboard_type {
+            ClipboardType::Clipboard => &self.captured_clipboard,
+            ClipboardType::Primary => &self.captured_primary,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts `count` clipboard events which a subscriber lagged behind on.
+    pub fn events_dropped(&self, count: u64) {
+        self.dropped_events.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// The counter of dropped events, for subscribers of the gRPC service.
+    #[inline]
+    pub fn dropped_events(&self) -> Arc<AtomicU64> { self.dropped_events.clone() }
+
+    /// Records the latency of a request, `method` is the path of the request.
+    /// Paths which are not a method of the services are recorded as `unknown`.
+    pub fn grpc_request(&self, method: &str, duration: Duration) {
+        let method = GRPC_METHODS
+            .iter()
+            .find(|known| **known == method)
+            .copied()
+            .unwrap_or(UNKNOWN_GRPC_METHOD);
+        let mut requests = self.grpc_requests.lock().unwrap_or_else(PoisonError::into_inner);
+        requests.entry(method).or_default().observe(duration.as_secs_f64());
+    }
+
+    /// Renders the metrics with the gauges of the clips in the history and the
+    /// clips which were spilled or evicted under memory pressure.
+    pub fn render<'a>(
+        &self,
+        clips: impl Iterator<Item = &'a ClipboardData>,
+        pressure: MemoryPressure,
+    ) -> String {
+        let (mut counts, mut sizes) = ([0usize; 2], [0usize; 2]);
+        let mut resident = 0;
+        for clip in clips {
+            let index = type_index(clip.clipboard_type);
+            counts[index] += 1;
+            sizes[index] += clip.size();
+            resident += clip.resident_size();
+        }
+
+        let mut out = String::new();
+        header(
+            &mut out,
+            "clipcat_clips_captured_total",
+            "counter",
+            "Clips captured by the monitor",
+        );
+        for &clipboard_type in &[ClipboardType::Clipboard, ClipboardType::Primary] {
+            let counter = match clipboard_type {
+                ClipboardType::Clipboard => &self.captured_clipboard,
+                ClipboardType::Primary => &self.captured_primary,
+            };
+            let _ = writeln!(
+                out,
+                "clipcat_clips_captured_total{{clipboard_type=\"{}\"}} {}",
+                type_label(clipboard_type),
+                counter.load(Ordering::Relaxed)
+            );
+        }
+
+        header(&mut out, "clipcat_history_clips", "gauge", "Clips in the history");
+        for &clipboard_type in &[ClipboardType::Clipboard, ClipboardType::Primary] {
+            let _ = writeln!(
+                out,
+                "clipcat_history_clips{{clipboard_type=\"{}\"}} {}",
+                type_label(clipboard_type),
+                counts[type_index(clipboard_type)]
+            );
+        }
+
+        header(&mut out, "clipcat_history_bytes", "gauge", "Bytes of the clips in the history");
+        for &clipboard_type in &[ClipboardType::Clipboard, ClipboardType::Primary] {
+            let _ = writeln!(
+                out,
+                "clipcat_history_bytes{{clipboard_type=\"{}\"}} {}",
+                type_label(clipboard_type),
+                sizes[type_index(clipboard_type)]
+            );
+        }
+
+        header(
+            &mut out,
+            "clipcat_resident_bytes",
+            "gauge",
+            "Bytes of the clips in the history which are held in memory",
+        );
+        let _ = writeln!(out, "clipcat_resident_bytes {}", resident);
+
+        header(
+            &mut out,
+            "clipcat_memory_pressure_clips_total",
+            "counter",
+            "Clips which were spilled or evicted because the memory budget was exceeded",
+        );
+        for &(action, count) in &[("spilled", pressure.spilled), ("evicted", pressure.evicted)] {
+            let _ = writeln!(
+                out,
+                "clipcat_memory_pressure_clips_total{{action=\"{}\"}} {}",
+                action, count
+            );
+        }
+
+        header(
+            &mut out,
+            "clipcat_dropped_events_total",
+            "counter",
+            "Clipboard events which subscribers lagged behind on",
+        );
+        let _ = writeln!(
+            out,
+            "clipcat_dropped_events_total {}",
+            self.dropped_events.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut out,
+            "clipcat_grpc_request_duration_seconds",
+            "histogram",
+            "Latencies of gRPC requests until the response is sent",
+        );
+        let requests = self.grpc_requests.lock().unwrap_or_else(PoisonError::into_inner);
+        for (method, histogram) in requests.iter() {
+            let name = "clipcat_grpc_request_duration_seconds";
+            for (bucket, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{method=\"{}\",le=\"{}\"}} {}",
+                    name, method, bound, bucket
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{}_bucket{{method=\"{}\",le=\"+Inf\"}} {}",
+                name, method, histogram.count
+            );
+            let _ = writeln!(out, "{}_sum{{method=\"{}\"}} {}", name, method, histogram.sum);
+            let _ = writeln!(out, "{}_count{{method=\"{}\"}} {}", name, method, histogram.count);
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+#[inline]
+fn type_index(clipboard_type: ClipboardType) -> usize {
+    match clipboard_type {
+        ClipboardType::Clipboard => 0,
+        ClipboardType::Primary => 1,
+    }
+}
+
+#[inline]
+fn type_label(clipboard_type: ClipboardType) -> &'static str {
+    match clipboard_type {
+        ClipboardType::Clipboard => "clipboard",
+        ClipboardType::Primary => "primary",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use clipcat::{ClipboardData, ClipboardType, MemoryPressure};
+
+    use crate::metrics::Metrics;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        metrics.clip_captured(ClipboardType::Clipboard);
+        metrics.clip_captured(ClipboardType::Clipboard);
+        metrics.clip_captured(ClipboardType::Primary);
+        metrics.events_dropped(3);
+        metrics.grpc_request("/manager.Manager/List", Duration::from_millis(20));
+        metrics.grpc_request("/manager.Manager/List", Duration::from_secs(20));
+
+        let clips = [
+            ClipboardData::new("abc", ClipboardType::Clipboard),
+            ClipboardData::new("de", ClipboardType::Primary),
+        ];
+        let pressure = MemoryPressure { spilled: 2, evicted: 1 };
+        let text = metrics.render(clips.iter(), pressure);
+        assert!(text.contains("clipcat_clips_captured_total{clipboard_type=\"clipboard\"} 2\n"));
+        assert!(text.contains("clipcat_clips_captured_total{clipboard_type=\"primary\"} 1\n"));
+        assert!(text.contains("clipcat_history_clips{clipboard_type=\"primary\"} 1\n"));
+        assert!(text.contains("clipcat_history_bytes{clipboard_type=\"clipboard\"} 3\n"));
+        assert!(text.contains("clipcat_resident_bytes 5\n"));
+        assert!(text.contains("clipcat_memory_pressure_clips_total{action=\"spilled\"} 2\n"));
+        assert!(text.contains("clipcat_dropped_events_total 3\n"));
+        assert!(text.contains(
+            "clipcat_grpc_request_duration_seconds_bucket{method=\"/manager.Manager/List\",le=\"0.\
+             01\"} 0\n"
+        ));
+        assert!(text.contains(
+            "clipcat_grpc_request_duration_seconds_bucket{method=\"/manager.Manager/List\",le=\"0.\
+             025\"} 1\n"
+        ));
+        assert!(text.contains(
+            "clipcat_grpc_request_duration_seconds_bucket{method=\"/manager.Manager/List\",le=\"\
+             +Inf\"} 2\n"
+        ));
+        assert!(text.contains(
+            "clipcat_grpc_request_duration_seconds_count{method=\"/manager.Manager/List\"} 2\n"
+        ));
+    }
+
+    #[test]
+    fn test_unknown_grpc_methods() {
+        let metrics = Metrics::new();
+        metrics.grpc_request("/manager.Manager/Made up", Duration::from_millis(20));
+        metrics.grpc_request("/made.Up/Method", Duration::from_millis(20));
+
+        let text = metrics.render(std::iter::empty(), MemoryPressure::default());
+        assert!(
+            text.contains("clipcat_grpc_request_duration_seconds_count{method=\"unknown\"} 2\n")
+        );
+        assert!(!text.contains("Made up"));
+        assert!(!text.contains("/made.Up/Method"));
+    }
+}
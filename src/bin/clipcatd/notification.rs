@@ -0,0 +1,89 @@
+use clipcat::{ClipboardData, ClipboardType};
+
+use crate::config;
+
+/// Decides which recorded clips are shown in a desktop notification and what
+/// the notification says.
+pub struct Notifier {
+    enable_clipboard: bool,
+    enable_primary: bool,
+    min_length: usize,
+    preview_length: usize,
+    timeout: u32,
+}
+
+/// Content of a desktop notification.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Notification {
+    pub summary: String,
+    pub body: String,
+}
+
+impl Notifier {
+    pub fn new(config: &config::Notification) -> Notifier {
+        Notifier {
+            enable_clipboard: config.enable_clipboard,
+            enable_primary: config.enable_primary,
+            min_length: config.min_length,
+            preview_length: config.preview_length,
+            timeout: config.timeout,
+        }
+    }
+
+    /// Milliseconds until a notification is closed.
+    #[inline]
+    pub fn timeout(&self) -> u32 { self.timeout }
+
+    /// Returns the notification of a recorded clip, or `None` if the clip is
+    /// not notified.
+    pub fn notification(&self, clip: &ClipboardData) -> Option<Notification> {
+        let (enabled, summary) = match clip.clipboard_type {
+            ClipboardType::Clipboard => (self.enable_clipboard, "Clipboard recorded"),
+            ClipboardType::Primary => (self.enable_primary, "Primary recorded"),
+        };
+        if !enabled {
+            return None;
+        }
+
+        let length = if clip.is_text() { clip.data.chars().count() } else { clip.size() };
+        if length < self.min_length {
+            return None;
+        }
+
+        // the content of sensitive clips is never shown
+        let body = if clip.sensitive {
+            "Sensitive clip".to_owned()
+        } else {
+            clip.printable_data(Some(self.preview_length))
+        };
+        Some(Notification { summary: summary.to_owned(), body })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clipcat::ClipboardData;
+
+    use crate::{config, notification::Notifier};
+
+    #[test]
+    fn test_notification() {
+        let config = config::Notification { min_length: 3, ..Default::default() };
+        let notifier = Notifier::new(&config);
+
+        let notification = notifier.notification(&ClipboardData::new_clipboard("clipcat")).unwrap();
+        assert_eq!(notification.summary, "Clipboard recorded");
+        assert_eq!(notification.body, "clipcat");
+        assert!(notifier.notification(&ClipboardData::new_clipboard("ok")).is_none());
+        assert!(notifier.notification(&ClipboardData::new_primary("clipcat")).is_none());
+
+        let mut sensitive = ClipboardData::new_clipboard("hunter2");
+        sensitive.sensitive = true;
+        assert_eq!(notifier.notification(&sensitive).unwrap().body, "Sensitive clip");
+
+        let config = config::Notification { enable_primary: true, ..Default::default() };
+        let notifier = Notifier::new(&config);
+        let notification = notifier.notification(&ClipboardData::new_primary("clipcat")).unwrap();
+        assert_eq!(notification.summary, "Primary recorded");
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    collections::BTreeSet,
+    convert::TryFrom,
+    path::{Path, PathBuf},
+};
+
+use wasmtime::{Engine, Instance, Module, Store, StoreLimitsBuilder};
+
+use clipcat::{mime, ClipboardEvent, ClipboardType};
+
+use crate::capture::Annotation;
+
+/// Version of the host interface, plugins which export `clipcat_abi_version`
+/// have to return it.
+pub const ABI_VERSION: i32 = 1;
+
+// Instructions a plugin may execute for each clip.
+const FUEL_PER_CLIP: u64 = 50_000_000;
+
+// Pages of 64 KiB a plugin may allocate, 64 MiB.
+const MAX_MEMORY_PAGES: u32 = 1024;
+
+// Bytes of the JSON answer of a plugin which are read at most.
+const MAX_OUTPUT_LEN: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Snafu)]
+pub enum PluginError {
+    #[snafu(display("Could not create WebAssembly engine: {}", message))]
+    CreateEngine { message: String },
+
+    #[snafu(display("Could not load plugin {}: {}", path.display(), message))]
+    LoadPlugin { path: PathBuf, message: String },
+
+    #[snafu(display("Plugin {} implements ABI version {}, expected {}", path.display(), version, ABI_VERSION))]
+    UnsupportedAbi { path: PathBuf, version: i32 },
+
+    #[snafu(display("Plugin {} failed: {}", path.display(), message))]
+    RunPlugin { path: PathBuf, message: String },
+}
+
+/// A clip as it is passed to plugins.
+#[derive(Debug, Serialize)]
+struct Input<'a> {
+    text: &'a str,
+    mime: &'a str,
+    clipboard_type: ClipboardType,
+    application: Option<&'a str>,
+    sensitive: bool,
+    tags: &'a BTreeSet<String>,
+}
+
+/// The answer of a plugin, fields which are not set are left as they are.
+#[derive(Debug, Deserialize)]
+struct Output {
+    #[serde(default)]
+    action: Action,
+
+    #[serde(default)]
+    text: Option<String>,
+
+    #[serde(default)]
+    sensitive: Option<bool>,
+
+    #[serde(default)]
+    tags: Option<BTreeSet<String>>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Action {
+    Keep,
+    Drop,
+}
+
+impl Default for Action {
+    fn default() -> Action { Action::Keep }
+}
+
+/// A WebAssembly module which processes captured clips.
+///
+/// Plugins import nothing from the host and export `memory`,
+/// `clipcat_alloc(len: i32) -> i32` and `clipcat_process(ptr: i32, len: i32)
+/// -> i64`. The host writes the clip as JSON into a buffer which it allocates
+/// with `clipcat_alloc` and calls `clipcat_process` with it, which returns the
+/// address of its JSON answer in the upper and the length in the lower 32 bits,
+/// or 0 to keep the clip as it is.
+pub struct Plugin {
+    path: PathBuf,
+    module: Module,
+}
+
+/// Runs plugins in order on each captured clip before it is stored, each call
+/// gets a fresh instance so plugins keep no state between clips.
+pub struct PluginRunner {
+    engine: Engine,
+    plugins: Vec<Plugin>,
+}
+
+impl PluginRunner {
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<PluginRunner, PluginError> {
+        let engine = engine()?;
+        let plugins = paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref();
+                let bytes = std::fs::read(path).map_err(|err| PluginError::LoadPlugin {
+                    path: path.to_path_buf(),
+                    message: err.to_string(),
+                })?;
+                compile(&engine, path, &bytes)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PluginRunner { engine, plugins })
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.plugins.is_empty() }
+
+    /// Runs all plugins on `event`, returns `None` if one of them drops the
+    /// clip. Plugins which fail leave the clip as it is.
+    pub fn process(
+        &self,
+        event: &mut ClipboardEvent,
+        mut annotation: Annotation,
+    ) -> Option<Annotation> {
+        for plugin in &self.plugins {
+            match self.run(plugin, event, &mut annotation) {
+                Ok(Action::Keep) => {}
+                Ok(Action::Drop) => return None,
+                Err(err) => tracing::warn!("{}", err),
+            }
+        }
+        Some(annotation)
+    }
+
+    fn run(
+        &self,
+        plugin: &Plugin,
+        event: &mut ClipboardEvent,
+        annotation: &mut Annotation,
+    ) -> Result<Action, PluginError> {
+        let failed = |err: &dyn std::fmt::Display| PluginError::RunPlugin {
+            path: plugin.path.clone(),
+            message: err.to_string(),
+        };
+
+        let is_text = mime::is_text(&event.mime);
+        let input = serde_json::to_vec(&Input {
+            text: if is_text { &event.data } else { "" },
+            mime: &event.mime,
+            clipboard_type: event.clipboard_type,
+            application: event.application.as_deref(),
+            sensitive: event.sensitive || annotation.sensitive,
+            tags: &annotation.tags,
+        })
+        .map_err(|err| failed(&err))?;
+        let input_len = i32::try_from(input.len()).map_err(|err| failed(&err))?;
+
+        let store = new_store(&self.engine);
+        store.add_fuel(FUEL_PER_CLIP).map_err(|err| failed(&err))?;
+        let instance = Instance::new(&store, &plugin.module, &[]).map_err(|err| failed(&err))?;
+        let memory = instance
+            .get_memory("memory")
+            .ok_or_else(|| failed(&"plugin does not export memory"))?;
+        let alloc =
+            instance.get_typed_func::<i32, i32>("clipcat_alloc").map_err(|err| failed(&err))?;
+        let process = instance
+            .get_typed_func::<(i32, i32), i64>("clipcat_process")
+            .map_err(|err| failed(&err))?;
+
+        let ptr = alloc.call(input_len).map_err(|err| failed(&err))?;
+        memory.write(ptr as usize, &input).map_err(|err| failed(&err))?;
+        let answer = process.call((ptr, input_len)).map_err(|err| failed(&err))?;
+        if answer == 0 {
+            return Ok(Action::Keep);
+        }
+
+        let (answer_ptr, answer_len) = ((answer >> 32) as u32 as usize, answer as u32 as usize);
+        // the answer is checked before its buffer is allocated
+        if answer_len > MAX_OUTPUT_LEN {
+            return Err(failed(&format!("answer is larger than {} bytes", MAX_OUTPUT_LEN)));
+        }
+        if answer_ptr.checked_add(answer_len).map_or(true, |end| end > memory.data_size()) {
+            return Err(failed(&"answer is out of bounds of the memory of the plugin"));
+        }
+        let mut buf = vec![0; answer_len];
+        memory.read(answer_ptr, &mut buf).map_err(|err| failed(&err))?;
+        let output: Output = serde_json::from_slice(&buf).map_err(|err| failed(&err))?;
+
+        if let Some(text) = output.text {
+            // binary clips have no text to replace
+            if is_text && text != event.data {
+                event.data = text;
+                // formatted targets carry the original content
+                event.targets.clear();
+            }
+        }
+        if let Some(sensitive) = output.sensitive {
+            annotation.sensitive = sensitive;
+        }
+        if let Some(tags) = output.tags {
+            annotation.tags = tags;
+        }
+        Ok(output.action)
+    }
+}
+
+fn engine() -> Result<Engine, PluginError> {
+    let mut config = wasmtime::Config::new();
+    config.consume_fuel(true);
+    Engine::new(&config).map_err(|err| PluginError::CreateEngine { message: err.to_string() })
+}
+
+// Plugins which declare or grow more memory than `MAX_MEMORY_PAGES` fail
+// instead of exhausting the memory of clipcatd.
+fn new_store(engine: &Engine) -> Store {
+    Store::new_with_limits(engine, StoreLimitsBuilder::new().memory_pages(MAX_MEMORY_PAGES).build())
+}
+
+fn compile(engine: &Engine, path: &Path, bytes: &[u8]) -> Result<Plugin, PluginError> {
+    let load_failed =
+        |message: String| PluginError::LoadPlugin { path: path.to_path_buf(), message };
+
+    let module = Module::new(engine, bytes).map_err(|err| load_failed(err.to_string()))?;
+    if module.imports().next().is_some() {
+        return Err(load_failed("plugins must not import anything from the host".to_owned()));
+    }
+
+    // the version is checked once, instances of the module are thrown away
+    let store = new_store(engine);
+    store.add_fuel(FUEL_PER_CLIP).map_err(|err| load_failed(err.to_string()))?;
+    let instance =
+        Instance::new(&store, &module, &[]).map_err(|err| load_failed(err.to_string()))?;
+    if let Ok(abi_version) = instance.get_typed_func::<(), i32>("clipcat_abi_version") {
+        let version = abi_version.call(()).map_err(|err| load_failed(err.to_string()))?;
+        if version != ABI_VERSION {
+            return Err(PluginError::UnsupportedAbi { path: path.to_path_buf(), version });
+        }
+    }
+    Ok(Plugin { path: path.to_path_buf(), module })
+}
+
+#[cfg(test)]
+mod tests {
+    use clipcat::ClipboardEvent;
+
+    use crate::{
+        capture::Annotation,
+        plugin::{compile, engine, PluginRunner},
+    };
+
+    // Answers with `answer` which is stored at address 0.
+    fn plugin(answer: &str, body: &str) -> String {
+        format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{}")
+                (func (export "clipcat_alloc") (param i32) (result i32) (i32.const 1024))
+                (func (export "clipcat_process") (param i32 i32) (result i64) {}))"#,
+            answer.replace('"', "\\\""),
+            body
+        )
+    }
+
+    fn runner(sources: &[String]) -> PluginRunner {
+        let engine = engine().unwrap();
+        let plugins = sources
+            .iter()
+            .map(|source| compile(&engine, "test.wat".as_ref(), source.as_bytes()).unwrap())
+            .collect();
+        PluginRunner { engine, plugins }
+    }
+
+    #[test]
+    fn test_process() {
+        let keep = plugin("", "(i64.const 0)");
+        let transform = plugin(r#"{"text":"meow","tags":["cat"]}"#, "(i64.const 30)");
+        let runner = runner(&[keep, transform]);
+
+        let mut clip = ClipboardEvent::new_clipboard("clipcat");
+        let annotation = runner.process(&mut clip, Annotation::default()).unwrap();
+        assert_eq!(clip.data, "meow");
+        assert!(annotation.tags.contains("cat"));
+
+        let drop = plugin(r#"{"action":"drop"}"#, "(i64.const 17)");
+        let runner = self::runner(&[drop]);
+        let mut clip = ClipboardEvent::new_clipboard("clipcat");
+        assert!(runner.process(&mut clip, Annotation::default()).is_none());
+    }
+
+    #[test]
+    fn test_failing_plugin() {
+        let runaway = plugin("", "(loop (br 0)) (i64.const 0)");
+        let runner = runner(&[runaway]);
+        let mut clip = ClipboardEvent::new_clipboard("clipcat");
+        assert_eq!(runner.process(&mut clip, Annotation::default()).unwrap(), Default::default());
+        assert_eq!(clip.data, "clipcat");
+
+        let engine = engine().unwrap();
+        let importing = r#"(module (import "env" "f" (func)))"#;
+        assert!(compile(&engine, "test.wat".as_ref(), importing.as_bytes()).is_err());
+
+        let newer = r#"(module (func (export "clipcat_abi_version") (result i32) (i32.const 2)))"#;
+        assert!(compile(&engine, "test.wat".as_ref(), newer.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_plugin_limits() {
+        // an answer of 4 GiB at address 0 is refused before it is read
+        let oversized = plugin("", "(i64.const 4294967295)");
+        // an answer which starts beyond the single page of memory
+        let out_of_bounds = plugin("", "(i64.const 0x0001000000000010)");
+        let runner = runner(&[oversized, out_of_bounds]);
+        let mut clip = ClipboardEvent::new_clipboard("clipcat");
+        assert_eq!(runner.process(&mut clip, Annotation::default()).unwrap(), Default::default());
+        assert_eq!(clip.data, "clipcat");
+
+        let engine = engine().unwrap();
+        let greedy = r#"(module (memory (export "memory") 2048))"#;
+        assert!(compile(&engine, "test.wat".as_ref(), greedy.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use std::path::{Path, PathBuf};
+
+use rhai::{Dynamic, Engine, Scope, AST};
+use snafu::ResultExt;
+
+use clipcat::{mime, ClipboardEvent, ClipboardType};
+
+use crate::capture::Annotation;
+
+// Function which scripts define to process a captured clip.
+const ENTRY_POINT: &str = "process";
+
+// Limits which keep a runaway script from stalling the capture of clips.
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 16 * 1024 * 1024;
+const MAX_ARRAY_SIZE: usize = 10_000;
+
+#[derive(Debug, Snafu)]
+pub enum ScriptError {
+    #[snafu(display("Could not read script {}: {}", path.display(), source))]
+    ReadScript { path: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Could not compile script {}: {}", path.display(), source))]
+    CompileScript { path: PathBuf, source: rhai::ParseError },
+
+    #[snafu(display("Script {} failed: {}", path.display(), source))]
+    RunScript { path: PathBuf, source: Box<rhai::EvalAltResult> },
+
+    #[snafu(display("Script {} returned {}, expected bool, () or map", path.display(), type_name))]
+    InvalidResult { path: PathBuf, type_name: String },
+}
+
+/// A rhai script which defines `fn process(clip)`. The clip is passed as a map
+/// of `text`, `mime`, `clipboard_type`, `application`, `sensitive` and `tags`,
+/// and the script returns `false` to drop it, `true` or `()` to keep it, or a
+/// map of the fields it changes.
+pub struct Script {
+    path: PathBuf,
+    ast: AST,
+}
+
+/// Runs scripts in order on each captured clip before it is stored.
+pub struct ScriptRunner {
+    engine: Engine,
+    scripts: Vec<Script>,
+}
+
+impl ScriptRunner {
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<ScriptRunner, ScriptError> {
+        let engine = engine();
+        let scripts = paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref();
+                let source = std::fs::read_to_string(path)
+                    .context(ReadScript { path: path.to_path_buf() })?;
+                compile(&engine, path, &source)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ScriptRunner { engine, scripts })
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.scripts.is_empty() }
+
+    /// Runs all scripts on `event`, returns `None` if one of them drops the
+    /// clip. Scripts which fail leave the clip as it is.
+    pub fn process(&self, event: &mut ClipboardEvent) -> Option<Annotation> {
+        let mut annotation = Annotation::default();
+        for script in &self.scripts {
+            match self.run(script, event, &mut annotation) {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => tracing::warn!("{}", err),
+            }
+        }
+        Some(annotation)
+    }
+
+    fn run(
+        &self,
+        script: &Script,
+        event: &mut ClipboardEvent,
+        annotation: &mut Annotation,
+    ) -> Result<bool, ScriptError> {
+        let is_text = mime::is_text(&event.mime);
+        let mut clip = rhai::Map::new();
+        let text = if is_text { event.data.clone() } else { String::new() };
+        clip.insert("text".into(), Dynamic::from(text));
+        clip.insert("mime".into(), Dynamic::from(event.mime.clone()));
+        let clipboard_type = match event.clipboard_type {
+            ClipboardType::Clipboard => "clipboard",
+            ClipboardType::Primary => "primary",
+        };
+        clip.insert("clipboard_type".into(), Dynamic::from(clipboard_type.to_owned()));
+        clip.insert(
+            "application".into(),
+            event.application.clone().map_or(Dynamic::UNIT, Dynamic::from),
+        );
+        clip.insert("sensitive".into(), Dynamic::from(event.sensitive || annotation.sensitive));
+        let tags: rhai::Array = annotation.tags.iter().cloned().map(Dynamic::from).collect();
+        clip.insert("tags".into(), Dynamic::from(tags));
+
+        let result: Dynamic = self
+            .engine
+            .call_fn(&mut Scope::new(), &script.ast, ENTRY_POINT, (clip,))
+            .context(RunScript { path: script.path.clone() })?;
+
+        if result.is::<()>() {
+            return Ok(true);
+        }
+        if let Some(keep) = result.clone().try_cast::<bool>() {
+            return Ok(keep);
+        }
+        let changes = match result.clone().try_cast::<rhai::Map>() {
+            Some(changes) => changes,
+            None => {
+                return Err(ScriptError::InvalidResult {
+                    path: script.path.clone(),
+                    type_name: result.type_name().to_owned(),
+                })
+            }
+        };
+
+        if let Some(text) = changes.get("text").and_then(|text| text.clone().try_cast::<String>()) {
+            // binary clips have no text to replace
+            if is_text && text != event.data {
+                event.data = text;
+                // formatted targets carry the original content
+                event.targets.clear();
+            }
+        }
+        if let Some(sensitive) = changes.get("sensitive").and_then(|v| v.clone().try_cast::<bool>())
+        {
+            annotation.sensitive = sensitive;
+        }
+        if let Some(tags) = changes.get("tags").and_then(|v| v.clone().try_cast::<rhai::Array>()) {
+            annotation.tags = tags.into_iter().filter_map(|tag| tag.try_cast::<String>()).collect();
+        }
+        Ok(true)
+    }
+}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_ARRAY_SIZE);
+    engine.on_print(|text| tracing::info!("Script: {}", text));
+    engine.on_debug(|text| tracing::debug!("Script: {}", text));
+    engine
+}
+
+fn compile(engine: &Engine, path: &Path, source: &str) -> Result<Script, ScriptError> {
+    let ast = engine.compile(source).context(CompileScript { path: path.to_path_buf() })?;
+    Ok(Script { path: path.to_path_buf(), ast })
+}
+
+#[cfg(test)]
+mod tests {
+    use clipcat::ClipboardEvent;
+
+    use crate::script::{compile, engine, ScriptRunner};
+
+    fn runner(sources: &[&str]) -> ScriptRunner {
+        let engine = engine();
+        let scripts = sources
+            .iter()
+            .map(|source| compile(&engine, "test.rhai".as_ref(), source).unwrap())
+            .collect();
+        ScriptRunner { engine, scripts }
+    }
+
+    fn event(data: &str) -> ClipboardEvent {
+        ClipboardEvent {
+            application: Some("Firefox".to_owned()),
+            ..ClipboardEvent::new_clipboard(data)
+        }
+    }
+
+    #[test]
+    fn test_process() {
+        let runner = runner(&[
+            r#"fn process(clip) { clip.application != "KeePassXC" }"#,
+            r#"
+            fn process(clip) {
+                if clip.text.starts_with("https://") {
+                    clip.text.trim();
+                    clip.tags.push("url");
+                    return clip;
+                }
+            }
+            "#,
+        ]);
+
+        let mut url = event(" https://example.com ");
+        let annotation = runner.process(&mut url).unwrap();
+        assert_eq!(url.data, " https://example.com ");
+        assert!(annotation.tags.is_empty());
+
+        let mut url = event("https://example.com ");
+        let annotation = runner.process(&mut url).unwrap();
+        assert_eq!(url.data, "https://example.com");
+        assert!(annotation.tags.contains("url"));
+
+        let mut password = event("hunter2");
+        password.application = Some("KeePassXC".to_owned());
+        assert!(runner.process(&mut password).is_none());
+    }
+
+    #[test]
+    fn test_failing_script() {
+        let runner = runner(&["fn process(clip) { loop {} }", "fn process(clip) { 42 }"]);
+        let mut clip = event("clipcat");
+        assert_eq!(runner.process(&mut clip).unwrap(), Default::default());
+        assert_eq!(clip.data, "clipcat");
+    }
+}
@@ -0,0 +1,246 @@
+use clipcat::{urls, ClipboardType};
+
+use crate::config::{self, Transform};
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+
+/// Query parameters which are removed by `clean_urls`, a trailing `*` matches
+/// any suffix.
+pub const TRACKING_PARAMETERS: &[&str] = &[
+    "utm_*",
+    "fbclid",
+    "gclid",
+    "dclid",
+    "gbraid",
+    "wbraid",
+    "msclkid",
+    "yclid",
+    "twclid",
+    "ttclid",
+    "igshid",
+    "mc_cid",
+    "mc_eid",
+    "_hsenc",
+    "_hsmi",
+    "mkt_tok",
+    "vero_id",
+    "oly_anon_id",
+    "oly_enc_id",
+];
+
+/// Applies the configured transforms of each clipboard type to text.
+pub struct Transformer {
+    clipboard: Vec<Transform>,
+    primary: Vec<Transform>,
+    tracking_parameters: Vec<String>,
+}
+
+impl Transformer {
+    pub fn new(config: &config::Transforms) -> Transformer {
+        let tracking_parameters = TRACKING_PARAMETERS
+            .iter()
+            .map(|parameter| parameter.to_string())
+            .chain(config.tracking_parameters.iter().map(|parameter| parameter.to_lowercase()))
+            .collect();
+        Transformer {
+            clipboard: config.clipboard.clone(),
+            primary: config.primary.clone(),
+            tracking_parameters,
+        }
+    }
+
+    /// Returns `true` if clips of `clipboard_type` are transformed.
+    pub fn is_enabled(&self, clipboard_type: ClipboardType) -> bool {
+        !self.transforms(clipboard_type).is_empty()
+    }
+
+    /// Applies the transforms of `clipboard_type` to `text` in order.
+    pub fn apply(&self, clipboard_type: ClipboardType, text: &str) -> String {
+        self.transforms(clipboard_type).iter().fold(text.to_owned(), |text, transform| {
+            match transform {
+                Transform::Trim => text.trim().to_owned(),
+                Transform::CollapseWhitespace => collapse_whitespace(&text),
+                Transform::StripAnsi => strip_ansi(&text),
+                Transform::NormalizeLineEndings => text.replace("\r\n", "\n").replace('\r', "\n"),
+                Transform::CleanUrls => self.clean_urls(&text),
+            }
+        })
+    }
+
+    fn transforms(&self, clipboard_type: ClipboardType) -> &[Transform] {
+        match clipboard_type {
+            ClipboardType::Clipboard => &self.clipboard,
+            ClipboardType::Primary => &self.primary,
+        }
+    }
+
+    fn clean_urls(&self, text: &str) -> String {
+        let mut cleaned = String::with_capacity(text.len());
+        let mut end = 0;
+        for span in urls::spans(text) {
+            cleaned.push_str(&text[end..span.start]);
+            end = span.end;
+            cleaned.push_str(&self.clean_url(&text[span]));
+        }
+        cleaned.push_str(&text[end..]);
+        cleaned
+    }
+
+    fn clean_url(&self, url: &str) -> String {
+        let (url, fragment) = match url.find('#') {
+            Some(index) => url.split_at(index),
+            None => (url, ""),
+        };
+        let (base, query) = match url.find('?') {
+            Some(index) => (&url[..index], &url[index + 1..]),
+            None => return format!("{}{}", url, fragment),
+        };
+
+        let parameters: Vec<_> =
+            query.split('&').filter(|parameter| !self.is_tracking(parameter)).collect();
+        if parameters.iter().all(|parameter| parameter.is_empty()) {
+            format!("{}{}", base, fragment)
+        } else {
+            format!("{}?{}{}", base, parameters.join("&"), fragment)
+        }
+    }
+
+    fn is_tracking(&self, parameter: &str) -> bool {
+        let name = parameter.split('=').next().unwrap_or_default().to_lowercase();
+        self.tracking_parameters.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == *pattern,
+        })
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_run = false;
+    for c in text.chars() {
+        if c == ' ' || c == '\t' {
+            if !in_run {
+                collapsed.push(' ');
+            }
+            in_run = true;
+        } else {
+            collapsed.push(c);
+            in_run = false;
+        }
+    }
+    collapsed
+}
+
+// Removes CSI sequences like colors and cursor movements, OSC sequences like
+// window titles and hyperlinks, and all other escape sequences.
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != ESC {
+            stripped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            // parameters and intermediates are followed by a final byte
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // strings are terminated by BEL or ESC \
+            Some(']') | Some('P') | Some('X') | Some('^') | Some('_') => {
+                while let Some(c) = chars.next() {
+                    if c == BEL {
+                        break;
+                    }
+                    if c == ESC && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // intermediates are followed by a final byte, e.g. ESC ( B
+            Some(c) if (' '..='/').contains(&c) => {
+                for c in chars.by_ref() {
+                    if !(' '..='/').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use clipcat::ClipboardType;
+
+    use crate::{
+        config::{Transform, Transforms},
+        transform::Transformer,
+    };
+
+    fn apply(transforms: &[Transform], text: &str) -> String {
+        let config = Transforms { clipboard: transforms.to_vec(), ..Default::default() };
+        Transformer::new(&config).apply(ClipboardType::Clipboard, text)
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(apply(&[], " clip  cat "), " clip  cat ");
+        assert_eq!(apply(&[Transform::Trim], " \n clipcat\t\n"), "clipcat");
+        assert_eq!(
+            apply(&[Transform::CollapseWhitespace], "clip \t  cat\n  meow"),
+            "clip cat\n meow"
+        );
+        assert_eq!(apply(&[Transform::NormalizeLineEndings], "a\r\nb\rc\n"), "a\nb\nc\n");
+        assert_eq!(
+            apply(&[Transform::Trim, Transform::CollapseWhitespace], "  clip   cat  "),
+            "clip cat"
+        );
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        let strip = |text| apply(&[Transform::StripAnsi], text);
+        assert_eq!(strip("\x1b[1;31merror\x1b[0m: clipcat"), "error: clipcat");
+        assert_eq!(strip("\x1b]0;title\x07$ ls"), "$ ls");
+        assert_eq!(strip("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\"), "link");
+        assert_eq!(strip("\x1b(Bclipcat\x1b=\x1b"), "clipcat");
+        assert_eq!(strip("plain"), "plain");
+    }
+
+    #[test]
+    fn test_clean_urls() {
+        let clean = |text| apply(&[Transform::CleanUrls], text);
+        assert_eq!(
+            clean("https://example.com/a?utm_source=x&id=1&UTM_Medium=y#top"),
+            "https://example.com/a?id=1#top"
+        );
+        assert_eq!(
+            clean("see (https://example.com/?fbclid=abc). or http://example.com?gclid=1"),
+            "see (https://example.com/). or http://example.com"
+        );
+        assert_eq!(clean("https://example.com/?q=clipcat"), "https://example.com/?q=clipcat");
+        assert_eq!(clean("no urls, utm_source=x"), "no urls, utm_source=x");
+
+        let config = Transforms {
+            clipboard: vec![Transform::CleanUrls],
+            tracking_parameters: vec!["ref_*".to_owned(), "si".to_owned()],
+            ..Default::default()
+        };
+        let transformer = Transformer::new(&config);
+        assert_eq!(
+            transformer.apply(ClipboardType::Clipboard, "https://example.com/?si=1&ref_src=2&a=b"),
+            "https://example.com/?a=b"
+        );
+        assert!(!transformer.is_enabled(ClipboardType::Primary));
+    }
+}
@@ -0,0 +1,213 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use snafu::ResultExt;
+
+use clipcat::ClipboardData;
+
+use crate::config::{self, WebhookEvent};
+
+/// Header which carries the signature of a payload, `sha256=` followed by the
+/// hexadecimal HMAC-SHA256 of the body.
+pub const SIGNATURE_HEADER: &str = "x-clipcat-signature";
+
+const PREVIEW_LENGTH: usize = 80;
+
+#[derive(Debug, Snafu)]
+pub enum WebhookError {
+    #[snafu(display("Invalid webhook URL {}: {}", url, source))]
+    InvalidUrl { url: String, source: http::uri::InvalidUri },
+
+    #[snafu(display("Webhook URL {} is neither http nor https", url))]
+    UnsupportedScheme { url: String },
+
+    #[snafu(display("Template of webhook {} does not render JSON: {}", url, source))]
+    InvalidTemplate { url: String, source: serde_json::Error },
+}
+
+/// Renders the requests of a configured webhook.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    url: http::Uri,
+    events: Vec<WebhookEvent>,
+    template: Option<String>,
+    secret: Option<Vec<u8>>,
+    timeout: Duration,
+}
+
+/// A request which is POSTed to a webhook.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Request {
+    pub url: http::Uri,
+    pub body: String,
+    pub signature: Option<String>,
+}
+
+impl Webhook {
+    /// Fails if the URL is invalid or the template does not render JSON.
+    pub fn new(config: &config::Webhook) -> Result<Webhook, WebhookError> {
+        let url: http::Uri = config.url.parse().context(InvalidUrl { url: config.url.clone() })?;
+        match url.scheme_str() {
+            Some("http") | Some("https") => {}
+            _ => return Err(WebhookError::UnsupportedScheme { url: config.url.clone() }),
+        }
+
+        if let Some(ref template) = config.template {
+            let sample = ClipboardData::new_clipboard("clipcat");
+            let body = render(template, &values(WebhookEvent::Captured, &sample));
+            serde_json::from_str::<serde_json::Value>(&body)
+                .context(InvalidTemplate { url: config.url.clone() })?;
+        }
+
+        Ok(Webhook {
+            url,
+            events: config.events.clone(),
+            template: config.template.clone(),
+            secret: config.secret.as_ref().map(|secret| secret.as_bytes().to_vec()),
+            timeout: Duration::from_secs(config.timeout.max(1)),
+        })
+    }
+
+    #[inline]
+    pub fn timeout(&self) -> Duration { self.timeout }
+
+    /// Returns the request for `event` of `clip`, or `None` if the webhook is
+    /// not interested in `event`.
+    pub fn request(&self, event: WebhookEvent, clip: &ClipboardData) -> Option<Request> {
+        if !self.events.contains(&event) {
+            return None;
+        }
+
+        let body = match self.template {
+            Some(ref template) => render(template, &values(event, clip)),
+            None => payload(event, clip),
+        };
+        let signature = self.secret.as_ref().map(|secret| sign(secret, body.as_bytes()));
+        Some(Request { url: self.url.clone(), body, signature })
+    }
+}
+
+/// The JSON payload of `event` which is sent if no template is given, it
+/// carries the metadata and a preview of `clip` but not its full content.
+pub fn payload(event: WebhookEvent, clip: &ClipboardData) -> String {
+    let payload: serde_json::Map<_, _> =
+        values(event, clip).into_iter().filter(|(key, _)| key != "data").collect();
+    serde_json::Value::Object(payload).to_string()
+}
+
+/// Values which are available to templates as `{{name}}`, the content of
+/// sensitive clips is never sent.
+fn values(event: WebhookEvent, clip: &ClipboardData) -> serde_json::Map<String, serde_json::Value> {
+    let timestamp =
+        clip.timestamp.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_millis() as u64);
+    let (preview, data) = if clip.sensitive {
+        (serde_json::Value::Null, serde_json::Value::Null)
+    } else if clip.is_text() {
+        (clip.printable_data(Some(PREVIEW_LENGTH)).into(), clip.data.clone().into())
+    } else {
+        (clip.printable_data(Some(PREVIEW_LENGTH)).into(), serde_json::Value::Null)
+    };
+
+    serde_json::json!({
+        "event": event.as_str(),
+        "id": format!("{:016x}", clip.id),
+        "clipboard_type": clip.clipboard_type,
+        "mime": clip.mime,
+        "size": clip.size(),
+        "sensitive": clip.sensitive,
+        "timestamp": timestamp,
+        "preview": preview,
+        "data": data,
+    })
+    .as_object()
+    .cloned()
+    .expect("values are an object")
+}
+
+/// Replaces each `{{name}}` in `template` with the JSON encoding of the value,
+/// unknown names are replaced with `null`.
+fn render(template: &str, values: &serde_json::Map<String, serde_json::Value>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start + 2..].find("}}") {
+            Some(end) => start + 2 + end,
+            None => break,
+        };
+        rendered.push_str(&rest[..start]);
+        let name = rest[start + 2..end].trim();
+        rendered.push_str(&values.get(name).unwrap_or(&serde_json::Value::Null).to_string());
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_varkey(secret).expect("HMAC takes keys of any size");
+    mac.update(body);
+    let digest: String =
+        mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256={}", digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use clipcat::ClipboardData;
+
+    use crate::{
+        config::{self, WebhookEvent},
+        webhook::Webhook,
+    };
+
+    fn config(template: Option<&str>) -> config::Webhook {
+        config::Webhook {
+            url: "https://example.com/hook".to_owned(),
+            events: vec![WebhookEvent::Captured],
+            template: template.map(ToOwned::to_owned),
+            secret: Some("secret".to_owned()),
+            timeout: config::Webhook::default_timeout(),
+        }
+    }
+
+    #[test]
+    fn test_request() {
+        let clip = ClipboardData::new_clipboard("clipcat");
+        let webhook = Webhook::new(&config(None)).unwrap();
+        assert!(webhook.request(WebhookEvent::Removed, &clip).is_none());
+
+        let request = webhook.request(WebhookEvent::Captured, &clip).unwrap();
+        let payload: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(payload["event"], "captured");
+        assert_eq!(payload["id"], format!("{:016x}", clip.id));
+        assert_eq!(payload["preview"], "clipcat");
+        assert!(payload.get("data").is_none());
+
+        let signature = request.signature.unwrap();
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+
+        let mut sensitive = ClipboardData::new_clipboard("hunter2");
+        sensitive.sensitive = true;
+        let request = webhook.request(WebhookEvent::Captured, &sensitive).unwrap();
+        assert!(!request.body.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_template() {
+        let clip = ClipboardData::new_clipboard("say \"meow\"");
+        let webhook =
+            Webhook::new(&config(Some(r#"{"text": {{ data }}, "kind": {{event}}, "x": {{x}}}"#)))
+                .unwrap();
+        let request = webhook.request(WebhookEvent::Captured, &clip).unwrap();
+        assert_eq!(request.body, r#"{"text": "say \"meow\"", "kind": "captured", "x": null}"#);
+
+        assert!(Webhook::new(&config(Some(r#"{"text": "{{data}}"}"#))).is_err());
+        assert!(Webhook::new(&config::Webhook {
+            url: "ftp://example.com".to_owned(),
+            ..config(None)
+        })
+        .is_err());
+    }
+}
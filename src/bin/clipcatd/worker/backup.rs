@@ -0,0 +1,79 @@
+use std::{sync::Arc, time::SystemTime};
+
+use futures::FutureExt;
+use tokio::{
+    sync::{mpsc, RwLock},
+    task::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use clipcat::ClipboardManager;
+
+use crate::backup::BackupManager;
+
+pub enum Message {
+    Shutdown,
+}
+
+pub type MessageSender = mpsc::UnboundedSender<Message>;
+pub type MessageReceiver = mpsc::UnboundedReceiver<Message>;
+
+pub struct BackupWorker {
+    msg_rx: MessageReceiver,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+    backup_manager: BackupManager,
+    interval: Duration,
+}
+
+impl BackupWorker {
+    async fn run(mut self) {
+        // the first snapshot is written one interval after start up
+        let mut interval = tokio::time::interval_at(Instant::now() + self.interval, self.interval);
+
+        loop {
+            futures::select! {
+                _ = interval.tick().fuse() => self.backup().await,
+                msg = self.msg_rx.recv().fuse() => match msg {
+                    None | Some(Message::Shutdown) => {
+                        tracing::info!("BackupWorker is shutting down gracefully");
+                        break;
+                    }
+                },
+            }
+        }
+    }
+
+    async fn backup(&self) {
+        let clips = {
+            let cm = self.clipboard_manager.read().await;
+            cm.export(&[], false)
+        };
+
+        match self.backup_manager.snapshot(&clips, SystemTime::now()) {
+            Ok(path) => tracing::info!("Write snapshot of {} clip(s) to {:?}", clips.len(), path),
+            Err(err) => {
+                tracing::warn!("Failed to write snapshot, error: {}", err);
+                return;
+            }
+        }
+
+        match self.backup_manager.prune() {
+            Ok(removed) => {
+                for path in removed {
+                    tracing::info!("Remove snapshot {:?}", path);
+                }
+            }
+            Err(err) => tracing::warn!("Failed to remove old snapshots, error: {}", err),
+        }
+    }
+}
+
+pub fn start(
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+    backup_manager: BackupManager,
+    interval: Duration,
+) -> (MessageSender, JoinHandle<()>) {
+    let (tx, msg_rx) = mpsc::unbounded_channel::<Message>();
+    let worker = BackupWorker { msg_rx, clipboard_manager, backup_manager, interval };
+    (tx, tokio::spawn(worker.run()))
+}
@@ -1,32 +1,80 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use futures::FutureExt;
 use tokio::{
-    sync::{broadcast, mpsc, Mutex},
+    sync::{broadcast, mpsc, Mutex, RwLock},
     task::JoinHandle,
 };
 
 use clipcat::{ClipboardData, ClipboardEvent, ClipboardManager, ClipboardMonitor, ClipboardType};
 
+#[cfg(feature = "plugins")]
+use crate::plugin::PluginRunner;
+#[cfg(feature = "scripts")]
+use crate::script::ScriptRunner;
+#[cfg(feature = "mqtt")]
+use crate::worker::mqtt;
+#[cfg(feature = "dbus")]
+use crate::worker::notification;
 use crate::{
+    capture::CaptureFilter,
+    config::SelectionSync,
     error::Error,
     history::HistoryManager,
-    worker::{CtlMessage, CtlMessageSender},
+    metrics::Metrics,
+    secret::{SecretDetector, Verdict},
+    worker::{history_writer, webhook, CtlMessage, CtlMessageSender},
 };
 
+// How often sensitive clips are checked for expiry.
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(1);
+
+// How often clips are checked against `max_age`.
+const MAX_AGE_INTERVAL: Duration = Duration::from_secs(60);
+
 pub enum Message {
     Shutdown,
+
+    /// Replaces the settings of how clips are captured, after the
+    /// configuration is reloaded.
+    Reconfigure(Box<CaptureSettings>),
 }
 
 pub type MessageSender = mpsc::UnboundedSender<Message>;
 pub type MessageReceiver = mpsc::UnboundedReceiver<Message>;
 
+/// Settings of how clips are captured, which can be replaced while the worker
+/// runs.
+pub struct CaptureSettings {
+    pub capture_filter: CaptureFilter,
+    pub secret_detector: SecretDetector,
+    #[cfg(feature = "scripts")]
+    pub script_runner: ScriptRunner,
+    #[cfg(feature = "plugins")]
+    pub plugin_runner: PluginRunner,
+    pub sensitive_timeout: Duration,
+    pub sync: SelectionSync,
+}
+
+/// Workers which are sent every recorded clip.
+pub struct Subscribers {
+    #[cfg(feature = "dbus")]
+    pub notification_tx: Option<notification::MessageSender>,
+    pub webhook_tx: Option<webhook::MessageSender>,
+    #[cfg(feature = "mqtt")]
+    pub mqtt_tx: Option<mqtt::MessageSender>,
+}
+
 pub struct ClipboardWorker {
     ctl_tx: CtlMessageSender,
     msg_rx: MessageReceiver,
     clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
-    clipboard_manager: Arc<Mutex<ClipboardManager>>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
     history_manager: Arc<Mutex<HistoryManager>>,
+    history_tx: history_writer::MessageSender,
+    metrics: Arc<Metrics>,
+    settings: CaptureSettings,
+    subscribers: Subscribers,
 }
 
 impl ClipboardWorker {
@@ -36,17 +84,21 @@ impl ClipboardWorker {
             let monitor = self.clipboard_monitor.lock().await;
             monitor.subscribe()
         };
+        let mut expiry = tokio::time::interval(EXPIRY_INTERVAL);
+        let mut max_age = tokio::time::interval(MAX_AGE_INTERVAL);
 
         while !quit {
             quit = futures::select! {
                 event = event_recv.recv().fuse() => self.handle_event(event).await,
                 msg = self.msg_rx.recv().fuse() => self.handle_message(msg),
+                _ = expiry.tick().fuse() => self.expire_sensitive().await,
+                _ = max_age.tick().fuse() => self.remove_aged().await,
             };
         }
 
         let (clips, history_capacity) = {
-            let cm = self.clipboard_manager.lock().await;
-            (cm.list(), cm.capacity())
+            let cm = self.clipboard_manager.read().await;
+            (cm.list(), cm.total_capacity())
         };
 
         {
@@ -61,6 +113,7 @@ impl ClipboardWorker {
         Ok(())
     }
 
+    #[tracing::instrument(name = "capture", skip(self, event))]
     async fn handle_event(
         &self,
         event: Result<ClipboardEvent, broadcast::error::RecvError>,
@@ -74,20 +127,184 @@ impl ClipboardWorker {
 
                 return true;
             }
-            Err(broadcast::error::RecvError::Lagged(_)) => {}
-            Ok(event) => {
-                match event.clipboard_type {
-                    ClipboardType::Clipboard => tracing::info!("Clipboard [{:?}]", event.data),
-                    ClipboardType::Primary => tracing::info!("Primary [{:?}]", event.data),
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                tracing::warn!("ClipboardWorker lagged behind, {} clips are not stored", n);
+                self.metrics.events_dropped(n);
+            }
+            Ok(mut event) => {
+                if !self.settings.capture_filter.limit_size(&mut event) {
+                    tracing::info!("Clip exceeds max_clip_size, it is not stored");
+                    return false;
+                }
+
+                if self.settings.capture_filter.is_denied(&event) {
+                    tracing::info!("Clip is denied by capture filter, it is not stored");
+                    return false;
+                }
+
+                if !self.settings.capture_filter.transform(&mut event) {
+                    tracing::info!("Clip is empty after transforms, it is not stored");
+                    return false;
+                }
+
+                let mut masked = false;
+                if clipcat::mime::is_text(&event.mime) {
+                    match self.settings.secret_detector.inspect(&event.data) {
+                        Verdict::Allow => {}
+                        Verdict::Block { kind } => {
+                            tracing::info!("Clip contains a secret ({}), it is not stored", kind);
+                            return false;
+                        }
+                        Verdict::Mask { data } => {
+                            tracing::info!("Clip contains a secret, it is stored masked");
+                            // formatted targets carry the secret as well
+                            event.data = data;
+                            event.targets.clear();
+                            masked = true;
+                        }
+                    }
+                }
+
+                #[cfg(feature = "scripts")]
+                let annotation = match self.settings.script_runner.process(&mut event) {
+                    Some(annotation) => annotation,
+                    None => {
+                        tracing::info!("Clip is dropped by script, it is not stored");
+                        return false;
+                    }
+                };
+                #[cfg(not(feature = "scripts"))]
+                let annotation = crate::capture::Annotation::default();
+                #[cfg(feature = "plugins")]
+                let annotation = match self.settings.plugin_runner.process(&mut event, annotation) {
+                    Some(annotation) => annotation,
+                    None => {
+                        tracing::info!("Clip is dropped by plugin, it is not stored");
+                        return false;
+                    }
+                };
+
+                let sensitive =
+                    self.settings.capture_filter.is_sensitive(&event) || annotation.sensitive;
+                let detected_tags = self.settings.capture_filter.detect_tags(&event);
+                let display = event.display.clone();
+                let mut data = ClipboardData::from(event);
+                data.sensitive = sensitive;
+                data.tags = annotation.tags;
+                data.tags.extend(detected_tags.into_iter().map(str::to_owned));
+                if let Some(ref display) = display {
+                    data.tags.insert(format!("display:{}", display));
+                }
+                let clipboard_type = data.clipboard_type;
+                let label = match clipboard_type {
+                    ClipboardType::Clipboard => "Clipboard",
+                    ClipboardType::Primary => "Primary",
+                };
+                // the content of sensitive clips must not reach log files or collectors
+                let description = if sensitive {
+                    None
+                } else {
+                    Some(format!("[{:?}]", data.printable_data(None)))
+                };
+                let (kind, size) = (data.kind(), data.size());
+                let id = {
+                    let mut cm = self.clipboard_manager.write().await;
+                    tracing::info_span!("insert").in_scope(|| cm.insert(data))
+                };
+                match description {
+                    Some(description) => tracing::info!("{} {}", label, description),
+                    None => {
+                        tracing::info!("{} sensitive clip {} ({}, {} bytes)", label, id, kind, size)
+                    }
+                }
+                // the system clipboard keeps the original content of masked clips
+                if !masked {
+                    self.store_selections(id, clipboard_type, display.as_deref()).await;
+                }
+                let data = self.clipboard_manager.read().await.get(id);
+                self.metrics.clip_captured(clipboard_type);
+                if let Some(data) = data {
+                    if self
+                        .history_tx
+                        .try_send(history_writer::Message::Put(Box::new(data.clone())))
+                        .is_err()
+                    {
+                        tracing::warn!(
+                            "HistoryWriter lags behind, clip {} is saved at shutdown",
+                            id
+                        );
+                    }
+                    // sensitive clips are kept away from external sinks
+                    if data.sensitive {
+                        return false;
+                    }
+                    if let Some(ref webhook_tx) = self.subscribers.webhook_tx {
+                        let _ = webhook_tx.send(webhook::Message::Captured(Box::new(data.clone())));
+                    }
+                    #[cfg(feature = "mqtt")]
+                    if let Some(ref mqtt_tx) = self.subscribers.mqtt_tx {
+                        let _ = mqtt_tx.send(mqtt::Message::Publish(data.clone()));
+                    }
+                    #[cfg(feature = "dbus")]
+                    if let Some(ref notification_tx) = self.subscribers.notification_tx {
+                        let _ = notification_tx.send(notification::Message::Notify(Box::new(data)));
+                    }
                 }
+            }
+        }
+
+        false
+    }
 
-                let data = ClipboardData::from(event);
-                let id = self.clipboard_manager.lock().await.insert(data.clone());
-                let _ = self.clipboard_manager.lock().await.mark_as_primary(id).await;
-                let _ = self.clipboard_manager.lock().await.mark_as_clipboard(id).await;
-                let _ = self.history_manager.lock().await.put(&data);
+    // Takes over the selection the clip is captured from, and the other one if
+    // `sync` copies from it, on the display it is captured from. The origin is
+    // stored last, so the clip keeps its type. The manager is not locked while
+    // the backend stores the clip.
+    async fn store_selections(
+        &self,
+        id: u64,
+        clipboard_type: ClipboardType,
+        display: Option<&str>,
+    ) {
+        let other = match clipboard_type {
+            ClipboardType::Clipboard => ClipboardType::Primary,
+            ClipboardType::Primary => ClipboardType::Clipboard,
+        };
+        let types = if self.settings.sync.copies_from(clipboard_type) {
+            vec![other, clipboard_type]
+        } else {
+            vec![clipboard_type]
+        };
+
+        for clipboard_type in types {
+            let mark = match self.clipboard_manager.write().await.prepare_mark(id, clipboard_type) {
+                Some(mark) => mark,
+                None => return,
+            };
+            if mark.store(display).await.is_ok() {
+                self.clipboard_manager.write().await.finish_mark(mark);
             }
         }
+    }
+
+    async fn expire_sensitive(&self) -> bool {
+        let (expired, clear) =
+            self.clipboard_manager.write().await.expire_sensitive(self.settings.sensitive_timeout);
+        for clip in expired {
+            tracing::info!("Sensitive clip {} is expired and removed", clip.id);
+        }
+        if let Err(err) = clear.clear().await {
+            tracing::warn!("Failed to clear expired sensitive clip, error: {:?}", err);
+        }
+
+        false
+    }
+
+    async fn remove_aged(&self) -> bool {
+        let aged = self.clipboard_manager.write().await.remove_aged();
+        if !aged.is_empty() {
+            tracing::info!("{} clip(s) exceed max_age and are removed", aged.len());
+        }
 
         false
     }
@@ -100,19 +317,38 @@ impl ClipboardWorker {
                     tracing::info!("ClipboardWorker is shutting down gracefully");
                     true
                 }
+                Message::Reconfigure(settings) => {
+                    tracing::info!("ClipboardWorker is reconfigured");
+                    self.settings = *settings;
+                    false
+                }
             },
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn start(
     ctl_tx: CtlMessageSender,
     clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
-    clipboard_manager: Arc<Mutex<ClipboardManager>>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
     history_manager: Arc<Mutex<HistoryManager>>,
+    history_tx: history_writer::MessageSender,
+    metrics: Arc<Metrics>,
+    settings: CaptureSettings,
+    subscribers: Subscribers,
 ) -> (MessageSender, JoinHandle<Result<(), Error>>) {
     let (tx, msg_rx) = mpsc::unbounded_channel::<Message>();
-    let worker =
-        ClipboardWorker { ctl_tx, msg_rx, clipboard_monitor, clipboard_manager, history_manager };
+    let worker = ClipboardWorker {
+        ctl_tx,
+        msg_rx,
+        clipboard_monitor,
+        clipboard_manager,
+        history_manager,
+        history_tx,
+        metrics,
+        settings,
+        subscribers,
+    };
     (tx, tokio::spawn(worker.run()))
 }
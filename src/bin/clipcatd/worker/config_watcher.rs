@@ -0,0 +1,52 @@
+use std::{path::PathBuf, sync::mpsc, time::Duration};
+
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use snafu::ResultExt;
+
+use crate::{
+    error::{self, Error},
+    worker::{CtlMessage, CtlMessageSender},
+};
+
+// Editors write a file in several steps, they are reported as one change.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(500);
+
+/// Reloads the configuration when `config_file` changes, like `SIGHUP`.
+pub fn start(config_file: PathBuf, ctl_tx: CtlMessageSender) -> Result<(), Error> {
+    // editors replace the file instead of writing it, so the directory is
+    // watched and the file is picked by its name
+    let directory = match config_file.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = config_file.file_name().map(ToOwned::to_owned);
+
+    let (event_tx, event_rx) = mpsc::channel();
+    let mut watcher = notify::watcher(event_tx, DEBOUNCE_DELAY).context(error::WatchConfig)?;
+    watcher.watch(&directory, RecursiveMode::NonRecursive).context(error::WatchConfig)?;
+    tracing::info!("Configuration file {:?} is watched for changes", config_file);
+
+    // the watcher blocks, the thread is not joined and ends with the daemon
+    std::thread::spawn(move || {
+        let _watcher = watcher;
+        while let Ok(event) = event_rx.recv() {
+            let path = match event {
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Rename(_, path) => path,
+                DebouncedEvent::Error(err, _) => {
+                    tracing::warn!("Failed to watch configuration file, error: {}", err);
+                    continue;
+                }
+                _ => continue,
+            };
+            if path.file_name() == file_name.as_deref() {
+                tracing::info!("Configuration file {:?} is changed", config_file);
+                if ctl_tx.send(CtlMessage::Reload).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    Ok(())
+}
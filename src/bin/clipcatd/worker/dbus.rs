@@ -0,0 +1,253 @@
+use std::{
+    convert::TryFrom,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use snafu::ResultExt;
+use tokio::{
+    runtime::Handle,
+    sync::{mpsc, Mutex, RwLock},
+    task::JoinHandle,
+};
+use zbus::dbus_interface;
+use zvariant::ObjectPath;
+
+use clipcat::{ClipboardData, ClipboardManager, ClipboardMonitor, ClipboardType};
+
+use crate::error::{self, Error};
+
+pub const DBUS_NAME: &str = "org.clipcat.Clipcat";
+pub const DBUS_PATH: &str = "/org/clipcat/Clipcat";
+
+pub const KLIPPER_NAME: &str = "org.kde.klipper";
+pub const KLIPPER_PATH: &str = "/klipper";
+
+pub enum Message {
+    Shutdown,
+}
+
+pub type MessageSender = mpsc::UnboundedSender<Message>;
+
+/// Core operations of clipcatd on the session bus, for desktop environments
+/// and scripts which do not speak gRPC.
+struct Clipcat {
+    runtime: Handle,
+    clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+}
+
+#[dbus_interface(name = "org.clipcat.Clipcat")]
+impl Clipcat {
+    /// Lists clips as (id, MIME type, preview), previews are cut at
+    /// `preview_length` characters unless it is 0.
+    fn list(&self, preview_length: u32) -> Vec<(u64, String, String)> {
+        let preview_length = if preview_length == 0 { None } else { Some(preview_length as usize) };
+        let clips =
+            self.runtime.block_on(async { self.clipboard_manager.read().await.list_tagged(&[]) });
+        clips
+            .into_iter()
+            .map(|clip| (clip.id, clip.mime.clone(), clip.printable_data(preview_length)))
+            .collect()
+    }
+
+    /// Returns the MIME type and the content of the clip with `id`.
+    fn get(&self, id: u64) -> zbus::fdo::Result<(String, Vec<u8>)> {
+        let clip = self.runtime.block_on(async { self.clipboard_manager.read().await.get(id) });
+        match clip {
+            Some(clip) => Ok((clip.mime.clone(), clip.as_bytes().to_vec())),
+            None => Err(zbus::fdo::Error::InvalidArgs(format!("No clip with ID {:016x}", id))),
+        }
+    }
+
+    /// Puts the clip with `id` into the clipboard.
+    fn promote(&self, id: u64) -> bool {
+        let promoted = self.runtime.block_on(ClipboardManager::promote(
+            &self.clipboard_manager,
+            id,
+            ClipboardType::Clipboard,
+        ));
+        matches!(promoted, Ok(true))
+    }
+
+    fn remove(&self, id: u64) -> bool {
+        self.runtime.block_on(async { self.clipboard_manager.write().await.remove(id) })
+    }
+
+    /// Stops recording clips until `Resume` is called.
+    fn pause(&self) {
+        self.runtime.block_on(async { self.clipboard_monitor.lock().await.disable() })
+    }
+
+    fn resume(&self) {
+        self.runtime.block_on(async { self.clipboard_monitor.lock().await.enable() })
+    }
+
+    #[dbus_interface(property)]
+    fn paused(&self) -> bool {
+        self.runtime.block_on(async { !self.clipboard_monitor.lock().await.is_running() })
+    }
+}
+
+/// The interface of Klipper on top of ClipboardManager, so applets and tools
+/// which talk to Klipper work with clipcatd. Only text clips are visible.
+struct Klipper {
+    runtime: Handle,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+}
+
+impl Klipper {
+    fn history(&self) -> Vec<String> {
+        let clips =
+            self.runtime.block_on(async { self.clipboard_manager.read().await.list_tagged(&[]) });
+        clips.into_iter().filter(ClipboardData::is_text).map(|clip| clip.data).collect()
+    }
+}
+
+#[dbus_interface(name = "org.kde.klipper.klipper")]
+impl Klipper {
+    #[dbus_interface(name = "getClipboardContents")]
+    fn get_clipboard_contents(&self) -> String {
+        self.runtime.block_on(async {
+            let manager = self.clipboard_manager.read().await;
+            match manager.get_current_clipboard() {
+                Some(clip) if clip.is_text() => manager.load(clip.clone()).data,
+                _ => String::new(),
+            }
+        })
+    }
+
+    #[dbus_interface(name = "setClipboardContents")]
+    fn set_clipboard_contents(&self, contents: String) {
+        self.runtime.block_on(async {
+            let clip = ClipboardData::new_clipboard(&contents);
+            let id = self.clipboard_manager.write().await.insert(clip);
+            let _ =
+                ClipboardManager::promote(&self.clipboard_manager, id, ClipboardType::Clipboard)
+                    .await;
+        })
+    }
+
+    #[dbus_interface(name = "clearClipboardContents")]
+    fn clear_clipboard_contents(&self) {
+        self.runtime.block_on(async {
+            let mut manager = self.clipboard_manager.write().await;
+            if let Some(id) = manager.get_current_clipboard().map(|clip| clip.id) {
+                manager.remove(id);
+            }
+        })
+    }
+
+    #[dbus_interface(name = "clearClipboardHistory")]
+    fn clear_clipboard_history(&self) {
+        self.runtime.block_on(async { self.clipboard_manager.write().await.clear() })
+    }
+
+    /// Texts of the clips, the most relevant clip first.
+    #[dbus_interface(name = "getClipboardHistoryMenu")]
+    fn get_clipboard_history_menu(&self) -> Vec<String> { self.history() }
+
+    #[dbus_interface(name = "getClipboardHistoryItem")]
+    fn get_clipboard_history_item(&self, index: i32) -> String {
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| self.history().into_iter().nth(index))
+            .unwrap_or_default()
+    }
+}
+
+/// Serves `org.clipcat.Clipcat` and additionally the interface of Klipper if
+/// `klipper` is set.
+#[allow(clippy::never_loop)]
+pub fn start(
+    clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+    klipper: bool,
+) -> (MessageSender, JoinHandle<Result<(), Error>>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+    let shutdown = Arc::new(AtomicBool::new(false));
+
+    // zbus serves objects on a blocking connection, the service runs on its own
+    // thread and calls into the runtime for the managers
+    let runtime = Handle::current();
+    let server_shutdown = shutdown.clone();
+    let server = tokio::task::spawn_blocking(move || {
+        let klipper = if klipper {
+            Some(Klipper { runtime: runtime.clone(), clipboard_manager: clipboard_manager.clone() })
+        } else {
+            None
+        };
+        let clipcat = Clipcat { runtime, clipboard_monitor, clipboard_manager };
+        let result = serve(clipcat, klipper, &server_shutdown);
+        if let Err(ref err) = result {
+            tracing::warn!("D-Bus service is not available, error: {}", err);
+        }
+        result
+    });
+
+    let join_handle = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            match msg {
+                Message::Shutdown => {
+                    tracing::info!("D-Bus service is shutting down gracefully");
+                    break;
+                }
+            }
+        }
+
+        // the service only checks for shutdown after handling a message, so it
+        // is woken up by pinging it
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = tokio::task::spawn_blocking(ping).await;
+        server.await.unwrap_or(Ok(()))
+    });
+    (tx, join_handle)
+}
+
+fn serve(clipcat: Clipcat, klipper: Option<Klipper>, shutdown: &AtomicBool) -> Result<(), Error> {
+    let connection = zbus::Connection::new_session().context(error::ConnectDbus)?;
+    let dbus = zbus::fdo::DBusProxy::new(&connection).context(error::ConnectDbus)?;
+    dbus.request_name(DBUS_NAME, zbus::fdo::RequestNameFlags::DoNotQueue.into())
+        .context(error::RequestDbusName { name: DBUS_NAME })?;
+
+    let mut object_server = zbus::ObjectServer::new(&connection);
+    object_server
+        .at(&ObjectPath::from_static_str_unchecked(DBUS_PATH), clipcat)
+        .context(error::ServeDbus)?;
+    tracing::info!("D-Bus service is available as {}", DBUS_NAME);
+
+    if let Some(klipper) = klipper {
+        // Klipper itself may own the name already, clipcat is still served then
+        match dbus.request_name(KLIPPER_NAME, zbus::fdo::RequestNameFlags::DoNotQueue.into()) {
+            Ok(zbus::fdo::RequestNameReply::PrimaryOwner) => {
+                object_server
+                    .at(&ObjectPath::from_static_str_unchecked(KLIPPER_PATH), klipper)
+                    .context(error::ServeDbus)?;
+                tracing::info!("Klipper interface is available as {}", KLIPPER_NAME);
+            }
+            Ok(_) => tracing::warn!("{} is owned by another application", KLIPPER_NAME),
+            Err(err) => tracing::warn!("Could not own D-Bus name {}, error: {}", KLIPPER_NAME, err),
+        }
+    }
+
+    while !shutdown.load(Ordering::SeqCst) {
+        if let Err(err) = object_server.try_handle_next() {
+            tracing::warn!("Failed to handle D-Bus message, error: {}", err);
+        }
+    }
+    Ok(())
+}
+
+fn ping() -> Result<(), zbus::Error> {
+    let connection = zbus::Connection::new_session()?;
+    connection.call_method(
+        Some(DBUS_NAME),
+        DBUS_PATH,
+        Some("org.freedesktop.DBus.Peer"),
+        "Ping",
+        &(),
+    )?;
+    Ok(())
+}
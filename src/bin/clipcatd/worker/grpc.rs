@@ -1,57 +1,429 @@
-use std::sync::Arc;
+use std::{
+    future::Future,
+    net::SocketAddr,
+    path::Path,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+#[cfg(unix)]
+use std::{os::unix::io::RawFd, path::PathBuf};
 
+use futures::future::BoxFuture;
+#[cfg(unix)]
+use futures::TryStreamExt;
 use snafu::ResultExt;
+#[cfg(unix)]
+use tokio::net::{TcpListener, UnixListener};
 use tokio::{
-    sync::{mpsc, Mutex},
+    sync::{mpsc, Mutex, RwLock},
     task::JoinHandle,
 };
+#[cfg(unix)]
+use tokio_stream::wrappers::{TcpListenerStream, UnixListenerStream};
+use tonic::{
+    body::BoxBody,
+    transport::{Body, Certificate, Identity, NamedService, Server, ServerTlsConfig},
+};
+use tower::Service;
+use tracing_futures::Instrument;
 
 use clipcat::{
-    grpc::{self, ManagerService, MonitorService},
-    ClipboardManager, ClipboardMonitor,
+    grpc::{
+        self, HistoryMaintenance, ManagerService, MonitorService, RuntimeSettings, SettingsService,
+    },
+    ClipboardManager, ClipboardMonitor, SnippetManager,
 };
 
-use crate::error::{self, Error};
+use crate::{
+    config::{GrpcTls, GrpcWeb},
+    error::{self, Error},
+    history::HistoryManager,
+    metrics::Metrics,
+};
 
 pub enum Message {
     Shutdown,
 }
 
-#[allow(clippy::never_loop)]
+pub enum ListenAddress {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+
+    /// Socket passed by systemd socket activation.
+    #[cfg(unix)]
+    Activated(RawFd),
+}
+
+pub struct Options {
+    pub token: Option<String>,
+    pub tls: Option<ServerTlsConfig>,
+    pub web: Option<GrpcWeb>,
+}
+
+// Records the latency of each request in the metrics and traces the request in
+// a span, until the response is sent. Streams like `Subscribe` are timed until
+// they are opened.
+#[derive(Clone)]
+struct Timed<S> {
+    inner: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S> Timed<S> {
+    fn new(inner: S, metrics: &Arc<Metrics>) -> Timed<S> {
+        Timed { inner, metrics: metrics.clone() }
+    }
+}
+
+impl<S: NamedService> NamedService for Timed<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S> Service<http::Request<Body>> for Timed<S>
+where
+    S: Service<http::Request<Body>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+    type Response = S::Response;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let method = request.uri().path().to_owned();
+        let metrics = self.metrics.clone();
+        let span = tracing::info_span!("grpc", method = method.as_str());
+        let started = Instant::now();
+        let response = span.in_scope(|| self.inner.call(request));
+        Box::pin(
+            async move {
+                let response = response.await;
+                metrics.grpc_request(&method, started.elapsed());
+                response
+            }
+            .instrument(span),
+        )
+    }
+}
+
+// The clips in memory are saved before vacuuming, so clips which were removed
+// since start up are dropped from the history files as well.
+struct HistoryVacuum {
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+    history_manager: Arc<Mutex<HistoryManager>>,
+}
+
+#[tonic::async_trait]
+impl HistoryMaintenance for HistoryVacuum {
+    async fn vacuum(&self) -> Result<u64, String> {
+        let clips = self.clipboard_manager.read().await.list();
+        let mut hm = self.history_manager.lock().await;
+
+        tracing::info!("Vacuum history {:?}", hm.path());
+        let reclaimed = tokio::task::block_in_place(|| hm.save_and_vacuum(&clips))
+            .map_err(|err| err.to_string())?;
+        tracing::info!("Reclaimed {} bytes of history", reclaimed);
+        Ok(reclaimed)
+    }
+}
+
+#[allow(clippy::never_loop, clippy::too_many_arguments)]
 pub fn start(
-    grpc_addr: std::net::SocketAddr,
+    grpc_addr: ListenAddress,
+    opts: Options,
     clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
-    clipboard_manager: Arc<Mutex<ClipboardManager>>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+    history_manager: Arc<Mutex<HistoryManager>>,
+    snippet_manager: Arc<Mutex<SnippetManager>>,
+    settings: Arc<dyn RuntimeSettings>,
+    metrics: Arc<Metrics>,
 ) -> (mpsc::UnboundedSender<Message>, JoinHandle<Result<(), Error>>) {
-    let server = {
-        let monitor_service = MonitorService::new(clipboard_monitor);
-        let manager_service = ManagerService::new(clipboard_manager);
-
-        tonic::transport::Server::builder()
-            .add_service(grpc::MonitorServer::new(monitor_service))
-            .add_service(grpc::ManagerServer::new(manager_service))
-    };
-
     let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
 
     let join_handle = tokio::spawn(async move {
-        tracing::info!("gRPC service listening on {}", grpc_addr);
-
-        server
-            .serve_with_shutdown(grpc_addr, async move {
-                while let Some(msg) = rx.recv().await {
-                    match msg {
-                        Message::Shutdown => {
-                            tracing::info!("gRPC service is shutting down gracefully");
-                            return;
-                        }
+        let (monitor_server, manager_server, settings_server) = {
+            let monitor_service = MonitorService::new(clipboard_monitor)
+                .with_dropped_events(metrics.dropped_events());
+            let history = Arc::new(HistoryVacuum {
+                clipboard_manager: clipboard_manager.clone(),
+                history_manager,
+            });
+            let manager_service =
+                ManagerService::new(clipboard_manager, snippet_manager).with_history(history);
+            let settings_service = SettingsService::new(settings);
+            let interceptor = grpc::server_interceptor(opts.token.as_deref());
+            (
+                Timed::new(
+                    grpc::MonitorServer::with_interceptor(monitor_service, interceptor.clone()),
+                    &metrics,
+                ),
+                Timed::new(
+                    grpc::ManagerServer::with_interceptor(manager_service, interceptor.clone()),
+                    &metrics,
+                ),
+                Timed::new(
+                    grpc::SettingsServer::with_interceptor(settings_service, interceptor),
+                    &metrics,
+                ),
+            )
+        };
+
+        let mut builder = Server::builder();
+        if let Some(tls) = opts.tls {
+            builder = builder.tls_config(tls).context(error::ConfigureTls)?;
+        }
+
+        let shutdown_signal = async move {
+            while let Some(msg) = rx.recv().await {
+                match msg {
+                    Message::Shutdown => {
+                        tracing::info!("gRPC service is shutting down gracefully");
+                        return;
                     }
                 }
-            })
-            .await
-            .context(error::ServeGrpc)?;
+            }
+        };
 
-        Ok(())
+        match opts.web {
+            Some(ref web) => {
+                tracing::info!("gRPC-Web is enabled");
+                let web = web_config(web);
+                // browsers speak gRPC-Web over HTTP/1.1
+                let builder = builder.accept_http1(true);
+                let (monitor_server, manager_server, settings_server) = (
+                    web.enable(monitor_server),
+                    web.enable(manager_server),
+                    web.enable(settings_server),
+                );
+                serve(
+                    builder,
+                    (monitor_server, manager_server, settings_server),
+                    grpc_addr,
+                    shutdown_signal,
+                )
+                .await
+            }
+            None => {
+                serve(
+                    builder,
+                    (monitor_server, manager_server, settings_server),
+                    grpc_addr,
+                    shutdown_signal,
+                )
+                .await
+            }
+        }
     });
     (tx, join_handle)
 }
+
+fn web_config(web: &GrpcWeb) -> tonic_web::Config {
+    // an empty list refuses every cross-origin request
+    let config = if web.allows_any_origin() {
+        tonic_web::config().allow_all_origins()
+    } else {
+        tonic_web::config().allow_origins(web.allow_origins.iter().map(String::as_str))
+    };
+    config.allow_credentials(web.allow_credentials).max_age(Duration::from_secs(web.max_age))
+}
+
+async fn serve<M, N, O>(
+    mut builder: Server,
+    (monitor_server, manager_server, settings_server): (M, N, O),
+    grpc_addr: ListenAddress,
+    shutdown_signal: impl Future<Output = ()>,
+) -> Result<(), Error>
+where
+    M: Service<http::Request<Body>, Response = http::Response<BoxBody>>
+        + NamedService
+        + Clone
+        + Send
+        + 'static,
+    M::Future: Send + 'static,
+    M::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+    N: Service<http::Request<Body>, Response = http::Response<BoxBody>>
+        + NamedService
+        + Clone
+        + Send
+        + 'static,
+    N::Future: Send + 'static,
+    N::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+    O: Service<http::Request<Body>, Response = http::Response<BoxBody>>
+        + NamedService
+        + Clone
+        + Send
+        + 'static,
+    O::Future: Send + 'static,
+    O::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+{
+    let server = builder
+        .add_service(monitor_server)
+        .add_service(manager_server)
+        .add_service(settings_server);
+    match grpc_addr {
+        ListenAddress::Tcp(addr) => {
+            tracing::info!("gRPC service listening on {}", addr);
+            server.serve_with_shutdown(addr, shutdown_signal).await.context(error::ServeGrpc)?;
+        }
+        #[cfg(unix)]
+        ListenAddress::Unix(path) => {
+            let listener = bind_unix_socket(&path)?;
+            tracing::info!("gRPC service listening on {:?}", path);
+
+            let incoming = UnixListenerStream::new(listener).map_ok(unix::UnixStream);
+            let result = server.serve_with_incoming_shutdown(incoming, shutdown_signal).await;
+            let _ = std::fs::remove_file(&path);
+            result.context(error::ServeGrpc)?;
+        }
+        // the socket belongs to the socket unit, it is left behind for the next
+        // start
+        #[cfg(unix)]
+        ListenAddress::Activated(fd) => match activated_listener(fd)? {
+            ActivatedListener::Unix(listener) => {
+                tracing::info!("gRPC service listening on unix socket passed by systemd");
+                let incoming = UnixListenerStream::new(listener).map_ok(unix::UnixStream);
+                server
+                    .serve_with_incoming_shutdown(incoming, shutdown_signal)
+                    .await
+                    .context(error::ServeGrpc)?;
+            }
+            ActivatedListener::Tcp(listener) => {
+                let addr = listener.local_addr().context(error::ListenActivatedSocket)?;
+                tracing::info!("gRPC service listening on {} passed by systemd", addr);
+                let incoming = TcpListenerStream::new(listener);
+                server
+                    .serve_with_incoming_shutdown(incoming, shutdown_signal)
+                    .await
+                    .context(error::ServeGrpc)?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+pub fn load_tls_config(tls: &GrpcTls) -> Result<ServerTlsConfig, Error> {
+    fn read(filename: &Path) -> Result<Vec<u8>, Error> {
+        std::fs::read(filename).context(error::ReadTlsFile { filename: filename.to_path_buf() })
+    }
+
+    let identity = Identity::from_pem(read(&tls.certificate)?, read(&tls.key)?);
+    let mut config = ServerTlsConfig::new().identity(identity);
+    if let Some(ref client_ca_certificate) = tls.client_ca_certificate {
+        config = config.client_ca_root(Certificate::from_pem(read(client_ca_certificate)?));
+    }
+    Ok(config)
+}
+
+#[cfg(unix)]
+fn bind_unix_socket(path: &Path) -> Result<UnixListener, Error> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    // directories which exist already keep their permissions
+    if let Some(dir) = path.parent() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .context(error::CreateSocketDirectory { path: dir.to_path_buf() })?;
+    }
+
+    // remove the socket left behind by a previous run
+    if path.exists() {
+        std::fs::remove_file(path).context(error::BindUnixSocket { path: path.to_path_buf() })?;
+    }
+
+    // the socket is created with the permissions of the umask, which must not
+    // let other users connect before it is restricted below
+    let umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(path);
+    unsafe { libc::umask(umask) };
+    let listener = listener.context(error::BindUnixSocket { path: path.to_path_buf() })?;
+
+    // only the owner is allowed to access the clipboard
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .context(error::BindUnixSocket { path: path.to_path_buf() })?;
+
+    Ok(listener)
+}
+
+#[cfg(unix)]
+enum ActivatedListener {
+    Unix(UnixListener),
+    Tcp(TcpListener),
+}
+
+// Socket units listen on unix sockets and on TCP ports, the address family of
+// the socket tells which one is passed.
+#[cfg(unix)]
+fn activated_listener(fd: RawFd) -> Result<ActivatedListener, Error> {
+    use std::os::unix::io::{FromRawFd, IntoRawFd};
+
+    let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(fd) };
+    if listener.local_addr().is_ok() {
+        listener.set_nonblocking(true).context(error::ListenActivatedSocket)?;
+        let listener = UnixListener::from_std(listener).context(error::ListenActivatedSocket)?;
+        return Ok(ActivatedListener::Unix(listener));
+    }
+
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(listener.into_raw_fd()) };
+    listener.set_nonblocking(true).context(error::ListenActivatedSocket)?;
+    let listener = TcpListener::from_std(listener).context(error::ListenActivatedSocket)?;
+    Ok(ActivatedListener::Tcp(listener))
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use tonic::transport::server::Connected;
+
+    #[derive(Debug)]
+    pub struct UnixStream(pub tokio::net::UnixStream);
+
+    impl Connected for UnixStream {
+        type ConnectInfo = ();
+
+        fn connect_info(&self) -> Self::ConnectInfo {}
+    }
+
+    impl AsyncRead for UnixStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.0).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for UnixStream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            Pin::new(&mut self.0).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.0).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.0).poll_shutdown(cx)
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use futures::FutureExt;
+use tokio::{
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc, Mutex,
+    },
+    task::JoinHandle,
+};
+
+use clipcat::ClipboardData;
+
+use crate::history::HistoryManager;
+
+// Clips which wait for being written, captured clips are dropped from the
+// queue when it is full and are only saved at shutdown.
+const QUEUE_CAPACITY: usize = 256;
+
+// At most this many queued clips are written at once.
+const BATCH_SIZE: usize = 64;
+
+pub enum Message {
+    Shutdown,
+
+    Put(Box<ClipboardData>),
+}
+
+pub type MessageSender = mpsc::Sender<Message>;
+pub type MessageReceiver = mpsc::Receiver<Message>;
+
+/// Writes captured clips into the history, so that the latency of the disk
+/// does not slow down capturing, and deletes clips which were pasted once.
+pub struct HistoryWriter {
+    msg_rx: MessageReceiver,
+    pasted_once: broadcast::Receiver<u64>,
+    history_manager: Arc<Mutex<HistoryManager>>,
+}
+
+impl HistoryWriter {
+    async fn run(mut self) {
+        let mut quit = false;
+        while !quit {
+            let mut batch = Vec::new();
+            let mut removed = Vec::new();
+            futures::select! {
+                msg = self.msg_rx.recv().fuse() => match msg {
+                    None | Some(Message::Shutdown) => quit = true,
+                    Some(Message::Put(clip)) => batch.push(*clip),
+                },
+                id = self.pasted_once.recv().fuse() => match id {
+                    Ok(id) => removed.push(id),
+                    Err(RecvError::Lagged(n)) => tracing::warn!(
+                        "HistoryWriter lagged behind, {} clip(s) pasted once are kept in history",
+                        n
+                    ),
+                    Err(RecvError::Closed) => quit = true,
+                },
+            }
+            // queued clips are written before shutting down
+            while !quit && batch.len() < BATCH_SIZE {
+                match self.msg_rx.recv().now_or_never() {
+                    Some(Some(Message::Put(clip))) => batch.push(*clip),
+                    Some(None) | Some(Some(Message::Shutdown)) => quit = true,
+                    None => break,
+                }
+            }
+            // a queued clip which was pasted once meanwhile is written first,
+            // so that it is deleted afterwards
+            self.write(batch).await;
+            self.remove(removed).await;
+        }
+        tracing::info!("HistoryWriter is shutting down gracefully");
+    }
+
+    async fn write(&self, batch: Vec<ClipboardData>) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let mut hm = self.history_manager.clone().lock_owned().await;
+        let result =
+            tokio::task::spawn_blocking(move || hm.put_all(&batch).map(|_| batch.len())).await;
+        match result {
+            Ok(Ok(count)) => tracing::debug!("Write {} clip(s) into history", count),
+            Ok(Err(err)) => tracing::warn!("Failed to write clips into history, error: {}", err),
+            Err(err) => tracing::warn!("Failed to write clips into history, error: {}", err),
+        }
+    }
+
+    async fn remove(&self, ids: Vec<u64>) {
+        if ids.is_empty() {
+            return;
+        }
+
+        let mut hm = self.history_manager.clone().lock_owned().await;
+        let result =
+            tokio::task::spawn_blocking(move || hm.remove_all(&ids).map(|_| ids.len())).await;
+        match result {
+            Ok(Ok(count)) => tracing::debug!("Remove {} clip(s) from history", count),
+            Ok(Err(err)) => tracing::warn!("Failed to remove clips from history, error: {}", err),
+            Err(err) => tracing::warn!("Failed to remove clips from history, error: {}", err),
+        }
+    }
+}
+
+pub fn start(
+    history_manager: Arc<Mutex<HistoryManager>>,
+    pasted_once: broadcast::Receiver<u64>,
+) -> (MessageSender, JoinHandle<()>) {
+    let (tx, msg_rx) = mpsc::channel::<Message>(QUEUE_CAPACITY);
+    let worker = HistoryWriter { msg_rx, pasted_once, history_manager };
+    (tx, tokio::spawn(worker.run()))
+}
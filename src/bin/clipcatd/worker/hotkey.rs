@@ -0,0 +1,331 @@
+#[cfg(feature = "dbus")]
+use std::{collections::HashMap, convert::TryFrom};
+use std::{sync::Arc, time::SystemTime};
+
+use futures::FutureExt;
+use snafu::ResultExt;
+use tokio::{
+    sync::{mpsc, Mutex, RwLock},
+    task::JoinHandle,
+};
+use x11_clipboard::xcb;
+#[cfg(feature = "dbus")]
+use zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+
+use clipcat::{ClipboardBackendType, ClipboardManager, ClipboardMonitor, ClipboardType};
+
+use crate::{
+    error::{self, Error},
+    hotkey::{Action, Binding},
+};
+
+// Lock and Num Lock do not change which hotkey is pressed, so each hotkey is
+// grabbed with all of their combinations.
+const LOCK_MASK: u16 = 1 << 1;
+const NUM_LOCK_MASK: u16 = 1 << 4;
+const IGNORED_MASKS: [u16; 4] = [0, LOCK_MASK, NUM_LOCK_MASK, LOCK_MASK | NUM_LOCK_MASK];
+
+#[cfg(feature = "dbus")]
+const PORTAL_NAME: &str = "org.freedesktop.portal.Desktop";
+#[cfg(feature = "dbus")]
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+#[cfg(feature = "dbus")]
+const SHORTCUTS_INTERFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+#[cfg(feature = "dbus")]
+const SESSION_TOKEN: &str = "clipcat";
+
+pub enum Message {
+    Shutdown,
+}
+
+pub type MessageSender = mpsc::UnboundedSender<Message>;
+pub type MessageReceiver = mpsc::UnboundedReceiver<Message>;
+
+// Indices of the bindings which are pressed.
+type PressSender = mpsc::UnboundedSender<usize>;
+
+pub struct HotkeyWorker {
+    msg_rx: MessageReceiver,
+    clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+    bindings: Vec<Binding>,
+}
+
+impl HotkeyWorker {
+    async fn run(mut self, backend: ClipboardBackendType) {
+        let (press_tx, mut press_rx) = mpsc::unbounded_channel::<usize>();
+
+        // both X11 and D-Bus connections block, the thread is not joined and
+        // ends with the daemon
+        let bindings = self.bindings.clone();
+        let thread_press_tx = press_tx.clone();
+        std::thread::spawn(move || {
+            let press_tx = thread_press_tx;
+            let result = match backend.resolve() {
+                #[cfg(feature = "dbus")]
+                ClipboardBackendType::Wayland => bind_portal(&bindings, &press_tx),
+                #[cfg(not(feature = "dbus"))]
+                ClipboardBackendType::Wayland => {
+                    tracing::warn!(
+                        "clipcatd is built without the dbus feature, hotkeys need the desktop \
+                         portal on Wayland"
+                    );
+                    Ok(())
+                }
+                _ => grab_x11(&bindings, &press_tx),
+            };
+            if let Err(err) = result {
+                tracing::warn!("Hotkeys are not available, error: {}", err);
+            }
+        });
+
+        loop {
+            futures::select! {
+                msg = self.msg_rx.recv().fuse() => match msg {
+                    Some(Message::Shutdown) | None => {
+                        tracing::info!("HotkeyWorker is shutting down gracefully");
+                        break;
+                    }
+                },
+                // `press_tx` is kept, so the channel is never closed
+                index = press_rx.recv().fuse() => if let Some(index) = index {
+                    self.handle_press(index).await;
+                },
+            }
+        }
+        drop(press_tx);
+    }
+
+    async fn handle_press(&self, index: usize) {
+        let binding = match self.bindings.get(index) {
+            Some(binding) => binding,
+            None => return,
+        };
+        tracing::info!("Hotkey {} is pressed", binding.hotkey);
+
+        match binding.action {
+            Action::RunCommand(ref command) => {
+                let command = command.clone();
+                // the menu stays open until a clip is chosen, so the command is
+                // waited for on its own thread
+                tokio::task::spawn_blocking(move || {
+                    match std::process::Command::new("sh").arg("-c").arg(&command).status() {
+                        Ok(status) if status.success() => {}
+                        Ok(status) => {
+                            tracing::warn!("Hotkey command {} exited with {}", command, status)
+                        }
+                        Err(err) => {
+                            tracing::warn!("Could not run hotkey command {}: {}", command, err)
+                        }
+                    }
+                });
+            }
+            Action::PromotePrevious => {
+                let mut clips: Vec<(SystemTime, u64)> = self
+                    .clipboard_manager
+                    .read()
+                    .await
+                    .iter()
+                    .filter(|clip| clip.clipboard_type == ClipboardType::Clipboard)
+                    .map(|clip| (clip.timestamp, clip.id))
+                    .collect();
+                clips.sort_unstable_by(|a, b| b.cmp(a));
+                if let Some(&(_, id)) = clips.get(1) {
+                    let manager = &self.clipboard_manager;
+                    let result =
+                        ClipboardManager::promote(manager, id, ClipboardType::Clipboard).await;
+                    if let Err(err) = result {
+                        tracing::warn!("Could not promote clip {:016x}, error: {}", id, err);
+                    }
+                }
+            }
+            Action::ToggleMonitor => {
+                let mut monitor = self.clipboard_monitor.lock().await;
+                monitor.toggle();
+                tracing::info!(
+                    "Monitor is {}",
+                    if monitor.is_running() { "enabled" } else { "disabled" }
+                );
+            }
+        }
+    }
+}
+
+fn grab_x11(bindings: &[Binding], press_tx: &PressSender) -> Result<(), Error> {
+    let (connection, screen) = xcb::Connection::connect(None).context(error::ConnectX11)?;
+    let setup = connection.get_setup();
+    let root =
+        setup.roots().nth(screen as usize).map(|screen| screen.root()).ok_or(Error::NoX11Screen)?;
+
+    let (min_keycode, max_keycode) = (setup.min_keycode(), setup.max_keycode());
+    let mapping =
+        xcb::get_keyboard_mapping(&connection, min_keycode, max_keycode - min_keycode + 1)
+            .get_reply()
+            .context(error::GetKeyboardMapping)?;
+    let keysyms_per_keycode = usize::from(mapping.keysyms_per_keycode()).max(1);
+
+    // (keycode, modifiers) of each hotkey, keys which are not on the keyboard
+    // are never pressed
+    let mut keys = Vec::with_capacity(bindings.len());
+    for Binding { hotkey, .. } in bindings {
+        let keycode = mapping
+            .keysyms()
+            .chunks(keysyms_per_keycode)
+            .position(|keysyms| keysyms.contains(&hotkey.keysym))
+            .map(|position| min_keycode + position as u8);
+        match keycode {
+            Some(keycode) => {
+                let grabbed = IGNORED_MASKS.iter().try_for_each(|&mask| {
+                    xcb::grab_key_checked(
+                        &connection,
+                        false,
+                        root,
+                        hotkey.modifiers | mask,
+                        keycode,
+                        xcb::GRAB_MODE_ASYNC as u8,
+                        xcb::GRAB_MODE_ASYNC as u8,
+                    )
+                    .request_check()
+                });
+                match grabbed {
+                    Ok(()) => tracing::info!("Hotkey {} is grabbed", hotkey),
+                    // another application may have grabbed the key already
+                    Err(err) => tracing::warn!("Could not grab hotkey {}, error: {}", hotkey, err),
+                }
+            }
+            None => tracing::warn!("Key of hotkey {} is not on the keyboard", hotkey),
+        }
+        keys.push(keycode.map(|keycode| (keycode, hotkey.modifiers)));
+    }
+    connection.flush();
+
+    while let Some(event) = connection.wait_for_event() {
+        if event.response_type() & !0x80 == xcb::KEY_PRESS {
+            let event = unsafe { xcb::cast_event::<xcb::KeyPressEvent>(&event) };
+            let modifiers = event.state() & !(LOCK_MASK | NUM_LOCK_MASK);
+            let pressed = Some((event.detail(), modifiers));
+            if let Some(index) = keys.iter().position(|key| *key == pressed) {
+                if press_tx.send(index).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "dbus")]
+fn bind_portal(bindings: &[Binding], press_tx: &PressSender) -> Result<(), Error> {
+    let connection = zbus::Connection::new_session().context(error::ConnectDbus)?;
+    let dbus = zbus::fdo::DBusProxy::new(&connection).context(error::ConnectDbus)?;
+    for rule in &[
+        "type='signal',interface='org.freedesktop.portal.Request',member='Response'",
+        "type='signal',interface='org.freedesktop.portal.GlobalShortcuts',member='Activated'",
+    ] {
+        dbus.add_match(rule).context(error::SubscribePortal)?;
+    }
+
+    // the portal derives the path of the session from the unique name
+    let sender =
+        connection.unique_name().unwrap_or_default().trim_start_matches(':').replace('.', "_");
+    let session = format!("{}/session/{}/{}", PORTAL_PATH, sender, SESSION_TOKEN);
+
+    let mut options: HashMap<&str, Value> = HashMap::new();
+    options.insert("handle_token", Value::from("clipcat_session"));
+    options.insert("session_handle_token", Value::from(SESSION_TOKEN));
+    call_portal(&connection, "CreateSession", &(options,))?;
+
+    let shortcuts: Vec<(String, HashMap<&str, Value>)> = bindings
+        .iter()
+        .enumerate()
+        .map(|(index, binding)| {
+            let mut options: HashMap<&str, Value> = HashMap::new();
+            options.insert("description", Value::from(binding.action.description()));
+            options.insert("preferred_trigger", Value::from(binding.hotkey.to_string()));
+            (index.to_string(), options)
+        })
+        .collect();
+    let mut options: HashMap<&str, Value> = HashMap::new();
+    options.insert("handle_token", Value::from("clipcat_bind"));
+    let session_path = ObjectPath::try_from(session.as_str()).context(error::ParsePortalPath)?;
+    call_portal(&connection, "BindShortcuts", &(session_path, shortcuts, "", options))?;
+    tracing::info!("{} hotkey(s) are bound by desktop portal", bindings.len());
+
+    loop {
+        let message = connection.receive_message().context(error::ReceivePortal)?;
+        if !is_signal(&message, SHORTCUTS_INTERFACE, "Activated") {
+            continue;
+        }
+        let (_session, shortcut, _timestamp, _options) =
+            match message.body::<(OwnedObjectPath, String, u64, HashMap<String, OwnedValue>)>() {
+                Ok(body) => body,
+                Err(_) => continue,
+            };
+        if let Ok(index) = shortcut.parse::<usize>() {
+            if press_tx.send(index).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Calls a method of the portal and waits for the response of its request.
+#[cfg(feature = "dbus")]
+fn call_portal<B>(
+    connection: &zbus::Connection,
+    method: &'static str,
+    body: &B,
+) -> Result<(), Error>
+where
+    B: serde::Serialize + zvariant::Type,
+{
+    let reply = connection
+        .call_method(Some(PORTAL_NAME), PORTAL_PATH, Some(SHORTCUTS_INTERFACE), method, body)
+        .context(error::CallPortal { method })?;
+    let request: OwnedObjectPath =
+        reply.body().map_err(zbus::Error::from).context(error::ReceivePortal)?;
+
+    loop {
+        let message = connection.receive_message().context(error::ReceivePortal)?;
+        let header = match message.header() {
+            Ok(header) => header,
+            Err(_) => continue,
+        };
+        let path = header.path().ok().flatten().map(|path| path.as_str().to_owned());
+        if path.as_deref() != Some(request.as_str())
+            || !is_signal(&message, "org.freedesktop.portal.Request", "Response")
+        {
+            continue;
+        }
+        let (response, _results) = message
+            .body::<(u32, HashMap<String, OwnedValue>)>()
+            .map_err(zbus::Error::from)
+            .context(error::ReceivePortal)?;
+        return match response {
+            0 => Ok(()),
+            response => Err(Error::PortalRejected { method, response }),
+        };
+    }
+}
+
+#[cfg(feature = "dbus")]
+fn is_signal(message: &zbus::Message, interface: &str, member: &str) -> bool {
+    let header = match message.header() {
+        Ok(header) => header,
+        Err(_) => return false,
+    };
+    header.message_type().ok() == Some(zbus::MessageType::Signal)
+        && header.interface().ok().flatten() == Some(interface)
+        && header.member().ok().flatten() == Some(member)
+}
+
+pub fn start(
+    bindings: Vec<Binding>,
+    backend: ClipboardBackendType,
+    clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+) -> (MessageSender, JoinHandle<()>) {
+    let (tx, msg_rx) = mpsc::unbounded_channel::<Message>();
+    let worker = HotkeyWorker { msg_rx, clipboard_monitor, clipboard_manager, bindings };
+    (tx, tokio::spawn(worker.run(backend)))
+}
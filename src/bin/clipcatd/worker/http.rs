@@ -0,0 +1,378 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::UNIX_EPOCH};
+
+use futures::{FutureExt, SinkExt, StreamExt};
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc, Mutex, RwLock},
+    task::JoinHandle,
+};
+use warp::{
+    http::StatusCode,
+    reply::{self, Reply, Response},
+    ws::{Message as WsMessage, WebSocket, Ws},
+    Filter, Rejection,
+};
+
+use clipcat::{
+    export::ExportedClip, grpc, ClipboardData, ClipboardManager, ClipboardMonitor,
+    ClipboardNotification, ClipboardType,
+};
+
+use crate::metrics::Metrics;
+
+pub enum Message {
+    Shutdown,
+}
+
+pub type MessageSender = mpsc::UnboundedSender<Message>;
+
+/// A clip in responses, binary data is encoded as base64 like in exports.
+#[derive(Debug, Serialize)]
+struct Clip {
+    /// Hexadecimal like in clipcatctl.
+    id: String,
+    size: usize,
+    #[serde(flatten)]
+    clip: ExportedClip,
+}
+
+impl From<&ClipboardData> for Clip {
+    fn from(clip: &ClipboardData) -> Clip {
+        Clip { id: format!("{:016x}", clip.id), size: clip.size(), clip: clip.into() }
+    }
+}
+
+/// A clipboard event pushed to WebSocket clients, the content of the clip is
+/// fetched with `GET /clips/<id>`.
+#[derive(Debug, Serialize)]
+struct Event {
+    id: String,
+    clipboard_type: ClipboardType,
+    mime: String,
+
+    /// Milliseconds since the Unix epoch.
+    timestamp: u64,
+    preview: String,
+}
+
+impl From<&ClipboardNotification> for Event {
+    fn from(notification: &ClipboardNotification) -> Event {
+        Event {
+            id: format!("{:016x}", notification.id),
+            clipboard_type: notification.clipboard_type,
+            mime: notification.mime.clone(),
+            timestamp: notification
+                .timestamp
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis() as u64),
+            preview: notification.preview.clone(),
+        }
+    }
+}
+
+// Browsers can not set headers on WebSocket requests, so the token may be
+// passed as a query parameter as well.
+#[derive(Debug, Deserialize)]
+struct TokenQuery {
+    #[serde(default)]
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    #[serde(default)]
+    offset: usize,
+
+    /// All remaining clips are listed if it is 0.
+    #[serde(default)]
+    limit: usize,
+
+    /// Comma separated, only clips which carry all of the tags are listed.
+    #[serde(default)]
+    tags: Option<String>,
+
+    /// Lists previews of clips which are cut at this many characters instead
+    /// of their content if it is set.
+    #[serde(default)]
+    preview_length: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InsertBody {
+    data: String,
+
+    #[serde(default)]
+    clipboard_type: Option<ClipboardType>,
+
+    #[serde(default)]
+    sensitive: bool,
+}
+
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+#[derive(Debug)]
+struct ForbiddenOrigin;
+
+impl warp::reject::Reject for ForbiddenOrigin {}
+
+pub fn start(
+    addr: SocketAddr,
+    token: Option<String>,
+    allow_origins: Vec<String>,
+    clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+    metrics: Arc<Metrics>,
+) -> (MessageSender, JoinHandle<()>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+
+    let join_handle = tokio::spawn(async move {
+        let shutdown_signal = async move {
+            while let Some(msg) = rx.recv().await {
+                match msg {
+                    Message::Shutdown => {
+                        tracing::info!("HTTP gateway is shutting down gracefully");
+                        return;
+                    }
+                }
+            }
+        };
+
+        let origins = allowed_origins(addr, allow_origins);
+        let routes = routes(token, origins, clipboard_monitor, clipboard_manager, metrics);
+        match warp::serve(routes).try_bind_with_graceful_shutdown(addr, shutdown_signal) {
+            Ok((addr, server)) => {
+                tracing::info!("HTTP gateway listening on {}", addr);
+                server.await;
+            }
+            Err(err) => tracing::warn!("Could not serve HTTP gateway on {}, error: {}", addr, err),
+        }
+    });
+    (tx, join_handle)
+}
+
+fn routes(
+    token: Option<String>,
+    origins: Vec<String>,
+    clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+    metrics: Arc<Metrics>,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    let manager = warp::any().map(move || clipboard_manager.clone());
+    let monitor = warp::any().map(move || clipboard_monitor.clone());
+    let metrics = warp::any().map(move || metrics.clone());
+
+    let list = warp::path!("clips")
+        .and(warp::get())
+        .and(warp::query::<ListQuery>())
+        .and(manager.clone())
+        .and_then(list);
+    let get = warp::path!("clips" / String).and(warp::get()).and(manager.clone()).and_then(get);
+    let insert = warp::path!("clips")
+        .and(warp::post())
+        .and(warp::body::json::<InsertBody>())
+        .and(manager.clone())
+        .and_then(insert);
+    let remove = warp::path!("clips" / String).and(warp::delete()).and(manager).and_then(remove);
+    let events = warp::path!("events")
+        .and(check_origin(origins))
+        .and(warp::ws())
+        .and(monitor)
+        .and(metrics)
+        .map(|ws: Ws, monitor: Arc<Mutex<ClipboardMonitor>>, metrics: Arc<Metrics>| {
+            ws.on_upgrade(move |socket| push_events(socket, monitor, metrics))
+        });
+
+    authorize(token).and(list.or(get).or(insert).or(remove).or(events)).recover(handle_rejection)
+}
+
+fn authorize(token: Option<String>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    let token = Arc::new(token);
+    warp::header::optional::<String>("authorization")
+        .and(warp::query::<TokenQuery>())
+        .and_then(move |authorization: Option<String>, query: TokenQuery| {
+            let token = token.clone();
+            let authorization =
+                authorization.or_else(|| query.token.map(|token| format!("Bearer {}", token)));
+            async move {
+                match *token {
+                    Some(ref token) if !grpc::is_authorized(token, authorization.as_deref()) => {
+                        Err(warp::reject::custom(Unauthorized))
+                    }
+                    _ => Ok(()),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+// The gateway is served to web pages on its own address, the address is used
+// instead of the Host header, which pages on rebound domains control.
+fn allowed_origins(addr: SocketAddr, allow_origins: Vec<String>) -> Vec<String> {
+    let mut origins = allow_origins;
+    origins.push(format!("http://{}", addr));
+    if addr.ip().is_loopback() {
+        origins.push(format!("http://localhost:{}", addr.port()));
+    }
+    origins
+}
+
+// Browsers send the origin of the page with WebSocket upgrades but do not
+// apply the same origin policy to them, so any page could read the events.
+// Clients which are not browsers send no origin.
+fn check_origin(origins: Vec<String>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    let origins = Arc::new(origins);
+    warp::header::optional::<String>("origin")
+        .and_then(move |origin: Option<String>| {
+            let origins = origins.clone();
+            async move {
+                match origin {
+                    Some(ref origin)
+                        if !origins.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin)) =>
+                    {
+                        Err(warp::reject::custom(ForbiddenOrigin))
+                    }
+                    _ => Ok(()),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+async fn list(
+    query: ListQuery,
+    manager: Arc<RwLock<ClipboardManager>>,
+) -> Result<Response, Infallible> {
+    let tags: Vec<String> = query
+        .tags
+        .iter()
+        .flat_map(|tags| tags.split(','))
+        .filter(|tag| !tag.is_empty())
+        .map(ToOwned::to_owned)
+        .collect();
+    let (clips, total) =
+        manager.read().await.list_page(&tags, None, None, query.offset, query.limit);
+
+    let clips: Vec<Clip> = match query.preview_length {
+        Some(line_length) => {
+            clips.iter().map(|clip| Clip::from(&clip.to_preview(Some(line_length)))).collect()
+        }
+        None => clips.iter().map(Into::into).collect(),
+    };
+    Ok(reply::json(&serde_json::json!({ "total": total, "clips": clips })).into_response())
+}
+
+async fn get(id: String, manager: Arc<RwLock<ClipboardManager>>) -> Result<Response, Infallible> {
+    let id = match parse_id(&id) {
+        Ok(id) => id,
+        Err(response) => return Ok(response),
+    };
+
+    match manager.read().await.get(id) {
+        Some(clip) => Ok(reply::json(&Clip::from(&clip)).into_response()),
+        None => Ok(error(StatusCode::NOT_FOUND, "No such clip")),
+    }
+}
+
+async fn insert(
+    body: InsertBody,
+    manager: Arc<RwLock<ClipboardManager>>,
+) -> Result<Response, Infallible> {
+    let clipboard_type = body.clipboard_type.unwrap_or(ClipboardType::Clipboard);
+    let mut clip = ClipboardData::new(&body.data, clipboard_type);
+    clip.sensitive = body.sensitive;
+
+    let id = manager.write().await.insert(clip);
+    if let Err(err) = ClipboardManager::promote(&manager, id, clipboard_type).await {
+        tracing::warn!("Could not store inserted clip {:016x}, error: {}", id, err);
+        return Ok(error(StatusCode::INTERNAL_SERVER_ERROR, "Could not store clip in clipboard"));
+    }
+
+    let body = reply::json(&serde_json::json!({ "id": format!("{:016x}", id) }));
+    Ok(reply::with_status(body, StatusCode::CREATED).into_response())
+}
+
+async fn remove(
+    id: String,
+    manager: Arc<RwLock<ClipboardManager>>,
+) -> Result<Response, Infallible> {
+    let id = match parse_id(&id) {
+        Ok(id) => id,
+        Err(response) => return Ok(response),
+    };
+
+    if manager.write().await.remove(id) {
+        Ok(StatusCode::NO_CONTENT.into_response())
+    } else {
+        Ok(error(StatusCode::NOT_FOUND, "No such clip"))
+    }
+}
+
+/// Pushes an event for each clip captured by the monitor until the client
+/// disconnects.
+async fn push_events(
+    socket: WebSocket,
+    monitor: Arc<Mutex<ClipboardMonitor>>,
+    metrics: Arc<Metrics>,
+) {
+    let mut events = monitor.lock().await.subscribe();
+    let (mut sink, mut stream) = socket.split();
+
+    loop {
+        futures::select! {
+            event = events.recv().fuse() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(n)) => {
+                        tracing::warn!("WebSocket client lagged behind, {} events are skipped", n);
+                        metrics.events_dropped(n);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                let clip = ClipboardData::from(event);
+                let event = Event::from(&ClipboardNotification::from(&clip));
+                let json = serde_json::to_string(&event).expect("Event is serializable");
+                if sink.send(WsMessage::text(json)).await.is_err() {
+                    break;
+                }
+            }
+            msg = stream.next().fuse() => match msg {
+                Some(Ok(msg)) if !msg.is_close() => {}
+                _ => break,
+            },
+        }
+    }
+    tracing::debug!("WebSocket client is disconnected");
+}
+
+async fn handle_rejection(rejection: Rejection) -> Result<Response, Infallible> {
+    let (status, message) = if rejection.find::<Unauthorized>().is_some() {
+        (StatusCode::UNAUTHORIZED, "Invalid or missing token")
+    } else if rejection.find::<ForbiddenOrigin>().is_some() {
+        (StatusCode::FORBIDDEN, "Origin is not allowed")
+    } else if rejection.is_not_found() {
+        (StatusCode::NOT_FOUND, "Not found")
+    } else if rejection.find::<warp::reject::MethodNotAllowed>().is_some() {
+        (StatusCode::METHOD_NOT_ALLOWED, "Method not allowed")
+    } else if rejection.find::<warp::reject::InvalidQuery>().is_some()
+        || rejection.find::<warp::filters::body::BodyDeserializeError>().is_some()
+    {
+        (StatusCode::BAD_REQUEST, "Invalid request")
+    } else {
+        tracing::warn!("Unhandled rejection of HTTP request: {:?}", rejection);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Internal error")
+    };
+    Ok(error(status, message))
+}
+
+fn parse_id(id: &str) -> Result<u64, Response> {
+    u64::from_str_radix(id, 16).map_err(|_| error(StatusCode::BAD_REQUEST, "Invalid clip ID"))
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    reply::with_status(reply::json(&serde_json::json!({ "error": message })), status)
+        .into_response()
+}
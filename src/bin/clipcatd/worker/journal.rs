@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+use futures::FutureExt;
+use tokio::{
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::history::HistoryManager;
+
+pub enum Message {
+    Shutdown,
+}
+
+pub type MessageSender = mpsc::UnboundedSender<Message>;
+pub type MessageReceiver = mpsc::UnboundedReceiver<Message>;
+
+pub struct JournalWorker {
+    msg_rx: MessageReceiver,
+    history_manager: Arc<Mutex<HistoryManager>>,
+    interval: Duration,
+}
+
+impl JournalWorker {
+    async fn run(mut self) {
+        // the journal is compacted when it is opened at start up, so the first
+        // compaction is one interval later
+        let mut interval = tokio::time::interval_at(Instant::now() + self.interval, self.interval);
+
+        loop {
+            futures::select! {
+                _ = interval.tick().fuse() => self.compact().await,
+                msg = self.msg_rx.recv().fuse() => match msg {
+                    None | Some(Message::Shutdown) => {
+                        tracing::info!("JournalWorker is shutting down gracefully");
+                        break;
+                    }
+                },
+            }
+        }
+    }
+
+    async fn compact(&self) {
+        let mut hm = self.history_manager.lock().await;
+        match hm.compact_journal() {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Compact {} clip(s) from journal into history", count),
+            Err(err) => tracing::warn!("Failed to compact journal, error: {}", err),
+        }
+    }
+}
+
+pub fn start(
+    history_manager: Arc<Mutex<HistoryManager>>,
+    interval: Duration,
+) -> (MessageSender, JoinHandle<()>) {
+    let (tx, msg_rx) = mpsc::unbounded_channel::<Message>();
+    let worker = JournalWorker { msg_rx, history_manager, interval };
+    (tx, tokio::spawn(worker.run()))
+}
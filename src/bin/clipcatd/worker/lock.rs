@@ -0,0 +1,158 @@
+use std::{collections::HashMap, sync::Arc};
+
+use futures::FutureExt;
+use snafu::ResultExt;
+use tokio::{
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+};
+use zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use clipcat::ClipboardMonitor;
+
+use crate::error::{self, Error};
+
+const LOGIND_NAME: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+pub enum Message {
+    Shutdown,
+}
+
+pub type MessageSender = mpsc::UnboundedSender<Message>;
+pub type MessageReceiver = mpsc::UnboundedReceiver<Message>;
+
+// Whether the session is locked, each time logind reports a change.
+type LockSender = mpsc::UnboundedSender<bool>;
+
+/// Pauses the monitor while the login session is locked, so nothing which is
+/// copied on the lock screen is recorded.
+pub struct LockWorker {
+    msg_rx: MessageReceiver,
+    clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
+
+    // the monitor is only resumed on unlock if it was paused on lock, a monitor
+    // which is disabled by the user stays disabled
+    paused: bool,
+}
+
+impl LockWorker {
+    async fn run(mut self) {
+        let (lock_tx, mut lock_rx) = mpsc::unbounded_channel::<bool>();
+
+        // the D-Bus connection blocks, the thread is not joined and ends with
+        // the daemon
+        let thread_lock_tx = lock_tx.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = watch_session(&thread_lock_tx) {
+                tracing::warn!("Screen locks are not detected, error: {}", err);
+            }
+        });
+
+        loop {
+            futures::select! {
+                msg = self.msg_rx.recv().fuse() => match msg {
+                    Some(Message::Shutdown) | None => {
+                        tracing::info!("LockWorker is shutting down gracefully");
+                        break;
+                    }
+                },
+                // `lock_tx` is kept, so the channel is never closed
+                locked = lock_rx.recv().fuse() => if let Some(locked) = locked {
+                    self.handle_lock(locked).await;
+                },
+            }
+        }
+        drop(lock_tx);
+    }
+
+    async fn handle_lock(&mut self, locked: bool) {
+        let mut monitor = self.clipboard_monitor.lock().await;
+        if locked && monitor.is_running() {
+            tracing::info!("Session is locked, clips are not recorded until it is unlocked");
+            monitor.disable();
+            self.paused = true;
+        } else if !locked && self.paused {
+            tracing::info!("Session is unlocked");
+            monitor.enable();
+            self.paused = false;
+        }
+    }
+}
+
+fn watch_session(lock_tx: &LockSender) -> Result<(), Error> {
+    let connection = zbus::Connection::new_system().context(error::ConnectSystemBus)?;
+
+    // clipcatd may run as a user service outside of the session, logind picks
+    // the graphical session of the user then
+    let session_id = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".to_owned());
+    let reply = connection
+        .call_method(
+            Some(LOGIND_NAME),
+            LOGIND_PATH,
+            Some(MANAGER_INTERFACE),
+            "GetSession",
+            &(session_id.as_str(),),
+        )
+        .context(error::FindLoginSession)?;
+    let session: OwnedObjectPath =
+        reply.body().map_err(zbus::Error::from).context(error::FindLoginSession)?;
+    tracing::info!("Screen locks of session {} are detected", session.as_str());
+
+    let dbus = zbus::fdo::DBusProxy::new(&connection).context(error::ConnectSystemBus)?;
+    for interface in &[SESSION_INTERFACE, PROPERTIES_INTERFACE] {
+        let rule = format!(
+            "type='signal',sender='{}',interface='{}',path='{}'",
+            LOGIND_NAME,
+            interface,
+            session.as_str()
+        );
+        dbus.add_match(&rule).context(error::SubscribeLoginSession)?;
+    }
+
+    loop {
+        let message = connection.receive_message().context(error::ReceiveLoginSession)?;
+        if let Some(locked) = lock_change(&message, session.as_str()) {
+            if lock_tx.send(locked).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Screen lockers are asked to lock with `Lock`, and lockers which are started
+// otherwise report it with `LockedHint`.
+fn lock_change(message: &zbus::Message, session: &str) -> Option<bool> {
+    let header = message.header().ok()?;
+    if header.message_type().ok()? != zbus::MessageType::Signal
+        || header.path().ok().flatten().is_none_or(|path| path.as_str() != session)
+    {
+        return None;
+    }
+
+    match (header.interface().ok().flatten()?, header.member().ok().flatten()?) {
+        (SESSION_INTERFACE, "Lock") => Some(true),
+        (SESSION_INTERFACE, "Unlock") => Some(false),
+        (PROPERTIES_INTERFACE, "PropertiesChanged") => {
+            let (interface, changed, _invalidated) =
+                message.body::<(String, HashMap<String, OwnedValue>, Vec<String>)>().ok()?;
+            if interface != SESSION_INTERFACE {
+                return None;
+            }
+            match changed.get("LockedHint").map(|value| &**value) {
+                Some(Value::Bool(locked)) => Some(*locked),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+pub fn start(clipboard_monitor: Arc<Mutex<ClipboardMonitor>>) -> (MessageSender, JoinHandle<()>) {
+    let (tx, msg_rx) = mpsc::unbounded_channel::<Message>();
+    let worker = LockWorker { msg_rx, clipboard_monitor, paused: false };
+    (tx, tokio::spawn(worker.run()))
+}
@@ -0,0 +1,110 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use tokio::{
+    sync::{mpsc, RwLock},
+    task::JoinHandle,
+};
+
+use clipcat::{grpc, ClipboardManager};
+
+use crate::metrics::Metrics;
+
+// Version 0.0.4 of the text format of Prometheus.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+pub enum Message {
+    Shutdown,
+}
+
+pub type MessageSender = mpsc::UnboundedSender<Message>;
+
+/// Serves `metrics` on `GET /metrics`, requests have to carry `token` as a
+/// bearer token if it is set.
+#[allow(clippy::never_loop)]
+pub fn start(
+    addr: SocketAddr,
+    token: Option<String>,
+    metrics: Arc<Metrics>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+) -> (MessageSender, JoinHandle<()>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+
+    let join_handle = tokio::spawn(async move {
+        let shutdown_signal = async move {
+            while let Some(msg) = rx.recv().await {
+                match msg {
+                    Message::Shutdown => {
+                        tracing::info!("Metrics endpoint is shutting down gracefully");
+                        return;
+                    }
+                }
+            }
+        };
+
+        let token = Arc::new(token);
+        let make_service = make_service_fn(move |_| {
+            let (token, metrics, clipboard_manager) =
+                (token.clone(), metrics.clone(), clipboard_manager.clone());
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    respond(request, token.clone(), metrics.clone(), clipboard_manager.clone())
+                }))
+            }
+        });
+
+        let server = match Server::try_bind(&addr) {
+            Ok(builder) => builder.serve(make_service),
+            Err(err) => {
+                tracing::warn!("Could not serve metrics on {}, error: {}", addr, err);
+                return;
+            }
+        };
+        tracing::info!("Metrics endpoint listening on {}", addr);
+        if let Err(err) = server.with_graceful_shutdown(shutdown_signal).await {
+            tracing::warn!("Metrics endpoint failed, error: {}", err);
+        }
+    });
+    (tx, join_handle)
+}
+
+async fn respond(
+    request: Request<Body>,
+    token: Arc<Option<String>>,
+    metrics: Arc<Metrics>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+) -> Result<Response<Body>, Infallible> {
+    if let Some(ref token) = *token {
+        let authorization =
+            request.headers().get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+        if !grpc::is_authorized(token, authorization) {
+            return Ok(status(StatusCode::UNAUTHORIZED));
+        }
+    }
+
+    match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => {
+            let text = {
+                let cm = clipboard_manager.read().await;
+                metrics.render(cm.iter(), cm.memory_pressure())
+            };
+            let mut response = Response::new(Body::from(text));
+            response
+                .headers_mut()
+                .insert(header::CONTENT_TYPE, header::HeaderValue::from_static(CONTENT_TYPE));
+            Ok(response)
+        }
+        (_, "/metrics") => Ok(status(StatusCode::METHOD_NOT_ALLOWED)),
+        _ => Ok(status(StatusCode::NOT_FOUND)),
+    }
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
@@ -1,76 +1,426 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use snafu::ResultExt;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{mpsc, Mutex, RwLock};
+use tracing_subscriber::{filter::LevelFilter, reload, Registry};
 
-use clipcat::{ClipboardManager, ClipboardMonitor};
+use clipcat::{spill::SpillStore, ClipboardManager, ClipboardMonitor, SnippetManager};
 
+#[cfg(feature = "dbus")]
+use crate::notification::Notifier;
 use crate::{
-    config::Config,
+    backup::BackupManager,
+    config::{Config, ConfigError, HistoryJournal},
     error::{self, Error},
     history::HistoryManager,
+    hotkey::Binding,
+    metrics::Metrics,
+    webhook::Webhook,
 };
 
+mod backup;
 mod clipboard;
+mod config_watcher;
+#[cfg(feature = "dbus")]
+mod dbus;
 mod grpc;
+mod history_writer;
+#[cfg(target_os = "linux")]
+mod hotkey;
+#[cfg(feature = "http_gateway")]
+mod http;
+mod journal;
+#[cfg(all(target_os = "linux", feature = "dbus"))]
+mod lock;
+mod metrics;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "dbus")]
+mod notification;
+mod settings;
 mod signal;
+#[cfg(feature = "tray")]
+mod tray;
+mod webhook;
 
 pub enum CtlMessage {
     Shutdown,
+    Reload,
 }
 
 pub type CtlMessageSender = mpsc::UnboundedSender<CtlMessage>;
 
+/// Loads the configuration again when it is reloaded, with the options of the
+/// command line applied.
+pub type ConfigLoader = Box<dyn Fn() -> Result<Config, ConfigError> + Send>;
+
+/// Changes the log level while clipcatd runs.
+pub type LogLevelHandle = reload::Handle<LevelFilter, Registry>;
+
 #[allow(clippy::never_loop)]
-pub async fn start(config: Config) -> Result<(), Error> {
-    let grpc_addr = format!("{}:{}", config.grpc.host, config.grpc.port)
-        .parse()
-        .context(error::ParseSockAddr)?;
+pub async fn start(
+    config: Config,
+    config_file: PathBuf,
+    config_loader: ConfigLoader,
+    log_level: LogLevelHandle,
+) -> Result<(), Error> {
+    let active_config = config.clone();
+    let grpc_opts = grpc::Options {
+        token: config.grpc.token.clone(),
+        tls: match config.grpc.tls {
+            Some(ref tls) => Some(grpc::load_tls_config(tls)?),
+            None => None,
+        },
+        web: config.grpc.web.clone(),
+    };
+    #[cfg(not(unix))]
+    if config.grpc.socket_path.is_some() {
+        tracing::warn!("Unix sockets are not supported on this platform, `socket_path` is ignored");
+    }
+    #[cfg(unix)]
+    let activated = crate::activation::take_listener();
+    #[cfg(not(unix))]
+    let activated: Option<i32> = None;
+    let grpc_addr = match (activated, config.grpc.socket_path.clone()) {
+        #[cfg(unix)]
+        (Some(fd), _) => grpc::ListenAddress::Activated(fd),
+        #[cfg(unix)]
+        (None, Some(path)) => grpc::ListenAddress::Unix(path),
+        _ => grpc::ListenAddress::Tcp(
+            format!("{}:{}", config.grpc.host, config.grpc.port)
+                .parse()
+                .context(error::ParseSockAddr)?,
+        ),
+    };
+
+    let capture_settings = settings::capture_settings(&config)?;
+    let bindings = config
+        .hotkeys
+        .iter()
+        .map(|hotkey| Binding::new(hotkey).context(error::ParseHotkey))
+        .collect::<Result<Vec<_>, _>>()?;
+    let webhooks = config
+        .webhooks
+        .iter()
+        .map(|webhook| Webhook::new(webhook).context(error::CreateWebhook))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let snippet_manager = {
+        let snippets = config
+            .snippets
+            .iter()
+            .map(|snippet| snippet.load().context(error::LoadSnippet))
+            .collect::<Result<Vec<_>, _>>()?;
+        tracing::info!("{} snippet(s) loaded", snippets.len());
+        Arc::new(Mutex::new(SnippetManager::with_snippets(snippets)))
+    };
 
     let (clipboard_manager, history_manager) = {
         let file_path = config.history_file_path;
+        let passphrase = match config.history_encryption {
+            Some(ref encryption) => {
+                Some(encryption.read_passphrase().context(error::ReadPassphrase)?)
+            }
+            None => None,
+        };
 
         tracing::info!("History file path: {:?}", file_path);
-        let history_manager =
-            HistoryManager::new(&file_path).context(error::CreateHistoryManager)?;
+        let mut history_manager = HistoryManager::new(
+            &file_path,
+            config.history_driver,
+            passphrase.as_deref(),
+            config.history_compression.threshold(),
+        )
+        .context(error::CreateHistoryManager)?;
 
-        tracing::info!("Load history from {:?}", history_manager.path());
-        let history_clips = history_manager.load().context(error::LoadHistoryManager)?;
-        let clip_count = history_clips.len();
-        tracing::info!("{} clip(s) loaded", clip_count);
+        if let Some(ref primary_file_path) = config.primary_history_file_path {
+            tracing::info!("Primary history file path: {:?}", primary_file_path);
+            history_manager
+                .open_primary(
+                    primary_file_path,
+                    config.history_driver,
+                    passphrase.as_deref(),
+                    config.history_compression.threshold(),
+                )
+                .context(error::CreateHistoryManager)?;
+        }
+
+        if config.history_journal.enable {
+            let journal_path = HistoryJournal::path(&file_path);
+            tracing::info!("History journal path: {:?}", journal_path);
+            let recovered =
+                history_manager.open_journal(&journal_path).context(error::CreateHistoryManager)?;
+            if recovered > 0 {
+                tracing::info!("{} clip(s) are recovered from journal", recovered);
+            }
+        }
 
         tracing::info!("Initialize ClipboardManager with capacity {}", config.max_history);
         let mut clipboard_manager = ClipboardManager::with_capacity(config.max_history);
+        clipboard_manager.set_primary_capacity(config.max_primary_history);
+        clipboard_manager.set_backend(config.monitor.backend);
+        clipboard_manager.set_deduplicate(config.deduplicate);
+        clipboard_manager.set_ranking(config.ranking);
+        clipboard_manager.set_max_bytes(config.max_history_bytes);
+        clipboard_manager.set_max_age(config.max_age);
+        clipboard_manager.set_paste_options(config.paste.clone().into());
+        clipboard_manager
+            .set_trash_limits(config.trash.max_clips, Duration::from_secs(config.trash.ttl));
+        clipboard_manager
+            .set_displays(config.monitor.displays.clone(), config.monitor.mirror_promotions);
+        if let Some(ref spill) = config.spill {
+            let path = spill.directory(&file_path);
+            tracing::info!("Spill clips larger than {} bytes to {:?}", spill.threshold, path);
+            // spilled clips are encrypted like the history
+            let cipher = history_manager.spill_cipher();
+            let store = SpillStore::open_sealed(&path, spill.threshold, cipher)
+                .context(error::OpenSpillDirectory { path })?;
+            clipboard_manager.set_spill(Some(store));
+        }
+        clipboard_manager.set_memory_budget(config.memory_budget);
+
+        tracing::info!("Load history from {:?}", history_manager.path());
+        let capacity = clipboard_manager.total_capacity();
+        let previews = if config.lazy_history {
+            history_manager.load_previews(capacity).context(error::LoadHistoryManager)?
+        } else {
+            None
+        };
+        let history_clips = match previews {
+            Some(clips) => {
+                tracing::info!("Load previews of history, bodies are loaded on demand");
+                clips
+            }
+            None => history_manager.load_latest(capacity).context(error::LoadHistoryManager)?,
+        };
+        let clip_count = history_clips.len();
+        tracing::info!("{} clip(s) loaded", clip_count);
 
         tracing::info!("Import {} clip(s) into ClipboardManager", clip_count);
         clipboard_manager.import(&history_clips);
 
-        (Arc::new(Mutex::new(clipboard_manager)), Arc::new(Mutex::new(history_manager)))
+        if config.lazy_history {
+            let loader = history_manager.body_loader().context(error::LoadHistoryManager)?;
+            clipboard_manager.set_body_loader(Some(Box::new(loader)));
+        }
+        let history_manager = Arc::new(Mutex::new(history_manager));
+
+        (Arc::new(RwLock::new(clipboard_manager)), history_manager)
     };
 
     let (ctl_tx, mut ctl_rx) = mpsc::unbounded_channel::<CtlMessage>();
 
     let _signal_join = signal::start(ctl_tx.clone());
+    if config.watch_config {
+        if let Err(err) = config_watcher::start(config_file, ctl_tx.clone()) {
+            tracing::warn!("Configuration is not reloaded on changes, error: {}", err);
+        }
+    }
 
+    let backend = config.monitor.backend;
+    let pause_on_lock = config.monitor.pause_on_lock;
     let monitor_opts = config.monitor.into();
     let clipboard_monitor = {
         let monitor = ClipboardMonitor::new(monitor_opts).context(error::CreateClipboardMonitor)?;
         Arc::new(Mutex::new(monitor))
     };
 
+    let metrics = Arc::new(Metrics::new());
+    #[cfg(feature = "dbus")]
+    let notification_worker =
+        config.notification.as_ref().map(|config| notification::start(Notifier::new(config)));
+    #[cfg(not(feature = "dbus"))]
+    if config.notification.is_some() {
+        tracing::warn!("clipcatd is built without the dbus feature, [notification] is ignored");
+    }
+    let webhook_worker = if webhooks.is_empty() {
+        None
+    } else {
+        tracing::info!("{} webhook(s) configured", webhooks.len());
+        Some(webhook::start(webhooks, clipboard_manager.clone()))
+    };
+    #[cfg(feature = "mqtt")]
+    let mqtt_worker = match config.mqtt {
+        Some(ref mqtt) => Some(mqtt::start(mqtt)?),
+        None => None,
+    };
+    #[cfg(not(feature = "mqtt"))]
+    if config.mqtt.is_some() {
+        tracing::warn!("clipcatd is built without the mqtt feature, [mqtt] is ignored");
+    }
+    let subscribers = clipboard::Subscribers {
+        #[cfg(feature = "dbus")]
+        notification_tx: notification_worker
+            .as_ref()
+            .map(|(notification_tx, _)| notification_tx.clone()),
+        webhook_tx: webhook_worker.as_ref().map(|(webhook_tx, _)| webhook_tx.clone()),
+        #[cfg(feature = "mqtt")]
+        mqtt_tx: mqtt_worker.as_ref().map(|(mqtt_tx, _)| mqtt_tx.clone()),
+    };
+    let (history_tx, history_join) = history_writer::start(
+        history_manager.clone(),
+        clipboard_manager.read().await.subscribe_pasted_once(),
+    );
     let (clip_tx, clipboard_join) = clipboard::start(
         ctl_tx.clone(),
         clipboard_monitor.clone(),
         clipboard_manager.clone(),
+        history_manager.clone(),
+        history_tx.clone(),
+        metrics.clone(),
+        capture_settings,
+        subscribers,
+    );
+    let settings = Arc::new(settings::Settings::new(
+        active_config,
+        clipboard_manager.clone(),
+        clip_tx.clone(),
+        log_level,
+    ));
+    let backup_worker = match config.backup {
+        // snapshots are plaintext exports, they would leak an encrypted history
+        Some(_) if config.history_encryption.is_some() => {
+            tracing::warn!("Snapshots are disabled because history_encryption is set");
+            None
+        }
+        Some(config) => {
+            tracing::info!("Write snapshots of history to {:?}", config.directory);
+            let backup_manager = BackupManager::new(&config.directory, config.keep);
+            let interval = Duration::from_secs(config.interval.max(1));
+            Some(backup::start(clipboard_manager.clone(), backup_manager, interval))
+        }
+        None => None,
+    };
+    let journal_worker = if config.history_journal.enable {
+        let interval = Duration::from_secs(config.history_journal.compact_interval.max(1));
+        Some(journal::start(history_manager.clone(), interval))
+    } else {
+        None
+    };
+    #[cfg(feature = "dbus")]
+    let dbus_worker = if config.dbus.enable {
+        Some(dbus::start(clipboard_monitor.clone(), clipboard_manager.clone(), config.dbus.klipper))
+    } else {
+        None
+    };
+    #[cfg(not(feature = "dbus"))]
+    if config.dbus.enable {
+        tracing::warn!("clipcatd is built without the dbus feature, [dbus] is ignored");
+    }
+    #[cfg(feature = "http_gateway")]
+    let http_token = config.grpc.token.clone();
+    #[cfg(feature = "http_gateway")]
+    let http_worker = config.http.map(|http| {
+        let addr = std::net::SocketAddr::new(http.host, http.port);
+        http::start(
+            addr,
+            http_token,
+            http.allow_origins,
+            clipboard_monitor.clone(),
+            clipboard_manager.clone(),
+            metrics.clone(),
+        )
+    });
+    #[cfg(not(feature = "http_gateway"))]
+    if config.http.is_some() {
+        tracing::warn!("clipcatd is built without the http_gateway feature, [http] is ignored");
+    }
+    let metrics_token = config.grpc.token.clone();
+    let metrics_worker = config.metrics.as_ref().map(|endpoint| {
+        let addr = std::net::SocketAddr::new(endpoint.host, endpoint.port);
+        metrics::start(addr, metrics_token, metrics.clone(), clipboard_manager.clone())
+    });
+    #[cfg(target_os = "linux")]
+    let hotkey_worker = if bindings.is_empty() {
+        None
+    } else {
+        tracing::info!("{} hotkey(s) configured", bindings.len());
+        Some(hotkey::start(bindings, backend, clipboard_monitor.clone(), clipboard_manager.clone()))
+    };
+    #[cfg(not(target_os = "linux"))]
+    if !bindings.is_empty() {
+        tracing::warn!(
+            "Hotkeys are not supported by clipboard backend {}, [[hotkeys]] is ignored",
+            backend.to_string()
+        );
+    }
+    #[cfg(all(target_os = "linux", feature = "dbus"))]
+    let lock_worker =
+        if pause_on_lock { Some(lock::start(clipboard_monitor.clone())) } else { None };
+    #[cfg(all(target_os = "linux", not(feature = "dbus")))]
+    if pause_on_lock {
+        tracing::warn!("clipcatd is built without the dbus feature, pause_on_lock is ignored");
+    }
+    #[cfg(not(target_os = "linux"))]
+    if pause_on_lock {
+        tracing::info!("Screen locks are only detected with logind, pause_on_lock is ignored");
+    }
+    #[cfg(feature = "tray")]
+    let tray_worker = config
+        .tray
+        .map(|tray| tray::start(&tray, clipboard_monitor.clone(), clipboard_manager.clone()));
+    #[cfg(not(feature = "tray"))]
+    if config.tray.is_some() {
+        tracing::warn!("clipcatd is built without the tray feature, [tray] is ignored");
+    }
+    let (grpc_tx, grpc_join) = grpc::start(
+        grpc_addr,
+        grpc_opts,
+        clipboard_monitor,
+        clipboard_manager.clone(),
         history_manager,
+        snippet_manager,
+        settings.clone(),
+        metrics,
     );
-    let (grpc_tx, grpc_join) = grpc::start(grpc_addr, clipboard_monitor, clipboard_manager);
 
     while let Some(msg) = ctl_rx.recv().await {
         match msg {
+            CtlMessage::Reload => settings.reload(&config_loader).await,
             CtlMessage::Shutdown => {
                 let _ = clip_tx.send(clipboard::Message::Shutdown);
+                let _ = history_tx.send(history_writer::Message::Shutdown).await;
                 let _ = grpc_tx.send(grpc::Message::Shutdown);
+                if let Some((ref backup_tx, _)) = backup_worker {
+                    let _ = backup_tx.send(backup::Message::Shutdown);
+                }
+                if let Some((ref journal_tx, _)) = journal_worker {
+                    let _ = journal_tx.send(journal::Message::Shutdown);
+                }
+                #[cfg(feature = "http_gateway")]
+                if let Some((ref http_tx, _)) = http_worker {
+                    let _ = http_tx.send(http::Message::Shutdown);
+                }
+                #[cfg(feature = "dbus")]
+                if let Some((ref dbus_tx, _)) = dbus_worker {
+                    let _ = dbus_tx.send(dbus::Message::Shutdown);
+                }
+                if let Some((ref metrics_tx, _)) = metrics_worker {
+                    let _ = metrics_tx.send(metrics::Message::Shutdown);
+                }
+                #[cfg(target_os = "linux")]
+                if let Some((ref hotkey_tx, _)) = hotkey_worker {
+                    let _ = hotkey_tx.send(hotkey::Message::Shutdown);
+                }
+                #[cfg(all(target_os = "linux", feature = "dbus"))]
+                if let Some((ref lock_tx, _)) = lock_worker {
+                    let _ = lock_tx.send(lock::Message::Shutdown);
+                }
+                #[cfg(feature = "tray")]
+                if let Some((ref tray_tx, _)) = tray_worker {
+                    let _ = tray_tx.send(tray::Message::Shutdown);
+                }
+                #[cfg(feature = "dbus")]
+                if let Some((ref notification_tx, _)) = notification_worker {
+                    let _ = notification_tx.send(notification::Message::Shutdown);
+                }
+                if let Some((ref webhook_tx, _)) = webhook_worker {
+                    let _ = webhook_tx.send(webhook::Message::Shutdown);
+                }
+                #[cfg(feature = "mqtt")]
+                if let Some((ref mqtt_tx, _)) = mqtt_worker {
+                    let _ = mqtt_tx.send(mqtt::Message::Shutdown);
+                }
                 break;
             }
         }
@@ -79,8 +429,73 @@ pub async fn start(config: Config) -> Result<(), Error> {
     let _ = grpc_join.await;
     tracing::info!("gRPC service is down");
 
+    let _ = history_join.await;
+    tracing::info!("HistoryWriter is down");
+
     let _ = clipboard_join.await;
     tracing::info!("ClipboardWorker is down");
 
+    if let Some((_, backup_join)) = backup_worker {
+        let _ = backup_join.await;
+        tracing::info!("BackupWorker is down");
+    }
+
+    if let Some((_, journal_join)) = journal_worker {
+        let _ = journal_join.await;
+        tracing::info!("JournalWorker is down");
+    }
+
+    #[cfg(feature = "http_gateway")]
+    if let Some((_, http_join)) = http_worker {
+        let _ = http_join.await;
+        tracing::info!("HTTP gateway is down");
+    }
+
+    #[cfg(feature = "dbus")]
+    if let Some((_, dbus_join)) = dbus_worker {
+        let _ = dbus_join.await;
+        tracing::info!("D-Bus service is down");
+    }
+
+    if let Some((_, metrics_join)) = metrics_worker {
+        let _ = metrics_join.await;
+        tracing::info!("Metrics endpoint is down");
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some((_, hotkey_join)) = hotkey_worker {
+        let _ = hotkey_join.await;
+        tracing::info!("HotkeyWorker is down");
+    }
+
+    #[cfg(all(target_os = "linux", feature = "dbus"))]
+    if let Some((_, lock_join)) = lock_worker {
+        let _ = lock_join.await;
+        tracing::info!("LockWorker is down");
+    }
+
+    #[cfg(feature = "tray")]
+    if let Some((_, tray_join)) = tray_worker {
+        let _ = tray_join.await;
+        tracing::info!("TrayWorker is down");
+    }
+
+    #[cfg(feature = "dbus")]
+    if let Some((_, notification_join)) = notification_worker {
+        let _ = notification_join.await;
+        tracing::info!("NotificationWorker is down");
+    }
+
+    if let Some((_, webhook_join)) = webhook_worker {
+        let _ = webhook_join.await;
+        tracing::info!("WebhookWorker is down");
+    }
+
+    #[cfg(feature = "mqtt")]
+    if let Some((_, mqtt_join)) = mqtt_worker {
+        let _ = mqtt_join.await;
+        tracing::info!("MqttWorker is down");
+    }
+
     Ok(())
 }
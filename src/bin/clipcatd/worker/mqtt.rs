@@ -0,0 +1,132 @@
+use std::{path::Path, time::Duration};
+
+use futures::FutureExt;
+use rumqttc::{AsyncClient, EventLoop, Key, MqttOptions, QoS, TlsConfiguration, Transport};
+use snafu::ResultExt;
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use clipcat::ClipboardData;
+
+use crate::{
+    config::{self, WebhookEvent},
+    error::{self, Error},
+    webhook,
+};
+
+// Requests which are queued for the event loop.
+const REQUEST_CHANNEL_CAPACITY: usize = 16;
+
+// How long to wait before reconnecting to the broker.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+pub enum Message {
+    Publish(ClipboardData),
+    Shutdown,
+}
+
+pub type MessageSender = mpsc::UnboundedSender<Message>;
+pub type MessageReceiver = mpsc::UnboundedReceiver<Message>;
+
+pub struct MqttWorker {
+    msg_rx: MessageReceiver,
+    client: AsyncClient,
+    event_loop: EventLoop,
+    topic: String,
+    qos: QoS,
+    retain: bool,
+}
+
+impl MqttWorker {
+    async fn run(mut self) {
+        loop {
+            futures::select! {
+                msg = self.msg_rx.recv().fuse() => match msg {
+                    Some(Message::Publish(clip)) => self.publish(&clip),
+                    Some(Message::Shutdown) | None => {
+                        tracing::info!("MqttWorker is shutting down gracefully");
+                        break;
+                    }
+                },
+                // the connection is only driven while the event loop is polled,
+                // it reconnects on the next poll after an error
+                event = self.event_loop.poll().fuse() => if let Err(err) = event {
+                    tracing::warn!("MQTT connection failed, error: {}", err);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                },
+            }
+        }
+
+        if self.client.try_disconnect().is_ok() {
+            let _ = tokio::time::timeout(Duration::from_secs(1), self.event_loop.poll()).await;
+        }
+    }
+
+    fn publish(&self, clip: &ClipboardData) {
+        let payload = webhook::payload(WebhookEvent::Captured, clip);
+        if let Err(err) = self.client.try_publish(&self.topic, self.qos, self.retain, payload) {
+            tracing::warn!("Could not publish clip to MQTT, error: {}", err);
+        }
+    }
+}
+
+fn options(config: &config::Mqtt) -> Result<MqttOptions, Error> {
+    fn read(filename: &Path) -> Result<Vec<u8>, Error> {
+        std::fs::read(filename).context(error::ReadTlsFile { filename: filename.to_path_buf() })
+    }
+
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    if let Some(ref username) = config.username {
+        options.set_credentials(username.as_str(), config.password.as_deref().unwrap_or(""));
+    }
+
+    if let Some(ref tls) = config.tls {
+        let client_auth = match (&tls.client_certificate, &tls.client_key) {
+            (Some(certificate), Some(key)) => {
+                let key = read(key)?;
+                let key = if String::from_utf8_lossy(&key).contains("BEGIN EC PRIVATE KEY") {
+                    Key::ECC(key)
+                } else {
+                    Key::RSA(key)
+                };
+                Some((read(certificate)?, key))
+            }
+            _ => None,
+        };
+        let ca = read(&tls.ca_certificate)?;
+        options.set_transport(Transport::Tls(TlsConfiguration::Simple {
+            ca,
+            alpn: None,
+            client_auth,
+        }));
+    }
+    Ok(options)
+}
+
+fn qos(qos: u8) -> QoS {
+    match qos {
+        0 => QoS::AtMostOnce,
+        2 => QoS::ExactlyOnce,
+        _ => QoS::AtLeastOnce,
+    }
+}
+
+pub fn start(config: &config::Mqtt) -> Result<(MessageSender, JoinHandle<()>), Error> {
+    let (client, event_loop) = AsyncClient::new(options(config)?, REQUEST_CHANNEL_CAPACITY);
+    tracing::info!(
+        "Publish clips to MQTT topic {} on {}:{}",
+        config.topic,
+        config.host,
+        config.port
+    );
+
+    let (tx, msg_rx) = mpsc::unbounded_channel::<Message>();
+    let worker = MqttWorker {
+        msg_rx,
+        client,
+        event_loop,
+        topic: config.topic.clone(),
+        qos: qos(config.qos),
+        retain: config.retain,
+    };
+    Ok((tx, tokio::spawn(worker.run())))
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use tokio::{sync::mpsc, task::JoinHandle};
+use zvariant::Value;
+
+use clipcat::ClipboardData;
+
+use crate::notification::{Notification, Notifier};
+
+const NOTIFICATIONS_SERVICE: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const APP_NAME: &str = "clipcat";
+
+pub enum Message {
+    Notify(Box<ClipboardData>),
+    Shutdown,
+}
+
+pub type MessageSender = mpsc::UnboundedSender<Message>;
+pub type MessageReceiver = mpsc::UnboundedReceiver<Message>;
+
+pub struct NotificationWorker {
+    msg_rx: MessageReceiver,
+    notifier: Notifier,
+
+    // connected on the first notification and after errors
+    connection: Option<zbus::Connection>,
+
+    // each notification replaces the previous one instead of piling up
+    last_id: u32,
+}
+
+impl NotificationWorker {
+    async fn run(mut self) {
+        while let Some(msg) = self.msg_rx.recv().await {
+            match msg {
+                Message::Notify(clip) => {
+                    if let Some(notification) = self.notifier.notification(&clip) {
+                        tokio::task::block_in_place(|| self.notify(&notification));
+                    }
+                }
+                Message::Shutdown => {
+                    tracing::info!("NotificationWorker is shutting down gracefully");
+                    break;
+                }
+            }
+        }
+    }
+
+    fn notify(&mut self, notification: &Notification) {
+        match self.send(notification) {
+            Ok(id) => self.last_id = id,
+            Err(err) => {
+                tracing::warn!("Failed to send notification, error: {}", err);
+                self.connection = None;
+            }
+        }
+    }
+
+    fn send(&mut self, notification: &Notification) -> Result<u32, zbus::Error> {
+        if self.connection.is_none() {
+            self.connection = Some(zbus::Connection::new_session()?);
+        }
+        let connection = self.connection.as_ref().expect("connection must be some");
+
+        let mut hints: HashMap<&str, Value> = HashMap::new();
+        hints.insert("transient", Value::Bool(true));
+        hints.insert("urgency", Value::U8(0));
+        let actions: Vec<&str> = Vec::new();
+
+        let reply = connection.call_method(
+            Some(NOTIFICATIONS_SERVICE),
+            NOTIFICATIONS_PATH,
+            Some(NOTIFICATIONS_SERVICE),
+            "Notify",
+            &(
+                APP_NAME,
+                self.last_id,
+                "edit-paste",
+                notification.summary.as_str(),
+                notification.body.as_str(),
+                actions,
+                hints,
+                self.notifier.timeout() as i32,
+            ),
+        )?;
+        Ok(reply.body::<u32>()?)
+    }
+}
+
+pub fn start(notifier: Notifier) -> (MessageSender, JoinHandle<()>) {
+    let (tx, msg_rx) = mpsc::unbounded_channel::<Message>();
+    let worker = NotificationWorker { msg_rx, notifier, connection: None, last_id: 0 };
+    (tx, tokio::spawn(worker.run()))
+}
@@ -0,0 +1,379 @@
+use std::{sync::Arc, time::Duration};
+
+use snafu::ResultExt;
+use tokio::sync::{Mutex, RwLock};
+use tracing_subscriber::filter::LevelFilter;
+
+use clipcat::{grpc::RuntimeSettings, ClipboardManager};
+
+#[cfg(feature = "plugins")]
+use crate::plugin::PluginRunner;
+#[cfg(feature = "scripts")]
+use crate::script::ScriptRunner;
+use crate::{
+    capture::CaptureFilter,
+    config::Config,
+    error::{self, Error},
+    secret::SecretDetector,
+    worker::{clipboard, ConfigLoader, LogLevelHandle},
+};
+
+// Top-level settings which are applied while clipcatd runs, `sync` of
+// `[monitor]` is one as well.
+const RUNTIME_SETTINGS: &[&str] = &[
+    "max_history",
+    "max_primary_history",
+    "max_history_bytes",
+    "memory_budget",
+    "max_age",
+    "deduplicate",
+    "ranking",
+    "log_level",
+    "capture",
+    "paste",
+    "trash",
+];
+
+/// The configuration which is in effect, it is changed by reloading the
+/// configuration file and by the settings service.
+pub struct Settings {
+    config: Mutex<Config>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+    clip_tx: clipboard::MessageSender,
+    log_level: LogLevelHandle,
+}
+
+impl Settings {
+    pub fn new(
+        config: Config,
+        clipboard_manager: Arc<RwLock<ClipboardManager>>,
+        clip_tx: clipboard::MessageSender,
+        log_level: LogLevelHandle,
+    ) -> Settings {
+        Settings { config: Mutex::new(config), clipboard_manager, clip_tx, log_level }
+    }
+
+    /// Applies the history and trash limits, the capture settings and the log
+    /// level of the configuration file. The other settings are wired into
+    /// workers at start up, changes of them are reported as requiring a
+    /// restart.
+    pub async fn reload(&self, config_loader: &ConfigLoader) {
+        tracing::info!("Reload configuration");
+        let config = match config_loader() {
+            Ok(config) => config,
+            Err(err) => {
+                tracing::warn!("Could not reload configuration, error: {}", err);
+                return;
+            }
+        };
+
+        let mut active_config = self.config.lock().await;
+        let restart = restart_sections(&active_config, &config);
+        if restart.is_empty() && applied_settings(&active_config, &config).is_empty() {
+            tracing::info!("Configuration is unchanged");
+            return;
+        }
+        if let Err(err) = self.apply(&mut active_config, config).await {
+            // the previous settings are kept if any of the new ones are invalid
+            tracing::warn!("Could not reload configuration, error: {}", err);
+            return;
+        }
+        if !restart.is_empty() {
+            tracing::warn!("Changes of {} take effect after a restart", restart.join(", "));
+        }
+    }
+
+    async fn apply(&self, active_config: &mut Config, config: Config) -> Result<(), Error> {
+        let applied = applied_settings(active_config, &config);
+        if applied.is_empty() {
+            *active_config = config;
+            return Ok(());
+        }
+
+        let capture_settings = capture_settings(&config)?;
+
+        if config.log_level != active_config.log_level {
+            match self.log_level.reload(LevelFilter::from_level(config.log_level)) {
+                Ok(()) => tracing::info!("Log level is {}", config.log_level),
+                Err(err) => tracing::warn!("Could not change log level, error: {}", err),
+            }
+        }
+
+        {
+            let mut cm = self.clipboard_manager.write().await;
+            cm.set_capacity(config.max_history);
+            cm.set_primary_capacity(config.max_primary_history);
+            cm.set_max_bytes(config.max_history_bytes);
+            cm.set_memory_budget(config.memory_budget);
+            cm.set_max_age(config.max_age);
+            cm.set_deduplicate(config.deduplicate);
+            cm.set_ranking(config.ranking);
+            cm.set_paste_options(config.paste.clone().into());
+            cm.set_trash_limits(config.trash.max_clips, Duration::from_secs(config.trash.ttl));
+        }
+        let _ = self.clip_tx.send(clipboard::Message::Reconfigure(Box::new(capture_settings)));
+
+        tracing::info!("Changes of {} are applied", applied.join(", "));
+        *active_config = config;
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl RuntimeSettings for Settings {
+    async fn get(&self, key: &str) -> Result<String, String> {
+        let config = self.config.lock().await;
+        let tree = config_tree(&config)?;
+        let path = setting_path(key);
+        let mut value = &tree;
+        for name in &path {
+            value = value.get(name).ok_or_else(|| format!("No setting {}", key))?;
+        }
+        Ok(format_value(value))
+    }
+
+    async fn set(&self, key: &str, value: &str) -> Result<String, String> {
+        let path = setting_path(key);
+        if !is_runtime_setting(&path) {
+            return Err(format!(
+                "{} can not be changed while clipcatd runs, it takes effect after a restart",
+                key
+            ));
+        }
+
+        let mut active_config = self.config.lock().await;
+        let mut tree = config_tree(&active_config)?;
+        set_value(&mut tree, &path, parse_value(value))
+            .ok_or_else(|| format!("No setting {}", key))?;
+        let config: Config = tree.try_into().map_err(|err| err.to_string())?;
+
+        tracing::info!("Setting {} is changed to {}", key, value);
+        self.apply(&mut active_config, config).await.map_err(|err| err.to_string())?;
+
+        let tree = config_tree(&active_config)?;
+        let mut value = &tree;
+        for name in &path {
+            value = match value.get(name) {
+                Some(value) => value,
+                // an unset optional setting
+                None => return Ok(String::new()),
+            };
+        }
+        Ok(format_value(value))
+    }
+}
+
+pub fn capture_settings(config: &Config) -> Result<clipboard::CaptureSettings, Error> {
+    let capture_filter = CaptureFilter::new(&config.capture).context(error::CompileCaptureRegex)?;
+    let secret_detector = SecretDetector::new(&config.capture.secrets);
+    #[cfg(feature = "scripts")]
+    let script_runner = ScriptRunner::load(&config.capture.scripts).context(error::LoadScript)?;
+    #[cfg(feature = "scripts")]
+    if !script_runner.is_empty() {
+        tracing::info!("{} script(s) loaded", config.capture.scripts.len());
+    }
+    #[cfg(not(feature = "scripts"))]
+    if !config.capture.scripts.is_empty() {
+        tracing::warn!("clipcatd is built without the scripts feature, capture.scripts is ignored");
+    }
+    #[cfg(feature = "plugins")]
+    let plugin_runner = PluginRunner::load(&config.capture.plugins).context(error::LoadPlugin)?;
+    #[cfg(feature = "plugins")]
+    if !plugin_runner.is_empty() {
+        tracing::info!("{} plugin(s) loaded", config.capture.plugins.len());
+    }
+    #[cfg(not(feature = "plugins"))]
+    if !config.capture.plugins.is_empty() {
+        tracing::warn!("clipcatd is built without the plugins feature, capture.plugins is ignored");
+    }
+    Ok(clipboard::CaptureSettings {
+        capture_filter,
+        secret_detector,
+        #[cfg(feature = "scripts")]
+        script_runner,
+        #[cfg(feature = "plugins")]
+        plugin_runner,
+        sensitive_timeout: Duration::from_secs(config.capture.sensitive_timeout),
+        sync: config.monitor.sync,
+    })
+}
+
+// The configuration as TOML, without the token of the gRPC service.
+fn config_tree(config: &Config) -> Result<toml::Value, String> {
+    let mut tree = toml::Value::try_from(config).map_err(|err| err.to_string())?;
+    if let Some(grpc) = tree.get_mut("grpc").and_then(toml::Value::as_table_mut) {
+        grpc.remove("token");
+    }
+    Ok(tree)
+}
+
+// Settings are named like in the configuration file, with `-` for `_` as on
+// the command line.
+fn setting_path(key: &str) -> Vec<String> {
+    key.split('.').filter(|name| !name.is_empty()).map(|name| name.replace('-', "_")).collect()
+}
+
+fn is_runtime_setting(path: &[String]) -> bool {
+    match path {
+        [name, ..] if RUNTIME_SETTINGS.contains(&name.as_str()) => true,
+        [section, name] => section == "monitor" && name == "sync",
+        _ => false,
+    }
+}
+
+// Values are TOML, text which is not valid TOML is taken as a string so that
+// e.g. `debug` needs no quotes.
+fn parse_value(value: &str) -> Option<toml::Value> {
+    if value.is_empty() {
+        return None;
+    }
+    match format!("value = {}", value).parse::<toml::Value>() {
+        Ok(toml::Value::Table(mut table)) => table.remove("value"),
+        _ => Some(toml::Value::String(value.to_owned())),
+    }
+}
+
+// Sets the setting at `path` to `value`, or removes it so that its default
+// applies if `value` is `None`. Returns `None` if a section of `path` does not
+// exist.
+fn set_value(tree: &mut toml::Value, path: &[String], value: Option<toml::Value>) -> Option<()> {
+    let (name, sections) = path.split_last()?;
+    let mut table = tree.as_table_mut()?;
+    for section in sections {
+        table = table.get_mut(section)?.as_table_mut()?;
+    }
+    match value {
+        Some(value) => table.insert(name.clone(), value),
+        None => table.remove(name),
+    };
+    Some(())
+}
+
+fn format_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::Table(_) => toml::to_string_pretty(value).unwrap_or_default(),
+        value => value.to_string(),
+    }
+}
+
+// Names of the settings which differ between `old` and `new` and are applied
+// while clipcatd runs.
+fn applied_settings(old: &Config, new: &Config) -> Vec<&'static str> {
+    vec![
+        ("max_history", old.max_history != new.max_history),
+        ("max_primary_history", old.max_primary_history != new.max_primary_history),
+        ("max_history_bytes", old.max_history_bytes != new.max_history_bytes),
+        ("memory_budget", old.memory_budget != new.memory_budget),
+        ("max_age", old.max_age != new.max_age),
+        ("deduplicate", old.deduplicate != new.deduplicate),
+        ("ranking", old.ranking != new.ranking),
+        ("log_level", old.log_level != new.log_level),
+        ("monitor.sync", old.monitor.sync != new.monitor.sync),
+        ("capture", old.capture != new.capture),
+        ("paste", old.paste != new.paste),
+        ("trash", old.trash != new.trash),
+    ]
+    .into_iter()
+    .filter(|&(_, changed)| changed)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+// Names of the settings which differ between `old` and `new` and are only
+// applied when clipcatd starts.
+fn restart_sections(old: &Config, new: &Config) -> Vec<&'static str> {
+    // `sync` of `[monitor]` is a capture setting
+    let monitor = |config: &Config| {
+        let mut monitor = config.monitor.clone();
+        monitor.sync = Default::default();
+        monitor
+    };
+
+    vec![
+        ("monitor", monitor(old) != monitor(new)),
+        ("grpc", old.grpc != new.grpc),
+        (
+            "history files",
+            old.history_file_path != new.history_file_path
+                || old.primary_history_file_path != new.primary_history_file_path
+                || old.history_driver != new.history_driver
+                || old.lazy_history != new.lazy_history
+                || old.history_encryption != new.history_encryption
+                || old.history_compression != new.history_compression
+                || old.history_journal != new.history_journal,
+        ),
+        ("snippets", old.snippets != new.snippets),
+        ("backup", old.backup != new.backup),
+        ("spill", old.spill != new.spill),
+        ("notification", old.notification != new.notification),
+        ("dbus", old.dbus != new.dbus),
+        ("http", old.http != new.http),
+        ("metrics", old.metrics != new.metrics),
+        ("log", old.log != new.log),
+        ("otlp", old.otlp != new.otlp),
+        ("tray", old.tray != new.tray),
+        ("hotkeys", old.hotkeys != new.hotkeys),
+        ("webhooks", old.webhooks != new.webhooks),
+        ("mqtt", old.mqtt != new.mqtt),
+        ("watch_config", old.watch_config != new.watch_config),
+    ]
+    .into_iter()
+    .filter(|&(_, changed)| changed)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::Config,
+        worker::settings::{
+            applied_settings, config_tree, is_runtime_setting, parse_value, restart_sections,
+            set_value, setting_path,
+        },
+    };
+
+    #[test]
+    fn test_restart_sections() {
+        let old = Config::default();
+        let mut new = old.clone();
+        assert!(applied_settings(&old, &new).is_empty());
+
+        new.max_history = 10;
+        new.log_level = tracing::Level::DEBUG;
+        new.monitor.sync = crate::config::SelectionSync::Off;
+        assert!(restart_sections(&old, &new).is_empty());
+        assert_eq!(applied_settings(&old, &new), vec!["max_history", "log_level", "monitor.sync"]);
+
+        new.grpc.port += 1;
+        new.monitor.enable_primary = false;
+        assert_eq!(restart_sections(&old, &new), vec!["monitor", "grpc"]);
+    }
+
+    #[test]
+    fn test_set_value() {
+        assert!(is_runtime_setting(&setting_path("max-history")));
+        assert!(is_runtime_setting(&setting_path("capture.deny-regex")));
+        assert!(is_runtime_setting(&setting_path("monitor.sync")));
+        assert!(!is_runtime_setting(&setting_path("monitor.backend")));
+        assert!(!is_runtime_setting(&setting_path("grpc.port")));
+
+        let mut tree = config_tree(&Config::default()).unwrap();
+        set_value(&mut tree, &setting_path("max-history"), parse_value("500")).unwrap();
+        set_value(&mut tree, &setting_path("log-level"), parse_value("debug")).unwrap();
+        set_value(&mut tree, &setting_path("capture.deny_regex"), parse_value(r"['^\d{6}$']"))
+            .unwrap();
+        assert!(set_value(&mut tree, &setting_path("nothing.max_history"), None).is_none());
+
+        let config: Config = tree.try_into().unwrap();
+        assert_eq!(config.max_history, 500);
+        assert_eq!(config.log_level, tracing::Level::DEBUG);
+        assert_eq!(config.capture.deny_regex, vec![r"^\d{6}$".to_owned()]);
+
+        let mut tree = config_tree(&config).unwrap();
+        set_value(&mut tree, &setting_path("max-age"), parse_value("14d")).unwrap();
+        let config: Config = tree.try_into().unwrap();
+        assert_eq!(config.max_age, Some(std::time::Duration::from_secs(14 * 24 * 60 * 60)));
+        assert_eq!(config_tree(&config).unwrap().get("max_age"), parse_value("'2w'").as_ref());
+    }
+}
@@ -1,10 +1,10 @@
 use std::sync::atomic;
 
+#[cfg(unix)]
 use futures::FutureExt;
-use tokio::{
-    signal::unix::{signal, SignalKind},
-    sync::mpsc,
-};
+#[cfg(unix)]
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::mpsc;
 
 use crate::{
     worker::{CtlMessage, CtlMessageSender},
@@ -15,23 +15,71 @@ pub struct SignalWorker {
     ctl_tx: CtlMessageSender,
 }
 
+enum Received {
+    Shutdown,
+    #[cfg(unix)]
+    Reload,
+}
+
+#[cfg(unix)]
+struct Signals {
+    term_signal: Signal,
+    int_signal: Signal,
+    hup_signal: Signal,
+}
+
+#[cfg(unix)]
+impl Signals {
+    fn new() -> Signals {
+        Signals {
+            term_signal: signal(SignalKind::terminate()).unwrap(),
+            int_signal: signal(SignalKind::interrupt()).unwrap(),
+            hup_signal: signal(SignalKind::hangup()).unwrap(),
+        }
+    }
+
+    async fn recv(&mut self) -> Received {
+        futures::select! {
+            _ = self.term_signal.recv().fuse() => tracing::info!("SIGTERM received!"),
+            _ = self.int_signal.recv().fuse() => tracing::info!("SIGINT received!"),
+            _ = self.hup_signal.recv().fuse() => {
+                tracing::info!("SIGHUP received!");
+                return Received::Reload;
+            }
+        }
+        Received::Shutdown
+    }
+}
+
+// Windows only has Ctrl+C for console applications.
+#[cfg(not(unix))]
+struct Signals;
+
+#[cfg(not(unix))]
+impl Signals {
+    fn new() -> Signals { Signals }
+
+    async fn recv(&mut self) -> Received {
+        match tokio::signal::ctrl_c().await {
+            Ok(()) => tracing::info!("Ctrl+C received!"),
+            // without a console the daemon is ended by terminating its process
+            Err(_) => futures::future::pending().await,
+        }
+        Received::Shutdown
+    }
+}
+
 impl SignalWorker {
-    #[allow(clippy::never_loop)]
     async fn run(self) {
-        let mut term_signal = signal(SignalKind::terminate()).unwrap();
-        let mut int_signal = signal(SignalKind::interrupt()).unwrap();
+        let mut signals = Signals::new();
 
         loop {
-            loop {
-                futures::select! {
-                    _ = term_signal.recv().fuse() => {
-                        tracing::info!("SIGTERM received!");
-                        break;
-                    },
-                    _ = int_signal.recv().fuse() => {
-                        tracing::info!("SIGINT received!");
-                        break;
-                    },
+            match signals.recv().await {
+                Received::Shutdown => {}
+                #[cfg(unix)]
+                Received::Reload => {
+                    let _ = self.ctl_tx.send(CtlMessage::Reload);
+                    continue;
                 }
             }
 
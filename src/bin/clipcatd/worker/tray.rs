@@ -0,0 +1,236 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::FutureExt;
+use ksni::{
+    menu::{CheckmarkItem, StandardItem, SubMenu},
+    MenuItem, Status, ToolTip, TrayService,
+};
+use tokio::{
+    runtime::Handle,
+    sync::{broadcast::error::RecvError, mpsc, Mutex, RwLock},
+    task::JoinHandle,
+};
+
+use clipcat::{ClipboardManager, ClipboardMonitor, ClipboardType};
+
+use crate::config;
+
+// Changes are collected for this long before the tray is updated, so the tray
+// is not rebuilt for every step of a selection in the primary clipboard.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+pub enum Message {
+    Shutdown,
+}
+
+pub type MessageSender = mpsc::UnboundedSender<Message>;
+pub type MessageReceiver = mpsc::UnboundedReceiver<Message>;
+
+/// What the tray shows, the most recent clips as (id, preview).
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+struct TrayState {
+    paused: bool,
+    clips: Vec<(u64, String)>,
+}
+
+/// The StatusNotifierItem of clipcatd. ksni serves it on its own thread, the
+/// menu calls into the runtime for the managers.
+struct ClipcatTray {
+    runtime: Handle,
+    clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+    state: TrayState,
+}
+
+impl ksni::Tray for ClipcatTray {
+    fn id(&self) -> String { clipcat::DAEMON_PROGRAM_NAME.to_owned() }
+
+    fn title(&self) -> String { clipcat::DAEMON_PROGRAM_NAME.to_owned() }
+
+    fn icon_name(&self) -> String {
+        if self.state.paused {
+            "media-playback-pause".to_owned()
+        } else {
+            "edit-paste".to_owned()
+        }
+    }
+
+    fn status(&self) -> Status {
+        if self.state.paused {
+            Status::Passive
+        } else {
+            Status::Active
+        }
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        let description = if self.state.paused {
+            "Recording is paused".to_owned()
+        } else {
+            format!("Recording, {} recent clip(s)", self.state.clips.len())
+        };
+        ToolTip { title: self.title(), description, ..Default::default() }
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let recent: Vec<MenuItem<Self>> = self
+            .state
+            .clips
+            .iter()
+            .map(|&(id, ref preview)| {
+                StandardItem {
+                    label: preview.clone(),
+                    activate: Box::new(move |tray: &mut Self| tray.promote(id)),
+                    ..Default::default()
+                }
+                .into()
+            })
+            .collect();
+
+        vec![
+            SubMenu {
+                label: "Recent clips".to_owned(),
+                enabled: !recent.is_empty(),
+                submenu: recent,
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            CheckmarkItem {
+                label: "Pause recording".to_owned(),
+                checked: self.state.paused,
+                activate: Box::new(|tray: &mut Self| tray.toggle_monitor()),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Clear history".to_owned(),
+                icon_name: "edit-clear-all".to_owned(),
+                activate: Box::new(|tray: &mut Self| tray.clear()),
+                ..Default::default()
+            }
+            .into(),
+        ]
+    }
+}
+
+impl ClipcatTray {
+    fn promote(&mut self, id: u64) {
+        let result = self.runtime.block_on(ClipboardManager::promote(
+            &self.clipboard_manager,
+            id,
+            ClipboardType::Clipboard,
+        ));
+        if let Err(err) = result {
+            tracing::warn!("Could not promote clip {:016x} from tray, error: {}", id, err);
+        }
+    }
+
+    fn toggle_monitor(&mut self) {
+        let paused = self.runtime.block_on(async {
+            let mut monitor = self.clipboard_monitor.lock().await;
+            monitor.toggle();
+            !monitor.is_running()
+        });
+        self.state.paused = paused;
+    }
+
+    fn clear(&mut self) {
+        self.runtime.block_on(async { self.clipboard_manager.write().await.clear() });
+        self.state.clips.clear();
+    }
+}
+
+pub struct TrayWorker {
+    msg_rx: MessageReceiver,
+    clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+    recent_clips: usize,
+    preview_length: usize,
+}
+
+impl TrayWorker {
+    async fn run(mut self) {
+        let state = self.load_state().await;
+        let tray = ClipcatTray {
+            runtime: Handle::current(),
+            clipboard_monitor: self.clipboard_monitor.clone(),
+            clipboard_manager: self.clipboard_manager.clone(),
+            state: state.clone(),
+        };
+        let service = TrayService::new(tray);
+        let handle = service.handle();
+        service.spawn();
+        tracing::info!("Tray icon is shown");
+
+        let mut events = self.clipboard_monitor.lock().await.subscribe();
+        let mut removals = self.clipboard_manager.write().await.subscribe_removals();
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        let mut shown = state;
+        let mut dirty = false;
+
+        loop {
+            futures::select! {
+                msg = self.msg_rx.recv().fuse() => match msg {
+                    Some(Message::Shutdown) | None => {
+                        tracing::info!("TrayWorker is shutting down gracefully");
+                        break;
+                    }
+                },
+                event = events.recv().fuse() => match event {
+                    Ok(_) | Err(RecvError::Lagged(_)) => dirty = true,
+                    Err(RecvError::Closed) => break,
+                },
+                clip = removals.recv().fuse() => match clip {
+                    Ok(_) | Err(RecvError::Lagged(_)) => dirty = true,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = interval.tick().fuse() => {
+                    // monitoring may be paused over gRPC or D-Bus as well
+                    let paused = !self.clipboard_monitor.lock().await.is_running();
+                    if dirty || paused != shown.paused {
+                        let state = self.load_state().await;
+                        if state != shown {
+                            let update = state.clone();
+                            handle.update(move |tray| tray.state = update);
+                            shown = state;
+                        }
+                        dirty = false;
+                    }
+                },
+            }
+        }
+
+        handle.shutdown();
+    }
+
+    async fn load_state(&self) -> TrayState {
+        let paused = !self.clipboard_monitor.lock().await.is_running();
+        let clips = self
+            .clipboard_manager
+            .lock()
+            .await
+            .list_tagged(&[])
+            .into_iter()
+            .take(self.recent_clips)
+            .map(|clip| (clip.id, clip.printable_data(Some(self.preview_length))))
+            .collect();
+        TrayState { paused, clips }
+    }
+}
+
+pub fn start(
+    config: &config::Tray,
+    clipboard_monitor: Arc<Mutex<ClipboardMonitor>>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+) -> (MessageSender, JoinHandle<()>) {
+    let (tx, msg_rx) = mpsc::unbounded_channel::<Message>();
+    let worker = TrayWorker {
+        msg_rx,
+        clipboard_monitor,
+        clipboard_manager,
+        recent_clips: config.recent_clips,
+        preview_length: config.preview_length,
+    };
+    (tx, tokio::spawn(worker.run()))
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use futures::FutureExt;
+use hyper::{client::HttpConnector, header, Body, Client};
+use hyper_rustls::HttpsConnector;
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc, RwLock},
+    task::JoinHandle,
+};
+
+use clipcat::{ClipboardData, ClipboardManager};
+
+use crate::{
+    config::WebhookEvent,
+    webhook::{Request, Webhook, SIGNATURE_HEADER},
+};
+
+pub enum Message {
+    Captured(Box<ClipboardData>),
+    Shutdown,
+}
+
+pub type MessageSender = mpsc::UnboundedSender<Message>;
+pub type MessageReceiver = mpsc::UnboundedReceiver<Message>;
+
+pub struct WebhookWorker {
+    msg_rx: MessageReceiver,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+    webhooks: Vec<Webhook>,
+    client: Client<HttpsConnector<HttpConnector>>,
+}
+
+impl WebhookWorker {
+    async fn run(mut self) {
+        let mut removals = self.clipboard_manager.write().await.subscribe_removals();
+
+        loop {
+            futures::select! {
+                msg = self.msg_rx.recv().fuse() => match msg {
+                    Some(Message::Captured(clip)) => self.dispatch(WebhookEvent::Captured, &clip),
+                    Some(Message::Shutdown) | None => {
+                        tracing::info!("WebhookWorker is shutting down gracefully");
+                        break;
+                    }
+                },
+                clip = removals.recv().fuse() => match clip {
+                    Ok(clip) => self.dispatch(WebhookEvent::Removed, &clip),
+                    Err(RecvError::Lagged(n)) => {
+                        tracing::warn!("WebhookWorker lagged behind, {} removals are skipped", n);
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+    }
+
+    // Each request is sent on its own task, so a slow endpoint never holds up
+    // the other webhooks.
+    fn dispatch(&self, event: WebhookEvent, clip: &ClipboardData) {
+        for webhook in &self.webhooks {
+            if let Some(request) = webhook.request(event, clip) {
+                let client = self.client.clone();
+                let timeout = webhook.timeout();
+                tokio::spawn(async move {
+                    let url = request.url.clone();
+                    match tokio::time::timeout(timeout, post(&client, request)).await {
+                        Ok(Ok(status)) if status.is_success() => {}
+                        Ok(Ok(status)) => {
+                            tracing::warn!("Webhook {} responded with {}", url, status)
+                        }
+                        Ok(Err(err)) => tracing::warn!("Could not call webhook {}: {}", url, err),
+                        Err(_) => tracing::warn!("Webhook {} timed out", url),
+                    }
+                });
+            }
+        }
+    }
+}
+
+async fn post(
+    client: &Client<HttpsConnector<HttpConnector>>,
+    request: Request,
+) -> Result<hyper::StatusCode, hyper::Error> {
+    let mut builder = hyper::Request::post(request.url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::USER_AGENT, concat!("clipcat/", env!("CARGO_PKG_VERSION")));
+    if let Some(signature) = request.signature {
+        builder = builder.header(SIGNATURE_HEADER, signature);
+    }
+    let request = builder.body(Body::from(request.body)).expect("webhook request is valid");
+    Ok(client.request(request).await?.status())
+}
+
+pub fn start(
+    webhooks: Vec<Webhook>,
+    clipboard_manager: Arc<RwLock<ClipboardManager>>,
+) -> (MessageSender, JoinHandle<()>) {
+    let (tx, msg_rx) = mpsc::unbounded_channel::<Message>();
+    let client = Client::builder().build(HttpsConnector::with_native_roots());
+    let worker = WebhookWorker { msg_rx, clipboard_manager, webhooks, client };
+    (tx, tokio::spawn(worker.run()))
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+
+/// Tag which clipcatd attaches to clips holding a color value.
+pub const COLOR_TAG: &str = "color";
+
+/// A color in sRGB, `alpha` is between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: f32,
+}
+
+impl Color {
+    /// Parses `text` if it is a single CSS color value, `#rgb`, `#rgba`,
+    /// `#rrggbb`, `#rrggbbaa`, `rgb()`, `rgba()`, `hsl()` or `hsla()`.
+    pub fn parse(text: &str) -> Option<Color> {
+        let text = text.trim();
+        if let Some(hex) = text.strip_prefix('#') {
+            return parse_hex(hex);
+        }
+
+        let open = text.find('(')?;
+        let args = text[open + 1..].strip_suffix(')')?;
+        let args: Vec<_> = args
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .collect();
+        let alpha = match args.len() {
+            3 => 1.0,
+            4 => parse_alpha(args[3])?,
+            _ => return None,
+        };
+
+        match text[..open].to_lowercase().as_str() {
+            "rgb" | "rgba" => Some(Color {
+                red: parse_channel(args[0])?,
+                green: parse_channel(args[1])?,
+                blue: parse_channel(args[2])?,
+                alpha,
+            }),
+            "hsl" | "hsla" => {
+                let hue = args[0].trim_end_matches("deg").parse::<f32>().ok()?;
+                let saturation = parse_percentage(args[1])?;
+                let lightness = parse_percentage(args[2])?;
+                let (red, green, blue) = hsl_to_rgb(hue, saturation, lightness);
+                Some(Color { red, green, blue, alpha })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns `#rrggbb`, or `#rrggbbaa` if the color is translucent.
+    pub fn to_hex(&self) -> String {
+        let hex = format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue);
+        if self.alpha < 1.0 {
+            format!("{}{:02x}", hex, (self.alpha * 255.0).round() as u8)
+        } else {
+            hex
+        }
+    }
+
+    /// Returns a swatch of the color for terminals with true color support.
+    pub fn swatch(&self) -> String {
+        format!("\x1b[48;2;{};{};{}m   \x1b[0m", self.red, self.green, self.blue)
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(&self.to_hex()) }
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..=i], 16).ok().map(|d| d * 17);
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let (red, green, blue, alpha) = match hex.len() {
+        3 => (digit(0)?, digit(1)?, digit(2)?, 255),
+        4 => (digit(0)?, digit(1)?, digit(2)?, digit(3)?),
+        6 => (byte(0)?, byte(2)?, byte(4)?, 255),
+        8 => (byte(0)?, byte(2)?, byte(4)?, byte(6)?),
+        _ => return None,
+    };
+    Some(Color { red, green, blue, alpha: f32::from(alpha) / 255.0 })
+}
+
+fn parse_channel(arg: &str) -> Option<u8> {
+    let value = match arg.strip_suffix('%') {
+        Some(percentage) => percentage.parse::<f32>().ok()? * 2.55,
+        None => arg.parse::<f32>().ok()?,
+    };
+    if (0.0..=255.0).contains(&value) {
+        Some(value.round() as u8)
+    } else {
+        None
+    }
+}
+
+fn parse_alpha(arg: &str) -> Option<f32> {
+    let value = match arg.strip_suffix('%') {
+        Some(percentage) => percentage.parse::<f32>().ok()? / 100.0,
+        None => arg.parse::<f32>().ok()?,
+    };
+    if (0.0..=1.0).contains(&value) {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+fn parse_percentage(arg: &str) -> Option<f32> {
+    let value = arg.strip_suffix('%').unwrap_or(arg).parse::<f32>().ok()?;
+    if (0.0..=100.0).contains(&value) {
+        Some(value / 100.0)
+    } else {
+        None
+    }
+}
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (u8, u8, u8) {
+    let hue = hue.rem_euclid(360.0) / 30.0;
+    let a = saturation * lightness.min(1.0 - lightness);
+    let channel = |n: f32| {
+        let k = (n + hue) % 12.0;
+        let value = lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0);
+        (value * 255.0).round() as u8
+    };
+    (channel(0.0), channel(8.0), channel(4.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Color;
+
+    fn hex(text: &str) -> Option<String> { Color::parse(text).map(|color| color.to_hex()) }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(hex("#ff8800").as_deref(), Some("#ff8800"));
+        assert_eq!(hex(" #F80\n").as_deref(), Some("#ff8800"));
+        assert_eq!(hex("#f808").as_deref(), Some("#ff880088"));
+        assert_eq!(hex("#ff880080").as_deref(), Some("#ff880080"));
+        assert_eq!(hex("#ff88000"), None);
+        assert_eq!(hex("#gg8800"), None);
+        assert_eq!(hex("ff8800"), None);
+    }
+
+    #[test]
+    fn test_parse_functions() {
+        assert_eq!(hex("rgb(255, 136, 0)").as_deref(), Some("#ff8800"));
+        assert_eq!(hex("RGBA(255 136 0 / 50%)").as_deref(), Some("#ff880080"));
+        assert_eq!(hex("rgb(100%, 0%, 0%)").as_deref(), Some("#ff0000"));
+        assert_eq!(hex("hsl(120, 100%, 50%)").as_deref(), Some("#00ff00"));
+        assert_eq!(hex("hsla(240deg, 100%, 25%, 0.5)").as_deref(), Some("#00008080"));
+        assert_eq!(hex("hsl(0, 0%, 100%)").as_deref(), Some("#ffffff"));
+        assert_eq!(hex("rgb(256, 0, 0)"), None);
+        assert_eq!(hex("rgb(1, 2)"), None);
+        assert_eq!(hex("rgb(1, 2, 3); color: red"), None);
+        assert_eq!(hex("lab(50 20 30)"), None);
+        assert_eq!(hex("clipcat"), None);
+    }
+}
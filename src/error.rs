@@ -4,11 +4,88 @@ pub enum ClipboardError {
     #[snafu(display("Could not spawn tokio task, error: {}", source))]
     SpawnBlockingTask { source: tokio::task::JoinError },
 
-    #[cfg(feature = "monitor")]
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
     #[snafu(display("Could not initialize X11 clipboard, error: {}", source))]
     InitializeX11Clipboard { source: x11_clipboard::error::Error },
 
-    #[cfg(feature = "monitor")]
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
+    #[snafu(display("Could not load X11 clipboard, error: {}", source))]
+    LoadX11Clipboard { source: x11_clipboard::error::Error },
+
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
     #[snafu(display("Could not paste to X11 clipboard, error: {}", source))]
     PasteToX11Clipboard { source: x11_clipboard::error::Error },
+
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
+    #[snafu(display("Could not become the owner of X11 selection"))]
+    AcquireX11Selection,
+
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
+    #[snafu(display("Another clipboard manager owns CLIPBOARD_MANAGER"))]
+    ClipboardManagerExists,
+
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
+    // errors of wl-clipboard-rs only implement `failure::Fail`
+    #[snafu(display("Could not load Wayland clipboard, error: {}", error))]
+    LoadWaylandClipboard { error: String },
+
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
+    #[snafu(display("Could not read Wayland clipboard, error: {}", source))]
+    ReadWaylandClipboard { source: std::io::Error },
+
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
+    #[snafu(display("Could not paste to Wayland clipboard, error: {}", error))]
+    PasteToWaylandClipboard { error: String },
+
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
+    #[snafu(display("Could not connect to Wayland compositor, error: {}", source))]
+    ConnectWayland { source: wayland_client::ConnectError },
+
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
+    #[snafu(display("Wayland compositor does not support wlr-data-control, error: {}", source))]
+    BindDataControl { source: wayland_client::GlobalError },
+
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
+    #[snafu(display("Wayland seat {} is not found", seat))]
+    WaylandSeatNotFound { seat: String },
+
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
+    #[snafu(display("Could not receive Wayland events, error: {}", source))]
+    DispatchWaylandEvents { source: std::io::Error },
+
+    #[cfg(all(feature = "monitor", target_os = "linux"))]
+    #[snafu(display("Wayland selection events are no longer received"))]
+    WaylandSelectionClosed,
+
+    #[cfg(all(feature = "monitor", target_os = "macos"))]
+    #[snafu(display("Could not write {} to macOS pasteboard", mime))]
+    WriteMacOSPasteboard { mime: String },
+
+    #[cfg(all(feature = "monitor", windows))]
+    #[snafu(display("Could not listen to Windows clipboard, error: {}", source))]
+    ListenWindowsClipboard { source: std::io::Error },
+
+    #[cfg(all(feature = "monitor", windows))]
+    #[snafu(display("Could not open Windows clipboard, error: {}", source))]
+    OpenWindowsClipboard { source: std::io::Error },
+
+    #[cfg(all(feature = "monitor", windows))]
+    #[snafu(display("Could not write {} to Windows clipboard, error: {}", mime, source))]
+    WriteWindowsClipboard { mime: String, source: std::io::Error },
+
+    #[cfg(feature = "monitor")]
+    #[snafu(display("Clipboard backend {} is not supported on this platform", backend))]
+    UnsupportedBackend { backend: String },
+
+    #[cfg(feature = "monitor")]
+    #[snafu(display("Clipboard backend {} has no displays but the default one", backend))]
+    DisplaysUnsupported { backend: String },
+
+    #[cfg(feature = "monitor")]
+    #[snafu(display("Could not run paste command {}, error: {}", command, source))]
+    RunPasteCommand { command: String, source: std::io::Error },
+
+    #[cfg(feature = "monitor")]
+    #[snafu(display("Paste command {} exited with {}", command, status))]
+    PasteCommandFailed { command: String, status: String },
 }
@@ -1,35 +1,138 @@
 use std::{
     cmp::{Ord, Ordering, PartialEq, PartialOrd},
+    collections::BTreeMap,
     hash::{Hash, Hasher},
+    time::SystemTime,
 };
 
-use crate::{ClipboardData, ClipboardType};
+use crate::{mime, ClipboardData, ClipboardType};
 
 #[derive(Debug, Clone, Eq)]
 pub struct ClipboardEvent {
     pub data: String,
     pub clipboard_type: ClipboardType,
+    pub mime: String,
+    pub binary: Vec<u8>,
+    pub targets: BTreeMap<String, Vec<u8>>,
+
+    /// Window class, window title or Wayland app ID of the application which
+    /// owns the selection, if it is known.
+    pub application: Option<String>,
+
+    /// The owner asked clipboard managers not to store the clip, e.g. with
+    /// `x-kde-passwordManagerHint`.
+    pub sensitive: bool,
+
+    /// X11 display or Wayland seat the clip was captured from, `None` for the
+    /// default display.
+    pub display: Option<String>,
+}
+
+/// Summary of a clipboard event which is delivered to subscribers of the
+/// daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardNotification {
+    pub id: u64,
+    pub clipboard_type: ClipboardType,
+    pub preview: String,
+    pub mime: String,
+    pub timestamp: SystemTime,
 }
 
 impl ClipboardEvent {
     pub fn new_clipboard<S: ToString>(data: S) -> ClipboardEvent {
-        ClipboardEvent { data: data.to_string(), clipboard_type: ClipboardType::Clipboard }
+        ClipboardEvent {
+            data: data.to_string(),
+            clipboard_type: ClipboardType::Clipboard,
+            mime: mime::TEXT_PLAIN.to_owned(),
+            binary: Vec::new(),
+            targets: BTreeMap::new(),
+            application: None,
+            sensitive: false,
+            display: None,
+        }
     }
 
     pub fn new_primary<S: ToString>(data: S) -> ClipboardEvent {
-        ClipboardEvent { data: data.to_string(), clipboard_type: ClipboardType::Primary }
+        ClipboardEvent {
+            data: data.to_string(),
+            clipboard_type: ClipboardType::Primary,
+            mime: mime::TEXT_PLAIN.to_owned(),
+            binary: Vec::new(),
+            targets: BTreeMap::new(),
+            application: None,
+            sensitive: false,
+            display: None,
+        }
+    }
+
+    /// Creates an event from raw bytes, text MIME types are decoded as UTF-8.
+    pub fn from_bytes(mime: &str, bytes: Vec<u8>, clipboard_type: ClipboardType) -> ClipboardEvent {
+        if mime::is_text(mime) {
+            let data = String::from_utf8_lossy(&bytes).into_owned();
+            return ClipboardEvent {
+                data,
+                clipboard_type,
+                mime: mime::TEXT_PLAIN.to_owned(),
+                binary: Vec::new(),
+                targets: BTreeMap::new(),
+                application: None,
+                sensitive: false,
+                display: None,
+            };
+        }
+
+        ClipboardEvent {
+            data: String::new(),
+            clipboard_type,
+            mime: mime.to_owned(),
+            binary: bytes,
+            targets: BTreeMap::new(),
+            application: None,
+            sensitive: false,
+            display: None,
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.data.is_empty() && self.binary.is_empty() }
+}
+
+impl ClipboardNotification {
+    /// Maximum length of `preview` in characters.
+    pub const PREVIEW_LENGTH: usize = 100;
+}
+
+impl From<&ClipboardData> for ClipboardNotification {
+    fn from(clip: &ClipboardData) -> ClipboardNotification {
+        ClipboardNotification {
+            id: clip.id,
+            clipboard_type: clip.clipboard_type,
+            preview: clip.printable_data(Some(Self::PREVIEW_LENGTH)),
+            mime: clip.mime.clone(),
+            timestamp: clip.timestamp,
+        }
     }
 }
 
 impl From<ClipboardData> for ClipboardEvent {
     fn from(data: ClipboardData) -> ClipboardEvent {
-        let ClipboardData { data, clipboard_type, .. } = data;
-        ClipboardEvent { data, clipboard_type }
+        let ClipboardData { data, clipboard_type, mime, binary, targets, .. } = data;
+        ClipboardEvent {
+            data,
+            clipboard_type,
+            mime,
+            binary,
+            targets,
+            application: None,
+            sensitive: false,
+            display: None,
+        }
     }
 }
 
 impl PartialEq for ClipboardEvent {
-    fn eq(&self, other: &Self) -> bool { self.data == other.data }
+    fn eq(&self, other: &Self) -> bool { self.data == other.data && self.binary == other.binary }
 }
 
 impl PartialOrd for ClipboardEvent {
@@ -41,5 +144,8 @@ impl Ord for ClipboardEvent {
 }
 
 impl Hash for ClipboardEvent {
-    fn hash<H: Hasher>(&self, state: &mut H) { self.data.hash(state); }
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.data.hash(state);
+        self.binary.hash(state);
+    }
 }
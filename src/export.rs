@@ -0,0 +1,232 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::{ClipboardData, ClipboardType};
+
+/// Version of the export format which is written by `Export::new`.
+pub const EXPORT_VERSION: u32 = 1;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, Snafu)]
+pub enum ImportError {
+    #[snafu(display("Unsupported export version {}", version))]
+    UnsupportedVersion { version: u32 },
+
+    #[snafu(display("Invalid base64 data in clip {}", index))]
+    InvalidBase64 { index: usize },
+}
+
+/// History exported by `clipcatctl export`, it is written as JSON.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Export {
+    pub version: u32,
+    pub clips: Vec<ExportedClip>,
+}
+
+/// A clip in an export, binary data is encoded as base64.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ExportedClip {
+    /// `Clipboard` or `Primary`.
+    pub clipboard_type: ClipboardType,
+
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+
+    /// Milliseconds since the Unix epoch when the clip was captured first,
+    /// exports of older versions do not carry it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+
+    pub mime: String,
+
+    /// Content of text clips.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub data: String,
+
+    /// Content of binary clips.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub binary: String,
+
+    /// Alternative representations of the clip keyed by MIME type.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub targets: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub pinned: bool,
+
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+
+    #[serde(default)]
+    pub use_count: u64,
+
+    /// Window class of the application the clip was copied from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub application: Option<String>,
+}
+
+impl Export {
+    pub fn new(clips: &[ClipboardData]) -> Export {
+        Export { version: EXPORT_VERSION, clips: clips.iter().map(Into::into).collect() }
+    }
+
+    /// Converts the export back into clips, the IDs of the clips are computed
+    /// from their content.
+    pub fn into_clips(self) -> Result<Vec<ClipboardData>, ImportError> {
+        if self.version != EXPORT_VERSION {
+            return Err(ImportError::UnsupportedVersion { version: self.version });
+        }
+
+        self.clips
+            .into_iter()
+            .enumerate()
+            .map(|(index, clip)| clip.into_clip().ok_or(ImportError::InvalidBase64 { index }))
+            .collect()
+    }
+}
+
+impl ExportedClip {
+    fn into_clip(self) -> Option<ClipboardData> {
+        let mut clip = if crate::mime::is_text(&self.mime) {
+            ClipboardData::new(&self.data, self.clipboard_type)
+        } else {
+            ClipboardData::from_bytes(&self.mime, decode_base64(&self.binary)?, self.clipboard_type)
+        };
+
+        clip.timestamp = UNIX_EPOCH + Duration::from_millis(self.timestamp);
+        clip.created_at =
+            UNIX_EPOCH + Duration::from_millis(self.created_at.unwrap_or(self.timestamp));
+        clip.targets = self
+            .targets
+            .iter()
+            .map(|(mime, value)| Some((mime.clone(), decode_base64(value)?)))
+            .collect::<Option<_>>()?;
+        clip.pinned = self.pinned;
+        clip.tags = self.tags;
+        clip.use_count = self.use_count;
+        clip.application = self.application;
+        Some(clip)
+    }
+}
+
+impl From<&ClipboardData> for ExportedClip {
+    fn from(clip: &ClipboardData) -> ExportedClip {
+        let millis = |time: std::time::SystemTime| {
+            time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_millis() as u64)
+        };
+
+        ExportedClip {
+            clipboard_type: clip.clipboard_type,
+            timestamp: millis(clip.timestamp),
+            created_at: Some(millis(clip.created_at)),
+            mime: clip.mime.clone(),
+            data: clip.data.clone(),
+            binary: encode_base64(&clip.binary),
+            targets: clip
+                .targets
+                .iter()
+                .map(|(mime, value)| (mime.clone(), encode_base64(value)))
+                .collect(),
+            pinned: clip.pinned,
+            tags: clip.tags.clone(),
+            use_count: clip.use_count,
+            application: clip.application.clone(),
+        }
+    }
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+    let mut data = Vec::with_capacity(encoded.len() * 3 / 4);
+    for chunk in encoded.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+
+        let mut n = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let value = BASE64_ALPHABET.iter().position(|a| a == c)? as u32;
+            n |= value << (18 - 6 * i);
+        }
+        let bytes = n.to_be_bytes();
+        data.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::{
+        export::{decode_base64, encode_base64, Export, ImportError},
+        mime, ClipboardData, ClipboardType,
+    };
+
+    #[test]
+    fn test_base64() {
+        let cases: &[(&[u8], &str)] =
+            &[(b"", ""), (b"f", "Zg=="), (b"fo", "Zm8="), (b"foo", "Zm9v"), (b"foob", "Zm9vYg==")];
+        for (data, encoded) in cases {
+            assert_eq!(encode_base64(data), *encoded);
+            assert_eq!(decode_base64(encoded).as_deref(), Some(*data));
+        }
+        assert_eq!(decode_base64("Zm9v!"), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut text = ClipboardData::new_primary("clipcat");
+        text.timestamp = UNIX_EPOCH + Duration::from_millis(1_600_000_000_123);
+        text.created_at = UNIX_EPOCH + Duration::from_millis(1_500_000_000_000);
+        text.targets.insert(mime::TEXT_HTML.to_owned(), b"<b>clipcat</b>".to_vec());
+        text.pinned = true;
+        text.tags.insert("work".to_owned());
+        text.use_count = 3;
+        let mut image = ClipboardData::from_bytes(
+            mime::IMAGE_PNG,
+            vec![0x89, 0, 1, 2],
+            ClipboardType::Clipboard,
+        );
+        image.timestamp = UNIX_EPOCH + Duration::from_millis(1_600_000_000_456);
+
+        let export = Export::new(&[text.clone(), image.clone()]);
+        assert_eq!(export.clips[1].binary, "iQABAg==");
+        let clips = export.into_clips().unwrap();
+        assert_eq!(clips, vec![text.clone(), image.clone()]);
+        assert_eq!(clips[0].id, text.id);
+        assert_eq!(clips[0].clipboard_type, ClipboardType::Primary);
+        assert_eq!(clips[0].timestamp, text.timestamp);
+        assert_eq!(clips[0].created_at, text.created_at);
+        assert_eq!(clips[0].targets, text.targets);
+        assert_eq!(clips[0].tags, text.tags);
+        assert!(clips[0].pinned);
+        assert_eq!(clips[0].use_count, 3);
+        assert_eq!(clips[1].id, image.id);
+        assert_eq!(clips[1].mime, mime::IMAGE_PNG);
+        assert_eq!(clips[1].timestamp, image.timestamp);
+
+        let export = Export { version: 0, clips: Vec::new() };
+        assert!(matches!(export.into_clips(), Err(ImportError::UnsupportedVersion { version: 0 })));
+    }
+}
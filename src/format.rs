@@ -0,0 +1,322 @@
+use std::{fmt, iter::Peekable, str::Chars};
+
+const INDENT: &str = "  ";
+
+/// Structured text formats which are pretty-printed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Format {
+    Json,
+    Xml,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Json => f.write_str("JSON"),
+            Format::Xml => f.write_str("XML"),
+        }
+    }
+}
+
+/// Detects JSON or XML in `text` and pretty-prints it, returns `None` if `text`
+/// is neither. Keys and attributes keep their order.
+pub fn pretty_print(text: &str) -> Option<(Format, String)> {
+    let text = text.trim();
+    if text.starts_with('{') || text.starts_with('[') {
+        pretty_print_json(text).map(|json| (Format::Json, json))
+    } else if text.starts_with('<') {
+        pretty_print_xml(text).map(|xml| (Format::Xml, xml))
+    } else {
+        None
+    }
+}
+
+fn pretty_print_json(text: &str) -> Option<String> {
+    let mut printer = JsonPrinter { chars: text.chars().peekable(), output: String::new() };
+    printer.value(0)?;
+    printer.skip_whitespace();
+    if printer.chars.next().is_some() {
+        return None;
+    }
+    Some(printer.output)
+}
+
+struct JsonPrinter<'a> {
+    chars: Peekable<Chars<'a>>,
+    output: String,
+}
+
+impl JsonPrinter<'_> {
+    fn value(&mut self, depth: usize) -> Option<()> {
+        self.skip_whitespace();
+        match *self.chars.peek()? {
+            '{' => self.container(depth, '}', true),
+            '[' => self.container(depth, ']', false),
+            '"' => self.string(),
+            't' => self.literal("true"),
+            'f' => self.literal("false"),
+            'n' => self.literal("null"),
+            c if c == '-' || c.is_ascii_digit() => self.number(),
+            _ => None,
+        }
+    }
+
+    fn container(&mut self, depth: usize, close: char, is_object: bool) -> Option<()> {
+        self.output.push(self.chars.next()?);
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&close) {
+            self.output.push(self.chars.next()?);
+            return Some(());
+        }
+
+        loop {
+            self.newline(depth + 1);
+            if is_object {
+                self.skip_whitespace();
+                if self.chars.peek() != Some(&'"') {
+                    return None;
+                }
+                self.string()?;
+                self.skip_whitespace();
+                if self.chars.next()? != ':' {
+                    return None;
+                }
+                self.output.push_str(": ");
+            }
+            self.value(depth + 1)?;
+            self.skip_whitespace();
+            match self.chars.next()? {
+                ',' => self.output.push(','),
+                c if c == close => break,
+                _ => return None,
+            }
+        }
+        self.newline(depth);
+        self.output.push(close);
+        Some(())
+    }
+
+    fn string(&mut self) -> Option<()> {
+        self.output.push(self.chars.next()?);
+        loop {
+            let c = self.chars.next()?;
+            self.output.push(c);
+            match c {
+                '"' => return Some(()),
+                '\\' => self.output.push(self.chars.next()?),
+                c if c.is_control() => return None,
+                _ => {}
+            }
+        }
+    }
+
+    fn literal(&mut self, literal: &str) -> Option<()> {
+        for expected in literal.chars() {
+            if self.chars.next()? != expected {
+                return None;
+            }
+        }
+        self.output.push_str(literal);
+        Some(())
+    }
+
+    fn number(&mut self) -> Option<()> {
+        let mut number = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !(c.is_ascii_digit() || "+-.eE".contains(c)) {
+                break;
+            }
+            number.push(c);
+            self.chars.next();
+        }
+        number.parse::<f64>().ok()?;
+        self.output.push_str(&number);
+        Some(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn newline(&mut self, depth: usize) {
+        self.output.push('\n');
+        for _ in 0..depth {
+            self.output.push_str(INDENT);
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum XmlToken<'a> {
+    Open { name: &'a str, tag: &'a str },
+    Close { name: &'a str, tag: &'a str },
+    Empty(&'a str),
+    Text(&'a str),
+}
+
+fn pretty_print_xml(text: &str) -> Option<String> {
+    let tokens = tokenize_xml(text)?;
+    let mut output = String::new();
+    let mut open: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let depth = open.len();
+        match tokens[i] {
+            XmlToken::Open { name, tag } => {
+                indent(&mut output, depth);
+                output.push_str(tag);
+                // elements which only hold text stay on one line
+                if let (
+                    Some(XmlToken::Text(text)),
+                    Some(XmlToken::Close { name: close, tag: end }),
+                ) = (tokens.get(i + 1), tokens.get(i + 2))
+                {
+                    if *close == name {
+                        output.push_str(text.trim());
+                        output.push_str(end);
+                        output.push('\n');
+                        i += 3;
+                        continue;
+                    }
+                }
+                output.push('\n');
+                open.push(name);
+            }
+            XmlToken::Close { name, tag } => {
+                if open.pop()? != name {
+                    return None;
+                }
+                indent(&mut output, depth - 1);
+                output.push_str(tag);
+                output.push('\n');
+            }
+            XmlToken::Empty(tag) => {
+                indent(&mut output, depth);
+                output.push_str(tag);
+                output.push('\n');
+            }
+            XmlToken::Text(text) => {
+                // text outside of the root element is not XML
+                if depth == 0 {
+                    return None;
+                }
+                indent(&mut output, depth);
+                output.push_str(text.trim());
+                output.push('\n');
+            }
+        }
+        i += 1;
+    }
+
+    if !open.is_empty() {
+        return None;
+    }
+    output.pop();
+    Some(output)
+}
+
+// Splits `text` into tags and text, whitespace between tags is dropped.
+fn tokenize_xml(text: &str) -> Option<Vec<XmlToken<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        if !rest.starts_with('<') {
+            let end = rest.find('<').unwrap_or(rest.len());
+            if !rest[..end].trim().is_empty() {
+                tokens.push(XmlToken::Text(&rest[..end]));
+            }
+            rest = &rest[end..];
+            continue;
+        }
+
+        let terminator = if rest.starts_with("<!--") {
+            "-->"
+        } else if rest.starts_with("<![CDATA[") {
+            "]]>"
+        } else if rest.starts_with("<?") {
+            "?>"
+        } else {
+            ">"
+        };
+        let end = rest.find(terminator)? + terminator.len();
+        let tag = &rest[..end];
+        rest = &rest[end..];
+
+        if tag.starts_with("<!") || tag.starts_with("<?") {
+            tokens.push(XmlToken::Empty(tag));
+        } else if let Some(inner) = tag.strip_prefix("</") {
+            let name = inner.trim_end_matches('>').trim();
+            if !is_name(name) {
+                return None;
+            }
+            tokens.push(XmlToken::Close { name, tag });
+        } else if tag.ends_with("/>") {
+            if !is_name(&tag[1..]) {
+                return None;
+            }
+            tokens.push(XmlToken::Empty(tag));
+        } else {
+            let inner = &tag[1..tag.len() - 1];
+            if !is_name(inner) {
+                return None;
+            }
+            let name = inner.split_whitespace().next()?;
+            tokens.push(XmlToken::Open { name, tag });
+        }
+    }
+    Some(tokens)
+}
+
+// Returns `true` if `text` starts with a character which starts XML names.
+fn is_name(text: &str) -> bool {
+    text.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+}
+
+fn indent(output: &mut String, depth: usize) {
+    for _ in 0..depth {
+        output.push_str(INDENT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::{pretty_print, Format};
+
+    #[test]
+    fn test_pretty_print_json() {
+        let (format, json) =
+            pretty_print(r#" {"b": [1, 2.5e3, {}], "a":{"s":"x, \"y\"","n":null}, "e":[]} "#)
+                .unwrap();
+        assert_eq!(format, Format::Json);
+        assert_eq!(
+            json,
+            "{\n  \"b\": [\n    1,\n    2.5e3,\n    {}\n  ],\n  \"a\": {\n    \"s\": \"x, \
+             \\\"y\\\"\",\n    \"n\": null\n  },\n  \"e\": []\n}"
+        );
+
+        assert!(pretty_print("{\"a\": }").is_none());
+        assert!(pretty_print("[1, 2] 3").is_none());
+        assert!(pretty_print("[tru]").is_none());
+        assert!(pretty_print("clipcat").is_none());
+    }
+
+    #[test]
+    fn test_pretty_print_xml() {
+        let (format, xml) = pretty_print(
+            "<?xml version=\"1.0\"?><a x=\"1\"><b>text</b><!-- comment --><c/><d><e>1</e></d></a>",
+        )
+        .unwrap();
+        assert_eq!(format, Format::Xml);
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\"?>\n<a x=\"1\">\n  <b>text</b>\n  <!-- comment -->\n  <c/>\n  \
+             <d>\n    <e>1</e>\n  </d>\n</a>"
+        );
+
+        assert!(pretty_print("<a><b></a></b>").is_none());
+        assert!(pretty_print("<a>").is_none());
+        assert!(pretty_print("<3 clipcat").is_none());
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use tonic::{
+    metadata::{errors::InvalidMetadataValue, Ascii, MetadataValue},
+    service::Interceptor,
+    Request, Status,
+};
+
+const AUTHORIZATION: &str = "authorization";
+
+/// TLS settings of a client, all files are PEM encoded.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ClientTlsOptions {
+    /// Certificate of the CA which signed the server certificate, the system
+    /// roots are used if it is not set.
+    #[serde(default)]
+    pub ca_certificate: Option<PathBuf>,
+
+    /// Client certificate, required if the server verifies clients.
+    #[serde(default)]
+    pub certificate: Option<PathBuf>,
+
+    /// Private key of the client certificate.
+    #[serde(default)]
+    pub key: Option<PathBuf>,
+
+    /// Overrides the domain name which is verified against the server
+    /// certificate.
+    #[serde(default)]
+    pub domain_name: Option<String>,
+}
+
+/// Rejects requests without the shared token, every request is accepted if
+/// no token is set.
+#[derive(Debug, Clone)]
+pub struct ServerInterceptor {
+    expected: Option<Vec<u8>>,
+}
+
+impl Interceptor for ServerInterceptor {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(ref expected) = self.expected {
+            check_token(expected, &request)?;
+        }
+        Ok(request)
+    }
+}
+
+/// Attaches the shared token to every request if it is set.
+#[derive(Debug, Clone)]
+pub struct ClientInterceptor {
+    value: Option<MetadataValue<Ascii>>,
+}
+
+impl Interceptor for ClientInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(ref value) = self.value {
+            request.metadata_mut().insert(AUTHORIZATION, value.clone());
+        }
+        Ok(request)
+    }
+}
+
+/// Builds an interceptor which rejects requests without the shared `token`.
+pub fn server_interceptor(token: Option<&str>) -> ServerInterceptor {
+    ServerInterceptor { expected: token.map(|token| bearer(token).into_bytes()) }
+}
+
+/// Builds an interceptor which attaches the shared `token` to every request.
+pub fn client_interceptor(token: Option<&str>) -> Result<ClientInterceptor, InvalidMetadataValue> {
+    let value = token.map(|token| MetadataValue::from_str(&bearer(token))).transpose()?;
+    Ok(ClientInterceptor { value })
+}
+
+/// Checks the value of an `Authorization` header of a request which is not
+/// served by gRPC against the shared `token`.
+pub fn is_authorized(token: &str, authorization: Option<&str>) -> bool {
+    authorization.is_some_and(|value| constant_time_eq(value.as_bytes(), bearer(token).as_bytes()))
+}
+
+#[inline]
+fn bearer(token: &str) -> String { format!("Bearer {}", token) }
+
+fn check_token(expected: &[u8], request: &Request<()>) -> Result<(), Status> {
+    match request.metadata().get(AUTHORIZATION) {
+        Some(value) if constant_time_eq(value.as_bytes(), expected) => Ok(()),
+        Some(_) => Err(Status::unauthenticated("Invalid token")),
+        None => Err(Status::unauthenticated("Token is required")),
+    }
+}
+
+// compares without short-circuiting so that the token can not be guessed by
+// timing
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::{metadata::MetadataValue, Code, Request};
+
+    use tonic::service::Interceptor;
+
+    use super::{
+        bearer, check_token, client_interceptor, constant_time_eq, is_authorized,
+        server_interceptor, AUTHORIZATION,
+    };
+
+    #[test]
+    fn test_check_token() {
+        let expected = bearer("secret").into_bytes();
+
+        let request = Request::new(());
+        assert_eq!(check_token(&expected, &request).unwrap_err().code(), Code::Unauthenticated);
+
+        let mut request = Request::new(());
+        request.metadata_mut().insert(AUTHORIZATION, MetadataValue::from_static("Bearer wrong"));
+        assert_eq!(check_token(&expected, &request).unwrap_err().code(), Code::Unauthenticated);
+
+        let mut request = Request::new(());
+        request.metadata_mut().insert(AUTHORIZATION, MetadataValue::from_static("Bearer secret"));
+        assert!(check_token(&expected, &request).is_ok());
+    }
+
+    #[test]
+    fn test_interceptors() {
+        let mut server = server_interceptor(Some("secret"));
+        assert_eq!(server.call(Request::new(())).unwrap_err().code(), Code::Unauthenticated);
+        let mut client = client_interceptor(Some("secret")).unwrap();
+        assert!(server.call(client.call(Request::new(())).unwrap()).is_ok());
+
+        let mut server = server_interceptor(None);
+        let mut client = client_interceptor(None).unwrap();
+        let request = client.call(Request::new(())).unwrap();
+        assert!(request.metadata().get(AUTHORIZATION).is_none());
+        assert!(server.call(request).is_ok());
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        assert!(is_authorized("secret", Some("Bearer secret")));
+        assert!(!is_authorized("secret", Some("Bearer wrong")));
+        assert!(!is_authorized("secret", Some("secret")));
+        assert!(!is_authorized("secret", None));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+    }
+}
@@ -1,18 +1,39 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
 use snafu::{ResultExt, Snafu};
 use tonic::{
-    transport::{channel::Channel, Error as TonicTransportError},
-    Request, Status as TonicStatus,
+    metadata::errors::InvalidMetadataValue,
+    service::interceptor::InterceptedService,
+    transport::{
+        channel::Channel, Certificate, ClientTlsConfig, Error as TonicTransportError, Identity,
+    },
+    Request, Status as TonicStatus, Streaming,
 };
 
 use crate::{
-    grpc::protobuf::{
-        manager_client::ManagerClient, monitor_client::MonitorClient, BatchRemoveRequest,
-        ClearRequest, DisableMonitorRequest, EnableMonitorRequest, GetCurrentClipboardRequest,
-        GetCurrentPrimaryRequest, GetMonitorStateRequest, GetRequest, InsertRequest, LengthRequest,
-        ListRequest, MarkAsClipboardRequest, MarkAsPrimaryRequest, RemoveRequest,
-        ToggleMonitorRequest, UpdateRequest,
+    grpc::{
+        auth::{self, ClientInterceptor, ClientTlsOptions},
+        protobuf::{
+            get_data_response::Payload, manager_client::ManagerClient,
+            monitor_client::MonitorClient, settings_client::SettingsClient, BatchRemoveRequest,
+            ClearRequest, ClipboardEventReply, DeduplicateRequest, DisableMonitorRequest,
+            EnableMonitorRequest, ExportRequest, GetCurrentClipboardRequest,
+            GetCurrentPrimaryRequest, GetDataHeader, GetDataRequest, GetDataResponse,
+            GetMonitorStateRequest, GetRequest, GetSettingRequest, ImportRequest, InsertRequest,
+            InsertSnippetRequest, InsertSnippetResponse, LengthRequest, ListRequest,
+            ListSnippetsRequest, ListTrashRequest, MarkAsClipboardRequest, MarkAsPrimaryRequest,
+            PinRequest, RemoveRequest, RestoreFromTrashRequest, SearchMode, SearchRequest,
+            SetSettingRequest, SortOrder, StatsRequest, SubscribeRequest, TagRequest,
+            ToggleMonitorRequest, UndoRequest, UnpinRequest, UntagRequest, UpdateRequest,
+            VacuumRequest,
+        },
     },
-    ClipboardData, ClipboardType, MonitorState,
+    stats::HistoryStats,
+    trash::TrashEntry,
+    ClipboardData, ClipboardNotification, ClipboardType, MonitorState, SearchResult, Snippet,
 };
 
 #[derive(Debug, Snafu)]
@@ -23,12 +44,31 @@ pub enum GrpcClientError {
     #[snafu(display("Failed to connect gRPC service: {}, error: {}", addr, source))]
     ConnetRemote { addr: String, source: TonicTransportError },
 
+    #[cfg(unix)]
+    #[snafu(display("Failed to connect gRPC service: {}, error: {}", path.display(), source))]
+    ConnectUnixSocket { path: PathBuf, source: TonicTransportError },
+
+    #[snafu(display("Could not read {}, error: {}", filename.display(), source))]
+    ReadTlsFile { filename: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Both client certificate and key are required"))]
+    IncompleteTlsIdentity,
+
+    #[snafu(display("Could not configure TLS, error: {}", source))]
+    ConfigureTls { source: TonicTransportError },
+
+    #[snafu(display("Invalid token, error: {}", source))]
+    InvalidToken { source: InvalidMetadataValue },
+
     #[snafu(display("Could not list clips, error: {}", source))]
     List { source: TonicStatus },
 
     #[snafu(display("Could not get clip with id {}, error: {}", id, source))]
     GetData { id: u64, source: TonicStatus },
 
+    #[snafu(display("Received {} of {} bytes of clip with id {}", received, size, id))]
+    IncompleteData { id: u64, received: usize, size: usize },
+
     #[snafu(display("Could not get current clip, error: {}", source))]
     GetCurrentClipboard { source: TonicStatus },
 
@@ -54,6 +94,27 @@ pub enum GrpcClientError {
     ))]
     MarkAsPrimary { id: u64, source: TonicStatus },
 
+    #[snafu(display("Could not pin clip with id {}, error: {}", id, source))]
+    PinClip { id: u64, source: TonicStatus },
+
+    #[snafu(display("Could not unpin clip with id {}, error: {}", id, source))]
+    UnpinClip { id: u64, source: TonicStatus },
+
+    #[snafu(display("Could not tag clip with id {}, error: {}", id, source))]
+    TagClip { id: u64, source: TonicStatus },
+
+    #[snafu(display("Could not untag clip with id {}, error: {}", id, source))]
+    UntagClip { id: u64, source: TonicStatus },
+
+    #[snafu(display("Could not search clips, error: {}", source))]
+    Search { source: TonicStatus },
+
+    #[snafu(display("Could not list snippets, error: {}", source))]
+    ListSnippets { source: TonicStatus },
+
+    #[snafu(display("Could not insert snippet {}, error: {}", name, source))]
+    InsertSnippet { name: String, source: TonicStatus },
+
     #[snafu(display("Could not remove clip, error: {}", source))]
     RemoveData { source: TonicStatus },
 
@@ -63,6 +124,30 @@ pub enum GrpcClientError {
     #[snafu(display("Could not clear clips, error: {}", source))]
     Clear { source: TonicStatus },
 
+    #[snafu(display("Could not undo removal of clips, error: {}", source))]
+    Undo { source: TonicStatus },
+
+    #[snafu(display("Could not list trash, error: {}", source))]
+    ListTrash { source: TonicStatus },
+
+    #[snafu(display("Could not restore clip from trash, error: {}", source))]
+    RestoreFromTrash { source: TonicStatus },
+
+    #[snafu(display("Could not export clips, error: {}", source))]
+    Export { source: TonicStatus },
+
+    #[snafu(display("Could not import clips, error: {}", source))]
+    Import { source: TonicStatus },
+
+    #[snafu(display("Could not vacuum history, error: {}", source))]
+    Vacuum { source: TonicStatus },
+
+    #[snafu(display("Could not deduplicate history, error: {}", source))]
+    Deduplicate { source: TonicStatus },
+
+    #[snafu(display("Could not get statistics of history, error: {}", source))]
+    GetStats { source: TonicStatus },
+
     #[snafu(display("Could not enable monitor, error: {}", source))]
     EnableMonitor { source: TonicStatus },
 
@@ -75,36 +160,161 @@ pub enum GrpcClientError {
     #[snafu(display("Could not get monitor state, error: {}", source))]
     GetMonitorState { source: TonicStatus },
 
+    #[snafu(display("Could not subscribe to clipboard events, error: {}", source))]
+    Subscribe { source: TonicStatus },
+
+    #[snafu(display("Could not receive clipboard event, error: {}", source))]
+    ReceiveEvent { source: TonicStatus },
+
+    #[snafu(display("Could not get setting {}, error: {}", key, source))]
+    GetSetting { key: String, source: TonicStatus },
+
+    #[snafu(display("Could not change setting {}, error: {}", key, source))]
+    SetSetting { key: String, source: TonicStatus },
+
     #[snafu(display("Empty response"))]
     Empty,
 }
 
 pub struct GrpcClient {
-    monitor_client: MonitorClient<Channel>,
-    manager_client: ManagerClient<Channel>,
+    monitor_client: MonitorClient<InterceptedService<Channel, ClientInterceptor>>,
+    manager_client: ManagerClient<InterceptedService<Channel, ClientInterceptor>>,
+    settings_client: SettingsClient<InterceptedService<Channel, ClientInterceptor>>,
+}
+
+/// Options for connecting to a clipcat daemon.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// Shared token which is sent with every request.
+    pub token: Option<String>,
+
+    /// Enables TLS, the address has to use the `https` scheme.
+    pub tls: Option<ClientTlsOptions>,
+}
+
+/// Options for listing a page of clips.
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    /// Only clips which carry all of the tags are listed.
+    pub tags: Vec<String>,
+
+    pub offset: usize,
+
+    /// Maximum number of clips, all remaining clips are listed if it is 0.
+    pub limit: usize,
+
+    /// Only clips which were copied from the application are listed, names
+    /// are compared case-insensitively.
+    pub application: Option<String>,
+
+    /// Lists clips in this order instead of the ranking of the daemon.
+    pub order: Option<crate::SortOrder>,
+
+    /// Lists text previews of the clips truncated to the length if it is set,
+    /// the length 0 does not truncate.
+    pub preview_length: Option<usize>,
 }
 
 impl GrpcClient {
+    #[inline]
     pub async fn new(addr: String) -> Result<GrpcClient, GrpcClientError> {
+        Self::connect(addr, ConnectOptions::default()).await
+    }
+
+    pub async fn connect(
+        addr: String,
+        opts: ConnectOptions,
+    ) -> Result<GrpcClient, GrpcClientError> {
         use tonic::transport::Endpoint;
-        let channel = Endpoint::from_shared(addr.clone())
-            .context(ParseEndpoint { addr: addr.clone() })?
-            .connect()
+        let mut endpoint =
+            Endpoint::from_shared(addr.clone()).context(ParseEndpoint { addr: addr.clone() })?;
+        if let Some(ref tls) = opts.tls {
+            endpoint = endpoint.tls_config(load_tls_config(tls)?).context(ConfigureTls)?;
+        }
+        let channel = endpoint.connect().await.context(ConnetRemote { addr })?;
+        Self::from_channel(channel, opts.token.as_deref())
+    }
+
+    #[cfg(unix)]
+    pub async fn connect_unix<P: AsRef<Path>>(
+        socket_path: P,
+        token: Option<&str>,
+    ) -> Result<GrpcClient, GrpcClientError> {
+        use tonic::transport::{Endpoint, Uri};
+
+        let path = socket_path.as_ref().to_path_buf();
+        let connector = {
+            let path = path.clone();
+            tower::service_fn(move |_: Uri| tokio::net::UnixStream::connect(path.clone()))
+        };
+        // the URI is not used by the connector but it has to be valid
+        let channel = Endpoint::from_static("http://[::]:0")
+            .connect_with_connector(connector)
             .await
-            .context(ConnetRemote { addr })?;
-        let monitor_client = MonitorClient::new(channel.clone());
-        let manager_client = ManagerClient::new(channel);
-        Ok(GrpcClient { monitor_client, manager_client })
+            .context(ConnectUnixSocket { path })?;
+        Self::from_channel(channel, token)
+    }
+
+    fn from_channel(channel: Channel, token: Option<&str>) -> Result<GrpcClient, GrpcClientError> {
+        let interceptor = auth::client_interceptor(token).context(InvalidToken)?;
+        Ok(GrpcClient {
+            monitor_client: MonitorClient::with_interceptor(channel.clone(), interceptor.clone()),
+            manager_client: ManagerClient::with_interceptor(channel.clone(), interceptor.clone()),
+            settings_client: SettingsClient::with_interceptor(channel, interceptor),
+        })
     }
 
     pub async fn insert(
         &mut self,
         data: &str,
         clipboard_type: ClipboardType,
+    ) -> Result<u64, GrpcClientError> {
+        self.insert_text(data, clipboard_type, false).await
+    }
+
+    /// Inserts `data` as a sensitive clip, the daemon removes it again after
+    /// its sensitive timeout.
+    pub async fn insert_sensitive(
+        &mut self,
+        data: &str,
+        clipboard_type: ClipboardType,
+    ) -> Result<u64, GrpcClientError> {
+        self.insert_text(data, clipboard_type, true).await
+    }
+
+    async fn insert_text(
+        &mut self,
+        data: &str,
+        clipboard_type: ClipboardType,
+        sensitive: bool,
     ) -> Result<u64, GrpcClientError> {
         let request = Request::new(InsertRequest {
             clipboard_type: clipboard_type.into(),
             data: data.to_owned(),
+            mime: crate::mime::TEXT_PLAIN.to_owned(),
+            binary: Vec::new(),
+            sensitive,
+        });
+        let response = self.manager_client.insert(request).await.context(InsertData)?;
+        Ok(response.into_inner().id)
+    }
+
+    pub async fn insert_bytes(
+        &mut self,
+        mime: &str,
+        data: Vec<u8>,
+        clipboard_type: ClipboardType,
+    ) -> Result<u64, GrpcClientError> {
+        if crate::mime::is_text(mime) {
+            return self.insert(&String::from_utf8_lossy(&data), clipboard_type).await;
+        }
+
+        let request = Request::new(InsertRequest {
+            clipboard_type: clipboard_type.into(),
+            data: String::new(),
+            mime: mime.to_owned(),
+            binary: data,
+            sensitive: false,
         });
         let response = self.manager_client.insert(request).await.context(InsertData)?;
         Ok(response.into_inner().id)
@@ -127,6 +337,91 @@ impl GrpcClient {
         }
     }
 
+    pub async fn get_clip(&mut self, id: u64) -> Result<ClipboardData, GrpcClientError> {
+        let request = Request::new(GetRequest { id });
+        let response = self.manager_client.get(request).await.context(GetData { id })?;
+        match response.into_inner().data {
+            Some(data) => Ok(data.into()),
+            None => Err(GrpcClientError::Empty),
+        }
+    }
+
+    /// Gets clip `id` like `get_clip`, but receives its body in chunks of at
+    /// most `chunk_size` bytes, or of a default size if it is 0, so that large
+    /// clips do not exceed the size limit of messages. `progress` is called
+    /// with the number of received bytes and the size of the body. The
+    /// alternative representations of the clip are not received.
+    pub async fn get_clip_chunked<F>(
+        &mut self,
+        id: u64,
+        chunk_size: usize,
+        mut progress: F,
+    ) -> Result<ClipboardData, GrpcClientError>
+    where
+        F: FnMut(usize, usize),
+    {
+        let request = Request::new(GetDataRequest { id, chunk_size: chunk_size as u64 });
+        let response = self.manager_client.get_data(request).await.context(GetData { id })?;
+        let mut stream = response.into_inner();
+
+        let (mut clip, size) = match stream.message().await.context(GetData { id })? {
+            Some(GetDataResponse {
+                payload: Some(Payload::Header(GetDataHeader { data: Some(data), size })),
+            }) => (ClipboardData::from(data), size as usize),
+            _ => return Err(GrpcClientError::Empty),
+        };
+
+        let mut body = Vec::new();
+        progress(0, size);
+        while let Some(response) = stream.message().await.context(GetData { id })? {
+            if let Some(Payload::Chunk(chunk)) = response.payload {
+                body.extend_from_slice(&chunk);
+                progress(body.len(), size);
+            }
+        }
+        if body.len() != size {
+            return Err(GrpcClientError::IncompleteData { id, received: body.len(), size });
+        }
+
+        if clip.is_text() {
+            clip.data = String::from_utf8_lossy(&body).into_owned();
+        } else {
+            clip.binary = body;
+        }
+        Ok(clip)
+    }
+
+    pub async fn get_current_clip(
+        &mut self,
+        clipboard_type: ClipboardType,
+    ) -> Result<ClipboardData, GrpcClientError> {
+        let data = match clipboard_type {
+            ClipboardType::Clipboard => {
+                let request = Request::new(GetCurrentClipboardRequest {});
+                let response = self
+                    .manager_client
+                    .get_current_clipboard(request)
+                    .await
+                    .context(GetCurrentClipboard)?;
+                response.into_inner().data
+            }
+            ClipboardType::Primary => {
+                let request = Request::new(GetCurrentPrimaryRequest {});
+                let response = self
+                    .manager_client
+                    .get_current_primary(request)
+                    .await
+                    .context(GetCurrentPrimary)?;
+                response.into_inner().data
+            }
+        };
+
+        match data {
+            Some(data) => Ok(data.into()),
+            None => Err(GrpcClientError::Empty),
+        }
+    }
+
     pub async fn get_current_clipboard(&mut self) -> Result<String, GrpcClientError> {
         let request = Request::new(GetCurrentClipboardRequest {});
         let response = self
@@ -159,19 +454,92 @@ impl GrpcClient {
     }
 
     pub async fn mark_as_clipboard(&mut self, id: u64) -> Result<bool, GrpcClientError> {
-        let request = Request::new(MarkAsClipboardRequest { id });
+        let request = Request::new(MarkAsClipboardRequest { id, one_shot: false, paste: false });
+        let response =
+            self.manager_client.mark_as_clipboard(request).await.context(MarkAsClipboard { id })?;
+        Ok(response.into_inner().ok)
+    }
+
+    /// Replaces the content of clipboard with clip `id` for a single paste,
+    /// the daemon drops the clip afterwards.
+    pub async fn mark_as_clipboard_once(&mut self, id: u64) -> Result<bool, GrpcClientError> {
+        let request = Request::new(MarkAsClipboardRequest { id, one_shot: true, paste: false });
         let response =
             self.manager_client.mark_as_clipboard(request).await.context(MarkAsClipboard { id })?;
         Ok(response.into_inner().ok)
     }
 
     pub async fn mark_as_primary(&mut self, id: u64) -> Result<bool, GrpcClientError> {
-        let request = Request::new(MarkAsPrimaryRequest { id });
+        let request = Request::new(MarkAsPrimaryRequest { id, one_shot: false, paste: false });
+        let response =
+            self.manager_client.mark_as_primary(request).await.context(MarkAsPrimary { id })?;
+        Ok(response.into_inner().ok)
+    }
+
+    /// Replaces the content of primary clipboard with clip `id` for a single
+    /// paste, the daemon drops the clip afterwards.
+    pub async fn mark_as_primary_once(&mut self, id: u64) -> Result<bool, GrpcClientError> {
+        let request = Request::new(MarkAsPrimaryRequest { id, one_shot: true, paste: false });
         let response =
             self.manager_client.mark_as_primary(request).await.context(MarkAsPrimary { id })?;
         Ok(response.into_inner().ok)
     }
 
+    /// Marks clip `id` like `mark_as_clipboard` or `mark_as_primary` and lets
+    /// the daemon paste it into the focused window.
+    pub async fn paste(
+        &mut self,
+        id: u64,
+        clipboard_type: ClipboardType,
+        one_shot: bool,
+    ) -> Result<bool, GrpcClientError> {
+        let ok = match clipboard_type {
+            ClipboardType::Clipboard => {
+                let request = Request::new(MarkAsClipboardRequest { id, one_shot, paste: true });
+                let response = self
+                    .manager_client
+                    .mark_as_clipboard(request)
+                    .await
+                    .context(MarkAsClipboard { id })?;
+                response.into_inner().ok
+            }
+            ClipboardType::Primary => {
+                let request = Request::new(MarkAsPrimaryRequest { id, one_shot, paste: true });
+                let response = self
+                    .manager_client
+                    .mark_as_primary(request)
+                    .await
+                    .context(MarkAsPrimary { id })?;
+                response.into_inner().ok
+            }
+        };
+        Ok(ok)
+    }
+
+    pub async fn pin(&mut self, id: u64) -> Result<bool, GrpcClientError> {
+        let request = Request::new(PinRequest { id });
+        let response = self.manager_client.pin(request).await.context(PinClip { id })?;
+        Ok(response.into_inner().ok)
+    }
+
+    pub async fn unpin(&mut self, id: u64) -> Result<bool, GrpcClientError> {
+        let request = Request::new(UnpinRequest { id });
+        let response = self.manager_client.unpin(request).await.context(UnpinClip { id })?;
+        Ok(response.into_inner().ok)
+    }
+
+    pub async fn tag(&mut self, id: u64, tags: &[String]) -> Result<bool, GrpcClientError> {
+        let request = Request::new(TagRequest { id, tags: tags.to_vec() });
+        let response = self.manager_client.tag(request).await.context(TagClip { id })?;
+        Ok(response.into_inner().ok)
+    }
+
+    pub async fn untag(&mut self, id: u64, tags: &[String]) -> Result<bool, GrpcClientError> {
+        let request = Request::new(UntagRequest { id, tags: tags.to_vec() });
+        let response = self.manager_client.untag(request).await.context(UntagClip { id })?;
+        Ok(response.into_inner().ok)
+    }
+
     pub async fn remove(&mut self, id: u64) -> Result<bool, GrpcClientError> {
         let request = Request::new(RemoveRequest { id });
         let response = self.manager_client.remove(request).await.context(RemoveData)?;
@@ -186,38 +554,241 @@ impl GrpcClient {
     }
 
     pub async fn clear(&mut self) -> Result<(), GrpcClientError> {
-        let request = Request::new(ClearRequest {});
+        let request = Request::new(ClearRequest { older_than: 0 });
         let _response = self.manager_client.clear(request).await.context(Clear)?;
         Ok(())
     }
 
+    /// Removes unpinned clips which were last copied more than `age` ago,
+    /// returns the IDs of the removed clips.
+    pub async fn clear_older_than(&mut self, age: Duration) -> Result<Vec<u64>, GrpcClientError> {
+        let request = Request::new(ClearRequest { older_than: age.as_secs().max(1) });
+        let response = self.manager_client.clear(request).await.context(Clear)?;
+        Ok(response.into_inner().ids)
+    }
+
+    /// Restores the clips which were removed last, returns their IDs.
+    pub async fn undo(&mut self) -> Result<Vec<u64>, GrpcClientError> {
+        let request = Request::new(UndoRequest {});
+        let response = self.manager_client.undo(request).await.context(Undo)?;
+        Ok(response.into_inner().ids)
+    }
+
+    /// Lists the clips in the trash with previews of `preview_length`
+    /// characters, the most recently removed ones first.
+    pub async fn list_trash(
+        &mut self,
+        preview_length: usize,
+    ) -> Result<Vec<TrashEntry>, GrpcClientError> {
+        let request = Request::new(ListTrashRequest { preview_length: preview_length as u64 });
+        let response = self.manager_client.list_trash(request).await.context(ListTrash)?;
+        Ok(response.into_inner().entries.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn restore_from_trash(&mut self, id: u64) -> Result<bool, GrpcClientError> {
+        let request = Request::new(RestoreFromTrashRequest { id });
+        let response =
+            self.manager_client.restore_from_trash(request).await.context(RestoreFromTrash)?;
+        Ok(response.into_inner().ok)
+    }
+
+    /// Lists clips which carry all of `tags` with their full content,
+    /// sensitive clips are left out.
+    pub async fn export(
+        &mut self,
+        tags: &[String],
+        pinned_only: bool,
+    ) -> Result<Vec<ClipboardData>, GrpcClientError> {
+        let request = Request::new(ExportRequest { tags: tags.to_vec(), pinned_only });
+        let response = self.manager_client.export(request).await.context(Export)?;
+        Ok(response.into_inner().data.into_iter().map(Into::into).collect())
+    }
+
+    /// Adds `clips` which are not stored by the daemon yet, returns the number
+    /// of added clips.
+    pub async fn import(&mut self, clips: Vec<ClipboardData>) -> Result<usize, GrpcClientError> {
+        let data = clips.into_iter().map(Into::into).collect();
+        let response = self
+            .manager_client
+            .import(Request::new(ImportRequest { data }))
+            .await
+            .context(Import)?;
+        Ok(response.into_inner().count as usize)
+    }
+
+    /// Rewrites the history store of the daemon, returns the number of
+    /// reclaimed bytes.
+    pub async fn vacuum(&mut self) -> Result<u64, GrpcClientError> {
+        let request = Request::new(VacuumRequest {});
+        let response = self.manager_client.vacuum(request).await.context(Vacuum)?;
+        Ok(response.into_inner().reclaimed_bytes)
+    }
+
+    /// Merges clips with the same content into the most recently copied one,
+    /// returns the number of merged clips.
+    pub async fn deduplicate(&mut self) -> Result<usize, GrpcClientError> {
+        let request = Request::new(DeduplicateRequest {});
+        let response = self.manager_client.deduplicate(request).await.context(Deduplicate)?;
+        Ok(response.into_inner().merged as usize)
+    }
+
+    /// Returns statistics of the history, with previews of `preview_length`
+    /// characters of the `largest` largest clips.
+    pub async fn stats(
+        &mut self,
+        largest: usize,
+        preview_length: usize,
+    ) -> Result<HistoryStats, GrpcClientError> {
+        let request = Request::new(StatsRequest {
+            largest: largest as u64,
+            preview_length: preview_length as u64,
+        });
+        let response = self.manager_client.stats(request).await.context(GetStats)?;
+        Ok(response.into_inner().into())
+    }
+
+    /// Returns the value of setting `key` of the daemon as TOML, or its whole
+    /// configuration if `key` is empty.
+    pub async fn get_setting(&mut self, key: &str) -> Result<String, GrpcClientError> {
+        let request = Request::new(GetSettingRequest { key: key.to_owned() });
+        let response = self
+            .settings_client
+            .get_setting(request)
+            .await
+            .context(GetSetting { key: key.to_owned() })?;
+        Ok(response.into_inner().value)
+    }
+
+    /// Changes setting `key` of the running daemon to the TOML `value`, returns
+    /// its effective value. The configuration file is not changed.
+    pub async fn set_setting(&mut self, key: &str, value: &str) -> Result<String, GrpcClientError> {
+        let request =
+            Request::new(SetSettingRequest { key: key.to_owned(), value: value.to_owned() });
+        let response = self
+            .settings_client
+            .set_setting(request)
+            .await
+            .context(SetSetting { key: key.to_owned() })?;
+        Ok(response.into_inner().value)
+    }
+
     pub async fn length(&mut self) -> Result<usize, GrpcClientError> {
         let request = Request::new(LengthRequest {});
         let response = self.manager_client.length(request).await.context(GetLength)?;
         Ok(response.into_inner().length as usize)
     }
 
+    #[inline]
     pub async fn list(&mut self) -> Result<Vec<ClipboardData>, GrpcClientError> {
-        let request = Request::new(ListRequest {});
-        let response = self.manager_client.list(request).await.context(List)?;
-        let mut list: Vec<_> = response
-            .into_inner()
-            .data
-            .into_iter()
-            .map(|data| {
-                let timestamp = std::time::UNIX_EPOCH
-                    .checked_add(std::time::Duration::from_millis(data.timestamp))
-                    .unwrap_or_else(std::time::SystemTime::now);
-                ClipboardData {
-                    id: data.id,
-                    data: data.data,
-                    clipboard_type: data.clipboard_type.into(),
-                    timestamp,
-                }
-            })
-            .collect();
-        list.sort();
-        Ok(list)
+        self.list_tagged(&[]).await
+    }
+
+    /// Lists clips which carry all of `tags` in the order ranked by the
+    /// daemon.
+    pub async fn list_tagged(
+        &mut self,
+        tags: &[String],
+    ) -> Result<Vec<ClipboardData>, GrpcClientError> {
+        let opts = ListOptions { tags: tags.to_vec(), ..Default::default() };
+        let (clips, _total) = self.list_page(&opts).await?;
+        Ok(clips)
+    }
+
+    /// Lists a page of clips, returns the clips and the number of clips which
+    /// match `opts`.
+    pub async fn list_page(
+        &mut self,
+        opts: &ListOptions,
+    ) -> Result<(Vec<ClipboardData>, usize), GrpcClientError> {
+        let request = Request::new(ListRequest {
+            tags: opts.tags.clone(),
+            offset: opts.offset as u64,
+            limit: opts.limit as u64,
+            preview_only: opts.preview_length.is_some(),
+            preview_length: opts.preview_length.unwrap_or(0) as u64,
+            application: opts.application.clone().unwrap_or_default(),
+            sort: SortOrder::from(opts.order) as i32,
+        });
+        let response = self.manager_client.list(request).await.context(List)?.into_inner();
+        Ok((response.data.into_iter().map(Into::into).collect(), response.total as usize))
+    }
+
+    /// Fuzzy matches clips which carry all of `tags` against `query` on the
+    /// daemon, best matches first. At most `limit` results are returned if
+    /// `limit` is not 0, previews are truncated to `preview_length` characters
+    /// if it is not 0.
+    pub async fn search(
+        &mut self,
+        query: &str,
+        tags: &[String],
+        limit: usize,
+        preview_length: usize,
+    ) -> Result<Vec<SearchResult>, GrpcClientError> {
+        self.send_search(SearchRequest {
+            query: query.to_owned(),
+            limit: limit as u64,
+            preview_length: preview_length as u64,
+            mode: SearchMode::Fuzzy as i32,
+            context: 0,
+            tags: tags.to_vec(),
+        })
+        .await
+    }
+
+    /// Matches regular expression `pattern` against the whole content of text
+    /// clips which carry all of `tags` on the daemon, each result carries the
+    /// matched lines and up to `context` lines around them.
+    pub async fn grep(
+        &mut self,
+        pattern: &str,
+        tags: &[String],
+        context: usize,
+        limit: usize,
+        preview_length: usize,
+    ) -> Result<Vec<SearchResult>, GrpcClientError> {
+        self.send_search(SearchRequest {
+            query: pattern.to_owned(),
+            limit: limit as u64,
+            preview_length: preview_length as u64,
+            mode: SearchMode::Regex as i32,
+            context: context as u64,
+            tags: tags.to_vec(),
+        })
+        .await
+    }
+
+    async fn send_search(
+        &mut self,
+        request: SearchRequest,
+    ) -> Result<Vec<SearchResult>, GrpcClientError> {
+        let response = self.manager_client.search(Request::new(request)).await.context(Search)?;
+        Ok(response.into_inner().results.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn list_snippets(&mut self) -> Result<Vec<Snippet>, GrpcClientError> {
+        let request = Request::new(ListSnippetsRequest {});
+        let response = self.manager_client.list_snippets(request).await.context(ListSnippets)?;
+        Ok(response.into_inner().snippets.into_iter().map(Into::into).collect())
+    }
+
+    /// Inserts the content of snippet with `name` as a new clip, returns the ID
+    /// of the clip or `None` if there is no such snippet.
+    pub async fn insert_snippet(
+        &mut self,
+        name: &str,
+        clipboard_type: ClipboardType,
+    ) -> Result<Option<u64>, GrpcClientError> {
+        let request = Request::new(InsertSnippetRequest {
+            name: name.to_owned(),
+            clipboard_type: clipboard_type.into(),
+        });
+        let response = self
+            .manager_client
+            .insert_snippet(request)
+            .await
+            .context(InsertSnippet { name: name.to_owned() })?;
+        let InsertSnippetResponse { ok, id } = response.into_inner();
+        Ok(if ok { Some(id) } else { None })
     }
 
     pub async fn enable_monitor(&mut self) -> Result<MonitorState, GrpcClientError> {
@@ -245,4 +816,46 @@ impl GrpcClient {
             self.monitor_client.get_monitor_state(request).await.context(GetMonitorState)?;
         Ok(response.into_inner().state.into())
     }
+
+    pub async fn subscribe(&mut self) -> Result<ClipboardEventStream, GrpcClientError> {
+        let request = Request::new(SubscribeRequest {});
+        let response = self.monitor_client.subscribe(request).await.context(Subscribe)?;
+        Ok(ClipboardEventStream { inner: response.into_inner() })
+    }
+}
+
+fn load_tls_config(opts: &ClientTlsOptions) -> Result<ClientTlsConfig, GrpcClientError> {
+    fn read(filename: &Path) -> Result<Vec<u8>, GrpcClientError> {
+        std::fs::read(filename).context(ReadTlsFile { filename: filename.to_path_buf() })
+    }
+
+    let mut config = ClientTlsConfig::new();
+    if let Some(ref ca_certificate) = opts.ca_certificate {
+        config = config.ca_certificate(Certificate::from_pem(read(ca_certificate)?));
+    }
+    match (&opts.certificate, &opts.key) {
+        (Some(certificate), Some(key)) => {
+            config = config.identity(Identity::from_pem(read(certificate)?, read(key)?));
+        }
+        (None, None) => {}
+        _ => return Err(GrpcClientError::IncompleteTlsIdentity),
+    }
+    if let Some(ref domain_name) = opts.domain_name {
+        config = config.domain_name(domain_name.clone());
+    }
+    Ok(config)
+}
+
+/// Stream of clipboard events which are captured by the daemon.
+pub struct ClipboardEventStream {
+    inner: Streaming<ClipboardEventReply>,
+}
+
+impl ClipboardEventStream {
+    /// Waits for the next event, returns `None` if the daemon closed the
+    /// stream.
+    pub async fn next(&mut self) -> Result<Option<ClipboardNotification>, GrpcClientError> {
+        let reply = self.inner.message().await.context(ReceiveEvent)?;
+        Ok(reply.map(Into::into))
+    }
 }
@@ -1,3 +1,4 @@
+mod auth;
 mod client;
 mod protobuf;
 
@@ -5,10 +6,17 @@ mod protobuf;
 mod service;
 
 pub use self::{
-    client::{GrpcClient, GrpcClientError},
-    protobuf::{manager_server::ManagerServer, monitor_server::MonitorServer},
+    auth::{
+        client_interceptor, is_authorized, server_interceptor, ClientInterceptor, ClientTlsOptions,
+        ServerInterceptor,
+    },
+    client::{ClipboardEventStream, ConnectOptions, GrpcClient, GrpcClientError, ListOptions},
+    protobuf::{
+        manager_server::ManagerServer, monitor_server::MonitorServer,
+        settings_server::SettingsServer,
+    },
 };
 
-#[cfg(feature = "monitor")]
-pub use self::service::ManagerService;
 pub use self::service::MonitorService;
+#[cfg(feature = "monitor")]
+pub use self::service::{HistoryMaintenance, ManagerService, RuntimeSettings, SettingsService};
@@ -1,5 +1,6 @@
 tonic::include_proto!("manager");
 tonic::include_proto!("monitor");
+tonic::include_proto!("settings");
 
 impl From<ClipboardType> for crate::ClipboardType {
     fn from(t: ClipboardType) -> crate::ClipboardType {
@@ -26,7 +27,7 @@ impl From<crate::ClipboardType> for i32 {
 impl From<crate::ClipboardData> for ClipboardData {
     fn from(data: crate::ClipboardData) -> ClipboardData {
         ClipboardData {
-            id: data.id as u64,
+            id: data.id,
             data: data.data,
             clipboard_type: data.clipboard_type.into(),
             timestamp: data
@@ -34,6 +35,255 @@ impl From<crate::ClipboardData> for ClipboardData {
                 .duration_since(std::time::UNIX_EPOCH)
                 .expect("duration since")
                 .as_millis() as u64,
+            created_at: data
+                .created_at
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis() as u64),
+            mime: data.mime,
+            binary: data.binary,
+            targets: data.targets.into_iter().collect(),
+            pinned: data.pinned,
+            tags: data.tags.into_iter().collect(),
+            sensitive: data.sensitive,
+            use_count: data.use_count,
+            application: data.application.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<ClipboardData> for crate::ClipboardData {
+    fn from(data: ClipboardData) -> crate::ClipboardData {
+        let timestamp = std::time::UNIX_EPOCH
+            .checked_add(std::time::Duration::from_millis(data.timestamp))
+            .unwrap_or_else(std::time::SystemTime::now);
+        // clips of older clients carry no creation time
+        let created_at = match data.created_at {
+            0 => timestamp,
+            created_at => std::time::UNIX_EPOCH
+                .checked_add(std::time::Duration::from_millis(created_at))
+                .unwrap_or(timestamp),
+        };
+        let mime =
+            if data.mime.is_empty() { crate::ClipboardData::default_mime() } else { data.mime };
+        crate::ClipboardData {
+            id: data.id,
+            data: data.data,
+            clipboard_type: data.clipboard_type.into(),
+            timestamp,
+            created_at,
+            mime,
+            binary: data.binary,
+            targets: data.targets.into_iter().collect(),
+            pinned: data.pinned,
+            tags: data.tags.into_iter().collect(),
+            sensitive: data.sensitive,
+            use_count: data.use_count,
+            application: Some(data.application).filter(|application| !application.is_empty()),
+            spilled: None,
+        }
+    }
+}
+
+impl From<crate::Snippet> for Snippet {
+    fn from(snippet: crate::Snippet) -> Snippet {
+        Snippet { name: snippet.name, data: snippet.data }
+    }
+}
+
+impl From<Snippet> for crate::Snippet {
+    fn from(snippet: Snippet) -> crate::Snippet {
+        crate::Snippet { name: snippet.name, data: snippet.data }
+    }
+}
+
+impl From<crate::SearchLine> for SearchLine {
+    fn from(line: crate::SearchLine) -> SearchLine {
+        SearchLine { number: line.number as u64, text: line.text, matched: line.matched }
+    }
+}
+
+impl From<SearchLine> for crate::SearchLine {
+    fn from(line: SearchLine) -> crate::SearchLine {
+        crate::SearchLine { number: line.number as usize, text: line.text, matched: line.matched }
+    }
+}
+
+impl From<crate::SearchResult> for SearchResult {
+    fn from(result: crate::SearchResult) -> SearchResult {
+        SearchResult {
+            id: result.id,
+            score: result.score,
+            preview: result.preview,
+            lines: result.lines.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<SearchResult> for crate::SearchResult {
+    fn from(result: SearchResult) -> crate::SearchResult {
+        crate::SearchResult {
+            id: result.id,
+            score: result.score,
+            preview: result.preview,
+            lines: result.lines.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<Option<crate::SortOrder>> for SortOrder {
+    fn from(order: Option<crate::SortOrder>) -> SortOrder {
+        match order {
+            None => SortOrder::Ranking,
+            Some(crate::SortOrder::Time) => SortOrder::Time,
+            Some(crate::SortOrder::Created) => SortOrder::Created,
+            Some(crate::SortOrder::Size) => SortOrder::Size,
+            Some(crate::SortOrder::UseCount) => SortOrder::UseCount,
+        }
+    }
+}
+
+impl From<SortOrder> for Option<crate::SortOrder> {
+    fn from(order: SortOrder) -> Option<crate::SortOrder> {
+        match order {
+            SortOrder::Ranking => None,
+            SortOrder::Time => Some(crate::SortOrder::Time),
+            SortOrder::Created => Some(crate::SortOrder::Created),
+            SortOrder::Size => Some(crate::SortOrder::Size),
+            SortOrder::UseCount => Some(crate::SortOrder::UseCount),
+        }
+    }
+}
+
+impl From<crate::stats::TypeStats> for TypeStats {
+    fn from(stats: crate::stats::TypeStats) -> TypeStats {
+        TypeStats { name: stats.name, count: stats.count as u64, bytes: stats.bytes as u64 }
+    }
+}
+
+impl From<TypeStats> for crate::stats::TypeStats {
+    fn from(stats: TypeStats) -> crate::stats::TypeStats {
+        crate::stats::TypeStats {
+            name: stats.name,
+            count: stats.count as usize,
+            bytes: stats.bytes as usize,
+        }
+    }
+}
+
+impl From<crate::stats::ClipSummary> for ClipSummary {
+    fn from(clip: crate::stats::ClipSummary) -> ClipSummary {
+        ClipSummary {
+            id: clip.id,
+            clipboard_type: clip.clipboard_type.into(),
+            mime: clip.mime,
+            size: clip.size as u64,
+            preview: clip.preview,
+        }
+    }
+}
+
+impl From<ClipSummary> for crate::stats::ClipSummary {
+    fn from(clip: ClipSummary) -> crate::stats::ClipSummary {
+        crate::stats::ClipSummary {
+            id: clip.id,
+            clipboard_type: clip.clipboard_type.into(),
+            mime: clip.mime,
+            size: clip.size as usize,
+            preview: clip.preview,
+        }
+    }
+}
+
+impl From<crate::stats::HistoryStats> for StatsResponse {
+    fn from(stats: crate::stats::HistoryStats) -> StatsResponse {
+        let millis = |time: Option<std::time::SystemTime>| {
+            time.and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_millis() as u64)
+        };
+        StatsResponse {
+            count: stats.count as u64,
+            bytes: stats.bytes as u64,
+            clipboard_types: stats.clipboard_types.into_iter().map(Into::into).collect(),
+            mime_types: stats.mime_types.into_iter().map(Into::into).collect(),
+            largest: stats.largest.into_iter().map(Into::into).collect(),
+            oldest_timestamp: millis(stats.oldest),
+            newest_timestamp: millis(stats.newest),
+            last_hour: stats.last_hour as u64,
+            last_day: stats.last_day as u64,
+        }
+    }
+}
+
+impl From<StatsResponse> for crate::stats::HistoryStats {
+    fn from(response: StatsResponse) -> crate::stats::HistoryStats {
+        let time = |millis: u64| match millis {
+            0 => None,
+            millis => std::time::UNIX_EPOCH.checked_add(std::time::Duration::from_millis(millis)),
+        };
+        crate::stats::HistoryStats {
+            count: response.count as usize,
+            bytes: response.bytes as usize,
+            clipboard_types: response.clipboard_types.into_iter().map(Into::into).collect(),
+            mime_types: response.mime_types.into_iter().map(Into::into).collect(),
+            largest: response.largest.into_iter().map(Into::into).collect(),
+            oldest: time(response.oldest_timestamp),
+            newest: time(response.newest_timestamp),
+            last_hour: response.last_hour as usize,
+            last_day: response.last_day as usize,
+        }
+    }
+}
+
+impl From<crate::trash::TrashEntry> for TrashEntry {
+    fn from(entry: crate::trash::TrashEntry) -> TrashEntry {
+        let removed_at = entry
+            .removed_at
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis() as u64);
+        TrashEntry { data: Some(entry.clip.into()), removed_at }
+    }
+}
+
+impl From<TrashEntry> for crate::trash::TrashEntry {
+    fn from(entry: TrashEntry) -> crate::trash::TrashEntry {
+        let removed_at = std::time::UNIX_EPOCH
+            .checked_add(std::time::Duration::from_millis(entry.removed_at))
+            .unwrap_or(std::time::UNIX_EPOCH);
+        crate::trash::TrashEntry {
+            clip: entry.data.map(Into::into).unwrap_or_default(),
+            removed_at,
+            batch: 0,
+        }
+    }
+}
+
+impl From<crate::ClipboardNotification> for ClipboardEventReply {
+    fn from(notification: crate::ClipboardNotification) -> ClipboardEventReply {
+        ClipboardEventReply {
+            id: notification.id,
+            clipboard_type: notification.clipboard_type.into(),
+            preview: notification.preview,
+            timestamp: notification
+                .timestamp
+                .duration_since(std::time::UNIX_EPOCH)
+                .expect("duration since")
+                .as_millis() as u64,
+            mime: notification.mime,
+        }
+    }
+}
+
+impl From<ClipboardEventReply> for crate::ClipboardNotification {
+    fn from(reply: ClipboardEventReply) -> crate::ClipboardNotification {
+        let timestamp = std::time::UNIX_EPOCH
+            .checked_add(std::time::Duration::from_millis(reply.timestamp))
+            .unwrap_or_else(std::time::SystemTime::now);
+        crate::ClipboardNotification {
+            id: reply.id,
+            clipboard_type: reply.clipboard_type.into(),
+            preview: reply.preview,
+            mime: reply.mime,
+            timestamp,
         }
     }
 }
@@ -47,9 +297,9 @@ impl From<MonitorState> for crate::MonitorState {
     }
 }
 
-impl Into<MonitorState> for crate::MonitorState {
-    fn into(self) -> MonitorState {
-        match self {
+impl From<crate::MonitorState> for MonitorState {
+    fn from(state: crate::MonitorState) -> MonitorState {
+        match state {
             crate::MonitorState::Enabled => MonitorState::Enabled,
             crate::MonitorState::Disabled => MonitorState::Disabled,
         }
@@ -1,51 +1,118 @@
-use std::sync::Arc;
-
-use tokio::sync::Mutex;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 
+use regex::Regex;
+use tokio::sync::{broadcast::error::RecvError, mpsc, Mutex, RwLock};
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
 
 use crate::{
     grpc::protobuf::{
-        manager_server::Manager, monitor_server::Monitor, BatchRemoveRequest, BatchRemoveResponse,
-        ClearRequest, ClearResponse, DisableMonitorRequest, EnableMonitorRequest,
+        get_data_response::Payload, manager_server::Manager, monitor_server::Monitor,
+        settings_server::Settings, BatchRemoveRequest, BatchRemoveResponse, ClearRequest,
+        ClearResponse, ClipboardEventReply, DeduplicateRequest, DeduplicateResponse,
+        DisableMonitorRequest, EnableMonitorRequest, ExportRequest, ExportResponse,
         GetCurrentClipboardRequest, GetCurrentClipboardResponse, GetCurrentPrimaryRequest,
-        GetCurrentPrimaryResponse, GetMonitorStateRequest, GetRequest, GetResponse, InsertRequest,
-        InsertResponse, LengthRequest, LengthResponse, ListRequest, ListResponse,
+        GetCurrentPrimaryResponse, GetDataHeader, GetDataRequest, GetDataResponse,
+        GetMonitorStateRequest, GetRequest, GetResponse, GetSettingRequest, GetSettingResponse,
+        ImportRequest, ImportResponse, InsertRequest, InsertResponse, InsertSnippetRequest,
+        InsertSnippetResponse, LengthRequest, LengthResponse, ListRequest, ListResponse,
+        ListSnippetsRequest, ListSnippetsResponse, ListTrashRequest, ListTrashResponse,
         MarkAsClipboardRequest, MarkAsClipboardResponse, MarkAsPrimaryRequest,
-        MarkAsPrimaryResponse, MonitorStateReply, RemoveRequest, RemoveResponse,
-        ToggleMonitorRequest, UpdateRequest, UpdateResponse,
+        MarkAsPrimaryResponse, MonitorStateReply, PinRequest, PinResponse, RemoveRequest,
+        RemoveResponse, RestoreFromTrashRequest, RestoreFromTrashResponse, SearchMode,
+        SearchRequest, SearchResponse, SetSettingRequest, SetSettingResponse, SortOrder,
+        StatsRequest, StatsResponse, SubscribeRequest, TagRequest, TagResponse,
+        ToggleMonitorRequest, UndoRequest, UndoResponse, UnpinRequest, UnpinResponse, UntagRequest,
+        UntagResponse, UpdateRequest, UpdateResponse, VacuumRequest, VacuumResponse,
     },
-    ClipboardManager, ClipboardMonitor,
+    ClipboardManager, ClipboardMonitor, SnippetManager,
 };
 
+// Size of the chunks of `GetData` if the client asks for the default size, and
+// the largest size a client may ask for.
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Maintenance of the history store, which is owned by the daemon instead of
+/// the service.
+#[tonic::async_trait]
+pub trait HistoryMaintenance: Send + Sync {
+    /// Rewrites the history store, returns the number of reclaimed bytes.
+    async fn vacuum(&self) -> Result<u64, String>;
+}
+
+/// Settings of the daemon which can be changed while it runs, they are owned
+/// by the daemon instead of the service.
+#[tonic::async_trait]
+pub trait RuntimeSettings: Send + Sync {
+    /// Returns the effective value of `key` as TOML, or the whole
+    /// configuration if `key` is empty.
+    async fn get(&self, key: &str) -> Result<String, String>;
+
+    /// Changes `key` to the TOML `value` and returns its effective value.
+    async fn set(&self, key: &str, value: &str) -> Result<String, String>;
+}
+
 pub struct ManagerService {
-    manager: Arc<Mutex<ClipboardManager>>,
+    manager: Arc<RwLock<ClipboardManager>>,
+    snippets: Arc<Mutex<SnippetManager>>,
+    history: Option<Arc<dyn HistoryMaintenance>>,
 }
 
 impl ManagerService {
-    pub fn new(manager: Arc<Mutex<ClipboardManager>>) -> ManagerService {
-        ManagerService { manager }
+    pub fn new(
+        manager: Arc<RwLock<ClipboardManager>>,
+        snippets: Arc<Mutex<SnippetManager>>,
+    ) -> ManagerService {
+        ManagerService { manager, snippets, history: None }
+    }
+
+    /// Serves `Vacuum` requests with `history`, they are rejected as
+    /// unimplemented otherwise.
+    #[inline]
+    pub fn with_history(mut self, history: Arc<dyn HistoryMaintenance>) -> ManagerService {
+        self.history = Some(history);
+        self
+    }
+
+    // The paste runs on its own task, so the client is answered before the
+    // delay of the paste is over.
+    async fn paste(&self, clipboard_type: crate::ClipboardType) {
+        let (backend, opts) = {
+            let manager = self.manager.read().await;
+            (manager.backend(), manager.paste_options().clone())
+        };
+        tokio::spawn(async move {
+            if let Err(err) = crate::paste::paste(backend, clipboard_type, &opts).await {
+                tracing::warn!("Could not paste clip, error: {}", err);
+            }
+        });
     }
 }
 
 #[tonic::async_trait]
 impl Manager for ManagerService {
+    type GetDataStream = ReceiverStream<Result<GetDataResponse, Status>>;
+
     async fn insert(
         &self,
         request: Request<InsertRequest>,
     ) -> Result<Response<InsertResponse>, Status> {
-        let InsertRequest { data, clipboard_type } = request.into_inner();
+        let InsertRequest { data, clipboard_type, mime, binary, sensitive } = request.into_inner();
         let clipboard_type = clipboard_type.into();
-        let id = {
-            let mut manager = self.manager.lock().await;
-            let id = manager.insert(crate::ClipboardData::new(&data, clipboard_type));
-
-            if clipboard_type == crate::ClipboardType::Clipboard {
-                let _ = manager.mark_as_clipboard(id).await;
-            }
-
-            id
+        let mut clip = if crate::mime::is_text(&mime) {
+            crate::ClipboardData::new(&data, clipboard_type)
+        } else {
+            crate::ClipboardData::from_bytes(&mime, binary, clipboard_type)
         };
+        clip.sensitive = sensitive;
+        let id = self.manager.write().await.insert(clip);
+        if clipboard_type == crate::ClipboardType::Clipboard {
+            let _ = ClipboardManager::promote(&self.manager, id, clipboard_type).await;
+        }
         Ok(Response::new(InsertResponse { id }))
     }
 
@@ -55,7 +122,7 @@ impl Manager for ManagerService {
     ) -> Result<Response<RemoveResponse>, Status> {
         let id = request.into_inner().id;
         let ok = {
-            let mut manager = self.manager.lock().await;
+            let mut manager = self.manager.write().await;
             manager.remove(id)
         };
         Ok(Response::new(RemoveResponse { ok }))
@@ -67,39 +134,144 @@ impl Manager for ManagerService {
     ) -> Result<Response<BatchRemoveResponse>, Status> {
         let ids = request.into_inner().ids;
         let ids = {
-            let mut manager = self.manager.lock().await;
-            ids.into_iter().filter(|id| manager.remove(*id)).collect()
+            let mut manager = self.manager.write().await;
+            manager.batch_remove(&ids)
         };
         Ok(Response::new(BatchRemoveResponse { ids }))
     }
 
     async fn clear(
         &self,
-        _request: Request<ClearRequest>,
+        request: Request<ClearRequest>,
     ) -> Result<Response<ClearResponse>, Status> {
-        {
-            let mut manager = self.manager.lock().await;
-            manager.clear();
-        }
-        Ok(Response::new(ClearResponse {}))
+        let older_than = request.into_inner().older_than;
+        let ids = {
+            let mut manager = self.manager.write().await;
+            if older_than == 0 {
+                manager.clear();
+                Vec::new()
+            } else {
+                manager.clear_older_than(std::time::Duration::from_secs(older_than))
+            }
+        };
+        Ok(Response::new(ClearResponse { ids }))
+    }
+
+    async fn undo(&self, _request: Request<UndoRequest>) -> Result<Response<UndoResponse>, Status> {
+        let ids = {
+            let mut manager = self.manager.write().await;
+            manager.undo()
+        };
+        Ok(Response::new(UndoResponse { ids }))
+    }
+
+    async fn list_trash(
+        &self,
+        request: Request<ListTrashRequest>,
+    ) -> Result<Response<ListTrashResponse>, Status> {
+        let preview_length = request.into_inner().preview_length as usize;
+        let entries = self.manager.read().await.trash();
+        let entries = entries
+            .into_iter()
+            .map(|mut entry| {
+                entry.clip = entry.clip.to_preview(Some(preview_length));
+                entry.into()
+            })
+            .collect();
+        Ok(Response::new(ListTrashResponse { entries }))
+    }
+
+    async fn restore_from_trash(
+        &self,
+        request: Request<RestoreFromTrashRequest>,
+    ) -> Result<Response<RestoreFromTrashResponse>, Status> {
+        let id = request.into_inner().id;
+        let ok = {
+            let mut manager = self.manager.write().await;
+            manager.restore(id)
+        };
+        Ok(Response::new(RestoreFromTrashResponse { ok }))
+    }
+
+    async fn export(
+        &self,
+        request: Request<ExportRequest>,
+    ) -> Result<Response<ExportResponse>, Status> {
+        let ExportRequest { tags, pinned_only } = request.into_inner();
+        let data = {
+            let manager = self.manager.read().await;
+            manager.export(&tags, pinned_only).into_iter().map(Into::into).collect()
+        };
+        Ok(Response::new(ExportResponse { data }))
+    }
+
+    async fn import(
+        &self,
+        request: Request<ImportRequest>,
+    ) -> Result<Response<ImportResponse>, Status> {
+        let clips = request.into_inner().data.into_iter().map(Into::into).collect();
+        let count = {
+            let mut manager = self.manager.write().await;
+            manager.merge(clips)
+        };
+        Ok(Response::new(ImportResponse { count: count as u64 }))
     }
 
     async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
         let GetRequest { id } = request.into_inner();
         let data = {
-            let manager = self.manager.lock().await;
+            let manager = self.manager.read().await;
             manager.get(id).map(Into::into)
         };
         Ok(Response::new(GetResponse { data }))
     }
 
+    async fn get_data(
+        &self,
+        request: Request<GetDataRequest>,
+    ) -> Result<Response<Self::GetDataStream>, Status> {
+        const CHANNEL_SIZE: usize = 4;
+
+        let GetDataRequest { id, chunk_size } = request.into_inner();
+        let chunk_size = match chunk_size as usize {
+            0 => DEFAULT_CHUNK_SIZE,
+            chunk_size => chunk_size.min(MAX_CHUNK_SIZE),
+        };
+        let mut clip = {
+            let manager = self.manager.read().await;
+            manager.get(id)
+        }
+        .ok_or_else(|| Status::not_found(format!("No clip with id {:016x}", id)))?;
+        let body = if clip.is_text() {
+            std::mem::take(&mut clip.data).into_bytes()
+        } else {
+            std::mem::take(&mut clip.binary)
+        };
+        clip.targets.clear();
+
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        tokio::spawn(async move {
+            let header = GetDataHeader { data: Some(clip.into()), size: body.len() as u64 };
+            let responses = std::iter::once(Payload::Header(header))
+                .chain(body.chunks(chunk_size).map(|chunk| Payload::Chunk(chunk.to_vec())));
+            for payload in responses {
+                if tx.send(Ok(GetDataResponse { payload: Some(payload) })).await.is_err() {
+                    tracing::debug!("Client of clip {:016x} is disconnected", id);
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
     async fn get_current_clipboard(
         &self,
         _request: Request<GetCurrentClipboardRequest>,
     ) -> Result<Response<GetCurrentClipboardResponse>, Status> {
         let data = {
-            let manager = self.manager.lock().await;
-            manager.get_current_clipboard().map(|clip| clip.clone().into())
+            let manager = self.manager.read().await;
+            manager.get_current_clipboard().map(|clip| manager.load(clip.clone()).into())
         };
         Ok(Response::new(GetCurrentClipboardResponse { data }))
     }
@@ -109,18 +281,40 @@ impl Manager for ManagerService {
         _request: Request<GetCurrentPrimaryRequest>,
     ) -> Result<Response<GetCurrentPrimaryResponse>, Status> {
         let data = {
-            let manager = self.manager.lock().await;
-            manager.get_current_primary().map(|clip| clip.clone().into())
+            let manager = self.manager.read().await;
+            manager.get_current_primary().map(|clip| manager.load(clip.clone()).into())
         };
         Ok(Response::new(GetCurrentPrimaryResponse { data }))
     }
 
-    async fn list(&self, _request: Request<ListRequest>) -> Result<Response<ListResponse>, Status> {
-        let data = {
-            let manager = self.manager.lock().await;
-            manager.list().into_iter().map(Into::into).collect()
+    async fn list(&self, request: Request<ListRequest>) -> Result<Response<ListResponse>, Status> {
+        let ListRequest { tags, offset, limit, preview_only, preview_length, application, sort } =
+            request.into_inner();
+        let order: Option<crate::SortOrder> =
+            SortOrder::from_i32(sort).unwrap_or(SortOrder::Ranking).into();
+        let application = Some(application.as_str()).filter(|application| !application.is_empty());
+        let (clips, total) = {
+            let manager = self.manager.read().await;
+            let (clips, total) =
+                manager.list_page(&tags, application, order, offset as usize, limit as usize);
+            // previews are made from what is kept in memory
+            if preview_only {
+                (clips, total)
+            } else {
+                (clips.into_iter().map(|clip| manager.load(clip)).collect(), total)
+            }
         };
-        Ok(Response::new(ListResponse { data }))
+        let data = clips
+            .into_iter()
+            .map(|clip| {
+                if preview_only {
+                    clip.to_preview(Some(preview_length as usize)).into()
+                } else {
+                    clip.into()
+                }
+            })
+            .collect();
+        Ok(Response::new(ListResponse { data, total: total as u64 }))
     }
 
     async fn update(
@@ -129,7 +323,7 @@ impl Manager for ManagerService {
     ) -> Result<Response<UpdateResponse>, Status> {
         let UpdateRequest { id, data } = request.into_inner();
         let (ok, new_id) = {
-            let mut manager = self.manager.lock().await;
+            let mut manager = self.manager.write().await;
             manager.replace(id, &data)
         };
         Ok(Response::new(UpdateResponse { ok, new_id }))
@@ -139,11 +333,17 @@ impl Manager for ManagerService {
         &self,
         request: Request<MarkAsClipboardRequest>,
     ) -> Result<Response<MarkAsClipboardResponse>, Status> {
-        let MarkAsClipboardRequest { id } = request.into_inner();
-        let ok = {
-            let mut manager = self.manager.lock().await;
-            manager.mark_as_clipboard(id).await.is_ok()
+        let MarkAsClipboardRequest { id, one_shot, paste } = request.into_inner();
+        let clipboard_type = crate::ClipboardType::Clipboard;
+        let result = if one_shot {
+            ClipboardManager::promote_once(&self.manager, id, clipboard_type).await
+        } else {
+            ClipboardManager::promote(&self.manager, id, clipboard_type).await
         };
+        let ok = result.unwrap_or(false);
+        if ok && paste {
+            self.paste(clipboard_type).await;
+        }
         Ok(Response::new(MarkAsClipboardResponse { ok }))
     }
 
@@ -151,39 +351,186 @@ impl Manager for ManagerService {
         &self,
         request: Request<MarkAsPrimaryRequest>,
     ) -> Result<Response<MarkAsPrimaryResponse>, Status> {
-        let MarkAsPrimaryRequest { id } = request.into_inner();
-        let ok = {
-            let mut manager = self.manager.lock().await;
-            manager.mark_as_primary(id).await.is_ok()
+        let MarkAsPrimaryRequest { id, one_shot, paste } = request.into_inner();
+        let clipboard_type = crate::ClipboardType::Primary;
+        let result = if one_shot {
+            ClipboardManager::promote_once(&self.manager, id, clipboard_type).await
+        } else {
+            ClipboardManager::promote(&self.manager, id, clipboard_type).await
         };
+        let ok = result.unwrap_or(false);
+        if ok && paste {
+            self.paste(clipboard_type).await;
+        }
         Ok(Response::new(MarkAsPrimaryResponse { ok }))
     }
 
+    async fn pin(&self, request: Request<PinRequest>) -> Result<Response<PinResponse>, Status> {
+        let PinRequest { id } = request.into_inner();
+        let ok = {
+            let mut manager = self.manager.write().await;
+            manager.set_pinned(id, true)
+        };
+        Ok(Response::new(PinResponse { ok }))
+    }
+
+    async fn unpin(
+        &self,
+        request: Request<UnpinRequest>,
+    ) -> Result<Response<UnpinResponse>, Status> {
+        let UnpinRequest { id } = request.into_inner();
+        let ok = {
+            let mut manager = self.manager.write().await;
+            manager.set_pinned(id, false)
+        };
+        Ok(Response::new(UnpinResponse { ok }))
+    }
+
+    async fn tag(&self, request: Request<TagRequest>) -> Result<Response<TagResponse>, Status> {
+        let TagRequest { id, tags } = request.into_inner();
+        let ok = {
+            let mut manager = self.manager.write().await;
+            manager.add_tags(id, &tags)
+        };
+        Ok(Response::new(TagResponse { ok }))
+    }
+
+    async fn untag(
+        &self,
+        request: Request<UntagRequest>,
+    ) -> Result<Response<UntagResponse>, Status> {
+        let UntagRequest { id, tags } = request.into_inner();
+        let ok = {
+            let mut manager = self.manager.write().await;
+            manager.remove_tags(id, &tags)
+        };
+        Ok(Response::new(UntagResponse { ok }))
+    }
+
     async fn length(
         &self,
         _request: Request<LengthRequest>,
     ) -> Result<Response<LengthResponse>, Status> {
         let length = {
-            let manager = self.manager.lock().await;
+            let manager = self.manager.read().await;
             manager.len() as u64
         };
         Ok(Response::new(LengthResponse { length }))
     }
+
+    async fn stats(
+        &self,
+        request: Request<StatsRequest>,
+    ) -> Result<Response<StatsResponse>, Status> {
+        let StatsRequest { largest, preview_length } = request.into_inner();
+        let preview_length = if preview_length == 0 { None } else { Some(preview_length as usize) };
+        let stats = {
+            let manager = self.manager.read().await;
+            manager.stats(largest as usize, preview_length)
+        };
+        Ok(Response::new(stats.into()))
+    }
+
+    async fn search(
+        &self,
+        request: Request<SearchRequest>,
+    ) -> Result<Response<SearchResponse>, Status> {
+        let SearchRequest { query, limit, preview_length, mode, context, tags } =
+            request.into_inner();
+        let (limit, preview_length) = (limit as usize, Some(preview_length as usize));
+        let results = match SearchMode::from_i32(mode) {
+            Some(SearchMode::Regex) => {
+                let regex = Regex::new(&query)
+                    .map_err(|err| Status::invalid_argument(format!("Invalid regex: {}", err)))?;
+                let manager = self.manager.read().await;
+                manager.grep(&regex, &tags, context as usize, limit, preview_length)
+            }
+            _ => {
+                let manager = self.manager.read().await;
+                manager.search(&query, &tags, limit, preview_length)
+            }
+        };
+        let results = results.into_iter().map(Into::into).collect();
+        Ok(Response::new(SearchResponse { results }))
+    }
+
+    async fn vacuum(
+        &self,
+        _request: Request<VacuumRequest>,
+    ) -> Result<Response<VacuumResponse>, Status> {
+        let history = match self.history {
+            Some(ref history) => history,
+            None => return Err(Status::unimplemented("history maintenance is not available")),
+        };
+        let reclaimed_bytes = history.vacuum().await.map_err(Status::internal)?;
+        Ok(Response::new(VacuumResponse { reclaimed_bytes }))
+    }
+
+    async fn deduplicate(
+        &self,
+        _request: Request<DeduplicateRequest>,
+    ) -> Result<Response<DeduplicateResponse>, Status> {
+        let merged = {
+            let mut manager = self.manager.write().await;
+            manager.merge_duplicates()
+        };
+        Ok(Response::new(DeduplicateResponse { merged: merged as u64 }))
+    }
+
+    async fn list_snippets(
+        &self,
+        _request: Request<ListSnippetsRequest>,
+    ) -> Result<Response<ListSnippetsResponse>, Status> {
+        let snippets = {
+            let snippets = self.snippets.lock().await;
+            snippets.list().into_iter().map(Into::into).collect()
+        };
+        Ok(Response::new(ListSnippetsResponse { snippets }))
+    }
+
+    async fn insert_snippet(
+        &self,
+        request: Request<InsertSnippetRequest>,
+    ) -> Result<Response<InsertSnippetResponse>, Status> {
+        let InsertSnippetRequest { name, clipboard_type } = request.into_inner();
+        let clipboard_type = clipboard_type.into();
+        let snippet = self.snippets.lock().await.get(&name);
+        let snippet = match snippet {
+            Some(snippet) => snippet,
+            None => return Ok(Response::new(InsertSnippetResponse { ok: false, id: 0 })),
+        };
+
+        let clip = crate::ClipboardData::new(&snippet.data, clipboard_type);
+        let id = self.manager.write().await.insert(clip);
+        let _ = ClipboardManager::promote(&self.manager, id, clipboard_type).await;
+        Ok(Response::new(InsertSnippetResponse { ok: true, id }))
+    }
 }
 
 pub struct MonitorService {
     monitor: Arc<Mutex<ClipboardMonitor>>,
+    dropped_events: Arc<AtomicU64>,
 }
 
 impl MonitorService {
     #[inline]
     pub fn new(monitor: Arc<Mutex<ClipboardMonitor>>) -> MonitorService {
-        MonitorService { monitor }
+        MonitorService { monitor, dropped_events: Arc::default() }
+    }
+
+    /// Counts the events which subscribers lagged behind on in
+    /// `dropped_events`.
+    #[inline]
+    pub fn with_dropped_events(mut self, dropped_events: Arc<AtomicU64>) -> MonitorService {
+        self.dropped_events = dropped_events;
+        self
     }
 }
 
 #[tonic::async_trait]
 impl Monitor for MonitorService {
+    type SubscribeStream = ReceiverStream<Result<ClipboardEventReply, Status>>;
+
     async fn enable_monitor(
         &self,
         _request: Request<EnableMonitorRequest>,
@@ -193,7 +540,6 @@ impl Monitor for MonitorService {
             monitor.enable();
             MonitorStateReply { state: monitor.state().into() }
         };
-        dbg!(&state);
 
         Ok(Response::new(state))
     }
@@ -235,4 +581,70 @@ impl Monitor for MonitorService {
 
         Ok(Response::new(state))
     }
+
+    async fn subscribe(
+        &self,
+        _request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        const CHANNEL_SIZE: usize = 16;
+
+        let mut events = self.monitor.lock().await.subscribe();
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        let dropped_events = self.dropped_events.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(n)) => {
+                        tracing::warn!("Subscriber lagged behind, {} events are skipped", n);
+                        dropped_events.fetch_add(n, Ordering::Relaxed);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                let clip = crate::ClipboardData::from(event);
+                let reply = crate::ClipboardNotification::from(&clip).into();
+                if tx.send(Ok(reply)).await.is_err() {
+                    tracing::debug!("Subscriber is disconnected");
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+pub struct SettingsService {
+    settings: Arc<dyn RuntimeSettings>,
+}
+
+impl SettingsService {
+    #[inline]
+    pub fn new(settings: Arc<dyn RuntimeSettings>) -> SettingsService {
+        SettingsService { settings }
+    }
+}
+
+#[tonic::async_trait]
+impl Settings for SettingsService {
+    async fn get_setting(
+        &self,
+        request: Request<GetSettingRequest>,
+    ) -> Result<Response<GetSettingResponse>, Status> {
+        let GetSettingRequest { key } = request.into_inner();
+        let value = self.settings.get(&key).await.map_err(Status::invalid_argument)?;
+        Ok(Response::new(GetSettingResponse { value }))
+    }
+
+    async fn set_setting(
+        &self,
+        request: Request<SetSettingRequest>,
+    ) -> Result<Response<SetSettingResponse>, Status> {
+        let SetSettingRequest { key, value } = request.into_inner();
+        let value = self.settings.set(&key, &value).await.map_err(Status::invalid_argument)?;
+        Ok(Response::new(SetSettingResponse { value }))
+    }
 }
@@ -0,0 +1,285 @@
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use crate::format::{self, Format};
+
+const KEYWORD_COLOR: &str = "\x1b[35m";
+const STRING_COLOR: &str = "\x1b[32m";
+const NUMBER_COLOR: &str = "\x1b[36m";
+const COMMENT_COLOR: &str = "\x1b[90m";
+const RESET: &str = "\x1b[0m";
+
+// Characters of which code-like text carries at least one, plain sentences
+// rarely do.
+const CODE_CHARACTERS: &[char] =
+    &['(', ')', '{', '}', '[', ']', ';', '=', '$', '|', '&', '<', '>', ':', '#', '/', '-'];
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+    "use", "where", "while",
+];
+const PYTHON_KEYWORDS: &[&str] = &[
+    "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif",
+    "else", "except", "False", "finally", "for", "from", "global", "if", "import", "in", "is",
+    "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True", "try", "while",
+    "with", "yield",
+];
+const JSON_KEYWORDS: &[&str] = &["true", "false", "null"];
+const SHELL_KEYWORDS: &[&str] = &[
+    "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in",
+    "local", "return", "then", "until", "while",
+];
+
+/// Languages which are detected in code-like text clips.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Language {
+    Rust,
+    Python,
+    Json,
+    Shell,
+}
+
+impl Language {
+    pub const ALL: [Language; 4] =
+        [Language::Rust, Language::Python, Language::Json, Language::Shell];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Python => "python",
+            Language::Json => "json",
+            Language::Shell => "shell",
+        }
+    }
+
+    /// Tag which clipcatd attaches to clips in this language.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Language::Rust => "lang:rust",
+            Language::Python => "lang:python",
+            Language::Json => "lang:json",
+            Language::Shell => "lang:shell",
+        }
+    }
+
+    /// Returns the language of a clip which carries `tags`.
+    pub fn from_tags(tags: &BTreeSet<String>) -> Option<Language> {
+        Language::ALL.iter().copied().find(|language| tags.contains(language.tag()))
+    }
+
+    /// Guesses the language of `text`, returns `None` if it does not look like
+    /// code or no language clearly wins.
+    pub fn detect(text: &str) -> Option<Language> {
+        let trimmed = text.trim_start();
+        if let Some(shebang) = trimmed.lines().next().and_then(|line| line.strip_prefix("#!")) {
+            if shebang.contains("python") {
+                return Some(Language::Python);
+            }
+            if ["sh", "bash", "zsh", "fish"].iter().any(|shell| shebang.ends_with(shell)) {
+                return Some(Language::Shell);
+            }
+        }
+        if (trimmed.starts_with('{') || trimmed.starts_with('['))
+            && matches!(format::pretty_print(trimmed), Some((Format::Json, _)))
+        {
+            return Some(Language::Json);
+        }
+        if !text.contains(CODE_CHARACTERS) {
+            return None;
+        }
+
+        let mut scores = [(Language::Rust, 0), (Language::Python, 0), (Language::Shell, 0)];
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            scores[0].1 += rust_score(line);
+            scores[1].1 += python_score(line);
+            scores[2].1 += shell_score(line);
+        }
+        scores.sort_by(|(_, a), (_, b)| b.cmp(a));
+        match scores {
+            [(language, best), (_, second), _] if best >= 2 && best > second => Some(language),
+            _ => None,
+        }
+    }
+
+    fn keywords(self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => RUST_KEYWORDS,
+            Language::Python => PYTHON_KEYWORDS,
+            Language::Json => JSON_KEYWORDS,
+            Language::Shell => SHELL_KEYWORDS,
+        }
+    }
+
+    fn comment(self) -> Option<&'static str> {
+        match self {
+            Language::Rust => Some("//"),
+            Language::Python | Language::Shell => Some("#"),
+            Language::Json => None,
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.name()) }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Language, String> {
+        Language::ALL
+            .iter()
+            .copied()
+            .find(|language| language.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!("Unknown language {}, expected rust, python, json or shell", name)
+            })
+    }
+}
+
+// Starting a line with these weighs more than containing them somewhere.
+fn score(line: &str, prefixes: &[&str], fragments: &[&str]) -> u32 {
+    let prefix = prefixes.iter().filter(|prefix| line.starts_with(*prefix)).count() as u32;
+    let fragment = fragments.iter().filter(|fragment| line.contains(*fragment)).count() as u32;
+    prefix * 2 + fragment
+}
+
+fn rust_score(line: &str) -> u32 {
+    let mut score = score(
+        line,
+        &["fn ", "pub ", "impl ", "use ", "mod ", "struct ", "enum ", "trait ", "#[", "let mut "],
+        &["::", "->", "=>", "&mut ", "&self", "println!", "Some(", "Ok(", "Vec<", "String"],
+    );
+    if line.starts_with("let ") && line.ends_with(';') {
+        score += 2;
+    }
+    score
+}
+
+fn python_score(line: &str) -> u32 {
+    let mut score = score(
+        line,
+        &["def ", "class ", "import ", "elif ", "from ", "if __name__", "except", "@"],
+        &["self.", "None", "True", "False", "__init__", "lambda ", "print("],
+    );
+    if line.ends_with(':') && !line.ends_with("::") {
+        score += 1;
+    }
+    score
+}
+
+fn shell_score(line: &str) -> u32 {
+    score(
+        line,
+        &["$ ", "sudo ", "export ", "echo ", "cd ", "if [", "fi", "done", "apt ", "git ", "ls "],
+        &[" | ", " && ", " || ", "$(", "${", " 2>", " > /", "; then", "; do"],
+    )
+}
+
+/// Highlights keywords, strings, numbers and comments of `line` in
+/// `language` with ANSI colors.
+pub fn highlight(language: Language, line: &str) -> String {
+    let mut highlighted = String::with_capacity(line.len() * 2);
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        if let Some(comment) = language.comment() {
+            if rest.starts_with(comment) {
+                let end = rest.find('\n').unwrap_or(rest.len());
+                paint(&mut highlighted, COMMENT_COLOR, &rest[..end]);
+                rest = &rest[end..];
+                continue;
+            }
+        }
+
+        let len = if c == '"' || (c == '\'' && language != Language::Rust) {
+            let len = string_length(rest, c);
+            paint(&mut highlighted, STRING_COLOR, &rest[..len]);
+            len
+        } else if c.is_alphanumeric() || c == '_' {
+            let len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            let word = &rest[..len];
+            if language.keywords().contains(&word) {
+                paint(&mut highlighted, KEYWORD_COLOR, word);
+            } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+                paint(&mut highlighted, NUMBER_COLOR, word);
+            } else {
+                highlighted.push_str(word);
+            }
+            len
+        } else {
+            highlighted.push(c);
+            c.len_utf8()
+        };
+        rest = &rest[len..];
+    }
+    highlighted
+}
+
+// Length of the string literal at the start of `text` including its quotes, an
+// unterminated literal runs to the end of `text`.
+fn string_length(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (index, c) in text.char_indices().skip(1) {
+        match c {
+            '\\' if !escaped => escaped = true,
+            c if c == quote && !escaped => return index + c.len_utf8(),
+            _ => escaped = false,
+        }
+    }
+    text.len()
+}
+
+fn paint(output: &mut String, color: &str, text: &str) {
+    output.push_str(color);
+    output.push_str(text);
+    output.push_str(RESET);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::language::{highlight, Language};
+
+    #[test]
+    fn test_detect() {
+        let rust = "use std::io;\n\nfn main() -> io::Result<()> {\n    let mut line = \
+                    String::new();\n    Ok(())\n}";
+        assert_eq!(Language::detect(rust), Some(Language::Rust));
+        let python = "import os\n\ndef main():\n    if os.path.exists(path):\n        return None";
+        assert_eq!(Language::detect(python), Some(Language::Python));
+        assert_eq!(Language::detect(" {\"a\": [1, 2]}"), Some(Language::Json));
+        assert_eq!(Language::detect("$ ls -la | grep clipcat"), Some(Language::Shell));
+        assert_eq!(Language::detect("#!/bin/bash\nmake"), Some(Language::Shell));
+        assert_eq!(Language::detect("#!/usr/bin/env python3\nmain()"), Some(Language::Python));
+
+        assert_eq!(Language::detect("let me know when you are done"), None);
+        assert_eq!(Language::detect("see you at 10:30"), None);
+        assert_eq!(Language::detect("{not json"), None);
+    }
+
+    #[test]
+    fn test_tags() {
+        assert_eq!("Python".parse::<Language>(), Ok(Language::Python));
+        assert!("cobol".parse::<Language>().is_err());
+
+        let tags = vec!["work".to_owned(), Language::Shell.tag().to_owned()].into_iter().collect();
+        assert_eq!(Language::from_tags(&tags), Some(Language::Shell));
+        assert_eq!(Language::from_tags(&Default::default()), None);
+    }
+
+    #[test]
+    fn test_highlight() {
+        assert_eq!(
+            highlight(Language::Rust, "let x = \"a\\\"b\"; // 1"),
+            "\x1b[35mlet\x1b[0m x = \x1b[32m\"a\\\"b\"\x1b[0m; \x1b[90m// 1\x1b[0m"
+        );
+        assert_eq!(
+            highlight(Language::Json, "{\"n\": 10, \"b\": null}"),
+            "{\x1b[32m\"n\"\x1b[0m: \x1b[36m10\x1b[0m, \x1b[32m\"b\"\x1b[0m: \x1b[35mnull\x1b[0m}"
+        );
+        assert_eq!(
+            highlight(Language::Shell, "echo 'é' # x"),
+            "echo \x1b[32m'é'\x1b[0m \x1b[90m# x\x1b[0m"
+        );
+    }
+}
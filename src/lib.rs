@@ -6,6 +6,7 @@ extern crate snafu;
 
 use std::{
     cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
     hash::{Hash, Hasher},
     time::SystemTime,
 };
@@ -18,17 +19,52 @@ pub mod grpc;
 mod error;
 mod event;
 
+#[cfg(feature = "monitor")]
+mod backend;
 #[cfg(feature = "monitor")]
 mod manager;
 #[cfg(feature = "monitor")]
 mod monitor;
+#[cfg(feature = "monitor")]
+pub mod paste;
 
+#[cfg(feature = "actions")]
+pub mod action;
+pub mod age;
+pub mod color;
 pub mod editor;
+pub mod export;
+pub mod format;
+pub mod language;
+pub mod mime;
+#[cfg(feature = "ocr")]
+pub mod ocr;
+#[cfg(feature = "qr")]
+pub mod qr;
+mod ranking;
+mod search;
+mod snippet;
+pub mod spill;
+pub mod stats;
+pub mod template;
+pub mod trash;
+#[cfg(feature = "actions")]
+pub mod typing;
+pub mod urls;
+
+pub use self::{
+    error::ClipboardError,
+    event::{ClipboardEvent, ClipboardNotification},
+    ranking::{Ranking, SortOrder},
+    search::{fuzzy_score, SearchLine, SearchResult},
+    snippet::{Snippet, SnippetManager},
+    spill::SpilledBody,
+};
 
-pub use self::{error::ClipboardError, event::ClipboardEvent};
-
 #[cfg(feature = "monitor")]
-pub use self::manager::ClipboardManager;
+pub use self::backend::{ClipboardBackendType, LineEndings};
+#[cfg(feature = "monitor")]
+pub use self::manager::{ClipboardManager, MemoryPressure, PendingClear, PendingMark};
 #[cfg(feature = "monitor")]
 pub use self::monitor::{ClipboardMonitor, ClipboardMonitorOptions};
 
@@ -55,6 +91,9 @@ pub const DEFAULT_GRPC_HOST: &str = "127.0.0.1";
 pub const DEFAULT_WEBUI_PORT: u16 = 45046;
 pub const DEFAULT_WEBUI_HOST: &str = "127.0.0.1";
 
+pub const DEFAULT_METRICS_PORT: u16 = 45047;
+pub const DEFAULT_METRICS_HOST: &str = "127.0.0.1";
+
 #[derive(Debug, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, Clone, Copy, Hash)]
 pub enum ClipboardType {
     Clipboard = 0,
@@ -76,7 +115,53 @@ pub struct ClipboardData {
     pub id: u64,
     pub data: String,
     pub clipboard_type: ClipboardType,
+
+    /// When the clip was captured or last copied or promoted again.
     pub timestamp: SystemTime,
+
+    /// When the clip was captured first, the Unix epoch if it is unknown.
+    #[serde(default = "ClipboardData::default_created_at")]
+    pub created_at: SystemTime,
+
+    /// MIME type of the clip, text clips are stored in `data`, others are
+    /// stored in `binary`.
+    #[serde(default = "ClipboardData::default_mime")]
+    pub mime: String,
+
+    #[serde(default)]
+    pub binary: Vec<u8>,
+
+    /// Alternative representations of the clip keyed by MIME type, e.g.
+    /// `text/html`.
+    #[serde(default)]
+    pub targets: BTreeMap<String, Vec<u8>>,
+
+    /// Pinned clips are never evicted when the history is shrunk.
+    #[serde(default)]
+    pub pinned: bool,
+
+    /// Labels attached by the user, e.g. `work`.
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+
+    /// Sensitive clips expire after a timeout and are never written to the
+    /// history.
+    #[serde(default)]
+    pub sensitive: bool,
+
+    /// How often the clip was copied or promoted again after it was captured.
+    #[serde(default)]
+    pub use_count: u64,
+
+    /// Window class, window title or Wayland app ID of the application the
+    /// clip was copied from, if it is known.
+    #[serde(default)]
+    pub application: Option<String>,
+
+    /// Where the body of a large clip was spilled to, `data` or `binary` only
+    /// hold a preview of it then and `targets` are empty.
+    #[serde(skip)]
+    pub spilled: Option<SpilledBody>,
 }
 
 impl ClipboardData {
@@ -88,25 +173,203 @@ impl ClipboardData {
     }
 
     pub fn new_clipboard(data: &str) -> ClipboardData {
+        let now = SystemTime::now();
         ClipboardData {
             id: Self::compute_id(data),
             data: data.to_owned(),
             clipboard_type: ClipboardType::Clipboard,
-            timestamp: SystemTime::now(),
+            timestamp: now,
+            created_at: now,
+            mime: Self::default_mime(),
+            binary: Vec::new(),
+            targets: BTreeMap::new(),
+            pinned: false,
+            tags: BTreeSet::new(),
+            sensitive: false,
+            use_count: 0,
+            application: None,
+            spilled: None,
         }
     }
 
     pub fn new_primary(data: &str) -> ClipboardData {
+        let now = SystemTime::now();
         ClipboardData {
             id: Self::compute_id(data),
             data: data.to_owned(),
             clipboard_type: ClipboardType::Primary,
-            timestamp: SystemTime::now(),
+            timestamp: now,
+            created_at: now,
+            mime: Self::default_mime(),
+            binary: Vec::new(),
+            targets: BTreeMap::new(),
+            pinned: false,
+            tags: BTreeSet::new(),
+            sensitive: false,
+            use_count: 0,
+            application: None,
+            spilled: None,
+        }
+    }
+
+    /// Creates a clip from raw bytes, text MIME types are decoded as UTF-8.
+    pub fn from_bytes(mime: &str, bytes: Vec<u8>, clipboard_type: ClipboardType) -> ClipboardData {
+        if mime::is_text(mime) {
+            return Self::new(&String::from_utf8_lossy(&bytes), clipboard_type);
+        }
+
+        let now = SystemTime::now();
+        ClipboardData {
+            id: Self::compute_id(&bytes[..]),
+            data: String::new(),
+            clipboard_type,
+            timestamp: now,
+            created_at: now,
+            mime: mime.to_owned(),
+            binary: bytes,
+            targets: BTreeMap::new(),
+            pinned: false,
+            tags: BTreeSet::new(),
+            sensitive: false,
+            use_count: 0,
+            application: None,
+            spilled: None,
+        }
+    }
+
+    #[inline]
+    pub fn default_mime() -> String { mime::TEXT_PLAIN.to_owned() }
+
+    #[inline]
+    pub fn default_created_at() -> SystemTime { SystemTime::UNIX_EPOCH }
+
+    #[inline]
+    pub fn is_text(&self) -> bool { mime::is_text(&self.mime) }
+
+    #[inline]
+    pub fn is_image(&self) -> bool { mime::is_image(&self.mime) }
+
+    #[inline]
+    pub fn is_file_list(&self) -> bool { mime::is_file_list(&self.mime) }
+
+    /// Returns what the clip holds, `text`, `url`, `image`, `files` or
+    /// `binary`. Previews of binary clips are told apart by their printable
+    /// data.
+    pub fn kind(&self) -> &'static str {
+        if self.is_image() {
+            return "image";
+        } else if self.is_file_list() {
+            return "files";
+        } else if !self.is_text() {
+            return "binary";
+        }
+
+        let text = self.data.trim();
+        if text.starts_with("[image/") {
+            "image"
+        } else if text.starts_with('[') && text.contains(" file(s)] ") {
+            "files"
+        } else if !text.contains(char::is_whitespace) && !urls::find(text).is_empty() {
+            "url"
+        } else {
+            "text"
+        }
+    }
+
+    /// Returns the URIs of copied files, the list is empty if the clip is not a
+    /// file list.
+    #[inline]
+    pub fn file_list(&self) -> Vec<String> {
+        if self.is_file_list() {
+            mime::parse_uri_list(&self.binary)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Returns the content of the clip as it is offered to the clipboard.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.is_text() {
+            self.data.as_bytes()
+        } else {
+            &self.binary
         }
     }
 
+    /// Returns the size of the content, spilled clips count with their whole
+    /// body.
     #[inline]
-    pub fn compute_id(data: &str) -> u64 {
+    pub fn size(&self) -> usize {
+        self.spilled.map_or_else(|| self.as_bytes().len(), |spilled| spilled.size)
+    }
+
+    /// Returns the size of the clip including its alternative representations.
+    #[inline]
+    pub fn total_size(&self) -> usize {
+        match self.spilled {
+            Some(spilled) => spilled.total_size,
+            None => self.size() + self.targets.values().map(Vec::len).sum::<usize>(),
+        }
+    }
+
+    /// Returns the number of bytes of the content which are held in memory,
+    /// spilled clips only count with their previews.
+    #[inline]
+    pub fn resident_size(&self) -> usize {
+        self.data.len() + self.binary.len() + self.targets.values().map(Vec::len).sum::<usize>()
+    }
+
+    /// Returns all representations of the clip which are offered to other
+    /// applications.
+    pub fn offered_targets(&self) -> Vec<(String, Vec<u8>)> {
+        let mut targets = vec![(self.mime.clone(), self.as_bytes().to_vec())];
+        targets.extend(self.targets.iter().map(|(mime, data)| (mime.clone(), data.clone())));
+
+        // file managers expect their own targets, offer those which the clip lacks
+        if self.is_file_list() {
+            let uris = self.file_list();
+            if !self.targets.contains_key(mime::GNOME_COPIED_FILES) {
+                targets.push((
+                    mime::GNOME_COPIED_FILES.to_owned(),
+                    mime::format_gnome_copied_files(&uris),
+                ));
+            }
+            if !self.targets.keys().any(|mime| mime::is_text(mime)) {
+                let paths: Vec<_> = uris.iter().map(|uri| mime::uri_to_path(uri)).collect();
+                targets.push((mime::TEXT_PLAIN.to_owned(), paths.join("\n").into_bytes()));
+            }
+        }
+
+        targets
+    }
+
+    /// Returns `true` if the clip carries all of `tags`.
+    #[inline]
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.contains(tag))
+    }
+
+    /// Returns `true` if the clip was copied from `application`, names are
+    /// compared case-insensitively.
+    #[inline]
+    pub fn is_from(&self, application: &str) -> bool {
+        self.application
+            .as_ref()
+            .is_some_and(|app| app.to_lowercase() == application.to_lowercase())
+    }
+
+    #[inline]
+    pub fn image_dimensions(&self) -> Option<(u32, u32)> {
+        if self.is_image() {
+            mime::image_dimensions(&self.binary)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn compute_id<T: Hash + ?Sized>(data: &T) -> u64 {
         use std::collections::hash_map::DefaultHasher;
         let mut s = DefaultHasher::new();
         data.hash(&mut s);
@@ -121,7 +384,18 @@ impl ClipboardData {
             }
         }
 
-        let data = self.data.clone();
+        let data = if self.is_file_list() {
+            let paths: Vec<_> = self.file_list().iter().map(|uri| mime::uri_to_path(uri)).collect();
+            format!("[{} file(s)] {}", paths.len(), paths.join(" "))
+        } else if !self.is_text() {
+            let size = mime::human_readable_size(self.size());
+            return match self.image_dimensions() {
+                Some((width, height)) => format!("[{} {}x{}, {}]", self.mime, width, height, size),
+                None => format!("[{}, {}]", self.mime, size),
+            };
+        } else {
+            self.data.clone()
+        };
         let data = match line_length {
             None | Some(0) => data,
             Some(limit) => {
@@ -145,6 +419,27 @@ impl ClipboardData {
         data.replace("\n", "\\n").replace("\r", "\\r").replace("\t", "\\t")
     }
 
+    /// Returns a text clip which only carries the printable data of this clip,
+    /// the ID and other metadata are kept.
+    pub fn to_preview(&self, line_length: Option<usize>) -> ClipboardData {
+        ClipboardData {
+            id: self.id,
+            data: self.printable_data(line_length),
+            clipboard_type: self.clipboard_type,
+            timestamp: self.timestamp,
+            created_at: self.created_at,
+            mime: mime::TEXT_PLAIN.to_owned(),
+            binary: Vec::new(),
+            targets: BTreeMap::new(),
+            pinned: self.pinned,
+            tags: self.tags.clone(),
+            sensitive: self.sensitive,
+            use_count: self.use_count,
+            application: self.application.clone(),
+            spilled: None,
+        }
+    }
+
     #[inline]
     pub fn mark_as_clipboard(&mut self) {
         self.clipboard_type = ClipboardType::Clipboard;
@@ -160,10 +455,29 @@ impl ClipboardData {
 
 impl From<ClipboardEvent> for ClipboardData {
     fn from(event: ClipboardEvent) -> ClipboardData {
-        let ClipboardEvent { data, clipboard_type } = event;
-        let id = Self::compute_id(&data);
+        let ClipboardEvent { data, clipboard_type, mime, binary, targets, application, .. } = event;
+        let id = if mime::is_text(&mime) {
+            Self::compute_id(&data)
+        } else {
+            Self::compute_id(&binary[..])
+        };
         let timestamp = SystemTime::now();
-        ClipboardData { id, data, clipboard_type, timestamp }
+        ClipboardData {
+            id,
+            data,
+            clipboard_type,
+            timestamp,
+            created_at: timestamp,
+            mime,
+            binary,
+            targets,
+            pinned: false,
+            tags: BTreeSet::new(),
+            sensitive: false,
+            use_count: 0,
+            application,
+            spilled: None,
+        }
     }
 }
 
@@ -174,12 +488,22 @@ impl Default for ClipboardData {
             data: Default::default(),
             clipboard_type: ClipboardType::Primary,
             timestamp: SystemTime::UNIX_EPOCH,
+            created_at: SystemTime::UNIX_EPOCH,
+            mime: Self::default_mime(),
+            binary: Vec::new(),
+            targets: BTreeMap::new(),
+            pinned: false,
+            tags: BTreeSet::new(),
+            sensitive: false,
+            use_count: 0,
+            application: None,
+            spilled: None,
         }
     }
 }
 
 impl PartialEq for ClipboardData {
-    fn eq(&self, other: &Self) -> bool { self.data == other.data }
+    fn eq(&self, other: &Self) -> bool { self.data == other.data && self.binary == other.binary }
 }
 
 impl PartialOrd for ClipboardData {
@@ -196,7 +520,10 @@ impl Ord for ClipboardData {
 }
 
 impl Hash for ClipboardData {
-    fn hash<H: Hasher>(&self, state: &mut H) { self.data.hash(state); }
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.data.hash(state);
+        self.binary.hash(state);
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy, Hash)]
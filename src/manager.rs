@@ -1,16 +1,130 @@
-use std::{collections::HashMap, time::SystemTime};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, SystemTime},
+};
+
+use regex::Regex;
+use tokio::sync::{broadcast, RwLock};
+
+use crate::{
+    backend, fuzzy_score,
+    paste::PasteOptions,
+    search::grep_lines,
+    spill::{BodyLoader, SpillStore},
+    stats::HistoryStats,
+    trash::{Trash, TrashEntry},
+    ClipboardBackendType, ClipboardData, ClipboardError, ClipboardType, Ranking, SearchResult,
+    SortOrder,
+};
 
-use snafu::ResultExt;
+const DEFAULT_CAPACITY: usize = 40;
 
-use crate::{error, ClipboardData, ClipboardError, ClipboardType};
+// Removals which are kept for subscribers which lag behind.
+const REMOVAL_CHANNEL_CAPACITY: usize = 64;
 
-const DEFAULT_CAPACITY: usize = 40;
+// Clips which are held in memory with at most this many bytes are evicted
+// instead of being spilled under memory pressure, their previews would not be
+// smaller.
+const MIN_PRESSURE_SPILL_SIZE: usize = 4096;
+
+/// Clips which were spilled or evicted since start up because the memory
+/// budget was exceeded, see `ClipboardManager::set_memory_budget`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct MemoryPressure {
+    pub spilled: u64,
+    pub evicted: u64,
+}
+
+/// A clip which is marked by `ClipboardManager::prepare_mark` but not stored
+/// in its selection yet.
+pub struct PendingMark {
+    id: u64,
+    clipboard_type: ClipboardType,
+    backend: ClipboardBackendType,
+    clip: ClipboardData,
+
+    // displays which the clip is mirrored to once it is stored
+    mirrors: Vec<String>,
+}
+
+impl PendingMark {
+    /// Stores the clip in its selection on `display`, or on the default display
+    /// if it is `None`. The manager does not have to be locked meanwhile.
+    pub async fn store(&self, display: Option<&str>) -> Result<(), ClipboardError> {
+        backend::store(self.backend, display, &self.clip, self.clipboard_type).await
+    }
+
+    /// Offers the clip for a single paste, see `backend::store_once`.
+    pub async fn store_once(&self) -> Result<(), ClipboardError> {
+        backend::store_once(self.backend, &self.clip, self.clipboard_type).await
+    }
+
+    // The default display decides whether marking succeeds, the other
+    // displays may be gone, e.g. when a nested X server is closed.
+    async fn mirror(&self) {
+        for mirror in &self.mirrors {
+            if let Err(err) =
+                backend::store(self.backend, Some(mirror.as_str()), &self.clip, self.clipboard_type)
+                    .await
+            {
+                tracing::warn!(
+                    "Could not offer clip {:016x} on {}, error: {}",
+                    self.id,
+                    mirror,
+                    err
+                );
+            }
+        }
+    }
+}
+
+/// Selections which are cleared by `ClipboardManager::expire_sensitive`, they
+/// are cleared without locking the manager.
+pub struct PendingClear {
+    backend: ClipboardBackendType,
+    displays: Vec<String>,
+    clipboard_types: Vec<ClipboardType>,
+}
+
+impl PendingClear {
+    /// Clears the selections on the default display and the other displays.
+    pub async fn clear(&self) -> Result<(), ClipboardError> {
+        for &clipboard_type in &self.clipboard_types {
+            let empty = ClipboardData::new("", clipboard_type);
+            backend::store(self.backend, None, &empty, clipboard_type).await?;
+            for name in &self.displays {
+                if let Err(err) =
+                    backend::store(self.backend, Some(name.as_str()), &empty, clipboard_type).await
+                {
+                    tracing::warn!("Could not clear selection of {}, error: {}", name, err);
+                }
+            }
+        }
+        Ok(())
+    }
+}
 
 pub struct ClipboardManager {
     clips: HashMap<u64, ClipboardData>,
     capacity: usize,
     current_clipboard: Option<ClipboardData>,
     current_primary: Option<ClipboardData>,
+    backend: ClipboardBackendType,
+    deduplicate: bool,
+    max_bytes: Option<usize>,
+    max_age: Option<Duration>,
+    primary_capacity: Option<usize>,
+    ranking: Ranking,
+    paste_options: PasteOptions,
+    displays: Vec<String>,
+    mirror_promotions: bool,
+    removal_sender: broadcast::Sender<ClipboardData>,
+    paste_once_sender: broadcast::Sender<u64>,
+    trash: Trash,
+    spill: Option<SpillStore>,
+    body_loader: Option<Box<dyn BodyLoader>>,
+    memory_budget: Option<usize>,
+    memory_pressure: MemoryPressure,
 }
 
 impl Default for ClipboardManager {
@@ -24,9 +138,38 @@ impl ClipboardManager {
             clips: HashMap::default(),
             current_clipboard: None,
             current_primary: None,
+            backend: ClipboardBackendType::Auto,
+            deduplicate: true,
+            max_bytes: None,
+            max_age: None,
+            primary_capacity: None,
+            ranking: Ranking::default(),
+            paste_options: PasteOptions::default(),
+            displays: Vec::new(),
+            mirror_promotions: false,
+            removal_sender: broadcast::channel(REMOVAL_CHANNEL_CAPACITY).0,
+            paste_once_sender: broadcast::channel(REMOVAL_CHANNEL_CAPACITY).0,
+            trash: Trash::default(),
+            spill: None,
+            body_loader: None,
+            memory_budget: None,
+            memory_pressure: MemoryPressure::default(),
         }
     }
 
+    /// Subscribes to clips which are removed, evicted, expired or cleared.
+    #[inline]
+    pub fn subscribe_removals(&self) -> broadcast::Receiver<ClipboardData> {
+        self.removal_sender.subscribe()
+    }
+
+    /// Subscribes to IDs of clips which are dropped after being offered for a
+    /// single paste, stored copies of them have to be deleted as well.
+    #[inline]
+    pub fn subscribe_pasted_once(&self) -> broadcast::Receiver<u64> {
+        self.paste_once_sender.subscribe()
+    }
+
     #[inline]
     pub fn new() -> ClipboardManager { Self::default() }
 
@@ -36,26 +179,302 @@ impl ClipboardManager {
     #[inline]
     pub fn set_capacity(&mut self, v: usize) { self.capacity = v; }
 
+    #[inline]
+    pub fn primary_capacity(&self) -> Option<usize> { self.primary_capacity }
+
+    /// Gives primary clips their own capacity, `capacity` then only applies to
+    /// clipboard clips.
+    #[inline]
+    pub fn set_primary_capacity(&mut self, v: Option<usize>) { self.primary_capacity = v; }
+
+    /// Returns the number of unpinned clips which are kept at most.
+    #[inline]
+    pub fn total_capacity(&self) -> usize { self.capacity + self.primary_capacity.unwrap_or(0) }
+
+    #[inline]
+    pub fn ranking(&self) -> Ranking { self.ranking }
+
+    /// Sets the order of clips which are returned by `list_tagged`.
+    #[inline]
+    pub fn set_ranking(&mut self, ranking: Ranking) { self.ranking = ranking; }
+
+    #[inline]
+    pub fn backend(&self) -> ClipboardBackendType { self.backend }
+
+    #[inline]
+    pub fn set_backend(&mut self, backend: ClipboardBackendType) {
+        self.backend = backend.resolve();
+    }
+
+    #[inline]
+    pub fn paste_options(&self) -> &PasteOptions { &self.paste_options }
+
+    /// Sets how clips are pasted when a client asks for a paste after marking
+    /// a clip.
+    #[inline]
+    pub fn set_paste_options(&mut self, opts: PasteOptions) { self.paste_options = opts; }
+
+    #[inline]
+    pub fn displays(&self) -> &[String] { &self.displays }
+
+    /// Sets the X11 displays or Wayland seats which are monitored besides the
+    /// default one, and whether clips which are marked are offered on all of
+    /// them.
+    #[inline]
+    pub fn set_displays(&mut self, displays: Vec<String>, mirror_promotions: bool) {
+        self.displays = displays;
+        self.mirror_promotions = mirror_promotions;
+    }
+
+    #[inline]
+    pub fn deduplicate(&self) -> bool { self.deduplicate }
+
+    /// Sets whether inserting a clip whose content is already in the manager
+    /// bumps the existing clip instead of adding another one.
+    #[inline]
+    pub fn set_deduplicate(&mut self, v: bool) { self.deduplicate = v; }
+
+    #[inline]
+    pub fn max_bytes(&self) -> Option<usize> { self.max_bytes }
+
+    /// Sets the total size in bytes which unpinned clips may occupy, large and
+    /// old clips are evicted first when it is exceeded.
+    #[inline]
+    pub fn set_max_bytes(&mut self, v: Option<usize>) { self.max_bytes = v; }
+
+    #[inline]
+    pub fn memory_budget(&self) -> Option<usize> { self.memory_budget }
+
+    /// Sets how many bytes of clips may be held in memory. When it is
+    /// exceeded, clips which are large and old are spilled to disk if there is
+    /// a spill store, see `set_spill`, or evicted otherwise. Pinned clips are
+    /// only spilled, sensitive clips are never spilled but evicted.
+    pub fn set_memory_budget(&mut self, v: Option<usize>) {
+        self.memory_budget = v;
+        self.relieve_memory_pressure();
+        self.remove_unused_spills();
+    }
+
+    /// Returns the number of bytes of the clips which are held in memory, see
+    /// `ClipboardData::resident_size`.
+    #[inline]
+    pub fn resident_bytes(&self) -> usize {
+        self.clips.values().map(|clip| clip.resident_size()).sum()
+    }
+
+    #[inline]
+    pub fn memory_pressure(&self) -> MemoryPressure { self.memory_pressure }
+
+    #[inline]
+    pub fn max_age(&self) -> Option<Duration> { self.max_age }
+
+    /// Sets how long unpinned clips are kept after they were last copied, see
+    /// `remove_aged`.
+    #[inline]
+    pub fn set_max_age(&mut self, v: Option<Duration>) { self.max_age = v; }
+
+    /// Keeps the bodies of clips which are larger than the threshold of
+    /// `spill` on disk and only their previews in memory, bodies are loaded
+    /// again by `get`, `list`, `export` and when clips are marked. Clips which
+    /// are stored already are moved to `spill`, or back into memory if it is
+    /// `None`.
+    pub fn set_spill(&mut self, spill: Option<SpillStore>) {
+        let clips = std::mem::take(&mut self.clips);
+        let clips: Vec<_> = clips.into_values().map(|clip| self.load(clip)).collect();
+        let current_clipboard = self.current_clipboard.take().map(|clip| self.load(clip));
+        let current_primary = self.current_primary.take().map(|clip| self.load(clip));
+        self.spill = spill;
+
+        for clip in clips {
+            let clip = self.spill_clip(clip);
+            self.clips.insert(clip.id, clip);
+        }
+        self.current_clipboard = current_clipboard.map(|clip| self.spill_clip(clip));
+        self.current_primary = current_primary.map(|clip| self.spill_clip(clip));
+        self.remove_unused_spills();
+    }
+
+    /// Sets where the bodies of clips which were imported as previews are
+    /// loaded from, see `spill::strip_body`.
+    #[inline]
+    pub fn set_body_loader(&mut self, loader: Option<Box<dyn BodyLoader>>) {
+        self.body_loader = loader;
+    }
+
+    /// Puts the body of `clip` back if it was spilled to disk or imported as a
+    /// preview, see `set_spill` and `set_body_loader`. The preview is kept if
+    /// the body can not be read.
+    pub fn load(&self, mut clip: ClipboardData) -> ClipboardData {
+        let spilled = match clip.spilled {
+            Some(spilled) => spilled,
+            None => return clip,
+        };
+
+        let result = match (spilled.hash, &self.spill, &self.body_loader) {
+            (Some(_), Some(spill), _) => spill.load(&mut clip).map_err(|err| err.to_string()),
+            (None, _, Some(loader)) => loader.load_body(clip.id).map(|body| {
+                if let Some(body) = body {
+                    clip.data = body.data;
+                    clip.binary = body.binary;
+                    clip.targets = body.targets;
+                    clip.spilled = None;
+                }
+            }),
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            tracing::warn!("Could not load body of clip {:016x}, error: {}", clip.id, err);
+        }
+        clip
+    }
+
+    // Sensitive clips are never written to disk, a spilled clip which turned
+    // sensitive is loaded again.
+    fn spill_clip(&mut self, mut clip: ClipboardData) -> ClipboardData {
+        if clip.sensitive {
+            return self.load(clip);
+        }
+
+        if let Some(spill) = self.spill.as_mut() {
+            if let Err(err) = spill.spill(&mut clip) {
+                tracing::warn!("Could not spill clip {:016x}, error: {}", clip.id, err);
+            }
+        }
+        clip
+    }
+
+    // Files are removed once no clip refers to them, neither in the history
+    // nor in the trash.
+    fn remove_unused_spills(&mut self) {
+        let spill = match self.spill.as_mut() {
+            Some(spill) if !spill.is_empty() => spill,
+            _ => return,
+        };
+
+        let hashes: HashSet<_> = self
+            .clips
+            .values()
+            .chain(self.current_clipboard.iter())
+            .chain(self.current_primary.iter())
+            .chain(self.trash.clips())
+            .filter_map(|clip| clip.spilled.and_then(|spilled| spilled.hash))
+            .collect();
+        spill.retain(&hashes);
+    }
+
     #[inline]
     pub fn import(&mut self, clips: &[ClipboardData]) { self.import_iter(clips.iter()); }
 
     #[inline]
     pub fn import_iter<'a>(&'a mut self, clips_iter: impl Iterator<Item = &'a ClipboardData>) {
-        self.clips = clips_iter.fold(HashMap::new(), |mut clips, clip| {
-            clips.insert(clip.id, clip.clone());
-            clips
-        });
+        let mut clips = HashMap::new();
+        for clip in clips_iter {
+            let clip = self.spill_clip(clip.clone());
+            clips.insert(clip.id, clip);
+        }
+        self.clips = clips;
+        self.remove_oldest();
+    }
+
+    /// Adds `clips` which are not stored yet without changing the current
+    /// selections, returns the number of added clips.
+    pub fn merge(&mut self, clips: Vec<ClipboardData>) -> usize {
+        let mut count = 0;
+        for clip in clips {
+            if !self.clips.contains_key(&clip.id) {
+                let clip = self.spill_clip(clip);
+                self.clips.insert(clip.id, clip);
+                count += 1;
+            }
+        }
         self.remove_oldest();
+        count
     }
 
+    /// Collapses clips with the same content and MIME type into the most
+    /// recently copied one, which then counts the uses and carries the tags of
+    /// all of them. Returns the number of clips which were merged into
+    /// another one, e.g. after clips were imported or copied without
+    /// `set_deduplicate`.
+    pub fn merge_duplicates(&mut self) -> usize {
+        #[derive(Eq, PartialEq, Hash)]
+        enum Digest {
+            Content(u64),
+            Spilled(u64),
+        }
+
+        let mut groups: HashMap<(String, Digest), Vec<(SystemTime, u64)>> = HashMap::new();
+        for clip in self.clips.values().filter(|clip| clip.spilled.is_none()) {
+            let digest = Digest::Content(ClipboardData::compute_id(clip.as_bytes()));
+            groups.entry((clip.mime.clone(), digest)).or_default().push((clip.timestamp, clip.id));
+        }
+        // spilled clips are compared by the digest of their file, or by their
+        // ID if the body is only kept in the history, bodies are only loaded
+        // if the ID matches the content of a clip held in memory
+        for clip in self.clips.values() {
+            let spilled = match clip.spilled {
+                Some(spilled) => spilled,
+                None => continue,
+            };
+            let digest = if groups.contains_key(&(clip.mime.clone(), Digest::Content(clip.id))) {
+                Digest::Content(ClipboardData::compute_id(self.load(clip.clone()).as_bytes()))
+            } else {
+                Digest::Spilled(spilled.hash.unwrap_or(clip.id))
+            };
+            groups.entry((clip.mime.clone(), digest)).or_default().push((clip.timestamp, clip.id));
+        }
+
+        let mut merged = 0;
+        for (_, mut group) in groups.into_iter().filter(|(_, group)| group.len() > 1) {
+            group.sort();
+            let (_, id) = group.pop().expect("group is not empty");
+            let duplicates: Vec<_> =
+                group.into_iter().filter_map(|(_, id)| self.clips.remove(&id)).collect();
+            self.modify(id, |clip| {
+                for duplicate in &duplicates {
+                    clip.use_count += duplicate.use_count;
+                    clip.pinned |= duplicate.pinned;
+                    clip.sensitive |= duplicate.sensitive;
+                    clip.created_at = clip.created_at.min(duplicate.created_at);
+                    clip.tags.extend(duplicate.tags.iter().cloned());
+                }
+            });
+
+            // the current clips refer to the remaining clip instead
+            let clip = self.clips.get(&id).cloned();
+            for current in &mut [&mut self.current_clipboard, &mut self.current_primary] {
+                let current_id = current.as_ref().map(|current| current.id);
+                if duplicates.iter().any(|duplicate| Some(duplicate.id) == current_id) {
+                    **current = clip.clone();
+                }
+            }
+
+            merged += duplicates.len();
+            for duplicate in duplicates {
+                let _ = self.removal_sender.send(duplicate);
+            }
+        }
+
+        if merged > 0 {
+            self.remove_unused_spills();
+        }
+        merged
+    }
+
+    /// Lists all clips with their bodies, see `set_spill`.
     #[inline]
-    pub fn list(&self) -> Vec<ClipboardData> { self.iter().cloned().collect() }
+    pub fn list(&self) -> Vec<ClipboardData> {
+        self.iter().map(|clip| self.load(clip.clone())).collect()
+    }
 
+    /// Iterates over the clips, spilled clips only carry their previews.
     #[inline]
     pub fn iter(&self) -> impl Iterator<Item = &ClipboardData> { self.clips.values() }
 
     #[inline]
-    pub fn get(&self, id: u64) -> Option<ClipboardData> { self.clips.get(&id).map(Clone::clone) }
+    pub fn get(&self, id: u64) -> Option<ClipboardData> {
+        self.clips.get(&id).map(|clip| self.load(clip.clone()))
+    }
 
     #[inline]
     pub fn get_current_clipboard(&self) -> Option<&ClipboardData> {
@@ -70,17 +489,43 @@ impl ClipboardManager {
 
     #[inline]
     pub fn insert_clipboard(&mut self, data: &str) -> u64 {
-        let data = ClipboardData::new_clipboard(&data);
+        let data = ClipboardData::new_clipboard(data);
         self.insert_inner(data)
     }
 
     #[inline]
     pub fn insert_primary(&mut self, data: &str) -> u64 {
-        let data = ClipboardData::new_primary(&data);
+        let data = ClipboardData::new_primary(data);
         self.insert_inner(data)
     }
 
-    fn insert_inner(&mut self, clipboard_data: ClipboardData) -> u64 {
+    fn insert_inner(&mut self, mut clipboard_data: ClipboardData) -> u64 {
+        // IDs are hashes of the content, so the same content always has the same ID
+        if let Some(clip) = self.clips.get_mut(&clipboard_data.id) {
+            // copies of sensitive clips must not outlive them
+            if self.deduplicate || clip.sensitive {
+                // promotions are counted when they are marked, the monitor
+                // captures the promoted clip again as the current one
+                let current = match clipboard_data.clipboard_type {
+                    ClipboardType::Clipboard => &self.current_clipboard,
+                    ClipboardType::Primary => &self.current_primary,
+                };
+                if current.as_ref().map(|current| current.id) != Some(clip.id) {
+                    clip.use_count += 1;
+                }
+                clip.timestamp = clipboard_data.timestamp;
+                clip.clipboard_type = clipboard_data.clipboard_type;
+                clip.sensitive |= clipboard_data.sensitive;
+                if clipboard_data.application.is_some() {
+                    clip.application = clipboard_data.application;
+                }
+                clipboard_data = clip.clone();
+            } else {
+                clipboard_data.id = self.unique_id(clipboard_data.id, clipboard_data.timestamp);
+            }
+        }
+
+        let clipboard_data = self.spill_clip(clipboard_data);
         let id = clipboard_data.id;
         match clipboard_data.clipboard_type {
             ClipboardType::Clipboard => {
@@ -95,29 +540,425 @@ impl ClipboardManager {
         id
     }
 
+    fn unique_id(&self, id: u64, timestamp: SystemTime) -> u64 {
+        let mut id = ClipboardData::compute_id(&(id, timestamp));
+        while self.clips.contains_key(&id) {
+            id = ClipboardData::compute_id(&id);
+        }
+        id
+    }
+
     #[inline]
     pub fn len(&self) -> usize { self.clips.len() }
 
+    /// Statistics of the clips, with previews of the `largest` largest clips.
+    #[inline]
+    pub fn stats(&self, largest: usize, preview_length: Option<usize>) -> HistoryStats {
+        HistoryStats::compute(self.iter(), largest, preview_length, SystemTime::now())
+    }
+
     #[inline]
     pub fn is_empty(&self) -> bool { self.clips.is_empty() }
 
+    // Pinned clips do not count towards the capacity and are never removed.
     fn remove_oldest(&mut self) {
-        while self.clips.len() > self.capacity {
-            let (_, oldest_id) =
-                self.clips.iter().fold((SystemTime::now(), 0), |oldest, (id, clip)| {
-                    if clip.timestamp < oldest.0 {
-                        (clip.timestamp, *id)
-                    } else {
-                        oldest
-                    }
-                });
+        match self.primary_capacity {
+            Some(primary_capacity) => {
+                self.remove_oldest_of(Some(ClipboardType::Clipboard), self.capacity);
+                self.remove_oldest_of(Some(ClipboardType::Primary), primary_capacity);
+            }
+            None => self.remove_oldest_of(None, self.capacity),
+        }
+
+        self.remove_excess_bytes();
+        self.relieve_memory_pressure();
+        self.remove_unused_spills();
+    }
+
+    // Keeps at most `capacity` unpinned clips of `clipboard_type`, clips of all
+    // types are counted if it is `None`.
+    fn remove_oldest_of(&mut self, clipboard_type: Option<ClipboardType>, capacity: usize) {
+        let mut unpinned: Vec<_> = self
+            .clips
+            .values()
+            .filter(|clip| !clip.pinned)
+            .filter(|clip| clipboard_type.is_none_or(|t| clip.clipboard_type == t))
+            .map(|clip| (clip.timestamp, clip.id))
+            .collect();
+        if unpinned.len() <= capacity {
+            return;
+        }
+
+        unpinned.sort();
+        let excess = unpinned.len() - capacity;
+        for (_, id) in unpinned.into_iter().take(excess) {
+            self.evict(id);
+        }
+    }
+
+    /// Evicts unpinned clips which were last copied more than `max_age` ago,
+    /// like the other history limits they are not put into the trash.
+    pub fn remove_aged(&mut self) -> Vec<ClipboardData> {
+        let max_age = match self.max_age {
+            Some(max_age) => max_age,
+            None => return Vec::new(),
+        };
+
+        let now = SystemTime::now();
+        let aged: Vec<_> = self
+            .clips
+            .values()
+            .filter(|clip| !clip.pinned)
+            .filter(|clip| now.duration_since(clip.timestamp).is_ok_and(|age| age > max_age))
+            .map(|clip| clip.id)
+            .collect();
+
+        let aged = aged.into_iter().filter_map(|id| self.evict(id)).collect();
+        self.remove_unused_spills();
+        aged
+    }
+
+    // Evicts unpinned clips until they fit into `max_bytes`, clips are ranked
+    // by their size weighted with their age.
+    fn remove_excess_bytes(&mut self) {
+        let max_bytes = match self.max_bytes {
+            Some(max_bytes) => max_bytes,
+            None => return,
+        };
 
-            self.remove(oldest_id);
+        let now = SystemTime::now();
+        let mut unpinned: Vec<_> = self
+            .clips
+            .values()
+            .filter(|clip| !clip.pinned)
+            .map(|clip| {
+                let age = now.duration_since(clip.timestamp).map_or(0, |age| age.as_secs());
+                (clip.total_size(), age, clip.id)
+            })
+            .collect();
+        let mut total: usize = unpinned.iter().map(|(size, ..)| size).sum();
+        if total <= max_bytes {
+            return;
+        }
+
+        unpinned.sort_by_key(|(size, age, _)| {
+            std::cmp::Reverse(size.saturating_mul(*age as usize + 1))
+        });
+        for (size, _, id) in unpinned {
+            if total <= max_bytes {
+                break;
+            }
+            self.evict(id);
+            total -= size;
         }
     }
 
+    // Spills or evicts clips until the clips which are held in memory fit into
+    // `memory_budget`, clips are ranked like by `remove_excess_bytes`. Pinned
+    // clips are only spilled, never evicted. Sensitive clips are never written
+    // to disk, they are evicted even if they are pinned.
+    fn relieve_memory_pressure(&mut self) {
+        let budget = match self.memory_budget {
+            Some(budget) => budget,
+            None => return,
+        };
+        let mut resident = self.resident_bytes();
+        if resident <= budget {
+            return;
+        }
+
+        let now = SystemTime::now();
+        let mut clips: Vec<_> = self
+            .clips
+            .values()
+            .map(|clip| {
+                let age = now.duration_since(clip.timestamp).map_or(0, |age| age.as_secs());
+                (clip.resident_size(), age, clip.id, clip.pinned && !clip.sensitive)
+            })
+            .collect();
+        clips.sort_by_key(|(size, age, ..)| {
+            std::cmp::Reverse(size.saturating_mul(*age as usize + 1))
+        });
+
+        let (mut spilled, mut evicted) = (0, 0);
+        for (size, _, id, keep) in clips {
+            if resident <= budget {
+                break;
+            }
+            match self.spill_under_pressure(id) {
+                Some(spilled_size) => {
+                    resident -= size - spilled_size;
+                    spilled += 1;
+                }
+                None if keep => {}
+                None => {
+                    self.evict(id);
+                    resident -= size;
+                    evicted += 1;
+                }
+            }
+        }
+
+        if spilled + evicted == 0 {
+            return;
+        }
+        self.memory_pressure.spilled += spilled;
+        self.memory_pressure.evicted += evicted;
+        tracing::info!(
+            "Memory budget of {} bytes is exceeded, {} clip(s) are spilled and {} evicted",
+            budget,
+            spilled,
+            evicted
+        );
+    }
+
+    // Spills clip `id` regardless of the threshold of the spill store, returns
+    // the number of bytes which are left in memory, or `None` if the clip can
+    // not be spilled.
+    fn spill_under_pressure(&mut self, id: u64) -> Option<usize> {
+        let spill = self.spill.as_mut()?;
+        let clip = self.clips.get_mut(&id)?;
+        if clip.sensitive
+            || clip.spilled.is_some()
+            || clip.resident_size() <= MIN_PRESSURE_SPILL_SIZE
+        {
+            return None;
+        }
+        if let Err(err) = spill.spill_larger_than(clip, 0) {
+            tracing::warn!("Could not spill clip {:016x}, error: {}", id, err);
+            return None;
+        }
+
+        let clip = clip.clone();
+        for current in &mut [&mut self.current_clipboard, &mut self.current_primary] {
+            if current.as_ref().is_some_and(|current| current.id == id) {
+                **current = Some(clip.clone());
+            }
+        }
+        Some(clip.resident_size())
+    }
+
+    /// Pins or unpins clip with `id`, returns `false` if there is no such
+    /// clip.
+    pub fn set_pinned(&mut self, id: u64, pinned: bool) -> bool {
+        if !self.modify(id, |clip| clip.pinned = pinned) {
+            return false;
+        }
+
+        if !pinned {
+            self.remove_oldest();
+        }
+        true
+    }
+
+    /// Attaches `tags` to clip with `id`, returns `false` if there is no such
+    /// clip.
+    pub fn add_tags(&mut self, id: u64, tags: &[String]) -> bool {
+        self.modify(id, |clip| clip.tags.extend(tags.iter().cloned()))
+    }
+
+    /// Detaches `tags` from clip with `id`, returns `false` if there is no such
+    /// clip.
+    pub fn remove_tags(&mut self, id: u64, tags: &[String]) -> bool {
+        self.modify(id, |clip| {
+            for tag in tags {
+                clip.tags.remove(tag);
+            }
+        })
+    }
+
+    /// Lists clips which carry all of `tags` in order of the ranking, all
+    /// clips are listed if `tags` is empty. Spilled clips only carry their
+    /// previews.
+    pub fn list_tagged(&self, tags: &[String]) -> Vec<ClipboardData> {
+        let mut clips: Vec<_> = self.iter().filter(|clip| clip.has_tags(tags)).cloned().collect();
+        self.ranking.sort(&mut clips);
+        clips
+    }
+
+    /// Lists clips which carry all of `tags` for an export, sensitive clips
+    /// are never exported.
+    pub fn export(&self, tags: &[String], pinned_only: bool) -> Vec<ClipboardData> {
+        let mut clips = self.list_tagged(tags);
+        clips.retain(|clip| !clip.sensitive && (clip.pinned || !pinned_only));
+        clips.into_iter().map(|clip| self.load(clip)).collect()
+    }
+
+    /// Lists at most `limit` clips which carry all of `tags` starting at
+    /// `offset`, all remaining clips are listed if `limit` is 0. Only clips
+    /// copied from `application` are listed if it is set. Clips are listed in
+    /// `order`, or in order of the ranking if it is `None`. Returns the clips
+    /// and the number of clips which match.
+    pub fn list_page(
+        &self,
+        tags: &[String],
+        application: Option<&str>,
+        order: Option<SortOrder>,
+        offset: usize,
+        limit: usize,
+    ) -> (Vec<ClipboardData>, usize) {
+        let mut clips = self.list_tagged(tags);
+        if let Some(application) = application {
+            clips.retain(|clip| clip.is_from(application));
+        }
+        if let Some(order) = order {
+            order.sort(&mut clips);
+        }
+        let total = clips.len();
+        let limit = if limit == 0 { total } else { limit };
+        (clips.into_iter().skip(offset).take(limit).collect(), total)
+    }
+
+    /// Fuzzy matches clips which carry all of `tags` against `query`, best
+    /// matches first and clips with equal scores in order of the ranking. At
+    /// most `limit` results are returned if `limit` is not 0, previews are
+    /// truncated to `preview_length`.
+    pub fn search(
+        &self,
+        query: &str,
+        tags: &[String],
+        limit: usize,
+        preview_length: Option<usize>,
+    ) -> Vec<SearchResult> {
+        let mut matches: Vec<_> = self
+            .list_tagged(tags)
+            .into_iter()
+            .filter_map(|clip| {
+                let score = fuzzy_score(query, &clip.printable_data(None))?;
+                Some((score, clip))
+            })
+            .collect();
+        matches.sort_by(|(a, _), (b, _)| b.cmp(a));
+        if limit != 0 {
+            matches.truncate(limit);
+        }
+
+        matches
+            .into_iter()
+            .map(|(score, clip)| SearchResult {
+                id: clip.id,
+                score,
+                preview: clip.printable_data(preview_length),
+                lines: Vec::new(),
+            })
+            .collect()
+    }
+
+    /// Matches `regex` against the whole content of text clips which carry all
+    /// of `tags` in order of the ranking, the score of a result is its number
+    /// of matches. At most `limit` results are returned if `limit` is not 0.
+    pub fn grep(
+        &self,
+        regex: &Regex,
+        tags: &[String],
+        context: usize,
+        limit: usize,
+        preview_length: Option<usize>,
+    ) -> Vec<SearchResult> {
+        let results =
+            self.list_tagged(tags).into_iter().filter(ClipboardData::is_text).filter_map(|clip| {
+                let clip = self.load(clip);
+                let (count, lines) = grep_lines(regex, &clip.data, context)?;
+                Some(SearchResult {
+                    id: clip.id,
+                    score: count as i64,
+                    preview: clip.printable_data(preview_length),
+                    lines,
+                })
+            });
+
+        if limit == 0 {
+            results.collect()
+        } else {
+            results.take(limit).collect()
+        }
+    }
+
+    /// Removes sensitive clips which were copied at least `timeout` ago,
+    /// returns the removed clips.
+    pub fn remove_expired(&mut self, timeout: Duration) -> Vec<ClipboardData> {
+        let now = SystemTime::now();
+        let expired: Vec<_> = self
+            .clips
+            .values()
+            .filter(|clip| clip.sensitive)
+            .filter(|clip| now.duration_since(clip.timestamp).is_ok_and(|age| age >= timeout))
+            .map(|clip| clip.id)
+            .collect();
+
+        let expired = expired.into_iter().filter_map(|id| self.evict(id)).collect();
+        self.remove_unused_spills();
+        expired
+    }
+
+    // Applies `f` to clip with `id` and to its copies in current clipboards.
+    fn modify<F>(&mut self, id: u64, f: F) -> bool
+    where
+        F: Fn(&mut ClipboardData),
+    {
+        match self.clips.get_mut(&id) {
+            Some(clip) => f(clip),
+            None => return false,
+        }
+
+        if let Some(clip) = self.current_clipboard.as_mut().filter(|clip| clip.id == id) {
+            f(clip);
+        }
+
+        if let Some(clip) = self.current_primary.as_mut().filter(|clip| clip.id == id) {
+            f(clip);
+        }
+
+        true
+    }
+
+    /// Removes clip `id` and puts it into the trash, see `batch_remove`.
     #[inline]
-    pub fn remove(&mut self, id: u64) -> bool {
+    pub fn remove(&mut self, id: u64) -> bool { !self.batch_remove(&[id]).is_empty() }
+
+    /// Removes the clips with `ids` and puts them into the trash at once, so
+    /// that `undo` restores all of them. Returns the IDs of the removed clips.
+    pub fn batch_remove(&mut self, ids: &[u64]) -> Vec<u64> {
+        let removed: Vec<_> = ids.iter().filter_map(|&id| self.evict(id)).collect();
+        let ids = removed.iter().map(|clip| clip.id).collect();
+        if !removed.is_empty() {
+            self.trash.put(removed, SystemTime::now());
+        }
+        self.remove_unused_spills();
+        ids
+    }
+
+    /// Removes all clips and puts them into the trash at once.
+    pub fn clear(&mut self) {
+        self.current_clipboard.take();
+        self.current_primary.take();
+        let removed: Vec<_> = self.clips.drain().map(|(_, clip)| clip).collect();
+        for clip in &removed {
+            let _ = self.removal_sender.send(clip.clone());
+        }
+        if !removed.is_empty() {
+            self.trash.put(removed, SystemTime::now());
+        }
+        self.remove_unused_spills();
+    }
+
+    /// Removes unpinned clips which were last copied more than `age` ago and
+    /// puts them into the trash at once, returns the IDs of the removed clips.
+    pub fn clear_older_than(&mut self, age: Duration) -> Vec<u64> {
+        let now = SystemTime::now();
+        let ids: Vec<_> = self
+            .clips
+            .values()
+            .filter(|clip| !clip.pinned)
+            .filter(|clip| now.duration_since(clip.timestamp).is_ok_and(|elapsed| elapsed > age))
+            .map(|clip| clip.id)
+            .collect();
+        self.batch_remove(&ids)
+    }
+
+    // Removes clip `id` without putting it into the trash, e.g. when it is
+    // evicted or expires.
+    fn evict(&mut self, id: u64) -> Option<ClipboardData> {
         if let Some(clip) = self.current_clipboard.as_ref() {
             if clip.id == id {
                 self.current_clipboard.take();
@@ -130,80 +971,280 @@ impl ClipboardManager {
             }
         }
 
-        self.clips.remove(&id).is_some()
+        let clip = self.clips.remove(&id)?;
+        let _ = self.removal_sender.send(clip.clone());
+        Some(clip)
+    }
+
+    /// Restores the clips which were removed or cleared last from the trash,
+    /// returns the IDs of the restored clips.
+    pub fn undo(&mut self) -> Vec<u64> {
+        let clips = self.trash.take_last(SystemTime::now());
+        self.restore_clips(clips)
     }
 
+    /// Restores clip `id` from the trash, returns `false` if it is not in the
+    /// trash.
+    pub fn restore(&mut self, id: u64) -> bool {
+        match self.trash.take(id, SystemTime::now()) {
+            Some(clip) => {
+                self.restore_clips(vec![clip]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Lists the clips in the trash, the most recently removed ones first.
     #[inline]
-    pub fn clear(&mut self) {
-        self.current_clipboard.take();
-        self.current_primary.take();
-        self.clips.clear();
+    pub fn trash(&self) -> Vec<TrashEntry> { self.trash.list(SystemTime::now()) }
+
+    /// Sets how many removed clips are kept in the trash and for how long, the
+    /// trash is disabled if `capacity` is 0.
+    #[inline]
+    pub fn set_trash_limits(&mut self, capacity: usize, ttl: Duration) {
+        self.trash.set_limits(capacity, ttl);
     }
 
+    // Clips which were copied again since they were removed are kept as they
+    // are.
+    fn restore_clips(&mut self, clips: Vec<ClipboardData>) -> Vec<u64> {
+        let mut ids = Vec::with_capacity(clips.len());
+        for clip in clips {
+            ids.push(clip.id);
+            self.clips.entry(clip.id).or_insert(clip);
+        }
+        self.remove_oldest();
+        ids
+    }
+
+    /// Replaces the content of clip `old_id` with text `data`, the new clip
+    /// keeps its type, flags and tags. Returns `false` if there is no clip
+    /// `old_id`.
     pub fn replace(&mut self, old_id: u64, data: &str) -> (bool, u64) {
-        let (clipboard_type, timestamp) = match self.clips.remove(&old_id) {
-            Some(v) => (v.clipboard_type, v.timestamp),
-            None => (ClipboardType::Primary, SystemTime::now()),
+        let old = match self.evict(old_id) {
+            Some(old) => old,
+            None => return (false, 0),
         };
+        self.remove_unused_spills();
 
         let new_id = ClipboardData::compute_id(data);
         let data = data.to_owned();
-        let data = ClipboardData { id: new_id, data, timestamp, clipboard_type };
+        let data = ClipboardData {
+            id: new_id,
+            data,
+            timestamp: old.timestamp,
+            created_at: old.created_at,
+            clipboard_type: old.clipboard_type,
+            mime: ClipboardData::default_mime(),
+            binary: Vec::new(),
+            targets: Default::default(),
+            pinned: old.pinned,
+            tags: old.tags,
+            sensitive: old.sensitive,
+            use_count: old.use_count,
+            application: old.application,
+            spilled: None,
+        };
 
         self.insert_inner(data);
         (true, new_id)
     }
 
     pub async fn mark_as_clipboard(&mut self, id: u64) -> Result<(), ClipboardError> {
-        if let Some(clip) = self.clips.get_mut(&id) {
-            clip.mark_as_clipboard();
-            let clipboard_content = clip.data.clone();
-            Self::update_sys_clipboard(&clipboard_content, ClipboardType::Clipboard).await?;
+        if let Some(mark) = self.prepare_promotion(id, ClipboardType::Clipboard) {
+            mark.store(None).await?;
+            mark.mirror().await;
+            self.finish_mark(mark);
         }
         Ok(())
     }
 
     pub async fn mark_as_primary(&mut self, id: u64) -> Result<(), ClipboardError> {
-        if let Some(clip) = self.clips.get_mut(&id) {
-            clip.mark_as_primary();
-            let clipboard_content = clip.data.clone();
-            Self::update_sys_clipboard(&clipboard_content, ClipboardType::Primary).await?;
+        if let Some(mark) = self.prepare_promotion(id, ClipboardType::Primary) {
+            mark.store(None).await?;
+            mark.mirror().await;
+            self.finish_mark(mark);
         }
         Ok(())
     }
 
-    async fn update_sys_clipboard(
-        data: &str,
+    /// Promotes clip `id` like `mark_as_clipboard` and `mark_as_primary`, but
+    /// `manager` is only locked to prepare and finish the mark, not while the
+    /// backend stores the clip. Returns `false` if there is no clip `id`.
+    pub async fn promote(
+        manager: &RwLock<ClipboardManager>,
+        id: u64,
         clipboard_type: ClipboardType,
-    ) -> Result<(), ClipboardError> {
-        use x11_clipboard::Clipboard;
-        let clipboard = Clipboard::new().context(error::InitializeX11Clipboard)?;
+    ) -> Result<bool, ClipboardError> {
+        let mark = match manager.write().await.prepare_promotion(id, clipboard_type) {
+            Some(mark) => mark,
+            None => return Ok(false),
+        };
+        mark.store(None).await?;
+        mark.mirror().await;
+        manager.write().await.finish_mark(mark);
+        Ok(true)
+    }
 
-        let atom_clipboard = match clipboard_type {
-            ClipboardType::Clipboard => clipboard.setter.atoms.clipboard,
-            ClipboardType::Primary => clipboard.setter.atoms.primary,
+    /// Offers clip `id` for a single paste and drops it like
+    /// `mark_as_clipboard_once` and `mark_as_primary_once`, but `manager` is
+    /// not locked while the backend waits for the paste. Returns `false` if
+    /// there is no clip `id`.
+    pub async fn promote_once(
+        manager: &RwLock<ClipboardManager>,
+        id: u64,
+        clipboard_type: ClipboardType,
+    ) -> Result<bool, ClipboardError> {
+        let mark = match manager.read().await.prepare_once(id, clipboard_type) {
+            Some(mark) => mark,
+            None => return Ok(false),
         };
-        let atom_utf8string = clipboard.setter.atoms.utf8_string;
-        let data = data.to_owned();
+        mark.store_once().await?;
+        manager.write().await.finish_once(mark);
+        Ok(true)
+    }
 
-        tokio::task::spawn_blocking(move || -> Result<(), ClipboardError> {
-            clipboard
-                .store(atom_clipboard, atom_utf8string, data.as_bytes())
-                .context(error::PasteToX11Clipboard)?;
-            Ok(())
-        })
-        .await
-        .context(error::SpawnBlockingTask)??;
+    /// Like `mark_as_clipboard`, but offers clip `id` on `display` only, or on
+    /// the default display if it is `None`.
+    pub async fn mark_as_clipboard_on(
+        &mut self,
+        id: u64,
+        display: Option<&str>,
+    ) -> Result<(), ClipboardError> {
+        self.mark(id, ClipboardType::Clipboard, display).await
+    }
+
+    /// Like `mark_as_primary`, but offers clip `id` on `display` only, or on
+    /// the default display if it is `None`.
+    pub async fn mark_as_primary_on(
+        &mut self,
+        id: u64,
+        display: Option<&str>,
+    ) -> Result<(), ClipboardError> {
+        self.mark(id, ClipboardType::Primary, display).await
+    }
+
+    async fn mark(
+        &mut self,
+        id: u64,
+        clipboard_type: ClipboardType,
+        display: Option<&str>,
+    ) -> Result<(), ClipboardError> {
+        if let Some(mark) = self.prepare_mark(id, clipboard_type) {
+            mark.store(display).await?;
+            self.finish_mark(mark);
+        }
         Ok(())
     }
+
+    /// Marks clip `id` as the content of `clipboard_type` like `mark_as_*_on`,
+    /// but leaves storing it to the caller, so that the manager is not locked
+    /// while the backend stores it. The selection is recorded by `finish_mark`
+    /// once the clip is stored.
+    pub fn prepare_mark(&mut self, id: u64, clipboard_type: ClipboardType) -> Option<PendingMark> {
+        let clip = self.clips.get_mut(&id)?;
+        match clipboard_type {
+            ClipboardType::Clipboard => clip.mark_as_clipboard(),
+            ClipboardType::Primary => clip.mark_as_primary(),
+        }
+        clip.use_count += 1;
+        let clip = clip.clone();
+        let clip = self.load(clip);
+        Some(PendingMark { id, clipboard_type, backend: self.backend, clip, mirrors: Vec::new() })
+    }
+
+    // Like `prepare_mark`, the clip is mirrored to the other displays if
+    // promotions are mirrored.
+    fn prepare_promotion(&mut self, id: u64, clipboard_type: ClipboardType) -> Option<PendingMark> {
+        let mut mark = self.prepare_mark(id, clipboard_type)?;
+        if self.mirror_promotions {
+            mark.mirrors = self.displays.clone();
+        }
+        Some(mark)
+    }
+
+    // The clip is offered as it is, it is dropped by `finish_once` once it is
+    // pasted.
+    fn prepare_once(&self, id: u64, clipboard_type: ClipboardType) -> Option<PendingMark> {
+        let clip = self.get(id)?;
+        Some(PendingMark { id, clipboard_type, backend: self.backend, clip, mirrors: Vec::new() })
+    }
+
+    fn finish_once(&mut self, mark: PendingMark) {
+        self.evict(mark.id);
+        self.remove_unused_spills();
+        let _ = self.paste_once_sender.send(mark.id);
+    }
+
+    /// Records that the clip of `mark` is stored in its selection, the
+    /// selection is cleared if the clip was removed meanwhile.
+    pub fn finish_mark(&mut self, mark: PendingMark) {
+        let clip = self.clips.get(&mark.id).cloned();
+        match mark.clipboard_type {
+            ClipboardType::Clipboard => self.current_clipboard = clip,
+            ClipboardType::Primary => self.current_primary = clip,
+        }
+    }
+
+    /// Offers clip `id` in clipboard for a single paste and drops it, see
+    /// `backend::store_once`.
+    pub async fn mark_as_clipboard_once(&mut self, id: u64) -> Result<(), ClipboardError> {
+        self.store_once(id, ClipboardType::Clipboard).await
+    }
+
+    /// Offers clip `id` in primary clipboard for a single paste and drops it,
+    /// see `backend::store_once`.
+    pub async fn mark_as_primary_once(&mut self, id: u64) -> Result<(), ClipboardError> {
+        self.store_once(id, ClipboardType::Primary).await
+    }
+
+    async fn store_once(
+        &mut self,
+        id: u64,
+        clipboard_type: ClipboardType,
+    ) -> Result<(), ClipboardError> {
+        if let Some(mark) = self.prepare_once(id, clipboard_type) {
+            mark.store_once().await?;
+            self.finish_once(mark);
+        }
+        Ok(())
+    }
+
+    /// Removes expired sensitive clips like `remove_expired`, the selections
+    /// which still hold one of them are cleared with the returned
+    /// `PendingClear` once the manager is unlocked.
+    pub fn expire_sensitive(&mut self, timeout: Duration) -> (Vec<ClipboardData>, PendingClear) {
+        let clipboard = self.current_clipboard.as_ref().map(|clip| clip.id);
+        let primary = self.current_primary.as_ref().map(|clip| clip.id);
+
+        let expired = self.remove_expired(timeout);
+        let mut clipboard_types = Vec::new();
+        if expired.iter().any(|clip| clipboard == Some(clip.id)) {
+            clipboard_types.push(ClipboardType::Clipboard);
+        }
+        if expired.iter().any(|clip| primary == Some(clip.id)) {
+            clipboard_types.push(ClipboardType::Primary);
+        }
+        let clear = PendingClear {
+            backend: self.backend,
+            displays: self.displays.clone(),
+            clipboard_types,
+        };
+        (expired, clear)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
 
+    use regex::Regex;
+
     use crate::{
         manager::{ClipboardManager, DEFAULT_CAPACITY},
+        mime,
+        spill::SpillStore,
         ClipboardData, ClipboardType,
     };
 
@@ -300,6 +1341,356 @@ mod tests {
         assert_eq!(dumped, clips);
     }
 
+    #[test]
+    fn test_use_count() {
+        let mut mgr = ClipboardManager::new();
+        let id = mgr.insert(ClipboardData::new_clipboard("clipcat"));
+        mgr.insert(ClipboardData::new_clipboard("other"));
+        mgr.insert(ClipboardData::new_clipboard("clipcat"));
+        assert_eq!(mgr.get(id).unwrap().use_count, 1);
+
+        // copying the current clip again, e.g. after it was promoted, is not a use
+        mgr.insert(ClipboardData::new_clipboard("clipcat"));
+        assert_eq!(mgr.get(id).unwrap().use_count, 1);
+    }
+
+    #[test]
+    fn test_insert_image() {
+        let png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        let clip =
+            ClipboardData::from_bytes(mime::IMAGE_PNG, png.clone(), ClipboardType::Clipboard);
+        let mut mgr = ClipboardManager::new();
+        let id = mgr.insert(clip);
+
+        let clip = mgr.get(id).unwrap();
+        assert!(clip.is_image());
+        assert!(clip.data.is_empty());
+        assert_eq!(clip.as_bytes(), &png[..]);
+        assert_eq!(mgr.get_current_clipboard(), Some(&clip));
+        assert_ne!(id, ClipboardData::new_clipboard("").id);
+    }
+
+    #[test]
+    fn test_insert_rich_text() {
+        let mut clip = ClipboardData::new_clipboard("bold");
+        clip.targets.insert(mime::TEXT_HTML.to_owned(), b"<b>bold</b>".to_vec());
+        let mut mgr = ClipboardManager::new();
+        let id = mgr.insert(clip);
+
+        let clip = mgr.get(id).unwrap();
+        assert_eq!(clip.data, "bold");
+        assert_eq!(
+            clip.offered_targets(),
+            vec![
+                (mime::TEXT_PLAIN.to_owned(), b"bold".to_vec()),
+                (mime::TEXT_HTML.to_owned(), b"<b>bold</b>".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insert_file_list() {
+        let uris = b"file:///home/user/a.txt\r\nfile:///tmp/b%20c.png\r\n".to_vec();
+        let clip = ClipboardData::from_bytes(mime::TEXT_URI_LIST, uris, ClipboardType::Clipboard);
+        let mut mgr = ClipboardManager::new();
+        let id = mgr.insert(clip);
+
+        let clip = mgr.get(id).unwrap();
+        assert!(clip.is_file_list());
+        assert_eq!(clip.file_list(), vec!["file:///home/user/a.txt", "file:///tmp/b%20c.png"]);
+        assert_eq!(clip.printable_data(None), "[2 file(s)] /home/user/a.txt /tmp/b c.png");
+
+        let targets = clip.offered_targets();
+        assert_eq!(targets.len(), 3);
+        assert!(targets.contains(&(
+            mime::GNOME_COPIED_FILES.to_owned(),
+            b"copy\nfile:///home/user/a.txt\nfile:///tmp/b%20c.png".to_vec()
+        )));
+        assert!(targets
+            .contains(&(mime::TEXT_PLAIN.to_owned(), b"/home/user/a.txt\n/tmp/b c.png".to_vec())));
+    }
+
+    #[test]
+    fn test_insert_alternatives() {
+        let uris = b"file:///tmp/a.png\r\n".to_vec();
+        let mut clip =
+            ClipboardData::from_bytes(mime::TEXT_URI_LIST, uris, ClipboardType::Clipboard);
+        clip.targets.insert(mime::TEXT_PLAIN.to_owned(), b"a.png".to_vec());
+        clip.targets
+            .insert(mime::GNOME_COPIED_FILES.to_owned(), b"cut\nfile:///tmp/a.png".to_vec());
+        clip.targets.insert(mime::IMAGE_PNG.to_owned(), vec![0x89, b'P', b'N', b'G']);
+        let mut mgr = ClipboardManager::new();
+        let id = mgr.insert(clip);
+
+        // stored representations are offered as they are, nothing is added
+        let clip = mgr.get(id).unwrap();
+        let targets = clip.offered_targets();
+        assert_eq!(targets.len(), 4);
+        assert!(targets.contains(&(mime::TEXT_PLAIN.to_owned(), b"a.png".to_vec())));
+        assert!(targets
+            .contains(&(mime::GNOME_COPIED_FILES.to_owned(), b"cut\nfile:///tmp/a.png".to_vec())));
+        assert!(targets.contains(&(mime::IMAGE_PNG.to_owned(), vec![0x89, b'P', b'N', b'G'])));
+    }
+
+    #[test]
+    fn test_deduplicate() {
+        let mut mgr = ClipboardManager::new();
+        assert!(mgr.deduplicate());
+
+        let first = ClipboardData::new_primary("clipcat");
+        let first_timestamp = first.timestamp;
+        let id = mgr.insert(first);
+        mgr.insert_primary("other");
+
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        assert_eq!(mgr.insert_clipboard("clipcat"), id);
+        assert_eq!(mgr.len(), 2);
+
+        let clip = mgr.get(id).unwrap();
+        assert!(clip.timestamp > first_timestamp);
+        assert_eq!(clip.clipboard_type, ClipboardType::Clipboard);
+        assert_eq!(mgr.get_current_clipboard(), Some(&clip));
+
+        mgr.set_deduplicate(false);
+        let new_id = mgr.insert_clipboard("clipcat");
+        assert_ne!(new_id, id);
+        assert_eq!(mgr.len(), 3);
+        assert_eq!(mgr.get(new_id).unwrap().data, "clipcat");
+    }
+
+    #[test]
+    fn test_merge_duplicates() {
+        let mut mgr = ClipboardManager::new();
+        mgr.set_deduplicate(false);
+
+        let mut old = ClipboardData::new_clipboard("clipcat");
+        old.timestamp -= std::time::Duration::from_secs(100);
+        old.use_count = 2;
+        old.tags.insert("a".to_owned());
+        let old_id = mgr.insert(old);
+        let mut recent = ClipboardData::new_primary("clipcat");
+        recent.use_count = 1;
+        recent.tags.insert("b".to_owned());
+        let recent_id = mgr.insert(recent);
+        mgr.insert_clipboard("other");
+        assert_ne!(old_id, recent_id);
+        assert_eq!(mgr.len(), 3);
+
+        assert_eq!(mgr.merge_duplicates(), 1);
+        assert_eq!(mgr.len(), 2);
+        assert_eq!(mgr.get(old_id), None);
+        let clip = mgr.get(recent_id).unwrap();
+        assert_eq!(clip.use_count, 3);
+        assert!(clip.has_tags(&["a".to_owned(), "b".to_owned()]));
+        assert_eq!(mgr.merge_duplicates(), 0);
+    }
+
+    #[test]
+    fn test_sensitive() {
+        let mut mgr = ClipboardManager::new();
+        mgr.insert_clipboard("plain");
+
+        let mut secret = ClipboardData::new_clipboard("secret");
+        secret.sensitive = true;
+        let id = mgr.insert(secret);
+
+        // copying the content again keeps the clip sensitive
+        mgr.set_deduplicate(false);
+        assert_eq!(mgr.insert_clipboard("secret"), id);
+        assert!(mgr.get(id).unwrap().sensitive);
+
+        assert!(mgr.remove_expired(std::time::Duration::from_secs(60)).is_empty());
+        assert_eq!(mgr.len(), 2);
+
+        let expired = mgr.remove_expired(std::time::Duration::from_secs(0));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, id);
+        assert_eq!(mgr.get(id), None);
+        assert_eq!(mgr.get_current_clipboard(), None);
+        assert_eq!(mgr.len(), 1);
+    }
+
+    #[test]
+    fn test_primary_capacity() {
+        let mut mgr = ClipboardManager::with_capacity(2);
+        mgr.set_primary_capacity(Some(3));
+        assert_eq!(mgr.total_capacity(), 5);
+
+        let copied: Vec<_> = (0..2).map(|i| mgr.insert_clipboard(&format!("copy {}", i))).collect();
+        for i in 0..10 {
+            mgr.insert_primary(&format!("selection {}", i));
+        }
+
+        // selections do not evict copies
+        assert_eq!(mgr.len(), 5);
+        for id in copied {
+            assert!(mgr.get(id).is_some());
+        }
+
+        let id = mgr.insert_clipboard("copy 2");
+        assert_eq!(mgr.len(), 5);
+        assert!(mgr.get(id).is_some());
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        let mut mgr = ClipboardManager::new();
+        mgr.set_max_bytes(Some(20));
+
+        let mut old = ClipboardData::new_primary(&"a".repeat(8));
+        old.timestamp -= std::time::Duration::from_secs(100);
+        let old_id = mgr.insert(old);
+        let new_id = mgr.insert_primary(&"b".repeat(8));
+        let mut pinned = ClipboardData::new_primary(&"c".repeat(30));
+        pinned.pinned = true;
+        let pinned_id = mgr.insert(pinned);
+        assert_eq!(mgr.len(), 3);
+
+        // the old clip outranks the recent one of the same size
+        let id = mgr.insert_primary(&"d".repeat(8));
+        assert_eq!(mgr.len(), 3);
+        assert_eq!(mgr.get(old_id), None);
+        assert!(mgr.get(new_id).is_some());
+        assert!(mgr.get(pinned_id).is_some());
+        assert!(mgr.get(id).is_some());
+    }
+
+    #[test]
+    fn test_pinned() {
+        let cap = 5;
+        let mut mgr = ClipboardManager::with_capacity(cap);
+        let pinned_id = mgr.insert_primary("pinned");
+        assert!(mgr.set_pinned(pinned_id, true));
+        assert!(!mgr.set_pinned(43, true));
+
+        let n = 20;
+        let clips = create_clips(n);
+        clips.into_iter().for_each(|clip| {
+            mgr.insert(clip);
+        });
+        assert_eq!(mgr.len(), cap + 1);
+        assert!(mgr.get(pinned_id).unwrap().pinned);
+
+        let (ok, new_id) = mgr.replace(pinned_id, "still pinned");
+        assert!(ok);
+        assert!(mgr.get(new_id).unwrap().pinned);
+        assert_eq!(mgr.len(), cap + 1);
+
+        let mut mgr = ClipboardManager::with_capacity(1);
+        let first = mgr.insert_primary("first");
+        mgr.set_pinned(first, true);
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        let second = mgr.insert_primary("second");
+        assert_eq!(mgr.len(), 2);
+
+        assert!(mgr.set_pinned(first, false));
+        assert_eq!(mgr.len(), 1);
+        assert!(mgr.get(first).is_none());
+        assert!(mgr.get(second).is_some());
+    }
+
+    #[test]
+    fn test_tags() {
+        let mut mgr = ClipboardManager::new();
+        let work = mgr.insert_clipboard("work snippet");
+        let noise = mgr.insert_primary("noise");
+        let tags = vec!["work".to_owned(), "snippet".to_owned()];
+
+        assert!(mgr.add_tags(work, &tags));
+        assert!(!mgr.add_tags(43, &tags));
+        assert_eq!(mgr.get_current_clipboard().unwrap().tags.len(), 2);
+
+        assert_eq!(mgr.list_tagged(&[]).len(), 2);
+        let tagged = mgr.list_tagged(&tags[..1]);
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].id, work);
+
+        assert!(mgr.remove_tags(work, &tags[1..]));
+        assert!(mgr.list_tagged(&tags).is_empty());
+        assert_eq!(mgr.list_tagged(&tags[..1]).len(), 1);
+        assert!(mgr.get(noise).unwrap().tags.is_empty());
+
+        let (_, new_id) = mgr.replace(work, "edited snippet");
+        assert_eq!(mgr.list_tagged(&tags[..1])[0].id, new_id);
+    }
+
+    #[test]
+    fn test_list_page() {
+        let mut mgr = ClipboardManager::new();
+        for clip in create_clips(5) {
+            mgr.insert(clip);
+        }
+        let ids: Vec<_> = mgr.list_tagged(&[]).into_iter().map(|clip| clip.id).collect();
+
+        let (page, total) = mgr.list_page(&[], None, None, 1, 2);
+        assert_eq!(total, 5);
+        assert_eq!(page.into_iter().map(|clip| clip.id).collect::<Vec<_>>(), ids[1..3].to_vec());
+        assert_eq!(mgr.list_page(&[], None, None, 3, 0).0.len(), 2);
+        assert!(mgr.list_page(&[], None, None, 5, 2).0.is_empty());
+        assert_eq!(mgr.list_page(&["none".to_owned()], None, None, 0, 0), (Vec::new(), 0));
+    }
+
+    #[test]
+    fn test_list_page_of_application() {
+        let mut mgr = ClipboardManager::new();
+        for (index, mut clip) in create_clips(3).into_iter().enumerate() {
+            if index != 1 {
+                clip.application = Some("Slack".to_owned());
+            }
+            mgr.insert(clip);
+        }
+
+        let (page, total) = mgr.list_page(&[], Some("slack"), None, 0, 0);
+        assert_eq!(total, 2);
+        assert!(page.iter().all(|clip| clip.application.as_deref() == Some("Slack")));
+        assert_eq!(mgr.list_page(&[], Some("firefox"), None, 0, 0), (Vec::new(), 0));
+    }
+
+    #[test]
+    fn test_search() {
+        let mut mgr = ClipboardManager::new();
+        let clipcat = mgr.insert_clipboard("clipcat");
+        let cat = mgr.insert_primary("the cat sat on the mat");
+        mgr.insert_clipboard("dog");
+
+        let ids: Vec<_> =
+            mgr.search("cat", &[], 0, None).into_iter().map(|result| result.id).collect();
+        assert_eq!(ids, vec![cat, clipcat]);
+
+        let results = mgr.search("cat", &[], 1, Some(6));
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].preview, "the...");
+
+        assert_eq!(mgr.search("", &[], 0, None).len(), 3);
+        assert!(mgr.search("cow", &[], 0, None).is_empty());
+
+        mgr.add_tags(clipcat, &["cli".to_owned()]);
+        let ids: Vec<_> = mgr
+            .search("cat", &["cli".to_owned()], 0, None)
+            .into_iter()
+            .map(|result| result.id)
+            .collect();
+        assert_eq!(ids, vec![clipcat]);
+    }
+
+    #[test]
+    fn test_grep() {
+        let mut mgr = ClipboardManager::new();
+        let log = mgr.insert_clipboard("error: first\nok\nerror: second");
+        mgr.insert_primary("no problems");
+        let regex = Regex::new(r"^error: (\w+)$").unwrap();
+        assert!(mgr.grep(&regex, &[], 0, 0, None).is_empty());
+
+        let regex = Regex::new(r"(?m)^error: (\w+)$").unwrap();
+        let results = mgr.grep(&regex, &[], 0, 0, None);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, log);
+        assert_eq!(results[0].score, 2);
+        let numbers: Vec<_> = results[0].lines.iter().map(|line| line.number).collect();
+        assert_eq!(numbers, vec![1, 3]);
+        assert_eq!(mgr.grep(&regex, &[], 1, 0, None)[0].lines.len(), 3);
+    }
+
     #[test]
     fn test_import() {
         let n = 10;
@@ -319,6 +1710,28 @@ mod tests {
         assert_eq!(dumped, clips);
     }
 
+    #[test]
+    fn test_export_and_merge() {
+        let mut mgr = ClipboardManager::new();
+        let pinned = mgr.insert_clipboard("pinned");
+        mgr.set_pinned(pinned, true);
+        mgr.insert_clipboard("plain");
+        mgr.insert(ClipboardData { sensitive: true, ..ClipboardData::new_clipboard("secret") });
+
+        assert_eq!(mgr.export(&[], false).len(), 2);
+        let clips = mgr.export(&[], true);
+        assert_eq!(clips.len(), 1);
+        assert_eq!(clips[0].id, pinned);
+
+        let mut other = ClipboardManager::new();
+        let current = other.insert_primary("current");
+        assert_eq!(other.merge(mgr.export(&[], false)), 2);
+        assert_eq!(other.merge(mgr.export(&[], false)), 0);
+        assert_eq!(other.len(), 3);
+        assert!(other.get(pinned).unwrap().pinned);
+        assert_eq!(other.get_current_primary().unwrap().id, current);
+    }
+
     #[test]
     fn test_replace() {
         let data1 = "ABCDEFG";
@@ -336,13 +1749,25 @@ mod tests {
         let clip = mgr.get(new_id).unwrap();
         assert_eq!(clip.data, data2);
         assert_eq!(clip.clipboard_type, ClipboardType::Clipboard);
+
+        assert_eq!(mgr.replace(old_id, "missing"), (false, 0));
+        assert_eq!(mgr.len(), 1);
+
+        let mut removals = mgr.subscribe_removals();
+        let mut clip = ClipboardData::new_clipboard("secret");
+        clip.sensitive = true;
+        let secret_id = mgr.insert(clip);
+        let (ok, new_id) = mgr.replace(secret_id, "edited secret");
+        assert!(ok);
+        assert!(mgr.get(new_id).unwrap().sensitive);
+        assert_eq!(removals.try_recv().unwrap().id, secret_id);
     }
 
     #[test]
     fn test_remove() {
         let mut mgr = ClipboardManager::new();
         assert_eq!(mgr.len(), 0);
-        assert_eq!(mgr.remove(43), false);
+        assert!(!mgr.remove(43));
 
         let clip = ClipboardData::new_primary("АБВГДЕ");
         let id = mgr.insert(clip);
@@ -360,6 +1785,70 @@ mod tests {
         assert!(!ok);
     }
 
+    #[test]
+    fn test_prepare_mark() {
+        let mut mgr = ClipboardManager::new();
+        assert!(mgr.prepare_mark(43, ClipboardType::Clipboard).is_none());
+
+        let id = mgr.insert(ClipboardData::new_primary("clipcat"));
+        let mark = mgr.prepare_mark(id, ClipboardType::Clipboard).unwrap();
+        assert_eq!(mgr.get(id).unwrap().clipboard_type, ClipboardType::Clipboard);
+        assert!(mgr.get_current_clipboard().is_none());
+        mgr.finish_mark(mark);
+        assert_eq!(mgr.get_current_clipboard().unwrap().id, id);
+
+        // the selection is not recorded for a clip which was removed meanwhile
+        let mark = mgr.prepare_mark(id, ClipboardType::Primary).unwrap();
+        mgr.remove(id);
+        mgr.finish_mark(mark);
+        assert!(mgr.get_current_primary().is_none());
+    }
+
+    #[test]
+    fn test_subscribe_removals() {
+        let mut mgr = ClipboardManager::with_capacity(1);
+        let mut removals = mgr.subscribe_removals();
+
+        let first = mgr.insert(ClipboardData::new_clipboard("first"));
+        let second = mgr.insert(ClipboardData::new_clipboard("second"));
+        assert_eq!(removals.try_recv().unwrap().id, first);
+
+        assert!(!mgr.remove(first));
+        assert!(removals.try_recv().is_err());
+
+        mgr.clear();
+        assert_eq!(removals.try_recv().unwrap().id, second);
+        assert!(removals.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut mgr = ClipboardManager::with_capacity(2);
+        let evicted = mgr.insert(ClipboardData::new_clipboard("evicted"));
+        let first = mgr.insert(ClipboardData::new_clipboard("first"));
+        let second = mgr.insert(ClipboardData::new_clipboard("second"));
+        // evicted clips are not put into the trash
+        assert!(mgr.trash().is_empty());
+
+        assert_eq!(mgr.batch_remove(&[first, second, evicted]), vec![first, second]);
+        assert!(mgr.is_empty());
+        assert_eq!(mgr.trash().len(), 2);
+        assert_eq!(mgr.undo(), vec![first, second]);
+        assert_eq!(mgr.len(), 2);
+
+        assert!(mgr.remove(first));
+        mgr.clear();
+        assert_eq!(mgr.undo(), vec![second]);
+        assert!(mgr.restore(first));
+        assert!(!mgr.restore(first));
+        assert_eq!(mgr.len(), 2);
+        assert!(mgr.undo().is_empty());
+
+        mgr.set_trash_limits(0, std::time::Duration::from_secs(60));
+        mgr.clear();
+        assert!(mgr.undo().is_empty());
+    }
+
     #[test]
     fn test_clear() {
         let n = 20;
@@ -374,4 +1863,128 @@ mod tests {
         assert!(mgr.is_empty());
         assert_eq!(mgr.len(), 0);
     }
+
+    #[test]
+    fn test_remove_aged() {
+        let day = std::time::Duration::from_secs(24 * 60 * 60);
+        let mut clips = create_clips(3);
+        for clip in &mut clips[..2] {
+            clip.timestamp -= 2 * day;
+        }
+        let mut mgr = ClipboardManager::new();
+        mgr.import(&clips);
+        mgr.set_pinned(clips[0].id, true);
+        assert!(mgr.remove_aged().is_empty());
+
+        mgr.set_max_age(Some(day));
+        let aged: Vec<_> = mgr.remove_aged().into_iter().map(|clip| clip.id).collect();
+        assert_eq!(aged, vec![clips[1].id]);
+        assert_eq!(mgr.len(), 2);
+        assert!(mgr.trash().is_empty());
+    }
+
+    #[test]
+    fn test_clear_older_than() {
+        let day = std::time::Duration::from_secs(24 * 60 * 60);
+        let mut clips = create_clips(3);
+        for clip in &mut clips[..2] {
+            clip.timestamp -= 2 * day;
+        }
+        let mut mgr = ClipboardManager::new();
+        mgr.import(&clips);
+        mgr.set_pinned(clips[0].id, true);
+
+        assert_eq!(mgr.clear_older_than(day), vec![clips[1].id]);
+        assert_eq!(mgr.len(), 2);
+        assert!(mgr.get(clips[0].id).is_some());
+        assert!(mgr.get(clips[2].id).is_some());
+        assert!(mgr.clear_older_than(day).is_empty());
+        assert_eq!(mgr.undo(), vec![clips[1].id]);
+    }
+
+    #[test]
+    fn test_spill() {
+        let nanos =
+            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+        let directory = std::env::temp_dir().join(format!("clipcat-manager-spill-{}", nanos));
+        let mut mgr = ClipboardManager::new();
+        mgr.set_spill(Some(SpillStore::open(&directory, 64).unwrap()));
+
+        let large = ClipboardData::new_clipboard(&"clipcat ".repeat(1000));
+        let id = mgr.insert(large.clone());
+        mgr.insert_clipboard("small");
+        let stub = mgr.iter().find(|clip| clip.id == id).unwrap();
+        assert!(stub.spilled.is_some());
+        assert!(stub.data.len() < large.data.len());
+        assert_eq!(stub.size(), large.size());
+        assert_eq!(mgr.get(id).unwrap().data, large.data);
+        assert!(mgr.list().iter().all(|clip| clip.spilled.is_none()));
+
+        // the file is kept while the clip is in the trash
+        assert!(mgr.remove(id));
+        assert_eq!(mgr.undo(), vec![id]);
+        assert_eq!(mgr.get(id).unwrap().data, large.data);
+
+        // spilled duplicates are merged by the digest of their file
+        mgr.set_deduplicate(false);
+        let copy = mgr.insert(large.clone());
+        assert_ne!(copy, id);
+        assert_eq!(mgr.merge_duplicates(), 1);
+        assert_eq!(mgr.get(copy).unwrap().data, large.data);
+
+        mgr.set_trash_limits(0, std::time::Duration::from_secs(60));
+        mgr.clear();
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let mut mgr = ClipboardManager::new();
+        mgr.set_memory_budget(Some(20));
+        let mut old = ClipboardData::new_clipboard(&"a".repeat(8));
+        old.timestamp -= std::time::Duration::from_secs(100);
+        let old_id = mgr.insert(old);
+        let mut pinned = ClipboardData::new_clipboard(&"b".repeat(4));
+        pinned.pinned = true;
+        let pinned_id = mgr.insert(pinned);
+
+        // clips are evicted without a spill store
+        let id = mgr.insert_clipboard(&"c".repeat(10));
+        assert_eq!(mgr.get(old_id), None);
+        assert!(mgr.get(pinned_id).is_some());
+        assert!(mgr.get(id).is_some());
+        assert_eq!(mgr.memory_pressure().evicted, 1);
+
+        let nanos =
+            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+        let directory = std::env::temp_dir().join(format!("clipcat-manager-budget-{}", nanos));
+        let mut mgr = ClipboardManager::new();
+        mgr.set_spill(Some(SpillStore::open(&directory, 1024 * 1024).unwrap()));
+        let large = ClipboardData::new_clipboard(&"clipcat ".repeat(1000));
+        let large_id = mgr.insert(large.clone());
+        let small_id = mgr.insert_clipboard("small");
+
+        // large clips are spilled although they are below the threshold
+        mgr.set_memory_budget(Some(4096));
+        assert!(mgr.resident_bytes() <= 4096);
+        assert_eq!(mgr.memory_pressure().spilled, 1);
+        assert_eq!(mgr.len(), 2);
+        assert_eq!(mgr.get(large_id).unwrap().data, large.data);
+        assert!(mgr.get(small_id).is_some());
+
+        // sensitive clips are evicted instead of being spilled
+        let mut secret = ClipboardData::new_clipboard(&"secret ".repeat(1000));
+        secret.sensitive = true;
+        secret.pinned = true;
+        let secret_id = mgr.insert(secret);
+        assert_eq!(mgr.get(secret_id), None);
+        assert_eq!(mgr.memory_pressure().spilled, 1);
+        assert!(std::fs::read_dir(&directory).unwrap().all(|entry| !std::fs::read(
+            entry.unwrap().path()
+        )
+        .unwrap()
+        .starts_with(b"secret")));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
 }
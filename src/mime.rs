@@ -0,0 +1,291 @@
+pub const TEXT_PLAIN: &str = "text/plain;charset=utf-8";
+pub const IMAGE_PNG: &str = "image/png";
+pub const IMAGE_JPEG: &str = "image/jpeg";
+pub const TEXT_HTML: &str = "text/html";
+pub const TEXT_RTF: &str = "text/rtf";
+pub const TEXT_URI_LIST: &str = "text/uri-list";
+pub const GNOME_COPIED_FILES: &str = "x-special/gnome-copied-files";
+pub const KDE_PASSWORD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
+
+/// Offered alongside clips which are pasted only once, the clipcat watchers do
+/// not load selections which carry it.
+pub const CLIPCAT_ONE_SHOT: &str = "application/x-clipcat-one-shot";
+
+/// Image targets which are captured from the clipboard, in order of preference.
+pub const IMAGE_TYPES: &[&str] = &[IMAGE_PNG, IMAGE_JPEG];
+
+/// Targets which are stored with a clip besides its main representation and
+/// offered again when the clip is pasted, plain text is stored as well.
+pub const ALTERNATIVE_TYPES: &[&str] =
+    &[TEXT_HTML, TEXT_RTF, IMAGE_PNG, IMAGE_JPEG, TEXT_URI_LIST, GNOME_COPIED_FILES];
+
+#[inline]
+pub fn is_text(mime: &str) -> bool { mime.is_empty() || mime.starts_with("text/plain") }
+
+#[inline]
+pub fn is_image(mime: &str) -> bool { mime.starts_with("image/") }
+
+#[inline]
+pub fn is_file_list(mime: &str) -> bool { mime == TEXT_URI_LIST }
+
+/// Returns `true` if target `mime` with `value` asks clipboard managers not to
+/// store the clip, password managers offer `x-kde-passwordManagerHint` with
+/// value `secret` for this.
+#[inline]
+pub fn is_sensitive_hint(mime: &str, value: &[u8]) -> bool {
+    mime == KDE_PASSWORD_MANAGER_HINT && value == b"secret"
+}
+
+/// Parses a `text/uri-list` as defined by RFC 2483, comments and empty lines
+/// are skipped.
+pub fn parse_uri_list(data: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// Formats `uris` as `text/uri-list`.
+pub fn format_uri_list(uris: &[String]) -> Vec<u8> {
+    let mut data = String::new();
+    for uri in uris {
+        data.push_str(uri);
+        data.push_str("\r\n");
+    }
+    data.into_bytes()
+}
+
+/// Converts `x-special/gnome-copied-files` into a list of URIs. The first line
+/// of the target is the operation (`copy` or `cut`), the remaining lines are
+/// URIs.
+pub fn parse_gnome_copied_files(data: &[u8]) -> Vec<String> {
+    let uris = parse_uri_list(data);
+    match uris.first().map(String::as_str) {
+        Some("copy") | Some("cut") => uris[1..].to_vec(),
+        _ => uris,
+    }
+}
+
+/// Formats `uris` as `x-special/gnome-copied-files`.
+pub fn format_gnome_copied_files(uris: &[String]) -> Vec<u8> {
+    let mut data = String::from("copy");
+    for uri in uris {
+        data.push('\n');
+        data.push_str(uri);
+    }
+    data.into_bytes()
+}
+
+/// Converts a `file://` URI into a local path, other URIs are returned as they
+/// are.
+pub fn uri_to_path(uri: &str) -> String {
+    let path = match uri.strip_prefix("file://") {
+        // skip the optional host part
+        Some(rest) => match rest.find('/') {
+            Some(idx) => &rest[idx..],
+            None => return uri.to_owned(),
+        },
+        None => return uri.to_owned(),
+    };
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let byte = std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(byte) = byte {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Converts a local path into a `file://` URI, the backslashes of Windows
+/// paths become slashes.
+pub fn path_to_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b':' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            byte => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Reads the dimensions of a PNG or JPEG image from its header without decoding
+/// the image.
+pub fn image_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    png_dimensions(data).or_else(|| jpeg_dimensions(data))
+}
+
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    const SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+    if data.len() < 24 || !data.starts_with(SIGNATURE) || &data[12..16] != b"IHDR" {
+        return None;
+    }
+
+    let width = u32::from_be_bytes([data[16], data[17], data[18], data[19]]);
+    let height = u32::from_be_bytes([data[20], data[21], data[22], data[23]]);
+    Some((width, height))
+}
+
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.len() < 4 || data[0] != 0xff || data[1] != 0xd8 {
+        return None;
+    }
+
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xff {
+            return None;
+        }
+
+        let marker = data[pos + 1];
+        match marker {
+            // padding between segments
+            0xff => {
+                pos += 1;
+                continue;
+            }
+            // markers without payload
+            0x01 | 0xd0..=0xd7 => {
+                pos += 2;
+                continue;
+            }
+            _ => {}
+        }
+
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let is_start_of_frame =
+            (0xc0..=0xcf).contains(&marker) && marker != 0xc4 && marker != 0xc8 && marker != 0xcc;
+        if is_start_of_frame {
+            if pos + 9 > data.len() {
+                return None;
+            }
+            let height = u16::from_be_bytes([data[pos + 5], data[pos + 6]]) as u32;
+            let width = u16::from_be_bytes([data[pos + 7], data[pos + 8]]) as u32;
+            return Some((width, height));
+        }
+
+        pos += 2 + length;
+    }
+
+    None
+}
+
+/// Formats a size in bytes into a human readable string.
+pub fn human_readable_size(size: usize) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mime;
+
+    #[test]
+    fn test_is_text() {
+        assert!(mime::is_text(""));
+        assert!(mime::is_text(mime::TEXT_PLAIN));
+        assert!(mime::is_text("text/plain"));
+        assert!(!mime::is_text(mime::IMAGE_PNG));
+        assert!(mime::is_image(mime::IMAGE_JPEG));
+        assert!(mime::is_file_list(mime::TEXT_URI_LIST));
+        assert!(!mime::is_text(mime::TEXT_URI_LIST));
+    }
+
+    #[test]
+    fn test_sensitive_hint() {
+        assert!(mime::is_sensitive_hint(mime::KDE_PASSWORD_MANAGER_HINT, b"secret"));
+        assert!(!mime::is_sensitive_hint(mime::KDE_PASSWORD_MANAGER_HINT, b"accepted"));
+        assert!(!mime::is_sensitive_hint(mime::TEXT_PLAIN, b"secret"));
+    }
+
+    #[test]
+    fn test_uri_list() {
+        let data = b"# comment\r\nfile:///home/user/a.txt\r\n\r\nfile:///tmp/b%20c.png\r\n";
+        let uris = mime::parse_uri_list(data);
+        assert_eq!(uris, vec!["file:///home/user/a.txt", "file:///tmp/b%20c.png"]);
+        assert_eq!(mime::parse_uri_list(&mime::format_uri_list(&uris)), uris);
+
+        let gnome = mime::format_gnome_copied_files(&uris);
+        assert_eq!(gnome, b"copy\nfile:///home/user/a.txt\nfile:///tmp/b%20c.png".to_vec());
+        assert_eq!(mime::parse_gnome_copied_files(&gnome), uris);
+        assert_eq!(mime::parse_gnome_copied_files(b"cut\nfile:///a"), vec!["file:///a"]);
+    }
+
+    #[test]
+    fn test_uri_to_path() {
+        assert_eq!(mime::uri_to_path("file:///tmp/b%20c.png"), "/tmp/b c.png");
+        assert_eq!(mime::uri_to_path("file://localhost/etc/hosts"), "/etc/hosts");
+        assert_eq!(mime::uri_to_path("file:///100%"), "/100%");
+        assert_eq!(mime::uri_to_path("https://example.com/"), "https://example.com/");
+    }
+
+    #[test]
+    fn test_path_to_uri() {
+        assert_eq!(mime::path_to_uri("/tmp/b c.png"), "file:///tmp/b%20c.png");
+        assert_eq!(mime::path_to_uri("C:\\Users\\me\\ü.txt"), "file:///C:/Users/me/%C3%BC.txt");
+        assert_eq!(mime::uri_to_path(&mime::path_to_uri("/tmp/100% done")), "/tmp/100% done");
+    }
+
+    #[test]
+    fn test_png_dimensions() {
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0, 0, 0, 13];
+        data.extend_from_slice(b"IHDR");
+        data.extend_from_slice(&1920u32.to_be_bytes());
+        data.extend_from_slice(&1080u32.to_be_bytes());
+        assert_eq!(mime::image_dimensions(&data), Some((1920, 1080)));
+        assert_eq!(mime::image_dimensions(&data[..20]), None);
+    }
+
+    #[test]
+    fn test_jpeg_dimensions() {
+        let data = [
+            0xff, 0xd8, // SOI
+            0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, // APP0 with 2 bytes payload
+            0xff, 0xc0, 0x00, 0x0b, 0x08, 0x02, 0x58, 0x03, 0x20, 0x01, 0x01, 0x11,
+            0x00, // SOF0
+        ];
+        assert_eq!(mime::image_dimensions(&data), Some((800, 600)));
+        assert_eq!(mime::image_dimensions(b"not an image"), None);
+    }
+
+    #[test]
+    fn test_human_readable_size() {
+        assert_eq!(mime::human_readable_size(0), "0 B");
+        assert_eq!(mime::human_readable_size(1023), "1023 B");
+        assert_eq!(mime::human_readable_size(1536), "1.5 KiB");
+        assert_eq!(mime::human_readable_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
@@ -4,31 +4,57 @@ use std::{
         Arc,
     },
     thread,
+    time::Duration,
 };
 
-use snafu::ResultExt;
 use tokio::sync::broadcast::{self, error::SendError};
-use x11_clipboard::Clipboard;
 
-use crate::{error, ClipboardError, ClipboardEvent, ClipboardType, MonitorState};
+use crate::{
+    backend::{self, ClipboardBackendType, ClipboardWatcher, LineEndings},
+    ClipboardError, ClipboardEvent, ClipboardType, MonitorState,
+};
+
+// Delays between attempts to connect a watcher again after its connection
+// failed, e.g. when the X server restarts.
+const RECONNECT_MIN_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
 
 pub struct ClipboardMonitor {
     is_running: Arc<AtomicBool>,
     event_sender: broadcast::Sender<ClipboardEvent>,
     clipboard_thread: Option<thread::JoinHandle<()>>,
     primary_thread: Option<thread::JoinHandle<()>>,
+    display_threads: Vec<thread::JoinHandle<()>>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct ClipboardMonitorOptions {
     pub load_current: bool,
     pub enable_clipboard: bool,
     pub enable_primary: bool,
+    pub backend: ClipboardBackendType,
+
+    /// Saves the clipboard when its owner exits, as the X11 clipboard manager.
+    pub manage_clipboard: bool,
+
+    pub line_endings: LineEndings,
+
+    /// X11 displays or Wayland seats which are monitored besides the default
+    /// one, their clips carry the display they are captured from.
+    pub displays: Vec<String>,
 }
 
 impl Default for ClipboardMonitorOptions {
     fn default() -> Self {
-        ClipboardMonitorOptions { load_current: true, enable_clipboard: true, enable_primary: true }
+        ClipboardMonitorOptions {
+            load_current: true,
+            enable_clipboard: true,
+            enable_primary: true,
+            backend: ClipboardBackendType::Auto,
+            manage_clipboard: false,
+            line_endings: LineEndings::Keep,
+            displays: Vec::new(),
+        }
     }
 }
 
@@ -36,17 +62,24 @@ impl ClipboardMonitor {
     pub fn new(opts: ClipboardMonitorOptions) -> Result<ClipboardMonitor, ClipboardError> {
         let (event_sender, _event_receiver) = broadcast::channel(16);
 
+        let backend = opts.backend.resolve();
+        tracing::info!("Clipboard backend: {}", backend.to_string());
+
         let is_running = Arc::new(AtomicBool::new(true));
         let mut monitor = ClipboardMonitor {
             is_running: is_running.clone(),
             event_sender: event_sender.clone(),
             clipboard_thread: None,
             primary_thread: None,
+            display_threads: Vec::new(),
         };
 
         if opts.enable_clipboard {
             let thread = build_thread(
+                backend,
+                None,
                 opts.load_current,
+                opts.line_endings,
                 is_running.clone(),
                 ClipboardType::Clipboard,
                 event_sender.clone(),
@@ -54,13 +87,68 @@ impl ClipboardMonitor {
             monitor.clipboard_thread = Some(thread);
         }
 
-        if opts.enable_primary {
-            let thread =
-                build_thread(opts.load_current, is_running, ClipboardType::Primary, event_sender)?;
+        if opts.enable_primary && !backend.has_primary() {
+            tracing::info!("Clipboard backend {} has no primary selection", backend.to_string());
+        } else if opts.enable_primary {
+            let thread = build_thread(
+                backend,
+                None,
+                opts.load_current,
+                opts.line_endings,
+                is_running.clone(),
+                ClipboardType::Primary,
+                event_sender.clone(),
+            )?;
             monitor.primary_thread = Some(thread);
         }
 
-        if monitor.clipboard_thread.is_none() && monitor.primary_thread.is_none() {
+        if !opts.displays.is_empty() && !backend.has_displays() {
+            tracing::warn!("Clipboard backend {} has no other displays", backend.to_string());
+        } else {
+            for name in &opts.displays {
+                let mut clipboard_types = Vec::new();
+                if opts.enable_clipboard {
+                    clipboard_types.push(ClipboardType::Clipboard);
+                }
+                if opts.enable_primary && backend.has_primary() {
+                    clipboard_types.push(ClipboardType::Primary);
+                }
+                for clipboard_type in clipboard_types {
+                    // a nested X server may not be running yet, the other
+                    // displays are monitored anyway
+                    match build_thread(
+                        backend,
+                        Some(name.clone()),
+                        opts.load_current,
+                        opts.line_endings,
+                        is_running.clone(),
+                        clipboard_type,
+                        event_sender.clone(),
+                    ) {
+                        Ok(thread) => monitor.display_threads.push(thread),
+                        Err(err) => tracing::warn!(
+                            "Could not monitor {:?} of display {}, error: {}",
+                            clipboard_type,
+                            name,
+                            err
+                        ),
+                    }
+                }
+            }
+        }
+
+        if opts.manage_clipboard {
+            match backend::manage_clipboard(backend) {
+                Ok(true) => tracing::info!("Clipboard is saved when its owner exits"),
+                Ok(false) => {}
+                Err(err) => tracing::warn!("Could not become clipboard manager, error: {}", err),
+            }
+        }
+
+        if monitor.clipboard_thread.is_none()
+            && monitor.primary_thread.is_none()
+            && monitor.display_threads.is_empty()
+        {
             tracing::warn!("Both clipboard and primary are not monitored");
         }
 
@@ -105,70 +193,101 @@ impl ClipboardMonitor {
 }
 
 fn build_thread(
+    backend: ClipboardBackendType,
+    display: Option<String>,
     load_current: bool,
+    line_endings: LineEndings,
     is_running: Arc<AtomicBool>,
     clipboard_type: ClipboardType,
     sender: broadcast::Sender<ClipboardEvent>,
 ) -> Result<thread::JoinHandle<()>, ClipboardError> {
-    let send_event = move |data: &str| {
-        let event = match clipboard_type {
-            ClipboardType::Clipboard => ClipboardEvent::new_clipboard(data),
-            ClipboardType::Primary => ClipboardEvent::new_primary(data),
-        };
-        sender.send(event)
-    };
-
-    let clipboard = Clipboard::new().context(error::InitializeX11Clipboard)?;
-    let atom_clipboard = match clipboard_type {
-        ClipboardType::Clipboard => clipboard.getter.atoms.clipboard,
-        ClipboardType::Primary => clipboard.getter.atoms.primary,
-    };
-    let atom_utf8string = clipboard.getter.atoms.utf8_string;
-    let atom_property = clipboard.getter.atoms.property;
+    let mut watcher = ClipboardWatcher::new(backend, clipboard_type, display.as_deref())?;
 
     let join_handle = thread::spawn(move || {
         let mut last = if load_current {
-            let result = clipboard.load(atom_clipboard, atom_utf8string, atom_property, None);
-            match result {
-                Ok(data) => {
-                    let data = String::from_utf8_lossy(&data);
-                    if !data.is_empty() {
-                        if let Err(SendError(_curr)) = send_event(&data) {
+            match watcher.load() {
+                Ok(mut event) => {
+                    line_endings.apply(&mut event);
+                    event.display = display.clone();
+                    if !event.is_empty() {
+                        if let Err(SendError(_curr)) = sender.send(event.clone()) {
                             tracing::info!("ClipboardEvent receiver is closed.");
                             return;
                         }
                     }
-                    data.into_owned()
+                    Some(event)
                 }
-                Err(_) => String::new(),
+                Err(_) => None,
             }
         } else {
-            String::new()
+            None
         };
 
+        // sends `curr` unless the monitor is disabled or it was sent already,
+        // returns `false` once nobody receives events anymore
+        let mut send_event = |mut curr: ClipboardEvent| {
+            line_endings.apply(&mut curr);
+            curr.display = display.clone();
+            if is_running.load(Ordering::Acquire)
+                && !curr.is_empty()
+                && last.as_ref() != Some(&curr)
+            {
+                if let Err(SendError(_curr)) = sender.send(curr.clone()) {
+                    tracing::info!("ClipboardEvent receiver is closed.");
+                    return false;
+                }
+                last = Some(curr);
+            }
+            true
+        };
+
+        let mut delay = RECONNECT_MIN_DELAY;
         loop {
-            let result = clipboard.load_wait(atom_clipboard, atom_utf8string, atom_property);
-            match result {
+            match watcher.wait() {
                 Ok(curr) => {
-                    if is_running.load(Ordering::Acquire) {
-                        let curr = String::from_utf8_lossy(&curr);
-                        if !curr.is_empty() && last != curr {
-                            last = curr.into_owned();
-                            if let Err(SendError(_curr)) = send_event(&last) {
-                                tracing::info!("ClipboardEvent receiver is closed.");
-                                return;
-                            };
-                        }
+                    delay = RECONNECT_MIN_DELAY;
+                    if !send_event(curr) {
+                        return;
                     }
                 }
                 Err(err) => {
-                    drop(clipboard);
-                    tracing::error!(
-                        "Failed to load clipboard, error: {}, ClipboardMonitor({:?}) is closing",
+                    // the display server may have restarted, the history is
+                    // kept by the manager while the watcher is connected again
+                    drop(watcher);
+                    tracing::warn!(
+                        "Failed to load clipboard, error: {}, ClipboardMonitor({:?}) reconnects \
+                         in {:?}",
                         err,
-                        clipboard_type
+                        clipboard_type,
+                        delay
                     );
-                    return;
+                    watcher = loop {
+                        thread::sleep(delay);
+                        delay = next_reconnect_delay(delay);
+                        if sender.receiver_count() == 0 {
+                            tracing::info!("ClipboardEvent receiver is closed.");
+                            return;
+                        }
+                        match ClipboardWatcher::new(backend, clipboard_type, display.as_deref()) {
+                            Ok(watcher) => break watcher,
+                            Err(err) => tracing::warn!(
+                                "Could not reconnect ClipboardMonitor({:?}), error: {}, retrying \
+                                 in {:?}",
+                                clipboard_type,
+                                err,
+                                delay
+                            ),
+                        }
+                    };
+                    tracing::info!("ClipboardMonitor({:?}) is reconnected", clipboard_type);
+
+                    // clips copied while the watcher was disconnected are
+                    // lost, the current one is captured
+                    if let Ok(curr) = watcher.load() {
+                        if !send_event(curr) {
+                            return;
+                        }
+                    }
                 }
             }
         }
@@ -176,3 +295,22 @@ fn build_thread(
 
     Ok(join_handle)
 }
+
+// Doubles the delay between attempts to reconnect up to
+// `RECONNECT_MAX_DELAY`.
+#[inline]
+fn next_reconnect_delay(delay: Duration) -> Duration { (delay * 2).min(RECONNECT_MAX_DELAY) }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::monitor::{next_reconnect_delay, RECONNECT_MAX_DELAY, RECONNECT_MIN_DELAY};
+
+    #[test]
+    fn test_reconnect_delay() {
+        assert_eq!(next_reconnect_delay(RECONNECT_MIN_DELAY), Duration::from_secs(2));
+        assert_eq!(next_reconnect_delay(Duration::from_secs(40)), RECONNECT_MAX_DELAY);
+        assert_eq!(next_reconnect_delay(RECONNECT_MAX_DELAY), RECONNECT_MAX_DELAY);
+    }
+}
@@ -0,0 +1,53 @@
+use snafu::ResultExt;
+use tesseract::Tesseract;
+
+/// Language which is recognized if none is specified.
+pub const DEFAULT_LANGUAGE: &str = "eng";
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum OcrError {
+    #[snafu(display("Could not initialize tesseract with language {}: {}", language, message))]
+    Initialize { language: String, message: String },
+
+    #[snafu(display("Could not recognize text in image: {}", message))]
+    Recognize { message: String },
+
+    #[snafu(display("Could not find any text in image"))]
+    NoText,
+
+    #[snafu(display("Could not join recognition task, error: {}", source))]
+    JoinTask { source: tokio::task::JoinError },
+}
+
+/// Extracts the text of a PNG or JPEG `image` with tesseract, languages are
+/// combined with `+`, e.g. `eng+deu`.
+pub async fn recognize(image: Vec<u8>, language: &str) -> Result<String, OcrError> {
+    let language = language.to_owned();
+    tokio::task::spawn_blocking(move || recognize_blocking(&image, &language))
+        .await
+        .context(JoinTask)?
+}
+
+fn recognize_blocking(image: &[u8], language: &str) -> Result<String, OcrError> {
+    let recognize_failed =
+        |err: &dyn std::fmt::Display| OcrError::Recognize { message: err.to_string() };
+
+    let tesseract = Tesseract::new(None, Some(language)).map_err(|err| OcrError::Initialize {
+        language: language.to_owned(),
+        message: err.to_string(),
+    })?;
+    let text = tesseract
+        .set_image_from_mem(image)
+        .map_err(|err| recognize_failed(&err))?
+        .recognize()
+        .map_err(|err| recognize_failed(&err))?
+        .get_text()
+        .map_err(|err| recognize_failed(&err))?;
+
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(OcrError::NoText);
+    }
+    Ok(text.to_owned())
+}
@@ -0,0 +1,114 @@
+use std::{process::Command, time::Duration};
+
+use snafu::ResultExt;
+
+use crate::{error, ClipboardBackendType, ClipboardError, ClipboardType};
+
+/// Synthesizes the key which pastes a clipboard into the focused window, with
+/// `xdotool` on X11, `wtype` on Wayland, `osascript` on macOS and PowerShell on
+/// Windows unless the commands are set.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct PasteOptions {
+    /// Time for the menu to close and the previous window to get focus back.
+    pub delay: Duration,
+    pub clipboard_command: Option<String>,
+    pub primary_command: Option<String>,
+}
+
+impl PasteOptions {
+    /// Returns the shell command which pastes `clipboard_type`.
+    pub fn command(&self, backend: ClipboardBackendType, clipboard_type: ClipboardType) -> String {
+        let command = match clipboard_type {
+            ClipboardType::Clipboard => &self.clipboard_command,
+            ClipboardType::Primary => &self.primary_command,
+        };
+        match command {
+            Some(command) => command.clone(),
+            None => default_command(backend, clipboard_type).to_owned(),
+        }
+    }
+}
+
+// Paste commands are run by `cmd` on Windows and by `sh` elsewhere.
+#[cfg(windows)]
+const SHELL: (&str, &str) = ("cmd", "/C");
+#[cfg(not(windows))]
+const SHELL: (&str, &str) = ("sh", "-c");
+
+fn default_command(backend: ClipboardBackendType, clipboard_type: ClipboardType) -> &'static str {
+    match (backend.resolve(), clipboard_type) {
+        (ClipboardBackendType::MacOS, _) => {
+            "osascript -e 'tell application \"System Events\" to keystroke \"v\" using command \
+             down'"
+        }
+        (ClipboardBackendType::Windows, _) => {
+            "powershell -NoProfile -Command \"(New-Object -ComObject \
+             WScript.Shell).SendKeys('^v')\""
+        }
+        (ClipboardBackendType::Wayland, ClipboardType::Clipboard) => "wtype -M ctrl v -m ctrl",
+        (ClipboardBackendType::Wayland, ClipboardType::Primary) => {
+            "wtype -M shift -k Insert -m shift"
+        }
+        (_, ClipboardType::Clipboard) => "xdotool key --clearmodifiers ctrl+v",
+        (_, ClipboardType::Primary) => "xdotool key --clearmodifiers shift+Insert",
+    }
+}
+
+/// Waits for `opts.delay` and pastes `clipboard_type` into the focused window.
+pub async fn paste(
+    backend: ClipboardBackendType,
+    clipboard_type: ClipboardType,
+    opts: &PasteOptions,
+) -> Result<(), ClipboardError> {
+    tokio::time::sleep(opts.delay).await;
+
+    let command = opts.command(backend, clipboard_type);
+    let status = {
+        let command = command.clone();
+        tokio::task::spawn_blocking(move || {
+            Command::new(SHELL.0).arg(SHELL.1).arg(&command).status()
+        })
+        .await
+        .context(error::SpawnBlockingTask)?
+    }
+    .context(error::RunPasteCommand { command: command.clone() })?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ClipboardError::PasteCommandFailed { command, status: status.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{paste::PasteOptions, ClipboardBackendType, ClipboardType};
+
+    #[test]
+    fn test_command() {
+        let mut opts = PasteOptions::default();
+        assert_eq!(
+            opts.command(ClipboardBackendType::X11, ClipboardType::Clipboard),
+            "xdotool key --clearmodifiers ctrl+v"
+        );
+        assert_eq!(
+            opts.command(ClipboardBackendType::Wayland, ClipboardType::Clipboard),
+            "wtype -M ctrl v -m ctrl"
+        );
+        assert!(opts
+            .command(ClipboardBackendType::MacOS, ClipboardType::Clipboard)
+            .starts_with("osascript"));
+        assert!(opts
+            .command(ClipboardBackendType::Windows, ClipboardType::Primary)
+            .contains("SendKeys('^v')"));
+
+        opts.primary_command = Some("xdotool click 2".to_owned());
+        assert_eq!(
+            opts.command(ClipboardBackendType::X11, ClipboardType::Primary),
+            "xdotool click 2"
+        );
+        assert_eq!(
+            opts.command(ClipboardBackendType::X11, ClipboardType::Clipboard),
+            "xdotool key --clearmodifiers ctrl+v"
+        );
+    }
+}
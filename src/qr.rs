@@ -0,0 +1,102 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use snafu::ResultExt;
+
+// Smallest width and height of rendered images in pixels.
+const MIN_IMAGE_SIZE: u32 = 256;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum QrCodeError {
+    #[snafu(display("Could not encode clip as QR code, error: {}", source))]
+    Encode { source: qrcode::types::QrError },
+
+    #[snafu(display("Could not save QR code to {}, error: {}", filename.display(), source))]
+    SaveImage { filename: PathBuf, source: image::ImageError },
+}
+
+/// What a QR code carries, scanners open URLs and join Wi-Fi networks.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Payload {
+    Url,
+    Wifi,
+    Text,
+}
+
+impl Payload {
+    pub fn detect(text: &str) -> Payload {
+        let text = text.trim();
+        if text.starts_with("WIFI:") {
+            Payload::Wifi
+        } else if text.contains("://") && !text.contains(char::is_whitespace) {
+            Payload::Url
+        } else {
+            Payload::Text
+        }
+    }
+}
+
+impl fmt::Display for Payload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Payload::Url => f.write_str("URL"),
+            Payload::Wifi => f.write_str("Wi-Fi network"),
+            Payload::Text => f.write_str("text"),
+        }
+    }
+}
+
+/// Renders `text` as a QR code of Unicode half blocks, light on dark so it
+/// scans in terminals with a dark background.
+pub fn render_terminal(text: &str) -> Result<String, QrCodeError> {
+    let code = encode(text)?;
+    Ok(code.render::<Dense1x2>().dark_color(Dense1x2::Light).light_color(Dense1x2::Dark).build())
+}
+
+/// Renders `text` as a QR code and saves it as PNG image to `filename`.
+pub fn save_png(text: &str, filename: &Path) -> Result<(), QrCodeError> {
+    let code = encode(text)?;
+    let image =
+        code.render::<image::Luma<u8>>().min_dimensions(MIN_IMAGE_SIZE, MIN_IMAGE_SIZE).build();
+    image
+        .save_with_format(filename, image::ImageFormat::Png)
+        .context(SaveImage { filename: filename.to_path_buf() })
+}
+
+// URLs and Wi-Fi strings are usually copied with a trailing newline which
+// scanners would take as part of them.
+fn encode(text: &str) -> Result<QrCode, QrCodeError> {
+    let text = match Payload::detect(text) {
+        Payload::Url | Payload::Wifi => text.trim(),
+        Payload::Text => text,
+    };
+    QrCode::new(text.as_bytes()).context(Encode)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::qr::{render_terminal, Payload};
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Payload::detect("https://example.com/?q=clipcat\n"), Payload::Url);
+        assert_eq!(Payload::detect("WIFI:T:WPA;S:clipcat;P:meow;;"), Payload::Wifi);
+        assert_eq!(Payload::detect("see https://example.com"), Payload::Text);
+        assert_eq!(Payload::detect("clipcat"), Payload::Text);
+    }
+
+    #[test]
+    fn test_render_terminal() {
+        let code = render_terminal("https://example.com\n").unwrap();
+        let lines: Vec<_> = code.lines().collect();
+        assert!(!lines.is_empty());
+        assert!(lines.iter().all(|line| line.chars().count() == lines[0].chars().count()));
+        assert_eq!(code, render_terminal("https://example.com").unwrap());
+
+        assert!(render_terminal(&"clipcat".repeat(1000)).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use std::{cmp::Ordering, fmt, str::FromStr, time::SystemTime};
+
+use crate::ClipboardData;
+
+// Age in seconds after which the weight of a clip is halved.
+const FRECENCY_HALF_LIFE: f64 = 24.0 * 60.0 * 60.0;
+
+/// Order in which clips are listed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Default)]
+pub enum Ranking {
+    /// Most recently copied clips first.
+    #[serde(rename = "recency")]
+    #[default]
+    Recency,
+
+    /// Clips which are used often and recently first.
+    #[serde(rename = "frecency")]
+    Frecency,
+}
+
+impl Ranking {
+    pub fn sort(self, clips: &mut [ClipboardData]) {
+        match self {
+            Ranking::Recency => clips.sort(),
+            Ranking::Frecency => {
+                let now = SystemTime::now();
+                clips.sort_by(|a, b| {
+                    frecency(b, now)
+                        .partial_cmp(&frecency(a, now))
+                        .unwrap_or(Ordering::Equal)
+                        .then_with(|| a.cmp(b))
+                });
+            }
+        }
+    }
+}
+
+/// Order which is asked for when clips are listed, instead of the ranking.
+/// Every order lists the greatest clips first, ties are listed by recency.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum SortOrder {
+    /// When clips were last copied.
+    Time,
+
+    /// When clips were captured first.
+    Created,
+
+    Size,
+
+    UseCount,
+}
+
+impl SortOrder {
+    pub const ALL: &'static [SortOrder] =
+        &[SortOrder::Time, SortOrder::Created, SortOrder::Size, SortOrder::UseCount];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortOrder::Time => "time",
+            SortOrder::Created => "created",
+            SortOrder::Size => "size",
+            SortOrder::UseCount => "use-count",
+        }
+    }
+
+    pub fn sort(self, clips: &mut [ClipboardData]) {
+        match self {
+            SortOrder::Time => clips.sort(),
+            SortOrder::Created => {
+                clips.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.cmp(b)))
+            }
+            SortOrder::Size => clips.sort_by(|a, b| b.size().cmp(&a.size()).then_with(|| a.cmp(b))),
+            SortOrder::UseCount => {
+                clips.sort_by(|a, b| b.use_count.cmp(&a.use_count).then_with(|| a.cmp(b)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.name()) }
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<SortOrder, String> {
+        SortOrder::ALL.iter().copied().find(|order| order.name() == name).ok_or_else(|| {
+            format!("Unknown sort order {}, expected time, created, size or use-count", name)
+        })
+    }
+}
+
+/// Scores `clip` by its use count, the score decays exponentially with the age
+/// of the clip.
+fn frecency(clip: &ClipboardData, now: SystemTime) -> f64 {
+    let age = now.duration_since(clip.timestamp).map_or(0.0, |age| age.as_secs_f64());
+    (clip.use_count + 1) as f64 * 0.5f64.powf(age / FRECENCY_HALF_LIFE)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{ClipboardData, Ranking, SortOrder};
+
+    #[test]
+    fn test_sort() {
+        let mut used = ClipboardData::new_clipboard("used");
+        used.timestamp -= Duration::from_secs(60 * 60);
+        used.use_count = 5;
+        let recent = ClipboardData::new_clipboard("recent");
+        let mut stale = ClipboardData::new_clipboard("stale");
+        stale.timestamp -= Duration::from_secs(30 * 24 * 60 * 60);
+        stale.use_count = 100;
+
+        let mut clips = vec![stale.clone(), used.clone(), recent.clone()];
+        Ranking::Recency.sort(&mut clips);
+        assert_eq!(clips, vec![recent.clone(), used.clone(), stale.clone()]);
+
+        Ranking::Frecency.sort(&mut clips);
+        assert_eq!(clips, vec![used, recent, stale]);
+    }
+
+    #[test]
+    fn test_sort_order() {
+        let mut old = ClipboardData::new_clipboard("a long clip which was captured first");
+        old.created_at -= Duration::from_secs(60 * 60);
+        old.use_count = 1;
+        let mut used = ClipboardData::new_clipboard("used");
+        used.timestamp -= Duration::from_secs(60);
+        used.use_count = 7;
+        let new = ClipboardData::new_clipboard("new");
+
+        let mut clips = vec![old.clone(), used.clone(), new.clone()];
+        SortOrder::Time.sort(&mut clips);
+        assert_eq!(clips, vec![new.clone(), old.clone(), used.clone()]);
+        SortOrder::Created.sort(&mut clips);
+        assert_eq!(clips[2], old);
+        SortOrder::Size.sort(&mut clips);
+        assert_eq!(clips, vec![old.clone(), used.clone(), new.clone()]);
+        SortOrder::UseCount.sort(&mut clips);
+        assert_eq!(clips, vec![used, old, new]);
+
+        assert_eq!("use-count".parse(), Ok(SortOrder::UseCount));
+        assert!("random".parse::<SortOrder>().is_err());
+    }
+}
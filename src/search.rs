@@ -0,0 +1,185 @@
+#[cfg(feature = "monitor")]
+use regex::Regex;
+
+// Score of each matched character.
+const MATCH_SCORE: i64 = 16;
+
+// Bonus of a character which directly follows the previous matched character.
+const CONSECUTIVE_BONUS: i64 = 24;
+
+// Bonus of a character at the start of a word.
+const WORD_START_BONUS: i64 = 16;
+
+// Penalty of each skipped character between two matched characters.
+const GAP_PENALTY: i64 = 1;
+
+/// A clip matched by a search, with a preview of its content.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub id: u64,
+    pub score: i64,
+    pub preview: String,
+
+    /// Lines which matched a regular expression and their context, empty for
+    /// fuzzy matches.
+    pub lines: Vec<SearchLine>,
+}
+
+/// A line of a clip, `number` starts at 1.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct SearchLine {
+    pub number: usize,
+    pub text: String,
+
+    /// `false` if the line is only shown as context of a matched line.
+    pub matched: bool,
+}
+
+/// Scores how well `text` matches `query`, the characters of `query` have to
+/// appear in `text` in the same order, ignoring case. Returns `None` if `text`
+/// does not match, higher scores are better matches.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let text: Vec<char> = text.chars().collect();
+    let lowered: Vec<char> = text.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+
+    let first = match query.first() {
+        Some(first) => first,
+        None => return Some(0),
+    };
+
+    // match greedily from every occurrence of the first character and keep the
+    // best score
+    lowered
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| *c == first)
+        .filter_map(|(start, _)| score_from(&query, &text, &lowered, start))
+        .max()
+}
+
+fn score_from(query: &[char], text: &[char], lowered: &[char], start: usize) -> Option<i64> {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    let mut pos = start;
+
+    for c in query {
+        let idx = pos + lowered[pos..].iter().position(|l| l == c)?;
+        score += MATCH_SCORE;
+        match previous {
+            Some(previous) if previous + 1 == idx => score += CONSECUTIVE_BONUS,
+            Some(previous) => score -= (idx - previous - 1) as i64 * GAP_PENALTY,
+            None => {}
+        }
+        if idx == 0 || !text[idx - 1].is_alphanumeric() {
+            score += WORD_START_BONUS;
+        }
+        previous = Some(idx);
+        pos = idx + 1;
+    }
+
+    Some(score)
+}
+
+/// Matches `regex` against the whole of `text`, returns the number of matches
+/// and the matched lines with up to `context` lines around each of them, or
+/// `None` if nothing matches.
+#[cfg(feature = "monitor")]
+pub fn grep_lines(regex: &Regex, text: &str, context: usize) -> Option<(usize, Vec<SearchLine>)> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let starts: Vec<usize> = lines
+        .iter()
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len() + 1;
+            Some(start)
+        })
+        .collect();
+    let line_of = |offset: usize| match starts.binary_search(&offset) {
+        Ok(idx) => idx,
+        Err(idx) => idx - 1,
+    };
+
+    let mut matched = vec![false; lines.len()];
+    let mut count = 0;
+    for m in regex.find_iter(text) {
+        count += 1;
+        let first = line_of(m.start());
+        let last = if m.end() > m.start() { line_of(m.end() - 1) } else { first };
+        matched[first..=last].iter_mut().for_each(|line| *line = true);
+    }
+
+    if count == 0 {
+        return None;
+    }
+
+    let shown: Vec<_> = (0..lines.len())
+        .filter(|&idx| {
+            let from = idx.saturating_sub(context);
+            let to = (idx + context).min(lines.len() - 1);
+            matched[from..=to].contains(&true)
+        })
+        .map(|idx| SearchLine {
+            number: idx + 1,
+            text: lines[idx].trim_end_matches('\r').to_owned(),
+            matched: matched[idx],
+        })
+        .collect();
+    Some((count, shown))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::fuzzy_score;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("", "clipcat"), Some(0));
+        assert_eq!(fuzzy_score("xyz", "clipcat"), None);
+        assert_eq!(fuzzy_score("tac", "clipcat"), None);
+        assert!(fuzzy_score("CC", "clipcat").is_some());
+
+        let exact = fuzzy_score("cat", "cat");
+        let prefix = fuzzy_score("cat", "catalog");
+        let word = fuzzy_score("cat", "the cat sat");
+        let infix = fuzzy_score("cat", "clipcat");
+        let scattered = fuzzy_score("cat", "c-a-t");
+        assert!(exact.is_some());
+        assert_eq!(exact, prefix);
+        assert_eq!(exact, word);
+        assert!(infix < word);
+        assert!(scattered < infix);
+
+        // the best occurrence is picked instead of the first one
+        assert!(fuzzy_score("cat", "c a clipcat") > fuzzy_score("cat", "c a t"));
+    }
+
+    #[cfg(feature = "monitor")]
+    #[test]
+    fn test_grep_lines() {
+        use regex::Regex;
+
+        use crate::search::{grep_lines, SearchLine};
+
+        let text = "one\ntwo\r\nthree\nfour\nfive\nsix";
+        assert_eq!(grep_lines(&Regex::new("seven").unwrap(), text, 1), None);
+
+        let (count, lines) = grep_lines(&Regex::new("t[wh]").unwrap(), text, 0).unwrap();
+        assert_eq!(count, 2);
+        let numbers: Vec<_> = lines.iter().map(|line| line.number).collect();
+        assert_eq!(numbers, vec![2, 3]);
+        assert_eq!(lines[0].text, "two");
+
+        let (count, lines) = grep_lines(&Regex::new("(?s)four.five").unwrap(), text, 1).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(
+            lines,
+            vec![
+                SearchLine { number: 3, text: "three".to_owned(), matched: false },
+                SearchLine { number: 4, text: "four".to_owned(), matched: true },
+                SearchLine { number: 5, text: "five".to_owned(), matched: true },
+                SearchLine { number: 6, text: "six".to_owned(), matched: false },
+            ]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+/// A static clip defined by the user, e.g. an email signature.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Snippet {
+    pub name: String,
+    pub data: String,
+}
+
+impl Snippet {
+    #[inline]
+    pub fn new(name: &str, data: &str) -> Snippet {
+        Snippet { name: name.to_owned(), data: data.to_owned() }
+    }
+}
+
+/// Stores snippets by name, snippets are listed in order of their names.
+#[derive(Debug, Clone, Default)]
+pub struct SnippetManager {
+    snippets: BTreeMap<String, String>,
+}
+
+impl SnippetManager {
+    #[inline]
+    pub fn new() -> SnippetManager { Self::default() }
+
+    #[inline]
+    pub fn with_snippets(snippets: impl IntoIterator<Item = Snippet>) -> SnippetManager {
+        let snippets = snippets.into_iter().map(|Snippet { name, data }| (name, data)).collect();
+        SnippetManager { snippets }
+    }
+
+    /// Inserts `snippet`, returns `true` if a snippet with the same name is
+    /// replaced.
+    #[inline]
+    pub fn insert(&mut self, snippet: Snippet) -> bool {
+        self.snippets.insert(snippet.name, snippet.data).is_some()
+    }
+
+    #[inline]
+    pub fn remove(&mut self, name: &str) -> bool { self.snippets.remove(name).is_some() }
+
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<Snippet> {
+        self.snippets.get(name).map(|data| Snippet { name: name.to_owned(), data: data.clone() })
+    }
+
+    #[inline]
+    pub fn list(&self) -> Vec<Snippet> {
+        self.snippets
+            .iter()
+            .map(|(name, data)| Snippet { name: name.clone(), data: data.clone() })
+            .collect()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize { self.snippets.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.snippets.is_empty() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::snippet::{Snippet, SnippetManager};
+
+    #[test]
+    fn test_snippets() {
+        let mut mgr = SnippetManager::with_snippets(vec![
+            Snippet::new("signature", "Best regards"),
+            Snippet::new("address", "Somewhere 1"),
+        ]);
+        assert_eq!(mgr.len(), 2);
+        assert_eq!(mgr.get("signature"), Some(Snippet::new("signature", "Best regards")));
+        assert_eq!(mgr.get("none"), None);
+
+        let names: Vec<_> = mgr.list().into_iter().map(|snippet| snippet.name).collect();
+        assert_eq!(names, vec!["address", "signature"]);
+
+        assert!(mgr.insert(Snippet::new("signature", "Cheers")));
+        assert!(!mgr.insert(Snippet::new("command", "ls -la")));
+        assert_eq!(mgr.get("signature").unwrap().data, "Cheers");
+
+        assert!(mgr.remove("address"));
+        assert!(!mgr.remove("address"));
+        assert_eq!(mgr.len(), 2);
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    collections::HashSet,
+    convert::TryInto,
+    fs::{self, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::ClipboardData;
+
+pub const DEFAULT_SPILL_THRESHOLD: usize = 1024 * 1024;
+
+// Extension of files which are sealed by a `SpillCipher`.
+const SEALED_EXTENSION: &str = "sealed";
+
+// Characters of text clips and bytes of other clips which are kept in memory
+// as a preview, the headers of images fit into them.
+const PREVIEW_CHARS: usize = 1024;
+const PREVIEW_BYTES: usize = 4096;
+
+/// The body of a clip which was spilled to disk, the clip only holds a preview
+/// of it then.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SpilledBody {
+    /// Hash of the content of the file, which is also its name. It is `None`
+    /// if the body is only kept in the history, see `BodyLoader`.
+    pub hash: Option<u64>,
+
+    /// Size of the body, see `ClipboardData::size`.
+    pub size: usize,
+
+    /// Size of the body including the alternative representations, see
+    /// `ClipboardData::total_size`.
+    pub total_size: usize,
+}
+
+/// Loads the bodies of clips which were imported as previews, e.g. from the
+/// history at start up.
+pub trait BodyLoader: Send + Sync {
+    /// Returns clip `id` with its body, `None` if it is not stored.
+    fn load_body(&self, id: u64) -> Result<Option<ClipboardData>, String>;
+}
+
+/// Seals the files of a `SpillStore`, e.g. with the cipher of an encrypted
+/// history, so that bodies are never written to disk in plain text.
+pub trait SpillCipher: Send + Sync {
+    fn seal(&self, content: &[u8]) -> Vec<u8>;
+
+    /// Returns `None` if `content` was not sealed with this cipher.
+    fn open(&self, content: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Turns `clip` into a preview of itself, whose body is kept by the file with
+/// `hash` or by a `BodyLoader` if `hash` is `None`.
+pub fn strip_body(clip: &mut ClipboardData, hash: Option<u64>) {
+    if clip.spilled.is_some() {
+        return;
+    }
+
+    clip.spilled = Some(SpilledBody { hash, size: clip.size(), total_size: clip.total_size() });
+    if clip.is_text() {
+        if let Some((index, _)) = clip.data.char_indices().nth(PREVIEW_CHARS) {
+            clip.data.truncate(index);
+        }
+    } else {
+        clip.binary.truncate(PREVIEW_BYTES);
+    }
+    clip.targets.clear();
+}
+
+/// Keeps the bodies of clips which are larger than `threshold` in
+/// content-addressed files in `directory`, so that clips with the same content
+/// share a file. Sensitive clips are never spilled. The directory is only
+/// accessible by the user and files are only readable by the user.
+pub struct SpillStore {
+    directory: PathBuf,
+    threshold: usize,
+    files: HashSet<u64>,
+    cipher: Option<Box<dyn SpillCipher>>,
+}
+
+impl SpillStore {
+    /// Opens `directory`, files which are left from a previous run are reused
+    /// until `retain` removes them.
+    #[inline]
+    pub fn open<P: AsRef<Path>>(directory: P, threshold: usize) -> io::Result<SpillStore> {
+        Self::open_sealed(directory, threshold, None)
+    }
+
+    /// Opens `directory` like `open`, files are sealed with `cipher` if it is
+    /// set. Files which are left from a previous run with another cipher, or
+    /// without one, are removed.
+    pub fn open_sealed<P: AsRef<Path>>(
+        directory: P,
+        threshold: usize,
+        cipher: Option<Box<dyn SpillCipher>>,
+    ) -> io::Result<SpillStore> {
+        let directory = directory.as_ref().to_path_buf();
+        create_private_dir(&directory)?;
+        let mut files = HashSet::new();
+        for entry in fs::read_dir(&directory)? {
+            let path = entry?.path();
+            let sealed = path.extension().is_some_and(|ext| ext == SEALED_EXTENSION);
+            let hash = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| u64::from_str_radix(stem, 16).ok());
+            match hash {
+                // sealed files can not be told apart by their cipher, and
+                // plain files must not be kept once files are sealed
+                Some(hash) if !sealed && cipher.is_none() => {
+                    files.insert(hash);
+                }
+                _ => fs::remove_file(&path)?,
+            }
+        }
+        Ok(SpillStore { directory, threshold, files, cipher })
+    }
+
+    #[inline]
+    pub fn directory(&self) -> &Path { &self.directory }
+
+    #[inline]
+    pub fn threshold(&self) -> usize { self.threshold }
+
+    /// Moves the body of `clip` into a file and leaves a preview in `clip` if
+    /// it is larger than the threshold, `clip` is left as it is on errors.
+    #[inline]
+    pub fn spill(&mut self, clip: &mut ClipboardData) -> io::Result<()> {
+        self.spill_larger_than(clip, self.threshold)
+    }
+
+    /// Like `spill`, but with `threshold` instead of the threshold of the
+    /// store, e.g. to relieve memory pressure.
+    pub fn spill_larger_than(
+        &mut self,
+        clip: &mut ClipboardData,
+        threshold: usize,
+    ) -> io::Result<()> {
+        if clip.spilled.is_some() || clip.sensitive || clip.total_size() <= threshold {
+            return Ok(());
+        }
+
+        let content = encode(clip);
+        let hash = ClipboardData::compute_id(&content[..]);
+        if !self.files.contains(&hash) {
+            let path = self.path(hash);
+            let tmp_path = path.with_extension("tmp");
+            let content = match self.cipher {
+                Some(ref cipher) => cipher.seal(&content),
+                None => content,
+            };
+            write_private_file(&tmp_path, &content)?;
+            fs::rename(&tmp_path, &path)?;
+            self.files.insert(hash);
+        }
+
+        strip_body(clip, Some(hash));
+        Ok(())
+    }
+
+    /// Puts the body of `clip` back if it was spilled to a file, `clip` is
+    /// left as it is on errors.
+    pub fn load(&self, clip: &mut ClipboardData) -> io::Result<()> {
+        let hash = match clip.spilled.and_then(|spilled| spilled.hash) {
+            Some(hash) => hash,
+            None => return Ok(()),
+        };
+
+        let content = fs::read(self.path(hash))?;
+        let content = match self.cipher {
+            Some(ref cipher) => cipher.open(&content),
+            None => Some(content),
+        };
+        let (body, targets) = content.as_deref().and_then(decode).ok_or_else(|| {
+            io::Error::new(ErrorKind::InvalidData, format!("{:016x} is corrupted", hash))
+        })?;
+        if clip.is_text() {
+            clip.data = String::from_utf8_lossy(&body).into_owned();
+        } else {
+            clip.binary = body;
+        }
+        clip.targets = targets.into_iter().collect();
+        clip.spilled = None;
+        Ok(())
+    }
+
+    /// Removes the files whose hashes are not in `hashes`.
+    pub fn retain(&mut self, hashes: &HashSet<u64>) {
+        let unused: Vec<_> = self.files.difference(hashes).copied().collect();
+        for hash in unused {
+            match fs::remove_file(self.path(hash)) {
+                Err(err) if err.kind() != ErrorKind::NotFound => {
+                    tracing::warn!("Could not remove spilled clip {:016x}, error: {}", hash, err);
+                }
+                _ => {
+                    self.files.remove(&hash);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize { self.files.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.files.is_empty() }
+
+    fn path(&self, hash: u64) -> PathBuf {
+        match self.cipher {
+            Some(_) => self.directory.join(format!("{:016x}.{}", hash, SEALED_EXTENSION)),
+            None => self.directory.join(format!("{:016x}", hash)),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn create_private_dir(directory: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(directory)?;
+    // the directory may be left from a version which did not restrict it
+    fs::set_permissions(directory, fs::Permissions::from_mode(0o700))
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(directory: &Path) -> io::Result<()> { fs::create_dir_all(directory) }
+
+fn write_private_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(content)?;
+    file.sync_data()
+}
+
+// Alternative representations of a body as MIME types and values.
+type Targets = Vec<(String, Vec<u8>)>;
+
+// The body is followed by the alternative representations, with the length of
+// each MIME type and value in front of them.
+fn encode(clip: &ClipboardData) -> Vec<u8> {
+    let mut content = Vec::with_capacity(clip.total_size() + 8);
+    push_value(&mut content, clip.as_bytes());
+    for (mime, value) in &clip.targets {
+        content.extend_from_slice(&(mime.len() as u32).to_le_bytes());
+        content.extend_from_slice(mime.as_bytes());
+        push_value(&mut content, value);
+    }
+    content
+}
+
+fn push_value(content: &mut Vec<u8>, value: &[u8]) {
+    content.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    content.extend_from_slice(value);
+}
+
+fn decode(mut content: &[u8]) -> Option<(Vec<u8>, Targets)> {
+    let body = take_value(&mut content, 8)?;
+    let mut targets = Vec::new();
+    while !content.is_empty() {
+        let mime = String::from_utf8(take_value(&mut content, 4)?).ok()?;
+        targets.push((mime, take_value(&mut content, 8)?));
+    }
+    Some((body, targets))
+}
+
+// Takes a value whose length is stored in the first `width` bytes.
+fn take_value(content: &mut &[u8], width: usize) -> Option<Vec<u8>> {
+    let len = match width {
+        4 => u32::from_le_bytes(content.get(..4)?.try_into().ok()?) as usize,
+        _ => u64::from_le_bytes(content.get(..8)?.try_into().ok()?) as usize,
+    };
+    let value = content.get(width..width.checked_add(len)?)?.to_vec();
+    *content = &content[width + len..];
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use crate::{
+        spill::{SpillCipher, SpillStore},
+        ClipboardData, ClipboardType,
+    };
+
+    struct XorCipher;
+
+    impl SpillCipher for XorCipher {
+        fn seal(&self, content: &[u8]) -> Vec<u8> {
+            b"xor".iter().copied().chain(content.iter().map(|b| b ^ 0x5a)).collect()
+        }
+
+        fn open(&self, content: &[u8]) -> Option<Vec<u8>> {
+            content.strip_prefix(b"xor").map(|content| content.iter().map(|b| b ^ 0x5a).collect())
+        }
+    }
+
+    #[test]
+    fn test_spill() {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let directory = std::env::temp_dir().join(format!("clipcat-spill-{}", nanos));
+        let mut store = SpillStore::open(&directory, 16).unwrap();
+
+        let mut small = ClipboardData::new_clipboard("small");
+        store.spill(&mut small).unwrap();
+        assert!(small.spilled.is_none());
+        assert!(store.is_empty());
+
+        let png = [&b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\x07\x80\0\0\x04\x38"[..], &[0u8; 8192][..]]
+            .concat();
+        let mut image = ClipboardData::from_bytes("image/png", png, ClipboardType::Clipboard);
+        image.targets.insert("text/uri-list".to_owned(), b"file:///tmp/a.png".to_vec());
+        let original = image.clone();
+        store.spill(&mut image).unwrap();
+        assert_eq!(image.spilled.map(|spilled| spilled.size), Some(original.size()));
+        assert_eq!(image.size(), original.size());
+        assert_eq!(image.total_size(), original.total_size());
+        assert!(image.binary.len() < original.binary.len());
+        assert_eq!(image.image_dimensions(), Some((1920, 1080)));
+
+        // clips with the same content share a file
+        let mut copy = original.clone();
+        store.spill(&mut copy).unwrap();
+        assert_eq!(store.len(), 1);
+
+        store.load(&mut image).unwrap();
+        assert!(image.spilled.is_none());
+        assert_eq!(image.binary, original.binary);
+        assert_eq!(image.targets, original.targets);
+
+        let mut sensitive = ClipboardData::new_clipboard(&"secret".repeat(10));
+        sensitive.sensitive = true;
+        store.spill(&mut sensitive).unwrap();
+        assert!(sensitive.spilled.is_none());
+
+        store.retain(&HashSet::new());
+        assert!(store.is_empty());
+        assert!(store.load(&mut copy).is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_sealed() {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let directory = std::env::temp_dir().join(format!("clipcat-spill-sealed-{}", nanos));
+        let mut store = SpillStore::open_sealed(&directory, 16, Some(Box::new(XorCipher))).unwrap();
+
+        let original = ClipboardData::new_clipboard(&"secret ".repeat(100));
+        let mut clip = original.clone();
+        store.spill(&mut clip).unwrap();
+        let path = std::fs::read_dir(&directory).unwrap().next().unwrap().unwrap().path();
+        let content = std::fs::read(&path).unwrap();
+        assert!(!content.windows(6).any(|w| w == b"secret"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            let mode = std::fs::metadata(&directory).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        store.load(&mut clip).unwrap();
+        assert_eq!(clip.data, original.data);
+
+        // sealed files are not reused without the cipher
+        let store = SpillStore::open(&directory, 16).unwrap();
+        assert!(store.is_empty());
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
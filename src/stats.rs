@@ -0,0 +1,162 @@
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+
+use crate::{ClipboardData, ClipboardType};
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Number and total size in bytes of the clips of a clipboard type or a MIME
+/// type.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TypeStats {
+    pub name: String,
+    pub count: usize,
+    pub bytes: usize,
+}
+
+/// A clip listed among the largest ones, with a preview of its content.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ClipSummary {
+    pub id: u64,
+    pub clipboard_type: ClipboardType,
+    pub mime: String,
+    pub size: usize,
+    pub preview: String,
+}
+
+/// Statistics of the clips in the history, for tuning the capacity of the
+/// history and spotting sources which copy too much.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct HistoryStats {
+    pub count: usize,
+    pub bytes: usize,
+
+    /// Ordered by clipboard type.
+    pub clipboard_types: Vec<TypeStats>,
+
+    /// Ordered by count, the most frequent MIME type first.
+    pub mime_types: Vec<TypeStats>,
+
+    /// The largest clips, the largest one first.
+    pub largest: Vec<ClipSummary>,
+
+    pub oldest: Option<SystemTime>,
+    pub newest: Option<SystemTime>,
+
+    /// Clips which were copied within the last hour and day.
+    pub last_hour: usize,
+    pub last_day: usize,
+}
+
+impl HistoryStats {
+    /// Computes the statistics of `clips` at `now`, the `largest` largest clips
+    /// are listed with previews of `preview_length` characters.
+    pub fn compute<'a>(
+        clips: impl Iterator<Item = &'a ClipboardData>,
+        largest: usize,
+        preview_length: Option<usize>,
+        now: SystemTime,
+    ) -> HistoryStats {
+        let mut stats = HistoryStats::default();
+        let mut clipboard_types = BTreeMap::<ClipboardType, TypeStats>::new();
+        let mut mime_types = BTreeMap::<&str, TypeStats>::new();
+        let mut sizes = Vec::new();
+
+        for clip in clips {
+            let size = clip.size();
+            stats.count += 1;
+            stats.bytes += size;
+            let name = match clip.clipboard_type {
+                ClipboardType::Clipboard => "clipboard",
+                ClipboardType::Primary => "primary",
+            };
+            add(clipboard_types.entry(clip.clipboard_type).or_default(), name, size);
+            add(mime_types.entry(clip.mime.as_str()).or_default(), &clip.mime, size);
+
+            stats.oldest =
+                Some(stats.oldest.map_or(clip.timestamp, |oldest| oldest.min(clip.timestamp)));
+            stats.newest =
+                Some(stats.newest.map_or(clip.timestamp, |newest| newest.max(clip.timestamp)));
+            let age = now.duration_since(clip.timestamp).unwrap_or_default();
+            if age <= HOUR {
+                stats.last_hour += 1;
+            }
+            if age <= DAY {
+                stats.last_day += 1;
+            }
+            sizes.push((size, clip));
+        }
+
+        stats.clipboard_types = clipboard_types.into_values().collect();
+        stats.mime_types = mime_types.into_values().collect();
+        stats.mime_types.sort_by_key(|stats| Reverse(stats.count));
+
+        sizes.sort_by(|(a, _), (b, _)| b.cmp(a));
+        stats.largest = sizes
+            .into_iter()
+            .take(largest)
+            .map(|(size, clip)| ClipSummary {
+                id: clip.id,
+                clipboard_type: clip.clipboard_type,
+                mime: clip.mime.clone(),
+                size,
+                preview: clip.printable_data(preview_length),
+            })
+            .collect();
+        stats
+    }
+}
+
+fn add(stats: &mut TypeStats, name: &str, size: usize) {
+    if stats.name.is_empty() {
+        stats.name = name.to_owned();
+    }
+    stats.count += 1;
+    stats.bytes += size;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use crate::{stats::HistoryStats, ClipboardData, ClipboardType};
+
+    #[test]
+    fn test_compute() {
+        let now = SystemTime::now();
+        let mut clips = [
+            ClipboardData::new("clipcat", ClipboardType::Clipboard),
+            ClipboardData::new("a much longer clip", ClipboardType::Clipboard),
+            ClipboardData::new("primary", ClipboardType::Primary),
+        ];
+        clips[0].timestamp = now - Duration::from_secs(2 * 24 * 60 * 60);
+        clips[1].timestamp = now - Duration::from_secs(2 * 60 * 60);
+        clips[2].timestamp = now;
+
+        let stats = HistoryStats::compute(clips.iter(), 2, Some(6), now);
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.bytes, 7 + 18 + 7);
+        assert_eq!(stats.clipboard_types.len(), 2);
+        assert_eq!(stats.clipboard_types[0].name, "clipboard");
+        assert_eq!(stats.clipboard_types[0].count, 2);
+        assert_eq!(stats.clipboard_types[0].bytes, 25);
+        assert_eq!(stats.mime_types.len(), 1);
+        assert_eq!(stats.mime_types[0].count, 3);
+        assert_eq!(stats.largest.len(), 2);
+        assert_eq!(stats.largest[0].id, clips[1].id);
+        assert_eq!(stats.largest[0].size, 18);
+        assert_eq!(stats.oldest, Some(clips[0].timestamp));
+        assert_eq!(stats.newest, Some(now));
+        assert_eq!(stats.last_hour, 1);
+        assert_eq!(stats.last_day, 2);
+
+        assert_eq!(
+            HistoryStats::compute(std::iter::empty(), 5, None, now),
+            HistoryStats::default()
+        );
+    }
+}
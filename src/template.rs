@@ -0,0 +1,342 @@
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::ClipboardData;
+
+// Previews are never shortened below this length, shorter previews would not
+// leave room for the hint of the number of lines.
+const MIN_PREVIEW_LENGTH: usize = 20;
+
+/// Describes how a clip is shown in a single line, e.g. `{index} {time:rel}
+/// {type:5} {preview:60}`.
+///
+/// Placeholders are `{index}`, `{id}`, `{time}` or `{time:rel}`, the time since
+/// the clip was copied, `{time:abs}`, the time it was copied in UTC, `{app}`,
+/// the application it was copied from, `{type}`, `{tags}`, `{uses}`, how often
+/// it was copied or promoted again, and `{preview}`, its content. A number
+/// after a colon sets the width of a column, or the length of the preview.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EntryTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Part {
+    Literal(String),
+    Placeholder { field: Field, width: Option<usize> },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Field {
+    Index,
+    Id,
+    RelativeTime,
+    AbsoluteTime,
+    Application,
+    Type,
+    Tags,
+    UseCount,
+    Preview,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Field::Index => "index",
+            Field::Id => "id",
+            Field::RelativeTime => "time:rel",
+            Field::AbsoluteTime => "time:abs",
+            Field::Application => "app",
+            Field::Type => "type",
+            Field::Tags => "tags",
+            Field::UseCount => "uses",
+            Field::Preview => "preview",
+        }
+    }
+}
+
+impl EntryTemplate {
+    /// Renders the clip at `index`. A preview without a length is shortened so
+    /// the line fits into `line_length` characters if possible.
+    pub fn render(
+        &self,
+        index: usize,
+        clip: &ClipboardData,
+        now: SystemTime,
+        line_length: Option<usize>,
+    ) -> String {
+        let mut rendered = Vec::with_capacity(self.parts.len());
+        for part in &self.parts {
+            rendered.push(match *part {
+                Part::Literal(ref text) => Some(text.clone()),
+                Part::Placeholder { field: Field::Preview, width: Some(width) } => {
+                    Some(clip.printable_data(Some(width.max(MIN_PREVIEW_LENGTH))))
+                }
+                Part::Placeholder { field: Field::Preview, width: None } => None,
+                Part::Placeholder { field, width } => {
+                    let text = render_field(field, index, clip, now);
+                    Some(match width {
+                        Some(width) => pad(&text, width),
+                        None => text,
+                    })
+                }
+            });
+        }
+
+        let width: usize = rendered.iter().flatten().map(|text| text.chars().count()).sum();
+        let preview_length = match line_length {
+            None | Some(0) => None,
+            Some(line_length) => Some(line_length.saturating_sub(width).max(MIN_PREVIEW_LENGTH)),
+        };
+        rendered
+            .into_iter()
+            .map(|text| text.unwrap_or_else(|| clip.printable_data(preview_length)))
+            .collect()
+    }
+
+    /// Returns the longest length of previews which is given in the template.
+    pub fn preview_length(&self) -> Option<usize> {
+        self.parts
+            .iter()
+            .filter_map(|part| match *part {
+                Part::Placeholder { field: Field::Preview, width } => width,
+                _ => None,
+            })
+            .max()
+    }
+}
+
+impl FromStr for EntryTemplate {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<EntryTemplate, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_owned()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| format!("Unclosed placeholder in {}", template))?;
+            parts.push(parse_placeholder(&rest[start + 1..end])?);
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_owned()));
+        }
+        Ok(EntryTemplate { parts })
+    }
+}
+
+impl fmt::Display for EntryTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => f.write_str(text)?,
+                Part::Placeholder { field, width: None } => write!(f, "{{{}}}", field.name())?,
+                Part::Placeholder { field, width: Some(width) } => {
+                    write!(f, "{{{}:{}}}", field.name(), width)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<Part, String> {
+    let mut split = placeholder.splitn(2, ':');
+    let name = split.next().unwrap_or_default();
+    let mut argument = split.next();
+    let field = match name {
+        "time" => match argument.map(|argument| argument.splitn(2, ':')) {
+            Some(mut split) => match split.next() {
+                Some("rel") => {
+                    argument = split.next();
+                    Field::RelativeTime
+                }
+                Some("abs") => {
+                    argument = split.next();
+                    Field::AbsoluteTime
+                }
+                _ => Field::RelativeTime,
+            },
+            None => Field::RelativeTime,
+        },
+        "index" => Field::Index,
+        "id" => Field::Id,
+        "app" => Field::Application,
+        "type" => Field::Type,
+        "tags" => Field::Tags,
+        "uses" => Field::UseCount,
+        "preview" => Field::Preview,
+        _ => {
+            return Err(format!(
+                "Unknown placeholder {{{}}}, expected index, id, time, app, type, tags, uses or \
+                 preview",
+                placeholder
+            ))
+        }
+    };
+    let width = match argument {
+        Some(width) => Some(
+            width.parse::<usize>().map_err(|_| format!("Invalid width in {{{}}}", placeholder))?,
+        ),
+        None => None,
+    };
+    Ok(Part::Placeholder { field, width })
+}
+
+fn render_field(field: Field, index: usize, clip: &ClipboardData, now: SystemTime) -> String {
+    match field {
+        Field::Index => index.to_string(),
+        Field::Id => format!("{:016x}", clip.id),
+        Field::RelativeTime => relative_time(clip.timestamp, now),
+        Field::AbsoluteTime => absolute_time(clip.timestamp),
+        Field::Application => clip.application.clone().unwrap_or_default(),
+        Field::Type => clip.kind().to_owned(),
+        Field::Tags => clip.tags.iter().cloned().collect::<Vec<_>>().join(","),
+        Field::UseCount => clip.use_count.to_string(),
+        Field::Preview => clip.printable_data(None),
+    }
+}
+
+// Pads `text` with spaces or cuts it to `width` characters.
+fn pad(text: &str, width: usize) -> String {
+    let mut padded: String = text.chars().take(width).collect();
+    let len = padded.chars().count();
+    padded.extend(std::iter::repeat_n(' ', width - len));
+    padded
+}
+
+/// Returns how long ago `timestamp` was, e.g. `3 min ago`.
+pub fn relative_time(timestamp: SystemTime, now: SystemTime) -> String {
+    let seconds = now.duration_since(timestamp).unwrap_or(Duration::from_secs(0)).as_secs();
+    match seconds {
+        0..=59 => "just now".to_owned(),
+        60..=3599 => format!("{} min ago", seconds / 60),
+        3600..=86399 => format!("{} h ago", seconds / 3600),
+        _ => format!("{} d ago", seconds / 86400),
+    }
+}
+
+/// Returns `timestamp` in UTC, e.g. `2021-03-01 12:30:00`.
+pub fn absolute_time(timestamp: SystemTime) -> String {
+    let seconds = timestamp.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+// Converts days since the Unix epoch into a date of the proleptic Gregorian
+// calendar, see http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use crate::{
+        template::{absolute_time, relative_time, EntryTemplate},
+        ClipboardData,
+    };
+
+    #[test]
+    fn test_relative_time() {
+        let now = SystemTime::now();
+        assert_eq!(relative_time(now, now), "just now");
+        assert_eq!(relative_time(now - Duration::from_secs(200), now), "3 min ago");
+        assert_eq!(relative_time(now - Duration::from_secs(7200), now), "2 h ago");
+        assert_eq!(relative_time(now - Duration::from_secs(3 * 86400), now), "3 d ago");
+        assert_eq!(relative_time(now + Duration::from_secs(10), now), "just now");
+    }
+
+    #[test]
+    fn test_absolute_time() {
+        assert_eq!(absolute_time(UNIX_EPOCH), "1970-01-01 00:00:00");
+        assert_eq!(
+            absolute_time(UNIX_EPOCH + Duration::from_secs(1_614_601_800)),
+            "2021-03-01 12:30:00"
+        );
+        assert_eq!(
+            absolute_time(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29 00:00:00"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let template: EntryTemplate = "{time} [{app}] {preview}".parse().unwrap();
+        assert_eq!(template.to_string(), "{time:rel} [{app}] {preview}");
+
+        let mut clip = ClipboardData::new_clipboard("clipcat is a clipboard manager");
+        clip.application = Some("Firefox".to_owned());
+        let now = clip.timestamp + Duration::from_secs(120);
+        assert_eq!(
+            template.render(0, &clip, now, None),
+            "2 min ago [Firefox] clipcat is a clipboard manager"
+        );
+        assert_eq!(
+            template.render(0, &clip, now, Some(40)),
+            "2 min ago [Firefox] clipcat is a clip..."
+        );
+        clip.application = None;
+        assert_eq!(
+            template.render(0, &clip, now, None),
+            "2 min ago [] clipcat is a clipboard manager"
+        );
+    }
+
+    #[test]
+    fn test_render_columns() {
+        let template: EntryTemplate =
+            "{index:3}{type:5} {tags} {uses:2} {preview:20}".parse().unwrap();
+        assert_eq!(template.preview_length(), Some(20));
+
+        let mut clip = ClipboardData::new_clipboard("clipcat is a clipboard manager");
+        clip.tags.insert("work".to_owned());
+        clip.use_count = 4;
+        assert_eq!(
+            template.render(7, &clip, SystemTime::now(), Some(100)),
+            "7  text  work 4  clipcat is a clip..."
+        );
+
+        let url = ClipboardData::new_clipboard("https://example.com");
+        let template: EntryTemplate = "{time:abs:10}|{time:rel:3}|{time:4}".parse().unwrap();
+        assert_eq!(template.to_string(), "{time:abs:10}|{time:rel:3}|{time:rel:4}");
+
+        let template: EntryTemplate = "{id}: {type:2}".parse().unwrap();
+        assert_eq!(
+            template.render(0, &url, SystemTime::now(), None),
+            format!("{:016x}: ur", url.id)
+        );
+
+        assert!("{preview".parse::<EntryTemplate>().is_err());
+        assert!("{size}".parse::<EntryTemplate>().is_err());
+        assert!("{preview:long}".parse::<EntryTemplate>().is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime},
+};
+
+use crate::ClipboardData;
+
+pub const DEFAULT_TRASH_CAPACITY: usize = 100;
+pub const DEFAULT_TRASH_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A clip in the trash.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TrashEntry {
+    pub clip: ClipboardData,
+    pub removed_at: SystemTime,
+
+    // clips which were removed at once are restored at once
+    pub(crate) batch: u64,
+}
+
+/// Clips which were removed by the user, they are kept for `ttl` so that the
+/// removal can be undone. At most `capacity` clips are kept, the oldest ones
+/// are dropped first. Sensitive clips are never kept.
+#[derive(Debug, Clone)]
+pub struct Trash {
+    entries: VecDeque<TrashEntry>,
+    capacity: usize,
+    ttl: Duration,
+    next_batch: u64,
+}
+
+impl Default for Trash {
+    fn default() -> Trash { Trash::new(DEFAULT_TRASH_CAPACITY, DEFAULT_TRASH_TTL) }
+}
+
+impl Trash {
+    pub fn new(capacity: usize, ttl: Duration) -> Trash {
+        Trash { entries: VecDeque::new(), capacity, ttl, next_batch: 0 }
+    }
+
+    /// Changes the limits of the trash, clips which exceed them are dropped.
+    pub fn set_limits(&mut self, capacity: usize, ttl: Duration) {
+        self.capacity = capacity;
+        self.ttl = ttl;
+        self.expire(SystemTime::now());
+    }
+
+    /// Puts clips which were removed at once into the trash.
+    pub fn put(&mut self, clips: Vec<ClipboardData>, now: SystemTime) {
+        let batch = self.next_batch;
+        self.next_batch += 1;
+        self.entries.extend(
+            clips.into_iter().filter(|clip| !clip.sensitive).map(|clip| TrashEntry {
+                clip,
+                removed_at: now,
+                batch,
+            }),
+        );
+        self.expire(now);
+    }
+
+    /// Takes the clips which were removed last out of the trash.
+    pub fn take_last(&mut self, now: SystemTime) -> Vec<ClipboardData> {
+        self.expire(now);
+        let batch = match self.entries.back() {
+            Some(entry) => entry.batch,
+            None => return Vec::new(),
+        };
+        let index = self.entries.iter().position(|entry| entry.batch == batch).unwrap_or(0);
+        self.entries.drain(index..).map(|entry| entry.clip).collect()
+    }
+
+    /// Takes clip `id` out of the trash.
+    pub fn take(&mut self, id: u64, now: SystemTime) -> Option<ClipboardData> {
+        self.expire(now);
+        let index = self.entries.iter().position(|entry| entry.clip.id == id)?;
+        self.entries.remove(index).map(|entry| entry.clip)
+    }
+
+    /// Lists the clips in the trash which are not expired at `now`, the most
+    /// recently removed ones first.
+    pub fn list(&self, now: SystemTime) -> Vec<TrashEntry> {
+        self.entries.iter().rev().filter(|entry| !self.is_expired(entry, now)).cloned().collect()
+    }
+
+    /// Iterates over the clips in the trash, including expired ones which are
+    /// not dropped yet.
+    #[inline]
+    pub fn clips(&self) -> impl Iterator<Item = &ClipboardData> {
+        self.entries.iter().map(|entry| &entry.clip)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize { self.entries.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    #[inline]
+    fn is_expired(&self, entry: &TrashEntry, now: SystemTime) -> bool {
+        now.duration_since(entry.removed_at).is_ok_and(|age| age >= self.ttl)
+    }
+
+    fn expire(&mut self, now: SystemTime) {
+        let ttl = self.ttl;
+        self.entries
+            .retain(|entry| now.duration_since(entry.removed_at).map_or(true, |age| age < ttl));
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use crate::{trash::Trash, ClipboardData};
+
+    #[test]
+    fn test_take_last() {
+        let now = SystemTime::now();
+        let clips: Vec<_> = (0..4).map(|i| ClipboardData::new_clipboard(&i.to_string())).collect();
+        let mut trash = Trash::new(3, Duration::from_secs(60));
+        trash.put(vec![clips[0].clone()], now);
+        trash.put(vec![clips[1].clone(), clips[2].clone()], now);
+        assert_eq!(trash.len(), 3);
+        assert_eq!(trash.list(now)[0].clip, clips[2]);
+
+        assert_eq!(trash.take_last(now), vec![clips[1].clone(), clips[2].clone()]);
+        assert_eq!(trash.take_last(now), vec![clips[0].clone()]);
+        assert!(trash.take_last(now).is_empty());
+
+        // the oldest clips are dropped first
+        trash.put(clips.clone(), now);
+        assert_eq!(trash.len(), 3);
+        assert!(trash.take(clips[0].id, now).is_none());
+        assert_eq!(trash.take(clips[3].id, now), Some(clips[3].clone()));
+    }
+
+    #[test]
+    fn test_expire() {
+        let now = SystemTime::now();
+        let mut sensitive = ClipboardData::new_clipboard("password");
+        sensitive.sensitive = true;
+        let mut trash = Trash::new(10, Duration::from_secs(60));
+        trash.put(vec![ClipboardData::new_clipboard("clipcat"), sensitive], now);
+        assert_eq!(trash.len(), 1);
+
+        assert_eq!(trash.list(now + Duration::from_secs(59)).len(), 1);
+        assert!(trash.list(now + Duration::from_secs(60)).is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use crate::action::{Action, ActionError};
+
+/// Placeholder in typing commands which is replaced with the delay between
+/// keystrokes in milliseconds.
+pub const DELAY_PLACEHOLDER: &str = "{delay}";
+
+/// Types clips as synthetic keystrokes instead of pasting them, for fields and
+/// remote desktops which block paste. The text is passed on stdin, so it is
+/// never quoted for the shell.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TypingOptions {
+    /// Milliseconds to wait before typing, for the menu to close and the
+    /// previous window to get focus back.
+    #[serde(default = "TypingOptions::default_start_delay")]
+    pub start_delay: u64,
+
+    /// Milliseconds between two keystrokes, some applications drop keys which
+    /// are typed faster.
+    #[serde(default = "TypingOptions::default_key_delay")]
+    pub key_delay: u64,
+
+    /// Command which types its stdin, `xdotool` on X11 and `wtype` on Wayland
+    /// by default.
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+impl TypingOptions {
+    #[inline]
+    pub fn default_start_delay() -> u64 { 200 }
+
+    #[inline]
+    pub fn default_key_delay() -> u64 { 12 }
+
+    /// Returns the shell command which types its stdin.
+    pub fn command(&self, wayland: bool) -> String {
+        let command = match self.command {
+            Some(ref command) => command.as_str(),
+            None if wayland => "wtype -d {delay} -",
+            None => "xdotool type --clearmodifiers --delay {delay} --file -",
+        };
+        command.replace(DELAY_PLACEHOLDER, &self.key_delay.to_string())
+    }
+}
+
+impl Default for TypingOptions {
+    fn default() -> TypingOptions {
+        TypingOptions {
+            start_delay: Self::default_start_delay(),
+            key_delay: Self::default_key_delay(),
+            command: None,
+        }
+    }
+}
+
+/// Returns the characters of `text` which can be typed. Line endings become
+/// Return and tabs stay Tab, other control characters have no key and are left
+/// out.
+pub fn keystrokes(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .chars()
+        .filter(|&c| c == '\n' || c == '\t' || !c.is_control())
+        .collect()
+}
+
+/// Waits for `opts.start_delay` and types `text` into the focused window.
+pub async fn type_text(text: &str, opts: &TypingOptions) -> Result<(), ActionError> {
+    tokio::time::sleep(Duration::from_millis(opts.start_delay)).await;
+
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let action =
+        Action { name: "type".to_owned(), command: opts.command(wayland), insert_output: false };
+    action.run(&keystrokes(text)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::typing::{keystrokes, TypingOptions};
+
+    #[test]
+    fn test_keystrokes() {
+        assert_eq!(keystrokes("a\r\nb\rc\n"), "a\nb\nc\n");
+        assert_eq!(keystrokes("key:\tvalue\u{7}\u{1b}[0m"), "key:\tvalue[0m");
+        assert_eq!(keystrokes("ünïcødé 'quoted' $HOME"), "ünïcødé 'quoted' $HOME");
+    }
+
+    #[test]
+    fn test_command() {
+        let mut opts = TypingOptions::default();
+        assert_eq!(opts.command(false), "xdotool type --clearmodifiers --delay 12 --file -");
+        assert_eq!(opts.command(true), "wtype -d 12 -");
+
+        opts.key_delay = 50;
+        opts.command = Some("ydotool type -d {delay} -f -".to_owned());
+        assert_eq!(opts.command(true), "ydotool type -d 50 -f -");
+    }
+}
@@ -0,0 +1,62 @@
+use std::ops::Range;
+
+// Characters which end a URL in text, and characters which are rather
+// punctuation around a URL than part of it.
+const URL_TERMINATORS: &[char] = &['<', '>', '"', '`'];
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '}', '\''];
+
+/// Returns the byte ranges of `http://` and `https://` URLs in `text`, in
+/// order.
+pub fn spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = find_scheme(&text[offset..]) {
+        let start = offset + start;
+        let rest = &text[start..];
+        let end = rest
+            .find(|c: char| c.is_whitespace() || URL_TERMINATORS.contains(&c))
+            .unwrap_or(rest.len());
+        let url = rest[..end].trim_end_matches(TRAILING_PUNCTUATION);
+        spans.push(start..start + url.len());
+        offset = start + url.len();
+    }
+    spans
+}
+
+/// Returns the `http://` and `https://` URLs in `text`, in order and without
+/// duplicates.
+pub fn find(text: &str) -> Vec<&str> {
+    let mut urls: Vec<&str> = Vec::new();
+    for span in spans(text) {
+        let url = &text[span];
+        // a bare scheme is no URL
+        if !url.ends_with("://") && !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+fn find_scheme(text: &str) -> Option<usize> {
+    match (text.find("https://"), text.find("http://")) {
+        (Some(https), Some(http)) => Some(https.min(http)),
+        (https, http) => https.or(http),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::urls;
+
+    #[test]
+    fn test_find() {
+        assert_eq!(urls::find("https://example.com\n"), vec!["https://example.com"]);
+        assert_eq!(
+            urls::find(
+                "see (https://example.com/a?b=c), <http://example.org>. https://example.com/a?b=c"
+            ),
+            vec!["https://example.com/a?b=c", "http://example.org"]
+        );
+        assert!(urls::find("no urls, just https:// and example.com").is_empty());
+    }
+}